@@ -0,0 +1,104 @@
+use std::time::{Duration, Instant};
+
+use omnish_protocol::message::Message;
+
+/// How long IoData may wait before it is sent as one `Message::Batch`.
+pub const IO_BATCH_WINDOW: Duration = Duration::from_millis(10);
+
+/// Collects IoData from the main loop so bursts of terminal traffic reach
+/// the daemon as one `Message::Batch` instead of many small sends.
+pub struct BatchAccumulator {
+    messages: Vec<Message>,
+    flush_interval: Duration,
+    /// When the oldest pending message was pushed.
+    oldest: Option<Instant>,
+}
+
+impl BatchAccumulator {
+    pub fn new(flush_interval: Duration) -> Self {
+        Self {
+            messages: Vec::new(),
+            flush_interval,
+            oldest: None,
+        }
+    }
+
+    pub fn push(&mut self, msg: Message) {
+        self.oldest.get_or_insert_with(Instant::now);
+        self.messages.push(msg);
+    }
+
+    /// Time left until the oldest pending message has waited
+    /// `flush_interval`; `None` when nothing is pending.
+    pub fn time_until_due(&self) -> Option<Duration> {
+        self.oldest
+            .map(|t| self.flush_interval.saturating_sub(t.elapsed()))
+    }
+
+    pub fn is_due(&self) -> bool {
+        self.time_until_due() == Some(Duration::ZERO)
+    }
+
+    /// Take everything pending: a lone message is returned as is, several
+    /// are wrapped in a `Batch`.
+    pub fn flush(&mut self) -> Option<Message> {
+        self.oldest = None;
+        match self.messages.len() {
+            0 => None,
+            1 => self.messages.pop(),
+            _ => Some(Message::Batch {
+                messages: std::mem::take(&mut self.messages),
+            }),
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use omnish_protocol::message::{IoData, IoDirection};
+
+    fn io(i: u8) -> Message {
+        Message::IoData(IoData {
+            session_id: "s1".into(),
+            direction: IoDirection::Output,
+            timestamp_ms: i as u64,
+            data: vec![i],
+        })
+    }
+
+    #[test]
+    fn test_burst_is_sent_as_one_batch() {
+        let mut acc = BatchAccumulator::new(IO_BATCH_WINDOW);
+        assert!(acc.time_until_due().is_none());
+        for i in 0..5 {
+            acc.push(io(i));
+        }
+        assert!(!acc.is_due());
+        std::thread::sleep(IO_BATCH_WINDOW);
+        assert!(acc.is_due());
+
+        match acc.flush() {
+            Some(Message::Batch { messages }) => {
+                let data: Vec<u8> = messages
+                    .iter()
+                    .map(|m| match m {
+                        Message::IoData(io) => io.data[0],
+                        other => panic!("expected IoData, got {:?}", other),
+                    })
+                    .collect();
+                assert_eq!(data, vec![0, 1, 2, 3, 4]);
+            }
+            other => panic!("expected Batch, got {:?}", other),
+        }
+        assert!(acc.flush().is_none());
+        assert!(!acc.is_due());
+    }
+
+    #[test]
+    fn test_single_message_is_not_wrapped() {
+        let mut acc = BatchAccumulator::new(IO_BATCH_WINDOW);
+        acc.push(io(7));
+        assert!(matches!(acc.flush(), Some(Message::IoData(_))));
+    }
+}
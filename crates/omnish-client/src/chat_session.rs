@@ -10,9 +10,9 @@ use omnish_common::config::ClientSandboxConfig;
 use omnish_protocol::message::*;
 use omnish_protocol::message::{ChatToolStatus, ConfigHandlerInfo, StatusIcon};
 use omnish_pty::proxy::PtyProxy;
-use omnish_transport::rpc_client::RpcClient;
+use omnish_transport::rpc_pool::RpcClientPool;
 
-use crate::{client_plugin, command, display, ghost_complete, markdown, screen_capture, widgets};
+use crate::{client_plugin, command, display, ghost_complete, markdown, pager, screen_capture, widgets};
 use crate::display::{BOLD, BRIGHT_WHITE, CYAN, DIM, GRAY, GREEN, NEWLINE, RED, RESET, YELLOW};
 use widgets::scroll_view::ScrollView;
 
@@ -60,7 +60,11 @@ pub struct ChatSession {
     shell_cwd: Option<String>,
     /// Directory to cd into after chat mode exits (set by resume mismatch handler).
     pending_cd: Option<String>,
+    /// Command line to put at the shell prompt after chat mode exits (set by /hist).
+    pending_shell_input: Option<String>,
     extended_unicode: bool,
+    /// Paging and timeout for `/` queries sent to the daemon.
+    query_opts: super::QueryOptions,
     /// Total terminal lines printed (for tracking tool section position).
     lines_printed: usize,
     /// Line position where the current batch of tool headers starts.
@@ -681,7 +685,7 @@ fn client_config_path() -> std::path::PathBuf {
 /// Send a ConfigUpdate RPC to the daemon and return Ok(()) on success, Err(message) on failure.
 fn send_config_update(
     rt: &tokio::runtime::Handle,
-    rpc: &RpcClient,
+    rpc: &RpcClientPool,
     changes: Vec<ConfigChange>,
 ) -> Result<(), String> {
     let result = rt.block_on(async {
@@ -1058,6 +1062,7 @@ impl ChatSession {
     pub fn new(
         chat_history: VecDeque<String>,
         extended_unicode: bool,
+        query_opts: super::QueryOptions,
         sandbox_state: Arc<RwLock<ClientSandboxConfig>>,
         screen_capture: screen_capture::SharedScreenCapture,
     ) -> Self {
@@ -1088,7 +1093,9 @@ impl ChatSession {
             resumed_model: None,
             shell_cwd: None,
             pending_cd: None,
+            pending_shell_input: None,
             extended_unicode,
+            query_opts,
             lines_printed: 0,
             tool_section_start: None,
             tool_section_hist_idx: None,
@@ -1118,6 +1125,11 @@ impl ChatSession {
         self.pending_cd.as_deref()
     }
 
+    /// Return the command line picked in /hist, to be typed at the shell prompt.
+    pub fn pending_shell_input(&self) -> Option<&str> {
+        self.pending_shell_input.as_deref()
+    }
+
     fn thinking_line(&self) -> String {
         let ch = crate::display::spinner_char(self.spinner_frame);
         format!(
@@ -1337,10 +1349,43 @@ impl ChatSession {
         sv.run_browse();
     }
 
+    /// Fetch the `commands` list of a `__cmd:history...` query.
+    pub(crate) async fn fetch_command_lines(rpc: &RpcClientPool, session_id: &str, query: &str) -> Vec<String> {
+        let rid = Uuid::new_v4().to_string()[..8].to_string();
+        let req = Message::Request(Request {
+            request_id: rid.clone(),
+            session_id: session_id.to_string(),
+            query: query.to_string(),
+            scope: RequestScope::AllSessions,
+            model_override: None,
+            timeout_ms: None,
+            format_hint: None,
+        });
+        let Ok(Message::Response(resp)) = rpc.call(req).await else {
+            return Vec::new();
+        };
+        if resp.request_id != rid {
+            return Vec::new();
+        }
+        super::parse_cmd_response(&resp.content)
+            .and_then(|json| json.get("commands").and_then(|v| v.as_array()).cloned())
+            .map(|arr| arr.iter().filter_map(|v| v.as_str().map(String::from)).collect())
+            .unwrap_or_default()
+    }
+
+    /// Seed ghost completion with command lines from the last shell session.
+    async fn load_history_provider(&mut self, rpc: &RpcClientPool, session_id: &str) {
+        let commands = Self::fetch_command_lines(rpc, session_id, "__cmd:history").await;
+        if !commands.is_empty() {
+            self.completer
+                .add_provider(Box::new(ghost_complete::HistoryProvider::new(commands)));
+        }
+    }
+
     #[allow(clippy::too_many_arguments)]
     pub async fn run(
         &mut self,
-        rpc: &RpcClient,
+        rpc: &RpcClientPool,
         session_id: &str,
         proxy: &PtyProxy,
         initial_msg: Option<String>,
@@ -1366,6 +1411,11 @@ impl ChatSession {
             let _ = rpc.call(msg).await;
         }
 
+        if let Some(ref cwd) = self.shell_cwd {
+            self.completer.add_provider(Box::new(ghost_complete::PathProvider::new(cwd)));
+        }
+        self.load_history_provider(rpc, session_id).await;
+
         let is_resumed = initial_msg.as_ref()
             .map(|m| m.starts_with("/resume"))
             .unwrap_or(false);
@@ -1476,8 +1526,18 @@ impl ChatSession {
                 continue;
             }
 
-            // /model [name]
-            if trimmed == "/model" || trimmed.starts_with("/model ") {
+            // /hist [query] - browse shell history; the pick goes to the shell prompt
+            if trimmed == "/hist" || trimmed.starts_with("/hist ") {
+                if self.handle_hist(trimmed, session_id, rpc).await {
+                    break;
+                }
+                continue;
+            }
+
+            // /model [name] (/model <name> <query> is a one-off query, handled below)
+            if (trimmed == "/model" || trimmed.starts_with("/model "))
+                && command::parse_model_query(trimmed).is_none()
+            {
                 let arg = trimmed.strip_prefix("/model").unwrap().trim();
                 if arg.is_empty() {
                     self.handle_model(session_id, rpc).await;
@@ -1555,6 +1615,9 @@ impl ChatSession {
                     session_id: session_id.to_string(),
                     query,
                     scope: RequestScope::AllSessions,
+                    model_override: None,
+                    timeout_ms: None,
+                    format_hint: None,
                 });
                 match rpc.call(request).await {
                     Ok(Message::Response(resp)) if resp.request_id == request_id => {
@@ -1570,6 +1633,8 @@ impl ChatSession {
                         };
                         if let Some(path) = redirect {
                             super::handle_command_result(&display_text, Some(path), self.shell_cwd.as_deref());
+                        } else if self.page_long_output(&display_text, session_id, "context") {
+                            break;
                         } else {
                             // Command output is plain text; skip markdown rendering so that
                             // structured lines like `WORKING DIR: ...` inside the
@@ -1598,10 +1663,11 @@ impl ChatSession {
             if trimmed.starts_with('/')
                 && super::handle_slash_command(
                     trimmed, session_id, rpc, proxy, self.shell_cwd.as_deref(), client_debug_fn, cursor_col, cursor_row,
+                    self.query_opts, &mut self.pending_shell_input,
                 )
                 .await
             {
-                if auto_exit { break; }
+                if auto_exit || self.pending_shell_input.is_some() { break; }
                 continue;
             }
 
@@ -1628,6 +1694,9 @@ impl ChatSession {
                                 session_id: session_id.to_string(),
                                 query,
                                 scope: RequestScope::AllSessions,
+                                model_override: None,
+                                timeout_ms: None,
+                                format_hint: None,
                             });
                             let _ = rpc.call(req).await;
                         }
@@ -2037,7 +2106,7 @@ impl ChatSession {
         exit_action
     }
 
-    fn send_interrupt(req_id: &str, session_id: &str, thread_id: &str, query: &str, rpc: &RpcClient) {
+    fn send_interrupt(req_id: &str, session_id: &str, thread_id: &str, query: &str, rpc: &RpcClientPool) {
         let msg = Message::ChatInterrupt(ChatInterrupt {
             request_id: req_id.to_string(),
             session_id: session_id.to_string(),
@@ -2052,7 +2121,7 @@ impl ChatSession {
 
     // ── Command handlers ─────────────────────────────────────────────────
 
-    async fn handle_thread_del(&mut self, trimmed: &str, session_id: &str, rpc: &RpcClient) {
+    async fn handle_thread_del(&mut self, trimmed: &str, session_id: &str, rpc: &RpcClientPool) {
         let idx_str = trimmed
             .strip_prefix("/thread del")
             .map(|s| s.trim())
@@ -2065,6 +2134,9 @@ impl ChatSession {
                 session_id: session_id.to_string(),
                 query: "__cmd:conversations".to_string(),
                 scope: RequestScope::AllSessions,
+                model_override: None,
+                timeout_ms: None,
+                format_hint: None,
             });
             match rpc.call(req).await {
                 Ok(Message::Response(resp)) if resp.request_id == rid => {
@@ -2121,6 +2193,9 @@ impl ChatSession {
                 session_id: session_id.to_string(),
                 query: "__cmd:conversations".to_string(),
                 scope: RequestScope::AllSessions,
+                model_override: None,
+                timeout_ms: None,
+                format_hint: None,
             });
             if let Ok(Message::Response(resp)) = rpc.call(req).await {
                 if resp.request_id == rid {
@@ -2167,6 +2242,9 @@ impl ChatSession {
                             session_id: session_id.to_string(),
                             query: format!("__cmd:conversations del {}", tid),
                             scope: RequestScope::AllSessions,
+                            model_override: None,
+                            timeout_ms: None,
+                            format_hint: None,
                         });
                         match rpc.call(req).await {
                             Ok(Message::Response(resp)) if resp.request_id == rid => {
@@ -2204,7 +2282,7 @@ impl ChatSession {
         }
     }
 
-    async fn handle_thread_list(&mut self, session_id: &str, rpc: &RpcClient, limit: Option<usize>) {
+    async fn handle_thread_list(&mut self, session_id: &str, rpc: &RpcClientPool, limit: Option<usize>) {
         let query = match limit {
             Some(n) => format!("__cmd:conversations {}", n),
             None => "__cmd:conversations".to_string(),
@@ -2215,6 +2293,9 @@ impl ChatSession {
             session_id: session_id.to_string(),
             query,
             scope: RequestScope::AllSessions,
+            model_override: None,
+            timeout_ms: None,
+            format_hint: None,
         });
         match rpc.call(request).await {
             Ok(Message::Response(resp)) if resp.request_id == request_id => {
@@ -2237,7 +2318,7 @@ impl ChatSession {
         }
     }
 
-    async fn handle_thread_sandbox(&mut self, trimmed: &str, session_id: &str, rpc: &RpcClient) {
+    async fn handle_thread_sandbox(&mut self, trimmed: &str, session_id: &str, rpc: &RpcClientPool) {
         let sub = trimmed
             .strip_prefix("/thread sandbox")
             .map(|s| s.trim())
@@ -2286,6 +2367,9 @@ impl ChatSession {
             session_id: session_id.to_string(),
             query,
             scope: RequestScope::AllSessions,
+            model_override: None,
+            timeout_ms: None,
+            format_hint: None,
         });
         match rpc.call(request).await {
             Ok(Message::Response(resp)) if resp.request_id == rid => {
@@ -2302,7 +2386,7 @@ impl ChatSession {
         }
     }
 
-    async fn handle_thread_rename(&mut self, trimmed: &str, session_id: &str, rpc: &RpcClient) {
+    async fn handle_thread_rename(&mut self, trimmed: &str, session_id: &str, rpc: &RpcClientPool) {
         let raw = trimmed
             .strip_prefix("/thread rename")
             .map(|s| s.trim())
@@ -2332,6 +2416,9 @@ impl ChatSession {
             session_id: session_id.to_string(),
             query,
             scope: RequestScope::AllSessions,
+            model_override: None,
+            timeout_ms: None,
+            format_hint: None,
         });
         match rpc.call(request).await {
             Ok(Message::Response(resp)) if resp.request_id == rid => {
@@ -2356,7 +2443,38 @@ impl ChatSession {
         }
     }
 
-    async fn handle_resume(&mut self, trimmed: &str, session_id: &str, rpc: &RpcClient) -> bool {
+    /// When `text` is taller than the terminal, save it for paging as
+    /// `kind` (see `pager::save`) and leave chat mode with `less <path>`
+    /// typed at the shell prompt. Returns false (nothing done) when it fits
+    /// or the file cannot be written.
+    fn page_long_output(&mut self, text: &str, session_id: &str, kind: &str) -> bool {
+        let (rows, _) = crate::get_terminal_size().unwrap_or((24, 80));
+        if !pager::should_page(text, rows, self.query_opts.pager_threshold_lines) {
+            return false;
+        }
+        self.pending_shell_input = pager::offer(session_id, kind, text);
+        self.pending_shell_input.is_some()
+    }
+
+    /// Open the history browser. Returns true when a command was picked and
+    /// chat mode should exit so it can be placed at the shell prompt.
+    async fn handle_hist(&mut self, trimmed: &str, session_id: &str, rpc: &RpcClientPool) -> bool {
+        let query = trimmed.strip_prefix("/hist").map(str::trim).unwrap_or("");
+        let commands = Self::fetch_command_lines(rpc, session_id, "__cmd:history all").await;
+        if commands.is_empty() {
+            write_stdout(&display::render_error(crate::i18n::t("error.no_history")));
+            return false;
+        }
+        match widgets::history_browser::browse(commands, query) {
+            Some(line) => {
+                self.pending_shell_input = Some(line);
+                true
+            }
+            None => false,
+        }
+    }
+
+    async fn handle_resume(&mut self, trimmed: &str, session_id: &str, rpc: &RpcClientPool) -> bool {
         // Argument after /resume.  "" and "all" open picker (current host vs all hosts);
         // a numeric arg picks from the last-fetched cache.
         let rest = trimmed.strip_prefix("/resume").map(str::trim).unwrap_or("");
@@ -2406,7 +2524,7 @@ impl ChatSession {
 
     /// Fetch and cache thread IDs from the daemon.
     /// `all_hosts` = false restricts to threads created on the current host.
-    async fn fetch_thread_ids(&mut self, session_id: &str, rpc: &RpcClient, all_hosts: bool) {
+    async fn fetch_thread_ids(&mut self, session_id: &str, rpc: &RpcClientPool, all_hosts: bool) {
         let rid = Uuid::new_v4().to_string()[..8].to_string();
         let query = if all_hosts {
             "__cmd:conversations all".to_string()
@@ -2418,6 +2536,9 @@ impl ChatSession {
             session_id: session_id.to_string(),
             query,
             scope: RequestScope::AllSessions,
+            model_override: None,
+            timeout_ms: None,
+            format_hint: None,
         });
         if let Ok(Message::Response(resp)) = rpc.call(req).await {
             if resp.request_id == rid {
@@ -2436,7 +2557,7 @@ impl ChatSession {
     /// Show the resume picker with lock-aware disabled items.
     /// Returns the selected thread_id, or None on ESC/cancel.
     /// `all_hosts` = false restricts to threads created on the current host.
-    async fn show_resume_picker(&mut self, session_id: &str, rpc: &RpcClient, all_hosts: bool) -> Option<String> {
+    async fn show_resume_picker(&mut self, session_id: &str, rpc: &RpcClientPool, all_hosts: bool) -> Option<String> {
         self.fetch_thread_ids(session_id, rpc, all_hosts).await;
         if self.cached_thread_ids.is_empty() {
             write_stdout(&display::render_error(crate::i18n::t("error.no_conversations_to_resume")));
@@ -2453,6 +2574,9 @@ impl ChatSession {
             session_id: session_id.to_string(),
             query,
             scope: RequestScope::AllSessions,
+            model_override: None,
+            timeout_ms: None,
+            format_hint: None,
         });
         match rpc.call(req).await {
             Ok(Message::Response(resp)) if resp.request_id == rid => {
@@ -2660,7 +2784,7 @@ impl ChatSession {
 
     /// Resume a specific thread by ID via ChatStart protocol message.
     /// Returns `true` if the thread was successfully resumed, `false` if cancelled or failed.
-    async fn handle_resume_tid(&mut self, tid: &str, session_id: &str, rpc: &RpcClient) -> bool {
+    async fn handle_resume_tid(&mut self, tid: &str, session_id: &str, rpc: &RpcClientPool) -> bool {
         crate::event_log::push(format!("resume_tid: sending ChatStart thread={}", tid));
         let rid = Uuid::new_v4().to_string()[..8].to_string();
         let start_msg = Message::ChatStart(ChatStart {
@@ -2829,7 +2953,7 @@ impl ChatSession {
     /// Fetch the backend list from the daemon, with the current thread's
     /// selected backend marked.  Returns None when the call fails or the
     /// list is empty (caller should render `error.no_llm_backends`).
-    async fn fetch_models(&self, session_id: &str, rpc: &RpcClient) -> Option<Vec<serde_json::Value>> {
+    async fn fetch_models(&self, session_id: &str, rpc: &RpcClientPool) -> Option<Vec<serde_json::Value>> {
         let query = match &self.current_thread_id {
             Some(tid) => format!("__cmd:models {}", tid),
             None => "__cmd:models".to_string(),
@@ -2840,6 +2964,9 @@ impl ChatSession {
             session_id: session_id.to_string(),
             query,
             scope: RequestScope::AllSessions,
+            model_override: None,
+            timeout_ms: None,
+            format_hint: None,
         });
         let models = match rpc.call(req).await {
             Ok(Message::Response(resp)) if resp.request_id == rid => {
@@ -2859,7 +2986,7 @@ impl ChatSession {
         name: String,
         display_name: &str,
         session_id: &str,
-        rpc: &RpcClient,
+        rpc: &RpcClientPool,
     ) {
         if let Some(ref tid) = self.current_thread_id {
             let rid = Uuid::new_v4().to_string()[..8].to_string();
@@ -2887,7 +3014,7 @@ impl ChatSession {
         }
     }
 
-    async fn handle_model(&mut self, session_id: &str, rpc: &RpcClient) {
+    async fn handle_model(&mut self, session_id: &str, rpc: &RpcClientPool) {
         let models = match self.fetch_models(session_id, rpc).await {
             Some(m) => m,
             None => {
@@ -2922,7 +3049,7 @@ impl ChatSession {
     /// `/model <name>` direct switch without picker. Validates the name
     /// against the backend list returned by `__cmd:models`; on a miss prints
     /// the available names so the user can correct the typo.
-    async fn handle_model_set(&mut self, name: &str, session_id: &str, rpc: &RpcClient) {
+    async fn handle_model_set(&mut self, name: &str, session_id: &str, rpc: &RpcClientPool) {
         let models = match self.fetch_models(session_id, rpc).await {
             Some(m) => m,
             None => {
@@ -3023,7 +3150,7 @@ impl ChatSession {
         }
     }
 
-    async fn handle_test_disconnect(&self, arg: &str, rpc: &RpcClient) {
+    async fn handle_test_disconnect(&self, arg: &str, rpc: &RpcClientPool) {
         let parts: Vec<&str> = arg.split_whitespace().collect();
         // parts[0] = "disconnect", parts[1] = N1, parts[2] = N2 (optional)
         let delay_secs: u64 = parts.get(1).and_then(|s| s.parse().ok()).unwrap_or(0);
@@ -3363,7 +3490,7 @@ impl ChatSession {
         }
     }
 
-    async fn handle_config(&mut self, _session_id: &str, rpc: &RpcClient) {
+    async fn handle_config(&mut self, _session_id: &str, rpc: &RpcClientPool) {
         let (items, handlers) = match rpc.call(Message::ConfigQuery).await {
             Ok(Message::ConfigResponse { items, handlers }) => (items, handlers),
             Ok(_) => {
@@ -17,6 +17,11 @@ pub enum ChatAction {
         redirect: Option<String>,
         limit: Option<OutputLimit>,
     },
+    /// `/export <path>` - ask the daemon to write the current session as Markdown.
+    Export { output_path: String },
+    /// `/model <name> <query>` - answer one query with the named backend
+    /// without switching the thread's model.
+    LlmQueryWithModel { query: String, model: String },
 }
 
 /// Limit applied to command output (head or tail).
@@ -96,15 +101,12 @@ fn help_for(entry: &CommandEntry) -> String {
     }
 }
 
+fn export_usage(_args: &str) -> String {
+    crate::i18n::t("command.usage_export").to_string()
+}
+
 fn integrate_command(args: &str) -> String {
-    let omnish_bin = {
-        let home = std::env::var("OMNISH_HOME")
-            .unwrap_or_else(|_| {
-                let h = std::env::var("HOME").unwrap_or_default();
-                format!("{}/.omnish", h)
-            });
-        format!("{}/bin/omnish", home)
-    };
+    let omnish_bin = omnish_common::config::omnish_dir().join("bin/omnish").display().to_string();
 
     let target = args.trim();
     if target.is_empty() {
@@ -218,7 +220,8 @@ fn help_command(_args: &str) -> String {
     }
     // Chat-mode-only commands not in the registry.
     output.push_str(&format!("  /resume - {}\n", crate::i18n::t("command.help.resume")));
-    output.push_str(&format!("  /model [name] - {}\n", crate::i18n::t("command.help.model")));
+    output.push_str(&format!("  /hist [query] - {}\n", crate::i18n::t("command.help.hist")));
+    output.push_str(&format!("  /model [name [query]] - {}\n", crate::i18n::t("command.help.model")));
     output
 }
 
@@ -319,7 +322,7 @@ const COMMANDS: &[CommandEntry] = &[
     CommandEntry {
         path: "/sessions",
         kind: CommandKind::Daemon("sessions"),
-        help: "List sessions",
+        help: "List active sessions (--all [page] includes ended ones)",
     },
     CommandEntry {
         path: "/thread",
@@ -341,11 +344,71 @@ const COMMANDS: &[CommandEntry] = &[
         kind: CommandKind::Daemon("conversations del"),
         help: "Delete a conversation thread",
     },
+    CommandEntry {
+        path: "/search",
+        kind: CommandKind::Daemon("search"),
+        help: "Search command lines and output across sessions (/search <regex>)",
+    },
+    CommandEntry {
+        path: "/grep",
+        kind: CommandKind::Daemon("grep"),
+        help: "Show every output line matching a regex, with context (/grep <regex>)",
+    },
+    CommandEntry {
+        path: "/history",
+        kind: CommandKind::Daemon("history queries"),
+        help: "Show this session's recent LLM queries and answers (/history [N])",
+    },
+    CommandEntry {
+        path: "/replay",
+        kind: CommandKind::Daemon("replay"),
+        help: "Replay a session's recorded terminal output (/replay <session_id> [speed], 0 = instant)",
+    },
+    CommandEntry {
+        path: "/stats",
+        kind: CommandKind::Daemon("stats"),
+        help: "Show command counts, error rate and durations for this session",
+    },
+    CommandEntry {
+        path: "/tag",
+        kind: CommandKind::Daemon("tag"),
+        help: "Label this session's commands matching a regex (/tag <pattern> <label>)",
+    },
+    CommandEntry {
+        path: "/tag-session",
+        kind: CommandKind::Daemon("tag-session"),
+        help: "Set a session's tags, shown in /sessions (/tag-session <session> <tag>...)",
+    },
+    CommandEntry {
+        path: "/describe-session",
+        kind: CommandKind::Daemon("describe-session"),
+        help: "Set a session's description (/describe-session <session> <text>)",
+    },
+    CommandEntry {
+        path: "/merge",
+        kind: CommandKind::Daemon("merge"),
+        help: "Merge ended sessions into a new session (/merge <session> <session>...)",
+    },
+    CommandEntry {
+        path: "/vacuum",
+        kind: CommandKind::Daemon("vacuum"),
+        help: "Compact a session's stored output, dropping unreferenced bytes (/vacuum <session>)",
+    },
+    CommandEntry {
+        path: "/export",
+        kind: CommandKind::Local(export_usage),
+        help: "Export this session as Markdown (/export <file.md>)",
+    },
     CommandEntry {
         path: "/tasks",
         kind: CommandKind::Daemon("tasks"),
         help: "List or manage scheduled tasks",
     },
+    CommandEntry {
+        path: "/run-task",
+        kind: CommandKind::Daemon("run-task"),
+        help: "Run a scheduled task now (/run-task <name>)",
+    },
     // Registered as Daemon but intercepted client-side in main.rs
     // because it needs process state (proxy fd/pid) for exec.
     CommandEntry {
@@ -366,6 +429,7 @@ const COMMANDS: &[CommandEntry] = &[
 pub const CHAT_ONLY_COMMANDS: &[&str] = &[
     "/resume",
     "/resume all",
+    "/hist",
     "/model",
     "/thread sandbox",
     "/thread sandbox on",
@@ -529,12 +593,36 @@ pub fn apply_limit(text: &str, limit: &OutputLimit) -> String {
     selected.join("\n")
 }
 
+/// Turn a message typed after prefix `prefix_index` into the chat input to
+/// dispatch. Extra prefixes (index > 0) are shortcuts for `/` commands, so
+/// `!sessions` runs `/sessions`; the primary prefix passes the message through.
+pub fn route_prefixed(msg: String, prefix_index: usize) -> String {
+    if prefix_index == 0 || msg.trim().is_empty() || msg.starts_with('/') {
+        msg
+    } else {
+        format!("/{}", msg)
+    }
+}
+
+/// Split `/model <name> <query>` into `(name, query)`. None for `/model`
+/// and `/model <name>`, which select the thread model instead.
+pub fn parse_model_query(msg: &str) -> Option<(&str, &str)> {
+    let rest = msg.strip_prefix("/model ")?.trim_start();
+    let (name, query) = rest.split_once(char::is_whitespace)?;
+    let query = query.trim();
+    (!query.is_empty()).then_some((name, query))
+}
+
 /// Dispatch a chat message. Returns ChatAction describing what to do.
 pub fn dispatch(msg: &str) -> ChatAction {
     if !msg.starts_with('/') {
         return ChatAction::LlmQuery(msg.to_string());
     }
 
+    if let Some((model, query)) = parse_model_query(msg) {
+        return ChatAction::LlmQueryWithModel { query: query.to_string(), model: model.to_string() };
+    }
+
     // Parse redirect first, then limit
     let (cmd_str_without_redirect, redirect) = parse_redirect(msg);
     let redirect = redirect.map(|s| s.to_string());
@@ -554,6 +642,10 @@ pub fn dispatch(msg: &str) -> ChatAction {
     if let Some(entry) = best {
         let remainder = cmd_str[entry.path.len()..].trim();
 
+        if entry.path == "/export" && !remainder.is_empty() {
+            return ChatAction::Export { output_path: remainder.to_string() };
+        }
+
         match &entry.kind {
             CommandKind::Local(f) => ChatAction::Command {
                 result: f(remainder),
@@ -588,6 +680,21 @@ mod tests {
         );
     }
 
+    #[test]
+    fn test_model_query_carries_model_name() {
+        match dispatch("/model gpt-4 why does curl fail") {
+            ChatAction::LlmQueryWithModel { query, model } => {
+                assert_eq!(model, "gpt-4");
+                assert_eq!(query, "why does curl fail");
+            }
+            _ => panic!("expected LlmQueryWithModel"),
+        }
+        // Bare /model and /model <name> still switch the thread model.
+        assert!(parse_model_query("/model").is_none());
+        assert!(parse_model_query("/model gpt-4").is_none());
+        assert!(parse_model_query("/model gpt-4   ").is_none());
+    }
+
     #[test]
     fn test_non_command_is_llm_query() {
         match dispatch("what is this error?") {
@@ -634,6 +741,99 @@ mod tests {
         }
     }
 
+    #[test]
+    fn test_search_dispatches_to_daemon() {
+        match dispatch("/search error\\[E0308") {
+            ChatAction::DaemonQuery { query, redirect, .. } => {
+                assert_eq!(query, "__cmd:search error\\[E0308");
+                assert!(redirect.is_none());
+            }
+            _ => panic!("expected DaemonQuery"),
+        }
+    }
+
+    #[test]
+    fn test_grep_dispatches_to_daemon() {
+        match dispatch("/grep connection refused") {
+            ChatAction::DaemonQuery { query, .. } => assert_eq!(query, "__cmd:grep connection refused"),
+            _ => panic!("expected DaemonQuery"),
+        }
+    }
+
+    #[test]
+    fn test_route_prefixed_shortcut_runs_command() {
+        assert!(matches!(
+            dispatch(&route_prefixed("why did make fail".into(), 0)),
+            ChatAction::LlmQuery(_)
+        ));
+        match dispatch(&route_prefixed("search panic".into(), 1)) {
+            ChatAction::DaemonQuery { query, .. } => assert_eq!(query, "__cmd:search panic"),
+            _ => panic!("expected DaemonQuery"),
+        }
+        assert_eq!(route_prefixed("/search x".into(), 1), "/search x");
+        assert_eq!(route_prefixed(String::new(), 1), "");
+    }
+
+    #[test]
+    fn test_tag_dispatches_to_daemon() {
+        match dispatch("/tag ^cargo rust") {
+            ChatAction::DaemonQuery { query, .. } => assert_eq!(query, "__cmd:tag ^cargo rust"),
+            _ => panic!("expected DaemonQuery"),
+        }
+    }
+
+    #[test]
+    fn test_tag_session_is_not_tag() {
+        match dispatch("/tag-session abc12345 prod debug") {
+            ChatAction::DaemonQuery { query, .. } => assert_eq!(query, "__cmd:tag-session abc12345 prod debug"),
+            _ => panic!("expected DaemonQuery"),
+        }
+    }
+
+    #[test]
+    fn test_history_dispatches_to_daemon() {
+        match dispatch("/history 5") {
+            ChatAction::DaemonQuery { query, .. } => assert_eq!(query, "__cmd:history queries 5"),
+            _ => panic!("expected DaemonQuery"),
+        }
+    }
+
+    #[test]
+    fn test_replay_dispatches_to_daemon() {
+        match dispatch("/replay abc123 2") {
+            ChatAction::DaemonQuery { query, .. } => assert_eq!(query, "__cmd:replay abc123 2"),
+            _ => panic!("expected DaemonQuery"),
+        }
+    }
+
+    #[test]
+    fn test_stats_dispatches_to_daemon() {
+        match dispatch("/stats") {
+            ChatAction::DaemonQuery { query, .. } => assert_eq!(query, "__cmd:stats"),
+            _ => panic!("expected DaemonQuery"),
+        }
+    }
+
+    #[test]
+    fn test_merge_dispatches_to_daemon() {
+        match dispatch("/merge s1 s2") {
+            ChatAction::DaemonQuery { query, .. } => assert_eq!(query, "__cmd:merge s1 s2"),
+            _ => panic!("expected DaemonQuery"),
+        }
+    }
+
+    #[test]
+    fn test_export_dispatch() {
+        match dispatch("/export /tmp/session.md") {
+            ChatAction::Export { output_path } => assert_eq!(output_path, "/tmp/session.md"),
+            _ => panic!("expected Export"),
+        }
+        match dispatch("/export") {
+            ChatAction::Command { result, .. } => assert!(result.contains("/export")),
+            _ => panic!("expected usage Command"),
+        }
+    }
+
     #[test]
     fn test_template_no_args_dispatches_to_daemon() {
         match dispatch("/template") {
@@ -690,6 +890,10 @@ mod tests {
             }
             _ => panic!("expected DaemonQuery"),
         }
+        match dispatch("/sessions --all 2") {
+            ChatAction::DaemonQuery { query, .. } => assert_eq!(query, "__cmd:sessions --all 2"),
+            _ => panic!("expected DaemonQuery"),
+        }
     }
 
     #[test]
@@ -765,6 +969,16 @@ mod tests {
         }
     }
 
+    #[test]
+    fn test_run_task_forwards_name() {
+        match dispatch("/run-task house_keeping") {
+            ChatAction::DaemonQuery { query, .. } => {
+                assert_eq!(query, "__cmd:run-task house_keeping");
+            }
+            _ => panic!("expected DaemonQuery"),
+        }
+    }
+
     #[test]
     fn test_debug_client_dispatches_to_daemon() {
         match dispatch("/debug client") {
@@ -1,7 +1,7 @@
-use std::time::Instant;
-use std::collections::HashMap;
+use std::time::{Duration, Instant};
+use std::collections::{HashMap, VecDeque};
 use omnish_protocol::message::{
-    CompletionRequest, CompletionResponse, CompletionSummary, Message,
+    CompletionRequest, CompletionResponse, CompletionSuggestion, CompletionSummary, Message,
 };
 
 const DEBOUNCE_MS: u64 = 500;
@@ -9,6 +9,82 @@ const DEBOUNCE_MS: u64 = 500;
 const IN_FLIGHT_TIMEOUT_MS: u64 = 5000;
 /// Maximum number of concurrent requests allowed
 const MAX_CONCURRENT_REQUESTS: usize = 5;
+/// Maximum number of entries kept in the completion cache
+const CACHE_MAX_ENTRIES: usize = 200;
+/// Default lifetime of a cached completion
+const DEFAULT_CACHE_TTL_SECS: u64 = 300;
+
+/// LRU cache of full-command suggestions keyed on (input, cwd).
+///
+/// Completions depend on the working directory, so the same prefix typed in
+/// two directories is cached separately. `order` holds keys from least to
+/// most recently used; the front is evicted once `CACHE_MAX_ENTRIES` is hit.
+pub struct CompletionCache {
+    entries: HashMap<(String, String), (String, Instant)>,
+    order: VecDeque<(String, String)>,
+    ttl: Duration,
+}
+
+impl CompletionCache {
+    pub fn new(ttl: Duration) -> Self {
+        Self {
+            entries: HashMap::new(),
+            order: VecDeque::new(),
+            ttl,
+        }
+    }
+
+    pub fn set_ttl(&mut self, ttl: Duration) {
+        self.ttl = ttl;
+    }
+
+    /// Look up a suggestion younger than the TTL, marking it most recently used.
+    pub fn get(&mut self, input: &str, cwd: &str) -> Option<String> {
+        if self.ttl.is_zero() {
+            return None;
+        }
+        let key = (input.to_string(), cwd.to_string());
+        let (text, stored_at) = self.entries.get(&key)?;
+        if stored_at.elapsed() >= self.ttl {
+            self.entries.remove(&key);
+            self.order.retain(|k| k != &key);
+            return None;
+        }
+        let text = text.clone();
+        self.touch(&key);
+        Some(text)
+    }
+
+    pub fn insert(&mut self, input: &str, cwd: &str, suggestion: &str) {
+        if self.ttl.is_zero() {
+            return;
+        }
+        let key = (input.to_string(), cwd.to_string());
+        if self.entries.insert(key.clone(), (suggestion.to_string(), Instant::now())).is_some() {
+            self.touch(&key);
+            return;
+        }
+        self.order.push_back(key);
+        while self.order.len() > CACHE_MAX_ENTRIES {
+            if let Some(oldest) = self.order.pop_front() {
+                self.entries.remove(&oldest);
+            }
+        }
+    }
+
+    pub fn clear(&mut self) {
+        self.entries.clear();
+        self.order.clear();
+    }
+
+    fn touch(&mut self, key: &(String, String)) {
+        if let Some(pos) = self.order.iter().position(|k| k == key) {
+            if let Some(k) = self.order.remove(pos) {
+                self.order.push_back(k);
+            }
+        }
+    }
+}
 
 /// State of an active completion request
 #[derive(Debug, Clone)]
@@ -17,6 +93,8 @@ struct RequestState {
     input: String,
     sent_at: Instant,
     sequence_id: u64,
+    /// Working directory the request was made in (cache key component)
+    cwd: String,
     /// Best suggestion from the response (if received)
     best_suggestion: Option<String>,
     /// When the response was received
@@ -45,6 +123,16 @@ pub struct ShellCompleter {
     /// Input that was explicitly dismissed by the user (ESC key).
     /// Prevents re-requesting completion for the same input.
     dismissed_input: Option<String>,
+    /// Recently accepted-as-valid suggestions, reused instead of re-requesting.
+    cache: CompletionCache,
+    /// Working directory used for requests marked from now on.
+    cwd: String,
+    /// Readline is in vi command mode: keys are editing commands, so no
+    /// completions are requested or shown.
+    vi_command_mode: bool,
+    /// Command lines run before, most recent first, completed locally
+    /// ahead of the cache and the LLM.
+    history: crate::ghost_complete::HistoryProvider,
 }
 
 /// Info about the last completion response
@@ -87,6 +175,82 @@ impl ShellCompleter {
             sent_input: String::new(),
             last_completion: None,
             dismissed_input: None,
+            cache: CompletionCache::new(Duration::from_secs(DEFAULT_CACHE_TTL_SECS)),
+            cwd: String::new(),
+            vi_command_mode: false,
+            history: crate::ghost_complete::HistoryProvider::new(Vec::new()),
+        }
+    }
+
+    /// Set how long cached completions stay valid (0 disables the cache).
+    pub fn set_cache_ttl_secs(&mut self, secs: u64) {
+        self.cache.set_ttl(Duration::from_secs(secs));
+    }
+
+    /// Set the working directory used as part of the cache key for
+    /// subsequent requests. Changing it drops the cache, since what a
+    /// directory holds may have changed by the time it is entered again.
+    pub fn set_cwd(&mut self, cwd: Option<&str>) {
+        let cwd = cwd.unwrap_or_default();
+        if self.cwd != cwd {
+            self.cache.clear();
+            self.cwd = cwd.to_string();
+        }
+    }
+
+    /// Serve a request from the cache. Call when `should_request` returns
+    /// true: on a hit the request is marked as sent and a synthetic response
+    /// is returned, which the caller feeds through `on_response` in place of
+    /// a daemon round-trip.
+    pub fn cached_response(&mut self, sequence_id: u64, input: &str) -> Option<CompletionResponse> {
+        let text = self.cache.get(input, &self.cwd)?;
+        Some(self.local_response(sequence_id, input, text))
+    }
+
+    /// Complete a partial path in `input` from the filesystem (relative to
+    /// the cwd set via `set_cwd`). Consulted before the cache and the LLM;
+    /// used the same way as `cached_response`.
+    pub fn path_response(&mut self, sequence_id: u64, input: &str) -> Option<CompletionResponse> {
+        use crate::ghost_complete::{CompletionProvider, PathCompletionProvider};
+        let provider = PathCompletionProvider::new(&self.cwd);
+        let text = provider.suggest(input)?;
+        Some(self.local_response(sequence_id, input, text))
+    }
+
+    /// Seed the history used by `history_response` with `commands`, most
+    /// recent first. They rank below commands already noted this session.
+    pub fn seed_history(&mut self, commands: Vec<String>) {
+        self.history.extend_older(commands);
+    }
+
+    /// Record a command line the shell just finished running.
+    pub fn note_command(&mut self, command_line: &str) {
+        self.history.push_recent(command_line);
+    }
+
+    /// Complete `input` to the most recent command line that starts with
+    /// it. Consulted after `path_response` and before the cache; used the
+    /// same way as `cached_response`, so no LLM request is sent.
+    pub fn history_response(&mut self, sequence_id: u64, input: &str) -> Option<CompletionResponse> {
+        use crate::ghost_complete::CompletionProvider;
+        let text = self.history.suggest(input)?;
+        Some(self.local_response(sequence_id, input, text))
+    }
+
+    /// Complete `input` from the built-in table of common shell commands.
+    /// Only meant for when the daemon can't be reached; used the same way
+    /// as `cached_response`.
+    pub fn common_command_response(&mut self, sequence_id: u64, input: &str) -> Option<CompletionResponse> {
+        use crate::ghost_complete::{CommonCommandProvider, CompletionProvider};
+        let text = CommonCommandProvider.suggest(input)?;
+        Some(self.local_response(sequence_id, input, text))
+    }
+
+    fn local_response(&mut self, sequence_id: u64, input: &str, text: String) -> CompletionResponse {
+        self.mark_sent(sequence_id, input);
+        CompletionResponse {
+            sequence_id,
+            suggestions: vec![CompletionSuggestion { text, confidence: 1.0 }],
         }
     }
 
@@ -112,9 +276,11 @@ impl ShellCompleter {
         if let Some(ref ghost) = self.current_ghost {
             if input.starts_with(&self.ghost_input) {
                 let extra_typed = input.len() - self.ghost_input.len();
-                // Verify the extra characters actually match the ghost text
-                if extra_typed < ghost.len()
-                    && ghost[..extra_typed] == input[self.ghost_input.len()..]
+                // Verify the extra characters actually match the ghost text.
+                // Typing it out completely leaves `Some("")` so the caller
+                // can tell it apart from a mismatch (see `on_ghost_fully_consumed`).
+                if extra_typed <= ghost.len()
+                    && ghost.get(..extra_typed) == Some(&input[self.ghost_input.len()..])
                 {
                     // Trim consumed portion so accept() returns only the remaining suffix
                     self.current_ghost = Some(ghost[extra_typed..].to_string());
@@ -129,6 +295,20 @@ impl ShellCompleter {
         had_ghost
     }
 
+    /// Follow the shell's vi mode, passed alongside each `on_input_changed`.
+    /// Entering command mode drops the ghost; returns `true` if one was
+    /// shown (caller should erase it from screen).
+    pub fn set_vi_command_mode(&mut self, on: bool) -> bool {
+        self.vi_command_mode = on;
+        if on {
+            let had_ghost = self.current_ghost.is_some();
+            self.current_ghost = None;
+            had_ghost
+        } else {
+            false
+        }
+    }
+
     /// Check if debounce timer has expired and we should send a request.
     ///
     /// Logic:
@@ -138,6 +318,10 @@ impl ShellCompleter {
     ///    after timeout (2× for empty input to reduce spam).
     /// 4. Require new input - sequence_id must have advanced since last send.
     pub fn should_request(&self, current_sequence_id: u64, current_input: &str) -> bool {
+        if self.vi_command_mode {
+            return false;
+        }
+
         // Don't re-request for explicitly dismissed input
         if self.dismissed_input.as_deref() == Some(current_input) {
             return false;
@@ -181,6 +365,7 @@ impl ShellCompleter {
             input: input.to_string(),
             sent_at: Instant::now(),
             sequence_id,
+            cwd: self.cwd.clone(),
             best_suggestion: None,
             response_at: None,
         });
@@ -248,6 +433,7 @@ impl ShellCompleter {
 
         // Extract sent_at for latency calculation before removing the request
         let sent_at = request_state.as_ref().map(|rs| rs.sent_at);
+        let request_cwd = request_state.as_ref().map(|rs| rs.cwd.clone()).unwrap_or_default();
 
         // Remove this completed request from active tracking (only after validation)
         self.active_requests.remove(&response.sequence_id);
@@ -323,6 +509,7 @@ impl ShellCompleter {
                     ));
                     return None;
                 };
+                self.cache.insert(&request_input, &request_cwd, &full_suggestion);
 
                 // Check if current input is a prefix of the full suggestion
                 if let Some(suffix) = full_suggestion.strip_prefix(current_input) {
@@ -372,6 +559,36 @@ impl ShellCompleter {
         Some(ghost)
     }
 
+    /// Accept the first word of the ghost text, including the whitespace that
+    /// follows it, and keep the remainder as ghost. Returns text to inject into PTY.
+    pub fn accept_word(&mut self) -> Option<String> {
+        let ghost = self.current_ghost.as_deref()?;
+        let word_start = ghost.len() - ghost.trim_start().len();
+        let word_end = ghost[word_start..]
+            .find(char::is_whitespace)
+            .map_or(ghost.len(), |i| word_start + i);
+        let rest = ghost[word_end..].trim_start();
+        if rest.is_empty() {
+            return self.accept();
+        }
+        let split = ghost.len() - rest.len();
+        let word = ghost[..split].to_string();
+        self.current_ghost = Some(rest.to_string());
+        self.ghost_input.push_str(&word);
+        Some(word)
+    }
+
+    /// The user has consumed the whole ghost, by typing it out or accepting
+    /// it. Drops the empty ghost and restarts the debounce, keeping the
+    /// sequence bookkeeping, so `should_request` asks for the next
+    /// suggestion once the user pauses.
+    pub fn on_ghost_fully_consumed(&mut self) {
+        self.current_ghost = None;
+        self.ghost_input.clear();
+        self.ghost_set_at = None;
+        self.last_change = Some(Instant::now());
+    }
+
     /// Dismiss ghost text explicitly (user pressed ESC).
     /// Clears ghost and suppresses re-requesting for the same input.
     /// Returns `true` if ghost text was dismissed (caller should erase it from screen).
@@ -387,7 +604,8 @@ impl ShellCompleter {
     }
 
     /// Clear ghost text and clean up any active requests.
-    /// Called when prompt appears or user cancels completion.
+    /// Called when prompt appears or user cancels completion. The cache
+    /// survives: entries expire by TTL or when the cwd changes.
     pub fn clear(&mut self) {
         self.current_ghost = None;
         self.ghost_input.clear();
@@ -518,6 +736,17 @@ mod tests {
         assert!(c.should_request(1, ""));
     }
 
+    #[test]
+    fn test_no_request_in_vi_command_mode() {
+        let mut c = ShellCompleter::new();
+        c.on_input_changed("git sta", 5);
+        c.last_change = Some(Instant::now() - std::time::Duration::from_secs(1));
+        assert!(!c.set_vi_command_mode(true));
+        assert!(!c.should_request(5, "git sta"));
+        c.set_vi_command_mode(false);
+        assert!(c.should_request(5, "git sta"));
+    }
+
     #[test]
     fn test_debounce_ready_after_timeout() {
         let mut c = ShellCompleter::new();
@@ -788,6 +1017,83 @@ mod tests {
         assert_eq!(c.accept(), Some(" run".to_string()));
     }
 
+    #[test]
+    fn test_typing_out_ghost_requests_next_after_debounce() {
+        let mut c = ShellCompleter::new();
+        c.mark_sent(1, "git");
+        let resp = CompletionResponse {
+            sequence_id: 1,
+            suggestions: vec![CompletionSuggestion {
+                text: "git add".to_string(),
+                confidence: 0.9,
+            }],
+        };
+        c.on_response(&resp, "git");
+        assert!(!c.on_input_changed("git a", 2));
+        assert!(!c.on_input_changed("git ad", 3));
+        assert_eq!(c.ghost(), Some("d"));
+
+        // Last character consumes the ghost; nothing to erase
+        assert!(!c.on_input_changed("git add", 4));
+        assert_eq!(c.ghost(), Some(""));
+
+        c.on_ghost_fully_consumed();
+        assert!(c.ghost().is_none());
+        assert!(!c.should_request(4, "git add"), "must wait for debounce");
+        c.last_change = Some(Instant::now() - std::time::Duration::from_millis(DEBOUNCE_MS + 1));
+        assert!(c.should_request(4, "git add"));
+    }
+
+    #[test]
+    fn test_accepted_ghost_requests_next_after_debounce() {
+        let mut c = ShellCompleter::new();
+        c.mark_sent(1, "git");
+        let resp = CompletionResponse {
+            sequence_id: 1,
+            suggestions: vec![CompletionSuggestion {
+                text: "git commit".to_string(),
+                confidence: 0.9,
+            }],
+        };
+        c.on_response(&resp, "git");
+        assert_eq!(c.accept_word(), Some(" commit".to_string()));
+        assert!(c.ghost().is_none());
+
+        c.on_ghost_fully_consumed();
+        assert!(!c.should_request(2, "git commit"));
+        c.last_change = Some(Instant::now() - std::time::Duration::from_millis(DEBOUNCE_MS + 1));
+        assert!(c.should_request(2, "git commit"));
+    }
+
+    #[test]
+    fn test_accept_word_takes_one_word_per_tab() {
+        let mut c = ShellCompleter::new();
+        c.mark_sent(1, "git");
+        let resp = CompletionResponse {
+            sequence_id: 1,
+            suggestions: vec![CompletionSuggestion {
+                text: "git status --short".to_string(),
+                confidence: 0.9,
+            }],
+        };
+        c.on_response(&resp, "git");
+        assert_eq!(c.ghost(), Some(" status --short"));
+
+        // The separating space is injected with the word so the remaining
+        // ghost lines up after it.
+        assert_eq!(c.accept_word(), Some(" status ".to_string()));
+        assert_eq!(c.ghost(), Some("--short"));
+        assert_eq!(c.ghost_input(), "git status ");
+
+        // Echo of the injected word must not clear the remaining ghost.
+        assert!(!c.on_input_changed("git status ", 2));
+        assert_eq!(c.ghost(), Some("--short"));
+
+        assert_eq!(c.accept_word(), Some("--short".to_string()));
+        assert!(c.ghost().is_none());
+        assert!(c.accept_word().is_none());
+    }
+
     #[test]
     fn test_ghost_expired_after_timeout() {
         let mut c = ShellCompleter::new();
@@ -1461,4 +1767,112 @@ mod tests {
         // Now should_request should return true
         assert!(c.should_request(25, "git s"), "Should allow new request when there's new input");
     }
+
+    #[test]
+    fn test_cached_completion_served_without_request() {
+        let mut c = ShellCompleter::new();
+        c.set_cwd(Some("/repo"));
+        c.on_input_changed("git sta", 5);
+        c.mark_sent(5, "git sta");
+        let resp = CompletionResponse {
+            sequence_id: 5,
+            suggestions: vec![CompletionSuggestion {
+                text: "git status".to_string(),
+                confidence: 0.9,
+            }],
+        };
+        assert_eq!(c.on_response(&resp, "git sta"), Some("tus"));
+
+        // User types on, then backspaces to the same input.
+        c.on_input_changed("git stax", 6);
+        c.on_input_changed("git sta", 7);
+        c.last_change = Some(Instant::now() - std::time::Duration::from_secs(1));
+        assert!(c.should_request(7, "git sta"));
+
+        let cached = c.cached_response(7, "git sta").expect("cache hit");
+        assert_eq!(cached.sequence_id, 7);
+        assert_eq!(c.on_response(&cached, "git sta"), Some("tus"));
+
+        // Different cwd is a miss.
+        c.set_cwd(Some("/tmp"));
+        assert!(c.cached_response(8, "git sta").is_none());
+    }
+
+    #[test]
+    fn test_path_completion_served_locally() {
+        let dir = tempfile::tempdir().unwrap();
+        std::fs::write(dir.path().join("sshd_config"), "").unwrap();
+        std::fs::write(dir.path().join("ssh_config"), "").unwrap();
+        let mut c = ShellCompleter::new();
+        c.set_cwd(dir.path().to_str());
+        c.on_input_changed("cat ./ss", 3);
+
+        let resp = c.path_response(3, "cat ./ss").expect("path match");
+        assert_eq!(c.on_response(&resp, "cat ./ss"), Some("h"));
+        assert!(c.path_response(4, "git sta").is_none());
+    }
+
+    #[test]
+    fn test_history_completion_served_locally() {
+        let mut c = ShellCompleter::new();
+        assert!(c.history_response(1, "git s").is_none());
+        c.seed_history(vec!["git status".to_string(), "git stash pop".to_string()]);
+        c.on_input_changed("git s", 2);
+
+        let resp = c.history_response(2, "git s").expect("history match");
+        assert_eq!(resp.suggestions[0].confidence, 1.0);
+        assert_eq!(c.on_response(&resp, "git s"), Some("tatus"));
+        // Marked as sent, so no LLM request for this input
+        assert!(!c.should_request(2, "git s"));
+
+        // A command run since moves to the top
+        c.note_command("git stash pop");
+        let resp = c.history_response(3, "git s").expect("history match");
+        assert_eq!(resp.suggestions[0].text, "git stash pop");
+    }
+
+    #[test]
+    fn test_cache_survives_clear() {
+        let mut c = ShellCompleter::new();
+        c.set_cwd(Some("/repo"));
+        c.cache.insert("ls", "/repo", "ls -la");
+        // clear() runs on every prompt, Enter and Tab
+        c.clear();
+        let cached = c.cached_response(1, "ls").expect("cache hit after clear");
+        assert_eq!(c.on_response(&cached, "ls"), Some(" -la"));
+    }
+
+    #[test]
+    fn test_cache_invalidated_on_cwd_change() {
+        let mut c = ShellCompleter::new();
+        c.set_cwd(Some("/repo"));
+        c.cache.insert("ls", "/repo", "ls -la");
+        // Same cwd again keeps the cache
+        c.set_cwd(Some("/repo"));
+        assert!(c.cache.get("ls", "/repo").is_some());
+
+        c.set_cwd(Some("/tmp"));
+        c.set_cwd(Some("/repo"));
+        assert!(c.cached_response(1, "ls").is_none());
+    }
+
+    #[test]
+    fn test_cache_evicts_oldest_after_limit() {
+        let mut cache = CompletionCache::new(Duration::from_secs(60));
+        for i in 0..=CACHE_MAX_ENTRIES {
+            cache.insert(&format!("cmd{i}"), "/", &format!("cmd{i} --flag"));
+        }
+        assert_eq!(cache.entries.len(), CACHE_MAX_ENTRIES);
+        assert!(cache.get("cmd0", "/").is_none());
+        assert_eq!(cache.get("cmd1", "/").as_deref(), Some("cmd1 --flag"));
+    }
+
+    #[test]
+    fn test_cache_expired_entry_is_miss() {
+        let mut cache = CompletionCache::new(Duration::from_millis(1));
+        cache.insert("ls", "/", "ls -la");
+        std::thread::sleep(std::time::Duration::from_millis(5));
+        assert!(cache.get("ls", "/").is_none());
+        assert_eq!(cache.entries.len(), 0);
+    }
 }
@@ -179,12 +179,27 @@ pub fn render_dismiss() -> String {
 
 /// Render the input echo line: moves cursor to column 0, prints ❯ followed by user text,
 /// then clears to end of line (to handle backspace correctly).
-#[cfg(test)]
+/// Embedded newlines (multi-line chat input) start a new row with the same prefix.
 pub fn render_input_echo(user_input: &[u8]) -> String {
-    format!(
-        "\r{CYAN}❯{RESET} {}\x1b[K",
-        String::from_utf8_lossy(user_input)
-    )
+    let text = String::from_utf8_lossy(user_input);
+    let lines: Vec<String> = text
+        .split('\n')
+        .map(|line| format!("\r{CYAN}❯{RESET} {}\x1b[K", line))
+        .collect();
+    lines.join("\n")
+}
+
+/// Re-render multi-line chat input (prefix stripped) after Enter added a
+/// continuation row, before the chat prompt has opened. The cursor is on
+/// the last row of the previous render, or on the shell prompt line when
+/// this is the first row break.
+pub fn render_chat_continuation(user_input: &[u8]) -> String {
+    let breaks = user_input.iter().filter(|&&b| b == b'\n').count();
+    let lead = match breaks {
+        0 | 1 => NEWLINE.to_string(),
+        n => format!("\x1b[{}A", n - 1),
+    };
+    format!("{lead}{}", render_input_echo(user_input))
 }
 
 /// Format an LLM response for raw-mode display.
@@ -194,6 +209,12 @@ pub fn render_response(content: &str) -> String {
     format!("{NEWLINE}{}{NEWLINE}", rendered)
 }
 
+/// Whether a response would scroll past a terminal of `rows` rows, leaving
+/// room for the prompt and separators.
+pub fn is_response_long(content: &str, rows: u16) -> bool {
+    content.lines().count() > rows.saturating_sub(4) as usize
+}
+
 /// Format an error message in red.
 pub fn render_error(msg: &str) -> String {
     format!("{NEWLINE}{RED}[omnish] {}{RESET}{NEWLINE}", msg)
@@ -707,6 +728,33 @@ mod tests {
 
     // --- Boundary tests ---
 
+    #[test]
+    fn test_render_input_echo_multiline() {
+        let cols: u16 = 40;
+        let output = render_input_echo(b"hello\nworld");
+        let parser = parse_ansi(&output, cols, 24);
+        let row0 = get_row(parser.screen(), 0, cols);
+        let row1 = get_row(parser.screen(), 1, cols);
+        assert!(row0.contains("❯ hello"), "first line: {row0:?}");
+        assert!(row1.contains("❯ world"), "second line: {row1:?}");
+    }
+
+    #[test]
+    fn test_render_chat_continuation() {
+        let cols: u16 = 40;
+        let mut output = String::from("$ ");
+        output.push_str(&render_chat_continuation(b"hello\n"));
+        output.push_str(&render_chat_continuation(b"hello\nworld\n"));
+        let parser = parse_ansi(&output, cols, 24);
+        assert_eq!(get_row(parser.screen(), 0, cols).trim_end(), "$");
+        assert!(get_row(parser.screen(), 1, cols).contains("❯ hello"));
+        assert!(get_row(parser.screen(), 2, cols).contains("❯ world"));
+        // The cursor waits on a fresh continuation row
+        assert_eq!(get_row(parser.screen(), 3, cols).trim_end(), "❯");
+        assert_eq!(parser.screen().cursor_position(), (3, 2));
+        assert!(get_row(parser.screen(), 4, cols).trim().is_empty());
+    }
+
     #[test]
     fn test_render_input_echo_empty() {
         let output = render_input_echo(b"");
@@ -948,4 +996,11 @@ mod tests {
         let all = parser.screen().contents();
         assert!(!all.contains("line"), "all lines should be erased: {all:?}");
     }
+
+    #[test]
+    fn test_is_response_long() {
+        assert!(!is_response_long("a\nb\nc", 24));
+        assert!(!is_response_long(&"x\n".repeat(20), 24));
+        assert!(is_response_long(&"x\n".repeat(21), 24));
+    }
 }
@@ -1,9 +1,17 @@
-/// Trait for completion data sources. Providers are queried in order; first match wins.
+use std::path::{Path, PathBuf};
+
+/// Trait for completion data sources. Providers are queried in rank order
+/// (lowest first, ties keep registration order); first match wins.
 pub trait CompletionProvider {
     /// Given current input text (after the `:` prefix), return a full-line suggestion.
     /// Returns None if no completion available.
     /// The suggestion MUST start with `input` as a prefix.
     fn suggest(&self, input: &str) -> Option<String>;
+
+    /// Priority of this provider. Lower ranks are consulted first.
+    fn rank(&self) -> u8 {
+        100
+    }
 }
 
 /// Completes omnish built-in `/` commands.
@@ -30,6 +38,516 @@ impl CompletionProvider for BuiltinProvider {
             .find(|cmd| cmd.starts_with(input) && cmd.len() > input.len())
             .cloned()
     }
+
+    fn rank(&self) -> u8 {
+        0
+    }
+}
+
+/// Common shell commands with their usual arguments, most used first.
+/// Backs `CommonCommandProvider`; the order is the rank.
+const COMMON_COMMANDS: &[(&str, &[&str])] = &[
+    ("git", &["status", "add .", "commit -m \"", "push", "pull", "log --oneline", "diff", "checkout -b ", "branch -a", "stash", "stash pop", "switch ", "rebase -i ", "fetch --all", "clone ", "reset --hard", "merge ", "remote -v", "show", "restore --staged "]),
+    ("ls", &["-la", "-lh", "-ltr", "-a", "-1"]),
+    ("cd", &["..", "-", "~"]),
+    ("grep", &["-rn ", "-ri ", "-v ", "-E ", "-l ", "-c "]),
+    ("cat", &[]),
+    ("vim", &[]),
+    ("sudo", &["!!", "-i", "-s"]),
+    ("docker", &["ps", "ps -a", "images", "compose up -d", "compose down", "compose logs -f", "run -it --rm ", "exec -it ", "logs -f ", "build -t ", "pull ", "stop ", "rm ", "rmi ", "system prune"]),
+    ("kubectl", &["get pods", "get pods -A", "get svc", "get deployments", "get nodes", "describe pod ", "logs -f ", "apply -f ", "delete -f ", "exec -it ", "config get-contexts", "config use-context ", "port-forward ", "rollout restart deployment/", "top pods"]),
+    ("cargo", &["build", "build --release", "test", "run", "check", "clippy", "fmt", "add ", "update", "doc --open", "clean", "install --path ."]),
+    ("npm", &["install", "run dev", "run build", "test", "start", "install -D ", "run lint", "ci", "outdated", "update", "publish"]),
+    ("make", &["-j$(nproc)", "clean", "install", "test", "all"]),
+    ("ssh", &["-i ", "-L ", "-p ", "-A "]),
+    ("find", &[". -name \"", ". -type f -name \"", ". -type d -name \"", ". -mtime -1", ". -size +100M", ". -empty -delete"]),
+    ("curl", &["-sSL ", "-I ", "-X POST ", "-o ", "-fsSL ", "-H \"Content-Type: application/json\" ", "-v "]),
+    ("tar", &["-xzf ", "-czf ", "-xf ", "-tf ", "-xjf ", "-cJf "]),
+    ("python3", &["-m venv .venv", "-m pip install ", "-m http.server", "-c \"", "-m pytest"]),
+    ("python", &["-m venv .venv", "-m pip install ", "-m http.server", "-c \""]),
+    ("pip", &["install ", "install -r requirements.txt", "install -e .", "list", "freeze > requirements.txt", "uninstall ", "show "]),
+    ("pip3", &["install ", "install -r requirements.txt", "list", "freeze"]),
+    ("systemctl", &["status ", "restart ", "start ", "stop ", "enable --now ", "disable ", "daemon-reload", "list-units --failed", "--user status "]),
+    ("journalctl", &["-u ", "-f", "-xe", "-b", "--since \"1 hour ago\""]),
+    ("rm", &["-rf ", "-r ", "-i "]),
+    ("cp", &["-r ", "-a ", "-v "]),
+    ("mv", &["-i ", "-v "]),
+    ("mkdir", &["-p "]),
+    ("chmod", &["+x ", "-R 755 ", "644 ", "600 "]),
+    ("chown", &["-R ", "-R $USER:$USER "]),
+    ("ln", &["-s ", "-sf "]),
+    ("touch", &[]),
+    ("echo", &["$PATH", "$?", "$SHELL"]),
+    ("export", &["PATH=$PATH:"]),
+    ("source", &["~/.bashrc", ".venv/bin/activate", "~/.zshrc"]),
+    ("history", &["| grep ", "| tail -20"]),
+    ("ps", &["aux", "aux | grep ", "-ef", "-ef | grep "]),
+    ("kill", &["-9 ", "-HUP ", "-l"]),
+    ("killall", &[]),
+    ("pkill", &["-f "]),
+    ("pgrep", &["-af ", "-f "]),
+    ("top", &["-o %CPU", "-u "]),
+    ("htop", &[]),
+    ("df", &["-h", "-hT", "-i"]),
+    ("du", &["-sh *", "-sh ", "-h --max-depth=1", "-ah "]),
+    ("free", &["-h", "-m"]),
+    ("uname", &["-a", "-r"]),
+    ("whoami", &[]),
+    ("which", &[]),
+    ("whereis", &[]),
+    ("man", &[]),
+    ("head", &["-n ", "-n 20 "]),
+    ("tail", &["-f ", "-n 100 ", "-F ", "-n +2 "]),
+    ("less", &["+F ", "-R ", "-S "]),
+    ("more", &[]),
+    ("wc", &["-l ", "-w ", "-c "]),
+    ("sort", &["-u", "-n", "-rn", "-k2 ", "-h"]),
+    ("uniq", &["-c", "-d", "-u"]),
+    ("cut", &["-d' ' -f1", "-d, -f", "-c1-"]),
+    ("tr", &["-d ", "'[:upper:]' '[:lower:]'", "-s ' '"]),
+    ("awk", &["'{print $1}'", "-F: '{print $1}'", "'{print $NF}'", "'NR==1'", "'{sum+=$1} END {print sum}'"]),
+    ("sed", &["-i 's/", "-n 'p'", "-e 's/", "-i '/^$/d' ", "-n '1,10p' "]),
+    ("xargs", &["-I{} ", "-0 ", "-n1 ", "-P4 ", "rm -f"]),
+    ("tee", &["-a "]),
+    ("diff", &["-u ", "-r ", "-y ", "--color "]),
+    ("patch", &["-p1 < "]),
+    ("wget", &["-c ", "-O ", "-qO- ", "-r -np ", "--no-check-certificate "]),
+    ("rsync", &["-avz ", "-avzP ", "-av --delete ", "-avz -e ssh ", "-an "]),
+    ("scp", &["-r ", "-P "]),
+    ("sftp", &[]),
+    ("gzip", &["-d ", "-k ", "-9 "]),
+    ("gunzip", &[]),
+    ("zip", &["-r "]),
+    ("unzip", &["-l ", "-d ", "-o "]),
+    ("bzip2", &["-d "]),
+    ("xz", &["-d ", "-k "]),
+    ("zcat", &[]),
+    ("7z", &["x ", "a ", "l "]),
+    ("apt", &["update", "upgrade", "install ", "install -y ", "remove ", "search ", "show ", "autoremove", "list --installed"]),
+    ("apt-get", &["update", "install -y ", "upgrade -y", "remove ", "purge ", "autoremove"]),
+    ("dpkg", &["-l", "-i ", "-L ", "-S "]),
+    ("yum", &["install -y ", "update", "remove ", "search ", "list installed", "info "]),
+    ("dnf", &["install -y ", "update", "remove ", "search ", "list installed", "info "]),
+    ("rpm", &["-qa", "-qi ", "-ql ", "-ivh "]),
+    ("pacman", &["-Syu", "-S ", "-Rns ", "-Ss ", "-Qi "]),
+    ("brew", &["install ", "update", "upgrade", "list", "search ", "info ", "uninstall ", "services list", "doctor", "cleanup"]),
+    ("snap", &["install ", "list", "refresh"]),
+    ("flatpak", &["install ", "list", "update", "run "]),
+    ("yarn", &["install", "add ", "add -D ", "dev", "build", "test", "start"]),
+    ("pnpm", &["install", "add ", "add -D ", "dev", "build", "test", "run "]),
+    ("npx", &[]),
+    ("node", &["-v", "-e \"", "--inspect "]),
+    ("deno", &["run ", "task ", "fmt", "test"]),
+    ("bun", &["install", "run ", "add ", "test"]),
+    ("go", &["build ./...", "test ./...", "run .", "mod tidy", "get ", "fmt ./...", "vet ./...", "install ", "env"]),
+    ("rustup", &["update", "default stable", "target add ", "component add ", "show"]),
+    ("rustc", &["--version"]),
+    ("gcc", &["-o ", "-Wall -O2 -o ", "-g -o "]),
+    ("g++", &["-std=c++17 -o ", "-Wall -O2 -o ", "-g -o "]),
+    ("clang", &["-o ", "-Wall -O2 -o "]),
+    ("cmake", &["-B build", "--build build", "-DCMAKE_BUILD_TYPE=Release ..", "..", "--install build"]),
+    ("ninja", &["-C build"]),
+    ("gdb", &["--args ", "-p "]),
+    ("lldb", &["-- ", "-p "]),
+    ("strace", &["-f ", "-p ", "-e trace=", "-c "]),
+    ("ltrace", &[]),
+    ("valgrind", &["--leak-check=full "]),
+    ("java", &["-jar ", "-version"]),
+    ("javac", &[]),
+    ("mvn", &["clean install", "package", "test", "clean package -DskipTests", "dependency:tree"]),
+    ("gradle", &["build", "test", "clean build"]),
+    ("./gradlew", &["build", "test", "clean build", "bootRun"]),
+    ("ruby", &["-v", "-e \""]),
+    ("gem", &["install ", "list", "update"]),
+    ("bundle", &["install", "exec ", "update"]),
+    ("rails", &["server", "console", "db:migrate", "generate "]),
+    ("php", &["-v", "-S localhost:8000", "artisan "]),
+    ("composer", &["install", "require ", "update", "dump-autoload"]),
+    ("perl", &["-pi -e 's/", "-ne 'print if /", "-e '"]),
+    ("lua", &[]),
+    ("ghci", &[]),
+    ("stack", &["build", "test", "run"]),
+    ("dotnet", &["build", "run", "test", "new ", "add package "]),
+    ("terraform", &["init", "plan", "apply", "destroy", "fmt", "validate", "output", "state list"]),
+    ("ansible", &["all -m ping", "-i inventory "]),
+    ("ansible-playbook", &["-i inventory ", "--check ", "-K "]),
+    ("helm", &["install ", "upgrade --install ", "list -A", "uninstall ", "repo update", "template ", "repo add "]),
+    ("minikube", &["start", "stop", "status", "dashboard", "delete"]),
+    ("kind", &["create cluster", "delete cluster", "get clusters"]),
+    ("k9s", &[]),
+    ("kubectx", &[]),
+    ("kubens", &[]),
+    ("podman", &["ps", "ps -a", "images", "run -it --rm ", "exec -it ", "logs -f ", "build -t "]),
+    ("docker-compose", &["up -d", "down", "logs -f", "ps", "build", "restart", "pull"]),
+    ("aws", &["s3 ls", "s3 cp ", "s3 sync ", "sts get-caller-identity", "configure", "ec2 describe-instances", "logs tail "]),
+    ("gcloud", &["auth login", "config list", "config set project ", "compute instances list", "container clusters get-credentials "]),
+    ("az", &["login", "account show", "account set --subscription ", "group list"]),
+    ("vagrant", &["up", "ssh", "halt", "destroy", "status"]),
+    ("ping", &["-c 4 ", "-c 3 8.8.8.8"]),
+    ("traceroute", &[]),
+    ("mtr", &[]),
+    ("dig", &["+short ", "-x ", "@8.8.8.8 "]),
+    ("nslookup", &[]),
+    ("host", &[]),
+    ("ip", &["a", "addr show", "route", "link", "-br a", "neigh"]),
+    ("ifconfig", &[]),
+    ("netstat", &["-tulpn", "-an", "-rn"]),
+    ("ss", &["-tulpn", "-tan", "-s"]),
+    ("lsof", &["-i :", "-p ", "+D "]),
+    ("nc", &["-zv ", "-l ", "-lvp "]),
+    ("telnet", &[]),
+    ("nmap", &["-sV ", "-p- ", "-sn "]),
+    ("iptables", &["-L -n -v", "-S", "-F"]),
+    ("ufw", &["status", "allow ", "enable", "deny "]),
+    ("firewall-cmd", &["--list-all", "--reload", "--add-port="]),
+    ("openssl", &["s_client -connect ", "rand -hex 32", "req -new -x509 ", "genrsa -out "]),
+    ("ssh-keygen", &["-t ed25519 -C \"", "-R ", "-lf "]),
+    ("ssh-copy-id", &["-i "]),
+    ("ssh-add", &["-l", "-D", "~/.ssh/"]),
+    ("gpg", &["--list-keys", "--list-secret-keys", "--import ", "--decrypt ", "--armor --export "]),
+    ("tmux", &["new -s ", "attach -t ", "ls", "kill-session -t ", "a"]),
+    ("screen", &["-S ", "-r ", "-ls"]),
+    ("watch", &["-n 1 ", "-n 2 ", "-d "]),
+    ("nohup", &[]),
+    ("time", &[]),
+    ("crontab", &["-e", "-l", "-r"]),
+    ("at", &["now + 1 hour"]),
+    ("date", &["+%Y-%m-%d", "+%s", "-u", "-d @"]),
+    ("cal", &[]),
+    ("uptime", &[]),
+    ("dmesg", &["-T", "-w", "| tail"]),
+    ("lsblk", &["-f"]),
+    ("blkid", &[]),
+    ("mount", &["-o loop ", "| column -t", "-a"]),
+    ("umount", &["-l "]),
+    ("fdisk", &["-l"]),
+    ("mkfs.ext4", &[]),
+    ("fsck", &[]),
+    ("lscpu", &[]),
+    ("lsusb", &[]),
+    ("lspci", &["-k"]),
+    ("nproc", &[]),
+    ("env", &["| grep ", "| sort"]),
+    ("printenv", &[]),
+    ("alias", &[]),
+    ("type", &["-a "]),
+    ("stat", &[]),
+    ("file", &[]),
+    ("tree", &["-L 2", "-a", "-d"]),
+    ("realpath", &[]),
+    ("basename", &[]),
+    ("dirname", &[]),
+    ("readlink", &["-f "]),
+    ("md5sum", &[]),
+    ("sha256sum", &["-c "]),
+    ("base64", &["-d", "-w0 "]),
+    ("xxd", &["-r ", "-p "]),
+    ("hexdump", &["-C "]),
+    ("strings", &[]),
+    ("od", &["-c "]),
+    ("jq", &["'.'", "-r '.", "'.[] | ", "-c '.", "'keys'"]),
+    ("yq", &["'.'", "-i '.", "e '."]),
+    ("column", &["-t", "-t -s,"]),
+    ("paste", &["-sd, ", "-d, "]),
+    ("join", &[]),
+    ("comm", &["-12 ", "-23 "]),
+    ("split", &["-l 1000 ", "-b 100M "]),
+    ("shuf", &["-n 1 "]),
+    ("seq", &["1 10"]),
+    ("yes", &[]),
+    ("bc", &["-l"]),
+    ("expr", &[]),
+    ("rg", &["-n ", "-i ", "-l ", "--hidden ", "-t ", "-g '"]),
+    ("fd", &["-e ", "-t f ", "-H "]),
+    ("fzf", &[]),
+    ("bat", &["-p ", "-l "]),
+    ("exa", &["-la", "--tree"]),
+    ("eza", &["-la", "--tree", "-l --git"]),
+    ("ag", &[]),
+    ("ack", &[]),
+    ("nano", &[]),
+    ("nvim", &[]),
+    ("emacs", &["-nw "]),
+    ("code", &[".", "-r "]),
+    ("open", &[".", "-a "]),
+    ("xdg-open", &["."]),
+    ("pbcopy", &[]),
+    ("pbpaste", &[]),
+    ("xclip", &["-selection clipboard"]),
+    ("psql", &["-h localhost -U ", "-U postgres", "-d ", "-c \""]),
+    ("mysql", &["-u root -p"]),
+    ("mysqldump", &["-u root -p "]),
+    ("pg_dump", &["-U postgres ", "-Fc "]),
+    ("redis-cli", &["ping", "-h ", "monitor", "info"]),
+    ("mongosh", &[]),
+    ("sqlite3", &[]),
+    ("gh", &["pr create", "pr list", "pr checkout ", "pr view --web", "repo clone ", "issue list", "run list", "auth login", "pr status"]),
+    ("glab", &["mr create", "mr list", "ci status"]),
+    ("svn", &["update", "status", "commit -m \"", "diff", "log"]),
+    ("hg", &["status", "pull -u", "commit -m \"", "log", "diff"]),
+    ("useradd", &["-m "]),
+    ("usermod", &["-aG "]),
+    ("passwd", &[]),
+    ("groups", &[]),
+    ("id", &[]),
+    ("su", &["-", "- "]),
+    ("chgrp", &["-R "]),
+    ("umask", &[]),
+    ("shutdown", &["-h now", "-r now"]),
+    ("reboot", &[]),
+    ("hostnamectl", &["set-hostname "]),
+    ("timedatectl", &["set-timezone "]),
+    ("localectl", &[]),
+    ("nmcli", &["device wifi list", "connection show", "device status"]),
+    ("ffmpeg", &["-i "]),
+    ("convert", &[]),
+    ("magick", &[]),
+    ("youtube-dl", &[]),
+    ("yt-dlp", &["-x ", "-f best "]),
+    ("pandoc", &["-o "]),
+    ("pytest", &["-x", "-v", "-k ", "-s", "--lf"]),
+    ("black", &["."]),
+    ("ruff", &["check .", "format ."]),
+    ("mypy", &["."]),
+    ("flake8", &[]),
+    ("poetry", &["install", "add ", "run ", "shell", "update", "lock"]),
+    ("uv", &["pip install ", "venv", "run ", "sync", "add "]),
+    ("conda", &["activate ", "deactivate", "env list", "create -n ", "install "]),
+    ("virtualenv", &[]),
+    ("jupyter", &["notebook", "lab"]),
+    ("ipython", &[]),
+    ("sh", &["-c \""]),
+    ("bash", &["-c \"", "-x ", "-n "]),
+    ("zsh", &[]),
+    ("exec", &["$SHELL", "bash", "zsh"]),
+    ("exit", &[]),
+    ("clear", &[]),
+    ("reset", &[]),
+    ("pwd", &[]),
+    ("pushd", &[]),
+    ("popd", &[]),
+    ("dirs", &["-v"]),
+    ("jobs", &["-l"]),
+    ("fg", &[]),
+    ("bg", &[]),
+    ("disown", &[]),
+    ("wait", &[]),
+    ("sleep", &[]),
+    ("true", &[]),
+    ("test", &["-f ", "-d ", "-z "]),
+    ("printf", &["'%s\\n' "]),
+    ("read", &["-p "]),
+    ("unset", &[]),
+    ("set", &["-e", "-x", "-o vi"]),
+    ("trap", &[]),
+    ("ulimit", &["-a", "-n "]),
+];
+
+/// Completes shell command names and their common arguments from a fixed
+/// table (`COMMON_COMMANDS`). Knows nothing about the user, so it is only
+/// a fallback for when no LLM completion can be had.
+pub struct CommonCommandProvider;
+
+impl CompletionProvider for CommonCommandProvider {
+    fn suggest(&self, input: &str) -> Option<String> {
+        let trimmed = input.trim_start();
+        if trimmed.is_empty() {
+            return None;
+        }
+        let Some((name, rest)) = trimmed.split_once(' ') else {
+            // Still typing the command name
+            return COMMON_COMMANDS
+                .iter()
+                .find(|(name, _)| name.starts_with(trimmed) && name.len() > trimmed.len())
+                .map(|(name, _)| format!("{}{}", input, &name[trimmed.len()..]));
+        };
+        let (_, args) = COMMON_COMMANDS.iter().find(|(n, _)| *n == name)?;
+        let partial = rest.trim_start();
+        args.iter()
+            .find(|arg| arg.starts_with(partial) && arg.len() > partial.len())
+            .map(|arg| format!("{}{}", input, &arg[partial.len()..]))
+    }
+
+    fn rank(&self) -> u8 {
+        200
+    }
+}
+
+/// Completes from command lines previously run in the shell.
+pub struct HistoryProvider {
+    /// Command lines, most recent first.
+    commands: Vec<String>,
+}
+
+impl HistoryProvider {
+    /// `commands` is expected most recent first; duplicates are dropped.
+    pub fn new(commands: Vec<String>) -> Self {
+        let mut seen = std::collections::HashSet::new();
+        let commands = commands
+            .into_iter()
+            .filter(|c| !c.trim().is_empty() && seen.insert(c.clone()))
+            .collect();
+        Self { commands }
+    }
+
+    /// Append `commands` (most recent first) below the known ones.
+    pub fn extend_older(&mut self, commands: Vec<String>) {
+        for command in commands {
+            if !command.trim().is_empty() && !self.commands.contains(&command) {
+                self.commands.push(command);
+            }
+        }
+    }
+
+    /// Record `command` as the most recently run one.
+    pub fn push_recent(&mut self, command: &str) {
+        let command = command.trim();
+        if command.is_empty() {
+            return;
+        }
+        self.commands.retain(|c| c != command);
+        self.commands.insert(0, command.to_string());
+    }
+}
+
+impl CompletionProvider for HistoryProvider {
+    fn suggest(&self, input: &str) -> Option<String> {
+        if input.trim().is_empty() {
+            return None;
+        }
+        self.commands
+            .iter()
+            .find(|cmd| cmd.starts_with(input) && cmd.len() > input.len())
+            .cloned()
+    }
+
+    fn rank(&self) -> u8 {
+        10
+    }
+}
+
+/// Completes the last whitespace-separated word when it looks like a path
+/// (contains `/`), listing entries relative to `base`.
+pub struct PathProvider {
+    base: PathBuf,
+}
+
+impl PathProvider {
+    pub fn new(base: impl Into<PathBuf>) -> Self {
+        Self { base: base.into() }
+    }
+}
+
+/// Entries completing the partial path at the end of `input`, sorted by
+/// name, with whether each is a directory. Returns the partial file name
+/// too. None if the last word has no `/` or its directory can't be read.
+fn path_matches(base: &Path, input: &str) -> Option<(String, Vec<(String, bool)>)> {
+    let word_start = input.rfind(char::is_whitespace).map(|i| i + 1).unwrap_or(0);
+    let word = &input[word_start..];
+    let slash = word.rfind('/')?;
+    let (dir_part, partial) = (&word[..=slash], &word[slash + 1..]);
+
+    let dir = if let Some(rest) = dir_part.strip_prefix("~/") {
+        std::env::var_os("HOME").map(PathBuf::from)?.join(rest)
+    } else {
+        base.join(dir_part)
+    };
+
+    let mut names: Vec<(String, bool)> = std::fs::read_dir(&dir)
+        .ok()?
+        .filter_map(|e| e.ok())
+        .filter_map(|e| {
+            let name = e.file_name().into_string().ok()?;
+            let is_dir = e.file_type().map(|t| t.is_dir()).unwrap_or(false);
+            Some((name, is_dir))
+        })
+        .filter(|(name, _)| {
+            name.starts_with(partial)
+                && name.len() > partial.len()
+                && (partial.starts_with('.') || !name.starts_with('.'))
+        })
+        .collect();
+    names.sort();
+    Some((partial.to_string(), names))
+}
+
+impl CompletionProvider for PathProvider {
+    fn suggest(&self, input: &str) -> Option<String> {
+        let (partial, names) = path_matches(&self.base, input)?;
+        let (name, is_dir) = names.into_iter().next()?;
+        let mut suggestion = format!("{}{}", input, &name[partial.len()..]);
+        if is_dir {
+            suggestion.push('/');
+        }
+        Some(suggestion)
+    }
+
+    fn rank(&self) -> u8 {
+        20
+    }
+}
+
+/// Words that start shell syntax rather than a command taking paths.
+const SHELL_KEYWORDS: &[&str] = &[
+    "if", "then", "else", "elif", "fi", "for", "while", "until", "do", "done",
+    "case", "esac", "select", "function", "time", "!", "[[", "{",
+];
+
+/// Completes a partial path at the end of a shell command line to the
+/// longest prefix shared by all matching entries, like bash's first Tab.
+/// Used ahead of LLM completion since the answer is exact and local.
+pub struct PathCompletionProvider {
+    base: PathBuf,
+}
+
+impl PathCompletionProvider {
+    pub fn new(base: impl Into<PathBuf>) -> Self {
+        Self { base: base.into() }
+    }
+
+    /// Whether `input` ends in a path worth completing locally: the last
+    /// word contains `/` and the line does not start with a shell keyword.
+    pub fn looks_like_path(input: &str) -> bool {
+        let last_word = input.rsplit(char::is_whitespace).next().unwrap_or("");
+        let first_word = input.split_whitespace().next().unwrap_or("");
+        last_word.contains('/') && !SHELL_KEYWORDS.contains(&first_word)
+    }
+}
+
+impl CompletionProvider for PathCompletionProvider {
+    fn suggest(&self, input: &str) -> Option<String> {
+        if !Self::looks_like_path(input) {
+            return None;
+        }
+        let (partial, names) = path_matches(&self.base, input)?;
+        let (first, first_is_dir) = names.first()?;
+        let common = names[1..]
+            .iter()
+            .fold(first.as_str(), |acc, (name, _)| common_prefix(acc, name));
+        if common.len() <= partial.len() {
+            return None;
+        }
+        let mut suggestion = format!("{}{}", input, &common[partial.len()..]);
+        if names.len() == 1 && *first_is_dir {
+            suggestion.push('/');
+        }
+        Some(suggestion)
+    }
+
+    fn rank(&self) -> u8 {
+        5
+    }
+}
+
+/// Longest common prefix of `a` and `b`, on a char boundary.
+fn common_prefix<'a>(a: &'a str, b: &str) -> &'a str {
+    let end = a
+        .char_indices()
+        .zip(b.chars())
+        .find(|((_, x), y)| x != y)
+        .map(|((i, _), _)| i)
+        .unwrap_or_else(|| a.len().min(b.len()));
+    &a[..end]
 }
 
 /// Manages ghost text completion state.
@@ -42,7 +560,8 @@ pub struct GhostCompleter {
 }
 
 impl GhostCompleter {
-    pub fn new(providers: Vec<Box<dyn CompletionProvider>>) -> Self {
+    pub fn new(mut providers: Vec<Box<dyn CompletionProvider>>) -> Self {
+        providers.sort_by_key(|p| p.rank());
         Self {
             providers,
             current_suggestion: None,
@@ -50,6 +569,12 @@ impl GhostCompleter {
         }
     }
 
+    /// Register an additional provider, keeping providers in rank order.
+    pub fn add_provider(&mut self, provider: Box<dyn CompletionProvider>) {
+        self.providers.push(provider);
+        self.providers.sort_by_key(|p| p.rank());
+    }
+
     /// Update with new input. Returns the ghost suffix to display, or None.
     pub fn update(&mut self, input: &str) -> Option<&str> {
         self.current_suggestion = None;
@@ -174,7 +699,11 @@ mod tests {
                     None
                 }
             }
+            fn rank(&self) -> u8 {
+                0
+            }
         }
+        // Equal ranks keep registration order
         let providers: Vec<Box<dyn CompletionProvider>> = vec![
             Box::new(AlwaysHello),
             Box::new(BuiltinProvider::new()),
@@ -182,4 +711,151 @@ mod tests {
         let mut c = GhostCompleter::new(providers);
         assert_eq!(c.update("/deb"), Some("hello"));
     }
+
+    #[test]
+    fn test_common_command_provider_completions() {
+        let p = CommonCommandProvider;
+        let cases = [
+            ("git s", "git status"),
+            ("git stash p", "git stash pop"),
+            ("tar -x", "tar -xzf "),
+            ("docker compose u", "docker compose up -d"),
+            ("kubectl get p", "kubectl get pods"),
+            ("cargo b", "cargo build"),
+            ("ls -l", "ls -la"),
+            ("systemctl dae", "systemctl daemon-reload"),
+            ("journalctl -", "journalctl -u "),
+            ("rsync -", "rsync -avz "),
+            ("du -", "du -sh *"),
+            ("ps a", "ps aux"),
+            ("awk ", "awk '{print $1}'"),
+            ("kub", "kubectl"),
+            ("journ", "journalctl"),
+        ];
+        for (input, expected) in cases {
+            assert_eq!(p.suggest(input).as_deref(), Some(expected), "input {:?}", input);
+        }
+        // Leading space is kept so the suggestion still extends the input
+        assert_eq!(p.suggest(" git pu").as_deref(), Some(" git push"));
+        assert_eq!(p.suggest("unknowncmd -"), None);
+        assert_eq!(p.suggest("git status"), None);
+        assert_eq!(p.suggest(""), None);
+    }
+
+    #[test]
+    fn test_common_commands_table_is_well_formed() {
+        assert!(COMMON_COMMANDS.len() >= 200);
+        let mut seen = std::collections::HashSet::new();
+        for (name, args) in COMMON_COMMANDS {
+            assert!(seen.insert(name), "duplicate entry {}", name);
+            assert!(!name.contains(' '), "{}", name);
+            assert!(args.iter().all(|a| !a.is_empty()), "{}", name);
+        }
+    }
+
+    #[test]
+    fn test_history_provider_prefix_match() {
+        let providers: Vec<Box<dyn CompletionProvider>> = vec![Box::new(HistoryProvider::new(
+            vec!["git status".to_string(), "git commit".to_string()],
+        ))];
+        let mut c = GhostCompleter::new(providers);
+        assert_eq!(c.update("git s"), Some("tatus"));
+        assert_eq!(c.update("git c"), Some("ommit"));
+        assert_eq!(c.update("git p"), None);
+    }
+
+    #[test]
+    fn test_history_provider_prefers_most_recent() {
+        let p = HistoryProvider::new(vec![
+            "cargo test".to_string(),
+            "cargo build".to_string(),
+            "cargo test".to_string(),
+        ]);
+        assert_eq!(p.suggest("cargo "), Some("cargo test".to_string()));
+        assert_eq!(p.suggest(""), None);
+    }
+
+    #[test]
+    fn test_history_provider_push_recent() {
+        let mut p = HistoryProvider::new(vec!["git status".to_string(), "git stash pop".to_string()]);
+        assert_eq!(p.suggest("git s"), Some("git status".to_string()));
+        p.push_recent("git stash pop");
+        assert_eq!(p.suggest("git s"), Some("git stash pop".to_string()));
+        p.push_recent("  ");
+        assert_eq!(p.suggest("git st"), Some("git stash pop".to_string()));
+        p.extend_older(vec!["git show HEAD".to_string(), "git status".to_string()]);
+        assert_eq!(p.suggest("git sh"), Some("git show HEAD".to_string()));
+        assert_eq!(p.suggest("git s"), Some("git stash pop".to_string()));
+    }
+
+    #[test]
+    fn test_path_provider_completes_entries() {
+        let dir = tempfile::tempdir().unwrap();
+        std::fs::create_dir(dir.path().join("src")).unwrap();
+        std::fs::write(dir.path().join("src/main.rs"), "").unwrap();
+        std::fs::write(dir.path().join(".hidden"), "").unwrap();
+        let p = PathProvider::new(dir.path());
+        assert_eq!(p.suggest("cat ./s"), Some("cat ./src/".to_string()));
+        assert_eq!(p.suggest("cat ./src/ma"), Some("cat ./src/main.rs".to_string()));
+        assert_eq!(p.suggest("cat ./."), Some("cat ./.hidden".to_string()));
+        // No slash in the word: not treated as a path
+        assert_eq!(p.suggest("cat s"), None);
+    }
+
+    #[test]
+    fn test_path_completion_provider_common_prefix() {
+        let dir = tempfile::tempdir().unwrap();
+        let ssh = dir.path().join("ssh");
+        std::fs::create_dir(&ssh).unwrap();
+        std::fs::write(ssh.join("sshd_config"), "").unwrap();
+        std::fs::write(ssh.join("ssh_config"), "").unwrap();
+        std::fs::write(ssh.join("moduli"), "").unwrap();
+        let base = dir.path().display();
+        let p = PathCompletionProvider::new("/");
+
+        let mut c = GhostCompleter::new(vec![Box::new(PathCompletionProvider::new("/"))]);
+        // sshd_config / ssh_config share "ssh"
+        assert_eq!(c.update(&format!("cat {base}/ssh/s")), Some("sh"));
+        assert_eq!(c.update(&format!("cat {base}/ssh/ssh_")), Some("config"));
+        assert_eq!(c.update(&format!("cat {base}/ssh/m")), Some("oduli"));
+        // Nothing to add beyond what is typed
+        assert_eq!(p.suggest(&format!("cat {base}/ssh/ssh")), None);
+        // A single directory match gets its trailing slash
+        assert_eq!(
+            p.suggest(&format!("ls {base}/s")),
+            Some(format!("ls {base}/ssh/"))
+        );
+    }
+
+    #[test]
+    fn test_path_completion_provider_skips_non_paths() {
+        assert!(PathCompletionProvider::looks_like_path("cat /etc/ssh/s"));
+        assert!(!PathCompletionProvider::looks_like_path("cat sshd"));
+        assert!(!PathCompletionProvider::looks_like_path("for f in /tmp/a"));
+        assert_eq!(PathCompletionProvider::new("/").suggest("if [ -f /et"), None);
+    }
+
+    #[test]
+    fn test_path_completion_ranks_ahead_of_path_provider() {
+        let dir = tempfile::tempdir().unwrap();
+        std::fs::write(dir.path().join("sshd_config"), "").unwrap();
+        std::fs::write(dir.path().join("ssh_config"), "").unwrap();
+        let providers: Vec<Box<dyn CompletionProvider>> = vec![
+            Box::new(PathProvider::new(dir.path())),
+            Box::new(PathCompletionProvider::new(dir.path())),
+        ];
+        let mut c = GhostCompleter::new(providers);
+        assert_eq!(c.update("cat ./s"), Some("sh"));
+    }
+
+    #[test]
+    fn test_completer_consults_providers_by_rank() {
+        let providers: Vec<Box<dyn CompletionProvider>> = vec![
+            Box::new(HistoryProvider::new(vec!["/debugging".to_string()])),
+            Box::new(BuiltinProvider::new()),
+        ];
+        let mut c = GhostCompleter::new(providers);
+        // BuiltinProvider ranks ahead of history despite being registered later
+        assert_eq!(c.update("/deb"), Some("ug"));
+    }
 }
@@ -156,11 +156,12 @@ pub enum InterceptAction {
     Buffering(Vec<u8>),
     /// Forward these bytes to PTY
     Forward(Vec<u8>),
-    /// Chat mode message completed (user pressed Enter after prefix)
-    Chat(String),
+    /// Chat mode message completed (user pressed Enter after prefix).
+    /// `prefix_index` says which configured prefix started it (0 = primary).
+    Chat { message: String, prefix_index: usize },
     /// Resume last chat session (prefix typed twice, e.g. "::")
     ResumeChat,
-    /// Backspace in buffering mode - erased one char
+    /// Backspace in buffering mode - erased one char (or one word for Ctrl+W)
     /// Contains updated buffer for echo display
     Backspace(Vec<u8>),
     /// User pressed ESC to cancel chat mode
@@ -170,6 +171,9 @@ pub enum InterceptAction {
     /// Tab pressed while in chat mode. Contains current buffer.
     /// Caller should check GhostCompleter for completion to accept.
     Tab(Vec<u8>),
+    /// Single Enter in multi-line chat input: a newline was appended and the
+    /// message continues. Contains current buffer for echo display.
+    NewlineInChat(Vec<u8>),
 }
 
 /// Strategy for deciding whether to start intercepting at the current moment.
@@ -236,6 +240,13 @@ fn utf8_char_len(first: u8) -> usize {
     else { 4 }
 }
 
+/// Length of `s` once the last word is removed readline-style (Ctrl+W):
+/// trailing whitespace first, then the run of non-whitespace before it.
+fn trim_last_word(s: &str) -> usize {
+    let without_spaces = s.trim_end_matches(char::is_whitespace);
+    without_spaces.trim_end_matches(|c: char| !c.is_whitespace()).len()
+}
+
 /// Check if a byte buffer ends with an incomplete UTF-8 character.
 /// Returns true if the trailing bytes form a partial multi-byte sequence.
 fn has_incomplete_utf8_tail(buf: &[u8]) -> bool {
@@ -265,38 +276,84 @@ fn has_incomplete_utf8_tail(buf: &[u8]) -> bool {
 }
 
 pub struct InputInterceptor {
-    prefix: Vec<u8>,
+    /// Chat prefixes, tried in order. Index 0 is the primary `command_prefix`.
+    prefixes: Vec<Vec<u8>>,
+    /// Index into `prefixes` of the prefix being matched or in chat.
+    active: usize,
     resume_prefix: Vec<u8>,
     buffer: VecDeque<u8>,
     in_chat: bool,
     /// When true, all input is forwarded directly (e.g. inside vim/less)
     suppressed: bool,
-    guard: Box<dyn InterceptGuard>,
+    /// One guard per prefix, paired by index.
+    guards: Vec<Box<dyn InterceptGuard>>,
     /// Active ESC sequence filter (only while in chat/buffering mode).
     esc_filter: Option<EscSeqFilter>,
     /// When false (default), : and :: only trigger chat on empty command line; when true, allows chat even with existing content
     developer_mode: bool,
     /// Tracks whether command line already has content (based on forwarded input since last shell output)
     command_line_has_content: bool,
+    /// Sticky: this client runs inside another omnish session, so the prefix
+    /// is always forwarded and the outer interceptor handles it.
+    suppress_if_nested: bool,
+    /// When true, a single Enter in chat mode inserts a newline and a second
+    /// consecutive Enter sends the message.
+    multiline: bool,
 }
 
 impl InputInterceptor {
+    /// Single-prefix convenience wrapper around `with_prefixes`.
+    #[cfg(test)]
     pub fn new(prefix: &str, resume_prefix: &str, guard: Box<dyn InterceptGuard>, developer_mode: bool) -> Self {
+        Self::with_prefixes(vec![prefix.to_string()], resume_prefix, vec![guard], developer_mode)
+    }
+
+    /// Intercept several prefixes, each with its own guard (paired by index).
+    /// `Chat` actions report which prefix matched via `prefix_index`.
+    pub fn with_prefixes(
+        prefixes: Vec<String>,
+        resume_prefix: &str,
+        guards: Vec<Box<dyn InterceptGuard>>,
+        developer_mode: bool,
+    ) -> Self {
+        assert_eq!(prefixes.len(), guards.len(), "one guard per prefix");
         Self {
-            prefix: prefix.as_bytes().to_vec(),
+            prefixes: prefixes.into_iter().map(String::into_bytes).collect(),
+            active: 0,
             resume_prefix: resume_prefix.as_bytes().to_vec(),
             buffer: VecDeque::new(),
             in_chat: false,
             suppressed: false,
-            guard,
+            guards,
             esc_filter: None,
             developer_mode,
             command_line_has_content: false,
+            suppress_if_nested: false,
+            multiline: false,
         }
     }
 
+    pub fn set_multiline(&mut self, multiline: bool) {
+        self.multiline = multiline;
+    }
+
+    /// Replace the primary prefix (index 0).
     pub fn update_prefix(&mut self, prefix: &str) {
-        self.prefix = prefix.as_bytes().to_vec();
+        self.prefixes[0] = prefix.as_bytes().to_vec();
+    }
+
+    /// The prefix currently being matched, or that started the current chat.
+    pub fn active_prefix(&self) -> &[u8] {
+        &self.prefixes[self.active]
+    }
+
+    /// First prefix (in order) that starts with the buffered bytes and whose
+    /// guard allows interception.
+    fn matching_prefix(&self, buf: &[u8]) -> Option<usize> {
+        self.prefixes
+            .iter()
+            .zip(&self.guards)
+            .position(|(p, g)| p.starts_with(buf) && g.should_intercept())
     }
 
     pub fn update_resume_prefix(&mut self, prefix: &str) {
@@ -308,7 +365,15 @@ impl InputInterceptor {
     }
 
     pub fn update_min_gap(&mut self, gap: std::time::Duration) {
-        self.guard.update_min_gap(gap);
+        for guard in &mut self.guards {
+            guard.update_min_gap(gap);
+        }
+    }
+
+    /// Set at startup when `OMNISH_SESSION_ID` shows a parent omnish session.
+    /// Unlike `set_suppressed`, this is never toggled off by alt-screen changes.
+    pub fn set_suppress_if_nested(&mut self, nested: bool) {
+        self.suppress_if_nested = nested;
     }
 
     /// Set suppression state (e.g. when alternate screen is active)
@@ -464,7 +529,7 @@ impl InputInterceptor {
         // Handle backspace/delete
         if byte == 0x7f || byte == 0x08 {
             // If we're buffering or in chat mode, handle backspace
-            if !self.buffer.is_empty() && (self.in_chat || self.buffer.len() <= self.prefix.len()) {
+            if !self.buffer.is_empty() && (self.in_chat || self.buffer.len() <= self.active_prefix().len()) {
                 // Delete one UTF-8 character (may be multiple bytes)
                 // Work backwards to find the start of the last character
                 let buf_vec: Vec<u8> = self.buffer.iter().copied().collect();
@@ -478,7 +543,7 @@ impl InputInterceptor {
                 }
 
                 // Check if we dropped out of chat mode
-                if self.in_chat && self.buffer.len() < self.prefix.len() {
+                if self.in_chat && self.buffer.len() < self.active_prefix().len() {
                     self.in_chat = false;
                 }
 
@@ -490,6 +555,20 @@ impl InputInterceptor {
             }
         }
 
+        // Handle Ctrl+W (delete previous word) in chat mode. The prefix
+        // itself is never erased.
+        if byte == 0x17 && self.in_chat {
+            let buf_vec: Vec<u8> = self.buffer.iter().copied().collect();
+            let as_str = String::from_utf8_lossy(&buf_vec).into_owned();
+            let prefix_len = self.active_prefix().len().min(as_str.len());
+            if as_str.is_char_boundary(prefix_len) {
+                let keep = prefix_len + trim_last_word(&as_str[prefix_len..]);
+                self.buffer = as_str[..keep].bytes().collect();
+            }
+            let current_buf: Vec<u8> = self.buffer.iter().copied().collect();
+            return InterceptAction::Backspace(current_buf);
+        }
+
         self.buffer.push_back(byte);
 
         // Check for Enter/newline
@@ -497,77 +576,80 @@ impl InputInterceptor {
             return self.handle_enter();
         }
 
-        // Check if buffer matches prefix so far
-        if !self.in_chat && self.buffer.len() <= self.prefix.len() {
-            if self.buffer.iter().copied().collect::<Vec<_>>() == self.prefix[..self.buffer.len()] {
-                // On first prefix byte, check guard
-                if self.buffer.len() == 1 && (!self.guard.should_intercept()
+        // Check if buffer matches a prefix so far
+        if !self.in_chat {
+            let buf: Vec<u8> = self.buffer.iter().copied().collect();
+            if let Some(index) = self.matching_prefix(&buf) {
+                self.active = index;
+                // On first prefix byte, check the remaining intercept conditions
+                if self.buffer.len() == 1 && (self.suppress_if_nested
                     || (!self.developer_mode && self.command_line_has_content))
                 {
-                    let flushed: Vec<u8> = self.buffer.iter().copied().collect();
                     self.buffer.clear();
-                    return self.forward(flushed);
+                    return self.forward(buf);
                 }
 
-                // Still matching prefix
-                if self.buffer.len() == self.prefix.len() {
+                if self.buffer.len() == self.prefixes[index].len() {
                     // Complete prefix match - transition to chat buffering.
                     // Don't return Chat yet; wait for next byte to detect
                     // double-prefix (e.g. "::") for resume, or timeout for new chat.
                     self.in_chat = true;
-                    let current_buf: Vec<u8> = self.buffer.iter().copied().collect();
-                    return InterceptAction::Buffering(current_buf);
                 }
                 // Keep buffering, don't send to PTY yet, return buffer for echo
-                let current_buf: Vec<u8> = self.buffer.iter().copied().collect();
-                return InterceptAction::Buffering(current_buf);
+                return InterceptAction::Buffering(buf);
             } else {
                 // Prefix mismatch, flush buffer to PTY
-                let flushed: Vec<u8> = self.buffer.iter().copied().collect();
-                if has_incomplete_utf8_tail(&flushed) {
+                if has_incomplete_utf8_tail(&buf) {
                     // Buffer ends with an incomplete multi-byte UTF-8 char;
                     // wait for remaining continuation bytes before flushing.
                     return InterceptAction::Pending;
                 }
                 self.buffer.clear();
-                return self.forward(flushed);
+                return self.forward(buf);
             }
         }
 
-        // In chat mode, keep buffering and return for echo
-        if self.in_chat {
-            // Detect resume prefix (e.g. "::" by default) for resume
-            if self.buffer.len() == self.resume_prefix.len() {
-                let buf: Vec<u8> = self.buffer.iter().copied().collect();
-                if buf == self.resume_prefix {
-                    self.buffer.clear();
-                    self.in_chat = false;
-                    return InterceptAction::ResumeChat;
-                }
+        // In chat mode, keep buffering and return for echo.
+        // Detect resume prefix (e.g. "::" by default) for resume
+        if self.buffer.len() == self.resume_prefix.len() {
+            let buf: Vec<u8> = self.buffer.iter().copied().collect();
+            if buf == self.resume_prefix {
+                self.buffer.clear();
+                self.in_chat = false;
+                return InterceptAction::ResumeChat;
             }
-            let current_buf: Vec<u8> = self.buffer.iter().copied().collect();
-            return InterceptAction::Buffering(current_buf);
         }
-
-        // Not in chat mode and buffer exceeded prefix length - flush and reset
-        let flushed: Vec<u8> = self.buffer.iter().copied().collect();
-        if has_incomplete_utf8_tail(&flushed) {
-            // Buffer ends with an incomplete multi-byte UTF-8 char;
-            // wait for remaining continuation bytes before flushing.
-            return InterceptAction::Pending;
-        }
-        self.buffer.clear();
-        self.forward(flushed)
+        let current_buf: Vec<u8> = self.buffer.iter().copied().collect();
+        InterceptAction::Buffering(current_buf)
     }
 
-    /// Forward bytes and record input activity for the guard.
+    /// Forward bytes and record input activity for the guards.
     fn forward(&mut self, bytes: Vec<u8>) -> InterceptAction {
-        self.guard.note_input();
+        for guard in &mut self.guards {
+            guard.note_input();
+        }
         self.command_line_has_content = true;
         InterceptAction::Forward(bytes)
     }
 
     fn handle_enter(&mut self) -> InterceptAction {
+        if self.in_chat && self.multiline {
+            // Buffer holds prefix + content + this Enter. A newline right
+            // before it means this is the second Enter in a row: drop it and
+            // send. Otherwise keep the newline and keep buffering.
+            let len = self.buffer.len();
+            let has_content = len > self.active_prefix().len() + 1;
+            let double_enter = len >= 2 && self.buffer[len - 2] == b'\n';
+            if has_content && !double_enter {
+                self.buffer[len - 1] = b'\n';
+                let current_buf: Vec<u8> = self.buffer.iter().copied().collect();
+                return InterceptAction::NewlineInChat(current_buf);
+            }
+            if double_enter {
+                self.buffer.pop_back();
+            }
+        }
+
         let buffered: Vec<u8> = self.buffer.iter().copied().collect();
         self.buffer.clear();
 
@@ -583,17 +665,18 @@ impl InputInterceptor {
         self.in_chat = false;
 
         // Content after prefix, excluding trailing newline
-        let content_start = self.prefix.len();
+        let prefix_index = self.active;
+        let content_start = self.active_prefix().len();
         let content_end = buffered.len().saturating_sub(1); // exclude \n or \r
         if content_start < content_end {
             let cmd_bytes = &buffered[content_start..content_end];
             if let Ok(cmd_str) = std::str::from_utf8(cmd_bytes) {
-                return InterceptAction::Chat(cmd_str.to_string());
+                return InterceptAction::Chat { message: cmd_str.to_string(), prefix_index };
             }
         }
 
         // Just prefix + Enter → new chat (empty message)
-        InterceptAction::Chat(String::new())
+        InterceptAction::Chat { message: String::new(), prefix_index }
     }
 
     /// Called by the main loop when the prefix-match timeout expires.
@@ -602,10 +685,10 @@ impl InputInterceptor {
     pub fn expire_prefix(&mut self) -> Option<InterceptAction> {
         if self.in_chat {
             let buf: Vec<u8> = self.buffer.iter().copied().collect();
-            if buf == self.prefix {
+            if buf == self.active_prefix() {
                 self.buffer.clear();
                 self.in_chat = false;
-                return Some(InterceptAction::Chat(String::new()));
+                return Some(InterceptAction::Chat { message: String::new(), prefix_index: self.active });
             }
         }
         None
@@ -651,6 +734,78 @@ mod tests {
         assert_eq!(interceptor.feed_byte(b'\n'), InterceptAction::Forward(vec![b'\n']));
     }
 
+    fn two_prefix_interceptor() -> InputInterceptor {
+        InputInterceptor::with_prefixes(
+            vec!["::".into(), "!".into()],
+            "::::",
+            vec![Box::new(AlwaysIntercept), Box::new(AlwaysIntercept)],
+            false,
+        )
+    }
+
+    fn feed_all(ic: &mut InputInterceptor, bytes: &[u8]) -> InterceptAction {
+        let mut last = InterceptAction::Pending;
+        for &b in bytes {
+            last = ic.feed_byte(b);
+        }
+        last
+    }
+
+    #[test]
+    fn test_multi_prefix_reports_prefix_index() {
+        let mut ic = two_prefix_interceptor();
+        assert_eq!(
+            feed_all(&mut ic, b"::why\r"),
+            InterceptAction::Chat { message: "why".into(), prefix_index: 0 }
+        );
+        assert_eq!(ic.feed_byte(b'!'), InterceptAction::Buffering(b"!".to_vec()));
+        assert_eq!(ic.active_prefix(), b"!");
+        assert_eq!(
+            feed_all(&mut ic, b"history\r"),
+            InterceptAction::Chat { message: "history".into(), prefix_index: 1 }
+        );
+    }
+
+    #[test]
+    fn test_multi_prefix_mismatch_flush() {
+        let mut ic = two_prefix_interceptor();
+        // ":" starts "::" but "!" does not continue it: flush both bytes,
+        // don't reinterpret the "!" as the second prefix.
+        assert_eq!(ic.feed_byte(b':'), InterceptAction::Buffering(b":".to_vec()));
+        assert_eq!(ic.feed_byte(b'!'), InterceptAction::Forward(b":!".to_vec()));
+        ic.on_prompt();
+        assert_eq!(ic.feed_byte(b'x'), InterceptAction::Forward(b"x".to_vec()));
+        ic.on_prompt();
+        // "!" matches the second prefix; the first is still matched afterwards.
+        assert_eq!(
+            feed_all(&mut ic, b"!ls\r"),
+            InterceptAction::Chat { message: "ls".into(), prefix_index: 1 }
+        );
+        assert_eq!(ic.feed_byte(b':'), InterceptAction::Buffering(b":".to_vec()));
+        assert_eq!(ic.feed_byte(b':'), InterceptAction::Buffering(b"::".to_vec()));
+        assert_eq!(
+            ic.expire_prefix(),
+            Some(InterceptAction::Chat { message: String::new(), prefix_index: 0 })
+        );
+    }
+
+    #[test]
+    fn test_multi_prefix_guard_per_prefix() {
+        struct NeverIntercept;
+        impl InterceptGuard for NeverIntercept {
+            fn note_input(&mut self) {}
+            fn should_intercept(&self) -> bool { false }
+        }
+        let mut ic = InputInterceptor::with_prefixes(
+            vec![":".into(), "!".into()],
+            "::",
+            vec![Box::new(AlwaysIntercept), Box::new(NeverIntercept)],
+            true,
+        );
+        assert_eq!(ic.feed_byte(b'!'), InterceptAction::Forward(b"!".to_vec()));
+        assert_eq!(ic.feed_byte(b':'), InterceptAction::Buffering(b":".to_vec()));
+    }
+
     #[test]
     fn test_chat_detected() {
         let mut interceptor = new_interceptor("::");
@@ -658,7 +813,7 @@ mod tests {
         // Full prefix match → Buffering (awaiting timeout or double-prefix)
         assert_eq!(interceptor.feed_byte(b':'), InterceptAction::Buffering(vec![b':', b':']));
         // Timeout → Chat("")
-        assert_eq!(interceptor.expire_prefix(), Some(InterceptAction::Chat(String::new())));
+        assert_eq!(interceptor.expire_prefix(), Some(InterceptAction::Chat { message: String::new(), prefix_index: 0 }));
     }
 
     #[test]
@@ -670,7 +825,42 @@ mod tests {
         // Type content after prefix, then Enter → Chat with content
         assert_eq!(interceptor.feed_byte(b'h'), InterceptAction::Buffering(vec![b':', b':', b'h']));
         assert_eq!(interceptor.feed_byte(b'i'), InterceptAction::Buffering(vec![b':', b':', b'h', b'i']));
-        assert_eq!(interceptor.feed_byte(b'\r'), InterceptAction::Chat("hi".to_string()));
+        assert_eq!(interceptor.feed_byte(b'\r'), InterceptAction::Chat { message: "hi".to_string(), prefix_index: 0 });
+    }
+
+    #[test]
+    fn test_multiline_chat_double_enter_sends() {
+        let mut interceptor = new_interceptor("::");
+        interceptor.set_multiline(true);
+        let mut last = None;
+        for &b in b"::hello\nworld\n" {
+            last = Some(interceptor.feed_byte(b));
+        }
+        assert_eq!(
+            last,
+            Some(InterceptAction::NewlineInChat(b"::hello\nworld\n".to_vec()))
+        );
+        assert!(interceptor.is_in_chat());
+        assert_eq!(interceptor.feed_byte(b'\n'), InterceptAction::Chat { message: "hello\nworld".to_string(), prefix_index: 0 });
+        assert!(!interceptor.is_in_chat());
+    }
+
+    #[test]
+    fn test_multiline_chat_prefix_enter_opens_chat() {
+        let mut interceptor = new_interceptor("::");
+        interceptor.set_multiline(true);
+        interceptor.feed_byte(b':');
+        interceptor.feed_byte(b':');
+        assert_eq!(interceptor.feed_byte(b'\r'), InterceptAction::Chat { message: String::new(), prefix_index: 0 });
+    }
+
+    #[test]
+    fn test_single_enter_sends_without_multiline() {
+        let mut interceptor = new_interceptor("::");
+        for &b in b"::hello" {
+            interceptor.feed_byte(b);
+        }
+        assert_eq!(interceptor.feed_byte(b'\n'), InterceptAction::Chat { message: "hello".to_string(), prefix_index: 0 });
     }
 
     #[test]
@@ -732,6 +922,29 @@ mod tests {
         assert_eq!(interceptor.feed_byte(0x7f), InterceptAction::Forward(vec![0x7f]));
     }
 
+    #[test]
+    fn test_ctrl_w_deletes_previous_word() {
+        let mut interceptor = new_interceptor("::");
+        for &b in b"::delete the word" {
+            interceptor.feed_byte(b);
+        }
+        assert_eq!(interceptor.feed_byte(0x17), InterceptAction::Backspace(b"::delete the ".to_vec()));
+        assert_eq!(interceptor.feed_byte(0x17), InterceptAction::Backspace(b"::delete ".to_vec()));
+        assert_eq!(interceptor.feed_byte(0x17), InterceptAction::Backspace(b"::".to_vec()));
+        // Nothing left but the prefix: stays in chat mode
+        assert_eq!(interceptor.feed_byte(0x17), InterceptAction::Backspace(b"::".to_vec()));
+        assert!(interceptor.in_chat);
+    }
+
+    #[test]
+    fn test_ctrl_w_multibyte_word() {
+        let mut interceptor = new_interceptor("::");
+        for &b in "::你好 世界".as_bytes() {
+            interceptor.feed_byte(b);
+        }
+        assert_eq!(interceptor.feed_byte(0x17), InterceptAction::Backspace("::你好 ".as_bytes().to_vec()));
+    }
+
     // test_backspace_multibyte_chars: removed - chat input is now handled by read_chat_input
 
     #[test]
@@ -760,7 +973,7 @@ mod tests {
         // Should intercept again
         assert_eq!(interceptor.feed_byte(b':'), InterceptAction::Buffering(vec![b':']));
         assert_eq!(interceptor.feed_byte(b':'), InterceptAction::Buffering(vec![b':', b':']));
-        assert_eq!(interceptor.expire_prefix(), Some(InterceptAction::Chat(String::new())));
+        assert_eq!(interceptor.expire_prefix(), Some(InterceptAction::Chat { message: String::new(), prefix_index: 0 }));
     }
 
     #[test]
@@ -783,6 +996,23 @@ mod tests {
         assert_eq!(interceptor.feed_byte(b':'), InterceptAction::Buffering(vec![b':']));
     }
 
+    #[test]
+    fn test_suppress_if_nested_forwards_prefix() {
+        let mut interceptor = new_interceptor("::");
+        interceptor.set_suppress_if_nested(true);
+
+        // AlwaysIntercept would normally buffer the prefix
+        assert_eq!(interceptor.feed_byte(b':'), InterceptAction::Forward(vec![b':']));
+        assert_eq!(interceptor.feed_byte(b':'), InterceptAction::Forward(vec![b':']));
+        assert_eq!(interceptor.expire_prefix(), None);
+
+        // Alt-screen toggles don't clear the nested flag
+        interceptor.set_suppressed(true);
+        interceptor.set_suppressed(false);
+        interceptor.on_prompt();
+        assert_eq!(interceptor.feed_byte(b':'), InterceptAction::Forward(vec![b':']));
+    }
+
     // --- ESC / escape-sequence tests ---
 
     // test_esc_cancels_chat_mode: removed - chat input is now handled by read_chat_input
@@ -887,7 +1117,7 @@ mod tests {
         // ":" with no prior input → guard allows → Buffering (awaiting timeout)
         assert_eq!(interceptor.feed_byte(b':'), InterceptAction::Buffering(vec![b':']));
         // Timeout → Chat("")
-        assert_eq!(interceptor.expire_prefix(), Some(InterceptAction::Chat(String::new())));
+        assert_eq!(interceptor.expire_prefix(), Some(InterceptAction::Chat { message: String::new(), prefix_index: 0 }));
     }
 
     #[test]
@@ -925,7 +1155,7 @@ mod tests {
                 InterceptAction::Buffering(_) => actions.push("buffering".into()),
                 InterceptAction::Forward(_) => actions.push("forward".into()),
                 InterceptAction::Cancel => actions.push("cancel".into()),
-                InterceptAction::Chat(msg) => actions.push(format!("chat:{msg}")),
+                InterceptAction::Chat { message: msg, .. } => actions.push(format!("chat:{msg}")),
                 InterceptAction::Backspace(ref buf) if buf.is_empty() => {
                     actions.push("dismiss".into())
                 }
@@ -936,6 +1166,7 @@ mod tests {
                     ))
                 }
                 InterceptAction::Tab(_) => actions.push("tab".into()),
+                InterceptAction::NewlineInChat(_) => actions.push("newline".into()),
                 InterceptAction::ResumeChat => actions.push("resume_chat".into()),
                 InterceptAction::Pending => actions.push("pending".into()),
             }
@@ -957,7 +1188,7 @@ mod tests {
         let actions = simulate_main_loop(&mut ic, b":");
         assert_eq!(actions, vec!["prompt"]);
         // Timeout would call expire_prefix → Chat("")
-        assert_eq!(ic.expire_prefix(), Some(InterceptAction::Chat(String::new())));
+        assert_eq!(ic.expire_prefix(), Some(InterceptAction::Chat { message: String::new(), prefix_index: 0 }));
     }
 
     #[test]
@@ -983,7 +1214,7 @@ mod tests {
         let actions = simulate_main_loop(&mut ic, b"::");
         assert_eq!(actions, vec!["prompt", "echo::"]);
         // Timeout → Chat("")
-        assert_eq!(ic.expire_prefix(), Some(InterceptAction::Chat(String::new())));
+        assert_eq!(ic.expire_prefix(), Some(InterceptAction::Chat { message: String::new(), prefix_index: 0 }));
     }
 
     #[test]
@@ -1121,7 +1352,7 @@ mod tests {
         let mut ic = new_interceptor(":");
         assert_eq!(ic.feed_byte(b':'), InterceptAction::Buffering(vec![b':']));
         // Enter after prefix → Chat("")
-        assert_eq!(ic.feed_byte(b'\r'), InterceptAction::Chat(String::new()));
+        assert_eq!(ic.feed_byte(b'\r'), InterceptAction::Chat { message: String::new(), prefix_index: 0 });
     }
 
     #[test]
@@ -1289,7 +1520,7 @@ mod tests {
                     self.timer_active = false;
                     LoopOutcome::Forward
                 }
-                InterceptAction::Chat(msg) => {
+                InterceptAction::Chat { message: msg, .. } => {
                     self.timer_active = false;
                     LoopOutcome::NewChat(msg)
                 }
@@ -1305,6 +1536,11 @@ mod tests {
                     LoopOutcome::Backspace(buf)
                 }
                 InterceptAction::Tab(_) => LoopOutcome::Tab,
+                InterceptAction::NewlineInChat(buf) => {
+                    self.timer_active = false;
+                    let content = String::from_utf8_lossy(&buf[self.prefix.len()..]).to_string();
+                    LoopOutcome::Echo(content)
+                }
                 InterceptAction::Pending => LoopOutcome::Pending,
             }
         }
@@ -1316,7 +1552,7 @@ mod tests {
             }
             self.timer_active = false;
             match self.ic.expire_prefix() {
-                Some(InterceptAction::Chat(_)) => LoopOutcome::Timeout,
+                Some(InterceptAction::Chat { .. }) => LoopOutcome::Timeout,
                 _ => LoopOutcome::Pending,
             }
         }
@@ -1,4 +1,5 @@
 // crates/omnish-client/src/main.rs
+mod batch;
 mod chat_session;
 mod client_plugin;
 mod command;
@@ -20,6 +21,7 @@ mod shell_input;
 mod throttle;
 mod util;
 mod onboarding;
+mod pager;
 mod widgets;
 
 use anyhow::{Context, Result};
@@ -29,8 +31,11 @@ use widgets::line_status::LineStatus;
 use omnish_protocol::message::*;
 use omnish_pty::proxy::PtyProxy;
 use omnish_pty::raw_mode::RawModeGuard;
+use omnish_transport::reconnect::BackoffConfig;
 use omnish_transport::rpc_client::RpcClient;
-use std::collections::{HashMap, VecDeque};
+use omnish_transport::rpc_pool::RpcClientPool;
+use std::cmp::Reverse;
+use std::collections::{BinaryHeap, HashMap, VecDeque};
 use std::os::fd::AsRawFd;
 use std::sync::{Arc, RwLock};
 use std::sync::atomic::{AtomicBool, Ordering};
@@ -38,9 +43,105 @@ use tokio::sync::Mutex;
 use tokio::sync::mpsc;
 use uuid::Uuid;
 
-type MessageBuffer = Arc<Mutex<VecDeque<Message>>>;
+type MessageBuffer = Arc<Mutex<ReplayBuffer>>;
+
+/// How the replay buffer makes room when full (`buffer_size` and
+/// `buffer_prioritize_commands` in client.toml).
+#[derive(Clone, Copy)]
+struct BufferPolicy {
+    max_len: usize,
+    prioritize_commands: bool,
+}
+
+impl BufferPolicy {
+    fn from_config(config: &omnish_common::config::ClientConfig) -> Self {
+        Self {
+            max_len: config.buffer_size,
+            prioritize_commands: config.buffer_prioritize_commands,
+        }
+    }
+}
+
+/// A buffered message, ordered by `(priority, timestamp)`.
+struct PrioritizedMessage {
+    priority: u8,
+    /// Logical insertion time (a counter, not wall clock) so replay keeps
+    /// send order even for messages within the same millisecond.
+    timestamp: u64,
+    msg: Message,
+}
+
+impl PrioritizedMessage {
+    fn key(&self) -> (u8, u64) {
+        (self.priority, self.timestamp)
+    }
+}
+
+impl PartialEq for PrioritizedMessage {
+    fn eq(&self, other: &Self) -> bool {
+        self.key() == other.key()
+    }
+}
+
+impl Eq for PrioritizedMessage {}
+
+impl PartialOrd for PrioritizedMessage {
+    fn partial_cmp(&self, other: &Self) -> Option<std::cmp::Ordering> {
+        Some(self.cmp(other))
+    }
+}
+
+impl Ord for PrioritizedMessage {
+    fn cmp(&self, other: &Self) -> std::cmp::Ordering {
+        self.key().cmp(&other.key())
+    }
+}
+
+/// Messages held while the daemon is unreachable, replayed on reconnect.
+/// The heap top is the lowest-priority oldest message, which is the one
+/// evicted when the buffer is full.
+#[derive(Default)]
+struct ReplayBuffer {
+    heap: BinaryHeap<Reverse<PrioritizedMessage>>,
+    next_timestamp: u64,
+}
+
+impl ReplayBuffer {
+    /// `CommandComplete` and `SessionUpdate` outrank `IoData` when the policy
+    /// prioritizes commands; otherwise everything is equal and eviction
+    /// is oldest-first.
+    fn priority(msg: &Message, policy: BufferPolicy) -> u8 {
+        match msg {
+            _ if !policy.prioritize_commands => 1,
+            Message::IoData(_) => 1,
+            _ => 2,
+        }
+    }
+
+    fn push(&mut self, msg: Message, policy: BufferPolicy) {
+        if policy.max_len == 0 {
+            return;
+        }
+        while self.heap.len() >= policy.max_len {
+            self.heap.pop();
+        }
+        let timestamp = self.next_timestamp;
+        self.next_timestamp += 1;
+        self.heap.push(Reverse(PrioritizedMessage {
+            priority: Self::priority(&msg, policy),
+            timestamp,
+            msg,
+        }));
+    }
+
+    /// Take every buffered message, in the order they were buffered.
+    fn drain(&mut self) -> Vec<Message> {
+        let mut entries: Vec<PrioritizedMessage> = self.heap.drain().map(|Reverse(e)| e).collect();
+        entries.sort_by_key(|e| e.timestamp);
+        entries.into_iter().map(|e| e.msg).collect()
+    }
+}
 
-const MAX_BUFFER_SIZE: usize = 10_000;
 
 /// Set by the SIGHUP/SIGTERM handler so the main poll loop can break out and
 /// run the same SessionEnd-then-exit path that a normal shell exit takes.
@@ -81,7 +182,7 @@ fn should_buffer(msg: &Message) -> bool {
 
 /// Send completion summary to daemon if there's a pending completion
 fn send_completion_summary(
-    rpc: &RpcClient,
+    rpc: &RpcClientPool,
     shell_completer: &mut completion::ShellCompleter,
     session_id: &str,
     accepted: bool,
@@ -98,7 +199,7 @@ fn send_completion_summary(
 
 /// Send completion summary for ignored completion (accepted=false)
 fn send_ignored_summary(
-    rpc: &RpcClient,
+    rpc: &RpcClientPool,
     shell_completer: &mut completion::ShellCompleter,
     session_id: &str,
     cwd: Option<String>,
@@ -109,13 +210,38 @@ fn send_ignored_summary(
 
 /// Send a message to the daemon, buffering it if the send fails and
 /// the message type is eligible for retry.
-async fn send_or_buffer(rpc: &RpcClient, msg: Message, buffer: &MessageBuffer) {
-    if rpc.send(msg.clone()).await.is_err() && should_buffer(&msg) {
-        let mut buf = buffer.lock().await;
-        if buf.len() >= MAX_BUFFER_SIZE {
-            buf.pop_front();
-        }
-        buf.push_back(msg);
+async fn send_or_buffer(rpc: &RpcClientPool, msg: Message, buffer: &MessageBuffer, policy: BufferPolicy) {
+    if rpc.send(msg.clone()).await.is_ok() {
+        return;
+    }
+    // A failed batch is buffered as its individual messages
+    let messages = match msg {
+        Message::Batch { messages } => messages,
+        msg => vec![msg],
+    };
+    let mut buf = buffer.lock().await;
+    for msg in messages.into_iter().filter(should_buffer) {
+        buf.push(msg, policy);
+    }
+}
+
+/// Whether a daemon connection exists and is currently up.
+async fn daemon_connected(daemon_conn: &Option<RpcClientPool>) -> bool {
+    match daemon_conn {
+        Some(rpc) => rpc.is_connected().await,
+        None => false,
+    }
+}
+
+/// Send whatever `io_batch` holds (see `batch::BatchAccumulator`).
+async fn flush_io_batch(
+    rpc: &RpcClientPool,
+    io_batch: &mut batch::BatchAccumulator,
+    buffer: &MessageBuffer,
+    policy: BufferPolicy,
+) {
+    if let Some(msg) = io_batch.flush() {
+        send_or_buffer(rpc, msg, buffer, policy).await;
     }
 }
 
@@ -361,7 +487,7 @@ fn compare_versions(a: &str, b: &str) -> std::cmp::Ordering {
 }
 
 async fn download_and_extract_update(
-    rpc: &RpcClient,
+    rpc: &RpcClientPool,
     os: &str,
     arch: &str,
     version: &str,
@@ -493,7 +619,7 @@ async fn download_and_extract_update(
 /// the same-host case: daemon and client sharing `~/.omnish/plugins/`
 /// see identical content, both sides compute the same checksum, and
 /// `available` is naturally false - no self-mutating download+extract.
-async fn sync_plugin_bundle(rpc: &RpcClient, hostname: &str) -> anyhow::Result<()> {
+async fn sync_plugin_bundle(rpc: &RpcClientPool, hostname: &str) -> anyhow::Result<()> {
     let omnish_dir = omnish_common::config::omnish_dir();
     let plugins_dir = omnish_dir.join("plugins");
 
@@ -538,7 +664,7 @@ async fn sync_plugin_bundle(rpc: &RpcClient, hostname: &str) -> anyhow::Result<(
 /// return the fully-assembled tar.gz bytes. Verifies the checksum sent in
 /// the first chunk matches the expected daemon checksum.
 async fn download_plugin_bundle(
-    rpc: &RpcClient,
+    rpc: &RpcClientPool,
     hostname: &str,
     expected_checksum: &str,
 ) -> anyhow::Result<Vec<u8>> {
@@ -680,13 +806,26 @@ fn mirror_delete(
     Ok(())
 }
 
-#[tokio::main(worker_threads = 4)]
-async fn main() -> Result<()> {
+fn main() -> Result<()> {
+    // Exports $OMNISH_DIR, so it must run before the runtime starts threads.
+    omnish_common::config::apply_omnish_dir_flag();
+    tokio::runtime::Builder::new_multi_thread()
+        .worker_threads(4)
+        .enable_all()
+        .build()?
+        .block_on(async_main())
+}
+
+async fn async_main() -> Result<()> {
     if std::env::args().any(|a| a == "--version" || a == "-V") {
         println!("omnish {}", omnish_common::VERSION);
         return Ok(());
     }
 
+    if std::env::args().nth(1).as_deref() == Some(omnish_common::completions::GENERATE_COMPLETIONS) {
+        std::process::exit(omnish_common::completions::run(std::env::args().nth(2).as_deref()));
+    }
+
     // Initialize file-based tracing for debugging (does not write to stderr/stdout to avoid PTY interference)
     {
         let log_path = omnish_common::config::omnish_dir().join("client.log");
@@ -708,6 +847,10 @@ async fn main() -> Result<()> {
     let lang = std::env::var("OMNISH_LANG").unwrap_or_else(|_| config.shell.language.clone());
     i18n::init(&lang);
 
+    if std::env::args().any(|a| a == "--health") {
+        std::process::exit(run_health_check(&config).await);
+    }
+
     // Catch SIGHUP / SIGTERM so the main loop can break out and send a
     // proper SessionEnd before exit (tmux kill-session, manual kill, etc).
     install_shutdown_signal_handlers();
@@ -719,9 +862,9 @@ async fn main() -> Result<()> {
     if resume_args.is_none() && !nix::unistd::isatty(0).unwrap_or(false) {
         let shell = resolve_shell(&config.shell.command);
         let shell_cstr = std::ffi::CString::new(shell.as_str()).expect("shell path");
-        // Pass through any arguments after argv[0]
+        // Pass through any arguments after argv[0], minus our own --omnish-dir
         let mut args: Vec<std::ffi::CString> = vec![shell_cstr.clone()];
-        for arg in std::env::args().skip(1) {
+        for arg in omnish_common::config::split_omnish_dir_flag(std::env::args().skip(1)).1 {
             args.push(std::ffi::CString::new(arg).expect("arg"));
         }
         nix::unistd::execvp(&shell_cstr, &args)?;
@@ -732,15 +875,21 @@ async fn main() -> Result<()> {
     let shell = resolve_shell(&config.shell.command);
 
     // Install shell-specific OSC 133 hook
-    let osc133_rcfile = shell_hook::install_bash_hook(&shell);
-    let osc133_zdotdir = shell_hook::install_zsh_hook(&shell);
-    let osc133_hook_installed = osc133_rcfile.is_some() || osc133_zdotdir.is_some();
-
-    let shell_args: Vec<String> = if let Some(ref rcfile) = osc133_rcfile {
-        vec!["--rcfile".to_string(), rcfile.to_string_lossy().to_string()]
-    } else {
-        vec![]
+    let mut osc133_zdotdir = None;
+    let osc133_args: Option<Vec<String>> = match shell_hook::ShellKind::detect(&shell) {
+        shell_hook::ShellKind::Bash => shell_hook::install_bash_hook(&shell, config.shell.source_completions)
+            .map(|rcfile| vec!["--rcfile".to_string(), rcfile.to_string_lossy().to_string()]),
+        // zsh picks up the hook via ZDOTDIR in the child env, not an argument
+        shell_hook::ShellKind::Zsh => {
+            osc133_zdotdir = shell_hook::install_zsh_hook(&shell);
+            osc133_zdotdir.as_ref().map(|_| Vec::new())
+        }
+        shell_hook::ShellKind::Fish => shell_hook::install_fish_hook(&shell),
+        shell_hook::ShellKind::PowerShell => shell_hook::install_pwsh_hook(&shell),
+        shell_hook::ShellKind::Unknown => None,
     };
+    let osc133_hook_installed = osc133_args.is_some();
+    let shell_args: Vec<String> = osc133_args.unwrap_or_default();
     let shell_args_ref: Vec<&str> = shell_args.iter().map(|s| s.as_str()).collect();
 
     let (session_id, mut proxy, osc133_hook_installed) = if let Some(ref resume) = resume_args {
@@ -774,14 +923,16 @@ async fn main() -> Result<()> {
         (session_id, proxy, osc133_hook_installed)
     };
     let parent_session_id = std::env::var("OMNISH_SESSION_ID").ok();
+    let is_nested = parent_session_id.is_some();
     let daemon_addr = std::env::var("OMNISH_SOCKET")
         .unwrap_or_else(|_| config.daemon_addr.clone());
 
     // Connect to daemon (graceful degradation)
-    let pending_buffer: MessageBuffer = Arc::new(Mutex::new(VecDeque::new()));
+    let pending_buffer: MessageBuffer = Arc::new(Mutex::new(ReplayBuffer::default()));
+    let buffer_policy = BufferPolicy::from_config(&config);
     let update_needed = Arc::new(AtomicBool::new(false));
     let client_addr_opt = config.client_addr.clone();
-    let daemon_conn = connect_daemon(&daemon_addr, &session_id, parent_session_id, proxy.child_pid() as u32, client_addr_opt.clone(), pending_buffer.clone(), update_needed.clone()).await;
+    let daemon_conn = connect_daemon(&daemon_addr, &session_id, parent_session_id, proxy.child_pid() as u32, client_addr_opt.clone(), config.shell.session_env_vars.clone(), config.tls.ca_cert_path.clone(), reconnect_backoff(&config), pending_buffer.clone(), update_needed.clone(), config.connection_pool_size.max(1)).await;
 
     // Spawn shell info polling task (progressive interval: 1/2/4/8/15/30s, then 60s)
     // Reset to 1s on each command start
@@ -848,7 +999,7 @@ async fn main() -> Result<()> {
                     timestamp_ms: timestamp_ms(),
                     attrs: changed,
                 });
-                send_or_buffer(&rpc_poll, msg, &poll_buffer).await;
+                send_or_buffer(&rpc_poll, msg, &poll_buffer, buffer_policy).await;
 
                 last_attrs = current;
 
@@ -882,11 +1033,23 @@ async fn main() -> Result<()> {
     // Main I/O loop using poll
     let mut input_buf = [0u8; 4096];
     let mut output_buf = [0u8; 4096];
-    let guard = TimeGapGuard::new(std::time::Duration::from_millis(config.shell.intercept_gap_ms));
-    let mut interceptor = InputInterceptor::new(&config.shell.command_prefix, &config.shell.resume_prefix, Box::new(guard), config.shell.developer_mode);
-    let mut prefix_bytes: Vec<u8> = config.shell.command_prefix.as_bytes().to_vec();
+    let prefixes: Vec<String> = std::iter::once(config.shell.command_prefix.clone())
+        .chain(config.shell.extra_prefixes.iter().filter(|p| !p.is_empty()).cloned())
+        .collect();
+    let guards: Vec<Box<dyn interceptor::InterceptGuard>> = prefixes
+        .iter()
+        .map(|_| {
+            Box::new(TimeGapGuard::new(std::time::Duration::from_millis(config.shell.intercept_gap_ms)))
+                as Box<dyn interceptor::InterceptGuard>
+        })
+        .collect();
+    let mut interceptor = InputInterceptor::with_prefixes(prefixes, &config.shell.resume_prefix, guards, config.shell.developer_mode);
+    interceptor.set_multiline(config.shell.multiline_chat);
+    // Running inside another omnish session: let the outer client own the prefix.
+    interceptor.set_suppress_if_nested(is_nested);
     let mut completion_enabled = config.shell.completion_enabled;
     let mut ghost_timeout_ms = config.shell.ghost_timeout_ms;
+    let tab_accepts_word = config.shell.tab_accepts_word;
     // Client-local sandbox state (enabled + preferred backend), shared with
     // chat session so menu edits can update it and flow into subsequent chat
     // sessions. Writes persist to client.toml via save_client_local_config.
@@ -903,7 +1066,9 @@ async fn main() -> Result<()> {
     let mut command_tracker = omnish_tracker::command_tracker::CommandTracker::new(
         session_id.clone(), cwd,
     );
-    let mut throttle = throttle::OutputThrottle::new();
+    let mut throttle = throttle::OutputThrottle::new().with_fast_command_ms(config.throttle.fast_command_ms);
+    // Set on CommandStart, cleared when the command completes
+    let mut command_started_at: Option<std::time::Instant> = None;
     let mut osc133_detector = omnish_tracker::osc133_detector::Osc133Detector::new();
     let mut dsr_detector = DsrDetector::new();
     let mut osc133_warned = false;
@@ -912,6 +1077,7 @@ async fn main() -> Result<()> {
         Box::new(ghost_complete::BuiltinProvider::new()),
     ]);
     let mut shell_input = shell_input::ShellInputTracker::new();
+    shell_input.set_vi_keymap(config.shell.readline_keymap == "vi");
     let mut last_readline_content: Option<String> = None;
     // Pending completion responses waiting for readline report
     let mut pending_completion_responses: Vec<omnish_protocol::message::CompletionResponse> = Vec::new();
@@ -932,6 +1098,17 @@ async fn main() -> Result<()> {
         nix::unistd::write(std::io::stdout(), title.as_bytes()).ok();
     }
     let mut shell_completer = completion::ShellCompleter::new();
+    shell_completer.set_cache_ttl_secs(config.shell.completion_cache_ttl_secs);
+    // Seed history completion with recent commands of all sessions
+    let (history_tx, mut history_rx) = tokio::sync::oneshot::channel::<Vec<String>>();
+    if let Some(ref rpc) = daemon_conn {
+        let rpc = rpc.clone();
+        let sid = session_id.clone();
+        tokio::spawn(async move {
+            let commands = chat_session::ChatSession::fetch_command_lines(&rpc, &sid, "__cmd:history all").await;
+            let _ = history_tx.send(commands);
+        });
+    }
     let (completion_tx, mut completion_rx) = tokio::sync::mpsc::channel::<
         omnish_protocol::message::CompletionResponse
     >(4);
@@ -960,6 +1137,7 @@ async fn main() -> Result<()> {
     // double-prefix (e.g. "::") vs single prefix (":").
     let mut prefix_match_time: Option<std::time::Instant> = None;
     const PREFIX_TIMEOUT: std::time::Duration = std::time::Duration::from_millis(250);
+    let mut io_batch = batch::BatchAccumulator::new(batch::IO_BATCH_WINDOW);
 
     loop {
         let mut fds = [
@@ -975,8 +1153,17 @@ async fn main() -> Result<()> {
             },
         ];
 
+        // Wake up in time to send pending IoData
+        let poll_timeout = io_batch
+            .time_until_due()
+            .map_or(100, |d| d.as_millis().min(100) as i32);
         let poll_start = std::time::Instant::now();
-        let ret = unsafe { libc::poll(fds.as_mut_ptr(), 2, 100) };
+        let ret = unsafe { libc::poll(fds.as_mut_ptr(), 2, poll_timeout) };
+        if io_batch.is_due() {
+            if let Some(ref rpc) = daemon_conn {
+                flush_io_batch(rpc, &mut io_batch, &pending_buffer, buffer_policy).await;
+            }
+        }
         if ret < 0 {
             continue;
         }
@@ -1141,7 +1328,7 @@ async fn main() -> Result<()> {
             if t.elapsed() >= PREFIX_TIMEOUT {
                 prefix_match_time = None;
                 if let Some(action) = interceptor.expire_prefix() {
-                    if matches!(action, InterceptAction::Chat(_)) {
+                    if matches!(action, InterceptAction::Chat { .. }) {
                         // notice_queue::push(&format!(": timeout ({}ms)", t.elapsed().as_millis()));
                         event_log::push("chat mode enter (timeout)");
                         let exit_action = enter_chat_mode(
@@ -1216,12 +1403,13 @@ async fn main() -> Result<()> {
             for &byte in &filtered_input {
                 match interceptor.feed_byte(byte) {
                     InterceptAction::Buffering(buf) => {
-                        if buf == prefix_bytes {
+                        let prefix = interceptor.active_prefix();
+                        if buf == prefix {
                             // Full prefix matched - start timer for double-prefix detection.
                             // No visual feedback yet; chat prompt appears on timeout or Enter.
                             shell_completer.clear();
                             prefix_match_time = Some(std::time::Instant::now());
-                        } else if buf.len() > prefix_bytes.len() && buf.starts_with(&prefix_bytes) {
+                        } else if buf.len() > prefix.len() && buf.starts_with(prefix) {
                             // Additional input after prefix - cancel timer
                             prefix_match_time = None;
                         }
@@ -1229,10 +1417,26 @@ async fn main() -> Result<()> {
                     InterceptAction::Backspace(_buf) => {
                         // No visual prompt to update - prefix buffering is invisible
                     }
+                    InterceptAction::NewlineInChat(buf) => {
+                        // Multi-line chat input continues; the message is sent
+                        // on the next Enter, so don't let the timer open chat.
+                        prefix_match_time = None;
+                        let content = buf.strip_prefix(interceptor.active_prefix()).unwrap_or(&buf);
+                        let echo = display::render_chat_continuation(content);
+                        nix::unistd::write(std::io::stdout(), echo.as_bytes()).ok();
+                    }
                     InterceptAction::Forward(bytes) => {
                         // Check if Tab should be intercepted for shell completion
                         if bytes == [b'\t'] && shell_completer.ghost().is_some() {
-                            if let Some(suffix) = shell_completer.accept() {
+                            let accepted = if tab_accepts_word {
+                                shell_completer.accept_word()
+                            } else {
+                                shell_completer.accept()
+                            };
+                            if shell_completer.ghost().is_none() {
+                                shell_completer.on_ghost_fully_consumed();
+                            }
+                            if let Some(suffix) = accepted {
                                 event_log::push(format!("completion accepted suffix={suffix:?}"));
                                 // Safety: if cursor is not at end, move to end first
                                 if !shell_input.cursor_at_end() {
@@ -1311,8 +1515,14 @@ async fn main() -> Result<()> {
                             // Always reset debounce on input activity, even if
                             // take_change() returns None due to pending_rl_report
                             shell_completer.note_activity();
+                            let vi_command = shell_input.vi_mode() == shell_input::ViModeState::Command;
                             if let Some((input, seq)) = shell_input.take_change() {
-                                if shell_completer.on_input_changed(input, seq) {
+                                let vi_cleared = shell_completer.set_vi_command_mode(vi_command);
+                                let cleared = shell_completer.on_input_changed(input, seq);
+                                if shell_completer.ghost() == Some("") {
+                                    shell_completer.on_ghost_fully_consumed();
+                                }
+                                if cleared || vi_cleared {
                                     // Ghost was cleared - erase stale ghost text from screen
                                     erase_ghost_with_log(ghost_wrap_rows, "input_changed_forward");
                                     ghost_wrap_rows = 0;
@@ -1328,16 +1538,14 @@ async fn main() -> Result<()> {
                             command_tracker.feed_input(&bytes, timestamp_ms());
 
                             // Report to daemon async (skip during alt screen)
-                            if let Some(ref rpc) = daemon_conn {
-                                if !alt_screen_detector.is_active() {
-                                    let msg = Message::IoData(IoData {
-                                        session_id: session_id.clone(),
-                                        direction: IoDirection::Input,
-                                        timestamp_ms: timestamp_ms(),
-                                        data: bytes,
-                                    });
-                                    send_or_buffer(rpc, msg, &pending_buffer).await;
-                                }
+                            if daemon_conn.is_some() && !alt_screen_detector.is_active() {
+                                let msg = Message::IoData(IoData {
+                                    session_id: session_id.clone(),
+                                    direction: IoDirection::Input,
+                                    timestamp_ms: timestamp_ms(),
+                                    data: bytes,
+                                });
+                                io_batch.push(msg);
                             }
                         }
                     }
@@ -1346,10 +1554,11 @@ async fn main() -> Result<()> {
                         prefix_match_time = None;
                         completer.clear();
                     }
-                    InterceptAction::Chat(msg) => {
+                    InterceptAction::Chat { message, prefix_index } => {
                         prefix_match_time = None;
                         event_log::push("chat mode enter");
                         completer.clear();
+                        let msg = command::route_prefixed(message, prefix_index);
                         let initial = if msg.trim().is_empty() { None } else { Some(msg) };
                         let exit_action = enter_chat_mode(
                             initial, &daemon_conn, &mut chat_history, &mut last_thread_id,
@@ -1430,16 +1639,14 @@ async fn main() -> Result<()> {
                             proxy.write_all(&bytes)?;
                             shell_input.feed_forwarded(&bytes);
                             command_tracker.feed_input(&bytes, timestamp_ms());
-                            if let Some(ref rpc) = daemon_conn {
-                                if !alt_screen_detector.is_active() {
-                                    let msg = Message::IoData(IoData {
-                                        session_id: session_id.clone(),
-                                        direction: IoDirection::Input,
-                                        timestamp_ms: timestamp_ms(),
-                                        data: bytes,
-                                    });
-                                    send_or_buffer(rpc, msg, &pending_buffer).await;
-                                }
+                            if daemon_conn.is_some() && !alt_screen_detector.is_active() {
+                                let msg = Message::IoData(IoData {
+                                    session_id: session_id.clone(),
+                                    direction: IoDirection::Input,
+                                    timestamp_ms: timestamp_ms(),
+                                    data: bytes,
+                                });
+                                io_batch.push(msg);
                             }
                         }
                     }
@@ -1530,17 +1737,18 @@ async fn main() -> Result<()> {
 
                     // Send IoData to daemon (throttled) - skip while alternate screen
                     // is active (vim, less, htop, etc.) to avoid storing TUI noise.
-                    if let Some(ref rpc) = daemon_conn {
-                        if !alt_screen_detector.is_active() && throttle.should_send(n) {
-                            let msg = Message::IoData(IoData {
-                                session_id: session_id.clone(),
-                                direction: IoDirection::Output,
-                                timestamp_ms: timestamp_ms(),
-                                data: display_data.to_vec(),
-                            });
-                            send_or_buffer(rpc, msg, &pending_buffer).await;
-                            throttle.record_sent(n);
-                        }
+                    if let Some(started) = command_started_at {
+                        throttle.note_command_duration(started.elapsed().as_millis() as u64);
+                    }
+                    if daemon_conn.is_some() && !alt_screen_detector.is_active() && throttle.should_send(n) {
+                        let msg = Message::IoData(IoData {
+                            session_id: session_id.clone(),
+                            direction: IoDirection::Output,
+                            timestamp_ms: timestamp_ms(),
+                            data: display_data.to_vec(),
+                        });
+                        io_batch.push(msg);
+                        throttle.record_sent(n);
                     }
 
                     // Feed OSC 133 events to command tracker
@@ -1555,6 +1763,7 @@ async fn main() -> Result<()> {
                                 shell_input.on_prompt();
                                 interceptor.on_prompt();
                                 shell_completer.clear();
+                                shell_completer.set_vi_command_mode(false);
                                 pending_completion_responses.clear();
                                 readline_triggered_for_completions = false;
                                 readline_trigger_time = None;
@@ -1569,6 +1778,7 @@ async fn main() -> Result<()> {
                                 shell_input.on_prompt();
                                 interceptor.on_prompt();
                                 shell_completer.clear();
+                                shell_completer.set_vi_command_mode(false);
                                 pending_completion_responses.clear();
                                 readline_triggered_for_completions = false;
                                 readline_trigger_time = None;
@@ -1601,6 +1811,7 @@ async fn main() -> Result<()> {
                                 pending_completion_responses.clear();
                                 readline_triggered_for_completions = false;
                                 readline_trigger_time = None;
+                                command_started_at = Some(std::time::Instant::now());
                                 // Reset polling interval to 1s on command start
                                 let _ = cmd_start_tx_for_loop.try_send(());
                                 if let Some(cmd) = command {
@@ -1655,9 +1866,15 @@ async fn main() -> Result<()> {
                                     readline_trigger_time = None;
                                 }
 
+                                let vi_command = shell_input.vi_mode() == shell_input::ViModeState::Command;
                                 if let Some((input, seq)) = shell_input.take_change() {
                                     let had_ghost = shell_completer.ghost().is_some();
-                                    if shell_completer.on_input_changed(input, seq) {
+                                    let vi_cleared = shell_completer.set_vi_command_mode(vi_command);
+                                    let cleared = shell_completer.on_input_changed(input, seq);
+                                    if shell_completer.ghost() == Some("") {
+                                        shell_completer.on_ghost_fully_consumed();
+                                    }
+                                    if cleared || vi_cleared {
                                         event_log::push(format!("on_input_changed cleared ghost input={:?}", input));
                                         erase_ghost_with_log(ghost_wrap_rows, "rl_input_changed");
                                         ghost_wrap_rows = 0;
@@ -1666,6 +1883,14 @@ async fn main() -> Result<()> {
                                     }
                                 }
                             }
+                            Osc133EventKind::WorkingDirChange { path } => {
+                                event_log::push(format!("osc7 cwd={path}"));
+                                command_tracker.set_cwd(path.clone());
+                            }
+                            Osc133EventKind::ContinuationPrompt => {
+                                // PS2: the command continues on the next line
+                                event_log::push("osc133 ContinuationPrompt");
+                            }
                             Osc133EventKind::NoReadline => {
                                 if !no_readline_warned {
                                     notice("[omnish] bash readline not available (bind -x unsupported). Completions disabled.");
@@ -1694,16 +1919,22 @@ async fn main() -> Result<()> {
                             "command complete: {:?} exit={:?}",
                             record.command_line, record.exit_code
                         ));
+                        if let Some(ref line) = record.command_line {
+                            shell_completer.note_command(line);
+                        }
                         if let Some(ref rpc) = daemon_conn {
+                            // Output must reach the daemon before the command that produced it
+                            flush_io_batch(rpc, &mut io_batch, &pending_buffer, buffer_policy).await;
                             let msg = Message::CommandComplete(omnish_protocol::message::CommandComplete {
                                 session_id: session_id.clone(),
                                 record: record.clone(),
                             });
-                            send_or_buffer(rpc, msg, &pending_buffer).await;
+                            send_or_buffer(rpc, msg, &pending_buffer, buffer_policy).await;
                         }
                     }
                     if !completed.is_empty() {
                         throttle.reset();
+                        command_started_at = None;
                     }
 
                     // Flush deferred ghost if set during this PTY read's OSC processing,
@@ -1734,11 +1965,36 @@ async fn main() -> Result<()> {
 
             // Clean up timed-out requests first
             let _cleaned = shell_completer.cleanup_timed_out_requests();
+            if let Ok(commands) = history_rx.try_recv() {
+                shell_completer.seed_history(commands);
+            }
 
             if completion_enabled && at_prompt && !in_chat && !shell_input.in_isearch() && shell_input.cursor_at_end() && shell_completer.should_request(shell_input.sequence_id(), current) {
                 let seq = shell_input.sequence_id();
-                if let Some(ref rpc) = daemon_conn {
-                    let shell_cwd = get_shell_cwd(proxy.child_pid() as u32);
+                let shell_cwd = get_shell_cwd(proxy.child_pid() as u32);
+                shell_completer.set_cwd(shell_cwd.as_deref());
+                if let Some(resp) = shell_completer.path_response(seq, current) {
+                    // Local path completion is exact, so it takes priority over the LLM
+                    event_log::push(format!("completion from filesystem seq={seq} input={current:?}"));
+                    completion_tx.try_send(resp).ok();
+                } else if let Some(resp) = shell_completer.history_response(seq, current) {
+                    // A command run before is the likeliest completion and costs nothing
+                    event_log::push(format!("completion from history seq={seq} input={current:?}"));
+                    completion_tx.try_send(resp).ok();
+                } else if let Some(resp) = shell_completer.cached_response(seq, current) {
+                    // Cache hit: feed it through the same pending-response path
+                    // as a daemon reply so readline state is checked first.
+                    event_log::push(format!("completion cache hit seq={seq} input={current:?}"));
+                    completion_tx.try_send(resp).ok();
+                } else if !daemon_connected(&daemon_conn).await {
+                    // No LLM to ask: fall back to the table of common commands
+                    if let Some(resp) = shell_completer.common_command_response(seq, current) {
+                        event_log::push(format!("completion from builtin table seq={seq} input={current:?}"));
+                        completion_tx.try_send(resp).ok();
+                    } else {
+                        shell_completer.mark_sent(seq, current);
+                    }
+                } else if let Some(ref rpc) = daemon_conn {
                     let msg = completion::ShellCompleter::build_request(
                         &session_id, current, seq, shell_cwd,
                     );
@@ -1845,9 +2101,9 @@ async fn main() -> Result<()> {
                         apply_client_config_changes(
                             &changes,
                             &mut interceptor,
+                            &mut command_tracker,
                             &mut completion_enabled,
                             &mut ghost_timeout_ms,
-                            &mut prefix_bytes,
                         );
                     }
                     Message::NoticePush { level, text, kind } => {
@@ -1901,6 +2157,7 @@ async fn main() -> Result<()> {
 
     // Send session end
     if let Some(ref rpc) = daemon_conn {
+        flush_io_batch(rpc, &mut io_batch, &pending_buffer, buffer_policy).await;
         let msg = Message::SessionEnd(SessionEnd {
             session_id: session_id.clone(),
             timestamp_ms: timestamp_ms(),
@@ -1919,16 +2176,15 @@ async fn main() -> Result<()> {
 fn apply_client_config_changes(
     changes: &[omnish_protocol::message::ConfigChange],
     interceptor: &mut InputInterceptor,
+    command_tracker: &mut omnish_tracker::command_tracker::CommandTracker,
     completion_enabled: &mut bool,
     ghost_timeout_ms: &mut u64,
-    prefix_bytes: &mut Vec<u8>,
 ) {
     let mut any_changed = false;
     for change in changes {
         match change.path.as_str() {
             "client.command_prefix" => {
                 interceptor.update_prefix(&change.value);
-                *prefix_bytes = change.value.as_bytes().to_vec();
                 any_changed = true;
             }
             "client.resume_prefix" => {
@@ -1959,6 +2215,19 @@ fn apply_client_config_changes(
                     any_changed = true;
                 }
             }
+            "context.tracker.prompt_regex" => {
+                // Not cached in client.toml: only matters once connected
+                let pattern = Some(change.value.as_str()).filter(|p| !p.is_empty());
+                if let Err(e) = command_tracker.set_prompt_regex(pattern) {
+                    notice(&format!("[omnish] invalid context.tracker.prompt_regex: {}", e));
+                }
+            }
+            "context.tracker.ps2_regex" => {
+                let pattern = Some(change.value.as_str()).filter(|p| !p.is_empty());
+                if let Err(e) = command_tracker.set_ps2_regex(pattern) {
+                    notice(&format!("[omnish] invalid context.tracker.ps2_regex: {}", e));
+                }
+            }
             "client.language" => {
                 // OMNISH_LANG env var overrides daemon-pushed language
                 if std::env::var("OMNISH_LANG").is_err() {
@@ -2080,15 +2349,29 @@ fn save_client_config_cache(changes: &[omnish_protocol::message::ConfigChange])
     Ok(())
 }
 
+/// Reconnect delays from client.toml, default jitter.
+fn reconnect_backoff(config: &omnish_common::config::ClientConfig) -> BackoffConfig {
+    BackoffConfig {
+        initial_ms: config.reconnect_initial_ms,
+        max_ms: config.reconnect_max_ms,
+        ..BackoffConfig::default()
+    }
+}
+
+#[allow(clippy::too_many_arguments)]
 async fn connect_daemon(
     daemon_addr: &str,
     session_id: &str,
     parent_session_id: Option<String>,
     child_pid: u32,
     client_addr: Option<String>,
+    env_vars: Vec<String>,
+    ca_cert_path: Option<String>,
+    backoff: BackoffConfig,
     buffer: MessageBuffer,
     update_needed: Arc<AtomicBool>,
-) -> Option<RpcClient> {
+    pool_size: usize,
+) -> Option<RpcClientPool> {
     let socket_path = daemon_addr.to_string();
     let sid = session_id.to_string();
     let psid = parent_session_id.clone();
@@ -2107,9 +2390,8 @@ async fn connect_daemon(
 
     // Set up TLS connector for TCP mode
     let tls_connector = if socket_path.contains(':') {
-        let tls_dir = omnish_transport::tls::default_tls_dir();
-        let cert_path = tls_dir.join("cert.pem");
-        match omnish_transport::tls::make_connector(&cert_path) {
+        let ca_cert = ca_cert_path.map(std::path::PathBuf::from);
+        match omnish_transport::tls::ClientTls::new(ca_cert.as_deref()) {
             Ok(c) => Some(c),
             Err(e) => {
                 notice(&format!("[omnish] Failed to set up TLS: {}", e));
@@ -2121,13 +2403,16 @@ async fn connect_daemon(
         None
     };
 
-    match RpcClient::connect_with_reconnect_full(
+    let member_token = auth_token.clone();
+    let member_tls = tls_connector.clone();
+    match RpcClient::connect_with_reconnect_backoff(
         &socket_path,
         tls_connector,
         move |rpc| {
             let sid = sid.clone();
             let psid = psid.clone();
             let caddr = caddr.clone();
+            let env_vars = env_vars.clone();
             let rpc = rpc.clone();
             let buffer = buffer.clone();
             let token = auth_token.clone();
@@ -2165,19 +2450,14 @@ async fn connect_daemon(
                             update_needed.store(true, Ordering::Relaxed);
                         }
 
-                        if !result.ok {
+                        // The daemon checked our version; check its version too
+                        if let Err(e) = auth_version_check(result) {
                             let behind = if omnish_protocol::message::PROTOCOL_VERSION < result.protocol_version {
                                 "client"
                             } else {
                                 "daemon"
                             };
-                            notice(&format!(
-                                "[omnish] Protocol mismatch \
-                                 (client={}, daemon={}), waiting for {} upgrade...",
-                                omnish_protocol::message::PROTOCOL_VERSION,
-                                result.protocol_version,
-                                behind
-                            ));
+                            notice(&format!("[omnish] {}, waiting for {} upgrade...", e, behind));
                             // Don't fail - keep connection alive for update messages
                             return Ok(());
                         }
@@ -2189,7 +2469,7 @@ async fn connect_daemon(
                 }
 
                 // Then register session (only if auth succeeded)
-                let attrs = probe::default_session_probes(child_pid, caddr).collect_all();
+                let attrs = probe::default_session_probes(child_pid, caddr, &env_vars).collect_all();
                 event_log::push("reconnect_cb: sending SessionStart");
                 rpc.call(Message::SessionStart(SessionStart {
                     session_id: sid,
@@ -2198,10 +2478,19 @@ async fn connect_daemon(
                     attrs,
                 })).await?;
 
+                match rpc.call(Message::HealthCheck).await {
+                    Ok(Message::HealthStatus { uptime_secs, session_count, llm_available, stream_write_errors }) => {
+                        tracing::info!(
+                            "daemon health: uptime={}s sessions={} llm_available={} stream_write_errors={}",
+                            uptime_secs, session_count, llm_available, stream_write_errors
+                        );
+                    }
+                    Ok(other) => tracing::info!("daemon health: unexpected reply {:?}", std::mem::discriminant(&other)),
+                    Err(e) => tracing::info!("daemon health: check failed: {}", e),
+                }
+
                 // Replay buffered messages after successful SessionStart
-                let buffered: Vec<Message> = {
-                    buffer.lock().await.drain(..).collect()
-                };
+                let buffered: Vec<Message> = buffer.lock().await.drain();
                 if !buffered.is_empty() {
                     event_log::push(format!("reconnect_cb: replaying {} buffered msgs", buffered.len()));
                 }
@@ -2222,6 +2511,7 @@ async fn connect_daemon(
         Some(|| {
             event_log::push("disconnect: connection lost to daemon");
         }),
+        backoff,
     ).await {
         Ok(client) => {
             if client.is_connected().await {
@@ -2231,7 +2521,17 @@ async fn connect_daemon(
                 notice(&format!("[omnish] Socket: {}", socket_path));
                 notice("[omnish] To start: omnish-daemon");
             }
-            Some(client)
+            let mut clients = vec![client];
+            for _ in 1..pool_size {
+                match connect_pool_member(&socket_path, member_tls.clone(), member_token.clone(), backoff).await {
+                    Ok(member) => clients.push(member),
+                    Err(e) => {
+                        tracing::warn!("extra daemon connection failed: {}", e);
+                        break;
+                    }
+                }
+            }
+            Some(RpcClientPool::from_clients(clients))
         }
         Err(e) => {
             // This should not happen with our updated connect_with_reconnect,
@@ -2244,6 +2544,100 @@ async fn connect_daemon(
     }
 }
 
+/// Open an extra pooled connection. It only authenticates: the session is
+/// registered on the first connection, which carries all session traffic.
+async fn connect_pool_member(
+    socket_path: &str,
+    tls_connector: Option<omnish_transport::tls::ClientTls>,
+    token: String,
+    backoff: BackoffConfig,
+) -> anyhow::Result<RpcClient> {
+    RpcClient::connect_with_reconnect_backoff(
+        socket_path,
+        tls_connector,
+        move |rpc| {
+            let rpc = rpc.clone();
+            let token = token.clone();
+            Box::pin(async move {
+                let resp = rpc.call(Message::Auth(Auth {
+                    token,
+                    protocol_version: omnish_protocol::message::PROTOCOL_VERSION,
+                })).await?;
+                match resp {
+                    Message::AuthResult(result) if !result.ok => anyhow::bail!("auth rejected"),
+                    _ => Ok(()),
+                }
+            })
+        },
+        None::<fn()>,
+        None::<fn()>,
+        backoff,
+    ).await
+}
+
+/// Outcome of the Auth exchange as a version check. A rejected Auth is
+/// reported as the daemon's mismatch; an accepted one still fails when the
+/// daemon is older than this build supports.
+fn auth_version_check(result: &omnish_protocol::message::AuthResult) -> anyhow::Result<()> {
+    if !result.ok {
+        anyhow::bail!(
+            "{}",
+            omnish_protocol::message::version_mismatch_message(
+                result.protocol_version,
+                omnish_protocol::message::PROTOCOL_VERSION,
+            )
+        );
+    }
+    omnish_protocol::message::check_protocol_version(result.protocol_version)
+}
+
+/// `omnish --health`: authenticate, send `HealthCheck` and print the answer.
+/// Returns the process exit code: 0 when the daemon has an LLM backend,
+/// 1 otherwise (including when it cannot be reached).
+async fn run_health_check(config: &omnish_common::config::ClientConfig) -> i32 {
+    let addr = std::env::var("OMNISH_SOCKET").unwrap_or_else(|_| config.daemon_addr.clone());
+    let ca_cert = config.tls.ca_cert_path.as_ref().map(std::path::PathBuf::from);
+    let status = async {
+        let token = omnish_common::auth::load_token(&omnish_common::auth::default_token_path())?;
+        let rpc = match omnish_transport::parse_addr(&addr) {
+            omnish_transport::TransportAddr::Unix(path) => RpcClient::connect_unix(&path).await?,
+            omnish_transport::TransportAddr::Tcp(hp) | omnish_transport::TransportAddr::TcpTls(hp) => {
+                let server_name = omnish_transport::tls::server_name(&hp, ca_cert.as_deref());
+                RpcClient::connect_tls(&hp, server_name, ca_cert.as_deref()).await?
+            }
+        };
+        let auth = rpc
+            .call(Message::Auth(Auth {
+                token,
+                protocol_version: omnish_protocol::message::PROTOCOL_VERSION,
+            }))
+            .await?;
+        if let Message::AuthResult(result) = &auth {
+            auth_version_check(result)?;
+        }
+        rpc.call(Message::HealthCheck).await
+    }
+    .await;
+
+    match status {
+        Ok(Message::HealthStatus { uptime_secs, session_count, llm_available, stream_write_errors }) => {
+            println!("uptime:              {}s", uptime_secs);
+            println!("active sessions:     {}", session_count);
+            println!("llm available:       {}", llm_available);
+            println!("stream write errors: {}", stream_write_errors);
+            if llm_available { 0 } else { 1 }
+        }
+        Ok(_) => {
+            eprintln!("omnish: daemon at {} does not support health checks", addr);
+            1
+        }
+        Err(e) => {
+            eprintln!("omnish: daemon at {} unreachable: {}", addr, e);
+            1
+        }
+    }
+}
+
 /// Respawn the shell with or without sandbox restrictions.
 /// Uses the unified sandbox backend (bwrap/landlock/seatbelt).
 fn handle_lock(
@@ -2395,6 +2789,7 @@ static mut MASTER_FD: i32 = -1;
 ///
 /// Follows `\r` (reset to 0), printable ASCII, multi-byte UTF-8 characters,
 /// and skips ANSI escape sequences (CSI, OSC) so they don't inflate the count.
+/// CSI cursor moves (CUP, CHA, CUF/CUB, CUU/CUD) update the position.
 /// CJK / fullwidth characters are counted as 2 columns using `unicode-width`.
 /// Used to save/restore cursor column when dismissing the omnish UI.
 struct CursorTracker {
@@ -2504,12 +2899,25 @@ impl CursorTracker {
                 let n = self.parse_csi_param_1().max(1);
                 self.row = self.row.saturating_sub(n);
             }
-            // CUB - Cursor Back: \x1b[nD  (handled here for completeness)
             // CUD - Cursor Down: \x1b[nB
             b'B' => {
                 let n = self.parse_csi_param_1().max(1);
                 self.row = self.row.saturating_add(n);
             }
+            // CUF - Cursor Forward: \x1b[nC
+            b'C' => {
+                let n = self.parse_csi_param_1().max(1);
+                self.col = self.col.saturating_add(n);
+            }
+            // CUB - Cursor Back: \x1b[nD
+            b'D' => {
+                let n = self.parse_csi_param_1().max(1);
+                self.col = self.col.saturating_sub(n);
+            }
+            // CHA - Cursor Horizontal Absolute: \x1b[nG (1-based)
+            b'G' => {
+                self.col = self.parse_csi_param_1().max(1) - 1;
+            }
             // CUP / HVP - Cursor Position: \x1b[n;mH or \x1b[n;mf
             b'H' | b'f' => {
                 let (r, c) = self.parse_csi_param_2();
@@ -2775,7 +3183,7 @@ fn bwrap_hint(preferred: omnish_plugin::SandboxBackendType) -> &'static str {
 #[allow(clippy::too_many_arguments)]
 async fn enter_chat_mode(
     initial_msg: Option<String>,
-    daemon_conn: &Option<RpcClient>,
+    daemon_conn: &Option<RpcClientPool>,
     chat_history: &mut VecDeque<String>,
     last_thread_id: &mut Option<String>,
     session_id: &str,
@@ -2797,7 +3205,7 @@ async fn enter_chat_mode(
     set_chat_tmux_title(None);
     let saved_input = shell_input.input().to_string();
 
-    let (exit_action, pending_cd) = if let Some(ref rpc) = daemon_conn {
+    let (exit_action, pending_cd, pending_input) = if let Some(ref rpc) = daemon_conn {
         let shell_pid = proxy.child_pid() as u32;
         let dbg_fn = || debug_client_state(
             shell_input, interceptor, shell_completer,
@@ -2806,10 +3214,12 @@ async fn enter_chat_mode(
         );
         let action;
         let pending_cd;
+        let pending_input;
         {
             let mut session = chat_session::ChatSession::new(
                 std::mem::take(chat_history),
                 config.shell.extended_unicode,
+                QueryOptions::from_config(config),
                 Arc::clone(&sandbox_state),
                 Arc::clone(&screen_capture),
             );
@@ -2830,13 +3240,14 @@ async fn enter_chat_mode(
                 std::env::remove_var("OMNISH_LAST_THREAD_ID");
             }
             pending_cd = session.pending_cd().map(String::from);
+            pending_input = session.pending_shell_input().map(String::from);
             *chat_history = session.into_history();
         }
-        (action, pending_cd)
+        (action, pending_cd, pending_input)
     } else {
         let err = display::render_error(i18n::t("error.daemon_not_connected"));
         nix::unistd::write(std::io::stdout(), err.as_bytes()).ok();
-        (chat_session::ChatExitAction::Normal, None)
+        (chat_session::ChatExitAction::Normal, None, None)
     };
 
     nix::unistd::write(std::io::stdout(), b"\r\x1b[K").ok();
@@ -2856,8 +3267,10 @@ async fn enter_chat_mode(
     } else {
         proxy.write_all(b"\r").ok();
     }
-    if !saved_input.is_empty() {
-        proxy.write_all(saved_input.as_bytes()).ok();
+    // A command picked in /hist replaces the saved input; it is typed, not run.
+    let restored = pending_input.unwrap_or(saved_input);
+    if !restored.is_empty() {
+        proxy.write_all(restored.as_bytes()).ok();
     }
     exit_action
 }
@@ -2868,7 +3281,7 @@ fn debug_client_state(
     shell_input: &shell_input::ShellInputTracker,
     interceptor: &interceptor::InputInterceptor,
     shell_completer: &completion::ShellCompleter,
-    daemon_conn: &Option<RpcClient>,
+    daemon_conn: &Option<RpcClientPool>,
     _osc133_detector: &omnish_tracker::osc133_detector::Osc133Detector,
     last_readline: &Option<String>,
     shell_pid: u32,
@@ -3015,8 +3428,8 @@ extern "C" fn sigwinch_handler(_sig: libc::c_int) {
 ///
 /// Full-screen programs (vim, less, htop, etc.) switch to the alternate screen
 /// buffer via these CSI sequences:
-///   - Enter: \x1b[?1049h or \x1b[?47h
-///   - Exit:  \x1b[?1049l or \x1b[?47l
+///   - Enter: \x1b[?1049h, \x1b[?1047h or \x1b[?47h
+///   - Exit:  \x1b[?1049l, \x1b[?1047l or \x1b[?47l
 ///
 /// We scan output bytes with a small state machine to detect these transitions
 /// without needing a full VTE parser.
@@ -3056,6 +3469,7 @@ impl AltScreenDetector {
 
                 // We're looking for patterns like:
                 //   \x1b [ ? 1049 h/l
+                //   \x1b [ ? 1047 h/l
                 //   \x1b [ ? 47 h/l
                 // Max length we care about: \x1b[?1049h = 9 bytes
                 if self.seq_buf.len() > 10 {
@@ -3069,8 +3483,9 @@ impl AltScreenDetector {
                     let s = &self.seq_buf;
                     let entering = byte == b'h';
 
-                    // Check \x1b[?1049h/l
+                    // Check \x1b[?1049h/l, \x1b[?1047h/l, \x1b[?47h/l
                     if (s == b"\x1b[?1049h" || s == b"\x1b[?1049l"
+                        || s == b"\x1b[?1047h" || s == b"\x1b[?1047l"
                         || s == b"\x1b[?47h" || s == b"\x1b[?47l")
                         && self.active != entering
                     {
@@ -3143,6 +3558,33 @@ pub(crate) fn cmd_display_str(json: &serde_json::Value) -> String {
         .to_string()
 }
 
+/// Saves long query responses for `less` instead of letting them scroll past.
+#[derive(Clone, Copy)]
+pub(crate) struct ResponsePager {
+    /// See `ClientConfig::pager_threshold_lines`.
+    pub threshold_lines: Option<u16>,
+}
+
+/// Client settings for `/` queries sent to the daemon.
+#[derive(Clone, Copy, Default)]
+pub(crate) struct QueryOptions {
+    /// See `ClientConfig::pager_threshold_lines`.
+    pub pager_threshold_lines: Option<u16>,
+    /// See `ClientConfig::query_timeout_ms`; `None` waits forever.
+    pub timeout: Option<std::time::Duration>,
+}
+
+impl QueryOptions {
+    fn from_config(config: &omnish_common::config::ClientConfig) -> Self {
+        Self {
+            pager_threshold_lines: config.pager_threshold_lines,
+            timeout: Some(config.query_timeout_ms)
+                .filter(|ms| *ms > 0)
+                .map(std::time::Duration::from_millis),
+        }
+    }
+}
+
 /// Display a command result or write to file if redirected.
 /// `cwd` is used to resolve relative redirect paths.
 pub(crate) fn handle_command_result(content: &str, redirect: Option<&str>, cwd: Option<&str>) {
@@ -3172,19 +3614,80 @@ pub(crate) fn handle_command_result(content: &str, redirect: Option<&str>, cwd:
     }
 }
 
+/// What came back for a query sent with `call_stream`.
+#[derive(Default)]
+struct QueryReply {
+    /// The final Response; None on disconnect or timeout.
+    response: Option<Response>,
+    /// Text of the StreamingChunks that preceded it.
+    streamed: String,
+    timed_out: bool,
+}
+
+/// Read `rx` until the Response to `request_id`, passing the text of each
+/// StreamingChunk to `on_chunk` as it arrives. Gives up at `deadline`.
+async fn receive_query_reply(
+    rx: &mut mpsc::Receiver<Message>,
+    request_id: &str,
+    deadline: Option<tokio::time::Instant>,
+    mut on_chunk: impl FnMut(&str),
+) -> QueryReply {
+    let mut reply = QueryReply::default();
+    loop {
+        let next = match deadline {
+            Some(at) => match tokio::time::timeout_at(at, rx.recv()).await {
+                Ok(next) => next,
+                Err(_) => {
+                    reply.timed_out = true;
+                    return reply;
+                }
+            },
+            None => rx.recv().await,
+        };
+        match next {
+            Some(Message::StreamingChunk { request_id: rid, chunk, .. }) if rid == request_id => {
+                if chunk.is_empty() {
+                    continue;
+                }
+                on_chunk(&chunk);
+                reply.streamed.push_str(&chunk);
+            }
+            Some(Message::Response(resp)) if resp.request_id == request_id => {
+                reply.response = Some(resp);
+                return reply;
+            }
+            Some(_) => continue,
+            None => return reply,
+        }
+    }
+}
+
+/// Final Response text still to show after `streamed` was printed: all of
+/// it, unless it only repeats the stream. The daemon answers a stream that
+/// fails partway with an `Error:` Response.
+fn streamed_final_display<'a>(streamed: &str, final_display: &'a str) -> Option<&'a str> {
+    (!final_display.is_empty() && final_display != streamed).then_some(final_display)
+}
+
 /// Send a query to the daemon and display the result.
 ///
 /// If `redirect` is Some, the response is written to the given file path instead of stdout.
 /// If `show_thinking` is true, a thinking spinner is shown while waiting for the response
 /// and a separator is appended after.
+/// With a `pager`, a long response is saved for paging; the returned `less`
+/// command is for the caller to leave at the shell prompt.
+#[allow(clippy::too_many_arguments)]
 async fn send_daemon_query(
     query: &str,
     session_id: &str,
-    rpc: &RpcClient,
+    rpc: &RpcClientPool,
     redirect: Option<&str>,
     show_thinking: bool,
     cwd: Option<&str>,
-) {
+    model_override: Option<&str>,
+    pager: Option<ResponsePager>,
+    timeout: Option<std::time::Duration>,
+) -> Option<String> {
     let (_rows, cols) = get_terminal_size().unwrap_or((24, 80));
     let mut status = LineStatus::new(cols as usize, 5);
     if show_thinking {
@@ -3197,46 +3700,166 @@ async fn send_daemon_query(
         session_id: session_id.to_string(),
         query: query.to_string(),
         scope: RequestScope::AllSessions,
+        model_override: model_override.map(String::from),
+        timeout_ms: timeout.map(|t| t.as_millis() as u64),
+        format_hint: None,
     });
 
-    match rpc.call(request).await {
-        Ok(Message::Response(resp)) if resp.request_id == request_id => {
+    // LLM answers may arrive as StreamingChunk messages ahead of the final
+    // Response; builtin commands answer with a single Response. The whole
+    // exchange shares one deadline, so `call_with_timeout` (single reply)
+    // can't be used here.
+    let deadline = timeout.map(|t| tokio::time::Instant::now() + t);
+    let mut streamed = false;
+    let reply = match rpc.call_stream(request).await {
+        Ok(mut rx) => {
+            receive_query_reply(&mut rx, &request_id, deadline, |chunk| {
+                if redirect.is_some() {
+                    return;
+                }
+                if !streamed {
+                    streamed = true;
+                    let start = if show_thinking { status.clear() } else { String::new() };
+                    nix::unistd::write(std::io::stdout(), format!("{start}{NEWLINE}").as_bytes()).ok();
+                }
+                nix::unistd::write(std::io::stdout(), chunk.replace('\n', NEWLINE).as_bytes()).ok();
+            })
+            .await
+        }
+        Err(_) => QueryReply::default(),
+    };
+    let timed_out = reply.timed_out;
+    let final_resp = reply.response;
+
+    match final_resp {
+        Some(resp) => {
             let display = if let Some(json) = parse_cmd_response(&resp.content) {
                 cmd_display_str(&json)
             } else {
                 resp.content.clone()
             };
             if show_thinking {
-                std::fs::write("/tmp/omnish_last_response.txt", &display).ok();
-                nix::unistd::write(std::io::stdout(), status.clear().as_bytes()).ok();
+                pager::save(session_id, "last-response", &display).ok();
+                if !streamed {
+                    nix::unistd::write(std::io::stdout(), status.clear().as_bytes()).ok();
+                }
+            }
+            let (rows, _) = get_terminal_size().unwrap_or((24, 80));
+            let page = redirect.is_none()
+                && pager.is_some_and(|p| pager::should_page(&display, rows, p.threshold_lines));
+            let mut paged = None;
+            if streamed {
+                nix::unistd::write(std::io::stdout(), NEWLINE.as_bytes()).ok();
+                // The stream broke off or was replaced: show how it ended
+                if let Some(rest) = streamed_final_display(&reply.streamed, &display) {
+                    let output = if rest.starts_with("Error:") {
+                        display::render_error(rest)
+                    } else {
+                        display::render_response(rest)
+                    };
+                    nix::unistd::write(std::io::stdout(), output.as_bytes()).ok();
+                }
+                // Already on screen, but scrolled past: offer it in the pager too
+                if page {
+                    paged = pager::offer(session_id, "response", &display);
+                }
+            } else {
+                if page {
+                    paged = pager::offer(session_id, "response", &display);
+                }
+                if paged.is_none() {
+                    handle_command_result(&display, redirect, cwd);
+                }
             }
-            handle_command_result(&display, redirect, cwd);
             if show_thinking {
                 let (_rows, cols) = get_terminal_size().unwrap_or((24, 80));
                 let separator = display::render_separator(cols);
                 let sep_line = format!("{}{NEWLINE}", separator);
                 nix::unistd::write(std::io::stdout(), sep_line.as_bytes()).ok();
             }
+            paged
         }
-        _ => {
+        None => {
             nix::unistd::write(std::io::stdout(), status.clear().as_bytes()).ok();
+            let msg = match timeout {
+                Some(t) if timed_out => i18n::tf("error.query_timed_out", &[("secs", &t.as_secs().to_string())]),
+                _ => i18n::t("error.failed_receive_response_main").to_string(),
+            };
+            let err = display::render_error(&msg);
+            nix::unistd::write(std::io::stdout(), err.as_bytes()).ok();
+            None
+        }
+    }
+}
+
+/// Run `/replay`: write the replayed output bytes straight to the terminal
+/// as `StreamingChunk`s arrive, then show any message in the final Response
+/// (usage or errors) on a fresh line.
+async fn replay_session(query: &str, session_id: &str, rpc: &RpcClientPool) {
+    let request_id = Uuid::new_v4().to_string()[..8].to_string();
+    let request = Message::Request(Request {
+        request_id: request_id.clone(),
+        session_id: session_id.to_string(),
+        query: query.to_string(),
+        scope: RequestScope::AllSessions,
+        model_override: None,
+        timeout_ms: None,
+        format_hint: None,
+    });
+    let mut rx = match rpc.call_stream(request).await {
+        Ok(rx) => rx,
+        Err(_) => {
             let err = display::render_error(i18n::t("error.failed_receive_response_main"));
             nix::unistd::write(std::io::stdout(), err.as_bytes()).ok();
+            return;
+        }
+    };
+    nix::unistd::write(std::io::stdout(), NEWLINE.as_bytes()).ok();
+    loop {
+        match rx.recv().await {
+            Some(Message::StreamingChunk { request_id: rid, chunk, done }) if rid == request_id => {
+                nix::unistd::write(std::io::stdout(), chunk.as_bytes()).ok();
+                if done {
+                    // Leave the replayed screen state behind a clean line
+                    // so the prompt redraws below it.
+                    nix::unistd::write(std::io::stdout(), format!("\x1b[0m{NEWLINE}").as_bytes()).ok();
+                }
+            }
+            Some(Message::Response(resp)) if resp.request_id == request_id => {
+                let display = parse_cmd_response(&resp.content)
+                    .map(|json| cmd_display_str(&json))
+                    .unwrap_or(resp.content);
+                if !display.is_empty() {
+                    let output = format!("{}{NEWLINE}", display.replace('\n', NEWLINE));
+                    nix::unistd::write(std::io::stdout(), output.as_bytes()).ok();
+                }
+                break;
+            }
+            Some(_) => continue,
+            None => {
+                let err = display::render_error(i18n::t("error.failed_receive_response_main"));
+                nix::unistd::write(std::io::stdout(), err.as_bytes()).ok();
+                break;
+            }
         }
     }
 }
 
 /// Handle a /command in chat mode. Returns true if the command was handled.
+/// When its output was saved for paging, `shell_input` is set to the `less`
+/// command and chat mode should exit so it can be placed at the shell prompt.
 #[allow(clippy::too_many_arguments)]
 pub(crate) async fn handle_slash_command(
     trimmed: &str,
     session_id: &str,
-    rpc: &RpcClient,
+    rpc: &RpcClientPool,
     proxy: &PtyProxy,
     cwd: Option<&str>,
     client_debug_fn: &dyn Fn() -> String,
     cursor_col: u16,
     cursor_row: u16,
+    query_opts: QueryOptions,
+    shell_input: &mut Option<String>,
 ) -> bool {
     // /update is intercepted in DaemonQuery handling below
     // (it needs process state: proxy fd/pid)
@@ -3281,9 +3904,12 @@ pub(crate) async fn handle_slash_command(
                 let tid = std::env::var("OMNISH_LAST_THREAD_ID").ok().filter(|s| !s.is_empty());
                 exec_update(proxy, session_id, cursor_col, cursor_row, tid.as_deref());
                 return true; // Only reached if exec failed
+            } else if query.starts_with("__cmd:replay") && redirect.is_none() {
+                replay_session(&query, session_id, rpc).await;
+                return true;
             }
             if let Some(path) = redirect.as_deref() {
-                send_daemon_query(&query, session_id, rpc, Some(path), false, cwd).await;
+                send_daemon_query(&query, session_id, rpc, Some(path), false, cwd, None, None, query_opts.timeout).await;
             } else {
                 let request_id = Uuid::new_v4().to_string()[..8].to_string();
                 let request = Message::Request(Request {
@@ -3291,6 +3917,9 @@ pub(crate) async fn handle_slash_command(
                     session_id: session_id.to_string(),
                     query,
                     scope: RequestScope::AllSessions,
+                    model_override: None,
+                    timeout_ms: None,
+                    format_hint: None,
                 });
                 match rpc.call(request).await {
                     Ok(Message::Response(resp)) if resp.request_id == request_id => {
@@ -3316,6 +3945,40 @@ pub(crate) async fn handle_slash_command(
             }
             true
         }
+        command::ChatAction::Export { output_path } => {
+            let path = std::path::Path::new(&output_path);
+            let resolved = match cwd {
+                Some(cwd) if path.is_relative() => std::path::Path::new(cwd).join(path),
+                _ => path.to_path_buf(),
+            };
+            let request = Message::ExportRequest {
+                session_id: session_id.to_string(),
+                format: ExportFormat::Markdown,
+            };
+            let out = match rpc.call(request).await {
+                Ok(Message::ExportResult { content, command_count, error: None }) => {
+                    match tokio::fs::write(&resolved, content).await {
+                        Ok(()) => display::render_response(&format!(
+                            "Exported {} commands to {}", command_count, resolved.display()
+                        )),
+                        Err(e) => display::render_error(&format!("Export failed: {}", e)),
+                    }
+                }
+                Ok(Message::ExportResult { error: Some(e), .. }) => {
+                    display::render_error(&format!("Export failed: {}", e))
+                }
+                _ => display::render_error(i18n::t("error.failed_receive_response_main")),
+            };
+            nix::unistd::write(std::io::stdout(), out.as_bytes()).ok();
+            true
+        }
+        command::ChatAction::LlmQueryWithModel { query, model } => {
+            let pager = ResponsePager { threshold_lines: query_opts.pager_threshold_lines };
+            *shell_input =
+                send_daemon_query(&query, session_id, rpc, None, true, cwd, Some(&model), Some(pager), query_opts.timeout)
+                    .await;
+            true
+        }
         command::ChatAction::LlmQuery(_) => false,
     }
 }
@@ -3326,6 +3989,49 @@ pub(crate) async fn handle_slash_command(
 mod tests {
     use super::*;
 
+    #[tokio::test]
+    async fn test_stream_failing_partway_shows_final_error() {
+        let (tx, mut rx) = mpsc::channel(8);
+        let chunk = |text: &str, done| Message::StreamingChunk { request_id: "r1".into(), chunk: text.into(), done };
+        tx.send(chunk("curl needs ", false)).await.unwrap();
+        tx.send(chunk("", true)).await.unwrap();
+        tx.send(Message::Response(Response {
+            request_id: "r1".into(),
+            content: "Error: stream reset by peer".into(),
+            is_streaming: false,
+            is_final: true,
+        }))
+        .await
+        .unwrap();
+
+        let mut shown = String::new();
+        let reply = receive_query_reply(&mut rx, "r1", None, |c| shown.push_str(c)).await;
+        assert_eq!(shown, "curl needs ");
+        assert_eq!(reply.streamed, "curl needs ");
+        let content = reply.response.unwrap().content;
+        assert_eq!(streamed_final_display(&reply.streamed, &content), Some("Error: stream reset by peer"));
+
+        // A complete stream is not printed twice
+        assert_eq!(streamed_final_display("curl needs --proxy", "curl needs --proxy"), None);
+    }
+
+    #[test]
+    fn test_auth_version_check() {
+        use omnish_protocol::message::{AuthResult, MIN_COMPATIBLE_VERSION, PROTOCOL_VERSION};
+        let result = |ok, protocol_version| AuthResult { ok, protocol_version, daemon_version: String::new() };
+
+        assert!(auth_version_check(&result(true, PROTOCOL_VERSION)).is_ok());
+        // Rejected by a newer daemon: it expected its own version
+        let err = auth_version_check(&result(false, PROTOCOL_VERSION + 1)).unwrap_err().to_string();
+        assert_eq!(
+            err,
+            format!("protocol version mismatch: expected {}, got {}", PROTOCOL_VERSION + 1, PROTOCOL_VERSION)
+        );
+        // Accepted by a daemon this build no longer supports
+        let err = auth_version_check(&result(true, MIN_COMPATIBLE_VERSION - 1)).unwrap_err().to_string();
+        assert!(err.starts_with("protocol version mismatch"), "{err}");
+    }
+
     /// Issue #588: build a minimal plugin bundle and verify that
     /// `extract_and_mirror_plugins` creates the expected files, deletes
     /// anything on disk that is not in the bundle, and preserves dotfiles
@@ -3410,6 +4116,15 @@ mod tests {
         assert_eq!(d.feed(b"\x1b[?47l"), Some(false));
     }
 
+    #[test]
+    fn test_alt_screen_detect_1047h() {
+        let mut d = AltScreenDetector::new();
+        assert_eq!(d.feed(b"\x1b[?1047h"), Some(true));
+        assert_eq!(d.feed(b"\x1b[?1047h"), None);
+        assert_eq!(d.feed(b"\x1b[?1047l"), Some(false));
+        assert_eq!(d.feed(b"\x1b[?1047l"), None);
+    }
+
     #[test]
     fn test_alt_screen_no_duplicate_events() {
         let mut d = AltScreenDetector::new();
@@ -3456,7 +4171,7 @@ mod tests {
 
         // Normal mode: ":" matches prefix → Buffering (awaiting timeout)
         assert_eq!(interceptor.feed_byte(b':'), InterceptAction::Buffering(vec![b':']));
-        assert_eq!(interceptor.expire_prefix(), Some(InterceptAction::Chat(String::new())));
+        assert_eq!(interceptor.expire_prefix(), Some(InterceptAction::Chat { message: String::new(), prefix_index: 0 }));
 
         // Reset for clean test
         interceptor.note_output(b"reset");
@@ -3476,7 +4191,25 @@ mod tests {
 
         // Back to normal: ":" should intercept again → Buffering
         assert_eq!(interceptor.feed_byte(b':'), InterceptAction::Buffering(vec![b':']));
-        assert_eq!(interceptor.expire_prefix(), Some(InterceptAction::Chat(String::new())));
+        assert_eq!(interceptor.expire_prefix(), Some(InterceptAction::Chat { message: String::new(), prefix_index: 0 }));
+    }
+
+    #[test]
+    fn test_alt_screen_1047_suppresses_interceptor() {
+        use interceptor::AlwaysIntercept;
+
+        let mut interceptor = InputInterceptor::new(":", "::", Box::new(AlwaysIntercept), false);
+        let mut detector = AltScreenDetector::new();
+
+        if let Some(active) = detector.feed(b"\x1b[?1047h") {
+            interceptor.set_suppressed(active);
+        }
+        assert_eq!(interceptor.feed_byte(b':'), InterceptAction::Forward(vec![b':']));
+
+        if let Some(active) = detector.feed(b"\x1b[?1047l") {
+            interceptor.set_suppressed(active);
+        }
+        assert_eq!(interceptor.feed_byte(b':'), InterceptAction::Buffering(vec![b':']));
     }
 
     // --- Message buffer tests ---
@@ -3507,6 +4240,9 @@ mod tests {
                 stream_offset: 0,
                 stream_length: 0,
                 exit_code: None,
+                exit_signal: None,
+                checksum: None,
+                tags: Vec::new(),
             },
         });
         assert!(should_buffer(&msg));
@@ -3536,34 +4272,126 @@ mod tests {
             session_id: "s1".to_string(),
             query: "test".to_string(),
             scope: RequestScope::CurrentSession,
+            model_override: None,
+            timeout_ms: None,
+            format_hint: None,
         })));
     }
 
-    #[tokio::test]
-    async fn test_buffer_cap_drops_oldest() {
-        let buffer: MessageBuffer = Arc::new(Mutex::new(VecDeque::new()));
-        {
-            let mut buf = buffer.lock().await;
-            for i in 0..MAX_BUFFER_SIZE + 1 {
-                let msg = Message::IoData(IoData {
-                    session_id: "s1".to_string(),
-                    direction: IoDirection::Output,
-                    timestamp_ms: i as u64,
-                    data: vec![i as u8],
-                });
-                if buf.len() >= MAX_BUFFER_SIZE {
-                    buf.pop_front();
-                }
-                buf.push_back(msg);
-            }
-            assert_eq!(buf.len(), MAX_BUFFER_SIZE);
-            // Oldest (timestamp 0) was dropped; front should be timestamp 1
-            if let Some(Message::IoData(io)) = buf.front() {
-                assert_eq!(io.timestamp_ms, 1);
-            } else {
-                panic!("expected IoData at front of buffer");
-            }
+    fn io_msg(ts: u64) -> Message {
+        Message::IoData(IoData {
+            session_id: "s1".to_string(),
+            direction: IoDirection::Output,
+            timestamp_ms: ts,
+            data: vec![ts as u8],
+        })
+    }
+
+    fn complete_msg(command_id: &str) -> Message {
+        Message::CommandComplete(omnish_protocol::message::CommandComplete {
+            session_id: "s1".to_string(),
+            record: omnish_store::command::CommandRecord {
+                command_id: command_id.to_string(),
+                session_id: "s1".to_string(),
+                command_line: None,
+                cwd: None,
+                started_at: 0,
+                ended_at: None,
+                output_summary: String::new(),
+                stream_offset: 0,
+                stream_length: 0,
+                exit_code: None,
+                exit_signal: None,
+                checksum: None,
+                tags: Vec::new(),
+            },
+        })
+    }
+
+    fn buffered_ids(buf: &mut ReplayBuffer) -> Vec<String> {
+        buf.drain()
+            .iter()
+            .map(|m| match m {
+                Message::CommandComplete(cc) => cc.record.command_id.clone(),
+                Message::IoData(io) => format!("io{}", io.timestamp_ms),
+                other => panic!("unexpected {:?}", other),
+            })
+            .collect()
+    }
+
+    #[test]
+    fn test_buffer_cap_drops_oldest() {
+        let policy = BufferPolicy { max_len: 10_000, prioritize_commands: false };
+        let mut buf = ReplayBuffer::default();
+        for i in 0..policy.max_len + 1 {
+            buf.push(io_msg(i as u64), policy);
+        }
+        let drained = buf.drain();
+        assert_eq!(drained.len(), policy.max_len);
+        // Oldest (timestamp 0) was dropped; first should be timestamp 1
+        if let Some(Message::IoData(io)) = drained.first() {
+            assert_eq!(io.timestamp_ms, 1);
+        } else {
+            panic!("expected IoData at front of buffer");
+        }
+    }
+
+    #[test]
+    fn test_buffer_full_keeps_command_complete_over_io() {
+        let policy = BufferPolicy { max_len: 10_000, prioritize_commands: true };
+        let mut buf = ReplayBuffer::default();
+        buf.push(complete_msg("c0"), policy);
+        for i in 0..policy.max_len - 1 {
+            buf.push(io_msg(i as u64), policy);
+        }
+        // 10,001st message: the buffer is full
+        buf.push(complete_msg("c1"), policy);
+
+        let ids = buffered_ids(&mut buf);
+        assert_eq!(ids.len(), policy.max_len);
+        assert_eq!(ids.first().map(String::as_str), Some("c0"), "old command kept");
+        assert_eq!(ids.last().map(String::as_str), Some("c1"), "new command buffered");
+        assert!(!ids.contains(&"io0".to_string()), "oldest IoData dropped");
+        assert!(ids.contains(&"io1".to_string()));
+    }
+
+    #[test]
+    fn test_buffer_prioritize_commands_drops_io_first() {
+        let policy = BufferPolicy { max_len: 3, prioritize_commands: true };
+        let mut buf = ReplayBuffer::default();
+        buf.push(complete_msg("c1"), policy);
+        buf.push(io_msg(1), policy);
+        buf.push(io_msg(2), policy);
+        buf.push(complete_msg("c2"), policy);
+        buf.push(complete_msg("c3"), policy);
+        assert_eq!(buffered_ids(&mut buf), vec!["c1", "c2", "c3"]);
+
+        // With no IoData left, the oldest command goes
+        for id in ["c1", "c2", "c3", "c4"] {
+            buf.push(complete_msg(id), policy);
         }
+        assert_eq!(buffered_ids(&mut buf), vec!["c2", "c3", "c4"]);
+    }
+
+    #[test]
+    fn test_buffer_without_priority_drops_commands_too() {
+        let policy = BufferPolicy { max_len: 2, prioritize_commands: false };
+        let mut buf = ReplayBuffer::default();
+        buf.push(complete_msg("c1"), policy);
+        buf.push(io_msg(1), policy);
+        buf.push(io_msg(2), policy);
+        assert_eq!(buffered_ids(&mut buf), vec!["io1", "io2"]);
+    }
+
+    #[test]
+    fn test_buffer_drain_keeps_send_order() {
+        let policy = BufferPolicy { max_len: 10, prioritize_commands: true };
+        let mut buf = ReplayBuffer::default();
+        buf.push(io_msg(1), policy);
+        buf.push(complete_msg("c1"), policy);
+        buf.push(io_msg(2), policy);
+        assert_eq!(buffered_ids(&mut buf), vec!["io1", "c1", "io2"]);
+        assert!(buf.drain().is_empty());
     }
 
     // --- CursorTracker tests ---
@@ -3714,6 +4542,24 @@ mod tests {
         assert_eq!(t.col, 0);
     }
 
+    #[test]
+    fn test_col_tracker_horizontal_moves() {
+        let mut t = CursorTracker::new();
+        t.feed(b"prompt$ ");
+        // \x1b[5G - absolute column 5 (1-based)
+        t.feed(b"\x1b[5G");
+        assert_eq!(t.col, 4);
+        // \x1b[3C - forward 3
+        t.feed(b"\x1b[3C");
+        assert_eq!(t.col, 7);
+        // \x1b[2D - back 2
+        t.feed(b"\x1b[2D");
+        assert_eq!(t.col, 5);
+        // No parameter means 1; back saturates at column 0
+        t.feed(b"\x1b[C\x1b[G\x1b[9D");
+        assert_eq!(t.col, 0);
+    }
+
     // --- DSR detector tests ---
 
     #[test]
@@ -0,0 +1,146 @@
+//! Output too tall for the terminal is saved to a private file under
+//! `<omnish_dir>/pager/` and handed back to the shell as a `less` command
+//! typed at the prompt. Files are owner-only (0600), named per session so
+//! each session overwrites its own, and pruned once they go stale.
+
+use std::fs;
+use std::io::Write;
+use std::os::unix::fs::{OpenOptionsExt, PermissionsExt};
+use std::path::{Path, PathBuf};
+use std::time::{Duration, SystemTime};
+
+use crate::display::{DIM, NEWLINE, RESET};
+
+/// Pager files older than this are removed on the next save.
+const STALE_AFTER: Duration = Duration::from_secs(24 * 60 * 60);
+
+/// Whether output goes to the pager on a terminal of `rows` rows.
+/// `threshold_lines` is `ClientConfig::pager_threshold_lines`.
+pub fn should_page(content: &str, rows: u16, threshold_lines: Option<u16>) -> bool {
+    match threshold_lines {
+        Some(0) => false,
+        Some(n) => content.lines().count() > n as usize,
+        None => crate::display::is_response_long(content, rows),
+    }
+}
+
+fn pager_dir() -> PathBuf {
+    omnish_common::config::omnish_dir().join("pager")
+}
+
+/// Save `content` as `<session_id>-<kind>.txt` in the pager directory.
+pub fn save(session_id: &str, kind: &str, content: &str) -> std::io::Result<PathBuf> {
+    save_in(&pager_dir(), &format!("{session_id}-{kind}.txt"), content)
+}
+
+fn save_in(dir: &Path, name: &str, content: &str) -> std::io::Result<PathBuf> {
+    fs::create_dir_all(dir)?;
+    fs::set_permissions(dir, fs::Permissions::from_mode(0o700))?;
+    prune_stale(dir, name);
+    let path = dir.join(name);
+    let mut file = fs::OpenOptions::new()
+        .write(true)
+        .create(true)
+        .truncate(true)
+        .mode(0o600)
+        .open(&path)?;
+    // `mode` only applies on create; tighten a file left by an older build
+    file.set_permissions(fs::Permissions::from_mode(0o600))?;
+    file.write_all(content.as_bytes())?;
+    Ok(path)
+}
+
+/// Remove files in `dir` not modified for `STALE_AFTER`, except `keep`.
+fn prune_stale(dir: &Path, keep: &str) {
+    let Ok(entries) = fs::read_dir(dir) else { return };
+    let now = SystemTime::now();
+    for entry in entries.flatten() {
+        if entry.file_name() == keep {
+            continue;
+        }
+        let stale = entry
+            .metadata()
+            .and_then(|m| m.modified())
+            .ok()
+            .and_then(|t| now.duration_since(t).ok())
+            .is_some_and(|age| age > STALE_AFTER);
+        if stale {
+            fs::remove_file(entry.path()).ok();
+        }
+    }
+}
+
+/// Shell input that opens `path` in `less`. It is typed at the prompt,
+/// not run, so it carries no trailing `\r`.
+pub fn command(path: &Path) -> String {
+    let path = path.display().to_string();
+    let safe = |c: char| c.is_ascii_alphanumeric() || "/._-+~".contains(c);
+    if path.chars().all(safe) {
+        format!("less {path}")
+    } else {
+        format!("less '{}'", path.replace('\'', r"'\''"))
+    }
+}
+
+/// Save `content` for paging and tell the user where it went. Returns the
+/// `less` command to leave at the shell prompt, or None if the file could
+/// not be written.
+pub fn offer(session_id: &str, kind: &str, content: &str) -> Option<String> {
+    let path = save(session_id, kind, content).ok()?;
+    let notice = format!(
+        "{DIM}{}{RESET}{NEWLINE}",
+        crate::i18n::tf("chat.output_paged", &[("path", &path.display().to_string())])
+    );
+    nix::unistd::write(std::io::stdout(), notice.as_bytes()).ok();
+    Some(command(&path))
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_should_page() {
+        let content = (0..200).map(|i| format!("line {i}")).collect::<Vec<_>>().join("\n");
+        assert!(should_page(&content, 24, None));
+        assert!(!should_page("short", 24, None));
+        // 0 disables paging; an explicit threshold overrides terminal height
+        assert!(!should_page(&content, 24, Some(0)));
+        assert!(!should_page(&content, 24, Some(300)));
+        assert!(should_page(&content, 500, Some(100)));
+    }
+
+    #[test]
+    fn test_command_is_typed_not_run() {
+        assert_eq!(command(Path::new("/home/u/.omnish/pager/s1-response.txt")), "less /home/u/.omnish/pager/s1-response.txt");
+        assert_eq!(command(Path::new("/tmp/my dir/it's.txt")), r"less '/tmp/my dir/it'\''s.txt'");
+    }
+
+    #[test]
+    fn test_save_is_owner_only() {
+        let tmp = tempfile::tempdir().unwrap();
+        let dir = tmp.path().join("pager");
+        let path = save_in(&dir, "s1-response.txt", "secret").unwrap();
+        assert_eq!(fs::read_to_string(&path).unwrap(), "secret");
+        assert_eq!(fs::metadata(&path).unwrap().permissions().mode() & 0o777, 0o600);
+        assert_eq!(fs::metadata(&dir).unwrap().permissions().mode() & 0o777, 0o700);
+
+        // An existing world-readable file is tightened and overwritten
+        fs::set_permissions(&path, fs::Permissions::from_mode(0o644)).unwrap();
+        save_in(&dir, "s1-response.txt", "new").unwrap();
+        assert_eq!(fs::read_to_string(&path).unwrap(), "new");
+        assert_eq!(fs::metadata(&path).unwrap().permissions().mode() & 0o777, 0o600);
+    }
+
+    #[test]
+    fn test_save_prunes_stale_files() {
+        let tmp = tempfile::tempdir().unwrap();
+        let old = save_in(tmp.path(), "old-response.txt", "x").unwrap();
+        let file = fs::File::options().write(true).open(&old).unwrap();
+        file.set_modified(SystemTime::now() - STALE_AFTER * 2).unwrap();
+        let recent = save_in(tmp.path(), "recent-context.txt", "y").unwrap();
+        save_in(tmp.path(), "s1-response.txt", "z").unwrap();
+        assert!(!old.exists());
+        assert!(recent.exists());
+    }
+}
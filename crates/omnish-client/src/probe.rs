@@ -10,6 +10,14 @@ fn process_basename(name: &str) -> &str {
 pub trait Probe: Send + Sync {
     fn key(&self) -> &str;
     fn collect(&self) -> Option<String>;
+
+    /// All attrs this probe reports. Probes that read several related values
+    /// from one source override this; the default is `key() = collect()`.
+    fn collect_attrs(&self) -> Vec<(String, String)> {
+        self.collect()
+            .map(|value| vec![(self.key().to_string(), value)])
+            .unwrap_or_default()
+    }
 }
 
 pub struct ProbeSet {
@@ -28,9 +36,7 @@ impl ProbeSet {
     pub fn collect_all(&self) -> HashMap<String, String> {
         let mut map = HashMap::new();
         for probe in &self.probes {
-            if let Some(value) = probe.collect() {
-                map.insert(probe.key().to_string(), value);
-            }
+            map.extend(probe.collect_attrs());
         }
         map
     }
@@ -89,6 +95,168 @@ impl Probe for ClientAddrProbe {
     }
 }
 
+/// Reports an environment variable as `env.<NAME>`. Empty values are skipped.
+pub struct EnvProbe {
+    key: String,
+    var: String,
+}
+
+impl EnvProbe {
+    pub fn new(var: &str) -> Self {
+        Self { key: format!("env.{}", var), var: var.to_string() }
+    }
+}
+
+impl Probe for EnvProbe {
+    fn key(&self) -> &str { &self.key }
+    fn collect(&self) -> Option<String> {
+        std::env::var(&self.var).ok().filter(|v| !v.is_empty())
+    }
+}
+
+/// Active Python virtualenv directory (`$VIRTUAL_ENV`) as `venv.path`.
+pub struct VenvPathProbe;
+impl Probe for VenvPathProbe {
+    fn key(&self) -> &str { "venv.path" }
+    fn collect(&self) -> Option<String> {
+        std::env::var("VIRTUAL_ENV").ok().filter(|v| !v.is_empty())
+    }
+}
+
+/// Python version of the active virtualenv as `venv.python_version`, read
+/// from `$VIRTUAL_ENV/pyvenv.cfg`.
+pub struct VenvPythonVersionProbe;
+impl Probe for VenvPythonVersionProbe {
+    fn key(&self) -> &str { "venv.python_version" }
+    fn collect(&self) -> Option<String> {
+        let venv = std::env::var("VIRTUAL_ENV").ok().filter(|v| !v.is_empty())?;
+        let cfg = std::fs::read_to_string(std::path::Path::new(&venv).join("pyvenv.cfg")).ok()?;
+        pyvenv_python_version(&cfg)
+    }
+}
+
+/// Extract the Python version from a `pyvenv.cfg`. The stdlib `venv` module
+/// writes `version = 3.11.4`; virtualenv and uv write `version_info = 3.11.4.final.0`.
+fn pyvenv_python_version(cfg: &str) -> Option<String> {
+    let mut version_info = None;
+    for line in cfg.lines() {
+        let Some((key, value)) = line.split_once('=') else { continue };
+        let value = value.trim();
+        match key.trim() {
+            "version" if !value.is_empty() => return Some(value.to_string()),
+            "version_info" if !value.is_empty() => {
+                let parts: Vec<&str> = value.split('.').take(3).collect();
+                version_info = Some(parts.join("."));
+            }
+            _ => {}
+        }
+    }
+    version_info
+}
+
+/// Active conda environment (`$CONDA_DEFAULT_ENV`) as `conda.env_name`.
+pub struct CondaEnvProbe;
+impl Probe for CondaEnvProbe {
+    fn key(&self) -> &str { "conda.env_name" }
+    fn collect(&self) -> Option<String> {
+        std::env::var("CONDA_DEFAULT_ENV").ok().filter(|v| !v.is_empty())
+    }
+}
+
+/// Active kubectl context from the kube config: `k8s.context`, plus
+/// `k8s.cluster`, `k8s.user` and `k8s.namespace` when the context sets them.
+/// A missing or malformed config reports nothing.
+pub struct KubeContextProbe {
+    path: Option<std::path::PathBuf>,
+}
+
+impl KubeContextProbe {
+    /// Reads the first file in `$KUBECONFIG`, else `~/.kube/config`.
+    pub fn new() -> Self {
+        Self::with_path(
+            std::env::var("KUBECONFIG")
+                .ok()
+                .and_then(|v| v.split(':').find(|p| !p.is_empty()).map(std::path::PathBuf::from))
+                .or_else(|| std::env::var("HOME").ok().map(|h| std::path::Path::new(&h).join(".kube/config"))),
+        )
+    }
+
+    pub fn with_path(path: Option<std::path::PathBuf>) -> Self {
+        Self { path }
+    }
+}
+
+impl Probe for KubeContextProbe {
+    fn key(&self) -> &str { "k8s.context" }
+    fn collect(&self) -> Option<String> {
+        self.collect_attrs().into_iter().find(|(k, _)| k == "k8s.context").map(|(_, v)| v)
+    }
+    fn collect_attrs(&self) -> Vec<(String, String)> {
+        let Some(text) = self.path.as_ref().and_then(|p| std::fs::read_to_string(p).ok()) else {
+            return Vec::new();
+        };
+        parse_kube_config(&text)
+    }
+}
+
+fn unquote(value: &str) -> &str {
+    let v = value.trim();
+    v.strip_prefix('"').and_then(|v| v.strip_suffix('"'))
+        .or_else(|| v.strip_prefix('\'').and_then(|v| v.strip_suffix('\'')))
+        .unwrap_or(v)
+}
+
+/// Pull the current context out of a kube config. Only handles the block
+/// style kubectl writes, which avoids a YAML dependency for four fields.
+fn parse_kube_config(text: &str) -> Vec<(String, String)> {
+    let mut current = None;
+    let mut section = "";
+    // Leaf `key: value` pairs of each item under `contexts:`
+    let mut contexts: Vec<HashMap<String, String>> = Vec::new();
+    for line in text.lines() {
+        let trimmed = line.trim();
+        if trimmed.is_empty() || trimmed.starts_with('#') {
+            continue;
+        }
+        let top_level = !line.starts_with(' ') && !line.starts_with('-');
+        let Some((key, value)) = trimmed.trim_start_matches("- ").split_once(':') else {
+            continue;
+        };
+        let (key, value) = (key.trim(), unquote(value));
+        if top_level {
+            section = key;
+            if key == "current-context" && !value.is_empty() {
+                current = Some(value.to_string());
+            }
+            continue;
+        }
+        if section != "contexts" {
+            continue;
+        }
+        if trimmed.starts_with("- ") || contexts.is_empty() {
+            contexts.push(HashMap::new());
+        }
+        if !value.is_empty() {
+            if let Some(item) = contexts.last_mut() {
+                item.insert(key.to_string(), value.to_string());
+            }
+        }
+    }
+
+    let Some(current) = current else {
+        return Vec::new();
+    };
+    let mut attrs = vec![("k8s.context".to_string(), current.clone())];
+    if let Some(ctx) = contexts.iter().find(|c| c.get("name") == Some(&current)) {
+        for field in ["cluster", "user", "namespace"] {
+            if let Some(value) = ctx.get(field) {
+                attrs.push((format!("k8s.{}", field), value.clone()));
+            }
+        }
+    }
+    attrs
+}
+
 pub struct ShellCwdProbe(pub u32);
 impl Probe for ShellCwdProbe {
     fn key(&self) -> &str { "shell_cwd" }
@@ -213,7 +381,7 @@ impl Probe for OsVersionProbe {
     }
 }
 
-pub fn default_session_probes(child_pid: u32, client_addr: Option<String>) -> ProbeSet {
+pub fn default_session_probes(child_pid: u32, client_addr: Option<String>, env_vars: &[String]) -> ProbeSet {
     let mut set = ProbeSet::new();
     set.add(Box::new(ShellProbe));
     set.add(Box::new(PidProbe(child_pid)));
@@ -228,6 +396,13 @@ pub fn default_session_probes(child_pid: u32, client_addr: Option<String>) -> Pr
     // replaces attrs wholesale, and polling only re-sends diffs against local
     // last_attrs, so a daemon-side wipe would otherwise never be repopulated).
     set.add(Box::new(ShellCwdProbe(child_pid)));
+    set.add(Box::new(VenvPathProbe));
+    set.add(Box::new(VenvPythonVersionProbe));
+    set.add(Box::new(CondaEnvProbe));
+    set.add(Box::new(KubeContextProbe::new()));
+    for var in env_vars {
+        set.add(Box::new(EnvProbe::new(var)));
+    }
     set
 }
 
@@ -282,6 +457,102 @@ mod tests {
         assert!(probe.collect().is_some());
     }
 
+    #[test]
+    fn test_env_probe() {
+        let probe = EnvProbe::new("PATH");
+        assert_eq!(probe.key(), "env.PATH");
+        assert_eq!(probe.collect(), std::env::var("PATH").ok());
+        assert_eq!(EnvProbe::new("OMNISH_TEST_SURELY_UNSET_VAR").collect(), None);
+    }
+
+    #[test]
+    fn test_session_probes_include_env_vars() {
+        let vars = vec!["PATH".to_string(), "OMNISH_TEST_SURELY_UNSET_VAR".to_string()];
+        let attrs = default_session_probes(std::process::id(), None, &vars).collect_all();
+        assert_eq!(attrs.get("env.PATH"), std::env::var("PATH").ok().as_ref());
+        assert!(!attrs.contains_key("env.OMNISH_TEST_SURELY_UNSET_VAR"));
+    }
+
+    #[test]
+    fn test_venv_probes() {
+        let dir = tempfile::tempdir().unwrap();
+        let venv = dir.path().join("myvenv");
+        std::fs::create_dir(&venv).unwrap();
+        std::fs::write(
+            venv.join("pyvenv.cfg"),
+            "home = /usr/bin\ninclude-system-site-packages = false\nversion = 3.11.4\n",
+        )
+        .unwrap();
+        std::env::set_var("VIRTUAL_ENV", &venv);
+        let attrs = default_session_probes(std::process::id(), None, &[]).collect_all();
+        std::env::remove_var("VIRTUAL_ENV");
+        assert_eq!(attrs.get("venv.path"), Some(&venv.to_string_lossy().to_string()));
+        assert_eq!(attrs.get("venv.python_version").map(String::as_str), Some("3.11.4"));
+    }
+
+    #[test]
+    fn test_pyvenv_python_version() {
+        assert_eq!(pyvenv_python_version("home = /usr/bin\nversion = 3.11.4\n"), Some("3.11.4".into()));
+        assert_eq!(
+            pyvenv_python_version("home = /usr/bin\nversion_info = 3.12.1.final.0\n"),
+            Some("3.12.1".into()),
+        );
+        assert_eq!(pyvenv_python_version("home = /usr/bin\n"), None);
+    }
+
+    const KUBE_CONFIG: &str = "\
+apiVersion: v1
+clusters:
+- cluster:
+    server: https://10.0.0.1:6443
+  name: mycluster
+contexts:
+- context:
+    cluster: staging
+    user: dev
+  name: staging-ctx
+- context:
+    cluster: mycluster
+    namespace: prod
+    user: admin
+  name: prod-ctx
+current-context: \"prod-ctx\"
+kind: Config
+users:
+- name: admin
+  user:
+    token: secret
+";
+
+    #[test]
+    fn test_kube_context_probe() {
+        let dir = tempfile::tempdir().unwrap();
+        let path = dir.path().join("config");
+        std::fs::write(&path, KUBE_CONFIG).unwrap();
+        let mut set = ProbeSet::new();
+        set.add(Box::new(KubeContextProbe::with_path(Some(path.clone()))));
+        let attrs = set.collect_all();
+        assert_eq!(attrs.get("k8s.context").map(String::as_str), Some("prod-ctx"));
+        assert_eq!(attrs.get("k8s.cluster").map(String::as_str), Some("mycluster"));
+        assert_eq!(attrs.get("k8s.user").map(String::as_str), Some("admin"));
+        assert_eq!(attrs.get("k8s.namespace").map(String::as_str), Some("prod"));
+    }
+
+    #[test]
+    fn test_kube_context_probe_missing_or_malformed() {
+        let dir = tempfile::tempdir().unwrap();
+        assert!(KubeContextProbe::with_path(Some(dir.path().join("nope"))).collect_attrs().is_empty());
+        let path = dir.path().join("config");
+        std::fs::write(&path, "{not: [valid yaml").unwrap();
+        assert!(KubeContextProbe::with_path(Some(path.clone())).collect_attrs().is_empty());
+        // Current context with no matching entry still reports its name
+        std::fs::write(&path, "current-context: gone\ncontexts: []\n").unwrap();
+        assert_eq!(
+            KubeContextProbe::with_path(Some(path.clone())).collect_attrs(),
+            vec![("k8s.context".to_string(), "gone".to_string())],
+        );
+    }
+
     #[test]
     fn test_client_addr_probe() {
         assert_eq!(ClientAddrProbe(None).collect(), None);
@@ -121,9 +121,149 @@ precmd_functions+=(__omnish_precmd)
 preexec_functions+=(__omnish_preexec)
 "#;
 
+const FISH_HOOK: &str = r#"
+# omnish shell integration - OSC 133 semantic prompts for fish
+function __omnish_prompt --on-event fish_prompt
+    printf '\e]133;A\a'
+end
+
+function __omnish_preexec --on-event fish_preexec
+    # $argv[1] is the raw command line, so it doubles as orig:
+    set -l cmd_esc (string replace -a \n '\n' -- $argv[1] | string replace -a ';' '\;')
+    set -l pwd_esc (string replace -a ';' '\;' -- $PWD)
+    printf '\e]133;B;%s;cwd:%s;orig:%s\a' "$cmd_esc" "$pwd_esc" "$cmd_esc"
+    printf '\e]133;C\a'
+end
+
+function __omnish_postexec --on-event fish_postexec
+    set -l ec $status
+    printf '\e]133;D;%d\a' $ec
+end
+
+# Readline reporting (bound to same key as bash/zsh)
+function __omnish_rl_report
+    printf '\e]133;RL;%s;%s\a' (commandline) (commandline -C)
+end
+bind \e\[13337~ __omnish_rl_report
+"#;
+
+const PWSH_HOOK: &str = r#"
+# omnish shell integration - OSC 133 semantic prompts for PowerShell
+# ESC is written as $([char]27): PowerShell 5.1 has no backtick-e escape
+if ($global:__OmnishHooked) { return }
+$global:__OmnishHooked = $true
+$global:__OmnishOrigPrompt = $function:prompt
+# The line PSReadLine accepted, until its prompt comes back
+$global:__OmnishLine = $null
+$global:__OmnishPreexecFired = $true
+$global:__OmnishInPrompt = $false
+
+function global:__OmnishEscape([string]$text) {
+    return $text.Replace(';', '\;').Replace("`r", '').Replace("`n", '\n')
+}
+
+function global:__OmnishPreexec {
+    if ($global:__OmnishPreexecFired -or $null -eq $global:__OmnishLine) { return }
+    $global:__OmnishPreexecFired = $true
+    $cmd = __OmnishEscape $global:__OmnishLine
+    $cwd = __OmnishEscape $ExecutionContext.SessionState.Path.CurrentLocation.Path
+    [Console]::Write("$([char]27)]133;B;${cmd};cwd:${cwd};orig:${cmd}`a")
+    [Console]::Write("$([char]27)]133;C`a")
+}
+
+function global:prompt {
+    $ok = $?
+    $ec = $global:LASTEXITCODE
+    $global:__OmnishInPrompt = $true
+    try {
+        if ($null -ne $global:__OmnishLine) {
+            # Lines made only of expressions never look up a command
+            __OmnishPreexec
+            $code = if ($ok) { 0 } elseif ($ec -is [int] -and $ec -ne 0) { $ec } else { 1 }
+            [Console]::Write("$([char]27)]133;D;${code}`a")
+            $global:__OmnishLine = $null
+        }
+        [Console]::Write("$([char]27)]133;A`a")
+        if ($null -ne $global:__OmnishOrigPrompt) {
+            & $global:__OmnishOrigPrompt
+        } else {
+            "PS $($ExecutionContext.SessionState.Path.CurrentLocation)> "
+        }
+    } finally {
+        $global:LASTEXITCODE = $ec
+        $global:__OmnishInPrompt = $false
+    }
+}
+
+# Pre-exec: the first command lookup after a line is accepted
+$global:__OmnishOrigLookup = $ExecutionContext.InvokeCommand.PreCommandLookupAction
+$ExecutionContext.InvokeCommand.PreCommandLookupAction = {
+    param($commandName, $eventArgs)
+    if (-not $global:__OmnishInPrompt -and $commandName -notlike '__Omnish*') {
+        __OmnishPreexec
+    }
+    if ($null -ne $global:__OmnishOrigLookup) {
+        & $global:__OmnishOrigLookup $commandName $eventArgs
+    }
+}
+
+# PSReadLine hands every accepted line to the history handler before it runs
+if (Get-Module PSReadLine) {
+    $global:__OmnishOrigHistoryHandler = (Get-PSReadLineOption).AddToHistoryHandler
+    Set-PSReadLineOption -AddToHistoryHandler {
+        param([string]$line)
+        $global:__OmnishLine = $line
+        $global:__OmnishPreexecFired = $false
+        if ($null -ne $global:__OmnishOrigHistoryHandler) {
+            return & $global:__OmnishOrigHistoryHandler $line
+        }
+        return $true
+    }
+} else {
+    [Console]::Write("$([char]27)]133;NO_READLINE`a")
+}
+"#;
+
+/// Shell flavours with an OSC 133 hook installer.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum ShellKind {
+    Bash,
+    Zsh,
+    Fish,
+    PowerShell,
+    Unknown,
+}
+
+impl ShellKind {
+    /// Classify a shell by the name of its binary path.
+    pub fn detect(shell: &str) -> Self {
+        if shell.ends_with("bash") {
+            ShellKind::Bash
+        } else if shell.ends_with("zsh") {
+            ShellKind::Zsh
+        } else if shell.ends_with("fish") {
+            ShellKind::Fish
+        } else if is_powershell(shell) {
+            ShellKind::PowerShell
+        } else {
+            ShellKind::Unknown
+        }
+    }
+}
+
+/// True for PowerShell binaries: `pwsh`, `pwsh-preview`, `powershell`, or
+/// `powershell.exe` through WSL interop.
+fn is_powershell(shell: &str) -> bool {
+    Path::new(shell)
+        .file_name()
+        .map(|n| n.to_string_lossy().to_lowercase())
+        .is_some_and(|n| n.starts_with("pwsh") || n.starts_with("powershell"))
+}
+
 /// Generate an rcfile that sources the user's original bashrc then loads the OSC 133 hook.
+/// With `completions`, it also sources the omnish flag completion script.
 /// Returns the rcfile path, or None if the shell is not bash.
-pub fn install_bash_hook(shell: &str) -> Option<PathBuf> {
+pub fn install_bash_hook(shell: &str, completions: bool) -> Option<PathBuf> {
     if !shell.ends_with("bash") {
         return None;
     }
@@ -158,6 +298,17 @@ pub fn install_bash_hook(shell: &str) -> Option<PathBuf> {
         "source \"{}\"\n",
         hook_path.to_string_lossy()
     ));
+    if completions {
+        let script = omnish_common::completions::bash_completion_script();
+        let completion_path = dir.join("omnish_completion.bash");
+        if std::fs::read(&completion_path).ok().as_deref() != Some(script.as_bytes()) {
+            write_atomic(&completion_path, script.as_bytes()).ok()?;
+        }
+        content.push_str(&format!(
+            "source \"{}\"\n",
+            completion_path.to_string_lossy()
+        ));
+    }
     let should_write_rc = match std::fs::read(&rcfile_path) {
         Ok(existing) => existing != content.as_bytes(),
         Err(e) if e.kind() == std::io::ErrorKind::NotFound => true,
@@ -223,6 +374,66 @@ pub fn install_zsh_hook(shell: &str) -> Option<PathBuf> {
     Some(zdotdir)
 }
 
+/// Install the fish OSC 133 hook.
+/// Fish has no rcfile override; it reads config.fish as usual and then runs
+/// `--init-command`, so the hook is sourced from there. Returns the extra
+/// shell arguments, or None if the shell is not fish.
+pub fn install_fish_hook(shell: &str) -> Option<Vec<String>> {
+    if !shell.ends_with("fish") {
+        return None;
+    }
+
+    let dir = omnish_common::config::omnish_dir().join("hooks");
+    std::fs::create_dir_all(&dir).ok()?;
+
+    let hook_path = dir.join("fish_hook.fish");
+    let should_write = match std::fs::read(&hook_path) {
+        Ok(existing) => existing != FISH_HOOK.as_bytes(),
+        Err(e) if e.kind() == std::io::ErrorKind::NotFound => true,
+        Err(_) => false,
+    };
+    if should_write {
+        write_atomic(&hook_path, FISH_HOOK.as_bytes()).ok()?;
+    }
+
+    Some(vec![
+        "--init-command".to_string(),
+        format!("source '{}'", hook_path.to_string_lossy().replace('\'', "\\'")),
+    ])
+}
+
+/// Install the PowerShell OSC 133 hook.
+/// Like fish, PowerShell loads the user's profile as usual; the hook is
+/// dot-sourced afterwards by `-Command` and wraps whatever `prompt` the
+/// profile defined. Returns the extra shell arguments (the last one names
+/// the hook script), or None if the shell is not PowerShell.
+pub fn install_pwsh_hook(shell: &str) -> Option<Vec<String>> {
+    if !is_powershell(shell) {
+        return None;
+    }
+
+    let dir = omnish_common::config::omnish_dir().join("hooks");
+    std::fs::create_dir_all(&dir).ok()?;
+
+    let hook_path = dir.join("pwsh_hook.ps1");
+    let should_write = match std::fs::read(&hook_path) {
+        Ok(existing) => existing != PWSH_HOOK.as_bytes(),
+        Err(e) if e.kind() == std::io::ErrorKind::NotFound => true,
+        Err(_) => false,
+    };
+    if should_write {
+        write_atomic(&hook_path, PWSH_HOOK.as_bytes()).ok()?;
+    }
+
+    Some(vec![
+        "-NoLogo".to_string(),
+        "-NoExit".to_string(),
+        "-Command".to_string(),
+        // Single-quoted PowerShell string: quotes are escaped by doubling
+        format!(". '{}'", hook_path.to_string_lossy().replace('\'', "''")),
+    ])
+}
+
 #[cfg(test)]
 mod tests {
     use super::*;
@@ -237,13 +448,13 @@ mod tests {
 
     #[test]
     fn test_non_bash_returns_none() {
-        assert!(install_bash_hook("/bin/zsh").is_none());
-        assert!(install_bash_hook("/bin/fish").is_none());
+        assert!(install_bash_hook("/bin/zsh", false).is_none());
+        assert!(install_bash_hook("/bin/fish", false).is_none());
     }
 
     #[test]
     fn test_bash_returns_rcfile_path() {
-        let result = install_bash_hook("/bin/bash");
+        let result = install_bash_hook("/bin/bash", false);
         assert!(result.is_some());
         let rcfile = result.unwrap();
         assert!(rcfile.exists());
@@ -251,6 +462,14 @@ mod tests {
         let content = std::fs::read_to_string(&rcfile).unwrap();
         // rcfile sources the hook script
         assert!(content.contains("bash_hook.sh"), "rcfile should source hook: {content}");
+        assert!(!content.contains("omnish_completion.bash"), "{content}");
+
+        let rcfile = install_bash_hook("/bin/bash", true).unwrap();
+        let content = std::fs::read_to_string(&rcfile).unwrap();
+        assert!(content.contains("omnish_completion.bash"), "{content}");
+        let script = rcfile.with_file_name("omnish_completion.bash");
+        assert!(std::fs::read_to_string(script).unwrap().contains("--health"));
+        install_bash_hook("/bin/bash", false);
     }
 
     #[test]
@@ -268,6 +487,13 @@ mod tests {
         assert!(ZSH_HOOK.contains("133;RL"));
     }
 
+    #[test]
+    fn test_non_zsh_returns_none() {
+        assert!(install_zsh_hook("/bin/bash").is_none());
+        assert!(install_zsh_hook("/usr/bin/fish").is_none());
+        assert!(install_zsh_hook("/bin/sh").is_none());
+    }
+
     #[test]
     fn test_zsh_returns_zdotdir() {
         let result = install_zsh_hook("/bin/zsh");
@@ -282,6 +508,43 @@ mod tests {
         assert!(content.contains("zsh_hook.zsh"), "zshrc should source hook: {content}");
     }
 
+    #[test]
+    fn test_shell_kind_detect() {
+        assert_eq!(ShellKind::detect("/bin/bash"), ShellKind::Bash);
+        assert_eq!(ShellKind::detect("/usr/local/bin/zsh"), ShellKind::Zsh);
+        assert_eq!(ShellKind::detect("/opt/homebrew/bin/fish"), ShellKind::Fish);
+        assert_eq!(ShellKind::detect("/usr/bin/pwsh"), ShellKind::PowerShell);
+        assert_eq!(ShellKind::detect("/mnt/c/Windows/System32/powershell.exe"), ShellKind::PowerShell);
+        assert_eq!(ShellKind::detect("/bin/sh"), ShellKind::Unknown);
+    }
+
+    #[test]
+    fn test_fish_hook_content_has_osc133_sequences() {
+        for seq in ["133;A", "133;B", "133;C", "133;D", "133;RL"] {
+            assert!(FISH_HOOK.contains(seq), "missing {seq}");
+        }
+        assert!(FISH_HOOK.contains("--on-event fish_preexec"));
+        assert!(FISH_HOOK.contains("--on-event fish_postexec"));
+    }
+
+    #[test]
+    fn test_fish_returns_init_command() {
+        assert!(install_fish_hook("/bin/bash").is_none());
+        let args = install_fish_hook("/usr/bin/fish").unwrap();
+        assert_eq!(args.len(), 2);
+        assert_eq!(args[0], "--init-command");
+        assert!(args[1].starts_with("source '"), "{}", args[1]);
+        assert!(args[1].contains("fish_hook.fish"), "{}", args[1]);
+
+        let path = args[1].trim_start_matches("source '").trim_end_matches('\'');
+        let script = std::fs::read_to_string(path).unwrap();
+        // Every function block must be closed
+        let opens = script.lines().filter(|l| l.starts_with("function ")).count();
+        let ends = script.lines().filter(|l| l.trim() == "end").count();
+        assert_eq!(opens, 4);
+        assert_eq!(opens, ends);
+    }
+
     #[test]
     fn test_zsh_hook_preserves_original_zdotdir() {
         let result = install_zsh_hook("/usr/bin/zsh");
@@ -291,4 +554,39 @@ mod tests {
         let content = std::fs::read_to_string(&zshrc).unwrap();
         assert!(content.contains("OMNISH_ORIG_ZDOTDIR"), "should reference original ZDOTDIR: {content}");
     }
+
+    #[test]
+    fn test_pwsh_hook_content_has_osc133_sequences() {
+        // $([char]27) and `a are \x1b and \x07; Windows PowerShell 5.1
+        // has no `e escape
+        assert!(PWSH_HOOK.contains("\"$([char]27)]133;A`a\""));
+        for seq in ["$([char]27)]133;B;", "$([char]27)]133;C`a", "$([char]27)]133;D;"] {
+            assert!(PWSH_HOOK.contains(seq), "missing {seq}");
+        }
+        assert!(!PWSH_HOOK.contains("`e]"));
+        assert!(PWSH_HOOK.contains("function global:prompt"));
+        assert!(PWSH_HOOK.contains("PreCommandLookupAction"));
+        assert!(PWSH_HOOK.contains("Set-PSReadLineOption"));
+    }
+
+    #[test]
+    fn test_pwsh_returns_dot_source_command() {
+        assert!(install_pwsh_hook("/bin/bash").is_none());
+        let args = install_pwsh_hook("/usr/bin/pwsh").unwrap();
+        assert_eq!(args[..3], ["-NoLogo", "-NoExit", "-Command"]);
+        let path = args[3].trim_start_matches(". '").trim_end_matches('\'');
+        assert!(path.ends_with("pwsh_hook.ps1"), "{}", args[3]);
+        let script = std::fs::read_to_string(path).unwrap();
+        assert_eq!(script.matches('{').count(), script.matches('}').count());
+
+        // Full syntax check where PowerShell is available
+        let check = format!(
+            "$errors = $null; $null = [System.Management.Automation.Language.Parser]::ParseFile('{}', [ref]$null, [ref]$errors); exit $errors.Count",
+            path.replace('\'', "''")
+        );
+        match std::process::Command::new("pwsh").args(["-NoProfile", "-Command", &check]).status() {
+            Ok(status) => assert!(status.success(), "pwsh_hook.ps1 has syntax errors"),
+            Err(e) => eprintln!("skipping PowerShell parse check: {e}"),
+        }
+    }
 }
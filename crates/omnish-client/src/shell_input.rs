@@ -10,6 +10,8 @@
 /// 4. Backspace (0x7f / 0x08) removes the last character
 /// 5. Ctrl+C (0x03) / Ctrl+U (0x15) clears input
 /// 6. Enter (0x0d) clears input (command submitted)
+/// 7. With the vi keymap, a bare ESC switches to command mode, where keys
+///    are editing commands rather than text (see `feed_vi_command`)
 pub struct ShellInputTracker {
     input: String,
     at_prompt: bool,
@@ -32,6 +34,18 @@ pub struct ShellInputTracker {
     /// cleared on next prompt (on_prompt). Unlike pending_rl_report, this does
     /// NOT auto-clear after timeout - isearch can last indefinitely.
     in_isearch: bool,
+    /// Whether readline uses the vi keymap (`set -o vi`).
+    vi_keymap: bool,
+    vi_mode: ViModeState,
+    /// First key of a two-key vi command (`d` of `dd`, `c` of `cc`).
+    vi_pending_op: Option<u8>,
+}
+
+/// Readline vi editing mode. Always `Insert` with the emacs keymap.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum ViModeState {
+    Insert,
+    Command,
 }
 
 impl ShellInputTracker {
@@ -46,9 +60,19 @@ impl ShellInputTracker {
             pending_rl_report_at: None,
             cursor_at_end: true,
             in_isearch: false,
+            vi_keymap: false,
+            vi_mode: ViModeState::Insert,
+            vi_pending_op: None,
         }
     }
 
+    /// Track input for readline's vi keymap instead of emacs.
+    pub fn set_vi_keymap(&mut self, vi: bool) {
+        self.vi_keymap = vi;
+        self.vi_mode = ViModeState::Insert;
+        self.vi_pending_op = None;
+    }
+
     /// Call when OSC 133;A (PromptStart) or 133;D (CommandEnd) is detected.
     pub fn on_prompt(&mut self) {
         self.at_prompt = true;
@@ -58,6 +82,8 @@ impl ShellInputTracker {
         self.pending_rl_report_at = None;
         self.cursor_at_end = true;
         self.in_isearch = false;
+        self.vi_mode = ViModeState::Insert;
+        self.vi_pending_op = None;
         self.bump(); // always bump so completion can fire on empty prompt
     }
 
@@ -67,16 +93,21 @@ impl ShellInputTracker {
         if !self.at_prompt {
             return;
         }
-        for &b in bytes {
+        for (i, &b) in bytes.iter().enumerate() {
             // ESC sequence state machine: skip CSI and single-char escapes
             match self.esc_state {
                 1 => {
                     if b == b'[' {
                         self.esc_state = 2;
-                    } else {
-                        self.esc_state = 0;
+                        continue;
                     }
-                    continue;
+                    self.esc_state = 0;
+                    if !self.vi_keymap {
+                        continue;
+                    }
+                    // vi: the ESC was a bare keypress, so this byte is
+                    // already a command-mode key
+                    self.set_vi_mode(ViModeState::Command);
                 }
                 2 => {
                     if (0x40..=0x7e).contains(&b) {
@@ -87,7 +118,15 @@ impl ShellInputTracker {
                 _ => {}
             }
             if b == 0x1b {
-                self.esc_state = 1;
+                if self.vi_keymap && i + 1 == bytes.len() {
+                    // A lone ESC read: the key itself, not a sequence prefix
+                    self.set_vi_mode(ViModeState::Command);
+                } else {
+                    self.esc_state = 1;
+                }
+                continue;
+            }
+            if self.vi_mode == ViModeState::Command && self.feed_vi_command(b) {
                 continue;
             }
             match b {
@@ -108,6 +147,7 @@ impl ShellInputTracker {
                     self.bump();
                 }
                 // Backspace / DEL -> remove last char
+                #[allow(clippy::collapsible_match)]
                 0x7f | 0x08 => {
                     if self.input.pop().is_some() {
                         self.bump();
@@ -126,6 +166,61 @@ impl ShellInputTracker {
         }
     }
 
+    /// Handle a key in vi command mode. Returns false for keys that behave
+    /// as in insert mode (Enter, Ctrl+C, Ctrl+U). Only commands whose effect
+    /// on the line is certain are applied; the rest leave `input` as is.
+    fn feed_vi_command(&mut self, b: u8) -> bool {
+        if matches!(b, 0x0d | 0x0a | 0x03 | 0x15) {
+            self.vi_pending_op = None;
+            return false;
+        }
+        let op = self.vi_pending_op.take();
+        match (op, b) {
+            // dd / cc: delete the whole line (cc keeps typing)
+            (Some(b'd'), b'd') | (Some(b'c'), b'c') => {
+                self.input.clear();
+                self.cursor_at_end = true;
+                self.bump();
+                if b == b'c' {
+                    self.set_vi_mode(ViModeState::Insert);
+                }
+            }
+            (None, b'd' | b'c') => self.vi_pending_op = Some(b),
+            // S: substitute the whole line
+            (None, b'S') => {
+                self.input.clear();
+                self.cursor_at_end = true;
+                self.bump();
+                self.set_vi_mode(ViModeState::Insert);
+            }
+            (None, b'A') => {
+                self.cursor_at_end = true;
+                self.set_vi_mode(ViModeState::Insert);
+            }
+            // ESC moved the cursor back one char, so `i`/`I` insert before the end
+            (None, b'i' | b'I') => {
+                self.cursor_at_end = false;
+                self.set_vi_mode(ViModeState::Insert);
+            }
+            (None, b'a') => self.set_vi_mode(ViModeState::Insert),
+            _ => {}
+        }
+        true
+    }
+
+    fn set_vi_mode(&mut self, mode: ViModeState) {
+        if self.vi_mode != mode {
+            self.vi_mode = mode;
+            self.vi_pending_op = None;
+            self.bump();
+        }
+    }
+
+    /// Current vi editing mode (`Insert` unless the vi keymap is active).
+    pub fn vi_mode(&self) -> ViModeState {
+        self.vi_mode
+    }
+
     /// Append text to the input (e.g., after Tab acceptance writes to PTY).
     pub fn inject(&mut self, text: &str) {
         self.input.push_str(text);
@@ -252,6 +347,70 @@ impl ShellInputTracker {
 mod tests {
     use super::*;
 
+    fn vi_tracker() -> ShellInputTracker {
+        let mut t = ShellInputTracker::new();
+        t.set_vi_keymap(true);
+        t
+    }
+
+    #[test]
+    fn test_vi_dd_clears_line() {
+        let mut t = vi_tracker();
+        t.feed_forwarded(b"git stat\x1bdd");
+        assert_eq!(t.input(), "");
+        assert_eq!(t.vi_mode(), ViModeState::Command);
+
+        // Same keys split across reads: the lone ESC is the mode switch
+        let mut t = vi_tracker();
+        t.feed_forwarded(b"git stat");
+        t.feed_forwarded(b"\x1b");
+        assert_eq!(t.vi_mode(), ViModeState::Command);
+        t.feed_forwarded(b"d");
+        assert_eq!(t.input(), "git stat");
+        t.feed_forwarded(b"d");
+        assert_eq!(t.input(), "");
+    }
+
+    #[test]
+    fn test_vi_command_keys_not_tracked_as_text() {
+        let mut t = vi_tracker();
+        // Motions only: the line itself is unchanged
+        t.feed_forwarded(b"ls -la\x1bbbw");
+        assert_eq!(t.input(), "ls -la");
+        // `A` appends at the end in insert mode again
+        t.feed_forwarded(b"A /tmp");
+        assert_eq!(t.vi_mode(), ViModeState::Insert);
+        assert_eq!(t.input(), "ls -la /tmp");
+        assert!(t.cursor_at_end());
+    }
+
+    #[test]
+    fn test_vi_arrow_keys_stay_in_insert_mode() {
+        let mut t = vi_tracker();
+        t.feed_forwarded(b"ls\x1b[D");
+        assert_eq!(t.vi_mode(), ViModeState::Insert);
+        assert_eq!(t.input(), "ls");
+    }
+
+    #[test]
+    fn test_vi_mode_reset_on_prompt() {
+        let mut t = vi_tracker();
+        t.feed_forwarded(b"ls\x1b");
+        t.feed_forwarded(b"\r");
+        t.on_prompt();
+        assert_eq!(t.vi_mode(), ViModeState::Insert);
+        t.feed_forwarded(b"dd");
+        assert_eq!(t.input(), "dd");
+    }
+
+    #[test]
+    fn test_emacs_keymap_esc_is_meta_prefix() {
+        let mut t = ShellInputTracker::new();
+        t.feed_forwarded(b"git stat\x1bdd");
+        assert_eq!(t.vi_mode(), ViModeState::Insert);
+        assert_eq!(t.input(), "git statd");
+    }
+
     #[test]
     fn test_basic_typing() {
         let mut t = ShellInputTracker::new();
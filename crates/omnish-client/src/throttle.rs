@@ -6,11 +6,17 @@ const DEFAULT_MAX_BYTES: u64 = 4 * 1024 * 1024; // 4MB
 /// Prevents high-frequency small-update programs from flooding the daemon.
 const DEFAULT_MAX_REQUESTS: u64 = 1_000;
 
+/// Commands that have run for less than this are never throttled.
+const DEFAULT_FAST_COMMAND_MS: u64 = 500;
+
 pub struct OutputThrottle {
     max_bytes: u64,
     max_requests: u64,
+    fast_command_ms: u64,
     command_bytes: u64,
     command_requests: u64,
+    /// How long the current command has been running, if known.
+    command_duration_ms: Option<u64>,
 }
 
 impl OutputThrottle {
@@ -18,13 +24,29 @@ impl OutputThrottle {
         Self {
             max_bytes: DEFAULT_MAX_BYTES,
             max_requests: DEFAULT_MAX_REQUESTS,
+            fast_command_ms: DEFAULT_FAST_COMMAND_MS,
             command_bytes: 0,
             command_requests: 0,
+            command_duration_ms: None,
         }
     }
 
-    /// Returns true if this command's output is still under both caps.
+    pub fn with_fast_command_ms(mut self, ms: u64) -> Self {
+        self.fast_command_ms = ms;
+        self
+    }
+
+    /// Record how long the current command has been running.
+    pub fn note_command_duration(&mut self, duration_ms: u64) {
+        self.command_duration_ms = Some(duration_ms);
+    }
+
+    /// Returns true if this command's output is still under both caps, or
+    /// the command is still young enough to count as fast.
     pub fn should_send(&self, _chunk_len: usize) -> bool {
+        if self.command_duration_ms.is_some_and(|ms| ms < self.fast_command_ms) {
+            return true;
+        }
         self.command_bytes < self.max_bytes && self.command_requests < self.max_requests
     }
 
@@ -39,6 +61,7 @@ impl OutputThrottle {
     pub fn reset(&mut self) {
         self.command_bytes = 0;
         self.command_requests = 0;
+        self.command_duration_ms = None;
     }
 }
 
@@ -88,6 +111,30 @@ mod tests {
         assert!(!t.should_send(1));
     }
 
+    #[test]
+    fn test_fast_command_bypasses_caps() {
+        let mut t = OutputThrottle::new();
+        t.command_bytes = DEFAULT_MAX_BYTES;
+        assert!(!t.should_send(10 * 1024));
+        t.note_command_duration(100);
+        assert!(t.should_send(10 * 1024));
+        // Past the threshold the caps apply again
+        t.note_command_duration(DEFAULT_FAST_COMMAND_MS);
+        assert!(!t.should_send(10 * 1024));
+        t.note_command_duration(100);
+        t.reset();
+        t.command_bytes = DEFAULT_MAX_BYTES;
+        assert!(!t.should_send(10 * 1024));
+    }
+
+    #[test]
+    fn test_fast_command_threshold_configurable() {
+        let mut t = OutputThrottle::new().with_fast_command_ms(0);
+        t.command_requests = DEFAULT_MAX_REQUESTS;
+        t.note_command_duration(0);
+        assert!(!t.should_send(1));
+    }
+
     #[test]
     fn test_reset_returns_to_normal() {
         let mut t = OutputThrottle::new();
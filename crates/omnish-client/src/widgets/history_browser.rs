@@ -0,0 +1,385 @@
+// crates/omnish-client/src/widgets/history_browser.rs
+//
+// Interactive command history browser for `/hist`: incremental filter,
+// Up/Down to move, Enter to pick, ESC/Ctrl-C to cancel. The state machine
+// and rendering are pure; `run_browser` drives them from any input source so
+// the loop can be tested without a terminal.
+
+use std::io::Write;
+use std::os::unix::io::AsRawFd;
+
+use super::common;
+use crate::display::{BOLD_REVERSE, CYAN, DIM, NEWLINE, RESET};
+
+/// How often the terminal loop re-checks the window size while idle.
+const RESIZE_POLL_MS: i32 = 200;
+
+/// Lines drawn besides the item list: query line, separator, hint.
+const CHROME_LINES: usize = 3;
+
+/// One unit of input for the browser loop.
+#[derive(Debug, Clone, PartialEq)]
+pub enum BrowserInput {
+    /// Raw bytes read from the terminal (may hold several keys).
+    Bytes(Vec<u8>),
+    /// The terminal was resized to `(rows, cols)`.
+    Resize(u16, u16),
+}
+
+#[derive(Debug, PartialEq)]
+pub enum BrowserEvent {
+    /// Keep going; redraw.
+    Continue,
+    /// Enter on a command line.
+    Select(String),
+    /// ESC or Ctrl-C.
+    Cancel,
+}
+
+pub struct HistoryBrowser {
+    /// Command lines, most recent first.
+    commands: Vec<String>,
+    query: String,
+    /// Indices into `commands` matching `query`.
+    filtered: Vec<usize>,
+    /// Position within `filtered`.
+    cursor: usize,
+    /// First visible position within `filtered`.
+    scroll: usize,
+}
+
+impl HistoryBrowser {
+    pub fn new(commands: Vec<String>) -> Self {
+        let mut browser = Self {
+            commands,
+            query: String::new(),
+            filtered: Vec::new(),
+            cursor: 0,
+            scroll: 0,
+        };
+        browser.refilter();
+        browser
+    }
+
+    /// Start with `query` already typed.
+    pub fn with_query(mut self, query: &str) -> Self {
+        self.query = query.to_string();
+        self.refilter();
+        self
+    }
+
+    /// Command lines currently shown, in display order.
+    #[cfg(test)]
+    fn matches(&self) -> Vec<&str> {
+        self.filtered.iter().map(|&i| self.commands[i].as_str()).collect()
+    }
+
+    /// Case-insensitive substring filter; resets the cursor to the top.
+    fn refilter(&mut self) {
+        let needle = self.query.to_lowercase();
+        self.filtered = self
+            .commands
+            .iter()
+            .enumerate()
+            .filter(|(_, c)| c.to_lowercase().contains(&needle))
+            .map(|(i, _)| i)
+            .collect();
+        self.cursor = 0;
+        self.scroll = 0;
+    }
+
+    /// Feed a batch of input bytes. Stops at the first byte that ends the
+    /// browser.
+    pub fn feed_bytes(&mut self, bytes: &[u8]) -> BrowserEvent {
+        let mut i = 0;
+        while i < bytes.len() {
+            match bytes[i] {
+                0x03 => return BrowserEvent::Cancel,
+                b'\r' | b'\n' => {
+                    return match self.filtered.get(self.cursor) {
+                        Some(&idx) => BrowserEvent::Select(self.commands[idx].clone()),
+                        None => BrowserEvent::Cancel,
+                    };
+                }
+                0x1b => {
+                    // CSI/SS3 cursor keys arrive whole in one read; a lone
+                    // ESC (or ESC + anything else) cancels.
+                    match (bytes.get(i + 1), bytes.get(i + 2)) {
+                        (Some(b'[' | b'O'), Some(b'A')) => self.move_cursor(-1),
+                        (Some(b'[' | b'O'), Some(b'B')) => self.move_cursor(1),
+                        (Some(b'[' | b'O'), Some(_)) => {}
+                        _ => return BrowserEvent::Cancel,
+                    }
+                    i += 3;
+                    continue;
+                }
+                0x10 => self.move_cursor(-1), // Ctrl-P
+                0x0e => self.move_cursor(1),  // Ctrl-N
+                0x7f | 0x08 if self.query.pop().is_some() => self.refilter(),
+                0x15 => {
+                    // Ctrl-U
+                    self.query.clear();
+                    self.refilter();
+                }
+                b if b >= 0x20 => {
+                    // Take the whole UTF-8 character starting here.
+                    let len = utf8_len(b).min(bytes.len() - i);
+                    if let Ok(s) = std::str::from_utf8(&bytes[i..i + len]) {
+                        self.query.push_str(s);
+                        self.refilter();
+                    }
+                    i += len;
+                    continue;
+                }
+                _ => {}
+            }
+            i += 1;
+        }
+        BrowserEvent::Continue
+    }
+
+    fn move_cursor(&mut self, delta: isize) {
+        if self.filtered.is_empty() {
+            return;
+        }
+        let max = self.filtered.len() - 1;
+        self.cursor = self.cursor.saturating_add_signed(delta).min(max);
+    }
+
+    /// Number of list rows that fit in a terminal of `rows` lines.
+    fn visible_rows(rows: u16) -> usize {
+        (rows as usize).saturating_sub(CHROME_LINES + 1).clamp(1, 20)
+    }
+
+    /// Total lines `render` draws for a terminal of `rows` lines.
+    pub fn height(rows: u16) -> usize {
+        Self::visible_rows(rows) + CHROME_LINES
+    }
+
+    /// Draw the browser from the current line downwards. Always draws
+    /// `height(rows)` lines and leaves the cursor on the last one.
+    pub fn render(&mut self, rows: u16, cols: u16) -> String {
+        let vis = Self::visible_rows(rows);
+        if self.cursor < self.scroll {
+            self.scroll = self.cursor;
+        } else if self.cursor >= self.scroll + vis {
+            self.scroll = self.cursor + 1 - vis;
+        }
+        let width = (cols as usize).saturating_sub(3).max(1);
+
+        let mut out = String::from("\r\x1b[J");
+        out.push_str(&format!(
+            "{CYAN}hist>{RESET} {}  {DIM}{}/{}{RESET}{NEWLINE}",
+            self.query,
+            self.filtered.len(),
+            self.commands.len()
+        ));
+        out.push_str(&common::render_separator(cols));
+        out.push_str(NEWLINE);
+        for row in 0..vis {
+            let pos = self.scroll + row;
+            if let Some(&idx) = self.filtered.get(pos) {
+                let line: String = self.commands[idx].chars().take(width).collect();
+                if pos == self.cursor {
+                    out.push_str(&format!("\r{BOLD_REVERSE}> {}{RESET}\x1b[K", line));
+                } else {
+                    out.push_str(&format!("\r  {}\x1b[K", line));
+                }
+            } else {
+                out.push_str("\r\x1b[K");
+            }
+            out.push_str(NEWLINE);
+        }
+        out.push_str(&format!(
+            "\r{DIM}{}{RESET}\x1b[K",
+            crate::i18n::t("hint.history_browser")
+        ));
+        out
+    }
+}
+
+fn utf8_len(first: u8) -> usize {
+    match first {
+        0xF0.. => 4,
+        0xE0.. => 3,
+        0xC0.. => 2,
+        _ => 1,
+    }
+}
+
+/// Move from the last line of a drawn browser back to its first line.
+fn cursor_to_top(height: usize) -> String {
+    if height > 1 {
+        format!("\x1b[{}A\r", height - 1)
+    } else {
+        "\r".to_string()
+    }
+}
+
+/// Drive `browser` from `inputs`, drawing to `out`. Returns the selected
+/// command line, or None on cancel or end of input. The drawn area is
+/// cleared before returning.
+pub fn run_browser(
+    browser: &mut HistoryBrowser,
+    mut rows: u16,
+    mut cols: u16,
+    inputs: impl IntoIterator<Item = BrowserInput>,
+    out: &mut impl Write,
+) -> Option<String> {
+    // Make room below the current line, then draw.
+    let mut height = HistoryBrowser::height(rows);
+    let _ = out.write_all(NEWLINE.repeat(height - 1).as_bytes());
+    let _ = out.write_all(cursor_to_top(height).as_bytes());
+    let _ = out.write_all(browser.render(rows, cols).as_bytes());
+    let _ = out.flush();
+
+    let mut result = None;
+    for input in inputs {
+        let event = match input {
+            BrowserInput::Bytes(bytes) => browser.feed_bytes(&bytes),
+            BrowserInput::Resize(r, c) => {
+                rows = r;
+                cols = c;
+                BrowserEvent::Continue
+            }
+        };
+        match event {
+            BrowserEvent::Continue => {
+                let _ = out.write_all(cursor_to_top(height).as_bytes());
+                height = HistoryBrowser::height(rows);
+                let _ = out.write_all(browser.render(rows, cols).as_bytes());
+                let _ = out.flush();
+            }
+            BrowserEvent::Select(line) => {
+                result = Some(line);
+                break;
+            }
+            BrowserEvent::Cancel => break,
+        }
+    }
+    let _ = out.write_all(cursor_to_top(height).as_bytes());
+    let _ = out.write_all(b"\x1b[J");
+    let _ = out.flush();
+    result
+}
+
+/// Stdin as a `BrowserInput` stream. Polls so that a window resize
+/// (SIGWINCH) is noticed and reported while waiting for keys.
+struct TerminalInput {
+    fd: i32,
+    size: (u16, u16),
+}
+
+impl Iterator for TerminalInput {
+    type Item = BrowserInput;
+
+    fn next(&mut self) -> Option<BrowserInput> {
+        loop {
+            let mut pfd = libc::pollfd { fd: self.fd, events: libc::POLLIN, revents: 0 };
+            let ready = unsafe { libc::poll(&mut pfd, 1, RESIZE_POLL_MS) };
+            if ready > 0 {
+                let mut buf = [0u8; 64];
+                return match nix::unistd::read(self.fd, &mut buf) {
+                    Ok(n) if n > 0 => Some(BrowserInput::Bytes(buf[..n].to_vec())),
+                    _ => None,
+                };
+            }
+            let size = crate::get_terminal_size().unwrap_or(self.size);
+            if size != self.size {
+                self.size = size;
+                return Some(BrowserInput::Resize(size.0, size.1));
+            }
+        }
+    }
+}
+
+/// Browse `commands` (most recent first) on the terminal. Returns the
+/// chosen command line.
+pub fn browse(commands: Vec<String>, query: &str) -> Option<String> {
+    let (rows, cols) = crate::get_terminal_size().unwrap_or((24, 80));
+    let mut browser = HistoryBrowser::new(commands).with_query(query);
+    let input = TerminalInput { fd: std::io::stdin().as_raw_fd(), size: (rows, cols) };
+    common::write_stdout(b"\x1b[?25l");
+    let result = run_browser(&mut browser, rows, cols, input, &mut std::io::stdout());
+    common::write_stdout(b"\x1b[?25h");
+    result
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn sample() -> Vec<String> {
+        ["git push", "cargo build", "git status", "ls -la", "vim Cargo.toml"]
+            .iter()
+            .map(|s| s.to_string())
+            .collect()
+    }
+
+    #[test]
+    fn test_typing_filters_incrementally() {
+        let mut b = HistoryBrowser::new(sample());
+        assert_eq!(b.feed_bytes(b"g"), BrowserEvent::Continue);
+        assert_eq!(b.matches(), vec!["git push", "cargo build", "git status", "vim Cargo.toml"]);
+        b.feed_bytes(b"i");
+        assert_eq!(b.matches(), vec!["git push", "git status"]);
+        b.feed_bytes(b"\x7f\x7f");
+        assert_eq!(b.matches().len(), 5);
+    }
+
+    #[test]
+    fn test_mock_stream_selects_filtered_command() {
+        let mut b = HistoryBrowser::new(sample());
+        let inputs = vec![
+            BrowserInput::Bytes(b"g".to_vec()),
+            BrowserInput::Bytes(b"i".to_vec()),
+            BrowserInput::Bytes(b"\x1b[B".to_vec()),
+            BrowserInput::Bytes(b"\r".to_vec()),
+        ];
+        let mut out = Vec::new();
+        let picked = run_browser(&mut b, 24, 80, inputs, &mut out);
+        assert_eq!(picked.as_deref(), Some("git status"));
+        assert_eq!(picked.unwrap().as_bytes(), b"git status");
+        let out = String::from_utf8(out).unwrap();
+        assert!(out.ends_with("\x1b[J"), "drawn area is cleared");
+    }
+
+    #[test]
+    fn test_cancel_and_arrow_bounds() {
+        let mut b = HistoryBrowser::new(sample());
+        b.feed_bytes(b"\x1b[A\x1b[A");
+        assert_eq!(b.feed_bytes(b"\r"), BrowserEvent::Select("git push".into()));
+        assert_eq!(b.feed_bytes(b"\x1b"), BrowserEvent::Cancel);
+        assert_eq!(b.feed_bytes(b"\x03"), BrowserEvent::Cancel);
+        // Enter with nothing matching cancels instead of selecting.
+        let mut b = HistoryBrowser::new(sample()).with_query("zzz");
+        assert_eq!(b.feed_bytes(b"\r"), BrowserEvent::Cancel);
+    }
+
+    #[test]
+    fn test_render_scrolls_and_fits_height() {
+        let commands: Vec<String> = (0..50).map(|i| format!("echo {i}")).collect();
+        let mut b = HistoryBrowser::new(commands);
+        for _ in 0..10 {
+            b.feed_bytes(b"\x1b[B");
+        }
+        let out = b.render(10, 40);
+        // 10 rows: 6 list rows + query + separator + hint.
+        assert_eq!(HistoryBrowser::height(10), 9);
+        assert_eq!(out.matches(NEWLINE).count(), 8);
+        assert!(out.contains("> echo 10"));
+        assert!(!out.contains("echo 4\x1b"));
+    }
+
+    #[test]
+    fn test_resize_redraws_with_new_height() {
+        let mut b = HistoryBrowser::new(sample());
+        let mut out = Vec::new();
+        let inputs = vec![BrowserInput::Resize(8, 40), BrowserInput::Bytes(b"\x03".to_vec())];
+        assert!(run_browser(&mut b, 24, 80, inputs, &mut out).is_none());
+        let out = String::from_utf8(out).unwrap();
+        // Final clear moves up from the last line of the resized (shorter) block.
+        let up = format!("\x1b[{}A\r\x1b[J", HistoryBrowser::height(8) - 1);
+        assert!(out.ends_with(&up), "{:?}", &out[out.len().saturating_sub(40)..]);
+    }
+}
@@ -456,6 +456,7 @@ fn run_text_edit(
                     return None;
                 }
             }
+            #[allow(clippy::collapsible_match)]
             0x7f | 0x08 => {
                 if char_cursor > 0 {
                     chars.remove(char_cursor - 1);
@@ -1,5 +1,6 @@
 pub mod chat_layout;
 pub mod common;
+pub mod history_browser;
 pub mod inline_notice;
 pub mod line_editor;
 pub mod line_status;
@@ -307,6 +307,7 @@ impl ScrollView {
         let thumb_height = (viewport * viewport / total).max(1);
         let track_range = viewport.saturating_sub(thumb_height);
         let max_offset = total.saturating_sub(viewport);
+        #[allow(clippy::manual_checked_ops)]
         let thumb_top = if max_offset > 0 {
             scroll_offset * track_range / max_offset
         } else {
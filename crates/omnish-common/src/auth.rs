@@ -4,6 +4,7 @@ use std::path::{Path, PathBuf};
 use crate::config::omnish_dir;
 
 const TOKEN_BYTES: usize = 32;
+const MASTER_KEY_BYTES: usize = 32;
 
 /// Return the default auth token path: ~/.omnish/auth_token
 pub fn default_token_path() -> PathBuf {
@@ -42,6 +43,42 @@ fn generate_token() -> String {
     hex::encode(bytes)
 }
 
+/// Return the default stream encryption master key path: ~/.omnish/master.key
+pub fn default_master_key_path() -> PathBuf {
+    omnish_dir().join("master.key")
+}
+
+/// Load the hex-encoded master key from file, or generate a new one if it
+/// doesn't exist. The file is created with permission 0600. A file that
+/// exists but does not hold a valid key is an error rather than being
+/// replaced, since streams encrypted with the old key would be lost.
+pub fn load_or_create_master_key(path: &Path) -> Result<[u8; MASTER_KEY_BYTES]> {
+    if path.exists() {
+        let text = std::fs::read_to_string(path)?;
+        let bytes = hex::decode(text.trim())
+            .map_err(|e| anyhow::anyhow!("invalid master key in {}: {}", path.display(), e))?;
+        return bytes
+            .try_into()
+            .map_err(|_| anyhow::anyhow!("master key in {} is not {} bytes", path.display(), MASTER_KEY_BYTES));
+    }
+
+    use rand::Rng;
+    let key: [u8; MASTER_KEY_BYTES] = rand::thread_rng().gen();
+
+    if let Some(parent) = path.parent() {
+        std::fs::create_dir_all(parent)?;
+    }
+
+    std::fs::write(path, hex::encode(key))?;
+    #[cfg(unix)]
+    {
+        use std::os::unix::fs::PermissionsExt;
+        std::fs::set_permissions(path, std::fs::Permissions::from_mode(0o600))?;
+    }
+
+    Ok(key)
+}
+
 /// Load token from file. Returns error if file doesn't exist or is empty.
 pub fn load_token(path: &Path) -> Result<String> {
     let token = std::fs::read_to_string(path)
@@ -89,6 +126,18 @@ mod tests {
         assert_eq!(token1, token2);
     }
 
+    #[test]
+    fn test_load_or_create_master_key_reuses_existing() {
+        let dir = tempfile::tempdir().unwrap();
+        let path = dir.path().join("master.key");
+        let key1 = load_or_create_master_key(&path).unwrap();
+        let key2 = load_or_create_master_key(&path).unwrap();
+        assert_eq!(key1, key2);
+
+        std::fs::write(&path, "not hex").unwrap();
+        assert!(load_or_create_master_key(&path).is_err());
+    }
+
     #[test]
     fn test_load_token_missing_file() {
         let dir = tempfile::tempdir().unwrap();
@@ -0,0 +1,123 @@
+//! Shell completion scripts for the `omnish` and `omnish-daemon` command
+//! lines, printed by `<binary> generate-completions [bash|zsh|fish]`.
+
+/// Flags `omnish` accepts. Internal respawn flags (`--resume`, `--fd=`,
+/// ...) are left out on purpose.
+pub const OMNISH_FLAGS: &[&str] = &["--version", "-V", "--health", "--omnish-dir"];
+/// Flags `omnish-daemon` accepts.
+pub const DAEMON_FLAGS: &[&str] = &["--version", "-V", "--init", "--no-import-history", "--omnish-dir"];
+/// Subcommand both binaries accept.
+pub const GENERATE_COMPLETIONS: &str = "generate-completions";
+/// Shells `generate-completions` can target.
+pub const SHELLS: &[&str] = &["bash", "zsh", "fish"];
+
+const BASH: &str = r#"# bash completion for omnish and omnish-daemon
+_omnish_complete() {
+    local cur=${COMP_WORDS[COMP_CWORD]}
+    if [[ ${COMP_WORDS[1]} == generate-completions ]]; then
+        [[ $COMP_CWORD -eq 2 ]] && COMPREPLY=($(compgen -W "bash zsh fish" -- "$cur"))
+        return
+    fi
+    if [[ ${COMP_WORDS[COMP_CWORD-1]} == --omnish-dir ]]; then
+        COMPREPLY=($(compgen -d -- "$cur"))
+        return
+    fi
+    COMPREPLY=($(compgen -W "$1" -- "$cur"))
+}
+_omnish() { _omnish_complete "--version -V --health --omnish-dir generate-completions"; }
+_omnish_daemon() { _omnish_complete "--version -V --init --no-import-history --omnish-dir generate-completions"; }
+complete -F _omnish omnish
+complete -F _omnish_daemon omnish-daemon
+"#;
+
+const ZSH: &str = r#"#compdef omnish omnish-daemon
+# zsh completion for omnish and omnish-daemon
+case $service in
+    omnish)
+        _arguments \
+            '(- *)'{-V,--version}'[print version and exit]' \
+            '(- *)--health[check the daemon connection and exit]' \
+            '--omnish-dir[use DIR instead of ~/.omnish]:directory:_files -/' \
+            '1::command:(generate-completions)' \
+            '2::shell:(bash zsh fish)'
+        ;;
+    omnish-daemon)
+        _arguments \
+            '(- *)'{-V,--version}'[print version and exit]' \
+            '(- *)--init[create the auth token and TLS certificate, then exit]' \
+            '--no-import-history[do not seed context from ~/.bash_history on first start]' \
+            '--omnish-dir[use DIR instead of ~/.omnish]:directory:_files -/' \
+            '1::command:(generate-completions)' \
+            '2::shell:(bash zsh fish)'
+        ;;
+esac
+"#;
+
+const FISH: &str = r#"# fish completion for omnish and omnish-daemon
+complete -c omnish -s V -l version -d 'Print version and exit'
+complete -c omnish -l health -d 'Check the daemon connection and exit'
+complete -c omnish-daemon -s V -l version -d 'Print version and exit'
+complete -c omnish-daemon -l init -d 'Create the auth token and TLS certificate, then exit'
+complete -c omnish-daemon -l no-import-history -d 'Do not seed context from ~/.bash_history'
+for cmd in omnish omnish-daemon
+    complete -c $cmd -l omnish-dir -x -a '(__fish_complete_directories)' -d 'Use DIR instead of ~/.omnish'
+    complete -c $cmd -n __fish_use_subcommand -a generate-completions -d 'Print a shell completion script'
+    complete -c $cmd -n '__fish_seen_subcommand_from generate-completions' -f -a 'bash zsh fish'
+end
+"#;
+
+/// Completion script covering both binaries, for `bash`, `zsh` or `fish`.
+pub fn completion_script(shell: &str) -> Option<&'static str> {
+    match shell {
+        "bash" => Some(BASH),
+        "zsh" => Some(ZSH),
+        "fish" => Some(FISH),
+        _ => None,
+    }
+}
+
+pub fn bash_completion_script() -> &'static str {
+    BASH
+}
+
+/// Handle `generate-completions [shell]` (bash by default): print the
+/// script, or a usage line on stderr. Returns the process exit code.
+pub fn run(shell: Option<&str>) -> i32 {
+    match completion_script(shell.unwrap_or("bash")) {
+        Some(script) => {
+            print!("{}", script);
+            0
+        }
+        None => {
+            eprintln!("usage: {} [{}]", GENERATE_COMPLETIONS, SHELLS.join("|"));
+            2
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_bash_script_lists_every_flag() {
+        let script = bash_completion_script();
+        for flag in OMNISH_FLAGS.iter().chain(DAEMON_FLAGS) {
+            assert!(script.contains(flag), "missing {flag}");
+        }
+        assert!(script.contains("complete -F _omnish omnish\n"));
+        assert!(script.contains("complete -F _omnish_daemon omnish-daemon\n"));
+    }
+
+    #[test]
+    fn test_every_shell_has_a_script() {
+        for shell in SHELLS {
+            let script = completion_script(shell).unwrap();
+            assert!(script.contains(GENERATE_COMPLETIONS), "{shell}");
+            for long in OMNISH_FLAGS.iter().chain(DAEMON_FLAGS).filter_map(|f| f.strip_prefix("--")) {
+                assert!(script.contains(long), "{shell} script missing {long}");
+            }
+        }
+        assert!(completion_script("tcsh").is_none());
+    }
+}
@@ -1,7 +1,7 @@
 use anyhow::Result;
 use serde::{Deserialize, Serialize};
 use std::collections::HashMap;
-use std::path::PathBuf;
+use std::path::{Path, PathBuf};
 
 /// Serde helpers that accept both integer and string representations for numeric
 /// config fields, e.g. `context_window = 200000` and `context_window = "200000"`.
@@ -116,15 +116,53 @@ mod string_or_bool {
     }
 }
 
+/// Command-line flag both binaries accept to override `omnish_dir()`.
+pub const OMNISH_DIR_FLAG: &str = "--omnish-dir";
+
 /// Returns the omnish base directory.
-/// Priority: `$OMNISH_HOME` > `~/.omnish` > `/tmp/omnish`.
+/// Priority: `$OMNISH_DIR` (also set by `--omnish-dir`) > `$OMNISH_HOME` >
+/// `~/.omnish` if it exists > `$XDG_DATA_HOME/omnish` > `~/.omnish` > `/tmp/omnish`.
 pub fn omnish_dir() -> PathBuf {
-    if let Ok(dir) = std::env::var("OMNISH_HOME") {
+    let env_dir = |name| std::env::var(name).ok().filter(|d: &String| !d.is_empty());
+    if let Some(dir) = env_dir("OMNISH_DIR").or_else(|| env_dir("OMNISH_HOME")) {
         return PathBuf::from(dir);
     }
-    dirs::home_dir()
-        .map(|h| h.join(".omnish"))
-        .unwrap_or_else(|| PathBuf::from("/tmp/omnish"))
+    let home = dirs::home_dir().map(|h| h.join(".omnish"));
+    if let Some(home) = home.as_ref().filter(|h| h.exists()) {
+        return home.clone();
+    }
+    if let Some(xdg) = env_dir("XDG_DATA_HOME") {
+        return PathBuf::from(xdg).join("omnish");
+    }
+    home.unwrap_or_else(|| PathBuf::from("/tmp/omnish"))
+}
+
+/// Split `--omnish-dir <dir>` / `--omnish-dir=<dir>` out of `args`.
+/// Returns the directory (last one wins) and the remaining arguments.
+pub fn split_omnish_dir_flag(args: impl IntoIterator<Item = String>) -> (Option<String>, Vec<String>) {
+    let mut dir = None;
+    let mut rest = Vec::new();
+    let mut args = args.into_iter();
+    while let Some(arg) = args.next() {
+        if arg == OMNISH_DIR_FLAG {
+            dir = args.next().or(dir);
+        } else if let Some(value) = arg.strip_prefix(OMNISH_DIR_FLAG).and_then(|v| v.strip_prefix('=')) {
+            dir = Some(value.to_string());
+        } else {
+            rest.push(arg);
+        }
+    }
+    (dir, rest)
+}
+
+/// Export `--omnish-dir` from the process arguments as `$OMNISH_DIR` so
+/// `omnish_dir()` and child processes see it. Call first thing in `main`,
+/// before a runtime or any other thread is started: `set_var` is unsound
+/// while other threads may read the environment.
+pub fn apply_omnish_dir_flag() {
+    if let (Some(dir), _) = split_omnish_dir_flag(std::env::args().skip(1)) {
+        std::env::set_var("OMNISH_DIR", dir);
+    }
 }
 
 fn default_socket_path() -> String {
@@ -154,6 +192,75 @@ pub struct ClientConfig {
     pub onboarded: bool,
     #[serde(default)]
     pub sandbox: ClientSandboxConfig,
+    /// Only `tls.ca_cert_path` is used on the client side.
+    #[serde(default)]
+    pub tls: TlsConfig,
+    /// Max messages held for replay while the daemon is unreachable.
+    #[serde(default = "default_buffer_size", deserialize_with = "string_or_int::deserialize")]
+    pub buffer_size: usize,
+    /// When the replay buffer is full, drop the oldest `IoData` before any
+    /// `CommandComplete` so command records survive long outages.
+    #[serde(default, deserialize_with = "string_or_bool::deserialize")]
+    pub buffer_prioritize_commands: bool,
+    /// First delay before reconnecting to the daemon; doubles per failed
+    /// attempt (with +/-25% jitter) up to `reconnect_max_ms`.
+    #[serde(default = "default_reconnect_initial_ms", deserialize_with = "string_or_int::deserialize")]
+    pub reconnect_initial_ms: u64,
+    #[serde(default = "default_reconnect_max_ms", deserialize_with = "string_or_int::deserialize")]
+    pub reconnect_max_ms: u64,
+    /// Connections opened to the daemon. Session traffic always uses the
+    /// first; `/` queries and other requests are spread over all of them.
+    #[serde(default = "default_connection_pool_size", deserialize_with = "string_or_int::deserialize")]
+    pub connection_pool_size: usize,
+    /// Query responses longer than this many lines open in `less` instead
+    /// of scrolling past. Unset means terminal height - 4; 0 disables paging.
+    #[serde(default, deserialize_with = "string_or_int::option::deserialize")]
+    pub pager_threshold_lines: Option<u16>,
+    #[serde(default)]
+    pub throttle: ThrottleConfig,
+    /// Give up on a `/` query to the daemon after this many milliseconds;
+    /// the daemon cancels the LLM call at the same point. 0 waits forever.
+    #[serde(default = "default_query_timeout_ms", deserialize_with = "string_or_int::deserialize")]
+    pub query_timeout_ms: u64,
+}
+
+fn default_buffer_size() -> usize {
+    10_000
+}
+
+fn default_query_timeout_ms() -> u64 {
+    60_000
+}
+
+fn default_connection_pool_size() -> usize {
+    1
+}
+
+fn default_reconnect_initial_ms() -> u64 {
+    100
+}
+
+fn default_reconnect_max_ms() -> u64 {
+    30_000
+}
+
+/// Limits on how much command output the client sends to the daemon.
+#[derive(Debug, Serialize, Deserialize, Clone, PartialEq)]
+pub struct ThrottleConfig {
+    /// Output is never throttled while its command has run for less than
+    /// this many milliseconds, so quick commands like `ls` are kept whole.
+    #[serde(default = "default_fast_command_ms", deserialize_with = "string_or_int::deserialize")]
+    pub fast_command_ms: u64,
+}
+
+fn default_fast_command_ms() -> u64 {
+    500
+}
+
+impl Default for ThrottleConfig {
+    fn default() -> Self {
+        Self { fast_command_ms: default_fast_command_ms() }
+    }
 }
 
 /// Client-local sandbox settings. Per-host because sandbox capability
@@ -203,6 +310,15 @@ impl Default for ClientConfig {
             client_addr: None,
             onboarded: false,
             sandbox: ClientSandboxConfig::default(),
+            tls: TlsConfig::default(),
+            buffer_size: default_buffer_size(),
+            buffer_prioritize_commands: false,
+            reconnect_initial_ms: default_reconnect_initial_ms(),
+            reconnect_max_ms: default_reconnect_max_ms(),
+            connection_pool_size: default_connection_pool_size(),
+            pager_threshold_lines: None,
+            throttle: ThrottleConfig::default(),
+            query_timeout_ms: default_query_timeout_ms(),
         }
     }
 }
@@ -395,6 +511,10 @@ impl<'de> serde::Deserialize<'de> for ProxyConfig {
 pub struct DaemonConfig {
     #[serde(default = "default_socket_path")]
     pub listen_addr: String,
+    /// Addresses served at the same time, e.g. a Unix socket plus a TCP
+    /// port. Empty means just `listen_addr`.
+    #[serde(default, skip_serializing_if = "Vec::is_empty")]
+    pub listen_addrs: Vec<String>,
     #[serde(default)]
     pub proxy: ProxyConfig,
     /// Backward compat: old top-level `no_proxy` key merges into `proxy.no_proxy`.
@@ -416,12 +536,136 @@ pub struct DaemonConfig {
     pub sandbox: SandboxConfig,
     #[serde(default)]
     pub client: ClientSection,
+    /// Permissions of the Unix socket (e.g. `0o660`). Default: owner-only 0600.
+    #[serde(default)]
+    pub socket_mode: Option<u32>,
+    /// Warn when a request takes longer than this to handle (ms). 0 disables.
+    #[serde(default = "default_slow_request_warn_ms")]
+    pub slow_request_warn_ms: u64,
+    /// Most recent `~/.bash_history` entries imported on first start. 0 disables.
+    #[serde(default = "default_max_import_lines", deserialize_with = "string_or_int::deserialize")]
+    pub max_import_lines: usize,
+    #[serde(default)]
+    pub search: SearchConfig,
+    #[serde(default)]
+    pub storage: StorageConfig,
+    #[serde(default)]
+    pub tls: TlsConfig,
+    #[serde(default)]
+    pub http: HttpConfig,
+}
+
+/// Certificate paths for TCP connections. Unset paths fall back to the
+/// self-signed pair generated in `~/.omnish/tls/`, which clients pin and
+/// accept at any address. With `ca_cert_path`, clients verify the
+/// certificate against the host in their daemon address.
+#[derive(Debug, Serialize, Deserialize, Clone, Default, PartialEq)]
+pub struct TlsConfig {
+    /// Server certificate chain (PEM) presented by the daemon.
+    #[serde(default)]
+    pub cert_path: Option<String>,
+    /// Private key (PEM) for `cert_path`.
+    #[serde(default)]
+    pub key_path: Option<String>,
+    /// CA certificate (PEM) clients use to verify the daemon.
+    #[serde(default)]
+    pub ca_cert_path: Option<String>,
+}
+
+/// Optional HTTP API served alongside the Unix socket, for integrations
+/// that cannot speak the socket protocol. Listens on 127.0.0.1 only.
+#[derive(Debug, Serialize, Deserialize, Clone, PartialEq)]
+pub struct HttpConfig {
+    #[serde(default, deserialize_with = "string_or_bool::deserialize")]
+    pub enabled: bool,
+    #[serde(default = "default_http_port", deserialize_with = "string_or_int::deserialize")]
+    pub port: u16,
+    /// Bearer token clients must send. Defaults to the daemon's auth token
+    /// (`~/.omnish/auth_token`).
+    #[serde(default)]
+    pub auth_token: Option<String>,
+}
+
+impl Default for HttpConfig {
+    fn default() -> Self {
+        Self {
+            enabled: false,
+            port: default_http_port(),
+            auth_token: None,
+        }
+    }
+}
+
+fn default_http_port() -> u16 {
+    8787
+}
+
+/// Settings for on-disk session data.
+#[derive(Debug, Serialize, Deserialize, Clone, PartialEq)]
+pub struct StorageConfig {
+    /// zstd-compress newly created stream.bin files. Existing files keep
+    /// their format.
+    #[serde(default, deserialize_with = "string_or_bool::deserialize")]
+    pub compress_streams: bool,
+    /// AES-256-GCM encrypt newly created stream.bin files with a per-session
+    /// key derived from `$omnish_dir/master.key` (created on first use).
+    /// Takes precedence over `compress_streams`.
+    #[serde(default, deserialize_with = "string_or_bool::deserialize")]
+    pub encrypt_streams: bool,
+    /// Cap on a session's stream.bin (uncompressed bytes). The oldest output
+    /// is dropped once a command pushes the file past it.
+    #[serde(default = "default_max_stream_bytes_per_session", deserialize_with = "string_or_int::deserialize")]
+    pub max_stream_bytes_per_session: u64,
+}
+
+impl Default for StorageConfig {
+    fn default() -> Self {
+        Self {
+            compress_streams: false,
+            encrypt_streams: false,
+            max_stream_bytes_per_session: default_max_stream_bytes_per_session(),
+        }
+    }
+}
+
+fn default_max_stream_bytes_per_session() -> u64 {
+    100 * 1024 * 1024
+}
+
+/// Settings for `/search` and `/grep` over stored command output.
+#[derive(Debug, Serialize, Deserialize, Clone, PartialEq)]
+pub struct SearchConfig {
+    /// Stop reading a session's stream.bin after this many bytes of command
+    /// output (newest commands are read first).
+    #[serde(default = "default_search_max_bytes_per_session", deserialize_with = "string_or_int::deserialize")]
+    pub max_bytes_per_session: u64,
+}
+
+impl Default for SearchConfig {
+    fn default() -> Self {
+        Self {
+            max_bytes_per_session: default_search_max_bytes_per_session(),
+        }
+    }
+}
+
+fn default_search_max_bytes_per_session() -> u64 {
+    8 * 1024 * 1024
+}
+
+fn default_slow_request_warn_ms() -> u64 {
+    5000
+}
+
+fn default_max_import_lines() -> usize {
+    1000
 }
 
 impl Default for DaemonConfig {
     fn default() -> Self {
         Self {
             listen_addr: default_socket_path(),
+            listen_addrs: Vec::new(),
             proxy: ProxyConfig::default(),
             no_proxy: None,
             llm: LlmConfig::default(),
@@ -430,11 +674,28 @@ impl Default for DaemonConfig {
             plugins: HashMap::new(),
             sandbox: SandboxConfig::default(),
             client: ClientSection::default(),
+            socket_mode: None,
+            slow_request_warn_ms: default_slow_request_warn_ms(),
+            max_import_lines: default_max_import_lines(),
+            search: SearchConfig::default(),
+            storage: StorageConfig::default(),
+            tls: TlsConfig::default(),
+            http: HttpConfig::default(),
         }
     }
 }
 
 impl DaemonConfig {
+    /// Every address to listen on: `listen_addrs`, or `[listen_addr]`
+    /// when that is empty.
+    pub fn effective_listen_addrs(&self) -> Vec<String> {
+        if self.listen_addrs.is_empty() {
+            vec![self.listen_addr.clone()]
+        } else {
+            self.listen_addrs.clone()
+        }
+    }
+
     /// Merge deprecated top-level `no_proxy` into `proxy.no_proxy`.
     pub fn normalize(&mut self) {
         if let Some(np) = self.no_proxy.take() {
@@ -469,6 +730,64 @@ pub fn load_daemon_config() -> Result<DaemonConfig> {
     }
 }
 
+/// Per-project override file, looked up from a session's cwd upwards.
+pub const PROJECT_CONFIG_FILE: &str = ".omnish.toml";
+
+/// Path of the nearest `.omnish.toml` in `cwd` or one of its ancestors.
+pub fn find_project_config(cwd: &Path) -> Option<PathBuf> {
+    cwd.ancestors()
+        .map(|dir| dir.join(PROJECT_CONFIG_FILE))
+        .find(|p| p.is_file())
+}
+
+/// Read a project config file as the raw table it contains, so merging
+/// only sees the keys the file actually sets. Returns None when it cannot
+/// be read or does not describe a valid config.
+pub fn load_project_config(path: &Path) -> Option<toml::Table> {
+    let contents = std::fs::read_to_string(path).ok()?;
+    let parsed = toml::from_str::<toml::Table>(&contents).and_then(|table| {
+        toml::Value::Table(table.clone()).try_into::<DaemonConfig>()?;
+        Ok(table)
+    });
+    match parsed {
+        Ok(table) => Some(table),
+        Err(e) => {
+            tracing::warn!("ignoring {}: {}", path.display(), e);
+            None
+        }
+    }
+}
+
+/// Overlay the keys set in `project` on `global`. Tables are merged key by
+/// key, so keys the project file leaves out keep the global setting.
+pub fn merge_configs(global: &DaemonConfig, project: &toml::Table) -> DaemonConfig {
+    let Ok(toml::Value::Table(mut base)) = toml::Value::try_from(global) else {
+        return global.clone();
+    };
+    merge_tables(&mut base, project.clone());
+    match toml::Value::Table(base).try_into::<DaemonConfig>() {
+        Ok(mut merged) => {
+            merged.normalize();
+            merged
+        }
+        Err(e) => {
+            tracing::warn!("ignoring project config: {}", e);
+            global.clone()
+        }
+    }
+}
+
+fn merge_tables(base: &mut toml::Table, overlay: toml::Table) {
+    for (key, value) in overlay {
+        match (base.get_mut(&key), value) {
+            (Some(toml::Value::Table(b)), toml::Value::Table(o)) => merge_tables(b, o),
+            (_, value) => {
+                base.insert(key, value);
+            }
+        }
+    }
+}
+
 /// Sanitize TOML text by removing duplicate table headers and duplicate keys
 /// within each section. Keeps the first occurrence of each.
 fn sanitize_toml(input: &str) -> String {
@@ -523,6 +842,10 @@ pub struct ShellConfig {
     /// Prefix to resume last chat thread (default: "::")
     #[serde(default = "default_resume_prefix")]
     pub resume_prefix: String,
+    /// Additional prefixes intercepted like `command_prefix`, whose message
+    /// runs as a `/` command (e.g. `"!"` makes `!sessions` run `/sessions`).
+    #[serde(default)]
+    pub extra_prefixes: Vec<String>,
     #[serde(default = "default_intercept_gap_ms", deserialize_with = "string_or_int::deserialize")]
     pub intercept_gap_ms: u64,
     #[serde(default = "default_ghost_timeout_ms", deserialize_with = "string_or_int::deserialize")]
@@ -532,6 +855,24 @@ pub struct ShellConfig {
     pub developer_mode: bool,
     #[serde(default = "default_true", deserialize_with = "string_or_bool::deserialize")]
     pub completion_enabled: bool,
+    /// When true, a single Enter after the chat prefix inserts a newline and
+    /// a double Enter sends the message.
+    #[serde(default, deserialize_with = "string_or_bool::deserialize")]
+    pub multiline_chat: bool,
+    /// When true, Tab accepts ghost text one word at a time instead of whole.
+    #[serde(default, deserialize_with = "string_or_bool::deserialize")]
+    pub tab_accepts_word: bool,
+    /// When true, the bash hook also sources the `omnish`/`omnish-daemon`
+    /// flag completion script.
+    #[serde(default, deserialize_with = "string_or_bool::deserialize")]
+    pub source_completions: bool,
+    /// Environment variables reported to the daemon at session start as
+    /// `env.<NAME>` attributes and shown to the LLM in context.
+    #[serde(default = "default_session_env_vars")]
+    pub session_env_vars: Vec<String>,
+    /// How long (seconds) a cached ghost completion stays valid. 0 disables the cache.
+    #[serde(default = "default_completion_cache_ttl_secs", deserialize_with = "string_or_int::deserialize")]
+    pub completion_cache_ttl_secs: u64,
     /// Use extended Unicode characters (e.g. ⎿) in the UI.
     /// Set to false for terminals lacking font support (e.g. ConEmu with default fonts).
     /// In the future this may be set automatically via terminal detection.
@@ -541,6 +882,13 @@ pub struct ShellConfig {
     /// Client-side default is "en"; the daemon pushes its locale-detected value.
     #[serde(default = "default_language_en")]
     pub language: String,
+    /// Readline keymap of the shell: "emacs" or "vi" (`set -o vi`).
+    #[serde(default = "default_readline_keymap")]
+    pub readline_keymap: String,
+}
+
+fn default_readline_keymap() -> String {
+    "emacs".to_string()
 }
 
 impl Default for ShellConfig {
@@ -549,12 +897,19 @@ impl Default for ShellConfig {
             command: default_shell_command(),
             command_prefix: default_command_prefix(),
             resume_prefix: default_resume_prefix(),
+            extra_prefixes: Vec::new(),
             intercept_gap_ms: default_intercept_gap_ms(),
             ghost_timeout_ms: default_ghost_timeout_ms(),
             developer_mode: default_developer_mode(),
             completion_enabled: true,
+            multiline_chat: false,
+            tab_accepts_word: false,
+            source_completions: false,
+            session_env_vars: default_session_env_vars(),
+            completion_cache_ttl_secs: default_completion_cache_ttl_secs(),
             extended_unicode: false,
             language: default_language_en(),
+            readline_keymap: default_readline_keymap(),
         }
     }
 }
@@ -648,6 +1003,17 @@ fn default_ghost_timeout_ms() -> u64 {
     10_000
 }
 
+fn default_session_env_vars() -> Vec<String> {
+    ["PATH", "VIRTUAL_ENV", "CONDA_DEFAULT_ENV", "GOPATH", "JAVA_HOME", "KUBECONFIG"]
+        .iter()
+        .map(|s| s.to_string())
+        .collect()
+}
+
+fn default_completion_cache_ttl_secs() -> u64 {
+    300
+}
+
 fn default_developer_mode() -> bool {
     false
 }
@@ -666,9 +1032,16 @@ pub struct LlmConfig {
     ///   chat = "claude"
     #[serde(default)]
     pub use_cases: HashMap<String, String>,
+    /// Backend name to retry with when a request to the use-case backend
+    /// fails, e.g. a local `ollama` backend.
+    #[serde(default)]
+    pub fallback: Option<String>,
     /// Optional Langfuse observability integration
     #[serde(default)]
     pub langfuse: Option<LangfuseConfig>,
+    /// Client-side limits shared by all backends.
+    #[serde(default)]
+    pub rate_limit: RateLimitConfig,
 }
 
 impl Default for LlmConfig {
@@ -677,11 +1050,42 @@ impl Default for LlmConfig {
             default: default_llm_name(),
             backends: HashMap::new(),
             use_cases: HashMap::new(),
+            fallback: None,
             langfuse: None,
+            rate_limit: RateLimitConfig::default(),
+        }
+    }
+}
+
+/// LLM API rate limits. 0 disables a limit.
+///   [llm.rate_limit]
+///   requests_per_minute = 50
+///   tokens_per_minute = 40000
+#[derive(Debug, Serialize, Deserialize, Clone, PartialEq)]
+pub struct RateLimitConfig {
+    #[serde(default, deserialize_with = "string_or_int::deserialize")]
+    pub requests_per_minute: u32,
+    #[serde(default, deserialize_with = "string_or_int::deserialize")]
+    pub tokens_per_minute: u32,
+    /// Longest a request may wait for quota before failing (ms).
+    #[serde(default = "default_rate_limit_max_wait_ms", deserialize_with = "string_or_int::deserialize")]
+    pub max_wait_ms: u64,
+}
+
+impl Default for RateLimitConfig {
+    fn default() -> Self {
+        Self {
+            requests_per_minute: 0,
+            tokens_per_minute: 0,
+            max_wait_ms: default_rate_limit_max_wait_ms(),
         }
     }
 }
 
+fn default_rate_limit_max_wait_ms() -> u64 {
+    10_000
+}
+
 /// Langfuse observability configuration.
 ///
 /// Example:
@@ -739,7 +1143,7 @@ pub struct LlmBackendConfig {
 // ---------------------------------------------------------------------------
 
 /// Completion-specific context configuration
-#[derive(Debug, Serialize, Deserialize, Clone)]
+#[derive(Debug, Serialize, Deserialize, Clone, PartialEq)]
 pub struct CompletionContextConfig {
     /// Number of recent commands shown with full detail (output, timing, exit code).
     #[serde(default = "default_detailed_commands", deserialize_with = "string_or_int::deserialize")]
@@ -757,9 +1161,17 @@ pub struct CompletionContextConfig {
     /// Minimum number of commands to keep from the current session.
     #[serde(default = "default_min_current_session_commands", deserialize_with = "string_or_int::deserialize")]
     pub min_current_session_commands: usize,
-    /// Maximum character limit for completion context.
+    /// Maximum estimated token count for completion context.
     /// If exceeded, the system will try reducing history_commands + detailed_commands by 1/4.
-    #[serde(default = "default_max_context_chars", deserialize_with = "string_or_int::option::deserialize")]
+    #[serde(default = "default_max_context_tokens", deserialize_with = "string_or_int::option::deserialize")]
+    pub max_context_tokens: Option<usize>,
+    /// Legacy character limit, replaced by `max_context_tokens`. Converted
+    /// to tokens when `max_context_tokens` is not set.
+    #[serde(
+        default,
+        skip_serializing_if = "Option::is_none",
+        deserialize_with = "string_or_int::option::deserialize"
+    )]
     pub max_context_chars: Option<usize>,
     /// Minimum number of detailed commands after elastic window reset.
     #[serde(default = "default_detailed_min", deserialize_with = "string_or_int::deserialize")]
@@ -772,6 +1184,29 @@ pub struct CompletionContextConfig {
     /// 0 disables the feature.
     #[serde(default = "default_cwd_history_limit", deserialize_with = "string_or_int::deserialize")]
     pub cwd_history_limit: usize,
+    /// Regex; commands whose command line matches it are left out of
+    /// context (e.g. `^(cd|ls|clear|echo)\b`).
+    #[serde(default)]
+    pub exclude_commands_pattern: Option<String>,
+    /// Leave out commands started more than this many hours ago.
+    #[serde(default, deserialize_with = "string_or_int::option::deserialize")]
+    pub max_command_age_hours: Option<u64>,
+    /// Show up to this many of the latest commands from the current
+    /// session's cwd with full output, even when older than the detailed
+    /// window. 0 disables the preference.
+    #[serde(default, deserialize_with = "string_or_int::deserialize")]
+    pub cwd_bonus: usize,
+    /// Only put commands carrying every one of these tags (set with `/tag`)
+    /// in chat context. Empty includes all commands.
+    #[serde(default)]
+    pub required_tags: Vec<String>,
+    /// Cap on each command's output in chat context, in bytes, applied
+    /// after head/tail line truncation.
+    #[serde(default, deserialize_with = "string_or_int::option::deserialize")]
+    pub max_output_bytes_per_command: Option<usize>,
+    /// Give up building chat context after this many milliseconds.
+    #[serde(default = "default_context_build_timeout_ms", deserialize_with = "string_or_int::deserialize")]
+    pub context_build_timeout_ms: u64,
 }
 
 impl Default for CompletionContextConfig {
@@ -783,18 +1218,75 @@ impl Default for CompletionContextConfig {
             tail_lines: default_tail_lines(),
             max_line_width: default_max_line_width(),
             min_current_session_commands: default_min_current_session_commands(),
-            max_context_chars: default_max_context_chars(),
+            max_context_tokens: default_max_context_tokens(),
+            max_context_chars: None,
             detailed_min: default_detailed_min(),
             detailed_max: default_detailed_max(),
             cwd_history_limit: default_cwd_history_limit(),
+            exclude_commands_pattern: None,
+            max_command_age_hours: None,
+            cwd_bonus: 0,
+            required_tags: Vec::new(),
+            max_output_bytes_per_command: None,
+            context_build_timeout_ms: default_context_build_timeout_ms(),
         }
     }
 }
 
-#[derive(Debug, Serialize, Deserialize, Clone, Default)]
+#[derive(Debug, Serialize, Deserialize, Clone, Default, PartialEq)]
 pub struct ContextConfig {
     #[serde(default)]
     pub completion: CompletionContextConfig,
+    /// Regex patterns whose matches are replaced with `***REDACTED***` in
+    /// session output before it is written to disk. Merged with patterns
+    /// from `~/.omnish/redact_patterns.toml`.
+    #[serde(default)]
+    pub redact_patterns: Vec<String>,
+    #[serde(default)]
+    pub tracker: TrackerConfig,
+    /// How chat context is rendered. Requests can override it with
+    /// `Request::format_hint`.
+    #[serde(default)]
+    pub format: ContextFormat,
+}
+
+/// Layout of the chat context given to the LLM.
+#[derive(Debug, Serialize, Deserialize, Clone, Copy, Default, PartialEq, Eq, Hash)]
+#[serde(rename_all = "lowercase")]
+pub enum ContextFormat {
+    /// Commands grouped by session, current session last.
+    #[default]
+    Grouped,
+    /// Commands of all sessions in time order.
+    Interleaved,
+    /// Structured XML for tool integrations.
+    Xml,
+}
+
+impl ContextFormat {
+    /// Parse a config or `format_hint` value (`grouped`, `interleaved`, `xml`).
+    pub fn parse(s: &str) -> Option<Self> {
+        match s.trim().to_ascii_lowercase().as_str() {
+            "grouped" => Some(Self::Grouped),
+            "interleaved" => Some(Self::Interleaved),
+            "xml" => Some(Self::Xml),
+            _ => None,
+        }
+    }
+}
+
+/// Command tracking for shells without OSC 133 integration.
+#[derive(Debug, Serialize, Deserialize, Clone, Default, PartialEq)]
+pub struct TrackerConfig {
+    /// Regex that recognizes the shell prompt. A `cmd` capture group, if
+    /// present, extracts the command from the echoed prompt line. `None`
+    /// keeps the built-in pattern.
+    #[serde(default)]
+    pub prompt_regex: Option<String>,
+    /// Regex that recognizes the continuation prompt (PS2). Lines entered at
+    /// it are joined into one command. `None` keeps the built-in `> `.
+    #[serde(default)]
+    pub ps2_regex: Option<String>,
 }
 
 fn default_detailed_commands() -> usize {
@@ -821,7 +1313,7 @@ fn default_min_current_session_commands() -> usize {
     5
 }
 
-fn default_max_context_chars() -> Option<usize> {
+fn default_max_context_tokens() -> Option<usize> {
     None
 }
 
@@ -837,6 +1329,10 @@ fn default_cwd_history_limit() -> usize {
     10
 }
 
+fn default_context_build_timeout_ms() -> u64 {
+    5000
+}
+
 #[cfg(test)]
 mod tests {
     use super::*;
@@ -1,4 +1,5 @@
 pub mod auth;
+pub mod completions;
 pub mod config;
 pub mod config_edit;
 pub mod plugin_bundle;
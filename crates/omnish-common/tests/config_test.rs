@@ -1,4 +1,4 @@
-use omnish_common::config::{ClientConfig, DaemonConfig};
+use omnish_common::config::{ClientConfig, ContextFormat, DaemonConfig};
 use std::sync::Mutex;
 
 /// Tests that mutate env vars (OMNISH_*_CONFIG) must hold this lock to avoid
@@ -47,6 +47,84 @@ fn test_client_config_defaults() {
     assert!(config.daemon_addr.ends_with("omnish.sock"));
 }
 
+#[test]
+fn test_client_buffer_config() {
+    let config: ClientConfig = toml::from_str("").unwrap();
+    assert_eq!(config.buffer_size, 10_000);
+    assert!(!config.buffer_prioritize_commands);
+
+    let toml_str = r#"
+buffer_size = "500"
+buffer_prioritize_commands = true
+"#;
+    let config: ClientConfig = toml::from_str(toml_str).unwrap();
+    assert_eq!(config.buffer_size, 500);
+    assert!(config.buffer_prioritize_commands);
+}
+
+#[test]
+fn test_shell_readline_keymap() {
+    let config: ClientConfig = toml::from_str("").unwrap();
+    assert_eq!(config.shell.readline_keymap, "emacs");
+
+    let config: ClientConfig = toml::from_str("[shell]\nreadline_keymap = \"vi\"\n").unwrap();
+    assert_eq!(config.shell.readline_keymap, "vi");
+}
+
+#[test]
+fn test_client_reconnect_config() {
+    let config: ClientConfig = toml::from_str("").unwrap();
+    assert_eq!(config.reconnect_initial_ms, 100);
+    assert_eq!(config.reconnect_max_ms, 30_000);
+
+    let config: ClientConfig = toml::from_str("reconnect_initial_ms = 250\nreconnect_max_ms = \"5000\"").unwrap();
+    assert_eq!(config.reconnect_initial_ms, 250);
+    assert_eq!(config.reconnect_max_ms, 5000);
+}
+
+#[test]
+fn test_client_pager_threshold_config() {
+    let config: ClientConfig = toml::from_str("").unwrap();
+    assert_eq!(config.pager_threshold_lines, None);
+
+    let config: ClientConfig = toml::from_str("pager_threshold_lines = \"60\"").unwrap();
+    assert_eq!(config.pager_threshold_lines, Some(60));
+}
+
+#[test]
+fn test_client_throttle_config() {
+    let config: ClientConfig = toml::from_str("").unwrap();
+    assert_eq!(config.throttle.fast_command_ms, 500);
+
+    let config: ClientConfig = toml::from_str("[throttle]\nfast_command_ms = \"200\"").unwrap();
+    assert_eq!(config.throttle.fast_command_ms, 200);
+}
+
+#[test]
+fn test_client_query_timeout_config() {
+    let config: ClientConfig = toml::from_str("").unwrap();
+    assert_eq!(config.query_timeout_ms, 60_000);
+
+    let config: ClientConfig = toml::from_str("query_timeout_ms = \"1000\"").unwrap();
+    assert_eq!(config.query_timeout_ms, 1000);
+}
+
+#[test]
+fn test_daemon_listen_addrs_fall_back_to_listen_addr() {
+    let config: DaemonConfig = toml::from_str(r#"listen_addr = "/tmp/a.sock""#).unwrap();
+    assert_eq!(config.effective_listen_addrs(), vec!["/tmp/a.sock".to_string()]);
+
+    let toml_str = r#"
+listen_addr = "/tmp/a.sock"
+listen_addrs = ["/tmp/b.sock", "tcp://0.0.0.0:9500"]
+"#;
+    let config: DaemonConfig = toml::from_str(toml_str).unwrap();
+    assert_eq!(
+        config.effective_listen_addrs(),
+        vec!["/tmp/b.sock".to_string(), "tcp://0.0.0.0:9500".to_string()]
+    );
+}
+
 #[test]
 fn test_daemon_config_defaults() {
     let toml_str = "";
@@ -146,10 +224,12 @@ session_evict_hours = "24"
 [shell]
 intercept_gap_ms = "500"
 ghost_timeout_ms = "5000"
+completion_cache_ttl_secs = "60"
 "#;
     let client: ClientConfig = toml::from_str(client_toml).unwrap();
     assert_eq!(client.shell.intercept_gap_ms, 500);
     assert_eq!(client.shell.ghost_timeout_ms, 5000);
+    assert_eq!(client.shell.completion_cache_ttl_secs, 60);
 }
 
 #[test]
@@ -163,3 +243,83 @@ context_window = 128000
     let config: DaemonConfig = toml::from_str(toml_str).unwrap();
     assert_eq!(config.llm.backends["test"].context_window, Some(128000));
 }
+
+#[test]
+fn test_tracker_prompt_regex_config() {
+    let config: DaemonConfig = toml::from_str("").unwrap();
+    assert_eq!(config.context.tracker.prompt_regex, None);
+
+    let config: DaemonConfig = toml::from_str("[context.tracker]\nprompt_regex = '\\$\\s+'\n").unwrap();
+    assert_eq!(config.context.tracker.prompt_regex.as_deref(), Some(r"\$\s+"));
+    assert_eq!(config.context.tracker.ps2_regex, None);
+
+    let config: DaemonConfig = toml::from_str("[context.tracker]\nps2_regex = '^\\.\\.\\. $'\n").unwrap();
+    assert_eq!(config.context.tracker.ps2_regex.as_deref(), Some(r"^\.\.\. $"));
+}
+
+#[test]
+fn test_context_format_config() {
+    let config: DaemonConfig = toml::from_str("").unwrap();
+    assert_eq!(config.context.format, ContextFormat::Grouped);
+
+    let config: DaemonConfig = toml::from_str("[context]\nformat = \"xml\"\n").unwrap();
+    assert_eq!(config.context.format, ContextFormat::Xml);
+    assert!(toml::from_str::<DaemonConfig>("[context]\nformat = \"yaml\"\n").is_err());
+    assert_eq!(ContextFormat::parse(" Interleaved "), Some(ContextFormat::Interleaved));
+    assert_eq!(ContextFormat::parse("yaml"), None);
+}
+
+#[test]
+fn test_storage_encrypt_streams_config() {
+    let config: DaemonConfig = toml::from_str("").unwrap();
+    assert!(!config.storage.encrypt_streams);
+
+    let config: DaemonConfig = toml::from_str("[storage]\nencrypt_streams = true\n").unwrap();
+    assert!(config.storage.encrypt_streams);
+}
+
+#[test]
+fn test_max_context_tokens_keeps_legacy_chars_apart() {
+    let config: DaemonConfig = toml::from_str("[context.completion]\nmax_context_tokens = 4000\n").unwrap();
+    assert_eq!(config.context.completion.max_context_tokens, Some(4000));
+    assert_eq!(config.context.completion.max_context_chars, None);
+
+    // A character count is not a token count; the daemon converts it
+    let legacy: DaemonConfig = toml::from_str("[context.completion]\nmax_context_chars = 8000\n").unwrap();
+    assert_eq!(legacy.context.completion.max_context_tokens, None);
+    assert_eq!(legacy.context.completion.max_context_chars, Some(8000));
+}
+
+#[test]
+fn test_project_config_lookup_and_merge() {
+    use omnish_common::config::{find_project_config, load_project_config, merge_configs};
+
+    let dir = tempfile::tempdir().unwrap();
+    let nested = dir.path().join("a/b");
+    std::fs::create_dir_all(&nested).unwrap();
+    assert!(find_project_config(&nested).is_none());
+
+    // detailed_commands = 10 is the built-in default; it must still win
+    std::fs::write(
+        dir.path().join(".omnish.toml"),
+        "[context.completion]\ndetailed_commands = 10\n\n[llm.use_cases]\nchat = \"local\"\n",
+    )
+    .unwrap();
+    let path = find_project_config(&nested).unwrap();
+    assert_eq!(path, dir.path().join(".omnish.toml"));
+    let project = load_project_config(&path).unwrap();
+
+    let global: DaemonConfig = toml::from_str(
+        "[context.completion]\ndetailed_commands = 20\nhistory_commands = 7\n\n[llm.use_cases]\ncompletion = \"fast\"\n",
+    )
+    .unwrap();
+    let merged = merge_configs(&global, &project);
+    assert_eq!(merged.context.completion.detailed_commands, 10);
+    // Keys the project leaves out keep the global value
+    assert_eq!(merged.context.completion.history_commands, 7);
+    assert_eq!(merged.llm.use_cases["chat"], "local");
+    assert_eq!(merged.llm.use_cases["completion"], "fast");
+
+    std::fs::write(path.clone(), "[context.completion]\ndetailed_commands = \"many\"\n").unwrap();
+    assert!(load_project_config(&path).is_none());
+}
@@ -0,0 +1,52 @@
+//! Kept in its own test binary: these tests change process-wide env vars
+//! that `omnish_dir()` reads.
+
+use omnish_common::config::{omnish_dir, split_omnish_dir_flag};
+use std::path::PathBuf;
+
+fn args(list: &[&str]) -> Vec<String> {
+    list.iter().map(|s| s.to_string()).collect()
+}
+
+#[test]
+fn test_omnish_dir_env_priority() {
+    std::env::remove_var("OMNISH_HOME");
+    std::env::set_var("OMNISH_DIR", "/tmp/test_omnish");
+    assert_eq!(omnish_dir(), PathBuf::from("/tmp/test_omnish"));
+
+    // OMNISH_DIR wins over the older OMNISH_HOME
+    std::env::set_var("OMNISH_HOME", "/tmp/test_omnish_home");
+    assert_eq!(omnish_dir(), PathBuf::from("/tmp/test_omnish"));
+
+    std::env::remove_var("OMNISH_DIR");
+    assert_eq!(omnish_dir(), PathBuf::from("/tmp/test_omnish_home"));
+
+    // XDG_DATA_HOME is used only when there is no ~/.omnish yet
+    std::env::remove_var("OMNISH_HOME");
+    let home = tempfile::tempdir().unwrap();
+    std::env::set_var("HOME", home.path());
+    std::env::set_var("XDG_DATA_HOME", "/tmp/test_xdg");
+    assert_eq!(omnish_dir(), PathBuf::from("/tmp/test_xdg/omnish"));
+
+    std::fs::create_dir(home.path().join(".omnish")).unwrap();
+    assert_eq!(omnish_dir(), home.path().join(".omnish"));
+
+    std::env::remove_var("XDG_DATA_HOME");
+    std::fs::remove_dir(home.path().join(".omnish")).unwrap();
+    assert_eq!(omnish_dir(), home.path().join(".omnish"));
+}
+
+#[test]
+fn test_split_omnish_dir_flag() {
+    let (dir, rest) = split_omnish_dir_flag(args(&["--omnish-dir", "/a", "-c", "ls"]));
+    assert_eq!(dir.as_deref(), Some("/a"));
+    assert_eq!(rest, args(&["-c", "ls"]));
+
+    let (dir, rest) = split_omnish_dir_flag(args(&["--init", "--omnish-dir=/b"]));
+    assert_eq!(dir.as_deref(), Some("/b"));
+    assert_eq!(rest, args(&["--init"]));
+
+    let (dir, rest) = split_omnish_dir_flag(args(&["--omnish-directory", "--omnish-dir"]));
+    assert_eq!(dir, None);
+    assert_eq!(rest, args(&["--omnish-directory"]));
+}
@@ -0,0 +1,99 @@
+use std::collections::HashMap;
+use std::path::PathBuf;
+
+use criterion::{criterion_group, criterion_main, Criterion};
+use omnish_context::recent::RecentCommands;
+use omnish_context::StreamReader;
+use omnish_store::command::CommandRecord;
+use omnish_store::stream::{read_range, StreamEntry, StreamWriter};
+
+const SESSIONS: usize = 10;
+const COMMANDS_PER_SESSION: usize = 5;
+/// 5 commands x 16 x 64 KiB = 5 MiB of output per session.
+const ENTRIES_PER_COMMAND: usize = 16;
+const ENTRY_BYTES: usize = 64 * 1024;
+
+/// Resolves a command's range to its session's stream file, like the
+/// daemon's multi-session reader.
+struct FileReader {
+    paths: HashMap<(u64, u64), PathBuf>,
+}
+
+impl StreamReader for FileReader {
+    fn read_command_output(&self, offset: u64, length: u64) -> anyhow::Result<Vec<StreamEntry>> {
+        read_range(&self.paths[&(offset, length)], offset, length, None)
+    }
+}
+
+/// Write one stream.bin per session. A leading pad entry of a different
+/// size per session keeps `(offset, length)` keys unique across files.
+fn make_sessions(dir: &std::path::Path) -> (Vec<CommandRecord>, FileReader) {
+    let mut commands = Vec::new();
+    let mut paths = HashMap::new();
+    let line = vec![b'x'; ENTRY_BYTES - 1].into_iter().chain([b'\n']).collect::<Vec<u8>>();
+    for s in 0..SESSIONS {
+        let path = dir.join(format!("s{s}.bin"));
+        let mut writer = StreamWriter::create(&path).unwrap();
+        writer.write_entry(0, 1, &vec![b' '; s + 1]).unwrap();
+        for c in 0..COMMANDS_PER_SESSION {
+            let start = writer.position();
+            let started_at = (c * SESSIONS + s) as u64 * 1000;
+            for _ in 0..ENTRIES_PER_COMMAND {
+                writer.write_entry(started_at, 1, &line).unwrap();
+            }
+            let length = writer.position() - start;
+            paths.insert((start, length), path.clone());
+            commands.push(CommandRecord {
+                command_id: format!("s{s}-c{c}"),
+                session_id: format!("s{s}"),
+                command_line: Some(format!("cat big{c}.log")),
+                cwd: None,
+                started_at,
+                ended_at: Some(started_at + 10),
+                output_summary: String::new(),
+                stream_offset: start,
+                stream_length: length,
+                exit_code: Some(0),
+                exit_signal: None,
+                checksum: None,
+                tags: Vec::new(),
+            });
+        }
+    }
+    commands.sort_by_key(|c| c.started_at);
+    (commands, FileReader { paths })
+}
+
+fn bench_parallel_read(c: &mut Criterion) {
+    let rt = tokio::runtime::Builder::new_current_thread().build().unwrap();
+    let dir = tempfile::tempdir().unwrap();
+    let (commands, reader) = make_sessions(dir.path());
+    // Same reads, but attributed to one session, so they run sequentially.
+    let single: Vec<CommandRecord> = commands
+        .iter()
+        .cloned()
+        .map(|mut c| {
+            c.session_id = "s0".into();
+            c
+        })
+        .collect();
+    let total = commands.len();
+    let strategy = RecentCommands::new(total);
+    let hostnames = HashMap::new();
+    let build = |cmds: &[CommandRecord]| {
+        rt.block_on(omnish_context::build_command_contexts_with_session(
+            &strategy, cmds, &reader, &hostnames, total, 512, None, 0,
+        ))
+        .unwrap()
+    };
+    assert_eq!(build(&commands).1.len(), total);
+
+    let mut group = c.benchmark_group("detailed read 10 sessions x 5 MiB");
+    group.sample_size(10);
+    group.bench_function("sequential", |b| b.iter(|| build(&single)));
+    group.bench_function("parallel", |b| b.iter(|| build(&commands)));
+    group.finish();
+}
+
+criterion_group!(benches, bench_parallel_read);
+criterion_main!(benches);
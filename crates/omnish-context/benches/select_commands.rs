@@ -0,0 +1,51 @@
+use criterion::{black_box, criterion_group, criterion_main, Criterion};
+use omnish_context::recent::RecentCommands;
+use omnish_context::ContextStrategy;
+use omnish_store::command::CommandRecord;
+
+const SESSIONS: usize = 50;
+const COMMANDS: usize = 10_000;
+const MAX: usize = 500;
+const MIN_CURRENT: usize = 200;
+const CURRENT_SESSION: &str = "sess-0";
+
+/// 10k commands round-robin across 50 sessions, so the current session's
+/// commands are spread thinly and the minimum forces the dedup path.
+fn make_commands() -> Vec<CommandRecord> {
+    (0..COMMANDS)
+        .map(|i| CommandRecord {
+            command_id: format!("cmd-{i}"),
+            session_id: format!("sess-{}", i % SESSIONS),
+            command_line: Some(format!("echo {i}")),
+            cwd: Some("/tmp".into()),
+            started_at: i as u64 * 1000,
+            ended_at: Some(i as u64 * 1000 + 10),
+            output_summary: String::new(),
+            stream_offset: 0,
+            stream_length: 0,
+            exit_code: Some(0),
+            exit_signal: None,
+            checksum: None,
+            tags: Vec::new(),
+        })
+        .collect()
+}
+
+fn bench_select_commands(c: &mut Criterion) {
+    let rt = tokio::runtime::Builder::new_current_thread().build().unwrap();
+    let commands = make_commands();
+    let strategy = RecentCommands::new(MAX).with_current_session(CURRENT_SESSION, MIN_CURRENT);
+
+    let selected = rt.block_on(strategy.select_commands(&commands));
+    assert_eq!(selected.len(), MAX);
+    assert!(selected.windows(2).all(|w| w[0].started_at <= w[1].started_at));
+    let current = selected.iter().filter(|c| c.session_id == CURRENT_SESSION).count();
+    assert!(current >= MIN_CURRENT, "only {current} current-session commands");
+
+    c.bench_function("select_commands 10k/50 sessions", |b| {
+        b.iter(|| rt.block_on(strategy.select_commands(black_box(&commands))))
+    });
+}
+
+criterion_group!(benches, bench_select_commands);
+criterion_main!(benches);
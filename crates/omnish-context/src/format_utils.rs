@@ -7,27 +7,91 @@ pub fn format_relative_time(timestamp_ms: u64, now_ms: u64) -> String {
     if now_ms <= timestamp_ms {
         return "just now".to_string();
     }
-    let diff_ms = now_ms - timestamp_ms;
-    let seconds = diff_ms / 1000;
+    format!("{} ago", relative_time(timestamp_ms, now_ms))
+}
+
+/// Compact duration: "850ms", "45s", "2m3s" or "1h5m" (zero trailing
+/// units are dropped, e.g. "2m").
+pub fn format_duration_short(ms: u64) -> String {
+    if ms < 1000 {
+        return format!("{}ms", ms);
+    }
+    let seconds = ms / 1000;
+    let (hours, minutes, seconds) = (seconds / 3600, seconds / 60 % 60, seconds % 60);
+    match (hours, minutes, seconds) {
+        (0, 0, s) => format!("{}s", s),
+        (0, m, 0) => format!("{}m", m),
+        (0, m, s) => format!("{}m{}s", m, s),
+        (h, 0, _) => format!("{}h", h),
+        (h, m, _) => format!("{}h{}m", h, m),
+    }
+}
+
+/// Elapsed time from `started_at_ms` to `now_ms` in its largest whole unit:
+/// "3s", "2m", "1h" or "4d". Future timestamps count as "0s".
+pub fn relative_time(started_at_ms: u64, now_ms: u64) -> String {
+    let seconds = now_ms.saturating_sub(started_at_ms) / 1000;
     let minutes = seconds / 60;
     let hours = minutes / 60;
     let days = hours / 24;
 
     if days >= 1 {
-        format!("{}d ago", days)
+        format!("{}d", days)
     } else if hours >= 1 {
-        format!("{}h ago", hours)
+        format!("{}h", hours)
     } else if minutes >= 1 {
-        format!("{}m ago", minutes)
+        format!("{}m", minutes)
     } else {
-        format!("{}s ago", seconds)
+        format!("{}s", seconds)
     }
 }
 
+/// Delimiters of a `deferred_relative_time` placeholder: control
+/// characters that don't turn up in ordinary command output.
+const DEFERRED_TIME_START: char = '\u{1}';
+const DEFERRED_TIME_END: char = '\u{2}';
+
+/// Placeholder for `relative_time(started_at_ms, now)`, filled in later by
+/// `render_relative_times`, so text holding it can be cached.
+pub fn deferred_relative_time(started_at_ms: u64) -> String {
+    format!("{}{}{}", DEFERRED_TIME_START, started_at_ms, DEFERRED_TIME_END)
+}
+
+/// Replace the `deferred_relative_time` placeholders in `text` with the
+/// time elapsed until `now_ms`.
+pub fn render_relative_times(text: &str, now_ms: u64) -> String {
+    let mut out = String::with_capacity(text.len());
+    let mut rest = text;
+    while let Some(start) = rest.find(DEFERRED_TIME_START) {
+        out.push_str(&rest[..start]);
+        let after = &rest[start + DEFERRED_TIME_START.len_utf8()..];
+        let parsed = after
+            .find(DEFERRED_TIME_END)
+            .and_then(|end| Some((after[..end].parse::<u64>().ok()?, end)));
+        match parsed {
+            Some((started_at, end)) => {
+                out.push_str(&relative_time(started_at, now_ms));
+                rest = &after[end + DEFERRED_TIME_END.len_utf8()..];
+            }
+            None => {
+                out.push(DEFERRED_TIME_START);
+                rest = after;
+            }
+        }
+    }
+    out.push_str(rest);
+    out
+}
+
 /// Generate a term name for index 0..N.
 /// 0 → "term A", 25 → "term Z", 26 → "term AA", 27 → "term AB", ...,
 /// 701 → "term ZZ", 702 → "term AAA", etc.  Works for any index.
 fn term_name(index: usize) -> String {
+    format!("term {}", term_letters(index))
+}
+
+/// Letters of `term_name`: 0 → "A", 26 → "AA".
+pub(crate) fn term_letters(index: usize) -> String {
     // Convert index to bijective base-26: A=0..Z=25, AA=26..AZ=51, BA=52..
     let mut n = index;
     let mut letters = Vec::new();
@@ -39,7 +103,7 @@ fn term_name(index: usize) -> String {
         n = n / 26 - 1;
     }
     letters.reverse();
-    format!("term {}", letters.into_iter().collect::<String>())
+    letters.into_iter().collect()
 }
 
 /// Build the final label map from term_letters + hostname lookup.
@@ -74,18 +138,25 @@ pub fn assign_term_labels(
     commands: &[super::CommandContext],
     current_session_id: &str,
 ) -> HashMap<String, String> {
-    let mut term_letters: HashMap<String, String> = HashMap::new();
-    term_letters.insert(current_session_id.to_string(), term_name(0));
+    let term_letters: HashMap<String, String> = assign_term_indices(commands, current_session_id)
+        .into_iter()
+        .map(|(session_id, index)| (session_id, term_name(index)))
+        .collect();
+    build_labels(&term_letters, commands)
+}
 
-    let mut index = 1usize;
+/// Position of each session in `assign_term_labels` order: the current
+/// session is 0, the others follow in order of first appearance.
+pub fn assign_term_indices(
+    commands: &[super::CommandContext],
+    current_session_id: &str,
+) -> HashMap<String, usize> {
+    let mut indices = HashMap::from([(current_session_id.to_string(), 0)]);
     for cmd in commands {
-        if !term_letters.contains_key(&cmd.session_id) {
-            term_letters.insert(cmd.session_id.clone(), term_name(index));
-            index += 1;
-        }
+        let next = indices.len();
+        indices.entry(cmd.session_id.clone()).or_insert(next);
     }
-
-    build_labels(&term_letters, commands)
+    indices
 }
 
 /// Assign session labels by chronological order of first appearance.
@@ -108,9 +179,29 @@ pub fn assign_stable_term_labels(
     build_labels(&term_letters, commands)
 }
 
-/// Truncate each line to at most `max_width` characters.
-/// Lines that exceed the limit are cut and appended with "..." (the total may be max_width + 3).
-pub fn truncate_line_width(text: &str, max_width: usize) -> String {
+/// Terminal tab stop used when measuring line width.
+pub const DEFAULT_TAB_STOP: usize = 8;
+
+/// Column reached after `c` when it starts at column `col`.
+fn advance_column(col: usize, c: char, tab_stop: usize) -> usize {
+    if c == '\t' {
+        let tab_stop = tab_stop.max(1);
+        (col / tab_stop + 1) * tab_stop
+    } else {
+        col + 1
+    }
+}
+
+/// Visual width of a single line, expanding tabs to the next multiple of `tab_stop`.
+pub fn line_width(line: &str, tab_stop: usize) -> usize {
+    line.chars().fold(0, |col, c| advance_column(col, c, tab_stop))
+}
+
+/// Truncate each line to at most `max_width` visual columns, with tabs
+/// expanding to the next multiple of `tab_stop`.
+/// Lines that exceed the limit are cut and appended with "...". Characters
+/// starting before `max_width` are kept, so a trailing tab may overshoot it.
+pub fn truncate_line_width(text: &str, max_width: usize, tab_stop: usize) -> String {
     if max_width == 0 {
         return text.to_string();
     }
@@ -119,8 +210,15 @@ pub fn truncate_line_width(text: &str, max_width: usize) -> String {
         if i > 0 {
             result.push('\n');
         }
-        if line.chars().count() > max_width {
-            result.extend(line.chars().take(max_width));
+        if line_width(line, tab_stop) > max_width {
+            let mut col = 0;
+            for c in line.chars() {
+                if col >= max_width {
+                    break;
+                }
+                result.push(c);
+                col = advance_column(col, c, tab_stop);
+            }
             result.push_str("...");
         } else {
             result.push_str(line);
@@ -129,9 +227,46 @@ pub fn truncate_line_width(text: &str, max_width: usize) -> String {
     result
 }
 
+/// Marker placed around the omitted-line count by `truncate_lines`.
+pub const DEFAULT_TRUNCATION_MARKER: &str = "...";
+
+/// Options for `truncate_lines_with`.
+#[derive(Debug, Clone, Copy)]
+pub struct TruncationConfig<'a> {
+    pub max_lines: usize,
+    pub head: usize,
+    pub tail: usize,
+    pub max_chars: Option<usize>,
+    /// Surrounds the count of omitted lines: `<marker> (N lines omitted) <marker>`.
+    pub marker: &'a str,
+}
+
+impl TruncationConfig<'static> {
+    pub fn new(max_lines: usize, head: usize, tail: usize) -> Self {
+        Self { max_lines, head, tail, max_chars: None, marker: DEFAULT_TRUNCATION_MARKER }
+    }
+}
+
+impl<'a> TruncationConfig<'a> {
+    pub fn with_max_chars(mut self, max_chars: Option<usize>) -> Self {
+        self.max_chars = max_chars;
+        self
+    }
+
+    pub fn with_marker(self, marker: &str) -> TruncationConfig<'_> {
+        TruncationConfig { marker, ..self }
+    }
+}
+
 /// Truncate output lines. If over max_lines, keep head + "..." + tail.
 /// Also limits total characters to max_chars if provided.
 pub fn truncate_lines(text: &str, max_lines: usize, head: usize, tail: usize, max_chars: Option<usize>) -> String {
+    truncate_lines_with(text, &TruncationConfig::new(max_lines, head, tail).with_max_chars(max_chars))
+}
+
+/// `truncate_lines` with a custom omission marker.
+pub fn truncate_lines_with(text: &str, config: &TruncationConfig) -> String {
+    let TruncationConfig { max_lines, head, tail, max_chars, marker } = *config;
     let lines: Vec<&str> = text
         .lines()
         .map(|l| l.trim_end_matches('\r'))
@@ -153,7 +288,7 @@ pub fn truncate_lines(text: &str, max_lines: usize, head: usize, tail: usize, ma
         let tail_part = &lines[total - tail..];
         let omitted = total - head - tail;
         format!(
-            "{}\n... ({} lines omitted) ...\n{}",
+            "{}\n{marker} ({} lines omitted) {marker}\n{}",
             head_part.join("\n"),
             omitted,
             tail_part.join("\n")
@@ -161,6 +296,20 @@ pub fn truncate_lines(text: &str, max_lines: usize, head: usize, tail: usize, ma
     }
 }
 
+/// Cut `text` to at most `max_bytes` bytes (on a char boundary) and append
+/// `\n[...truncated N bytes]`, N being the bytes dropped. Text within the
+/// limit is returned unchanged.
+pub fn truncate_bytes(text: &str, max_bytes: usize) -> String {
+    if text.len() <= max_bytes {
+        return text.to_string();
+    }
+    let mut cut = max_bytes;
+    while !text.is_char_boundary(cut) {
+        cut -= 1;
+    }
+    format!("{}\n[...truncated {} bytes]", &text[..cut], text.len() - cut)
+}
+
 /// Truncate text to at most max_chars characters.
 /// Keeps head + "..." + tail where head and tail are roughly equal.
 fn truncate_by_chars(text: &str, max_chars: usize) -> String {
@@ -191,6 +340,34 @@ mod tests {
         assert_eq!(format_relative_time(10000, 10000), "just now");
     }
 
+    #[test]
+    fn test_render_relative_times() {
+        let text = format!("[{} ago] ls\n[{} ago] pwd", deferred_relative_time(1_000), deferred_relative_time(58_000));
+        assert_eq!(render_relative_times(&text, 61_000), "[1m ago] ls\n[3s ago] pwd");
+        assert_eq!(render_relative_times(&text, 121_000), "[2m ago] ls\n[1m ago] pwd");
+        // Stray delimiters are kept as they are
+        assert_eq!(render_relative_times("a\u{1}b\u{2}", 0), "a\u{1}b\u{2}");
+    }
+
+    #[test]
+    fn test_relative_time_ranges() {
+        let min = 60_000;
+        let hour = 60 * min;
+        // < 1 minute
+        assert_eq!(relative_time(0, 0), "0s");
+        assert_eq!(relative_time(0, 59_999), "59s");
+        assert_eq!(relative_time(5_000, 1_000), "0s");
+        // 1-59 minutes
+        assert_eq!(relative_time(0, min), "1m");
+        assert_eq!(relative_time(0, 59 * min + 59_000), "59m");
+        // 1-23 hours
+        assert_eq!(relative_time(0, hour), "1h");
+        assert_eq!(relative_time(0, 23 * hour + 59 * min), "23h");
+        // >= 24 hours
+        assert_eq!(relative_time(0, 24 * hour), "1d");
+        assert_eq!(relative_time(0, 100 * 24 * hour), "100d");
+    }
+
     #[test]
     fn test_relative_time_minutes() {
         assert_eq!(format_relative_time(0, 120000), "2m ago");
@@ -225,6 +402,8 @@ mod tests {
                 ended_at: Some(1050),
                 output: String::new(),
                 exit_code: None,
+                exit_signal: None,
+                tags: Vec::new(),
             },
             CommandContext {
                 session_id: "other-sess".into(),
@@ -235,6 +414,8 @@ mod tests {
                 ended_at: Some(2050),
                 output: String::new(),
                 exit_code: None,
+                exit_signal: None,
+                tags: Vec::new(),
             },
         ];
         let labels = assign_term_labels(&commands, "my-sess");
@@ -253,6 +434,8 @@ mod tests {
             ended_at: Some(1050),
             output: String::new(),
             exit_code: None,
+            exit_signal: None,
+            tags: Vec::new(),
         }];
         let labels = assign_term_labels(&commands, "only");
         assert_eq!(labels.len(), 1);
@@ -270,11 +453,66 @@ mod tests {
             ended_at: Some(1050),
             output: String::new(),
             exit_code: None,
+            exit_signal: None,
+            tags: Vec::new(),
         }];
         let labels = assign_term_labels(&commands, "only");
         assert_eq!(labels.get("only").unwrap(), "term A");
     }
 
+    /// One command per session, `s00`, `s01`, ... in that order.
+    fn commands_for_sessions(count: usize) -> Vec<CommandContext> {
+        (0..count)
+            .map(|i| CommandContext {
+                session_id: format!("s{:02}", i),
+                hostname: None,
+                command_line: Some("ls".into()),
+                cwd: None,
+                started_at: 1000 + i as u64,
+                ended_at: Some(1050 + i as u64),
+                output: String::new(),
+                exit_code: None,
+                exit_signal: None,
+                tags: Vec::new(),
+            })
+            .collect()
+    }
+
+    #[test]
+    fn test_assign_labels_exactly_26_sessions() {
+        let labels = assign_term_labels(&commands_for_sessions(26), "s00");
+        assert_eq!(labels.len(), 26);
+        assert_eq!(labels["s00"], "term A");
+        assert_eq!(labels["s25"], "term Z");
+        assert!(labels.values().all(|l| l.len() == "term A".len()), "{:?}", labels);
+    }
+
+    #[test]
+    fn test_assign_labels_past_26_sessions_use_two_letters() {
+        let labels = assign_term_labels(&commands_for_sessions(53), "s00");
+        assert_eq!(labels["s00"], "term A");
+        assert_eq!(labels["s25"], "term Z");
+        assert_eq!(labels["s26"], "term AA");
+        assert_eq!(labels["s51"], "term AZ");
+        assert_eq!(labels["s52"], "term BA");
+
+        // The current session keeps "A" and pushes the rest along.
+        let labels = assign_term_labels(&commands_for_sessions(27), "s26");
+        assert_eq!(labels["s26"], "term A");
+        assert_eq!(labels["s00"], "term B");
+        assert_eq!(labels["s25"], "term AA");
+    }
+
+    #[test]
+    fn test_format_duration_short() {
+        assert_eq!(format_duration_short(850), "850ms");
+        assert_eq!(format_duration_short(45_000), "45s");
+        assert_eq!(format_duration_short(123_000), "2m3s");
+        assert_eq!(format_duration_short(120_500), "2m");
+        assert_eq!(format_duration_short(3_900_000), "1h5m");
+        assert_eq!(format_duration_short(7_200_000), "2h");
+    }
+
     #[test]
     fn test_truncate_lines_short() {
         let text = "line 1\nline 2\nline 3\n";
@@ -282,6 +520,17 @@ mod tests {
         assert_eq!(result, "line 1\nline 2\nline 3");
     }
 
+    #[test]
+    fn test_truncate_bytes() {
+        assert_eq!(truncate_bytes("short", 10), "short");
+        let big = "x".repeat(100 * 1024);
+        let out = truncate_bytes(&big, 4096);
+        assert!(out.starts_with(&"x".repeat(4096)));
+        assert!(out.ends_with(&format!("\n[...truncated {} bytes]", 100 * 1024 - 4096)));
+        // Never splits a multi-byte char.
+        assert_eq!(truncate_bytes("a\u{e9}b", 2), "a\n[...truncated 3 bytes]");
+    }
+
     #[test]
     fn test_truncate_lines_long() {
         let mut text = String::new();
@@ -297,6 +546,29 @@ mod tests {
         assert!(!result.contains("\nline 10\n"));
     }
 
+    #[test]
+    fn test_truncate_lines_with_marker() {
+        let text: String = (0..50).map(|i| format!("line {}\n", i)).collect();
+
+        // Fits: no marker at all
+        let config = TruncationConfig::new(50, 5, 5).with_marker("~~");
+        assert!(!truncate_lines_with(&text, &config).contains("~~"));
+
+        let config = TruncationConfig::new(20, 5, 3).with_marker("[snip]");
+        let result = truncate_lines_with(&text, &config);
+        let lines: Vec<&str> = result.lines().collect();
+        assert_eq!(lines.len(), 5 + 1 + 3);
+        assert_eq!(lines[4], "line 4");
+        assert_eq!(lines[5], "[snip] (42 lines omitted) [snip]");
+        assert_eq!(lines[6], "line 47");
+
+        // Default marker matches truncate_lines
+        assert_eq!(
+            truncate_lines_with(&text, &TruncationConfig::new(20, 5, 3)),
+            truncate_lines(&text, 20, 5, 3, None)
+        );
+    }
+
     #[test]
     fn test_truncate_lines_by_chars() {
         // Test character limit - text with 600 chars should be truncated to 500
@@ -318,14 +590,14 @@ mod tests {
     #[test]
     fn test_truncate_line_width_short_lines_unchanged() {
         let text = "short\nlines\nhere";
-        assert_eq!(truncate_line_width(text, 512), text);
+        assert_eq!(truncate_line_width(text, 512, DEFAULT_TAB_STOP), text);
     }
 
     #[test]
     fn test_truncate_line_width_long_line_truncated() {
         let long = "x".repeat(600);
         let text = format!("ok\n{}\nend", long);
-        let result = truncate_line_width(&text, 512);
+        let result = truncate_line_width(&text, 512, DEFAULT_TAB_STOP);
         let lines: Vec<&str> = result.lines().collect();
         assert_eq!(lines[0], "ok");
         assert_eq!(lines[1].len(), 515); // 512 chars + "..."
@@ -336,7 +608,24 @@ mod tests {
     #[test]
     fn test_truncate_line_width_zero_is_noop() {
         let text = "x".repeat(1000);
-        assert_eq!(truncate_line_width(&text, 0), text);
+        assert_eq!(truncate_line_width(&text, 0, DEFAULT_TAB_STOP), text);
+    }
+
+    #[test]
+    fn test_line_width_expands_tabs() {
+        assert_eq!(line_width("a\tb", 8), 9);
+        assert_eq!(line_width("\tb", 8), 9);
+        assert_eq!(line_width("abcdefgh\tb", 8), 17);
+        assert_eq!(line_width("a\tb", 4), 5);
+    }
+
+    #[test]
+    fn test_truncate_line_width_snaps_to_tab_stop() {
+        assert_eq!(truncate_line_width("a\tb", 5, 8), "a\t...");
+        // Fits by visual width: left alone
+        assert_eq!(truncate_line_width("a\tb", 9, 8), "a\tb");
+        // The first tab already reaches column 8
+        assert_eq!(truncate_line_width("\t\tx", 4, 8), "\t...");
     }
 
     #[test]
@@ -0,0 +1,211 @@
+use crate::format_utils::{term_letters, truncate_bytes, truncate_lines};
+use crate::{CommandContext, ContextFormatter};
+
+/// Formats commands as XML for tools that parse the context instead of
+/// reading it (IDE plugins, CI integrations):
+///
+/// ```text
+/// <context>
+/// <history>
+/// <command line="make" cwd="~/src"/>
+/// </history>
+/// <session id="A" current="true">
+/// <command line="ls" cwd="/tmp" exit_code="0"><output>...</output></command>
+/// </session>
+/// </context>
+/// ```
+///
+/// Sessions are grouped like `GroupedFormatter`, with the current session
+/// (always `A`) last.
+pub struct XmlFormatter {
+    current_session_id: String,
+    head_lines: usize,
+    tail_lines: usize,
+    max_output_bytes: Option<usize>,
+}
+
+impl XmlFormatter {
+    pub fn new(current_session_id: &str, head_lines: usize, tail_lines: usize) -> Self {
+        Self {
+            current_session_id: current_session_id.to_string(),
+            head_lines,
+            tail_lines,
+            max_output_bytes: None,
+        }
+    }
+
+    /// See `GroupedFormatter::with_max_output_bytes`.
+    pub fn with_max_output_bytes(mut self, max: usize) -> Self {
+        self.max_output_bytes = Some(max);
+        self
+    }
+
+    fn command_attrs(cmd: &CommandContext) -> String {
+        let mut attrs = format!(" line=\"{}\"", escape(cmd.command_line.as_deref().unwrap_or("")));
+        if let Some(ref cwd) = cmd.cwd {
+            attrs.push_str(&format!(" cwd=\"{}\"", escape(cwd)));
+        }
+        if let Some(ref host) = cmd.hostname {
+            attrs.push_str(&format!(" host=\"{}\"", escape(host)));
+        }
+        attrs.push_str(&format!(" started_at=\"{}\"", cmd.started_at));
+        if let Some(code) = cmd.exit_code {
+            attrs.push_str(&format!(" exit_code=\"{}\"", code));
+        }
+        if let Some(sig) = cmd.exit_signal {
+            attrs.push_str(&format!(" signal=\"{}\"", sig));
+        }
+        if !cmd.tags.is_empty() {
+            attrs.push_str(&format!(" tags=\"{}\"", escape(&cmd.tags.join(","))));
+        }
+        attrs
+    }
+}
+
+impl ContextFormatter for XmlFormatter {
+    fn format(&self, history: &[CommandContext], detailed: &[CommandContext]) -> String {
+        if history.is_empty() && detailed.is_empty() {
+            return String::new();
+        }
+
+        let mut lines = vec!["<context>".to_string()];
+
+        if !history.is_empty() {
+            lines.push("<history>".to_string());
+            for cmd in history {
+                lines.push(format!("<command{}/>", Self::command_attrs(cmd)));
+            }
+            lines.push("</history>".to_string());
+        }
+
+        // Current session first for lettering, last in the output
+        let mut session_order = vec![self.current_session_id.clone()];
+        for cmd in detailed {
+            if !session_order.contains(&cmd.session_id) {
+                session_order.push(cmd.session_id.clone());
+            }
+        }
+        let mut sessions: Vec<(usize, &String)> = session_order.iter().enumerate().skip(1).collect();
+        sessions.push((0, &session_order[0]));
+
+        for (index, session_id) in sessions {
+            let commands: Vec<&CommandContext> = detailed.iter().filter(|c| &c.session_id == session_id).collect();
+            if commands.is_empty() {
+                continue;
+            }
+            lines.push(format!(
+                "<session id=\"{}\" current=\"{}\">",
+                term_letters(index),
+                index == 0
+            ));
+            for cmd in commands {
+                let max_lines = self.head_lines + self.tail_lines;
+                let output = truncate_lines(&cmd.output, max_lines, self.head_lines, self.tail_lines, None);
+                let output = match self.max_output_bytes {
+                    Some(max) => truncate_bytes(&output, max),
+                    None => output,
+                };
+                if output.is_empty() {
+                    lines.push(format!("<command{}/>", Self::command_attrs(cmd)));
+                } else {
+                    lines.push(format!(
+                        "<command{}><output>{}</output></command>",
+                        Self::command_attrs(cmd),
+                        escape(&output)
+                    ));
+                }
+            }
+            lines.push("</session>".to_string());
+        }
+
+        lines.push("</context>".to_string());
+        lines.join("\n")
+    }
+}
+
+/// Escape `text` for XML content and attribute values. Control characters
+/// that XML 1.0 does not allow (anything below 0x20 except tab, newline and
+/// carriage return) are dropped.
+fn escape(text: &str) -> String {
+    let mut out = String::with_capacity(text.len());
+    for c in text.chars() {
+        match c {
+            '&' => out.push_str("&amp;"),
+            '<' => out.push_str("&lt;"),
+            '>' => out.push_str("&gt;"),
+            '"' => out.push_str("&quot;"),
+            '\'' => out.push_str("&apos;"),
+            '\t' | '\n' | '\r' => out.push(c),
+            c if (c as u32) < 0x20 => {}
+            c => out.push(c),
+        }
+    }
+    out
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn ctx(session_id: &str, line: &str, output: &str, exit_code: Option<i32>) -> CommandContext {
+        CommandContext {
+            session_id: session_id.into(),
+            hostname: None,
+            command_line: Some(line.into()),
+            cwd: Some("/tmp".into()),
+            started_at: 1000,
+            ended_at: Some(1100),
+            output: output.into(),
+            exit_code,
+            exit_signal: None,
+            tags: Vec::new(),
+        }
+    }
+
+    #[test]
+    fn test_xml_is_well_formed_with_all_fields() {
+        let history = vec![ctx("s1", "make", "", Some(0))];
+        let mut other = ctx("s2", "tail -f log", "line 1\nline 2", None);
+        other.hostname = Some("build-box".into());
+        let detailed = vec![
+            other,
+            ctx("s1", "grep \"a<b\" && echo 'ok'", "x < y & z\x1b\x07 > w", Some(1)),
+        ];
+        let xml = XmlFormatter::new("s1", 5, 5).format(&history, &detailed);
+
+        let doc = roxmltree::Document::parse(&xml).unwrap_or_else(|e| panic!("{e}\n{xml}"));
+        let root = doc.root_element();
+        assert_eq!(root.tag_name().name(), "context");
+        let children: Vec<_> = root.children().filter(|n| n.is_element()).collect();
+        assert_eq!(children.len(), 3);
+
+        let hist_cmd = children[0].first_element_child().unwrap();
+        assert_eq!(children[0].tag_name().name(), "history");
+        assert_eq!(hist_cmd.attribute("line"), Some("make"));
+
+        let other = children[1];
+        assert_eq!(other.attribute("id"), Some("B"));
+        assert_eq!(other.attribute("current"), Some("false"));
+        let tail = other.first_element_child().unwrap();
+        assert_eq!(tail.attribute("host"), Some("build-box"));
+        assert_eq!(tail.attribute("exit_code"), None);
+        assert_eq!(tail.first_element_child().unwrap().text(), Some("line 1\nline 2"));
+
+        let current = children[2];
+        assert_eq!(current.attribute("id"), Some("A"));
+        assert_eq!(current.attribute("current"), Some("true"));
+        let grep = current.first_element_child().unwrap();
+        assert_eq!(grep.attribute("line"), Some("grep \"a<b\" && echo 'ok'"));
+        assert_eq!(grep.attribute("cwd"), Some("/tmp"));
+        assert_eq!(grep.attribute("exit_code"), Some("1"));
+        assert_eq!(grep.attribute("started_at"), Some("1000"));
+        let output = grep.first_element_child().unwrap();
+        assert_eq!(output.tag_name().name(), "output");
+        assert_eq!(output.text(), Some("x < y & z > w"));
+    }
+
+    #[test]
+    fn test_xml_empty_input() {
+        assert_eq!(XmlFormatter::new("s1", 5, 5).format(&[], &[]), "");
+    }
+}
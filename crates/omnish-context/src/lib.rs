@@ -1,12 +1,15 @@
 pub mod format_utils;
+pub mod formatters;
 pub mod recent;
 
 use std::collections::HashMap;
+use std::sync::Arc;
 
-use anyhow::Result;
+use anyhow::{anyhow, Result};
 use async_trait::async_trait;
 use omnish_store::command::CommandRecord;
 use omnish_store::stream::StreamEntry;
+use tokio_util::sync::CancellationToken;
 
 /// Replace the user's home directory prefix with `~` to reduce context size.
 pub fn shorten_home(path: &str) -> String {
@@ -35,6 +38,8 @@ pub struct CommandContext {
     pub ended_at: Option<u64>,
     pub output: String,
     pub exit_code: Option<i32>,
+    pub exit_signal: Option<i32>,
+    pub tags: Vec<String>,
 }
 
 /// Reads stream entries for a given command's byte range.
@@ -45,7 +50,14 @@ pub trait StreamReader: Send + Sync {
 /// Selects which commands to include in context.
 #[async_trait]
 pub trait ContextStrategy: Send + Sync {
+    /// The commands to include, in `started_at` order.
     async fn select_commands<'a>(&self, commands: &'a [CommandRecord]) -> Vec<&'a CommandRecord>;
+
+    /// Commands among `selected` that should get full output even when they
+    /// are not among the most recent `detailed_count`.
+    fn preferred_detailed<'a>(&self, _selected: &[&'a CommandRecord]) -> Vec<&'a CommandRecord> {
+        Vec::new()
+    }
 }
 
 /// Formats selected commands into the final context string.
@@ -96,11 +108,23 @@ pub async fn select_and_split<'a>(
     current_session_id: Option<&str>,
     min_current_session_detailed: usize,
 ) -> (Vec<&'a CommandRecord>, Vec<&'a CommandRecord>) {
-    let selected = strategy.select_commands(commands).await;
+    let mut selected = strategy.select_commands(commands).await;
+    let preferred: std::collections::HashSet<*const CommandRecord> = strategy
+        .preferred_detailed(&selected)
+        .into_iter()
+        .map(|cmd| cmd as *const CommandRecord)
+        .collect();
+    // Preferred commands go last so the split counts them as detailed
+    selected.sort_by_key(|cmd| preferred.contains(&(*cmd as *const CommandRecord)));
     let initial_split = selected.len().saturating_sub(detailed_count);
-    split_with_current_session_minimum(
+    let (mut history, mut detailed) = split_with_current_session_minimum(
         &selected, initial_split, current_session_id, min_current_session_detailed,
-    )
+    );
+    if !preferred.is_empty() {
+        history.sort_by_key(|cmd| cmd.started_at);
+        detailed.sort_by_key(|cmd| cmd.started_at);
+    }
+    (history, detailed)
 }
 
 /// Like `build_context` but ensures at least `min_current_session_detailed` commands
@@ -145,9 +169,60 @@ pub async fn build_command_contexts_with_session(
         strategy, commands, detailed_count, current_session_id, min_current_session_detailed,
     ).await;
 
-    // History: command-line only, no stream reading
-    let history: Vec<CommandContext> = history_cmds
-        .iter()
+    let history = history_contexts(&history_cmds, session_hostnames);
+    let detailed = read_detailed(&detailed_cmds, reader, session_hostnames, max_line_width)?;
+
+    Ok((history, detailed))
+}
+
+/// Like `build_context_with_session`, but fails with "context build
+/// cancelled" as soon as `token` is cancelled.
+///
+/// Stream reads run on tokio's blocking pool so a slow reader cannot hold up
+/// cancellation; a read that is cancelled keeps running in the background
+/// and its result is dropped.
+#[allow(clippy::too_many_arguments)]
+pub async fn build_context_with_cancel(
+    strategy: &dyn ContextStrategy,
+    formatter: &dyn ContextFormatter,
+    commands: &[CommandRecord],
+    reader: Arc<dyn StreamReader>,
+    session_hostnames: &HashMap<String, String>,
+    detailed_count: usize,
+    max_line_width: usize,
+    current_session_id: Option<&str>,
+    min_current_session_detailed: usize,
+    token: CancellationToken,
+) -> Result<String> {
+    let work = async {
+        let (history_cmds, detailed_cmds) = select_and_split(
+            strategy, commands, detailed_count, current_session_id, min_current_session_detailed,
+        ).await;
+        let history = history_contexts(&history_cmds, session_hostnames);
+
+        let detailed_cmds: Vec<CommandRecord> = detailed_cmds.into_iter().cloned().collect();
+        let hostnames = session_hostnames.clone();
+        let detailed = tokio::task::spawn_blocking(move || {
+            let cmds: Vec<&CommandRecord> = detailed_cmds.iter().collect();
+            read_detailed(&cmds, &*reader, &hostnames, max_line_width)
+        })
+        .await??;
+
+        Ok(formatter.format(&history, &detailed))
+    };
+
+    tokio::select! {
+        result = work => result,
+        _ = token.cancelled() => Err(anyhow!("context build cancelled")),
+    }
+}
+
+/// History commands: command-line only, no stream reading.
+fn history_contexts(
+    cmds: &[&CommandRecord],
+    session_hostnames: &HashMap<String, String>,
+) -> Vec<CommandContext> {
+    cmds.iter()
         .map(|cmd| CommandContext {
             session_id: cmd.session_id.clone(),
             hostname: session_hostnames.get(&cmd.session_id).cloned(),
@@ -157,47 +232,104 @@ pub async fn build_command_contexts_with_session(
             ended_at: cmd.ended_at,
             output: String::new(),
             exit_code: cmd.exit_code,
+            exit_signal: cmd.exit_signal,
+            tags: cmd.tags.clone(),
         })
-        .collect();
-
-    // Detailed: full stream reading
-    let mut detailed = Vec::new();
-    for cmd in detailed_cmds {
-        let entries = reader.read_command_output(cmd.stream_offset, cmd.stream_length)?;
+        .collect()
+}
 
-        let mut raw_bytes = Vec::new();
-        for entry in &entries {
-            if entry.direction == 1 {
-                raw_bytes.extend_from_slice(&entry.data);
-            }
+/// Materialize `cmds` with their output, keeping their order.
+///
+/// Commands are grouped by session and each group is read on its own scoped
+/// thread, so reads from different sessions' stream files overlap.
+/// `StreamReader` is synchronous, which is why this uses threads rather than
+/// concurrent futures.
+fn read_detailed(
+    cmds: &[&CommandRecord],
+    reader: &dyn StreamReader,
+    session_hostnames: &HashMap<String, String>,
+    max_line_width: usize,
+) -> Result<Vec<CommandContext>> {
+    let mut by_session: Vec<(&str, Vec<(usize, &CommandRecord)>)> = Vec::new();
+    for (i, cmd) in cmds.iter().enumerate() {
+        match by_session.iter_mut().find(|(sid, _)| *sid == cmd.session_id) {
+            Some((_, group)) => group.push((i, cmd)),
+            None => by_session.push((&cmd.session_id, vec![(i, cmd)])),
         }
+    }
 
-        let output = strip_ansi(&raw_bytes);
-        // The PTY output stream starts with the prompt + echoed command line;
-        // strip that first line since the command is already shown in the header.
-        let output = match output.find('\n') {
-            Some(pos) => output[pos + 1..].to_string(),
-            None => String::new(),
-        };
-        // Trim leading whitespace (including \r, \n that may remain after stripping first line).
-        let output = output.trim_start().to_string();
+    let read_group = |group: &[(usize, &CommandRecord)]| -> Result<Vec<(usize, CommandContext)>> {
+        group
+            .iter()
+            .map(|(i, cmd)| Ok((*i, detailed_context(cmd, reader, session_hostnames, max_line_width)?)))
+            .collect()
+    };
+    let results: Vec<Result<Vec<(usize, CommandContext)>>> = if by_session.len() <= 1 {
+        by_session.iter().map(|(_, group)| read_group(group)).collect()
+    } else {
+        let read_group = &read_group;
+        std::thread::scope(|s| {
+            let handles: Vec<_> = by_session
+                .iter()
+                .map(|(_, group)| s.spawn(move || read_group(group)))
+                .collect();
+            handles
+                .into_iter()
+                .map(|h| h.join().expect("stream read thread panicked"))
+                .collect()
+        })
+    };
 
-        // Truncate overly long lines (e.g. snap progress bars).
-        let output = format_utils::truncate_line_width(&output, max_line_width);
+    let mut detailed = Vec::with_capacity(cmds.len());
+    for result in results {
+        detailed.extend(result?);
+    }
+    detailed.sort_by_key(|(i, _)| *i);
+    Ok(detailed.into_iter().map(|(_, ctx)| ctx).collect())
+}
 
-        detailed.push(CommandContext {
-            session_id: cmd.session_id.clone(),
-            hostname: session_hostnames.get(&cmd.session_id).cloned(),
-            command_line: cmd.command_line.clone(),
-            cwd: shorten_cwd(&cmd.cwd),
-            started_at: cmd.started_at,
-            ended_at: cmd.ended_at,
-            output,
-            exit_code: cmd.exit_code,
-        });
+/// One detailed command: its output read from the stream, with the echoed
+/// command line, ANSI sequences and leading blank lines removed.
+fn detailed_context(
+    cmd: &CommandRecord,
+    reader: &dyn StreamReader,
+    session_hostnames: &HashMap<String, String>,
+    max_line_width: usize,
+) -> Result<CommandContext> {
+    let entries = reader.read_command_output(cmd.stream_offset, cmd.stream_length)?;
+
+    let mut raw_bytes = Vec::new();
+    for entry in &entries {
+        if entry.direction == 1 {
+            raw_bytes.extend_from_slice(&entry.data);
+        }
     }
 
-    Ok((history, detailed))
+    let output = strip_ansi(&raw_bytes);
+    // The PTY output stream starts with the prompt + echoed command line;
+    // strip that first line since the command is already shown in the header.
+    let output = match output.find('\n') {
+        Some(pos) => output[pos + 1..].to_string(),
+        None => String::new(),
+    };
+    // Trim leading whitespace (including \r, \n that may remain after stripping first line).
+    let output = output.trim_start().to_string();
+
+    // Truncate overly long lines (e.g. snap progress bars).
+    let output = format_utils::truncate_line_width(&output, max_line_width, format_utils::DEFAULT_TAB_STOP);
+
+    Ok(CommandContext {
+        session_id: cmd.session_id.clone(),
+        hostname: session_hostnames.get(&cmd.session_id).cloned(),
+        command_line: cmd.command_line.clone(),
+        cwd: shorten_cwd(&cmd.cwd),
+        started_at: cmd.started_at,
+        ended_at: cmd.ended_at,
+        output,
+        exit_code: cmd.exit_code,
+        exit_signal: cmd.exit_signal,
+        tags: cmd.tags.clone(),
+    })
 }
 
 /// Given selected commands and an initial split point, adjust the split so that
@@ -265,50 +397,283 @@ fn split_with_current_session_minimum<'a>(
     (new_history, promoted)
 }
 
-/// Strip ANSI escape sequences (CSI and OSC) from raw bytes.
+/// Strip ANSI escape sequences (CSI, OSC, DCS, PM and APC) from raw bytes.
 pub fn strip_ansi(raw: &[u8]) -> String {
-    let s = String::from_utf8_lossy(raw);
+    let mut state = AnsiStripState::default();
+    let mut result = strip_ansi_incremental(raw, &mut state);
+    result.push_str(&state.finish());
+    result
+}
+
+/// Where `strip_ansi_incremental` stopped parsing.
+#[derive(Debug, Default, Clone, Copy, PartialEq, Eq)]
+enum AnsiPhase {
+    #[default]
+    Text,
+    /// After ESC.
+    Esc,
+    /// CSI body, up to its final letter.
+    Csi,
+    /// OSC (`bel_ends`) or DCS/PM/APC body, up to the String Terminator.
+    ControlString { bel_ends: bool },
+    /// ESC inside a control string. It ends the string, taking a following
+    /// backslash with it, so a cut-off sequence can't swallow the rest of
+    /// the output.
+    ControlStringEsc,
+    /// Rest of a multi-byte char following ESC.
+    SkipContinuation(u8),
+}
+
+/// State carried across `strip_ansi_incremental` calls: the escape
+/// sequence being parsed and the bytes of a UTF-8 char cut by the chunk
+/// boundary. `Default` starts outside any sequence.
+#[derive(Debug, Default, Clone)]
+pub struct AnsiStripState {
+    phase: AnsiPhase,
+    utf8_tail: Vec<u8>,
+}
+
+impl AnsiStripState {
+    /// Call once the input has ended: returns a held-back incomplete UTF-8
+    /// char (as U+FFFD) and resets the state.
+    pub fn finish(&mut self) -> String {
+        self.phase = AnsiPhase::Text;
+        String::from_utf8_lossy(&std::mem::take(&mut self.utf8_tail)).into_owned()
+    }
+}
+
+/// `strip_ansi` for one chunk of a longer stream. Escape sequences and
+/// UTF-8 chars may span chunks; `state` carries them to the next call, so
+/// the concatenated results equal `strip_ansi` of the whole input once
+/// `AnsiStripState::finish` is appended.
+pub fn strip_ansi_incremental(raw: &[u8], state: &mut AnsiStripState) -> String {
     let mut result = String::new();
-    let mut chars = s.chars().peekable();
-    while let Some(c) = chars.next() {
-        if c == '\x1b' {
-            match chars.peek() {
-                Some(&'[') => {
-                    // CSI: ESC [ ... <alpha>
-                    chars.next();
-                    while let Some(&next) = chars.peek() {
-                        chars.next();
-                        if next.is_ascii_alphabetic() {
-                            break;
-                        }
-                    }
-                }
-                Some(&']') => {
-                    // OSC: ESC ] ... BEL or ESC backslash
-                    chars.next();
-                    while let Some(&next) = chars.peek() {
-                        if next == '\x07' {
-                            chars.next();
-                            break;
-                        }
-                        if next == '\x1b' {
-                            chars.next();
-                            if chars.peek() == Some(&'\\') {
-                                chars.next();
-                            }
-                            break;
-                        }
-                        chars.next();
-                    }
-                }
-                _ => {
-                    // Other ESC sequence - skip one char
-                    chars.next();
+    let mut text = std::mem::take(&mut state.utf8_tail);
+    let mut i = 0;
+    while i < raw.len() {
+        let b = raw[i];
+        i += 1;
+        state.phase = match state.phase {
+            AnsiPhase::Text if b == 0x1b => {
+                result.push_str(&String::from_utf8_lossy(&text));
+                text.clear();
+                AnsiPhase::Esc
+            }
+            AnsiPhase::Text => {
+                text.push(b);
+                AnsiPhase::Text
+            }
+            AnsiPhase::Esc => match b {
+                b'[' => AnsiPhase::Csi,
+                b']' => AnsiPhase::ControlString { bel_ends: true },
+                b'P' | b'X' | b'_' => AnsiPhase::ControlString { bel_ends: false },
+                // Other ESC sequence - skip one char
+                0xC0..=0xDF => AnsiPhase::SkipContinuation(1),
+                0xE0..=0xEF => AnsiPhase::SkipContinuation(2),
+                0xF0..=0xF7 => AnsiPhase::SkipContinuation(3),
+                _ => AnsiPhase::Text,
+            },
+            AnsiPhase::Csi if b.is_ascii_alphabetic() => AnsiPhase::Text,
+            AnsiPhase::Csi => AnsiPhase::Csi,
+            AnsiPhase::ControlString { bel_ends: true } if b == 0x07 => AnsiPhase::Text,
+            AnsiPhase::ControlString { .. } if b == 0x1b => AnsiPhase::ControlStringEsc,
+            phase @ AnsiPhase::ControlString { .. } => phase,
+            AnsiPhase::ControlStringEsc => {
+                if b != b'\\' {
+                    i -= 1;
                 }
+                AnsiPhase::Text
             }
-        } else {
-            result.push(c);
-        }
+            AnsiPhase::SkipContinuation(n) if (0x80..=0xBF).contains(&b) => {
+                if n > 1 { AnsiPhase::SkipContinuation(n - 1) } else { AnsiPhase::Text }
+            }
+            AnsiPhase::SkipContinuation(_) => {
+                i -= 1;
+                AnsiPhase::Text
+            }
+        };
     }
+    let keep = incomplete_utf8_tail(&text);
+    state.utf8_tail = text.split_off(text.len() - keep);
+    result.push_str(&String::from_utf8_lossy(&text));
     result
 }
+
+/// Length of a UTF-8 char prefix at the end of `bytes` that the next chunk
+/// may complete, or 0.
+fn incomplete_utf8_tail(bytes: &[u8]) -> usize {
+    let Some(start) = bytes.iter().rev().take(3).position(|b| !(0x80..=0xBF).contains(b)) else {
+        return 0;
+    };
+    let start = bytes.len() - 1 - start;
+    match std::str::from_utf8(&bytes[start..]) {
+        Err(e) if e.error_len().is_none() => bytes.len() - start,
+        _ => 0,
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::recent::RecentCommands;
+
+    #[test]
+    fn test_strip_ansi_control_strings() {
+        // DCS (e.g. a sixel/DECRQSS reply) between plain text
+        assert_eq!(strip_ansi(b"before\x1bP0;1|17/ab\x1b\\after"), "beforeafter");
+        // PM and APC, and OSC still ending at BEL
+        assert_eq!(strip_ansi(b"a\x1bXprivacy\x1b\\b\x1b_Gf=100;\x1b\\c"), "abc");
+        assert_eq!(strip_ansi(b"\x1b]0;title\x07\x1b[1mbold\x1b[0m"), "bold");
+        // BEL does not end a DCS
+        assert_eq!(strip_ansi(b"x\x1bPq\x07#0\x1b\\y"), "xy");
+    }
+
+    #[test]
+    fn test_strip_ansi_incremental_across_chunks() {
+        let mut state = AnsiStripState::default();
+        let mut out = strip_ansi_incremental(b"\x1b[32mhe", &mut state);
+        out += &strip_ansi_incremental(b"llo\x1b[0m", &mut state);
+        assert_eq!(out, "hello");
+
+        // Every split point of a mixed input matches the one-shot result
+        let input = "a\x1b]0;title\x07b\x1b[1;31m\u{4e2d}\x1bPq\x1b\\c\x1b\u{e9}d\u{1f600}".as_bytes();
+        let whole = strip_ansi(input);
+        assert_eq!(whole, "ab\u{4e2d}cd\u{1f600}");
+        for cut in 0..=input.len() {
+            let mut state = AnsiStripState::default();
+            let mut out = strip_ansi_incremental(&input[..cut], &mut state);
+            out += &strip_ansi_incremental(&input[cut..], &mut state);
+            out += &state.finish();
+            assert_eq!(out, whole, "cut at {}", cut);
+        }
+
+        // A char cut off at the very end of the input is not lost silently
+        let mut state = AnsiStripState::default();
+        assert_eq!(strip_ansi_incremental(b"x\xe4\xb8", &mut state), "x");
+        assert_eq!(state.finish(), "\u{fffd}");
+    }
+
+    /// Output keyed by stream offset, with a delay so concurrent groups
+    /// actually overlap.
+    struct OffsetReader;
+
+    impl StreamReader for OffsetReader {
+        fn read_command_output(&self, offset: u64, _length: u64) -> Result<Vec<StreamEntry>> {
+            std::thread::sleep(std::time::Duration::from_millis(5));
+            if offset == 999 {
+                anyhow::bail!("unreadable");
+            }
+            Ok(vec![StreamEntry::new(
+                0,
+                1,
+                format!("$ cmd\r\n\x1b[1mout-{}\x1b[0m\n", offset).into_bytes(),
+            )])
+        }
+    }
+
+    fn cmd(i: u64, session_id: &str, started_at: u64) -> CommandRecord {
+        CommandRecord {
+            command_id: format!("c{}", i),
+            session_id: session_id.into(),
+            command_line: Some(format!("cmd {}", i)),
+            cwd: None,
+            started_at,
+            ended_at: Some(started_at + 1),
+            output_summary: String::new(),
+            stream_offset: i,
+            stream_length: 10,
+            exit_code: Some(0),
+            exit_signal: None,
+            checksum: None,
+            tags: Vec::new(),
+        }
+    }
+
+    fn key(c: &CommandContext) -> (String, Option<String>, Option<String>, u64, String) {
+        (c.session_id.clone(), c.hostname.clone(), c.command_line.clone(), c.started_at, c.output.clone())
+    }
+
+    #[tokio::test]
+    async fn test_parallel_read_matches_sequential() {
+        // Interleaved sessions, including a started_at tie across sessions.
+        let commands: Vec<CommandRecord> = (0..12)
+            .map(|i| cmd(i, ["s1", "s2", "s3"][i as usize % 3], 1000 + i / 2 * 10))
+            .collect();
+        let hostnames = HashMap::from([("s2".to_string(), "box".to_string())]);
+        let strategy = RecentCommands::new(12);
+
+        let (_, detailed) = build_command_contexts_with_session(
+            &strategy, &commands, &OffsetReader, &hostnames, 12, 200, None, 0,
+        ).await.unwrap();
+
+        let sequential: Vec<_> = commands
+            .iter()
+            .map(|c| key(&detailed_context(c, &OffsetReader, &hostnames, 200).unwrap()))
+            .collect();
+        assert_eq!(detailed.iter().map(key).collect::<Vec<_>>(), sequential);
+        assert_eq!(detailed[4].output, "out-4");
+        assert_eq!(detailed[1].hostname.as_deref(), Some("box"));
+    }
+
+    #[tokio::test]
+    async fn test_parallel_read_propagates_errors() {
+        let mut commands = vec![cmd(1, "s1", 1000), cmd(2, "s2", 1010)];
+        commands[1].stream_offset = 999;
+        let strategy = RecentCommands::new(2);
+        let result = build_command_contexts_with_session(
+            &strategy, &commands, &OffsetReader, &HashMap::new(), 2, 200, None, 0,
+        ).await;
+        assert!(result.is_err());
+    }
+
+    struct SlowReader;
+
+    impl StreamReader for SlowReader {
+        fn read_command_output(&self, _offset: u64, _length: u64) -> Result<Vec<StreamEntry>> {
+            std::thread::sleep(std::time::Duration::from_secs(10));
+            Ok(Vec::new())
+        }
+    }
+
+    #[test]
+    fn test_cancel_aborts_slow_read() {
+        let rt = tokio::runtime::Runtime::new().unwrap();
+        rt.block_on(async {
+            let commands = vec![cmd(1, "s1", 1000)];
+            let strategy = RecentCommands::new(1);
+            let formatter = crate::recent::GroupedFormatter::new("s1", 2000, 5, 5);
+            let token = CancellationToken::new();
+            let timer = token.clone();
+            tokio::spawn(async move {
+                tokio::time::sleep(std::time::Duration::from_millis(100)).await;
+                timer.cancel();
+            });
+
+            let start = std::time::Instant::now();
+            let err = build_context_with_cancel(
+                &strategy, &formatter, &commands, Arc::new(SlowReader), &HashMap::new(),
+                1, 200, None, 0, token,
+            ).await.unwrap_err();
+            assert_eq!(err.to_string(), "context build cancelled");
+            assert!(start.elapsed() < std::time::Duration::from_secs(2), "took {:?}", start.elapsed());
+        });
+        // The sleeping read is still on the blocking pool; don't wait for it.
+        rt.shutdown_background();
+    }
+
+    #[tokio::test]
+    async fn test_cancel_variant_matches_uncancelled_build() {
+        let commands: Vec<CommandRecord> = (0..4).map(|i| cmd(i, "s1", 1000 + i)).collect();
+        let strategy = RecentCommands::new(4);
+        let formatter = crate::recent::GroupedFormatter::new("s1", 2000, 5, 5);
+        let expected = build_context_with_session(
+            &strategy, &formatter, &commands, &OffsetReader, &HashMap::new(), 2, 200, None, 0,
+        ).await.unwrap();
+        let ctx = build_context_with_cancel(
+            &strategy, &formatter, &commands, Arc::new(OffsetReader), &HashMap::new(),
+            2, 200, None, 0, CancellationToken::new(),
+        ).await.unwrap();
+        assert_eq!(ctx, expected);
+        assert!(ctx.contains("out-3"), "{ctx}");
+    }
+}
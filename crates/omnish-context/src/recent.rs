@@ -1,7 +1,10 @@
 use async_trait::async_trait;
 use omnish_store::command::CommandRecord;
+use regex::Regex;
+use std::collections::{HashMap, HashSet};
+use std::time::{Duration, SystemTime, UNIX_EPOCH};
 
-use crate::format_utils::{assign_term_labels, truncate_lines};
+use crate::format_utils::{assign_term_indices, assign_term_labels, deferred_relative_time, format_duration_short, relative_time, truncate_bytes, truncate_lines};
 use crate::{CommandContext, ContextFormatter, ContextStrategy};
 
 fn format_command_prefix(hostname: &Option<String>, cwd: &Option<String>) -> String {
@@ -13,11 +16,69 @@ fn format_command_prefix(hostname: &Option<String>, cwd: &Option<String>) -> Str
     }
 }
 
+/// ` [tag1, tag2]` suffix for a command line, or empty when untagged.
+fn format_tags(tags: &[String]) -> String {
+    if tags.is_empty() {
+        String::new()
+    } else {
+        format!(" [{}]", tags.join(", "))
+    }
+}
+
+/// `  [KILLED: signal N]` or `  [FAILED: code]` suffix, or empty on success.
+fn status_tag(cmd: &CommandContext) -> String {
+    match (cmd.exit_signal, cmd.exit_code) {
+        (Some(sig), _) => format!("  [KILLED: signal {}]", sig),
+        (None, Some(code)) if code != 0 => format!("  [FAILED: {}]", code),
+        _ => String::new(),
+    }
+}
+
+/// True if `exclude` matches the (trimmed) command line of `cmd`.
+/// Describe the session's Python environment from its probe attrs, e.g.
+/// `venv: myenv/3.11.4` or `conda: base`. `None` when neither is active.
+pub fn python_env_label(attrs: &HashMap<String, String>) -> Option<String> {
+    let mut parts = Vec::new();
+    if let Some(path) = attrs.get("venv.path") {
+        let name = path.trim_end_matches('/').rsplit('/').next().unwrap_or(path);
+        match attrs.get("venv.python_version") {
+            Some(version) => parts.push(format!("venv: {}/{}", name, version)),
+            None => parts.push(format!("venv: {}", name)),
+        }
+    }
+    if let Some(env) = attrs.get("conda.env_name") {
+        parts.push(format!("conda: {}", env));
+    }
+    if parts.is_empty() { None } else { Some(parts.join(", ")) }
+}
+
+/// Describe the session's Kubernetes context from its probe attrs, e.g.
+/// `k8s: mycluster/prod`. The namespace defaults to `default` like kubectl.
+pub fn kube_label(attrs: &HashMap<String, String>) -> Option<String> {
+    let cluster = attrs.get("k8s.cluster").or_else(|| attrs.get("k8s.context"))?;
+    let namespace = attrs.get("k8s.namespace").map(String::as_str).unwrap_or("default");
+    Some(format!("k8s: {}/{}", cluster, namespace))
+}
+
+/// Tags for the current session's header in grouped context.
+pub fn session_header_tags(attrs: &HashMap<String, String>) -> Vec<String> {
+    [python_env_label(attrs), kube_label(attrs)].into_iter().flatten().collect()
+}
+
+pub fn is_excluded(exclude: Option<&Regex>, cmd: &CommandRecord) -> bool {
+    match (exclude, cmd.command_line.as_deref()) {
+        (Some(re), Some(line)) => re.is_match(line.trim()),
+        _ => false,
+    }
+}
+
 /// Selects the most recent N commands.
 pub struct RecentCommands {
     max: usize,
     current_session_id: Option<String>,
     min_current_session_commands: usize,
+    required_tags: Vec<String>,
+    exclude: Option<Regex>,
 }
 
 impl RecentCommands {
@@ -26,9 +87,35 @@ impl RecentCommands {
             max,
             current_session_id: None,
             min_current_session_commands: 0,
+            required_tags: Vec::new(),
+            exclude: None,
         }
     }
 
+    /// Skip commands whose command line matches `pattern` (e.g.
+    /// `^(cd|ls|clear|echo)\b`). Excluded commands do not take slots, so
+    /// selection reaches further back instead.
+    pub fn with_exclusion_pattern(self, pattern: &str) -> anyhow::Result<Self> {
+        Ok(self.with_exclusion(Some(Regex::new(pattern)?)))
+    }
+
+    /// Like `with_exclusion_pattern` with an already compiled regex.
+    pub fn with_exclusion(mut self, exclude: Option<Regex>) -> Self {
+        self.exclude = exclude;
+        self
+    }
+
+    /// Restrict selection to commands started within the last `hours`.
+    pub fn within_hours(self, hours: u64) -> TimeWindowStrategy {
+        TimeWindowStrategy::new(Box::new(self), Duration::from_secs(hours * 3600))
+    }
+
+    /// Only select commands carrying every one of `tags`. Empty selects all.
+    pub fn with_required_tags(mut self, tags: Vec<String>) -> Self {
+        self.required_tags = tags;
+        self
+    }
+
     pub fn with_current_session(mut self, session_id: &str, min_commands: usize) -> Self {
         self.current_session_id = Some(session_id.to_string());
         self.min_current_session_commands = min_commands;
@@ -42,6 +129,8 @@ impl ContextStrategy for RecentCommands {
         // Filter out empty commands (Enter with no input) so they don't consume slots
         let mut meaningful: Vec<_> = commands.iter()
             .filter(|c| c.command_line.is_some())
+            .filter(|c| self.required_tags.iter().all(|t| c.tags.contains(t)))
+            .filter(|c| !is_excluded(self.exclude.as_ref(), c))
             .collect();
 
         // Sort by started_at to ensure chronological order
@@ -98,18 +187,15 @@ impl ContextStrategy for RecentCommands {
         // Need more current session commands
         let needed = self.min_current_session_commands - current_in_recent;
 
-        // Get additional current session commands (most recent ones not already in recent_overall)
+        // Get additional current session commands (most recent ones not already in recent_overall).
+        // All references borrow from `commands`, so pointer identity is record identity.
+        let in_recent: HashSet<*const CommandRecord> = recent_overall
+            .iter()
+            .map(|cmd| *cmd as *const CommandRecord)
+            .collect();
         let mut additional_current: Vec<&CommandRecord> = Vec::new();
         for cmd in current_session_commands.iter().rev() {
-            // Check if cmd is already in recent_overall using command_id
-            let mut found = false;
-            for recent_cmd in &recent_overall {
-                if cmd.command_id == recent_cmd.command_id {
-                    found = true;
-                    break;
-                }
-            }
-            if !found {
+            if !in_recent.contains(&(*cmd as *const CommandRecord)) {
                 additional_current.push(cmd);
                 if additional_current.len() >= needed {
                     break;
@@ -149,23 +235,280 @@ impl ContextStrategy for RecentCommands {
     }
 }
 
+/// Hides commands started more than `window` ago from the inner strategy.
+///
+/// When `commands` is sorted by `started_at` (the usual case) the inner
+/// strategy only sees the in-window suffix. Otherwise its selection is
+/// filtered afterwards, which may return fewer commands than it would
+/// have picked from the window alone.
+pub struct TimeWindowStrategy {
+    inner: Box<dyn ContextStrategy>,
+    window: Duration,
+}
+
+impl TimeWindowStrategy {
+    pub fn new(inner: Box<dyn ContextStrategy>, window: Duration) -> Self {
+        Self { inner, window }
+    }
+}
+
+#[async_trait]
+impl ContextStrategy for TimeWindowStrategy {
+    async fn select_commands<'a>(&self, commands: &'a [CommandRecord]) -> Vec<&'a CommandRecord> {
+        let now_ms = SystemTime::now()
+            .duration_since(UNIX_EPOCH)
+            .unwrap_or_default()
+            .as_millis() as u64;
+        let cutoff = now_ms.saturating_sub(self.window.as_millis() as u64);
+        if commands.windows(2).all(|w| w[0].started_at <= w[1].started_at) {
+            let start = commands.partition_point(|c| c.started_at < cutoff);
+            return self.inner.select_commands(&commands[start..]).await;
+        }
+        let mut selected = self.inner.select_commands(commands).await;
+        selected.retain(|c| c.started_at >= cutoff);
+        selected
+    }
+}
+
+/// Selects the most recent N commands that failed (non-zero exit code).
+/// Commands without an exit code are not counted as failures.
+pub struct RecentFailedCommands {
+    max: usize,
+}
+
+impl RecentFailedCommands {
+    pub fn new(max: usize) -> Self {
+        Self { max }
+    }
+}
+
+#[async_trait]
+impl ContextStrategy for RecentFailedCommands {
+    async fn select_commands<'a>(&self, commands: &'a [CommandRecord]) -> Vec<&'a CommandRecord> {
+        let mut failed: Vec<_> = commands
+            .iter()
+            .filter(|c| c.command_line.is_some())
+            .filter(|c| c.exit_code.is_some() && c.exit_code != Some(0))
+            .collect();
+        failed.sort_by_key(|cmd| cmd.started_at);
+        let skip = failed.len().saturating_sub(self.max);
+        failed.split_off(skip)
+    }
+}
+
+/// Selects every command chosen by at least one child strategy, once per
+/// `command_id`, in `started_at` order.
+pub struct UnionStrategy {
+    strategies: Vec<Box<dyn ContextStrategy>>,
+}
+
+impl UnionStrategy {
+    pub fn new(strategies: Vec<Box<dyn ContextStrategy>>) -> Self {
+        Self { strategies }
+    }
+}
+
+#[async_trait]
+impl ContextStrategy for UnionStrategy {
+    async fn select_commands<'a>(&self, commands: &'a [CommandRecord]) -> Vec<&'a CommandRecord> {
+        let mut seen: HashSet<&str> = HashSet::new();
+        let mut selected = Vec::new();
+        for strategy in &self.strategies {
+            for cmd in strategy.select_commands(commands).await {
+                if seen.insert(&cmd.command_id) {
+                    selected.push(cmd);
+                }
+            }
+        }
+        selected.sort_by_key(|cmd| cmd.started_at);
+        selected
+    }
+}
+
+/// Wraps a strategy and prefers the latest `cwd_bonus` commands run in the
+/// current working directory for the detailed portion, adding them to the
+/// base selection if it left them out. `select_and_split` gives them full
+/// output and moves others into history. Without `with_cwd` it behaves like
+/// the base strategy.
+pub struct CwdPreferenceStrategy {
+    base: Box<dyn ContextStrategy>,
+    cwd_bonus: usize,
+    cwd: Option<String>,
+    exclude: Option<Regex>,
+}
+
+impl CwdPreferenceStrategy {
+    pub fn new(base: Box<dyn ContextStrategy>, cwd_bonus: usize) -> Self {
+        Self { base, cwd_bonus, cwd: None, exclude: None }
+    }
+
+    pub fn with_cwd(mut self, cwd: &str) -> Self {
+        self.cwd = Some(cwd.to_string());
+        self
+    }
+
+    /// Never add commands whose command line matches `exclude`.
+    pub fn with_exclusion(mut self, exclude: Option<Regex>) -> Self {
+        self.exclude = exclude;
+        self
+    }
+
+    fn in_cwd(&self, cmd: &CommandRecord) -> bool {
+        self.cwd.is_some() && cmd.cwd == self.cwd
+    }
+}
+
+#[async_trait]
+impl ContextStrategy for CwdPreferenceStrategy {
+    async fn select_commands<'a>(&self, commands: &'a [CommandRecord]) -> Vec<&'a CommandRecord> {
+        let mut selected = self.base.select_commands(commands).await;
+        let mut in_cwd: Vec<&CommandRecord> = commands
+            .iter()
+            .filter(|c| c.command_line.is_some() && self.in_cwd(c))
+            .filter(|c| !is_excluded(self.exclude.as_ref(), c))
+            .collect();
+        in_cwd.sort_by_key(|cmd| cmd.started_at);
+        let chosen: HashSet<*const CommandRecord> =
+            selected.iter().map(|cmd| *cmd as *const CommandRecord).collect();
+        selected.extend(
+            in_cwd
+                .into_iter()
+                .rev()
+                .take(self.cwd_bonus)
+                .filter(|cmd| !chosen.contains(&(*cmd as *const CommandRecord))),
+        );
+        selected.sort_by_key(|cmd| cmd.started_at);
+        selected
+    }
+
+    fn preferred_detailed<'a>(&self, selected: &[&'a CommandRecord]) -> Vec<&'a CommandRecord> {
+        let mut preferred = self.base.preferred_detailed(selected);
+        preferred.extend(
+            selected
+                .iter()
+                .rev()
+                .filter(|c| self.in_cwd(c))
+                .take(self.cwd_bonus)
+                .copied(),
+        );
+        preferred
+    }
+}
+
+/// Selects only commands chosen by every child strategy, in `started_at`
+/// order. With no children nothing is selected.
+pub struct IntersectionStrategy {
+    strategies: Vec<Box<dyn ContextStrategy>>,
+}
+
+impl IntersectionStrategy {
+    pub fn new(strategies: Vec<Box<dyn ContextStrategy>>) -> Self {
+        Self { strategies }
+    }
+}
+
+#[async_trait]
+impl ContextStrategy for IntersectionStrategy {
+    async fn select_commands<'a>(&self, commands: &'a [CommandRecord]) -> Vec<&'a CommandRecord> {
+        let Some((first, rest)) = self.strategies.split_first() else {
+            return Vec::new();
+        };
+        let mut selected = first.select_commands(commands).await;
+        for strategy in rest {
+            let ids: HashSet<&str> = strategy
+                .select_commands(commands)
+                .await
+                .into_iter()
+                .map(|cmd| cmd.command_id.as_str())
+                .collect();
+            selected.retain(|cmd| ids.contains(cmd.command_id.as_str()));
+        }
+        let mut seen: HashSet<&str> = HashSet::new();
+        selected.retain(|cmd| seen.insert(&cmd.command_id));
+        selected.sort_by_key(|cmd| cmd.started_at);
+        selected
+    }
+}
+
 /// Formats commands grouped by session, with the current session last.
 pub struct GroupedFormatter {
     current_session_id: String,
-    _now_ms: u64,
+    now_ms: u64,
     head_lines: usize,
     tail_lines: usize,
+    /// `(name, value)` environment variables of the current session.
+    env: Vec<(String, String)>,
+    /// Shown in the current session's header, see `session_header_tags`.
+    header_tags: Vec<String>,
+    max_output_bytes: Option<usize>,
+    /// Append `(45s)`-style run times to commands that took at least a second.
+    show_durations: bool,
+    /// Emit `deferred_relative_time` placeholders instead of "2m"-style ages.
+    deferred_times: bool,
 }
 
 impl GroupedFormatter {
     pub fn new(current_session_id: &str, now_ms: u64, head_lines: usize, tail_lines: usize) -> Self {
         Self {
             current_session_id: current_session_id.to_string(),
-            _now_ms: now_ms,
+            now_ms,
             head_lines,
             tail_lines,
+            env: Vec::new(),
+            header_tags: Vec::new(),
+            max_output_bytes: None,
+            show_durations: true,
+            deferred_times: false,
+        }
+    }
+
+    /// Leave ages as placeholders for `render_relative_times`, for output
+    /// that is cached and shown later.
+    pub fn with_deferred_times(mut self, deferred: bool) -> Self {
+        self.deferred_times = deferred;
+        self
+    }
+
+    fn ago(&self, started_at: u64) -> String {
+        if self.deferred_times {
+            deferred_relative_time(started_at)
+        } else {
+            relative_time(started_at, self.now_ms)
+        }
+    }
+
+    /// Cap each command's output at `max` bytes after line truncation, so a
+    /// few huge lines (e.g. base64) cannot bloat the context.
+    pub fn with_max_output_bytes(mut self, max: usize) -> Self {
+        self.max_output_bytes = Some(max);
+        self
+    }
+
+    /// Show or hide command run times (shown by default).
+    pub fn with_durations(mut self, show: bool) -> Self {
+        self.show_durations = show;
+        self
+    }
+
+    /// `  (45s)` for a command that ran at least a second, else empty.
+    fn duration_tag(&self, cmd: &CommandContext) -> String {
+        match cmd.ended_at.map(|end| end.saturating_sub(cmd.started_at)) {
+            Some(ms) if self.show_durations && ms >= 1000 => format!("  ({})", format_duration_short(ms)),
+            _ => String::new(),
         }
     }
+
+    /// Show these variables in an `--- Environment ---` section. Empty values are skipped.
+    pub fn with_env(mut self, env: Vec<(String, String)>) -> Self {
+        self.env = env.into_iter().filter(|(_, v)| !v.is_empty()).collect();
+        self
+    }
+
+    /// Tag the current session's header, e.g. with its Python environment.
+    pub fn with_header_tags(mut self, tags: Vec<String>) -> Self {
+        self.header_tags = tags;
+        self
+    }
 }
 
 impl ContextFormatter for GroupedFormatter {
@@ -176,6 +519,14 @@ impl ContextFormatter for GroupedFormatter {
 
         let mut sections = Vec::new();
 
+        if !self.env.is_empty() {
+            let mut env_lines = vec!["--- Environment ---".to_string()];
+            for (name, value) in &self.env {
+                env_lines.push(format!("{}={}", name, value));
+            }
+            sections.push(env_lines.join("\n"));
+        }
+
         // History section: command-line only
         if !history.is_empty() {
             let mut history_lines = vec!["--- History ---".to_string()];
@@ -183,7 +534,7 @@ impl ContextFormatter for GroupedFormatter {
                 let cmd_line = cmd.command_line.as_deref().unwrap_or("(unknown)");
                 let prefix = format_command_prefix(&cmd.hostname, &cmd.cwd);
                 let prefix_display = if prefix.is_empty() { String::new() } else { format!("{} ", prefix) };
-                history_lines.push(format!("{}$ {}", prefix_display, cmd_line));
+                history_lines.push(format!("{}$ {}{}", prefix_display, cmd_line, format_tags(&cmd.tags)));
             }
             sections.push(history_lines.join("\n"));
         }
@@ -211,27 +562,35 @@ impl ContextFormatter for GroupedFormatter {
             for session_id in &session_order {
                 let label = labels.get(session_id).unwrap();
                 let is_current = session_id == &self.current_session_id;
+                let current_session_commands: Vec<&CommandContext> = detailed.iter()
+                    .filter(|c| &c.session_id == session_id)
+                    .collect();
+                let last_started = current_session_commands.iter().map(|c| c.started_at).max().unwrap_or(0);
+                let ago = self.ago(last_started);
                 let header = if is_current {
-                    format!("--- {} [current] ---", label)
+                    let tags: String = self.header_tags.iter().map(|t| format!("{}, ", t)).collect();
+                    format!("--- {} [current, {}{} ago] ---", label, tags, ago)
                 } else {
-                    format!("--- {} ---", label)
+                    format!("--- {} [{} ago] ---", label, ago)
                 };
 
                 let mut group_lines = vec![header];
-                let current_session_commands: Vec<&CommandContext> = detailed.iter()
-                    .filter(|c| &c.session_id == session_id)
-                    .collect();
 
                 for cmd in &current_session_commands {
-                    let cmd_line = cmd.command_line.as_deref().unwrap_or("(unknown)");
+                    let cmd_line = format!(
+                        "{}{}",
+                        cmd.command_line.as_deref().unwrap_or("(unknown)"),
+                        format_tags(&cmd.tags)
+                    );
                     let prefix = format_command_prefix(&cmd.hostname, &cmd.cwd);
                     let max_lines = self.head_lines + self.tail_lines;
                     let output = truncate_lines(&cmd.output, max_lines, self.head_lines, self.tail_lines, None);
-
-                    let failed_tag = match cmd.exit_code {
-                        Some(code) if code != 0 => format!("  [FAILED: {}]", code),
-                        _ => String::new(),
+                    let output = match self.max_output_bytes {
+                        Some(max) => truncate_bytes(&output, max),
+                        None => output,
                     };
+
+                    let failed_tag = format!("{}{}", self.duration_tag(cmd), status_tag(cmd));
                     if output.is_empty() {
                         let prefix_display = if prefix.is_empty() { String::new() } else { format!("{} ", prefix) };
                         group_lines.push(format!("{}$ {}{}", prefix_display, cmd_line, failed_tag));
@@ -263,22 +622,64 @@ impl ContextFormatter for GroupedFormatter {
 }
 
 /// Formats commands interleaved by time, sorted by started_at.
+/// ANSI colors `InterleavedFormatter::with_colors` gives sessions, by
+/// their `assign_term_indices` position.
+pub const SESSION_COLORS: [&str; 8] = [
+    "\x1b[32m", // green
+    "\x1b[33m", // yellow
+    "\x1b[34m", // blue
+    "\x1b[35m", // magenta
+    "\x1b[36m", // cyan
+    "\x1b[92m", // bright green
+    "\x1b[93m", // bright yellow
+    "\x1b[94m", // bright blue
+];
+
+const COLOR_RESET: &str = "\x1b[0m";
+
 pub struct InterleavedFormatter {
     current_session_id: String,
-    _now_ms: u64,
+    now_ms: u64,
     head_lines: usize,
     tail_lines: usize,
+    max_output_bytes: Option<usize>,
+    /// Color each command's header line by session, for terminal display.
+    colors: bool,
+    /// See `GroupedFormatter::with_deferred_times`.
+    deferred_times: bool,
 }
 
 impl InterleavedFormatter {
     pub fn new(current_session_id: &str, now_ms: u64, head_lines: usize, tail_lines: usize) -> Self {
         Self {
             current_session_id: current_session_id.to_string(),
-            _now_ms: now_ms,
+            now_ms,
             head_lines,
             tail_lines,
+            max_output_bytes: None,
+            colors: false,
+            deferred_times: false,
         }
     }
+
+    /// See `GroupedFormatter::with_deferred_times`.
+    pub fn with_deferred_times(mut self, deferred: bool) -> Self {
+        self.deferred_times = deferred;
+        self
+    }
+
+    /// See `GroupedFormatter::with_max_output_bytes`.
+    pub fn with_max_output_bytes(mut self, max: usize) -> Self {
+        self.max_output_bytes = Some(max);
+        self
+    }
+
+    /// Color each command's label and command line with its session's
+    /// `SESSION_COLORS` entry. Off by default: the LLM gets plain text.
+    pub fn with_colors(mut self, enabled: bool) -> Self {
+        self.colors = enabled;
+        self
+    }
 }
 
 impl ContextFormatter for InterleavedFormatter {
@@ -304,6 +705,7 @@ impl ContextFormatter for InterleavedFormatter {
         // Detailed section: interleaved by time
         if !detailed.is_empty() {
             let labels = assign_term_labels(detailed, &self.current_session_id);
+            let indices = assign_term_indices(detailed, &self.current_session_id);
 
             let mut sorted: Vec<&CommandContext> = detailed.iter().collect();
             sorted.sort_by_key(|c| c.started_at);
@@ -319,17 +721,29 @@ impl ContextFormatter for InterleavedFormatter {
                 let cmd_line = cmd.command_line.as_deref().unwrap_or("(unknown)");
                 let max_lines = self.head_lines + self.tail_lines;
                 let output = truncate_lines(&cmd.output, max_lines, self.head_lines, self.tail_lines, None);
-
-                let failed_tag = match cmd.exit_code {
-                    Some(code) if code != 0 => format!("  [FAILED: {}]", code),
-                    _ => String::new(),
+                let output = match self.max_output_bytes {
+                    Some(max) => truncate_bytes(&output, max),
+                    None => output,
                 };
+
+                let failed_tag = status_tag(cmd);
                 let prefix = format_command_prefix(&cmd.hostname, &cmd.cwd);
                 let prefix_display = if prefix.is_empty() { String::new() } else { format!("{} ", prefix) };
+                let ago = if self.deferred_times {
+                    deferred_relative_time(cmd.started_at)
+                } else {
+                    relative_time(cmd.started_at, self.now_ms)
+                };
+                let (color, reset) = if self.colors {
+                    (SESSION_COLORS[indices[&cmd.session_id] % SESSION_COLORS.len()], COLOR_RESET)
+                } else {
+                    ("", "")
+                };
+                let header = format!("{}[{} ago] {} {}$ {}{}{}", color, ago, label_str, prefix_display, cmd_line, failed_tag, reset);
                 if output.is_empty() {
-                    sections.push(format!("{} {}$ {}{}", label_str, prefix_display, cmd_line, failed_tag));
+                    sections.push(header);
                 } else {
-                    sections.push(format!("{} {}$ {}{}\n{}\n--------------------", label_str, prefix_display, cmd_line, failed_tag, output));
+                    sections.push(format!("{}\n{}\n--------------------", header, output));
                 }
             }
         }
@@ -406,10 +820,7 @@ impl CompletionFormatter {
             &cmd.output, max_lines, self.head_lines, self.tail_lines,
             Some(self.max_command_output_chars),
         );
-        let failed_tag = match cmd.exit_code {
-            Some(code) if code != 0 => format!("  [FAILED: {}]", code),
-            _ => String::new(),
-        };
+        let failed_tag = status_tag(cmd);
         let prefix = format_command_prefix(&cmd.hostname, &cmd.cwd);
         let prefix_display = if prefix.is_empty() { String::new() } else { format!("{} ", prefix) };
         if output.is_empty() {
@@ -587,6 +998,9 @@ mod tests {
             stream_offset: 0,
             stream_length: 100,
             exit_code: None,
+            exit_signal: None,
+            checksum: None,
+            tags: Vec::new(),
         }
     }
 
@@ -604,23 +1018,17 @@ mod tests {
             ended_at: Some(started_at + 50),
             output: output.to_string(),
             exit_code: None,
+            exit_signal: None,
+            tags: Vec::new(),
         }
     }
 
     fn make_output_entry(text: &str) -> StreamEntry {
-        StreamEntry {
-            timestamp_ms: 1000,
-            direction: 1,
-            data: text.as_bytes().to_vec(),
-        }
+        StreamEntry::new(1000, 1, text.as_bytes().to_vec())
     }
 
     fn make_input_entry(text: &str) -> StreamEntry {
-        StreamEntry {
-            timestamp_ms: 1000,
-            direction: 0,
-            data: text.as_bytes().to_vec(),
-        }
+        StreamEntry::new(1000, 0, text.as_bytes().to_vec())
     }
 
     // --- Strategy tests ---
@@ -644,6 +1052,147 @@ mod tests {
         assert_eq!(selected[9].command_line.as_deref(), Some("cmd14"));
     }
 
+    #[tokio::test]
+    async fn test_select_required_tags() {
+        let mut cmds: Vec<_> = (0..5)
+            .map(|i| make_cmd(i, "sess", Some(&format!("cmd{}", i))))
+            .collect();
+        cmds[1].tags = vec!["build".into()];
+        cmds[3].tags = vec!["build".into(), "ci".into()];
+        let strategy = RecentCommands::new(10).with_required_tags(vec!["build".into()]);
+        let selected = strategy.select_commands(&cmds).await;
+        let lines: Vec<_> = selected.iter().map(|c| c.command_line.as_deref().unwrap()).collect();
+        assert_eq!(lines, vec!["cmd1", "cmd3"]);
+
+        let strategy = RecentCommands::new(10).with_required_tags(vec!["build".into(), "ci".into()]);
+        assert_eq!(strategy.select_commands(&cmds).await.len(), 1);
+    }
+
+    #[tokio::test]
+    async fn test_recent_failed_commands() {
+        let mut cmds: Vec<_> = (0..6)
+            .map(|i| make_cmd(i, "sess", Some(&format!("cmd{}", i))))
+            .collect();
+        cmds[0].exit_code = Some(1);
+        cmds[2].exit_code = Some(2);
+        cmds[3].exit_code = Some(0);
+        cmds[4].exit_code = Some(130);
+        let selected = RecentFailedCommands::new(2).select_commands(&cmds).await;
+        let lines: Vec<_> = selected.iter().map(|c| c.command_line.as_deref().unwrap()).collect();
+        assert_eq!(lines, vec!["cmd2", "cmd4"]);
+    }
+
+    #[tokio::test]
+    async fn test_union_and_intersection_strategies() {
+        let mut cmds: Vec<_> = (0..10)
+            .map(|i| make_cmd(i, "sess", Some(&format!("cmd{}", i))))
+            .collect();
+        for i in [1, 3, 8] {
+            cmds[i].exit_code = Some(1);
+        }
+        let union = UnionStrategy::new(vec![
+            Box::new(RecentCommands::new(5)),
+            Box::new(RecentFailedCommands::new(3)),
+        ]);
+        let selected = union.select_commands(&cmds).await;
+        let lines: Vec<_> = selected.iter().map(|c| c.command_line.as_deref().unwrap()).collect();
+        // cmd8 is both recent and failed but appears once.
+        assert_eq!(lines, vec!["cmd1", "cmd3", "cmd5", "cmd6", "cmd7", "cmd8", "cmd9"]);
+
+        let intersection = IntersectionStrategy::new(vec![
+            Box::new(RecentCommands::new(5)),
+            Box::new(RecentFailedCommands::new(3)),
+        ]);
+        let selected = intersection.select_commands(&cmds).await;
+        let lines: Vec<_> = selected.iter().map(|c| c.command_line.as_deref().unwrap()).collect();
+        assert_eq!(lines, vec!["cmd8"]);
+        assert!(IntersectionStrategy::new(Vec::new()).select_commands(&cmds).await.is_empty());
+    }
+
+    #[tokio::test]
+    async fn test_select_exclusion_pattern() {
+        // sess-a (current) runs cd/ls between real work; sess-b is noise.
+        let lines = ["make", "cd src", "ls", "cargo test", "cd ..", "clear", "echo hi", "ls -la"];
+        let mut cmds: Vec<_> = lines
+            .iter()
+            .enumerate()
+            .map(|(i, l)| make_cmd(i as u32 * 2, "sess-a", Some(l)))
+            .collect();
+        cmds.extend((0..8).map(|i| make_cmd(i * 2 + 1, "sess-b", Some(&format!("vim f{}", i)))));
+
+        let strategy = RecentCommands::new(4)
+            .with_current_session("sess-a", 2)
+            .with_exclusion_pattern(r"^(cd|ls|clear|echo)\b")
+            .unwrap();
+        let selected = strategy.select_commands(&cmds).await;
+        let lines: Vec<_> = selected.iter().map(|c| c.command_line.as_deref().unwrap()).collect();
+        assert!(!lines.iter().any(|l| l.starts_with("cd") || l.starts_with("ls")), "{lines:?}");
+        // The two remaining sess-a commands are the oldest; they are still
+        // pulled in to satisfy the current-session minimum.
+        assert_eq!(lines, vec!["make", "cargo test", "vim f6", "vim f7"]);
+
+        assert!(RecentCommands::new(4).with_exclusion_pattern("(").is_err());
+    }
+
+    #[tokio::test]
+    async fn test_cwd_preference_promotes_into_detailed() {
+        let dirs = ["/repo", "/tmp", "/home/u", "/repo", "/tmp", "/home/u", "/tmp", "/home/u", "/tmp", "/home/u"];
+        let cmds: Vec<_> = dirs
+            .iter()
+            .enumerate()
+            .map(|(i, d)| {
+                let mut c = make_cmd(i as u32, "sess", Some(&format!("cmd{}", i)));
+                c.cwd = Some(d.to_string());
+                c
+            })
+            .collect();
+
+        async fn split(strategy: CwdPreferenceStrategy, cmds: &[CommandRecord]) -> (usize, Vec<String>) {
+            let (history, detailed) = crate::select_and_split(&strategy, cmds, 3, None, 0).await;
+            (history.len(), detailed.iter().map(|c| c.command_line.clone().unwrap()).collect())
+        }
+        // Without a cwd the most recent three are detailed.
+        let plain = CwdPreferenceStrategy::new(Box::new(RecentCommands::new(10)), 2);
+        assert_eq!(split(plain, &cmds).await, (7, vec!["cmd7".into(), "cmd8".into(), "cmd9".into()]));
+
+        let strategy = CwdPreferenceStrategy::new(Box::new(RecentCommands::new(10)), 2).with_cwd("/repo");
+        let selected = strategy.select_commands(&cmds).await;
+        assert!(selected.windows(2).all(|w| w[0].started_at <= w[1].started_at));
+        let (history_len, detailed) = split(strategy, &cmds).await;
+        assert_eq!(history_len, 7);
+        // Still in started_at order
+        assert_eq!(detailed, vec!["cmd0", "cmd3", "cmd9"]);
+    }
+
+    #[tokio::test]
+    async fn test_time_window_strategy() {
+        let now_ms = SystemTime::now().duration_since(UNIX_EPOCH).unwrap().as_millis() as u64;
+        let hour = 3_600_000;
+        // One command every 12 hours over five days, oldest first.
+        let cmds: Vec<_> = (0..10u32)
+            .map(|i| {
+                let mut c = make_cmd(i, "sess", Some(&format!("cmd{}", i)));
+                c.started_at = now_ms - (9 - i as u64) * 12 * hour - 1000;
+                c
+            })
+            .collect();
+        assert!(now_ms - cmds[0].started_at > 4 * 24 * hour);
+
+        let selected = RecentCommands::new(100).within_hours(24).select_commands(&cmds).await;
+        let lines: Vec<_> = selected.iter().map(|c| c.command_line.as_deref().unwrap()).collect();
+        assert_eq!(lines, vec!["cmd8", "cmd9"]);
+        assert!(selected.iter().all(|c| now_ms - c.started_at <= 24 * hour));
+
+        // Out-of-order input is filtered after selection instead.
+        let mut shuffled = cmds.clone();
+        shuffled.swap(0, 9);
+        let selected = RecentCommands::new(100).within_hours(24).select_commands(&shuffled).await;
+        assert_eq!(selected.len(), 2);
+
+        // The inner limit still applies within the window.
+        assert_eq!(RecentCommands::new(1).within_hours(48).select_commands(&cmds).await.len(), 1);
+    }
+
     #[tokio::test]
     async fn test_select_min_current_session_commands() {
         // Create commands from two sessions: sess-a (current) and sess-b
@@ -724,10 +1273,127 @@ mod tests {
         let detailed = vec![make_ctx("sess-a", "ls", 30000, "file1.txt")];
         let formatter = GroupedFormatter::new("sess-a", 60000, 10, 10);
         let result = formatter.format(&[], &detailed);
-        assert!(result.contains("--- term A [current] ---"));
+        assert!(result.contains("--- term A [current, 30s ago] ---"));
         assert!(result.contains("$ ls"));
     }
 
+    #[test]
+    fn test_grouped_failed_and_killed_labels() {
+        let mut failed = make_ctx("sess-a", "make", 30000, "error");
+        failed.exit_code = Some(2);
+        let mut killed = make_ctx("sess-a", "sleep 100", 31000, "");
+        killed.exit_code = Some(-9);
+        killed.exit_signal = Some(9);
+        let result = GroupedFormatter::new("sess-a", 60000, 10, 10).format(&[], &[failed, killed]);
+        assert!(result.contains("$ make  [FAILED: 2]"), "{result}");
+        assert!(result.contains("$ sleep 100  [KILLED: signal 9]"), "{result}");
+        assert!(!result.contains("[FAILED: -9]"), "{result}");
+    }
+
+    #[test]
+    fn test_grouped_command_durations() {
+        let mut build = make_ctx("sess-a", "cargo build", 10_000, "");
+        build.cwd = Some("/tmp".into());
+        build.ended_at = Some(55_000);
+        let mut pull = make_ctx("sess-a", "docker pull x", 56_000, "");
+        pull.ended_at = Some(179_000);
+        pull.exit_code = Some(1);
+        let quick = make_ctx("sess-a", "ls", 180_000, "");
+        let detailed = vec![build, pull, quick];
+
+        let result = GroupedFormatter::new("sess-a", 200_000, 10, 10).format(&[], &detailed);
+        assert!(result.contains("/tmp $ cargo build  (45s)"), "{result}");
+        assert!(result.contains("$ docker pull x  (2m3s)  [FAILED: 1]"), "{result}");
+        assert!(result.contains("$ ls\n") || result.ends_with("$ ls"), "{result}");
+
+        let result = GroupedFormatter::new("sess-a", 200_000, 10, 10)
+            .with_durations(false)
+            .format(&[], &detailed);
+        assert!(!result.contains("(45s)") && !result.contains("(2m3s)"), "{result}");
+    }
+
+    #[test]
+    fn test_grouped_environment_section() {
+        let detailed = vec![make_ctx("sess-a", "ls", 30000, "file1.txt")];
+        let formatter = GroupedFormatter::new("sess-a", 60000, 10, 10).with_env(vec![
+            ("PATH".into(), "/usr/bin:/bin".into()),
+            ("VIRTUAL_ENV".into(), String::new()),
+        ]);
+        let result = formatter.format(&[], &detailed);
+        assert!(result.starts_with("--- Environment ---\nPATH=/usr/bin:/bin\n\n"));
+        assert!(!result.contains("VIRTUAL_ENV"));
+
+        let plain = GroupedFormatter::new("sess-a", 60000, 10, 10).format(&[], &detailed);
+        assert!(!plain.contains("--- Environment ---"));
+    }
+
+    #[test]
+    fn test_grouped_python_env_header() {
+        let attrs: HashMap<String, String> = [
+            ("venv.path".to_string(), "/tmp/myenv".to_string()),
+            ("venv.python_version".to_string(), "3.11.4".to_string()),
+        ]
+        .into_iter()
+        .collect();
+        assert_eq!(python_env_label(&attrs).as_deref(), Some("venv: myenv/3.11.4"));
+
+        let detailed = vec![
+            make_ctx("sess-a", "ls", 30000, "file1.txt"),
+            make_ctx("sess-b", "pwd", 40000, "/home"),
+        ];
+        let result = GroupedFormatter::new("sess-a", 60000, 10, 10)
+            .with_header_tags(session_header_tags(&attrs))
+            .format(&[], &detailed);
+        assert!(result.contains("--- term A [current, venv: myenv/3.11.4, 30s ago] ---"), "{result}");
+        assert!(result.contains("--- term B [20s ago] ---"), "{result}");
+    }
+
+    #[test]
+    fn test_python_env_label() {
+        assert_eq!(python_env_label(&HashMap::new()), None);
+        let attrs: HashMap<String, String> = [
+            ("venv.path".to_string(), "/srv/app/.venv/".to_string()),
+            ("conda.env_name".to_string(), "base".to_string()),
+        ]
+        .into_iter()
+        .collect();
+        assert_eq!(python_env_label(&attrs).as_deref(), Some("venv: .venv, conda: base"));
+    }
+
+    #[test]
+    fn test_grouped_kube_header() {
+        let attrs: HashMap<String, String> = [
+            ("k8s.context".to_string(), "prod-ctx".to_string()),
+            ("k8s.cluster".to_string(), "mycluster".to_string()),
+            ("k8s.namespace".to_string(), "prod".to_string()),
+            ("conda.env_name".to_string(), "base".to_string()),
+        ]
+        .into_iter()
+        .collect();
+        let detailed = vec![make_ctx("sess-a", "kubectl get pods", 30000, "")];
+        let result = GroupedFormatter::new("sess-a", 60000, 10, 10)
+            .with_header_tags(session_header_tags(&attrs))
+            .format(&[], &detailed);
+        assert!(result.contains("--- term A [current, conda: base, k8s: mycluster/prod, 30s ago] ---"), "{result}");
+
+        let no_ns: HashMap<String, String> =
+            [("k8s.cluster".to_string(), "dev".to_string())].into_iter().collect();
+        assert_eq!(kube_label(&no_ns).as_deref(), Some("k8s: dev/default"));
+    }
+
+    #[test]
+    fn test_grouped_renders_tags() {
+        let mut tagged = make_ctx("sess-a", "make deploy", 30000, "done");
+        tagged.tags = vec!["deploy".into(), "prod".into()];
+        let mut hist = make_ctx("sess-a", "git pull", 20000, "");
+        hist.tags = vec!["deploy".into()];
+        let result = GroupedFormatter::new("sess-a", 60000, 10, 10)
+            .format(&[hist, make_ctx("sess-a", "ls", 21000, "")], &[tagged]);
+        assert!(result.contains("$ git pull [deploy]\n"));
+        assert!(result.contains("$ ls\n"));
+        assert!(result.contains("$ make deploy [deploy, prod]\ndone"));
+    }
+
     #[test]
     fn test_grouped_multi_session() {
         let detailed = vec![
@@ -737,8 +1403,8 @@ mod tests {
         let formatter = GroupedFormatter::new("sess-a", 30000, 10, 10);
         let result = formatter.format(&[], &detailed);
         // Current session should be last (closest to LLM prompt)
-        let pos_a = result.find("--- term A [current] ---").unwrap();
-        let pos_b = result.find("--- term B ---").unwrap();
+        let pos_a = result.find("--- term A [current, 2s ago] ---").unwrap();
+        let pos_b = result.find("--- term B [5s ago] ---").unwrap();
         assert!(pos_b < pos_a);
         assert!(result.contains("term A"));
         assert!(result.contains("term B"));
@@ -752,8 +1418,8 @@ mod tests {
         ];
         let formatter = GroupedFormatter::new("sess-a", 30000, 10, 10);
         let result = formatter.format(&[], &detailed);
-        assert!(result.contains("--- workstation (term A) [current] ---"));
-        assert!(result.contains("--- server01 (term B) ---"));
+        assert!(result.contains("--- workstation (term A) [current, 2s ago] ---"));
+        assert!(result.contains("--- server01 (term B) [5s ago] ---"));
     }
 
     #[test]
@@ -770,7 +1436,7 @@ mod tests {
         assert!(result.contains("$ mkdir foo"));
         // History should appear before detailed
         let pos_history = result.find("--- History ---").unwrap();
-        let pos_detailed = result.find("--- term A [current] ---").unwrap();
+        let pos_detailed = result.find("--- term A [current, 30s ago] ---").unwrap();
         assert!(pos_history < pos_detailed);
         // History section should not contain timestamps - extract just the history block
         let history_block = &result[..result.find("--- term A").unwrap()];
@@ -797,6 +1463,8 @@ mod tests {
                 ended_at: Some(1050),
                 output: "file.txt".into(),
                 exit_code: Some(0),
+                exit_signal: None,
+                tags: Vec::new(),
             },
             CommandContext {
                 session_id: "sess-a".into(),
@@ -807,6 +1475,8 @@ mod tests {
                 ended_at: Some(2050),
                 output: "".into(),
                 exit_code: Some(0),
+                exit_signal: None,
+                tags: Vec::new(),
             },
             // Most recent command with new cwd
             CommandContext {
@@ -818,6 +1488,8 @@ mod tests {
                 ended_at: Some(3050),
                 output: "/tmp".into(),
                 exit_code: Some(0),
+                exit_signal: None,
+                tags: Vec::new(),
             },
         ];
 
@@ -825,7 +1497,7 @@ mod tests {
         let result = formatter.format(&[], &detailed);
 
         // Should contain "Current path: /tmp" at the end of the session section
-        let session_end_marker = "--- term A [current] ---";
+        let session_end_marker = "--- term A [current";
         let session_start_pos = result.find(session_end_marker).unwrap();
         let session_section = &result[session_start_pos..];
 
@@ -856,6 +1528,24 @@ mod tests {
         assert!(pos_ls < pos_pwd);
     }
 
+    #[test]
+    fn test_max_output_bytes_per_command() {
+        let big = "A".repeat(100 * 1024);
+        let detailed = vec![
+            make_ctx("sess-a", "base64 blob", 28000, &big),
+            make_ctx("sess-a", "echo hi", 29000, "hi"),
+        ];
+        let grouped = GroupedFormatter::new("sess-a", 30000, 10, 10).with_max_output_bytes(1024);
+        let interleaved = InterleavedFormatter::new("sess-a", 30000, 10, 10).with_max_output_bytes(1024);
+        for result in [grouped.format(&[], &detailed), interleaved.format(&[], &detailed)] {
+            let marker = format!("\n[...truncated {} bytes]", 100 * 1024 - 1024);
+            assert!(result.contains(&format!("{}{}", "A".repeat(1024), marker)), "{}", &result[..200]);
+            assert!(!result.contains(&"A".repeat(1025)));
+            assert!(result.contains("$ echo hi\nhi\n---"));
+            assert!(result.len() < 2048);
+        }
+    }
+
     #[test]
     fn test_interleaved_marks_current() {
         let detailed = vec![
@@ -868,6 +1558,30 @@ mod tests {
         assert!(result.contains("term B $"));
     }
 
+    #[test]
+    fn test_interleaved_session_colors() {
+        let detailed = vec![
+            make_ctx("sess-a", "ls", 28000, "file1.txt"),
+            make_ctx("sess-b", "npm start", 25000, "Server running"),
+            make_ctx("sess-a", "pwd", 29000, ""),
+        ];
+        let result = InterleavedFormatter::new("sess-a", 30000, 10, 10)
+            .with_colors(true)
+            .format(&[], &detailed);
+        let blocks: Vec<&str> = result.split("\n\n").collect();
+        assert_eq!(blocks.len(), 3, "{result}");
+        assert!(blocks[0].starts_with(&format!("{}[5s ago] term B $ npm start\x1b[0m\n", SESSION_COLORS[1])), "{result}");
+        assert!(blocks[1].starts_with(&format!("{}[2s ago] term A* $ ls\x1b[0m\n", SESSION_COLORS[0])), "{result}");
+        assert_eq!(blocks[2], format!("{}[1s ago] term A* $ pwd\x1b[0m", SESSION_COLORS[0]));
+        // Output itself stays uncolored
+        assert!(result.contains("\x1b[0m\nfile1.txt\n"));
+
+        let plain = InterleavedFormatter::new("sess-a", 30000, 10, 10)
+            .with_colors(false)
+            .format(&[], &detailed);
+        assert!(!plain.contains('\x1b'), "{plain}");
+    }
+
     #[test]
     fn test_interleaved_empty() {
         let formatter = InterleavedFormatter::new("sess-a", 30000, 10, 10);
@@ -887,7 +1601,7 @@ mod tests {
         let result = crate::build_context(&strategy, &formatter, &cmds, &reader, &std::collections::HashMap::new(), 10, 512)
             .await
             .unwrap();
-        assert!(result.contains("--- term A [current] ---"));
+        assert!(result.contains("--- term A [current, 29s ago] ---"));
         assert!(result.contains("$ ls"));
         assert!(result.contains("file1.txt"));
     }
@@ -1012,9 +1726,9 @@ mod tests {
 
         // The key assertion: sess-a commands should have output (be in detailed section),
         // not just appear as bare "$ acmd0" lines in history.
-        // In the grouped format, detailed commands under "--- term A [current] ---"
+        // In the grouped format, detailed commands under "--- term A [current, ...] ---"
         // will have their output. Let's check the current session section has output.
-        let current_section_start = result.find("[current]").expect("should have current section");
+        let current_section_start = result.find("[current").expect("should have current section");
         let current_section = &result[current_section_start..];
         // All 5 commands should be in the current session's detailed section
         for i in 0..5 {
@@ -1037,6 +1751,8 @@ mod tests {
             ended_at: Some(1002),
             output: "total 0\nfile.txt".into(),
             exit_code: Some(0),
+            exit_signal: None,
+            tags: Vec::new(),
         };
         let commands = vec![context];
         let formatter = GroupedFormatter::new("sess1", 10000, 5, 5);
@@ -1057,6 +1773,8 @@ mod tests {
             ended_at: Some(1002),
             output: "".into(),
             exit_code: Some(0),
+            exit_signal: None,
+            tags: Vec::new(),
         };
         let commands = vec![context];
         let formatter = GroupedFormatter::new("sess1", 10000, 5, 5);
@@ -1064,9 +1782,10 @@ mod tests {
         // Should show "myhost:/home/user/project $ ls -la" (without time)
         assert!(formatted.contains("myhost:/home/user/project $ ls -la"),
                 "Formatted output should include hostname:cwd prefix: {}", formatted);
-        // Should NOT contain time prefix "[...]"
-        assert!(!formatted.contains("ago]"),
-                "Formatted output should not contain time: {}", formatted);
+        // Only the session header carries a time; command lines do not
+        let body: Vec<&str> = formatted.lines().filter(|l| !l.starts_with("---")).collect();
+        assert!(body.iter().all(|l| !l.contains("ago]")),
+                "Command lines should not contain time: {}", formatted);
     }
 
     #[test]
@@ -1082,6 +1801,8 @@ mod tests {
                 ended_at: Some(1002),
                 output: "".into(),
                 exit_code: Some(0),
+                exit_signal: None,
+                tags: Vec::new(),
             },
         ];
         let formatter = GroupedFormatter::new("sess1", 10000, 5, 5);
@@ -1106,6 +1827,8 @@ mod tests {
                 ended_at: Some(1002),
                 output: "file1.txt\nfile2.txt".into(),
                 exit_code: Some(0),
+                exit_signal: None,
+                tags: Vec::new(),
             },
         ];
         let formatter = GroupedFormatter::new("sess-a", 2000, 10, 10);
@@ -1198,6 +1921,8 @@ mod tests {
                 ended_at: Some(1050),
                 output: "".into(),
                 exit_code: Some(0),
+                exit_signal: None,
+                tags: Vec::new(),
             },
             CommandContext {
                 session_id: "sess-a".into(),
@@ -1208,6 +1933,8 @@ mod tests {
                 ended_at: Some(2050),
                 output: "/tmp".into(),
                 exit_code: Some(0),
+                exit_signal: None,
+                tags: Vec::new(),
             },
         ];
         let formatter = CompletionFormatter::new("sess-a", 10, 10);
@@ -1,7 +1,6 @@
-use crate::task_mgr::{ScheduledTask, TaskContext};
+use crate::task_mgr::{ScheduledTask, TaskContext, TaskJob};
 use anyhow::Result;
 use omnish_common::config::ConfigMap;
-use tokio_cron_scheduler::Job;
 
 pub struct AutoUpdateTask {
     config: ConfigMap,
@@ -36,12 +35,12 @@ impl ScheduledTask for AutoUpdateTask {
         ].into()
     }
 
-    fn create_job(&self, ctx: &TaskContext) -> Result<Job> {
+    fn create_job(&self, ctx: &TaskContext) -> Result<TaskJob> {
         let check_url = self.config.get_opt_string("check_url");
         let restart_signal = ctx.daemon.restart_signal.clone();
         let update_cache = ctx.daemon.update_cache.clone();
         let daemon_config = ctx.daemon_config.clone();
-        Ok(Job::new_async_tz(self.schedule(), chrono::Local, move |_uuid, _lock| {
+        TaskJob::new_local(self.schedule(), move || {
             let check_url = check_url.clone();
             let restart_signal = restart_signal.clone();
             let update_cache = update_cache.clone();
@@ -146,6 +145,6 @@ impl ScheduledTask for AutoUpdateTask {
                 tracing::info!("task [auto_update] upgrade complete, requesting daemon restart");
                 restart_signal.notify_one();
             })
-        })?)
+        })
     }
 }
@@ -3,8 +3,13 @@ use omnish_common::config::DaemonConfig;
 use std::collections::HashMap;
 use std::path::PathBuf;
 use std::sync::{Arc, RwLock};
+use std::time::Duration;
 use tokio::sync::watch;
 
+/// Editors often write daemon.toml in several steps (truncate, write,
+/// rename); wait this long after the first event before re-reading it.
+const RELOAD_DEBOUNCE: Duration = Duration::from_millis(500);
+
 #[derive(Hash, Eq, PartialEq, Clone, Copy, Debug)]
 pub enum ConfigSection {
     Tools,
@@ -51,8 +56,8 @@ impl ConfigWatcher {
         ConfigSection::Plugins,
         ConfigSection::Tasks,
         ConfigSection::Client,
+        ConfigSection::Context,
         // Add sections here as their diff + subscriber is implemented:
-        // ConfigSection::Context,
         // ConfigSection::Tools,
     ];
 
@@ -91,6 +96,8 @@ impl ConfigWatcher {
         tokio::spawn(async move {
             let mut rx = file_rx;
             while rx.changed().await.is_ok() {
+                tokio::time::sleep(RELOAD_DEBOUNCE).await;
+                rx.borrow_and_update();
                 if let Err(e) = cw.reload() {
                     tracing::warn!("config reload failed: {}", e);
                 }
@@ -118,6 +125,10 @@ impl ConfigWatcher {
         let mut current = self.current.write().unwrap();
         let new_arc = Arc::new(new_config.clone());
 
+        for key in restart_required_changes(&current, &new_config) {
+            tracing::warn!("daemon.toml: {} changed, restart omnishd to apply", key);
+        }
+
         // Diff each section and notify if changed
         for section in Self::WATCHED_SECTIONS {
             let changed = match section {
@@ -126,7 +137,10 @@ impl ConfigWatcher {
                     || current.proxy != new_config.proxy,
                 ConfigSection::Plugins => current.plugins != new_config.plugins,
                 ConfigSection::Tasks => current.tasks != new_config.tasks,
-                ConfigSection::Client => current.client != new_config.client,
+                // context.tracker is pushed to clients with [client]
+                ConfigSection::Client => current.client != new_config.client
+                    || current.context.tracker != new_config.context.tracker,
+                ConfigSection::Context => current.context != new_config.context,
                 // Future: add diff for other sections here
                 _ => false,
             };
@@ -143,6 +157,33 @@ impl ConfigWatcher {
     }
 }
 
+/// Settings in `new` that differ from `old` but are only read at startup.
+fn restart_required_changes(old: &DaemonConfig, new: &DaemonConfig) -> Vec<&'static str> {
+    let mut keys = Vec::new();
+    if old.listen_addr != new.listen_addr || old.listen_addrs != new.listen_addrs {
+        keys.push("listen_addr");
+    }
+    if old.socket_mode != new.socket_mode {
+        keys.push("socket_mode");
+    }
+    if old.tls != new.tls {
+        keys.push("[tls]");
+    }
+    if old.http != new.http {
+        keys.push("[http]");
+    }
+    if old.storage != new.storage {
+        keys.push("[storage]");
+    }
+    if old.search != new.search {
+        keys.push("[search]");
+    }
+    if old.context.redact_patterns != new.context.redact_patterns {
+        keys.push("context.redact_patterns");
+    }
+    keys
+}
+
 #[cfg(test)]
 mod tests {
     use super::*;
@@ -323,4 +364,53 @@ command_prefix = "/"
         let config = rx.borrow();
         assert_eq!(config.client.command_prefix, "/");
     }
+
+    #[test]
+    fn test_restart_required_changes() {
+        let old = DaemonConfig::default();
+        let mut new = old.clone();
+        new.context.completion.detailed_commands += 1;
+        assert!(restart_required_changes(&old, &new).is_empty());
+
+        new.listen_addr = "127.0.0.1:9999".into();
+        new.context.redact_patterns.push("secret".into());
+        assert_eq!(restart_required_changes(&old, &new), vec!["listen_addr", "context.redact_patterns"]);
+    }
+
+    #[tokio::test]
+    async fn test_context_change_reaches_session_mgr() {
+        use omnish_daemon::session_mgr::SessionManager;
+
+        let tmp = tempfile::tempdir().unwrap();
+        let config_path = tmp.path().join("daemon.toml");
+        std::fs::write(&config_path, "").unwrap();
+
+        let file_watcher = Arc::new(FileWatcher::new());
+        let fw = Arc::clone(&file_watcher);
+        tokio::spawn(async move { fw.run().await });
+
+        let initial = DaemonConfig::default();
+        let session_mgr = Arc::new(SessionManager::new(tmp.path().join("omnish"), initial.context.clone()));
+        let cw = ConfigWatcher::new(config_path.clone(), initial, &file_watcher);
+
+        // Same wiring as main.rs
+        let mut rx = cw.subscribe(ConfigSection::Context);
+        let sm = Arc::clone(&session_mgr);
+        tokio::spawn(async move {
+            while rx.changed().await.is_ok() {
+                let config = rx.borrow_and_update().clone();
+                sm.set_context_config(config.context.clone());
+            }
+        });
+
+        // Let the watcher task start before touching the file
+        tokio::time::sleep(Duration::from_millis(100)).await;
+        std::fs::write(&config_path, "[context.completion]\ndetailed_commands = 7\n").unwrap();
+
+        let deadline = std::time::Instant::now() + Duration::from_secs(10);
+        while session_mgr.context_config().completion.detailed_commands != 7 {
+            assert!(std::time::Instant::now() < deadline, "config change not applied");
+            tokio::time::sleep(Duration::from_millis(50)).await;
+        }
+    }
 }
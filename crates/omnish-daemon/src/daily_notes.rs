@@ -1,11 +1,10 @@
 use crate::conversation_mgr::ConversationManager;
 use crate::session_mgr::SessionManager;
-use crate::task_mgr::{ScheduledTask, TaskContext};
+use crate::task_mgr::{ScheduledTask, TaskContext, TaskJob};
 use chrono::Local;
 use omnish_common::config::ConfigMap;
 use omnish_llm::{backend::{LlmBackend, LlmRequest, TriggerType, UseCase}, template};
 use std::path::{Path, PathBuf};
-use tokio_cron_scheduler::Job;
 
 pub struct DailyNotesTask {
     config: ConfigMap,
@@ -39,13 +38,13 @@ impl ScheduledTask for DailyNotesTask {
         ].into()
     }
 
-    fn create_job(&self, ctx: &TaskContext) -> anyhow::Result<Job> {
+    fn create_job(&self, ctx: &TaskContext) -> anyhow::Result<TaskJob> {
         let mgr = ctx.session_mgr.clone();
         let conv_mgr = ctx.conv_mgr.clone();
         let llm_holder = ctx.llm_backend.clone();
         let notes_dir = ctx.daemon.omnish_dir.join("notes");
         let daemon_config = ctx.daemon_config.clone();
-        Ok(Job::new_async_tz(self.schedule(), Local, move |_uuid, _lock| {
+        TaskJob::new_local(self.schedule(), move || {
             let mgr = mgr.clone();
             let conv_mgr = conv_mgr.clone();
             let llm = llm_holder.read().unwrap().get_backend(UseCase::Analysis);
@@ -58,7 +57,7 @@ impl ScheduledTask for DailyNotesTask {
                 }
                 tracing::debug!("task [daily_notes] finished");
             })
-        })?)
+        })
     }
 }
 
@@ -1,8 +1,7 @@
-use crate::task_mgr::{ScheduledTask, TaskContext};
+use crate::task_mgr::{ScheduledTask, TaskContext, TaskJob};
 use anyhow::Result;
 use omnish_common::config::ConfigMap;
 use std::time::Duration;
-use tokio_cron_scheduler::Job;
 
 /// Ends sessions whose transport connection dropped without a SessionEnd and
 /// whose disconnect grace period has elapsed. Pairs with the on_disconnect
@@ -58,11 +57,11 @@ impl ScheduledTask for DisconnectSweepTask {
         .into()
     }
 
-    fn create_job(&self, ctx: &TaskContext) -> Result<Job> {
+    fn create_job(&self, ctx: &TaskContext) -> Result<TaskJob> {
         let mgr = ctx.session_mgr.clone();
         let minutes = self.config.get_u64("grace_minutes", DEFAULT_GRACE_MINUTES);
         let grace = Duration::from_secs(minutes * 60);
-        Ok(Job::new_async(self.schedule(), move |_uuid, _lock| {
+        TaskJob::new(self.schedule(), move || {
             let mgr = mgr.clone();
             Box::pin(async move {
                 tracing::debug!("task [disconnect_sweep] started (grace={}m)", minutes);
@@ -80,7 +79,7 @@ impl ScheduledTask for DisconnectSweepTask {
                 // which no other task needs.
                 tracing::debug!("task [disconnect_sweep] finished");
             })
-        })?)
+        })
     }
 }
 
@@ -0,0 +1,158 @@
+use anyhow::Result;
+use omnish_context::StreamReader;
+use omnish_store::command::CommandRecord;
+
+/// Renders a session's commands as a shareable document.
+pub struct SessionExporter;
+
+impl SessionExporter {
+    /// Render `commands` as CommonMark: one section per command with the
+    /// command line as heading, a metadata table (exit code, start time,
+    /// cwd), and the ANSI-stripped output in a fenced code block.
+    pub fn to_markdown(commands: &[CommandRecord], reader: &dyn StreamReader) -> Result<String> {
+        let mut md = String::new();
+        match commands.first() {
+            Some(first) => md.push_str(&format!("# omnish session `{}`\n", first.session_id)),
+            None => {
+                md.push_str("# omnish session\n\n_No commands recorded._\n");
+                return Ok(md);
+            }
+        }
+
+        for (i, cmd) in commands.iter().enumerate() {
+            let line = cmd.command_line.as_deref().unwrap_or("(unknown command)");
+            md.push_str(&format!("\n## {}. {}\n\n", i + 1, code_span(line)));
+
+            md.push_str("| Exit code | Started | Cwd |\n");
+            md.push_str("| --- | --- | --- |\n");
+            let exit = cmd.exit_code.map(|c| c.to_string()).unwrap_or_else(|| "-".into());
+            let started = chrono::DateTime::from_timestamp_millis(cmd.started_at as i64)
+                .map(|t| t.format("%Y-%m-%d %H:%M:%S UTC").to_string())
+                .unwrap_or_else(|| "-".into());
+            let cwd = cmd.cwd.as_deref().map(code_span).unwrap_or_else(|| "-".into());
+            md.push_str(&format!(
+                "| {} | {} | {} |\n\n",
+                table_cell(&exit),
+                table_cell(&started),
+                table_cell(&cwd)
+            ));
+
+            let output = read_output(cmd, reader);
+            if output.is_empty() {
+                md.push_str("_No output._\n");
+            } else {
+                let fence = "`".repeat(longest_backtick_run(&output).max(2) + 1);
+                md.push_str(&format!("{fence}text\n{output}\n{fence}\n"));
+            }
+        }
+        Ok(md)
+    }
+}
+
+/// Output text of `cmd` without ANSI escapes and the echoed command line.
+fn read_output(cmd: &CommandRecord, reader: &dyn StreamReader) -> String {
+    if cmd.stream_length == 0 {
+        return String::new();
+    }
+    let entries = match reader.read_command_output(cmd.stream_offset, cmd.stream_length) {
+        Ok(entries) => entries,
+        Err(e) => return format!("(failed to read output: {})", e),
+    };
+    let raw: Vec<u8> = entries
+        .iter()
+        .filter(|e| e.direction == 1)
+        .flat_map(|e| e.data.iter().copied())
+        .collect();
+    let text = omnish_context::strip_ansi(&raw).replace("\r\n", "\n").replace('\r', "");
+    // Skip first line (echoed command)
+    let text = match text.find('\n') {
+        Some(pos) => &text[pos + 1..],
+        None => "",
+    };
+    text.trim().to_string()
+}
+
+fn longest_backtick_run(s: &str) -> usize {
+    s.split(|c| c != '`').map(str::len).max().unwrap_or(0)
+}
+
+/// Inline code span that survives backticks inside `s`.
+fn code_span(s: &str) -> String {
+    let ticks = "`".repeat(longest_backtick_run(s) + 1);
+    if s.starts_with('`') || s.ends_with('`') {
+        format!("{ticks} {s} {ticks}")
+    } else {
+        format!("{ticks}{s}{ticks}")
+    }
+}
+
+fn table_cell(s: &str) -> String {
+    s.replace('|', "\\|").replace('\n', " ")
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use omnish_store::stream::StreamEntry;
+
+    struct MapReader(Vec<(u64, Vec<u8>)>);
+
+    impl StreamReader for MapReader {
+        fn read_command_output(&self, offset: u64, _length: u64) -> Result<Vec<StreamEntry>> {
+            Ok(self
+                .0
+                .iter()
+                .filter(|(o, _)| *o == offset)
+                .map(|(_, data)| StreamEntry::new(0, 1, data.clone()))
+                .collect())
+        }
+    }
+
+    fn rec(seq: u64, line: &str, stream_length: u64, exit_code: Option<i32>) -> CommandRecord {
+        CommandRecord {
+            command_id: format!("c{}", seq),
+            session_id: "sess1".into(),
+            command_line: Some(line.into()),
+            cwd: Some("/tmp".into()),
+            started_at: 1_700_000_000_000 + seq,
+            ended_at: None,
+            output_summary: String::new(),
+            stream_offset: seq,
+            stream_length,
+            exit_code,
+            exit_signal: None,
+            checksum: None,
+            tags: Vec::new(),
+        }
+    }
+
+    #[test]
+    fn test_to_markdown_renders_sections() {
+        let reader = MapReader(vec![(0, b"ls\r\n\x1b[1mfoo\x1b[0m\r\nbar\r\n".to_vec())]);
+        let md = SessionExporter::to_markdown(&[rec(0, "ls", 10, Some(0))], &reader).unwrap();
+        assert!(md.starts_with("# omnish session `sess1`\n"));
+        assert!(md.contains("## 1. `ls`\n"));
+        assert!(md.contains("| 0 | 2023-11-14 22:13:20 UTC | `/tmp` |\n"));
+        assert!(md.contains("```text\nfoo\nbar\n```\n"));
+    }
+
+    #[test]
+    fn test_to_markdown_without_output() {
+        let reader = MapReader(vec![]);
+        let md = SessionExporter::to_markdown(&[rec(0, "true", 0, None)], &reader).unwrap();
+        assert!(md.contains("| - |"));
+        assert!(md.contains("_No output._\n"));
+        assert!(!md.contains("```"));
+
+        let empty = SessionExporter::to_markdown(&[], &reader).unwrap();
+        assert!(empty.contains("_No commands recorded._"));
+    }
+
+    #[test]
+    fn test_to_markdown_escapes_backticks_and_pipes() {
+        let reader = MapReader(vec![(0, b"cmd\n```\ninside\n".to_vec())]);
+        let md = SessionExporter::to_markdown(&[rec(0, "echo `a|b`", 5, Some(1))], &reader).unwrap();
+        assert!(md.contains("## 1. `` echo `a|b` ``\n"));
+        assert!(md.contains("````text\n```\ninside\n````\n"));
+    }
+}
@@ -0,0 +1,159 @@
+use crate::session_mgr::SessionManager;
+use anyhow::Result;
+use omnish_store::command::CommandRecord;
+use std::path::Path;
+
+/// Session id of the synthetic session holding imported shell history.
+pub const HISTORY_SESSION_ID: &str = "__history__";
+
+/// Marker written after the first import so it never runs twice.
+const IMPORTED_MARKER: &str = ".history_imported";
+
+/// Seeds LLM context with pre-omnish shell history.
+pub struct HistoryImporter;
+
+impl HistoryImporter {
+    /// Parse a bash history file into synthetic command records.
+    ///
+    /// Supports both plain files (one command per line) and files written
+    /// with `HISTTIMEFORMAT` set, where each command is preceded by a
+    /// `#<epoch-seconds>` line. Plain entries are timestamped backwards from
+    /// the file's modification time so their order is preserved.
+    pub fn import_bash_history(path: &Path) -> Result<Vec<CommandRecord>> {
+        let bytes = std::fs::read(path)?;
+        let content = String::from_utf8_lossy(&bytes);
+        let mtime_ms = std::fs::metadata(path)?
+            .modified()
+            .ok()
+            .and_then(|t| t.duration_since(std::time::UNIX_EPOCH).ok())
+            .map(|d| d.as_millis() as u64)
+            .unwrap_or(0);
+        Ok(parse_bash_history(&content, mtime_ms))
+    }
+
+    /// Import `~/.bash_history` into [`HISTORY_SESSION_ID`] when the daemon
+    /// starts with no sessions for the first time. Keeps the most recent
+    /// `max_lines` commands. Returns the number of imported commands.
+    pub async fn import_on_first_start(
+        mgr: &SessionManager,
+        omnish_dir: &Path,
+        history_path: &Path,
+        max_lines: usize,
+    ) -> Result<usize> {
+        let marker = omnish_dir.join(IMPORTED_MARKER);
+        if marker.exists() || max_lines == 0 || !history_path.exists() {
+            return Ok(0);
+        }
+        let mut records = Self::import_bash_history(history_path)?;
+        if records.len() > max_lines {
+            records.drain(..records.len() - max_lines);
+        }
+        let count = records.len();
+        if count > 0 {
+            let mut attrs = std::collections::HashMap::new();
+            attrs.insert("imported_from".to_string(), history_path.display().to_string());
+            mgr.register(HISTORY_SESSION_ID, None, attrs, None).await?;
+            for record in records {
+                mgr.receive_command(HISTORY_SESSION_ID, record).await?;
+            }
+        }
+        std::fs::write(&marker, format!("{}\n", count))?;
+        Ok(count)
+    }
+}
+
+/// Parse history `content`; `fallback_end_ms` anchors entries that carry no
+/// timestamp. Consecutive duplicates are collapsed.
+fn parse_bash_history(content: &str, fallback_end_ms: u64) -> Vec<CommandRecord> {
+    let mut entries: Vec<(Option<u64>, String)> = Vec::new();
+    let mut pending_ts: Option<u64> = None;
+    for line in content.lines() {
+        if let Some(ts) = line.strip_prefix('#').and_then(|s| s.trim().parse::<u64>().ok()) {
+            pending_ts = Some(ts * 1000);
+            continue;
+        }
+        let cmd = line.trim();
+        if cmd.is_empty() {
+            continue;
+        }
+        if entries.last().is_some_and(|(_, last)| last == cmd) {
+            pending_ts = None;
+            continue;
+        }
+        entries.push((pending_ts.take(), cmd.to_string()));
+    }
+
+    let n = entries.len() as u64;
+    entries
+        .into_iter()
+        .enumerate()
+        .map(|(i, (ts, cmd))| {
+            let started_at = ts.unwrap_or_else(|| fallback_end_ms.saturating_sub(n - i as u64));
+            CommandRecord {
+                command_id: format!("hist-{}", i),
+                session_id: HISTORY_SESSION_ID.to_string(),
+                command_line: Some(cmd),
+                cwd: None,
+                started_at,
+                ended_at: Some(started_at),
+                output_summary: String::new(),
+                stream_offset: 0,
+                stream_length: 0,
+                exit_code: None,
+                exit_signal: None,
+                checksum: None,
+                tags: Vec::new(),
+            }
+        })
+        .collect()
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn lines(records: &[CommandRecord]) -> Vec<&str> {
+        records.iter().map(|r| r.command_line.as_deref().unwrap()).collect()
+    }
+
+    #[test]
+    fn test_parse_plain_history() {
+        let records = parse_bash_history("ls\ncd /tmp\ncd /tmp\n\nmake\nls\n", 10_000);
+        assert_eq!(lines(&records), vec!["ls", "cd /tmp", "make", "ls"]);
+        let times: Vec<u64> = records.iter().map(|r| r.started_at).collect();
+        assert_eq!(times, vec![9_996, 9_997, 9_998, 9_999]);
+        assert!(records.iter().all(|r| r.stream_length == 0 && r.session_id == HISTORY_SESSION_ID));
+    }
+
+    #[test]
+    fn test_parse_timestamped_history() {
+        let content = "#1700000000\ngit status\n#1700000005\ngit status\n#1700000010\ngit push\n";
+        let records = parse_bash_history(content, 0);
+        assert_eq!(lines(&records), vec!["git status", "git push"]);
+        assert_eq!(records[0].started_at, 1_700_000_000_000);
+        assert_eq!(records[1].started_at, 1_700_000_010_000);
+    }
+
+    #[test]
+    fn test_comment_lines_are_commands_when_not_timestamps() {
+        let records = parse_bash_history("#1700000000\n# a note\n", 0);
+        assert_eq!(lines(&records), vec!["# a note"]);
+        assert_eq!(records[0].started_at, 1_700_000_000_000);
+    }
+
+    #[tokio::test]
+    async fn test_import_on_first_start_caps_and_runs_once() {
+        let dir = tempfile::tempdir().unwrap();
+        let hist = dir.path().join("bash_history");
+        std::fs::write(&hist, "a\nb\nc\nd\n").unwrap();
+        let mgr = SessionManager::new(dir.path().to_path_buf(), Default::default());
+
+        let n = HistoryImporter::import_on_first_start(&mgr, dir.path(), &hist, 3).await.unwrap();
+        assert_eq!(n, 3);
+        let commands = mgr.get_commands(HISTORY_SESSION_ID).await.unwrap();
+        assert_eq!(lines(&commands), vec!["b", "c", "d"]);
+
+        let n = HistoryImporter::import_on_first_start(&mgr, dir.path(), &hist, 3).await.unwrap();
+        assert_eq!(n, 0);
+    }
+}
@@ -1,12 +1,42 @@
 use crate::conversation_mgr::ConversationManager;
 use crate::session_mgr::SessionManager;
-use crate::task_mgr::{ScheduledTask, TaskContext};
+use crate::task_mgr::{ScheduledTask, TaskContext, TaskJob};
+use crate::webhook::{SummaryPayload, WebhookDelivery};
 use chrono::Local;
 use omnish_common::config::ConfigMap;
 use omnish_llm::{backend::{LlmBackend, LlmRequest, TriggerType, UseCase}, template};
-use std::path::Path;
+use serde::Deserialize;
+use std::path::{Path, PathBuf};
 use std::time::{SystemTime, UNIX_EPOCH};
-use tokio_cron_scheduler::Job;
+
+/// Where a finished hourly summary is sent, in addition to the notes file
+/// that daily notes are built from. Configured as
+/// `delivery = [{ type = "file", path = "..." }, { type = "webhook", url = "..." }]`.
+#[derive(Debug, Clone, PartialEq, Deserialize)]
+#[serde(tag = "type", rename_all = "lowercase")]
+pub enum DeliveryTarget {
+    /// Overwrite `path` with the latest summary markdown.
+    File { path: PathBuf },
+    /// POST the summary as JSON; `auth_header` is the `Authorization` value.
+    Webhook {
+        url: String,
+        #[serde(default)]
+        auth_header: Option<String>,
+    },
+    /// Print the summary markdown to the daemon's stdout.
+    Stdout,
+}
+
+impl DeliveryTarget {
+    /// Short description for log lines.
+    fn describe(&self) -> String {
+        match self {
+            DeliveryTarget::File { path } => format!("file {}", path.display()),
+            DeliveryTarget::Webhook { url, .. } => format!("webhook {}", url),
+            DeliveryTarget::Stdout => "stdout".to_string(),
+        }
+    }
+}
 
 pub struct HourlySummaryTask {
     config: ConfigMap,
@@ -18,6 +48,21 @@ impl HourlySummaryTask {
         let schedule = crate::task_mgr::normalize_cron(&config.get_string("schedule", ""));
         Self { config, schedule }
     }
+
+    /// Targets from `delivery`; falls back to the legacy `webhook_url` key
+    /// when `delivery` is not set.
+    fn delivery_targets(&self) -> anyhow::Result<Vec<DeliveryTarget>> {
+        if let Some(value) = self.config.get("delivery") {
+            return serde_json::from_value(value.clone())
+                .map_err(|e| anyhow::anyhow!("invalid hourly_summary.delivery: {}", e));
+        }
+        Ok(self
+            .config
+            .get_opt_string("webhook_url")
+            .map(|url| DeliveryTarget::Webhook { url, auth_header: None })
+            .into_iter()
+            .collect())
+    }
 }
 
 impl ScheduledTask for HourlySummaryTask {
@@ -40,26 +85,28 @@ impl ScheduledTask for HourlySummaryTask {
         ].into()
     }
 
-    fn create_job(&self, ctx: &TaskContext) -> anyhow::Result<Job> {
+    fn create_job(&self, ctx: &TaskContext) -> anyhow::Result<TaskJob> {
         let mgr = ctx.session_mgr.clone();
         let conv_mgr = ctx.conv_mgr.clone();
         let llm_holder = ctx.llm_backend.clone();
         let notes_dir = ctx.daemon.omnish_dir.join("notes");
         let daemon_config = ctx.daemon_config.clone();
-        Ok(Job::new_async(self.schedule(), move |_uuid, _lock| {
+        let delivery = self.delivery_targets()?;
+        TaskJob::new(self.schedule(), move || {
             let mgr = mgr.clone();
             let conv_mgr = conv_mgr.clone();
             let llm = llm_holder.read().unwrap().get_backend(UseCase::Analysis);
             let dir = notes_dir.clone();
             let language = daemon_config.read().unwrap().client.language.clone();
+            let delivery = delivery.clone();
             Box::pin(async move {
                 tracing::debug!("task [hourly_summary] started");
-                if let Err(e) = generate_hourly_summary(&mgr, &conv_mgr, Some(llm.as_ref()), &dir, &language).await {
+                if let Err(e) = generate_hourly_summary(&mgr, &conv_mgr, Some(llm.as_ref()), &dir, &language, &delivery).await {
                     tracing::warn!("task [hourly_summary] failed: {}", e);
                 }
                 tracing::debug!("task [hourly_summary] finished");
             })
-        })?)
+        })
     }
 }
 
@@ -162,6 +209,7 @@ async fn generate_hourly_summary(
     llm_backend: Option<&dyn LlmBackend>,
     summaries_dir: &Path,
     language: &str,
+    delivery: &[DeliveryTarget],
 ) -> anyhow::Result<()> {
     let now_ms = SystemTime::now()
         .duration_since(UNIX_EPOCH)
@@ -253,9 +301,37 @@ async fn generate_hourly_summary(
     std::fs::write(&file_path, &md)?;
     tracing::info!("hourly summary: wrote {}", file_path.display());
 
+    let payload = SummaryPayload {
+        session_id: None,
+        summary,
+        timestamp: now_ms,
+    };
+    deliver(delivery, &md, &payload).await;
+
     Ok(())
 }
 
+/// Send the summary to every target. A failing target is logged and the
+/// remaining ones are still tried.
+async fn deliver(targets: &[DeliveryTarget], md: &str, payload: &SummaryPayload) {
+    for target in targets {
+        let result = match target {
+            DeliveryTarget::File { path } => std::fs::write(path, md).map_err(anyhow::Error::from),
+            DeliveryTarget::Webhook { url, auth_header } => {
+                WebhookDelivery::send(url, auth_header.as_deref(), payload).await
+            }
+            DeliveryTarget::Stdout => {
+                println!("{}", md);
+                Ok(())
+            }
+        };
+        match result {
+            Ok(()) => tracing::debug!("hourly summary: delivered to {}", target.describe()),
+            Err(e) => tracing::warn!("hourly summary: delivery to {} failed: {}", target.describe(), e),
+        }
+    }
+}
+
 #[cfg(test)]
 mod tests {
     use super::*;
@@ -268,10 +344,109 @@ mod tests {
         let summaries_dir = dir.path().join("summaries");
 
         // No commands or conversations -> should skip without error
-        generate_hourly_summary(&mgr, &conv_mgr, None, &summaries_dir, "en").await.unwrap();
+        generate_hourly_summary(&mgr, &conv_mgr, None, &summaries_dir, "en", &[]).await.unwrap();
         assert!(!summaries_dir.exists());
     }
 
     // Note: test with real command output requires proper stream file setup,
     // which is complex. The empty commands test verifies the skip logic.
+
+    #[tokio::test]
+    async fn test_deliver_to_file_and_webhook() {
+        use wiremock::matchers::{body_json, header, method, path};
+        use wiremock::{Mock, MockServer, ResponseTemplate};
+
+        let server = MockServer::start().await;
+        Mock::given(method("POST"))
+            .and(path("/hook"))
+            .and(header("authorization", "Bearer s3cret"))
+            .and(body_json(serde_json::json!({
+                "session_id": null,
+                "summary": "Fixed the build",
+                "timestamp": 1_700_000_000_000u64,
+            })))
+            .respond_with(ResponseTemplate::new(200))
+            .expect(1)
+            .mount(&server)
+            .await;
+
+        let dir = tempfile::tempdir().unwrap();
+        let file_path = dir.path().join("latest.md");
+        let targets = vec![
+            // Parent is missing: fails, but must not stop the others
+            DeliveryTarget::File { path: dir.path().join("missing/latest.md") },
+            DeliveryTarget::File { path: file_path.clone() },
+            DeliveryTarget::Webhook {
+                url: format!("{}/hook", server.uri()),
+                auth_header: Some("Bearer s3cret".into()),
+            },
+        ];
+        let payload = SummaryPayload {
+            session_id: None,
+            summary: "Fixed the build".into(),
+            timestamp: 1_700_000_000_000,
+        };
+
+        deliver(&targets, "# Summary\n\nFixed the build", &payload).await;
+
+        assert_eq!(std::fs::read_to_string(&file_path).unwrap(), "# Summary\n\nFixed the build");
+        server.verify().await;
+    }
+
+    #[tokio::test]
+    async fn test_webhook_without_auth_header_sends_none() {
+        use wiremock::matchers::method;
+        use wiremock::{Mock, MockServer, ResponseTemplate};
+
+        let server = MockServer::start().await;
+        Mock::given(method("POST"))
+            .respond_with(ResponseTemplate::new(200))
+            .expect(1)
+            .mount(&server)
+            .await;
+        let targets = vec![DeliveryTarget::Webhook { url: server.uri(), auth_header: None }];
+        let payload = SummaryPayload {
+            session_id: None,
+            summary: "Fixed the build".into(),
+            timestamp: 1_700_000_000_000,
+        };
+
+        deliver(&targets, "Fixed the build", &payload).await;
+
+        let requests = server.received_requests().await.unwrap();
+        assert_eq!(requests.len(), 1);
+        assert!(!requests[0].headers.contains_key("authorization"));
+    }
+
+    #[test]
+    fn test_delivery_targets_from_config() {
+        let config: ConfigMap = toml::from_str(
+            r#"
+            delivery = [
+                { type = "file", path = "/tmp/hourly.md" },
+                { type = "webhook", url = "https://hooks.example.com/a", auth_header = "Bearer x" },
+                { type = "stdout" },
+            ]
+            "#,
+        )
+        .unwrap();
+        assert_eq!(
+            HourlySummaryTask::new(config).delivery_targets().unwrap(),
+            vec![
+                DeliveryTarget::File { path: "/tmp/hourly.md".into() },
+                DeliveryTarget::Webhook {
+                    url: "https://hooks.example.com/a".into(),
+                    auth_header: Some("Bearer x".into()),
+                },
+                DeliveryTarget::Stdout,
+            ]
+        );
+
+        // Legacy key still works when `delivery` is absent
+        let config: ConfigMap = toml::from_str(r#"webhook_url = "https://hooks.example.com/b""#).unwrap();
+        assert_eq!(
+            HourlySummaryTask::new(config).delivery_targets().unwrap(),
+            vec![DeliveryTarget::Webhook { url: "https://hooks.example.com/b".into(), auth_header: None }]
+        );
+    }
 }
@@ -1,8 +1,7 @@
-use crate::task_mgr::{ScheduledTask, TaskContext};
+use crate::task_mgr::{ScheduledTask, TaskContext, TaskJob};
 use anyhow::Result;
 use omnish_common::config::ConfigMap;
 use std::time::Duration;
-use tokio_cron_scheduler::Job;
 
 /// Merged housekeeping task that runs hourly and applies a user-configurable
 /// retention period to both in-memory session eviction and on-disk directory
@@ -56,12 +55,12 @@ impl ScheduledTask for HouseKeepingTask {
         .into()
     }
 
-    fn create_job(&self, ctx: &TaskContext) -> Result<Job> {
+    fn create_job(&self, ctx: &TaskContext) -> Result<TaskJob> {
         let mgr = ctx.session_mgr.clone();
         let period = self.config.get_string("period", "2 weeks");
         let hours = period_to_hours(&period);
         let max_age = Duration::from_secs(hours * 3600);
-        Ok(Job::new_async(self.schedule(), move |_uuid, _lock| {
+        TaskJob::new(self.schedule(), move || {
             let mgr = mgr.clone();
             Box::pin(async move {
                 tracing::debug!("task [house_keeping] started (period={}h)", hours);
@@ -76,7 +75,7 @@ impl ScheduledTask for HouseKeepingTask {
                 }
                 tracing::debug!("task [house_keeping] finished");
             })
-        })?)
+        })
     }
 }
 
@@ -0,0 +1,283 @@
+//! Optional HTTP/1.1 API (`[http]` in daemon.toml) for integrations that
+//! cannot speak the Unix socket protocol:
+//!
+//! - `GET /sessions` - metadata of every loaded session, as JSON
+//! - `GET /sessions/{id}/context` - the session's LLM context, as text
+//! - `POST /query` with `{"session_id", "query"}` - `{"response"}`
+//!
+//! Every request must carry `Authorization: Bearer <token>`; with an empty
+//! token every request is refused.
+
+use std::convert::Infallible;
+use std::sync::Arc;
+use std::time::Duration;
+
+use anyhow::Result;
+use http_body_util::{BodyExt, Full, LengthLimitError, Limited};
+use hyper::body::{Bytes, Incoming};
+use hyper::header::{AUTHORIZATION, CONTENT_TYPE};
+use hyper::server::conn::http1;
+use hyper::service::service_fn;
+use hyper::{Method, Request, Response, StatusCode};
+use hyper_util::rt::TokioIo;
+use omnish_daemon::session_mgr::SessionManager;
+use omnish_llm::factory::SharedLlmBackend;
+use omnish_protocol::message::RequestScope;
+use serde::Deserialize;
+use tokio::net::TcpListener;
+use tokio::sync::mpsc;
+
+/// Largest `POST /query` body read; larger ones get 413.
+const MAX_BODY_BYTES: usize = 1024 * 1024;
+
+/// Bounds of the pause after a failed accept (e.g. out of file
+/// descriptors), doubled on each consecutive failure.
+const ACCEPT_BACKOFF_MIN: Duration = Duration::from_millis(10);
+const ACCEPT_BACKOFF_MAX: Duration = Duration::from_secs(1);
+
+#[derive(Deserialize)]
+struct QueryBody {
+    session_id: String,
+    query: String,
+}
+
+pub struct DaemonHttpServer {
+    mgr: Arc<SessionManager>,
+    llm_backend: SharedLlmBackend,
+    auth_token: String,
+}
+
+impl DaemonHttpServer {
+    pub fn new(mgr: Arc<SessionManager>, llm_backend: SharedLlmBackend, auth_token: String) -> Self {
+        Self { mgr, llm_backend, auth_token }
+    }
+
+    /// Listen on `127.0.0.1:port` (0 picks a free port).
+    pub async fn bind(port: u16) -> Result<TcpListener> {
+        Ok(TcpListener::bind(("127.0.0.1", port)).await?)
+    }
+
+    /// Accept connections, each served on its own task. Accept errors are
+    /// retried after a growing pause so a persistent one doesn't spin.
+    pub async fn serve(self: Arc<Self>, listener: TcpListener) {
+        let mut backoff = ACCEPT_BACKOFF_MIN;
+        loop {
+            let stream = match listener.accept().await {
+                Ok((stream, _)) => {
+                    backoff = ACCEPT_BACKOFF_MIN;
+                    stream
+                }
+                Err(e) => {
+                    tracing::warn!("http accept failed, retrying in {:?}: {}", backoff, e);
+                    tokio::time::sleep(backoff).await;
+                    backoff = (backoff * 2).min(ACCEPT_BACKOFF_MAX);
+                    continue;
+                }
+            };
+            let server = Arc::clone(&self);
+            tokio::spawn(async move {
+                let service = service_fn(move |req| {
+                    let server = Arc::clone(&server);
+                    async move { Ok::<_, Infallible>(server.handle(req).await) }
+                });
+                if let Err(e) = http1::Builder::new()
+                    .serve_connection(TokioIo::new(stream), service)
+                    .await
+                {
+                    tracing::debug!("http connection error: {}", e);
+                }
+            });
+        }
+    }
+
+    async fn handle(&self, req: Request<Incoming>) -> Response<Full<Bytes>> {
+        if !self.authorized(&req) {
+            return error(StatusCode::UNAUTHORIZED, "missing or invalid bearer token");
+        }
+        let path = req.uri().path().to_string();
+        let segments: Vec<&str> = path.trim_matches('/').split('/').collect();
+        match (req.method(), segments.as_slice()) {
+            (&Method::GET, ["sessions"]) => json(StatusCode::OK, &self.mgr.list_sessions().await),
+            (&Method::GET, ["sessions", id, "context"]) => self.context(id).await,
+            (&Method::POST, ["query"]) => self.query(req).await,
+            _ => error(StatusCode::NOT_FOUND, "not found"),
+        }
+    }
+
+    fn authorized(&self, req: &Request<Incoming>) -> bool {
+        req.headers()
+            .get(AUTHORIZATION)
+            .and_then(|v| v.to_str().ok())
+            .and_then(|v| v.strip_prefix("Bearer "))
+            .is_some_and(|token| !self.auth_token.is_empty() && token == self.auth_token)
+    }
+
+    async fn context(&self, session_id: &str) -> Response<Full<Bytes>> {
+        if !self.mgr.list_sessions().await.iter().any(|m| m.session_id == session_id) {
+            return error(StatusCode::NOT_FOUND, &format!("session not found: {}", session_id));
+        }
+        match self.mgr.get_session_context(session_id).await {
+            Ok(context) => Response::builder()
+                .header(CONTENT_TYPE, "text/plain; charset=utf-8")
+                .body(Full::new(Bytes::from(context)))
+                .unwrap(),
+            Err(e) => error(StatusCode::INTERNAL_SERVER_ERROR, &e.to_string()),
+        }
+    }
+
+    async fn query(&self, req: Request<Incoming>) -> Response<Full<Bytes>> {
+        let body = match Limited::new(req.into_body(), MAX_BODY_BYTES).collect().await {
+            Ok(body) => body.to_bytes(),
+            Err(e) if e.is::<LengthLimitError>() => return error(StatusCode::PAYLOAD_TOO_LARGE, &e.to_string()),
+            Err(e) => return error(StatusCode::BAD_REQUEST, &e.to_string()),
+        };
+        let body: QueryBody = match serde_json::from_slice(&body) {
+            Ok(body) => body,
+            Err(e) => return error(StatusCode::BAD_REQUEST, &format!("invalid body: {}", e)),
+        };
+        let request = omnish_protocol::message::Request {
+            request_id: uuid::Uuid::new_v4().to_string(),
+            session_id: body.session_id,
+            query: body.query,
+            scope: RequestScope::CurrentSession,
+            model_override: None,
+            timeout_ms: None,
+            format_hint: None,
+        };
+        let backend = self.llm_backend.read().unwrap().clone();
+        // Nobody listens for streamed chunks; the full answer is returned.
+        let (tx, _) = mpsc::channel(1);
+        match crate::server::handle_llm_request(&request, &self.mgr, &backend, &tx).await {
+            Ok((response, _)) => json(StatusCode::OK, &serde_json::json!({ "response": response })),
+            Err(e) => error(StatusCode::BAD_GATEWAY, &e.to_string()),
+        }
+    }
+}
+
+fn json<T: serde::Serialize>(status: StatusCode, value: &T) -> Response<Full<Bytes>> {
+    Response::builder()
+        .status(status)
+        .header(CONTENT_TYPE, "application/json")
+        .body(Full::new(Bytes::from(serde_json::to_vec(value).unwrap_or_default())))
+        .unwrap()
+}
+
+fn error(status: StatusCode, message: &str) -> Response<Full<Bytes>> {
+    json(status, &serde_json::json!({ "error": message }))
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use async_trait::async_trait;
+    use omnish_llm::backend::{ContentBlock, LlmBackend, LlmRequest, LlmResponse, StopReason};
+    use omnish_llm::factory::MultiBackend;
+
+    /// Echoes the query back so tests can check it reached the backend.
+    struct EchoBackend;
+
+    #[async_trait]
+    impl LlmBackend for EchoBackend {
+        async fn complete(&self, req: &LlmRequest) -> Result<LlmResponse> {
+            Ok(LlmResponse {
+                content: vec![ContentBlock::Text(format!("echo: {}", req.query.as_deref().unwrap_or("")))],
+                stop_reason: StopReason::EndTurn,
+                model: "echo".into(),
+                usage: None,
+            })
+        }
+
+        fn name(&self) -> &str {
+            "echo"
+        }
+
+        fn model_name(&self) -> &str {
+            "echo"
+        }
+    }
+
+    async fn start(dir: &std::path::Path) -> String {
+        start_with_token(dir, "secret").await
+    }
+
+    async fn start_with_token(dir: &std::path::Path, token: &str) -> String {
+        let mgr = Arc::new(SessionManager::new(dir.to_path_buf(), Default::default()));
+        mgr.register("s1", None, std::collections::HashMap::new(), None)
+            .await
+            .unwrap();
+        let backend = Arc::new(MultiBackend::from_single(Arc::new(EchoBackend)));
+        let server = Arc::new(DaemonHttpServer::new(
+            mgr,
+            Arc::new(std::sync::RwLock::new(backend)),
+            token.into(),
+        ));
+        let listener = DaemonHttpServer::bind(0).await.unwrap();
+        let url = format!("http://{}", listener.local_addr().unwrap());
+        tokio::spawn(server.serve(listener));
+        url
+    }
+
+    #[tokio::test]
+    async fn test_query_returns_llm_output() {
+        let dir = tempfile::tempdir().unwrap();
+        let url = start(dir.path()).await;
+        let resp = reqwest::Client::new()
+            .post(format!("{}/query", url))
+            .bearer_auth("secret")
+            .json(&serde_json::json!({ "session_id": "s1", "query": "why did make fail" }))
+            .send()
+            .await
+            .unwrap();
+        assert_eq!(resp.status(), 200);
+        let body: serde_json::Value = resp.json().await.unwrap();
+        assert_eq!(body["response"], "echo: why did make fail");
+    }
+
+    #[tokio::test]
+    async fn test_sessions_and_auth() {
+        let dir = tempfile::tempdir().unwrap();
+        let url = start(dir.path()).await;
+        let client = reqwest::Client::new();
+
+        let resp = client.get(format!("{}/sessions", url)).send().await.unwrap();
+        assert_eq!(resp.status(), 401);
+        let resp = client.get(format!("{}/sessions", url)).bearer_auth("wrong").send().await.unwrap();
+        assert_eq!(resp.status(), 401);
+
+        let resp = client.get(format!("{}/sessions", url)).bearer_auth("secret").send().await.unwrap();
+        assert_eq!(resp.status(), 200);
+        let sessions: serde_json::Value = resp.json().await.unwrap();
+        assert_eq!(sessions[0]["session_id"], "s1");
+
+        let resp = client.get(format!("{}/sessions/s1/context", url)).bearer_auth("secret").send().await.unwrap();
+        assert_eq!(resp.status(), 200);
+        let resp = client.get(format!("{}/sessions/nope/context", url)).bearer_auth("secret").send().await.unwrap();
+        assert_eq!(resp.status(), 404);
+    }
+
+    #[tokio::test]
+    async fn test_empty_token_rejects_everything() {
+        let dir = tempfile::tempdir().unwrap();
+        let url = start_with_token(dir.path(), "").await;
+        let client = reqwest::Client::new();
+        let resp = client.get(format!("{}/sessions", url)).send().await.unwrap();
+        assert_eq!(resp.status(), 401);
+        let resp = client.get(format!("{}/sessions", url)).header("Authorization", "Bearer ").send().await.unwrap();
+        assert_eq!(resp.status(), 401);
+    }
+
+    #[tokio::test]
+    async fn test_query_body_is_limited() {
+        let dir = tempfile::tempdir().unwrap();
+        let url = start(dir.path()).await;
+        let query = "x".repeat(MAX_BODY_BYTES);
+        let resp = reqwest::Client::new()
+            .post(format!("{}/query", url))
+            .bearer_auth("secret")
+            .json(&serde_json::json!({ "session_id": "s1", "query": query }))
+            .send()
+            .await
+            .unwrap();
+        assert_eq!(resp.status(), 413);
+    }
+}
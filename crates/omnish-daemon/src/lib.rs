@@ -20,17 +20,23 @@ pub mod conversation_mgr;
 pub mod daily_notes;
 pub mod deploy;
 pub mod disconnect_sweep;
+pub mod export;
 pub mod file_watcher;
 pub mod formatter_mgr;
+pub mod history_import;
 pub mod house_keeping;
 pub mod hourly_summary;
 pub mod plugin;
 pub mod plugin_bundle;
 pub mod plugin_bundle_task;
 pub mod plugin_install;
+pub mod search;
 pub mod session_mgr;
+pub mod stats;
 pub mod task_mgr;
 pub mod thread_summary;
 pub mod tool_registry;
 pub mod tools;
+pub mod webhook;
+pub mod workspace;
 pub mod writer_idle;
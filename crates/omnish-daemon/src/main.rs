@@ -1,5 +1,6 @@
 mod config_schema;
 mod config_watcher;
+mod http_server;
 mod sandbox_rules;
 mod server;
 
@@ -9,6 +10,7 @@ use anyhow::Result;
 use omnish_common::config::{load_daemon_config, omnish_dir};
 use omnish_daemon::conversation_mgr::ConversationManager;
 use omnish_daemon::session_mgr::SessionManager;
+use omnish_daemon::history_import::HistoryImporter;
 use omnish_llm::backend::{LlmBackend, UnavailableBackend};
 use omnish_llm::factory::{MultiBackend, SharedLlmBackend};
 use server::DaemonServer;
@@ -19,11 +21,17 @@ use std::sync::Arc;
 const EXIT_RESTART: i32 = 42;
 
 fn main() {
+    omnish_common::config::apply_omnish_dir_flag();
+
     if std::env::args().any(|a| a == "--version" || a == "-V") {
         println!("omnish-daemon {}", omnish_common::VERSION);
         return;
     }
 
+    if std::env::args().nth(1).as_deref() == Some(omnish_common::completions::GENERATE_COMPLETIONS) {
+        std::process::exit(omnish_common::completions::run(std::env::args().nth(2).as_deref()));
+    }
+
     if std::env::args().any(|a| a == "--init") {
         let omnish_dir = omnish_dir();
         match init_omnish_dir(&omnish_dir) {
@@ -118,7 +126,10 @@ async fn async_main() -> Result<i32> {
     omnish_daemon::task_mgr::inject_task_defaults(&mut config.tasks);
 
     // Environment variable takes precedence over config file
-    let socket_path = std::env::var("OMNISH_SOCKET").unwrap_or_else(|_| config.listen_addr.clone());
+    let listen_addrs = match std::env::var("OMNISH_SOCKET") {
+        Ok(addr) => vec![addr],
+        Err(_) => config.effective_listen_addrs(),
+    };
 
     // SessionManager manages both sessions and completions internally:
     //   - sessions stored in $omnish_dir/sessions
@@ -140,9 +151,39 @@ async fn async_main() -> Result<i32> {
         Arc::new(std::sync::RwLock::new(backend))
     };
 
-    let session_mgr = Arc::new(SessionManager::new(omnish_dir.clone(), config.context.clone()));
+    let mut session_mgr = SessionManager::new(omnish_dir.clone(), config.context.clone())
+        .with_search_max_bytes(config.search.max_bytes_per_session)
+        .with_compress_streams(config.storage.compress_streams)
+        .with_encrypt_streams(config.storage.encrypt_streams)
+        .with_max_stream_bytes(config.storage.max_stream_bytes_per_session);
+    // Load the master key whenever it exists, so streams encrypted before
+    // encrypt_streams was turned off stay readable.
+    let master_key_path = omnish_common::auth::default_master_key_path();
+    if config.storage.encrypt_streams || master_key_path.exists() {
+        let master_key = omnish_common::auth::load_or_create_master_key(&master_key_path)?;
+        session_mgr = session_mgr.with_master_key(master_key);
+    }
+    let session_mgr = Arc::new(session_mgr);
     match session_mgr.load_existing().await {
         Ok(count) if count > 0 => tracing::info!("loaded {} existing session(s)", count),
+        Ok(_) if !std::env::args().any(|a| a == "--no-import-history") => {
+            // First start: seed context with the user's pre-omnish shell history
+            let history_path = std::env::var_os("HOME")
+                .map(|h| std::path::PathBuf::from(h).join(".bash_history"))
+                .unwrap_or_default();
+            match HistoryImporter::import_on_first_start(
+                &session_mgr,
+                &omnish_dir,
+                &history_path,
+                config.max_import_lines,
+            )
+            .await
+            {
+                Ok(0) => {}
+                Ok(n) => tracing::info!("imported {} command(s) from {}", n, history_path.display()),
+                Err(e) => tracing::warn!("failed to import shell history: {}", e),
+            }
+        }
         Ok(_) => {}
         Err(e) => tracing::warn!("failed to load existing sessions: {}", e),
     }
@@ -171,12 +212,39 @@ async fn async_main() -> Result<i32> {
     let (auth_token, token_status, _cert_status) = init_omnish_dir(&omnish_dir)?;
     tracing::info!("auth token {} ({})", omnish_common::auth::default_token_path().display(), token_status);
 
-    // Create TLS acceptor (only for TCP mode)
-    let tls_acceptor = if socket_path.contains(':') {
-        let tls_dir = omnish_transport::tls::default_tls_dir();
-        let acceptor = omnish_transport::tls::make_acceptor(&tls_dir)?;
-        tracing::info!("TLS enabled for TCP (cert dir: {})", tls_dir.display());
-        Some(acceptor)
+    let socket_mode = config
+        .socket_mode
+        .unwrap_or(omnish_transport::rpc_server::DEFAULT_SOCKET_MODE);
+    for socket_path in listen_addrs.iter().filter(|a| !a.contains(':')) {
+        if omnish_transport::rpc_server::socket_dir_is_world_writable(socket_path) {
+            tracing::warn!(
+                "socket directory of {} is world-writable; other users could replace the socket",
+                socket_path
+            );
+        }
+    }
+
+    // Create TLS acceptor (only used by TCP listeners)
+    let tls_acceptor = if listen_addrs.iter().any(|a| a.contains(':')) {
+        match (&config.tls.cert_path, &config.tls.key_path) {
+            (Some(cert), Some(key)) => {
+                let acceptor = omnish_transport::tls::acceptor_from_files(
+                    std::path::Path::new(cert),
+                    std::path::Path::new(key),
+                )?;
+                tracing::info!("TLS enabled for TCP (cert: {})", cert);
+                Some(acceptor)
+            }
+            (Some(_), None) | (None, Some(_)) => {
+                anyhow::bail!("tls.cert_path and tls.key_path must be set together");
+            }
+            (None, None) => {
+                let tls_dir = omnish_transport::tls::default_tls_dir();
+                let acceptor = omnish_transport::tls::make_acceptor(&tls_dir)?;
+                tracing::info!("TLS enabled for TCP (cert dir: {})", tls_dir.display());
+                Some(acceptor)
+            }
+        }
     } else {
         None
     };
@@ -284,6 +352,20 @@ async fn async_main() -> Result<i32> {
         });
     }
 
+    // Hot-reload [context]: applies from the next context build
+    {
+        let context_rx = config_watcher.subscribe(config_watcher::ConfigSection::Context);
+        let sm = Arc::clone(&session_mgr);
+        tokio::spawn(async move {
+            let mut rx = context_rx;
+            while rx.changed().await.is_ok() {
+                let config = rx.borrow_and_update().clone();
+                sm.set_context_config(config.context.clone());
+                tracing::info!("context config reloaded");
+            }
+        });
+    }
+
     let daemon_config_arc = std::sync::Arc::new(std::sync::RwLock::new(config.clone()));
 
     // Keep daemon_config_arc in sync with daemon.toml file changes.
@@ -295,6 +377,7 @@ async fn async_main() -> Result<i32> {
         let plugins_rx = config_watcher.subscribe(config_watcher::ConfigSection::Plugins);
         let tasks_rx = config_watcher.subscribe(config_watcher::ConfigSection::Tasks);
         let client_rx = config_watcher.subscribe(config_watcher::ConfigSection::Client);
+        let context_rx = config_watcher.subscribe(config_watcher::ConfigSection::Context);
         let dca = Arc::clone(&daemon_config_arc);
         tokio::spawn(async move {
             let mut llm = llm_rx;
@@ -302,6 +385,7 @@ async fn async_main() -> Result<i32> {
             let mut plugins = plugins_rx;
             let mut tasks = tasks_rx;
             let mut client = client_rx;
+            let mut context = context_rx;
             loop {
                 tokio::select! {
                     Ok(()) = llm.changed() => {
@@ -324,6 +408,10 @@ async fn async_main() -> Result<i32> {
                         let config = client.borrow_and_update().clone();
                         *dca.write().unwrap() = (*config).clone();
                     }
+                    Ok(()) = context.changed() => {
+                        let config = context.borrow_and_update().clone();
+                        *dca.write().unwrap() = (*config).clone();
+                    }
                     else => break,
                 }
             }
@@ -401,6 +489,29 @@ async fn async_main() -> Result<i32> {
         let _ = formatter_mgr.register_external(&name, path).await;
     }
     let formatter_mgr = Arc::new(formatter_mgr);
+
+    // Optional HTTP API, served next to the socket server
+    if config.http.enabled {
+        let token = config.http.auth_token.clone().unwrap_or_else(|| auth_token.clone());
+        if token.is_empty() {
+            // An empty bearer token would let any local process in
+            tracing::warn!("HTTP API not started: auth token is empty");
+        } else {
+            match http_server::DaemonHttpServer::bind(config.http.port).await {
+                Ok(listener) => {
+                    tracing::info!("HTTP API listening on 127.0.0.1:{}", config.http.port);
+                    let http = Arc::new(http_server::DaemonHttpServer::new(
+                        Arc::clone(&session_mgr),
+                        llm_backend.clone(),
+                        token,
+                    ));
+                    tokio::spawn(http.serve(listener));
+                }
+                Err(e) => tracing::warn!("HTTP API not started, cannot bind port {}: {}", config.http.port, e),
+            }
+        }
+    }
+
     let server = DaemonServer::new(session_mgr, llm_backend, task_mgr, conv_mgr, plugin_mgr.clone(), tool_registry.clone(), server_opts, formatter_mgr, Arc::clone(&update_cache), Arc::clone(&plugin_bundler));
 
     // Push client-relevant config changes to all connected clients via push_registry.
@@ -427,7 +538,7 @@ async fn async_main() -> Result<i32> {
         });
     }
 
-    tracing::info!("starting omnishd at {}", socket_path);
+    tracing::info!("starting omnishd at {}", listen_addrs.join(", "));
 
     // Set up signal handlers
     let mut sigterm = tokio::signal::unix::signal(tokio::signal::unix::SignalKind::terminate())?;
@@ -435,7 +546,7 @@ async fn async_main() -> Result<i32> {
 
     // Race between server, signals, and restart request
     let exit_code = tokio::select! {
-        result = server.run(&socket_path, auth_token, tls_acceptor) => {
+        result = server.run(&listen_addrs, auth_token, tls_acceptor, socket_mode) => {
             if let Err(e) = result {
                 tracing::error!("server error: {}", e);
                 1
@@ -6,10 +6,9 @@
 //! log only when *its own* rebuild observed a change (before vs after),
 //! so handler-triggered refreshes don't show up here retroactively.
 
-use crate::task_mgr::{ScheduledTask, TaskContext};
+use crate::task_mgr::{ScheduledTask, TaskContext, TaskJob};
 use anyhow::Result;
 use omnish_common::config::ConfigMap;
-use tokio_cron_scheduler::Job;
 
 pub struct PluginBundleTask {
     config: ConfigMap,
@@ -43,9 +42,9 @@ impl ScheduledTask for PluginBundleTask {
         ].into()
     }
 
-    fn create_job(&self, ctx: &TaskContext) -> Result<Job> {
+    fn create_job(&self, ctx: &TaskContext) -> Result<TaskJob> {
         let bundler = ctx.daemon.plugin_bundler.clone();
-        Ok(Job::new_async(self.schedule(), move |_uuid, _lock| {
+        TaskJob::new(self.schedule(), move || {
             let bundler = bundler.clone();
             Box::pin(async move {
                 tracing::debug!("task [plugin_bundle] started");
@@ -63,6 +62,6 @@ impl ScheduledTask for PluginBundleTask {
                 }
                 tracing::debug!("task [plugin_bundle] finished");
             })
-        })?)
+        })
     }
 }
@@ -0,0 +1,201 @@
+use regex::Regex;
+
+/// Lines of context kept on each side of the first match in a snippet.
+const SNIPPET_CONTEXT_LINES: usize = 1;
+
+/// Lines of context kept on each side of every match by `/grep`, as in `grep -C 2`.
+const GREP_CONTEXT_LINES: usize = 2;
+
+/// Separator between non-adjacent groups of grep output lines.
+pub const GREP_GROUP_SEPARATOR: &str = "--";
+
+/// A command whose line or output matched a `/search` pattern.
+#[derive(Debug, Clone, PartialEq)]
+pub struct SearchResult {
+    pub command_line: String,
+    pub session_id: String,
+    pub started_at: u64,
+    /// Output lines around the first match (empty when only the command
+    /// line matched).
+    pub snippet: String,
+}
+
+/// A command whose output matched a `/grep` pattern.
+#[derive(Debug, Clone, PartialEq)]
+pub struct GrepResult {
+    pub session_id: String,
+    pub command_line: String,
+    /// Every matching output line with its context. Non-adjacent groups are
+    /// separated by `GREP_GROUP_SEPARATOR`.
+    pub matched_lines: Vec<String>,
+    pub started_at: u64,
+}
+
+/// Compile a user-supplied search pattern. Input that is not a valid regex
+/// (e.g. `foo(`) is searched for literally instead of being rejected.
+pub fn compile_pattern(pattern: &str) -> Option<Regex> {
+    if pattern.is_empty() {
+        return None;
+    }
+    Regex::new(pattern)
+        .or_else(|_| Regex::new(&regex::escape(pattern)))
+        .ok()
+}
+
+/// Return the lines around the first match of `re` in `text`, or None when
+/// there is no match.
+pub fn snippet_around(text: &str, re: &Regex) -> Option<String> {
+    let lines: Vec<&str> = text.lines().collect();
+    let hit = lines.iter().position(|l| re.is_match(l))?;
+    let start = hit.saturating_sub(SNIPPET_CONTEXT_LINES);
+    let end = (hit + SNIPPET_CONTEXT_LINES + 1).min(lines.len());
+    Some(lines[start..end].join("\n"))
+}
+
+/// All lines of `text` matching `re`, each with `GREP_CONTEXT_LINES` of
+/// context. Overlapping ranges are merged. Empty when nothing matches.
+pub fn grep_lines(text: &str, re: &Regex) -> Vec<String> {
+    let lines: Vec<&str> = text.lines().collect();
+    let mut ranges: Vec<(usize, usize)> = Vec::new();
+    for (i, line) in lines.iter().enumerate() {
+        if !re.is_match(line) {
+            continue;
+        }
+        let start = i.saturating_sub(GREP_CONTEXT_LINES);
+        let end = (i + GREP_CONTEXT_LINES + 1).min(lines.len());
+        match ranges.last_mut() {
+            Some(last) if start <= last.1 => last.1 = end,
+            _ => ranges.push((start, end)),
+        }
+    }
+    let mut out = Vec::new();
+    for (idx, (start, end)) in ranges.into_iter().enumerate() {
+        if idx > 0 {
+            out.push(GREP_GROUP_SEPARATOR.to_string());
+        }
+        out.extend(lines[start..end].iter().map(|l| l.to_string()));
+    }
+    out
+}
+
+/// Renders search results for display, highlighting matches in bold.
+pub struct SearchFormatter<'a> {
+    re: &'a Regex,
+}
+
+impl<'a> SearchFormatter<'a> {
+    pub fn new(re: &'a Regex) -> Self {
+        Self { re }
+    }
+
+    pub fn format(&self, results: &[SearchResult]) -> String {
+        if results.is_empty() {
+            return format!("No matches for /{}/", self.re.as_str());
+        }
+        let mut out = String::new();
+        for r in results {
+            let time = chrono::DateTime::from_timestamp_millis(r.started_at as i64)
+                .map(|t| t.with_timezone(&chrono::Local).format("%Y-%m-%d %H:%M").to_string())
+                .unwrap_or_default();
+            let sid = &r.session_id[..8.min(r.session_id.len())];
+            out.push_str(&format!("[{}] {} $ {}\n", time, sid, self.highlight(&r.command_line)));
+            for line in r.snippet.lines() {
+                out.push_str(&format!("    {}\n", self.highlight(line)));
+            }
+        }
+        out.truncate(out.trim_end().len());
+        out
+    }
+
+    pub fn format_grep(&self, results: &[GrepResult]) -> String {
+        if results.is_empty() {
+            return format!("No matches for /{}/", self.re.as_str());
+        }
+        let mut out = String::new();
+        for r in results {
+            let time = chrono::DateTime::from_timestamp_millis(r.started_at as i64)
+                .map(|t| t.with_timezone(&chrono::Local).format("%Y-%m-%d %H:%M").to_string())
+                .unwrap_or_default();
+            let sid = &r.session_id[..8.min(r.session_id.len())];
+            out.push_str(&format!("[{}] {} $ {}\n", time, sid, r.command_line));
+            for line in &r.matched_lines {
+                out.push_str(&format!("    {}\n", self.highlight(line)));
+            }
+        }
+        out.truncate(out.trim_end().len());
+        out
+    }
+
+    fn highlight(&self, text: &str) -> String {
+        self.re
+            .replace_all(text, |caps: &regex::Captures| format!("\x1b[1m{}\x1b[22m", &caps[0]))
+            .into_owned()
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_invalid_regex_falls_back_to_literal() {
+        let re = compile_pattern("foo(").unwrap();
+        assert!(re.is_match("call foo(1)"));
+        assert!(compile_pattern("").is_none());
+    }
+
+    #[test]
+    fn test_snippet_keeps_context_lines() {
+        let re = compile_pattern("error").unwrap();
+        let text = "a\nb\nfatal error here\nc\nd";
+        assert_eq!(snippet_around(text, &re).unwrap(), "b\nfatal error here\nc");
+        assert!(snippet_around("nothing", &re).is_none());
+    }
+
+    #[test]
+    fn test_format_highlights_matches() {
+        let re = compile_pattern("make").unwrap();
+        let results = vec![SearchResult {
+            command_line: "make test".into(),
+            session_id: "abcdef123456".into(),
+            started_at: 0,
+            snippet: "make: *** [test] Error 1".into(),
+        }];
+        let out = SearchFormatter::new(&re).format(&results);
+        assert!(out.contains("abcdef12 $ \x1b[1mmake\x1b[22m test"));
+        assert!(out.contains("    \x1b[1mmake\x1b[22m: ***"));
+        assert!(!out.ends_with('\n'));
+    }
+
+    #[test]
+    fn test_grep_lines_context_and_groups() {
+        let re = compile_pattern("err").unwrap();
+        let text = "1\n2\n3\nerr a\n5\nerr b\n7\n8\n9\n10\n11\nerr c";
+        assert_eq!(
+            grep_lines(text, &re),
+            vec!["2", "3", "err a", "5", "err b", "7", "8", "--", "10", "11", "err c"],
+        );
+        assert!(grep_lines("nothing here", &re).is_empty());
+    }
+
+    #[test]
+    fn test_format_grep_highlights_matches() {
+        let re = compile_pattern("refused").unwrap();
+        let results = vec![GrepResult {
+            session_id: "abcdef123456".into(),
+            command_line: "curl localhost".into(),
+            matched_lines: vec!["Error: connection refused".into()],
+            started_at: 0,
+        }];
+        let out = SearchFormatter::new(&re).format_grep(&results);
+        assert!(out.contains("abcdef12 $ curl localhost"));
+        assert!(out.contains("    Error: connection \x1b[1mrefused\x1b[22m"));
+        assert_eq!(SearchFormatter::new(&re).format_grep(&[]), "No matches for /refused/");
+    }
+
+    #[test]
+    fn test_format_no_results() {
+        let re = compile_pattern("zzz").unwrap();
+        assert_eq!(SearchFormatter::new(&re).format(&[]), "No matches for /zzz/");
+    }
+}
@@ -5,10 +5,12 @@ use omnish_daemon::session_mgr::SessionManager;
 use omnish_daemon::task_mgr::TaskManager;
 use omnish_llm::backend::{ContentBlock, LlmBackend, LlmRequest, StopReason, TriggerType, UseCase};
 use omnish_llm::factory::{MultiBackend, SharedLlmBackend};
+use omnish_llm::rate_limit::RateLimitError;
+use omnish_llm::tokens::TokenBudget;
 use omnish_protocol::message::*;
 use omnish_transport::rpc_server::{OnPushConnect, PushRegistry, RpcServer};
 use std::collections::HashMap;
-use std::sync::atomic::{AtomicU64, Ordering};
+use std::sync::atomic::{AtomicI64, AtomicU64, Ordering};
 use std::sync::Arc;
 use tokio::sync::{mpsc, Mutex};
 use tokio_rustls::TlsAcceptor;
@@ -207,7 +209,8 @@ pub struct ServerOpts {
 
 /// Shared state threaded through every message handler.
 ///
-/// Built once per serve loop and shared across all connections.  Holds every
+/// Built once per `DaemonServer::run` and shared across all listeners and
+/// connections.  Holds every
 /// manager, registry, and runtime counter so individual handlers can be
 /// refactored without growing a new parameter per subsystem.
 struct HandlerCtx {
@@ -227,7 +230,158 @@ struct HandlerCtx {
     plugin_bundler: Arc<omnish_daemon::plugin_bundle::PluginBundler>,
     io_requests: Arc<AtomicU64>,
     io_bytes: Arc<AtomicU64>,
+    queue_depth: QueueDepthCounter,
     push_registry: PushRegistry,
+    workspace_detector: Arc<omnish_daemon::workspace::WorkspaceDetector>,
+    /// When `run` started serving, for `HealthStatus::uptime_secs`.
+    started_at: std::time::Instant,
+}
+
+/// Number of requests currently inside `handle_message`.
+#[derive(Clone, Default)]
+struct QueueDepthCounter(Arc<AtomicI64>);
+
+impl QueueDepthCounter {
+    fn enter(&self) -> QueueDepthGuard {
+        self.0.fetch_add(1, Ordering::Relaxed);
+        QueueDepthGuard {
+            counter: self.0.clone(),
+            start: std::time::Instant::now(),
+        }
+    }
+
+    fn get(&self) -> i64 {
+        self.0.load(Ordering::Relaxed)
+    }
+}
+
+/// Decrements the queue depth on drop.
+struct QueueDepthGuard {
+    counter: Arc<AtomicI64>,
+    start: std::time::Instant,
+}
+
+impl QueueDepthGuard {
+    /// Finish the request, warning if it took longer than `warn_ms`
+    /// (0 disables). Returns whether the request was slow.
+    fn finish(self, warn_ms: u64, query: &str) -> bool {
+        let elapsed = self.start.elapsed().as_millis() as u64;
+        let slow = warn_ms > 0 && elapsed > warn_ms;
+        if slow {
+            let query_prefix: String = query.chars().take(50).collect();
+            tracing::warn!("slow request: {}ms, query: {}", elapsed, query_prefix);
+        }
+        slow
+    }
+}
+
+impl Drop for QueueDepthGuard {
+    fn drop(&mut self) {
+        self.counter.fetch_sub(1, Ordering::Relaxed);
+    }
+}
+
+/// Short description of a message for slow-request logging. Only the
+/// free-text query variants carry user input; everything else is named by kind.
+fn request_label(msg: &Message) -> String {
+    match msg {
+        Message::Request(r) => r.query.clone(),
+        Message::ChatMessage(cm) => cm.query.clone(),
+        Message::CompletionRequest(_) => "<completion>".to_string(),
+        Message::IoData(_) => "<io>".to_string(),
+        Message::CommandComplete(_) => "<command>".to_string(),
+        Message::Batch { .. } => "<batch>".to_string(),
+        _ => "<other>".to_string(),
+    }
+}
+
+/// Append one `IoData` chunk to its session's stream.
+async fn write_io_data(io: &IoData, ctx: &HandlerCtx) -> anyhow::Result<()> {
+    ctx.io_requests.fetch_add(1, Ordering::Relaxed);
+    ctx.io_bytes.fetch_add(io.data.len() as u64, Ordering::Relaxed);
+    let dir = match io.direction {
+        IoDirection::Input => 0,
+        IoDirection::Output => 1,
+    };
+    ctx.session_mgr
+        .write_io(&io.session_id, io.timestamp_ms, dir, &io.data)
+        .await
+}
+
+/// Handle the messages of a `Batch` in order, one result each. A failing
+/// message is logged and reported without stopping the rest. Only
+/// messages answered with a single reply can be batched; anything else
+/// (queries, chat, nested batches) fails with an error.
+async fn handle_batch(messages: Vec<Message>, ctx: &HandlerCtx) -> Vec<anyhow::Result<Message>> {
+    let mut results = Vec::with_capacity(messages.len());
+    for (i, inner) in messages.into_iter().enumerate() {
+        let result = match inner {
+            Message::IoData(io) => write_io_data(&io, ctx).await.map(|_| Message::Ack),
+            msg @ (Message::SessionUpdate(_) | Message::CommandComplete(_)) => {
+                let (inner_tx, mut inner_rx) = mpsc::channel(1);
+                Box::pin(handle_message(msg, ctx, inner_tx)).await;
+                inner_rx.recv().await.ok_or_else(|| anyhow::anyhow!("no reply"))
+            }
+            other => Err(anyhow::anyhow!("{} cannot be batched", message_kind(&other))),
+        };
+        if let Err(e) = &result {
+            tracing::warn!("batch message {} failed: {}", i, e);
+        }
+        results.push(result);
+    }
+    results
+}
+
+/// Variant name of `msg`, for errors about messages in the wrong place.
+fn message_kind(msg: &Message) -> &'static str {
+    match msg {
+        Message::SessionStart(_) => "SessionStart",
+        Message::SessionEnd(_) => "SessionEnd",
+        Message::SessionUpdate(_) => "SessionUpdate",
+        Message::IoData(_) => "IoData",
+        Message::Event(_) => "Event",
+        Message::Request(_) => "Request",
+        Message::Response(_) => "Response",
+        Message::CommandComplete(_) => "CommandComplete",
+        Message::CompletionRequest(_) => "CompletionRequest",
+        Message::CompletionResponse(_) => "CompletionResponse",
+        Message::CompletionSummary(_) => "CompletionSummary",
+        Message::ChatStart(_) => "ChatStart",
+        Message::ChatReady(_) => "ChatReady",
+        Message::ChatEnd(_) => "ChatEnd",
+        Message::ChatMessage(_) => "ChatMessage",
+        Message::ChatResponse(_) => "ChatResponse",
+        Message::ChatInterrupt(_) => "ChatInterrupt",
+        Message::ChatToolStatus(_) => "ChatToolStatus",
+        Message::ChatToolCall(_) => "ChatToolCall",
+        Message::ChatToolResult(_) => "ChatToolResult",
+        Message::Ack => "Ack",
+        Message::Auth(_) => "Auth",
+        Message::AuthResult(_) => "AuthResult",
+        Message::ConfigQuery => "ConfigQuery",
+        Message::ConfigResponse { .. } => "ConfigResponse",
+        Message::ConfigUpdate { .. } => "ConfigUpdate",
+        Message::ConfigUpdateResult { .. } => "ConfigUpdateResult",
+        Message::UpdateCheck { .. } => "UpdateCheck",
+        Message::UpdateInfo { .. } => "UpdateInfo",
+        Message::UpdateRequest { .. } => "UpdateRequest",
+        Message::UpdateChunk { .. } => "UpdateChunk",
+        Message::ConfigClient { .. } => "ConfigClient",
+        Message::TestDisconnect { .. } => "TestDisconnect",
+        Message::NoticePush { .. } => "NoticePush",
+        Message::PluginSyncCheck { .. } => "PluginSyncCheck",
+        Message::PluginSyncInfo { .. } => "PluginSyncInfo",
+        Message::PluginSyncRequest { .. } => "PluginSyncRequest",
+        Message::StreamingChunk { .. } => "StreamingChunk",
+        Message::ExportRequest { .. } => "ExportRequest",
+        Message::ExportResult { .. } => "ExportResult",
+        Message::HealthCheck => "HealthCheck",
+        Message::HealthStatus { .. } => "HealthStatus",
+        Message::Batch { .. } => "Batch",
+        Message::Ping { .. } => "Ping",
+        Message::Pong { .. } => "Pong",
+        Message::BatchResponse { .. } => "BatchResponse",
+    }
 }
 
 impl HandlerCtx {
@@ -331,9 +485,31 @@ pub fn diff_client_config(old: &omnish_common::config::DaemonConfig, new: &omnis
     if old.client.language != new.client.language {
         changes.push(ConfigChange { path: "client.language".into(), value: new.client.language.clone() });
     }
+    if old.context.tracker.prompt_regex != new.context.tracker.prompt_regex {
+        changes.push(prompt_regex_change(&new.context.tracker));
+    }
+    if old.context.tracker.ps2_regex != new.context.tracker.ps2_regex {
+        changes.push(ps2_regex_change(&new.context.tracker));
+    }
     changes
 }
 
+/// Empty value means "use the built-in prompt pattern".
+fn prompt_regex_change(tracker: &omnish_common::config::TrackerConfig) -> ConfigChange {
+    ConfigChange {
+        path: "context.tracker.prompt_regex".into(),
+        value: tracker.prompt_regex.clone().unwrap_or_default(),
+    }
+}
+
+/// Empty value means "use the built-in continuation prompt pattern".
+fn ps2_regex_change(tracker: &omnish_common::config::TrackerConfig) -> ConfigChange {
+    ConfigChange {
+        path: "context.tracker.ps2_regex".into(),
+        value: tracker.ps2_regex.clone().unwrap_or_default(),
+    }
+}
+
 /// Build a full set of client-relevant config changes (for initial push).
 pub fn full_client_changes(cfg: &omnish_common::config::DaemonConfig) -> Vec<ConfigChange> {
     vec![
@@ -344,6 +520,8 @@ pub fn full_client_changes(cfg: &omnish_common::config::DaemonConfig) -> Vec<Con
         ConfigChange { path: "client.intercept_gap_ms".into(), value: cfg.client.intercept_gap_ms.to_string() },
         ConfigChange { path: "client.developer_mode".into(), value: cfg.client.developer_mode.to_string() },
         ConfigChange { path: "client.language".into(), value: cfg.client.language.clone() },
+        prompt_regex_change(&cfg.context.tracker),
+        ps2_regex_change(&cfg.context.tracker),
     ]
 }
 
@@ -533,14 +711,25 @@ impl DaemonServer {
         }
     }
 
+    /// Serve every address in `listen_addrs` at once (Unix sockets and TCP
+    /// can be mixed). All listeners share one connection handler, so
+    /// sessions and push channels work the same whichever one a client
+    /// uses. Returns when any listener fails.
     pub async fn run(
         &self,
-        addr: &str,
+        listen_addrs: &[String],
         auth_token: String,
         tls_acceptor: Option<TlsAcceptor>,
+        socket_mode: u32,
     ) -> Result<()> {
-        let mut server = RpcServer::bind(addr).await?;
-        tracing::info!("omnishd listening on {}", addr);
+        if listen_addrs.is_empty() {
+            anyhow::bail!("no listen address configured");
+        }
+        let mut servers = Vec::with_capacity(listen_addrs.len());
+        for addr in listen_addrs {
+            servers.push(RpcServer::bind_with_mode(addr, socket_mode).await?);
+            tracing::info!("omnishd listening on {}", addr);
+        }
 
         // Periodically sweep stale pending agent loop entries
         let pending_cleanup = self.pending_agent_loops.clone();
@@ -563,11 +752,13 @@ impl DaemonServer {
         // Counters for IoData traffic over the last minute.
         let io_requests = Arc::new(AtomicU64::new(0));
         let io_bytes = Arc::new(AtomicU64::new(0));
+        let queue_depth = QueueDepthCounter::default();
 
         // Periodically release idle thread claims (safety net: 30m10s) and log IoData stats.
         let idle_threads = self.active_threads.clone();
         let stats_requests = io_requests.clone();
         let stats_bytes = io_bytes.clone();
+        let stats_depth = queue_depth.clone();
         tokio::spawn(async move {
             let mut interval = tokio::time::interval(std::time::Duration::from_secs(60));
             let max_idle = std::time::Duration::from_secs(30 * 60 + 10);
@@ -585,6 +776,7 @@ impl DaemonServer {
                 let reqs = stats_requests.swap(0, Ordering::Relaxed);
                 let bytes = stats_bytes.swap(0, Ordering::Relaxed);
                 tracing::debug!("IoData last 60s: {} requests, {} bytes", reqs, bytes);
+                tracing::debug!("omnish_request_queue_depth={}", stats_depth.get());
             }
         });
 
@@ -617,7 +809,10 @@ impl DaemonServer {
             plugin_bundler: self.plugin_bundler.clone(),
             io_requests,
             io_bytes,
+            queue_depth,
             push_registry: self.push_registry.clone(),
+            workspace_detector: Arc::new(omnish_daemon::workspace::WorkspaceDetector::new()),
+            started_at: std::time::Instant::now(),
         });
 
         // Mark sessions whose transport connection just dropped as pending
@@ -634,19 +829,36 @@ impl DaemonServer {
             })
         };
 
-        server
-            .serve(
-                move |msg, tx| {
-                    let ctx = ctx.clone();
-                    Box::pin(async move { handle_message(msg, &ctx, tx).await })
-                },
-                Some(auth_token),
-                tls_acceptor,
-                Some(self.push_registry.clone()),
-                on_push_connect,
-                Some(on_disconnect),
-            )
-            .await
+        let connection_handler = move |msg, tx| {
+            let ctx = ctx.clone();
+            Box::pin(async move {
+                let warn_ms = ctx.opts.daemon_config.read().unwrap().slow_request_warn_ms;
+                let label = request_label(&msg);
+                let guard = ctx.queue_depth.enter();
+                handle_message(msg, &ctx, tx).await;
+                guard.finish(warn_ms, &label);
+            }) as std::pin::Pin<Box<dyn std::future::Future<Output = ()> + Send>>
+        };
+
+        let mut listeners = tokio::task::JoinSet::new();
+        for mut server in servers {
+            let handler = connection_handler.clone();
+            let auth_token = Some(auth_token.clone());
+            let tls_acceptor = tls_acceptor.clone();
+            let push_registry = Some(self.push_registry.clone());
+            let on_push_connect = on_push_connect.clone();
+            let on_disconnect = Some(on_disconnect.clone());
+            listeners.spawn(async move {
+                server
+                    .serve(handler, auth_token, tls_acceptor, push_registry, on_push_connect, on_disconnect)
+                    .await
+            });
+        }
+        // Listeners only return on error; the first one takes the daemon down.
+        match listeners.join_next().await {
+            Some(result) => result?,
+            None => Ok(()),
+        }
     }
 }
 
@@ -691,20 +903,22 @@ async fn handle_message(
             let _ = tx.send(Message::Ack).await;
         }
         Message::IoData(io) => {
-            ctx.io_requests.fetch_add(1, Ordering::Relaxed);
-            ctx.io_bytes.fetch_add(io.data.len() as u64, Ordering::Relaxed);
-            let dir = match io.direction {
-                IoDirection::Input => 0,
-                IoDirection::Output => 1,
-            };
-            if let Err(e) = mgr
-                .write_io(&io.session_id, io.timestamp_ms, dir, &io.data)
-                .await
-            {
+            if let Err(e) = write_io_data(&io, ctx).await {
                 tracing::error!("write_io error: {}", e);
             }
             let _ = tx.send(Message::Ack).await;
         }
+        Message::Batch { messages } => {
+            let results = handle_batch(messages, ctx)
+                .await
+                .into_iter()
+                .map(|r| match r {
+                    Ok(reply) => BatchResult::Ok(Box::new(reply)),
+                    Err(e) => BatchResult::Err(e.to_string()),
+                })
+                .collect();
+            let _ = tx.send(Message::BatchResponse { results }).await;
+        }
         Message::CommandComplete(cc) => {
             if let Err(e) = mgr.receive_command(&cc.session_id, cc.record).await {
                 tracing::error!("receive_command error: {}", e);
@@ -721,6 +935,18 @@ async fn handle_message(
             let _ = tx.send(Message::Ack).await;
         }
         Message::Request(req) => {
+            // /replay streams terminal output ahead of its final Response.
+            if req.query == "__cmd:replay" || req.query.starts_with("__cmd:replay ") {
+                let args = req.query["__cmd:replay".len()..].trim();
+                let result = handle_replay(args, &req.request_id, mgr, &tx).await;
+                let _ = tx.send(Message::Response(Response {
+                    request_id: req.request_id,
+                    content: result.to_string(),
+                    is_streaming: false,
+                    is_final: true,
+                })).await;
+                return;
+            }
             if req.query.starts_with("__cmd:") {
                 let result = handle_builtin_command(&req, ctx, &llm).await;
                 let content = serde_json::to_string(&result).unwrap_or_else(|_| {
@@ -735,18 +961,24 @@ async fn handle_message(
                 return;
             }
 
-            let content = match handle_llm_request(&req, mgr, &llm).await {
-                Ok(response) => response.text(),
-                Err(e) => {
-                    tracing::error!("LLM request failed: {}", e);
-                    format!("Error: {}", e)
-                }
+            let (content, is_streaming) = match handle_llm_request_with_deadline(&req, mgr, &llm, &tx).await {
+                Ok(answer) => answer,
+                Err(e) => match e.downcast_ref::<RateLimitError>() {
+                    Some(rl) => {
+                        tracing::warn!("LLM request rate limited: {}", rl);
+                        (rl.to_string(), false)
+                    }
+                    None => {
+                        tracing::error!("LLM request failed: {}", e);
+                        (format!("Error: {}", e), false)
+                    }
+                },
             };
 
             let _ = tx.send(Message::Response(Response {
                 request_id: req.request_id,
                 content,
-                is_streaming: false,
+                is_streaming,
                 is_final: true,
             })).await;
         }
@@ -939,12 +1171,53 @@ async fn handle_message(
         Message::PluginSyncRequest { hostname } => {
             handle_plugin_sync_request(hostname, ctx, tx).await;
         }
+        Message::ExportRequest { session_id, format } => {
+            let (content, command_count, error) = match export_session(mgr, &session_id, format).await {
+                Ok((content, n)) => (content, n, None),
+                Err(e) => {
+                    tracing::warn!("export of session {} failed: {}", session_id, e);
+                    (String::new(), 0, Some(e.to_string()))
+                }
+            };
+            let _ = tx.send(Message::ExportResult { content, command_count, error }).await;
+        }
+        Message::HealthCheck => {
+            let status = health_status(mgr, &llm, ctx.started_at.elapsed()).await;
+            let _ = tx.send(status).await;
+        }
         _ => {
             let _ = tx.send(Message::Ack).await;
         }
     }
 }
 
+/// Build the `HealthStatus` answer. The LLM counts as available when a
+/// real backend (not the `unavailable` fallback) serves chat.
+async fn health_status(mgr: &SessionManager, llm: &MultiBackend, uptime: std::time::Duration) -> Message {
+    Message::HealthStatus {
+        uptime_secs: uptime.as_secs(),
+        session_count: mgr.list_active().await.len(),
+        llm_available: llm.get_backend(UseCase::Chat).name() != "unavailable",
+        stream_write_errors: mgr.stream_write_errors(),
+    }
+}
+
+/// Render a session in `format`. Returns the document and the number of
+/// exported commands; the client writes the file.
+async fn export_session(
+    mgr: &SessionManager,
+    session_id: &str,
+    format: ExportFormat,
+) -> Result<(String, u32)> {
+    let (commands, reader) = mgr.get_commands_with_reader(session_id).await?;
+    let document = tokio::task::spawn_blocking(move || match format {
+        ExportFormat::Markdown => omnish_daemon::export::SessionExporter::to_markdown(&commands, &*reader)
+            .map(|doc| (doc, commands.len() as u32)),
+    })
+    .await??;
+    Ok(document)
+}
+
 /// Build an empty ChatReady (no history, blank thread_id).  Used for error
 /// responses (not_found / thread_locked) and the "no threads yet" case.
 fn empty_chat_ready(
@@ -1448,11 +1721,14 @@ async fn handle_chat_message(
         return;
     }
 
-    // Resolve per-thread model override for backend selection
+    // Resolve per-thread model override for backend selection, then the
+    // project's `[llm.use_cases]` from `.omnish.toml`
     let meta = conv_mgr.load_meta(&cm.thread_id);
     let use_case = UseCase::Chat;
+    let project_model = mgr.project_backend_name(&cm.session_id, "chat").await;
     let effective_backend: Arc<dyn LlmBackend> = meta.model.as_ref()
         .and_then(|name| llm.get_backend_by_name(name))
+        .or_else(|| project_model.and_then(|name| llm.get_backend_by_name(&name)))
         .unwrap_or_else(|| llm.get_backend(use_case));
 
     let max_context_chars = effective_backend.max_content_chars();
@@ -1479,6 +1755,16 @@ async fn handle_chat_message(
 
     let project_instructions = cm.project_instructions.clone();
 
+    // Project-level context (git root, package name, remote) for the session's
+    // cwd. Placed ahead of the reminder so the static base prompt stays cacheable.
+    let reminder = match session_attrs
+        .get("shell_cwd")
+        .and_then(|cwd| ctx.workspace_detector.detect(std::path::Path::new(cwd)))
+    {
+        Some(ws) => format!("{}\n\n{}", ws.to_prompt(), reminder),
+        None => reminder,
+    };
+
     let full_system_prompt = match project_instructions {
         Some(ref pi) => format!("{}\n\n{}\n\n{}", system_prompt, reminder, pi),
         None => format!("{}\n\n{}", system_prompt, reminder),
@@ -1951,6 +2237,7 @@ async fn run_agent_loop(
                     state.cumulative_usage.cache_creation_input_tokens += u.cache_creation_input_tokens;
                 }
                 state.last_model = backend.name().to_string();
+                record_response_usage(&ctx.session_mgr, &state.cm.request_id, &state.cm.session_id, &response);
 
                 if response.stop_reason == StopReason::ToolUse {
                     let tool_calls = response.tool_calls();
@@ -2319,7 +2606,9 @@ async fn run_agent_loop(
                 } else {
                     &err_str
                 };
-                let user_msg = if is_connection {
+                let user_msg = if let Some(rl) = e.downcast_ref::<RateLimitError>() {
+                    format!("{}. Your progress has been saved - you can continue by sending another message.", rl)
+                } else if is_connection {
                     format!("Connection to the AI service was lost: {}. Your progress has been saved - you can continue by sending another message.", display_err)
                 } else {
                     format!("AI service returned an error: {}. Your progress has been saved - you can continue by sending another message.", display_err)
@@ -2411,7 +2700,10 @@ async fn try_warmup_kv_cache(
     };
 
     match backend.complete(&req).await {
-        Ok(_) => tracing::debug!("KV cache warmup completed for session {}", session_id),
+        Ok(response) => {
+            record_response_usage(mgr, "warmup", session_id, &response);
+            tracing::debug!("KV cache warmup completed for session {}", session_id)
+        }
         Err(e) => tracing::debug!("KV cache warmup failed for session {}: {}", session_id, e),
     }
 }
@@ -2464,29 +2756,86 @@ fn build_completion_extra_messages(
 
 /// Resolve context for chat requests (without history, only recent commands with output).
 /// This is used for LLM chat/analysis requests where we only want recent commands.
-async fn resolve_chat_context(req: &Request, mgr: &SessionManager, max_context_chars: Option<usize>) -> Result<String> {
+/// `req.format_hint` picks the layout; unknown values fall back to the config.
+async fn resolve_chat_context(req: &Request, mgr: &SessionManager, budget: Option<TokenBudget>) -> Result<String> {
+    let format = req.format_hint.as_deref().and_then(|h| {
+        let format = omnish_common::config::ContextFormat::parse(h);
+        if format.is_none() {
+            tracing::warn!("ignoring unknown context format hint '{}'", h);
+        }
+        format
+    });
     match &req.scope {
-        RequestScope::CurrentSession => mgr.get_chat_context(&req.session_id, max_context_chars).await,
-        RequestScope::AllSessions => mgr.get_all_sessions_chat_context(&req.session_id, max_context_chars).await,
-        RequestScope::Sessions(ids) => {
-            let mut combined = String::new();
-            for sid in ids {
-                match mgr.get_chat_context(sid, max_context_chars).await {
-                    Ok(ctx) => {
-                        combined.push_str(&format!("\n=== Session {} ===\n", sid));
-                        combined.push_str(&ctx);
-                    }
-                    Err(e) => {
-                        tracing::warn!("Failed to get chat context for session {}: {}", sid, e);
-                    }
-                }
+        RequestScope::CurrentSession => mgr.get_chat_context(&req.session_id, budget, format).await,
+        RequestScope::AllSessions => mgr.get_all_sessions_chat_context(&req.session_id, budget, format).await,
+        RequestScope::Sessions(ids) => Ok(combined_chat_context(ids, mgr, budget, format).await),
+        RequestScope::HostSessions(host) => {
+            mgr.get_host_sessions_chat_context(host, &req.session_id, budget, format).await
+        }
+    }
+}
+
+/// Chat contexts of `ids`, each under a `=== Session <id> ===` header.
+async fn combined_chat_context(
+    ids: &[String],
+    mgr: &SessionManager,
+    budget: Option<TokenBudget>,
+    format: Option<omnish_common::config::ContextFormat>,
+) -> String {
+    let mut combined = String::new();
+    for sid in ids {
+        match mgr.get_chat_context(sid, budget.clone(), format).await {
+            Ok(ctx) => {
+                combined.push_str(&format!("\n=== Session {} ===\n", sid));
+                combined.push_str(&ctx);
+            }
+            Err(e) => {
+                tracing::warn!("Failed to get chat context for session {}: {}", sid, e);
             }
-            Ok(combined)
         }
     }
+    combined
 }
 
 
+/// Command lines of the most recently active session other than `exclude`,
+/// newest first, capped at `limit`.
+fn last_session_command_lines(commands: &[omnish_store::command::CommandRecord], exclude: &str, limit: usize) -> Vec<String> {
+    let Some(last_sid) = commands
+        .iter()
+        .filter(|c| c.session_id != exclude)
+        .max_by_key(|c| c.started_at)
+        .map(|c| c.session_id.as_str())
+    else {
+        return Vec::new();
+    };
+    let mut lines: Vec<(u64, &str)> = commands
+        .iter()
+        .filter(|c| c.session_id == last_sid)
+        .filter_map(|c| c.command_line.as_deref().map(|l| (c.started_at, l)))
+        .collect();
+    lines.sort_by_key(|l| std::cmp::Reverse(l.0));
+    lines.into_iter().take(limit).map(|(_, l)| l.to_string()).collect()
+}
+
+/// Distinct command lines across all sessions, newest first, capped at
+/// `limit`. Feeds the client's `/hist` browser.
+fn all_command_lines(commands: &[omnish_store::command::CommandRecord], limit: usize) -> Vec<String> {
+    let mut lines: Vec<(u64, &str)> = commands
+        .iter()
+        .filter_map(|c| c.command_line.as_deref().map(|l| (c.started_at, l.trim())))
+        .filter(|(_, l)| !l.is_empty())
+        .collect();
+    lines.sort_by_key(|l| std::cmp::Reverse(l.0));
+    let mut seen = std::collections::HashSet::new();
+    lines
+        .into_iter()
+        .filter(|(_, l)| seen.insert(*l))
+        .take(limit)
+        .map(|(_, l)| l.to_string())
+        .collect()
+}
+
 /// Helper to create a command response with only a display string.
 fn cmd_display(s: impl Into<String>) -> serde_json::Value {
     serde_json::json!({ "display": s.into() })
@@ -2670,6 +3019,153 @@ async fn handle_builtin_command(req: &Request, ctx: &HandlerCtx, llm_backend: &A
     let active_threads = &ctx.active_threads;
     let sub = req.query.strip_prefix("__cmd:").unwrap_or("");
 
+    // Handle history - command lines from the most recently active other
+    // session, most recent first. Seeds the client's ghost-text history.
+    if sub == "history" {
+        let (commands, _) = mgr.get_all_commands_with_reader().await;
+        return serde_json::json!({
+            "display": "",
+            "commands": last_session_command_lines(&commands, &req.session_id, 500),
+        });
+    }
+
+    // Handle history all - distinct command lines of every session, most
+    // recent first, for the client's /hist browser.
+    if sub == "history all" {
+        let (commands, _) = mgr.get_all_commands_with_reader().await;
+        return serde_json::json!({
+            "display": "",
+            "commands": all_command_lines(&commands, 1000),
+        });
+    }
+
+    // Handle /history [N] - this session's recent LLM queries
+    if sub == "history queries" || sub.starts_with("history queries ") {
+        let limit = sub["history queries".len()..].trim().parse().unwrap_or(10);
+        return cmd_display(format_query_history(&mgr.get_query_history(&req.session_id, limit)));
+    }
+
+    // Handle /grep <pattern> (also `grep:<pattern>`) - every matching output
+    // line with context
+    if let Some(rest) = sub.strip_prefix("grep").filter(|r| r.is_empty() || r.starts_with([' ', ':'])) {
+        let pattern = rest.trim_start_matches(':').trim();
+        let Some(re) = omnish_daemon::search::compile_pattern(pattern) else {
+            return cmd_display("Usage: /grep <pattern>");
+        };
+        return match mgr.grep_with_output(pattern, 20).await {
+            Ok(results) => cmd_display(omnish_daemon::search::SearchFormatter::new(&re).format_grep(&results)),
+            Err(e) => cmd_display(format!("grep failed: {}", e)),
+        };
+    }
+
+    // Handle /search <pattern> - regex search over command lines and output
+    if sub == "search" || sub.starts_with("search ") {
+        let pattern = sub["search".len()..].trim();
+        if pattern.is_empty() {
+            return cmd_display("Usage: /search <pattern>");
+        }
+        let results = mgr.search_commands(pattern, 20).await;
+        return match omnish_daemon::search::compile_pattern(pattern) {
+            Some(re) => cmd_display(omnish_daemon::search::SearchFormatter::new(&re).format(&results)),
+            None => cmd_display("Usage: /search <pattern>"),
+        };
+    }
+
+    // Handle /stats - command statistics for this session
+    if sub == "stats" {
+        return match mgr.get_statistics(&req.session_id).await {
+            Ok(stats) => cmd_display(omnish_daemon::stats::StatsFormatter::format(&stats)),
+            Err(e) => cmd_display(format!("Error: {}", e)),
+        };
+    }
+
+    // Handle /tag <pattern> <label> - label matching commands of this session
+    if sub == "tag" || sub.starts_with("tag ") {
+        let Some((pattern, label)) = sub["tag".len()..].trim().rsplit_once(' ') else {
+            return cmd_display("Usage: /tag <pattern> <label>");
+        };
+        return match mgr.tag_commands(&req.session_id, pattern.trim(), label).await {
+            Ok(n) => cmd_display(format!("Tagged {} command(s) with [{}]", n, label)),
+            Err(e) => cmd_display(format!("Tag failed: {}", e)),
+        };
+    }
+
+    // Handle /tag-session <session> <tags...> - label a whole session; no
+    // tags clears them
+    if sub == "tag-session" || sub.starts_with("tag-session ") {
+        let mut args = sub["tag-session".len()..]
+            .split(|c: char| c.is_whitespace() || c == ',')
+            .filter(|a| !a.is_empty());
+        let Some(id) = args.next() else {
+            return cmd_display("Usage: /tag-session <session> <tag>...");
+        };
+        let mut tags: Vec<String> = Vec::new();
+        for tag in args {
+            if !tags.iter().any(|t| t == tag) {
+                tags.push(tag.to_string());
+            }
+        }
+        let description = mgr.get_session_meta(id).await.and_then(|m| m.description);
+        let msg = if tags.is_empty() {
+            format!("Cleared tags of {}", id)
+        } else {
+            format!("Tagged session {} with [{}]", id, tags.join(", "))
+        };
+        return match mgr.set_session_metadata(id, tags, description).await {
+            Ok(()) => cmd_display(msg),
+            Err(e) => cmd_display(format!("Tag failed: {}", e)),
+        };
+    }
+
+    // Handle /describe-session <session> <description> - note on a whole
+    // session; no description clears it
+    if sub == "describe-session" || sub.starts_with("describe-session ") {
+        let rest = sub["describe-session".len()..].trim();
+        let (id, description) = rest.split_once(' ').unwrap_or((rest, ""));
+        if id.is_empty() {
+            return cmd_display("Usage: /describe-session <session> <description>");
+        }
+        let description = description.trim();
+        let description = (!description.is_empty()).then(|| description.to_string());
+        let tags = mgr.get_session_meta(id).await.map(|m| m.tags).unwrap_or_default();
+        let msg = match &description {
+            Some(d) => format!("Described {}: {}", id, d),
+            None => format!("Cleared description of {}", id),
+        };
+        return match mgr.set_session_metadata(id, tags, description).await {
+            Ok(()) => cmd_display(msg),
+            Err(e) => cmd_display(format!("Describe failed: {}", e)),
+        };
+    }
+
+    // Handle /merge <sess1> <sess2>... - combine ended sessions into a new one
+    if sub == "merge" || sub.starts_with("merge ") {
+        let ids: Vec<&str> = sub["merge".len()..].split_whitespace().collect();
+        if ids.len() < 2 {
+            return cmd_display("Usage: /merge <session> <session>...");
+        }
+        let target = uuid::Uuid::new_v4().to_string();
+        return match mgr.merge_sessions(&ids, &target).await {
+            Ok(()) => cmd_display(format!("Merged {} sessions into {}", ids.len(), target)),
+            Err(e) => cmd_display(format!("Merge failed: {}", e)),
+        };
+    }
+
+    // Handle /vacuum <session> - rewrite a session's stream.bin without gaps
+    if sub == "vacuum" || sub.starts_with("vacuum ") {
+        let id = sub["vacuum".len()..].trim();
+        if id.is_empty() {
+            return cmd_display("Usage: /vacuum <session>");
+        }
+        return match mgr.vacuum(id).await {
+            Ok(stats) => cmd_display(format!(
+                "Vacuumed {}: {} -> {} bytes in {} ms",
+                id, stats.bytes_before, stats.bytes_after, stats.duration_ms
+            )),
+            Err(e) => cmd_display(format!("Vacuum failed: {}", e)),
+        };
+    }
+
     // Build system-reminder for context display
     let (commands, stream_reader) = mgr.get_all_commands_with_reader().await;
     let command_query_tool = omnish_daemon::tools::command_query::CommandQueryTool::new(commands, stream_reader);
@@ -2861,6 +3357,10 @@ async fn handle_builtin_command(req: &Request, ctx: &HandlerCtx, llm_backend: &A
             }
         }
         "sessions" => cmd_display(mgr.format_sessions_list(&req.session_id).await),
+        s if s == "sessions --all" || s.starts_with("sessions --all ") => {
+            let page = s["sessions --all".len()..].trim().parse::<usize>().unwrap_or(1).max(1);
+            cmd_display(format_all_sessions(mgr, page).await)
+        }
         s if s == "conversations stats" || s.starts_with("conversations stats ") => {
             let limit = s
                 .strip_prefix("conversations stats")
@@ -2916,6 +3416,9 @@ async fn handle_builtin_command(req: &Request, ctx: &HandlerCtx, llm_backend: &A
         sub if sub == "tasks" || sub.starts_with("tasks ") => {
             cmd_display(handle_tasks_command(sub, task_mgr).await)
         }
+        sub if sub == "run-task" || sub.starts_with("run-task:") || sub.starts_with("run-task ") => {
+            cmd_display(handle_run_task_command(sub, task_mgr).await)
+        }
         other => cmd_display(format!("Unknown command: {}", other)),
     }
 }
@@ -3336,6 +3839,54 @@ async fn handle_tasks_command(
     }
 }
 
+/// `run-task:<name>` (or `run-task <name>`): fire a scheduled task now.
+async fn handle_run_task_command(sub: &str, task_mgr: &Mutex<TaskManager>) -> String {
+    let name = sub["run-task".len()..].trim_start_matches(':').trim();
+    let mgr = task_mgr.lock().await;
+    if name.is_empty() {
+        let names: Vec<String> = mgr.list_jobs().into_iter().map(|(name, _)| name).collect();
+        return format!("Usage: run-task <name>\nTasks: {}", names.join(", "));
+    }
+    match mgr.trigger_now(name) {
+        Ok(()) => format!("Triggered task '{}'", name),
+        Err(e) => format!("Error: {}", e),
+    }
+}
+
+/// Sessions per page of `/sessions --all`.
+const SESSIONS_PAGE_SIZE: usize = 20;
+
+/// `/sessions --all [page]`: every loaded session, ended ones included,
+/// newest first. `page` is 1-based.
+async fn format_all_sessions(mgr: &SessionManager, page: usize) -> String {
+    let (metas, total) = mgr.list_all_paged(page - 1, SESSIONS_PAGE_SIZE).await;
+    if total == 0 {
+        return "(no sessions)".to_string();
+    }
+    let pages = total.div_ceil(SESSIONS_PAGE_SIZE);
+    if metas.is_empty() {
+        return format!("No page {} (1-{})", page, pages);
+    }
+    let mut lines: Vec<String> = metas
+        .iter()
+        .map(|m| {
+            let host = m.attrs.get("hostname").map(String::as_str).unwrap_or("?");
+            let state = match &m.ended_at {
+                Some(ended) => format!("ended {}", ended),
+                None => "active".to_string(),
+            };
+            let tags = if m.tags.is_empty() {
+                String::new()
+            } else {
+                format!(" [{}]", m.tags.join(", "))
+            };
+            format!("  {} {} [{}] {}{}", m.session_id, m.started_at, host, state, tags)
+        })
+        .collect();
+    lines.push(format!("page {}/{}, {} session(s)", page, pages, total));
+    lines.join("\n")
+}
+
 async fn get_session_debug_info(session_id: &str, mgr: &SessionManager) -> Result<String> {
     let (meta, cmd_count, last_active_duration, last_update) = mgr.get_session_debug_info(session_id).await?;
     let commands = mgr.get_commands(session_id).await?;
@@ -3395,14 +3946,41 @@ async fn get_session_debug_info(session_id: &str, mgr: &SessionManager) -> Resul
     Ok(info)
 }
 
-async fn handle_llm_request(
+/// Answer a free-form `Request` with the chat model.
+///
+/// When the backend supports streaming, each piece of text is forwarded as a
+/// `StreamingChunk` as it arrives. Returns the full answer text and whether
+/// it was streamed.
+/// `handle_llm_request`, cancelled once the request's `timeout_ms` passes.
+/// The client has stopped waiting by then, so the LLM call is dropped.
+async fn handle_llm_request_with_deadline(
+    req: &Request,
+    mgr: &SessionManager,
+    backend: &Arc<MultiBackend>,
+    tx: &mpsc::Sender<Message>,
+) -> Result<(String, bool)> {
+    let Some(timeout_ms) = req.timeout_ms else {
+        return handle_llm_request(req, mgr, backend, tx).await;
+    };
+    let token = omnish_daemon::session_mgr::cancel_after(std::time::Duration::from_millis(timeout_ms));
+    // Also stops the timer once the request is done
+    let _guard = token.clone().drop_guard();
+    tokio::select! {
+        result = handle_llm_request(req, mgr, backend, tx) => result,
+        _ = token.cancelled() => Err(anyhow::anyhow!("query timed out after {}ms", timeout_ms)),
+    }
+}
+
+pub(crate) async fn handle_llm_request(
     req: &Request,
     mgr: &SessionManager,
     backend: &Arc<MultiBackend>,
-) -> Result<omnish_llm::backend::LlmResponse> {
+    tx: &mpsc::Sender<Message>,
+) -> Result<(String, bool)> {
     let use_case = UseCase::Chat;
     let max_context_chars = backend.get_max_content_chars(use_case);
-    let context = resolve_chat_context(req, mgr, max_context_chars).await?;
+    let budget = backend.get_context_budget(use_case);
+    let context = resolve_chat_context(req, mgr, budget).await?;
 
     let llm_req = LlmRequest {
         context,
@@ -3417,8 +3995,69 @@ async fn handle_llm_request(
         extra_messages: vec![],
     };
 
+    // Per-query model override (`/model <name> <query>`): reject unknown
+    // names up front so the user sees the error instead of a silent fallback.
+    if let Some(name) = req.model_override.as_deref() {
+        if backend.get_backend_by_name(name).is_none() {
+            return Err(anyhow::anyhow!("unknown model: {}", name));
+        }
+    }
+    // Without one, the project's `.omnish.toml` may pick the backend
+    let project_model = match req.model_override {
+        Some(_) => None,
+        None => mgr
+            .project_backend_name(&req.session_id, "chat")
+            .await
+            .filter(|name| backend.get_backend_by_name(name).is_some()),
+    };
+    let model_override = req.model_override.as_deref().or(project_model.as_deref());
+
     let start = std::time::Instant::now();
-    let result = backend.complete(&llm_req).await;
+    let stream = match model_override {
+        Some(name) => backend.stream_with_model(name, &llm_req).await,
+        None => backend.stream(&llm_req).await,
+    };
+    if let Some(stream) = stream {
+        let result = forward_stream(stream, &req.request_id, tx).await;
+        match &result {
+            Ok(text) => {
+                tracing::info!(
+                    "LLM request streamed in {:?} (session={}, chars={}, type=manual)",
+                    start.elapsed(),
+                    req.session_id,
+                    text.len()
+                );
+                let model = match model_override {
+                    Some(name) => name.to_string(),
+                    None => backend.model_name_for_use_case(use_case),
+                };
+                // Streams carry no usage report, so estimate it
+                let prompt = format!("{}{}", llm_req.context, req.query);
+                mgr.record_token_usage(omnish_store::usage::TokenUsageRecord {
+                    request_id: req.request_id.clone(),
+                    session_id: req.session_id.clone(),
+                    model: model.clone(),
+                    prompt_tokens: backend.estimate_tokens(&prompt) as u64,
+                    completion_tokens: backend.estimate_tokens(text) as u64,
+                    timestamp_ms: chrono::Utc::now().timestamp_millis() as u64,
+                    estimated: true,
+                });
+                mgr.record_query(query_record(req, text, model, start));
+            }
+            Err(e) => tracing::warn!(
+                "LLM stream failed after {:?} (session={}, error={})",
+                start.elapsed(),
+                req.session_id,
+                e
+            ),
+        }
+        return result.map(|text| (text, true));
+    }
+
+    let result = match model_override {
+        Some(name) => backend.complete_with_model(name, &llm_req).await,
+        None => backend.complete(&llm_req).await,
+    };
     let duration = start.elapsed();
 
     match &result {
@@ -3435,6 +4074,8 @@ async fn handle_llm_request(
                 tracing::info!("LLM thinking: {} chars", thinking.len());
                 tracing::debug!("LLM thinking content: {}", thinking);
             }
+            mgr.record_query(query_record(req, &response.text(), response.model.clone(), start));
+            record_response_usage(mgr, &req.request_id, &req.session_id, response);
         }
         Err(e) => {
             tracing::warn!(
@@ -3446,9 +4087,193 @@ async fn handle_llm_request(
         }
     }
 
+    result.map(|response| (response.text(), false))
+}
+
+/// Add the token usage a backend reported for a finished call to the
+/// session's totals. Calls without a usage report are skipped.
+fn record_response_usage(
+    mgr: &SessionManager,
+    request_id: &str,
+    session_id: &str,
+    response: &omnish_llm::backend::LlmResponse,
+) {
+    if let Some(ref u) = response.usage {
+        mgr.record_token_usage(omnish_store::usage::TokenUsageRecord {
+            request_id: request_id.to_string(),
+            session_id: session_id.to_string(),
+            model: response.model.clone(),
+            prompt_tokens: u.input_tokens,
+            completion_tokens: u.output_tokens,
+            timestamp_ms: chrono::Utc::now().timestamp_millis() as u64,
+            estimated: false,
+        });
+    }
+}
+
+fn query_record(
+    req: &Request,
+    response: &str,
+    model: String,
+    start: std::time::Instant,
+) -> omnish_store::query_log::QueryRecord {
+    omnish_store::query_log::QueryRecord {
+        request_id: req.request_id.clone(),
+        session_id: req.session_id.clone(),
+        query: req.query.clone(),
+        response: response.to_string(),
+        model,
+        latency_ms: start.elapsed().as_millis() as u64,
+        timestamp_ms: chrono::Utc::now().timestamp_millis() as u64,
+    }
+}
+
+/// Render `/history` output: one block per query, oldest first, with the
+/// first lines of each answer.
+fn format_query_history(records: &[omnish_store::query_log::QueryRecord]) -> String {
+    const ANSWER_LINES: usize = 3;
+    if records.is_empty() {
+        return "No LLM queries recorded in this session".to_string();
+    }
+    let mut out = String::new();
+    for r in records {
+        let time = chrono::DateTime::from_timestamp_millis(r.timestamp_ms as i64)
+            .map(|t| t.with_timezone(&chrono::Local).format("%Y-%m-%d %H:%M").to_string())
+            .unwrap_or_default();
+        out.push_str(&format!("[{}] {} ({} ms)\n> {}\n", time, r.model, r.latency_ms, r.query));
+        let lines: Vec<&str> = r.response.lines().filter(|l| !l.trim().is_empty()).collect();
+        for line in lines.iter().take(ANSWER_LINES) {
+            out.push_str(&format!("  {}\n", line));
+        }
+        if lines.len() > ANSWER_LINES {
+            out.push_str("  ...\n");
+        }
+    }
+    out.truncate(out.trim_end().len());
+    out
+}
+
+/// `/replay <session_id> [speed]`: send the session's recorded output as
+/// `StreamingChunk` messages (raw terminal bytes, UTF-8 decoded), paced by
+/// `SessionManager::replay_stream`. Returns the JSON for the final Response.
+async fn handle_replay(
+    args: &str,
+    request_id: &str,
+    mgr: &SessionManager,
+    tx: &mpsc::Sender<Message>,
+) -> serde_json::Value {
+    use futures_util::StreamExt;
+
+    let mut parts = args.split_whitespace();
+    let (Some(session_id), speed, None) = (parts.next(), parts.next(), parts.next()) else {
+        return cmd_display("Usage: /replay <session_id> [speed]");
+    };
+    let speed = match speed.map(str::parse::<f32>) {
+        None => 1.0,
+        Some(Ok(v)) if v >= 0.0 => v,
+        Some(_) => return cmd_display("Usage: /replay <session_id> [speed]"),
+    };
+    let mut stream = match mgr.replay_stream(session_id, speed).await {
+        Ok(stream) => stream,
+        Err(e) => return cmd_display(format!("Error: {}", e)),
+    };
+
+    let mut pending = Vec::new();
+    let mut result = cmd_display("");
+    while let Some(item) = stream.next().await {
+        match item {
+            Ok(bytes) => {
+                pending.extend_from_slice(&bytes);
+                let chunk = drain_utf8(&mut pending);
+                if chunk.is_empty() {
+                    continue;
+                }
+                if tx.send(Message::StreamingChunk {
+                    request_id: request_id.to_string(),
+                    chunk,
+                    done: false,
+                }).await.is_err() {
+                    // Client went away; stop pacing a replay nobody sees.
+                    return result;
+                }
+            }
+            Err(e) => {
+                result = cmd_display(format!("Error: {}", e));
+                break;
+            }
+        }
+    }
+    let _ = tx.send(Message::StreamingChunk {
+        request_id: request_id.to_string(),
+        chunk: String::from_utf8_lossy(&pending).into_owned(),
+        done: true,
+    }).await;
     result
 }
 
+/// Decode `buf` except for a trailing incomplete UTF-8 sequence, which is
+/// left in `buf` for the next call. Invalid bytes become U+FFFD.
+fn drain_utf8(buf: &mut Vec<u8>) -> String {
+    let mut keep_from = buf.len();
+    for i in (buf.len().saturating_sub(3)..buf.len()).rev() {
+        let b = buf[i];
+        if b & 0xC0 == 0x80 {
+            continue;
+        }
+        let need = match b {
+            0xF0.. => 4,
+            0xE0.. => 3,
+            0xC0.. => 2,
+            _ => 1,
+        };
+        if buf.len() - i < need {
+            keep_from = i;
+        }
+        break;
+    }
+    let rest = buf.split_off(keep_from);
+    let out = String::from_utf8_lossy(buf).into_owned();
+    *buf = rest;
+    out
+}
+
+/// Drain a backend text stream into `StreamingChunk` messages, finishing with
+/// a `done = true` chunk. Returns the concatenated text.
+async fn forward_stream(
+    stream: Result<omnish_llm::backend::TextStream>,
+    request_id: &str,
+    tx: &mpsc::Sender<Message>,
+) -> Result<String> {
+    use futures_util::StreamExt;
+
+    let mut stream = stream?;
+    let mut full = String::new();
+    let mut result = Ok(());
+    while let Some(item) = stream.next().await {
+        match item {
+            Ok(chunk) if chunk.is_empty() => {}
+            Ok(chunk) => {
+                full.push_str(&chunk);
+                let _ = tx.send(Message::StreamingChunk {
+                    request_id: request_id.to_string(),
+                    chunk,
+                    done: false,
+                }).await;
+            }
+            Err(e) => {
+                result = Err(e);
+                break;
+            }
+        }
+    }
+    let _ = tx.send(Message::StreamingChunk {
+        request_id: request_id.to_string(),
+        chunk: String::new(),
+        done: true,
+    }).await;
+    result.map(|_| full)
+}
+
 async fn handle_completion_request(
     req: &omnish_protocol::message::CompletionRequest,
     mgr: &SessionManager,
@@ -3537,8 +4362,16 @@ async fn handle_completion_request(
         req.session_id, req.sequence_id, req.input.len(), prompt_words
     );
 
+    let project_model = mgr
+        .project_backend_name(&req.session_id, "completion")
+        .await
+        .filter(|name| backend.get_backend_by_name(name).is_some());
+
     let start = std::time::Instant::now();
-    let result = backend.complete(&llm_req).await;
+    let result = match project_model.as_deref() {
+        Some(name) => backend.complete_with_model(name, &llm_req).await,
+        None => backend.complete(&llm_req).await,
+    };
     let duration = start.elapsed();
 
     // Format duration: use %.3f when > 1s, otherwise show as milliseconds
@@ -3574,6 +4407,7 @@ async fn handle_completion_request(
                 );
             }
             tracing::debug!("Completion LLM raw response: {:?}", response.text());
+            record_response_usage(mgr, &format!("completion-{}", req.sequence_id), &req.session_id, response);
 
             // Log thinking length and content
             if let Some(ref thinking) = response.thinking() {
@@ -3769,6 +4603,385 @@ mod tests {
         assert_eq!(parse_omnish_debug_args("length"), (None, None));
     }
 
+    #[tokio::test]
+    async fn test_queue_depth_tracks_in_flight_requests() {
+        let counter = QueueDepthCounter::default();
+        let guard = counter.enter();
+        let task = {
+            let counter = counter.clone();
+            tokio::spawn(async move {
+                let g = counter.enter();
+                sleep(Duration::from_millis(30)).await;
+                g.finish(10, "show me the slow query")
+            })
+        };
+        sleep(Duration::from_millis(10)).await;
+        assert_eq!(counter.get(), 2);
+
+        let slow = task.await.unwrap();
+        assert!(slow, "30ms request should exceed the 10ms threshold");
+        assert_eq!(counter.get(), 1);
+
+        assert!(!guard.finish(0, "disabled"), "warn_ms = 0 disables the warning");
+        assert_eq!(counter.get(), 0);
+    }
+
+    #[tokio::test]
+    async fn test_forward_stream_emits_chunks_then_done() {
+        let items: Vec<Result<String>> = vec![Ok("Hel".into()), Ok(String::new()), Ok("lo".into())];
+        let stream: omnish_llm::backend::TextStream = Box::pin(futures_util::stream::iter(items));
+        let (tx, mut rx) = mpsc::channel(16);
+        let full = forward_stream(Ok(stream), "r1", &tx).await.unwrap();
+        assert_eq!(full, "Hello");
+        drop(tx);
+
+        let mut chunks = Vec::new();
+        while let Some(Message::StreamingChunk { request_id, chunk, done }) = rx.recv().await {
+            assert_eq!(request_id, "r1");
+            chunks.push((chunk, done));
+        }
+        assert_eq!(chunks, vec![
+            ("Hel".to_string(), false),
+            ("lo".to_string(), false),
+            (String::new(), true),
+        ]);
+    }
+
+    #[tokio::test]
+    async fn test_forward_stream_error_still_sends_done() {
+        let items: Vec<Result<String>> = vec![Ok("partial".into()), Err(anyhow::anyhow!("boom"))];
+        let stream: omnish_llm::backend::TextStream = Box::pin(futures_util::stream::iter(items));
+        let (tx, mut rx) = mpsc::channel(16);
+        assert!(forward_stream(Ok(stream), "r2", &tx).await.is_err());
+        drop(tx);
+
+        let mut last_done = false;
+        while let Some(Message::StreamingChunk { done, .. }) = rx.recv().await {
+            last_done = done;
+        }
+        assert!(last_done);
+    }
+
+    #[test]
+    fn test_drain_utf8_keeps_partial_char() {
+        let mut buf = "ok \u{e9}".as_bytes().to_vec();
+        let split = buf.pop().unwrap();
+        assert_eq!(drain_utf8(&mut buf), "ok ");
+        assert_eq!(buf.len(), 1);
+        buf.push(split);
+        assert_eq!(drain_utf8(&mut buf), "\u{e9}");
+        assert!(buf.is_empty());
+    }
+
+    #[tokio::test]
+    async fn test_replay_streams_output_then_done() {
+        let dir = tempfile::tempdir().unwrap();
+        let mgr = SessionManager::new(dir.path().to_path_buf(), Default::default());
+        mgr.register("s1", None, HashMap::new(), None).await.unwrap();
+        mgr.write_io("s1", 1_000, 1, b"$ ls\r\n").await.unwrap();
+        mgr.write_io("s1", 1_050, 1, b"a.txt\r\n").await.unwrap();
+
+        let (tx, mut rx) = mpsc::channel(16);
+        let result = handle_replay("s1 0", "r1", &mgr, &tx).await;
+        assert_eq!(result["display"], "");
+        drop(tx);
+        let mut chunks = Vec::new();
+        while let Some(Message::StreamingChunk { request_id, chunk, done }) = rx.recv().await {
+            assert_eq!(request_id, "r1");
+            chunks.push((chunk, done));
+        }
+        assert_eq!(chunks, vec![
+            ("$ ls\r\n".to_string(), false),
+            ("a.txt\r\n".to_string(), false),
+            (String::new(), true),
+        ]);
+
+        let (tx, _rx) = mpsc::channel(16);
+        let usage = handle_replay("", "r2", &mgr, &tx).await;
+        assert!(usage["display"].as_str().unwrap().starts_with("Usage"));
+        let missing = handle_replay("nope", "r3", &mgr, &tx).await;
+        assert!(missing["display"].as_str().unwrap().contains("not found"));
+    }
+
+    /// A `HandlerCtx` over `mgr` with empty plugin, task and thread state.
+    async fn test_ctx(mgr: Arc<SessionManager>, dir: &std::path::Path) -> HandlerCtx {
+        let llm = Arc::new(MultiBackend::from_single(Arc::new(NamedBackend("default"))));
+        HandlerCtx {
+            session_mgr: mgr,
+            llm_holder: Arc::new(std::sync::RwLock::new(llm)),
+            task_mgr: Arc::new(Mutex::new(TaskManager::new().await.unwrap())),
+            conv_mgr: Arc::new(ConversationManager::new(dir.join("threads"))),
+            plugin_mgr: Arc::new(PluginManager::load(&dir.join("plugins"), &HashMap::new())),
+            tool_registry: Arc::new(omnish_daemon::tool_registry::ToolRegistry::new()),
+            formatter_mgr: Arc::new(omnish_daemon::formatter_mgr::FormatterManager::new()),
+            pending_loops: Default::default(),
+            cancel_flags: Default::default(),
+            thread_generations: Default::default(),
+            active_threads: Default::default(),
+            opts: Arc::new(ServerOpts {
+                sandbox_rules: Default::default(),
+                config_path: dir.join("daemon.toml"),
+                daemon_config: Default::default(),
+            }),
+            update_cache: Arc::new(omnish_daemon::update_cache::UpdateCache::new(dir)),
+            plugin_bundler: Arc::new(omnish_daemon::plugin_bundle::PluginBundler::new(dir.join("plugins"))),
+            io_requests: Default::default(),
+            io_bytes: Default::default(),
+            queue_depth: Default::default(),
+            push_registry: Default::default(),
+            workspace_detector: Arc::new(omnish_daemon::workspace::WorkspaceDetector::new()),
+            started_at: std::time::Instant::now(),
+        }
+    }
+
+    #[tokio::test]
+    async fn test_batch_handles_inner_messages_in_order() {
+        let dir = tempfile::tempdir().unwrap();
+        let mgr = Arc::new(SessionManager::new(dir.path().to_path_buf(), Default::default()));
+        mgr.register("s1", None, HashMap::new(), None).await.unwrap();
+        let ctx = test_ctx(mgr.clone(), dir.path()).await;
+
+        let io = |data: &str| Message::IoData(IoData {
+            session_id: "s1".into(),
+            direction: IoDirection::Output,
+            timestamp_ms: 1000,
+            data: data.as_bytes().to_vec(),
+        });
+        let (tx, mut rx) = mpsc::channel(16);
+        handle_message(
+            Message::Batch { messages: vec![io("one "), io("two "), io("three")] },
+            &ctx,
+            tx,
+        )
+        .await;
+
+        match rx.recv().await {
+            Some(Message::BatchResponse { results }) => {
+                assert_eq!(results.len(), 3);
+                assert!(results.iter().all(|r| matches!(r, BatchResult::Ok(m) if matches!(**m, Message::Ack))));
+            }
+            other => panic!("expected BatchResponse, got {:?}", other),
+        }
+        assert!(rx.recv().await.is_none(), "a batch is answered exactly once");
+        assert_eq!(ctx.io_requests.load(Ordering::Relaxed), 3);
+
+        use futures_util::StreamExt;
+        let replayed: Vec<u8> = mgr
+            .replay_stream("s1", 0.0)
+            .await
+            .unwrap()
+            .map(|chunk| chunk.unwrap())
+            .concat()
+            .await;
+        assert_eq!(String::from_utf8(replayed).unwrap(), "one two three");
+    }
+
+    #[test]
+    fn test_message_kind_names_variant_only() {
+        assert_eq!(message_kind(&Message::Ack), "Ack");
+        let io = Message::IoData(IoData {
+            session_id: "s1".into(),
+            direction: IoDirection::Output,
+            timestamp_ms: 1000,
+            data: b"password=hunter2".to_vec(),
+        });
+        assert_eq!(message_kind(&io), "IoData");
+        assert_eq!(message_kind(&Message::ConfigUpdate { changes: vec![] }), "ConfigUpdate");
+    }
+
+    #[tokio::test]
+    async fn test_batch_isolates_failing_message() {
+        let dir = tempfile::tempdir().unwrap();
+        let mgr = Arc::new(SessionManager::new(dir.path().to_path_buf(), Default::default()));
+        mgr.register("s1", None, HashMap::new(), None).await.unwrap();
+        let ctx = test_ctx(mgr.clone(), dir.path()).await;
+
+        let io = |data: &str| Message::IoData(IoData {
+            session_id: "s1".into(),
+            direction: IoDirection::Output,
+            timestamp_ms: 1000,
+            data: data.as_bytes().to_vec(),
+        });
+        let results = handle_batch(
+            vec![
+                Message::Batch { messages: vec![] },
+                io("kept"),
+            ],
+            &ctx,
+        )
+        .await;
+
+        assert_eq!(results.len(), 2);
+        let err = results[0].as_ref().unwrap_err().to_string();
+        assert_eq!(err, "Batch cannot be batched");
+        assert!(matches!(results[1], Ok(Message::Ack)));
+
+        use futures_util::StreamExt;
+        let replayed: Vec<u8> = mgr
+            .replay_stream("s1", 0.0)
+            .await
+            .unwrap()
+            .map(|chunk| chunk.unwrap())
+            .concat()
+            .await;
+        assert_eq!(String::from_utf8(replayed).unwrap(), "kept");
+    }
+
+    #[tokio::test]
+    async fn test_run_task_command_triggers_job() {
+        let ran = Arc::new(std::sync::Mutex::new(false));
+        let flag = ran.clone();
+        let job = omnish_daemon::task_mgr::TaskJob::new("0 0 0 1 1 *", move || {
+            let flag = flag.clone();
+            Box::pin(async move {
+                *flag.lock().unwrap() = true;
+            })
+        })
+        .unwrap();
+        let mut mgr = TaskManager::new().await.unwrap();
+        mgr.register("demo", "0 0 0 1 1 *", job).await.unwrap();
+        let task_mgr = Mutex::new(mgr);
+
+        assert_eq!(handle_run_task_command("run-task:demo", &task_mgr).await, "Triggered task 'demo'");
+        for _ in 0..100 {
+            if *ran.lock().unwrap() {
+                break;
+            }
+            tokio::time::sleep(std::time::Duration::from_millis(10)).await;
+        }
+        assert!(*ran.lock().unwrap());
+
+        assert!(handle_run_task_command("run-task missing", &task_mgr).await.starts_with("Error:"));
+        assert!(handle_run_task_command("run-task", &task_mgr).await.contains("demo"));
+    }
+
+    #[tokio::test]
+    async fn test_run_serves_unix_and_tcp_together() {
+        use omnish_transport::rpc_client::RpcClient;
+
+        let dir = tempfile::tempdir().unwrap();
+        let mgr = Arc::new(SessionManager::new(dir.path().to_path_buf(), Default::default()));
+        let ctx = test_ctx(mgr, dir.path()).await;
+        let server = DaemonServer::new(
+            ctx.session_mgr,
+            ctx.llm_holder,
+            ctx.task_mgr,
+            ctx.conv_mgr,
+            ctx.plugin_mgr,
+            ctx.tool_registry,
+            ctx.opts,
+            ctx.formatter_mgr,
+            ctx.update_cache,
+            ctx.plugin_bundler,
+        );
+
+        let sock = dir.path().join("multi.sock").to_str().unwrap().to_string();
+        let port = std::net::TcpListener::bind("127.0.0.1:0").unwrap().local_addr().unwrap().port();
+        let tcp = format!("127.0.0.1:{}", port);
+        let addrs = vec![sock.clone(), tcp.clone()];
+        tokio::spawn(async move {
+            let _ = server.run(&addrs, "secret".to_string(), None, 0o600).await;
+        });
+
+        for addr in [&sock, &tcp] {
+            let mut client = None;
+            for _ in 0..50 {
+                match RpcClient::connect(addr).await {
+                    Ok(c) => {
+                        client = Some(c);
+                        break;
+                    }
+                    Err(_) => tokio::time::sleep(std::time::Duration::from_millis(20)).await,
+                }
+            }
+            let client = client.unwrap_or_else(|| panic!("could not connect to {}", addr));
+            let auth = client
+                .call(Message::Auth(Auth {
+                    token: "secret".to_string(),
+                    protocol_version: PROTOCOL_VERSION,
+                }))
+                .await
+                .unwrap();
+            assert!(matches!(auth, Message::AuthResult(ref r) if r.ok), "auth via {}", addr);
+            let reply = client.call(Message::HealthCheck).await.unwrap();
+            assert!(matches!(reply, Message::HealthStatus { .. }), "reply via {}", addr);
+        }
+    }
+
+    #[test]
+    fn test_last_session_command_lines() {
+        let rec = |sid: &str, started_at: u64, line: &str| omnish_store::command::CommandRecord {
+            command_id: format!("{}-{}", sid, started_at),
+            session_id: sid.into(),
+            command_line: Some(line.into()),
+            cwd: None,
+            started_at,
+            ended_at: None,
+            output_summary: String::new(),
+            stream_offset: 0,
+            stream_length: 0,
+            exit_code: None,
+            exit_signal: None,
+            checksum: None,
+            tags: Vec::new(),
+        };
+        let commands = vec![
+            rec("old", 1, "ls"),
+            rec("last", 2, "git status"),
+            rec("last", 3, "git commit"),
+            rec("me", 4, "pwd"),
+        ];
+        assert_eq!(
+            last_session_command_lines(&commands, "me", 10),
+            vec!["git commit".to_string(), "git status".to_string()]
+        );
+        assert_eq!(last_session_command_lines(&commands, "me", 1), vec!["git commit".to_string()]);
+        assert!(last_session_command_lines(&commands[3..], "me", 10).is_empty());
+    }
+
+    #[test]
+    fn test_format_query_history() {
+        let rec = omnish_store::query_log::QueryRecord {
+            request_id: "r1".into(),
+            session_id: "s1".into(),
+            query: "why does curl fail".into(),
+            response: "a\n\nb\nc\nd".into(),
+            model: "gpt-4".into(),
+            latency_ms: 850,
+            timestamp_ms: 0,
+        };
+        let out = format_query_history(&[rec]);
+        assert!(out.contains("gpt-4 (850 ms)\n> why does curl fail\n  a\n  b\n  c\n  ..."), "{out}");
+        assert_eq!(format_query_history(&[]), "No LLM queries recorded in this session");
+    }
+
+    #[test]
+    fn test_all_command_lines_dedups_newest_first() {
+        let rec = |sid: &str, started_at: u64, line: &str| omnish_store::command::CommandRecord {
+            command_id: format!("{}-{}", sid, started_at),
+            session_id: sid.into(),
+            command_line: Some(line.into()),
+            cwd: None,
+            started_at,
+            ended_at: None,
+            output_summary: String::new(),
+            stream_offset: 0,
+            stream_length: 0,
+            exit_code: None,
+            exit_signal: None,
+            checksum: None,
+            tags: Vec::new(),
+        };
+        let commands = vec![
+            rec("a", 1, "ls"),
+            rec("b", 2, "git status"),
+            rec("a", 3, "ls"),
+            rec("b", 4, "  "),
+        ];
+        assert_eq!(all_command_lines(&commands, 10), vec!["ls".to_string(), "git status".to_string()]);
+        assert_eq!(all_command_lines(&commands, 1), vec!["ls".to_string()]);
+    }
+
     #[test]
     fn test_short_host() {
         // Missing / empty host (legacy threads): renders as "?".
@@ -3974,6 +5187,248 @@ mod tests {
         assert!(result.is_empty());
     }
 
+    /// Answers with its own name so tests can tell which backend was used.
+    struct NamedBackend(&'static str);
+
+    #[async_trait]
+    impl LlmBackend for NamedBackend {
+        async fn complete(&self, _req: &LlmRequest) -> Result<LlmResponse> {
+            Ok(LlmResponse {
+                content: vec![ContentBlock::Text(self.0.to_string())],
+                stop_reason: StopReason::EndTurn,
+                model: self.0.to_string(),
+                usage: None,
+            })
+        }
+
+        fn name(&self) -> &str {
+            self.0
+        }
+
+        fn model_name(&self) -> &str {
+            self.0
+        }
+    }
+
+    #[tokio::test]
+    async fn test_llm_request_model_override_selects_backend() {
+        let dir = tempfile::tempdir().unwrap();
+        let mgr = SessionManager::new(dir.path().to_path_buf(), Default::default());
+        mgr.register("s1", None, std::collections::HashMap::new(), None)
+            .await
+            .unwrap();
+        let backend = Arc::new(
+            MultiBackend::from_single(Arc::new(NamedBackend("default")))
+                .with_backend(Arc::new(NamedBackend("gpt-4"))),
+        );
+        let (tx, _rx) = mpsc::channel(8);
+        let req = |model: Option<&str>| Request {
+            request_id: "r1".into(),
+            session_id: "s1".into(),
+            query: "why does curl fail".into(),
+            scope: RequestScope::CurrentSession,
+            model_override: model.map(String::from),
+            timeout_ms: None,
+            format_hint: None,
+        };
+
+        let (text, _) = handle_llm_request(&req(Some("gpt-4")), &mgr, &backend, &tx).await.unwrap();
+        assert_eq!(text, "gpt-4");
+        let (text, _) = handle_llm_request(&req(None), &mgr, &backend, &tx).await.unwrap();
+        assert_eq!(text, "default");
+        let err = handle_llm_request(&req(Some("nope")), &mgr, &backend, &tx).await.unwrap_err();
+        assert!(err.to_string().contains("unknown model: nope"));
+
+        // Answered queries are logged with the model that answered them.
+        tokio::time::sleep(Duration::from_millis(100)).await;
+        let models: Vec<String> = mgr.get_query_history("s1", 10).into_iter().map(|r| r.model).collect();
+        assert_eq!(models, vec!["gpt-4", "default"]);
+    }
+
+    /// Records the user content of the last request it answers.
+    struct CapturingBackend(std::sync::Mutex<Option<String>>);
+
+    #[async_trait]
+    impl LlmBackend for CapturingBackend {
+        async fn complete(&self, req: &LlmRequest) -> Result<LlmResponse> {
+            let content = omnish_llm::template::build_user_content(&req.context, req.query.as_deref());
+            *self.0.lock().unwrap() = Some(content);
+            Ok(LlmResponse {
+                content: vec![ContentBlock::Text("ok".to_string())],
+                stop_reason: StopReason::EndTurn,
+                model: "capture".to_string(),
+                usage: None,
+            })
+        }
+
+        fn name(&self) -> &str {
+            "capture"
+        }
+
+        fn model_name(&self) -> &str {
+            "capture"
+        }
+    }
+
+    #[tokio::test]
+    async fn test_llm_request_fits_context_window() {
+        let dir = tempfile::tempdir().unwrap();
+        let mgr = SessionManager::new(dir.path().to_path_buf(), Default::default());
+        mgr.register("s1", None, std::collections::HashMap::new(), None)
+            .await
+            .unwrap();
+        for i in 0..30u64 {
+            mgr.receive_command(
+                "s1",
+                omnish_store::command::CommandRecord {
+                    command_id: format!("c{}", i),
+                    session_id: "s1".into(),
+                    command_line: Some(format!("cargo build -p crate{}", i)),
+                    cwd: Some("/src".into()),
+                    started_at: 1000 + i,
+                    ended_at: Some(1001 + i),
+                    output_summary: "warning: unused variable `x`".into(),
+                    stream_offset: 0,
+                    stream_length: 0,
+                    exit_code: Some(0),
+                    exit_signal: None,
+                    checksum: None,
+                    tags: Vec::new(),
+                },
+            )
+            .await
+            .unwrap();
+        }
+
+        // Unbudgeted, the context alone would fill the whole window
+        let full = mgr.get_chat_context("s1", None, None).await.unwrap();
+        let context_window = omnish_llm::tokens::token_count(&full);
+
+        let capture = Arc::new(CapturingBackend(std::sync::Mutex::new(None)));
+        let backend = Arc::new(MultiBackend::from_single(capture.clone()).with_context_window(context_window));
+        let (tx, _rx) = mpsc::channel(8);
+        let req = Request {
+            request_id: "r1".into(),
+            session_id: "s1".into(),
+            query: "why are there so many warnings".into(),
+            scope: RequestScope::CurrentSession,
+            model_override: None,
+            timeout_ms: None,
+            format_hint: None,
+        };
+        handle_llm_request(&req, &mgr, &backend, &tx).await.unwrap();
+
+        let sent = capture.0.lock().unwrap().clone().unwrap();
+        assert!(sent.contains("cargo build"), "{sent}");
+        // The prompt fits with the response's share of the window to spare
+        let prompt_tokens = capture.estimate_tokens(&sent);
+        assert!(
+            prompt_tokens + omnish_llm::tokens::reserved_tokens(context_window) / 2 <= context_window,
+            "{prompt_tokens} tokens sent to a {context_window}-token model"
+        );
+    }
+
+    /// Streams a fixed answer in two chunks; `complete` is never used.
+    struct StreamingBackend;
+
+    #[async_trait]
+    impl LlmBackend for StreamingBackend {
+        async fn complete(&self, _req: &LlmRequest) -> Result<LlmResponse> {
+            anyhow::bail!("stream only")
+        }
+
+        async fn stream(&self, _req: &LlmRequest) -> Option<Result<omnish_llm::backend::TextStream>> {
+            let chunks = vec![Ok("curl needs ".to_string()), Ok("--proxy here".to_string())];
+            Some(Ok(Box::pin(futures_util::stream::iter(chunks))))
+        }
+
+        fn name(&self) -> &str {
+            "streaming"
+        }
+
+        fn model_name(&self) -> &str {
+            "streaming"
+        }
+    }
+
+    #[tokio::test]
+    async fn test_streamed_llm_request_records_usage() {
+        let dir = tempfile::tempdir().unwrap();
+        let mgr = SessionManager::new(dir.path().to_path_buf(), Default::default());
+        mgr.register("s1", None, std::collections::HashMap::new(), None)
+            .await
+            .unwrap();
+        let backend = Arc::new(MultiBackend::from_single(Arc::new(StreamingBackend)));
+        let (tx, _rx) = mpsc::channel(8);
+        let req = Request {
+            request_id: "r1".into(),
+            session_id: "s1".into(),
+            query: "why does curl fail".into(),
+            scope: RequestScope::CurrentSession,
+            model_override: None,
+            timeout_ms: None,
+            format_hint: None,
+        };
+
+        let (text, streamed) = handle_llm_request(&req, &mgr, &backend, &tx).await.unwrap();
+        assert_eq!((text.as_str(), streamed), ("curl needs --proxy here", true));
+        let usage = mgr.token_usage("s1");
+        assert!(usage.prompt_tokens > 0 && usage.completion_tokens > 0, "{:?}", usage);
+    }
+
+    #[tokio::test]
+    async fn test_llm_request_deadline_cancels_slow_backend() {
+        let dir = tempfile::tempdir().unwrap();
+        let mgr = SessionManager::new(dir.path().to_path_buf(), Default::default());
+        mgr.register("s1", None, std::collections::HashMap::new(), None)
+            .await
+            .unwrap();
+        let backend = Arc::new(MultiBackend::from_single(Arc::new(MockDelayedBackend::new(5000))));
+        let (tx, _rx) = mpsc::channel(8);
+        let req = Request {
+            request_id: "r1".into(),
+            session_id: "s1".into(),
+            query: "why does curl fail".into(),
+            scope: RequestScope::CurrentSession,
+            model_override: None,
+            timeout_ms: Some(100),
+            format_hint: None,
+        };
+
+        let start = std::time::Instant::now();
+        let err = handle_llm_request_with_deadline(&req, &mgr, &backend, &tx).await.unwrap_err();
+        assert_eq!(err.to_string(), "query timed out after 100ms");
+        assert!(start.elapsed() < Duration::from_secs(2), "{:?}", start.elapsed());
+    }
+
+    #[tokio::test]
+    async fn test_health_status_fields() {
+        let dir = tempfile::tempdir().unwrap();
+        let mgr = SessionManager::new(dir.path().to_path_buf(), Default::default());
+        for sid in ["s1", "s2"] {
+            mgr.register(sid, None, std::collections::HashMap::new(), None)
+                .await
+                .unwrap();
+        }
+
+        let llm = MultiBackend::from_single(Arc::new(NamedBackend("default")));
+        match health_status(&mgr, &llm, Duration::from_secs(90)).await {
+            Message::HealthStatus { uptime_secs, session_count, llm_available, stream_write_errors } => {
+                assert_eq!(uptime_secs, 90);
+                assert_eq!(session_count, 2);
+                assert!(llm_available);
+                assert_eq!(stream_write_errors, 0);
+            }
+            other => panic!("expected HealthStatus, got {:?}", other),
+        }
+
+        let unavailable = MultiBackend::from_single(Arc::new(omnish_llm::backend::UnavailableBackend));
+        assert!(matches!(
+            health_status(&mgr, &unavailable, Duration::ZERO).await,
+            Message::HealthStatus { llm_available: false, .. }
+        ));
+    }
+
     #[tokio::test]
     async fn test_concurrent_completion_requests() {
         // Create a real SessionManager with temp directory
@@ -1,19 +1,95 @@
 use anyhow::{anyhow, Result};
-use omnish_common::config::ContextConfig;
-use omnish_context::recent::{CompletionFormatter, CompletionSections, GroupedFormatter, RecentCommands};
-use omnish_context::StreamReader;
+use omnish_common::config::{find_project_config, load_project_config, merge_configs, CompletionContextConfig, ContextConfig, ContextFormat, DaemonConfig};
+use omnish_context::formatters::XmlFormatter;
+use omnish_context::recent::{is_excluded, CompletionFormatter, CompletionSections, CwdPreferenceStrategy, GroupedFormatter, InterleavedFormatter, RecentCommands, session_header_tags, TimeWindowStrategy};
+use omnish_context::{ContextFormatter, ContextStrategy, StreamReader};
+use omnish_context::format_utils::render_relative_times;
+use omnish_llm::tokens::TokenBudget;
+use crate::search::{GrepResult, SearchResult};
+use crate::stats::SessionStats;
 use omnish_store::command::CommandRecord;
 use omnish_store::completion::CompletionRecord;
+use omnish_store::query_log::QueryRecord;
+use omnish_store::redact::{redact_text, SecretFilter};
 use omnish_store::sample::{CompletionSample, PendingSample};
 use omnish_store::session::SessionMeta;
 use omnish_store::session_update::SessionUpdateRecord;
-use omnish_store::stream::{read_range, StreamEntry, StreamWriter};
+use omnish_store::usage::{TokenUsage, TokenUsageRecord};
+use futures_util::Stream;
+use omnish_store::stream::{
+    derive_session_key, read_entries, read_range, read_range_mmap, stream_len, StreamEntry, StreamWriter,
+};
 use std::collections::HashMap;
-use std::path::PathBuf;
+use std::path::{Path, PathBuf};
+use std::pin::Pin;
+use std::sync::atomic::{AtomicU64, Ordering};
 use std::sync::Arc;
 use std::sync::mpsc;
 use std::time::{Duration, Instant, SystemTime, UNIX_EPOCH};
 use tokio::sync::{Mutex, RwLock};
+use tokio_util::sync::CancellationToken;
+
+/// How long a context cached by `build_context_with_limit` stays valid.
+const CONTEXT_CACHE_TTL: Duration = Duration::from_secs(60);
+
+struct CachedContext {
+    session_id: String,
+    context: String,
+    built_at: Instant,
+}
+
+/// Built contexts keyed by `ContextCache::key`, so a context is reused
+/// until its commands change. Relative times are stored as placeholders
+/// (see `render_relative_times`), so cached text doesn't go stale.
+struct ContextCache {
+    entries: HashMap<u64, CachedContext>,
+    ttl: Duration,
+}
+
+impl ContextCache {
+    fn new(ttl: Duration) -> Self {
+        Self { entries: HashMap::new(), ttl }
+    }
+
+    /// Hash of the commands a context was built from and the build
+    /// parameters (`params`, e.g. command counts and format).
+    fn key(current_session_id: &str, commands: &[CommandRecord], params: impl std::hash::Hash) -> u64 {
+        use std::hash::{Hash, Hasher};
+        let mut hasher = rustc_hash::FxHasher::default();
+        current_session_id.hash(&mut hasher);
+        commands.len().hash(&mut hasher);
+        commands.last().map(|c| c.started_at).hash(&mut hasher);
+        params.hash(&mut hasher);
+        hasher.finish()
+    }
+
+    fn get(&self, key: u64) -> Option<&str> {
+        self.entries
+            .get(&key)
+            .filter(|e| e.built_at.elapsed() < self.ttl)
+            .map(|e| e.context.as_str())
+    }
+
+    fn insert(&mut self, key: u64, session_id: &str, context: String) {
+        let ttl = self.ttl;
+        self.entries.retain(|_, e| e.built_at.elapsed() < ttl);
+        self.entries.insert(key, CachedContext {
+            session_id: session_id.to_string(),
+            context,
+            built_at: Instant::now(),
+        });
+    }
+
+    fn invalidate_session(&mut self, session_id: &str) {
+        self.entries.retain(|_, e| e.session_id != session_id);
+    }
+}
+
+/// A parsed `.omnish.toml`; None when the file is invalid.
+struct ProjectConfigEntry {
+    mtime: std::time::SystemTime,
+    table: Option<Arc<toml::Table>>,
+}
 
 /// Minimum edit distance similarity to consider a completion a "near miss".
 const SAMPLE_SIMILARITY_THRESHOLD: f64 = 0.3;
@@ -22,36 +98,133 @@ const SAMPLE_RATE_LIMIT_SECS: u64 = 300; // 5 minutes
 /// Max elapsed time (seconds) between completion request and next command for sampling.
 const SAMPLE_MAX_ELAPSED_SECS: u64 = 15;
 
+/// Ranges larger than this are read through a memory map.
+const MMAP_READ_THRESHOLD: u64 = 1_048_576;
+
+/// Longest pause `replay_stream` reproduces, before speed scaling.
+const MAX_REPLAY_GAP: Duration = Duration::from_secs(5);
+
+/// Result of `SessionManager::vacuum`. Byte counts are stream.bin sizes on
+/// disk.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub struct VacuumStats {
+    pub bytes_before: u64,
+    pub bytes_after: u64,
+    pub duration_ms: u64,
+}
+
+/// Counts stream.bin trims. Readers remember the value they were built
+/// with; once it moves on, their offsets may point at shifted data, so they
+/// read nothing instead.
+#[derive(Clone, Default)]
+struct StreamEpoch(Arc<AtomicU64>);
+
+impl StreamEpoch {
+    fn current(&self) -> u64 {
+        self.0.load(Ordering::SeqCst)
+    }
+
+    fn bump(&self) {
+        self.0.fetch_add(1, Ordering::SeqCst);
+    }
+}
+
+/// A session's stream.bin and the key its entries are encrypted with, if
+/// the daemon has a master key.
+#[derive(Clone)]
+struct StreamFile {
+    path: PathBuf,
+    key: Option<[u8; 32]>,
+}
+
+impl StreamFile {
+    fn read_range(&self, offset: u64, length: u64) -> Result<Vec<StreamEntry>> {
+        read_range(&self.path, offset, length, self.key.as_ref())
+    }
+}
+
+/// Commands of one session whose command line or output matches `re`,
+/// reading at most `max_bytes` of output, newest commands first.
+fn search_session(stream: &StreamFile, commands: &[CommandRecord], re: &regex::Regex, max_bytes: u64) -> Vec<SearchResult> {
+    let mut results = Vec::new();
+    let mut bytes_read = 0u64;
+    for cmd in commands.iter().rev() {
+        let command_line = cmd.command_line.clone().unwrap_or_default();
+        let mut snippet = None;
+        if cmd.stream_length > 0 && bytes_read < max_bytes {
+            bytes_read += cmd.stream_length;
+            if let Ok(entries) = stream.read_range(cmd.stream_offset, cmd.stream_length) {
+                let raw: Vec<u8> = entries
+                    .into_iter()
+                    .filter(|e| e.direction == 1)
+                    .flat_map(|e| e.data)
+                    .collect();
+                let text = omnish_context::strip_ansi(&raw);
+                snippet = crate::search::snippet_around(&text, re);
+            }
+        }
+        if snippet.is_some() || re.is_match(&command_line) {
+            results.push(SearchResult {
+                command_line,
+                session_id: cmd.session_id.clone(),
+                started_at: cmd.started_at,
+                snippet: snippet.unwrap_or_default(),
+            });
+        }
+    }
+    results
+}
+
 struct FileStreamReader {
-    stream_path: PathBuf,
+    stream: StreamFile,
+    epoch: StreamEpoch,
+    built_at: u64,
 }
 
 impl StreamReader for FileStreamReader {
     fn read_command_output(&self, offset: u64, length: u64) -> Result<Vec<StreamEntry>> {
-        if length == 0 {
+        if length == 0 || self.epoch.current() != self.built_at {
             return Ok(Vec::new());
         }
-        read_range(&self.stream_path, offset, length)
+        if length > MMAP_READ_THRESHOLD {
+            return read_range_mmap(&self.stream.path, offset, length, self.stream.key.as_ref());
+        }
+        self.stream.read_range(offset, length)
     }
 }
 
 struct MultiSessionReader {
-    readers: HashMap<(u64, u64), PathBuf>,
+    readers: HashMap<(u64, u64), StreamFile>,
+    epoch: StreamEpoch,
+    built_at: u64,
 }
 
 impl StreamReader for MultiSessionReader {
     fn read_command_output(&self, offset: u64, length: u64) -> Result<Vec<StreamEntry>> {
-        if length == 0 {
+        if length == 0 || self.epoch.current() != self.built_at {
             return Ok(Vec::new());
         }
-        let path = self
+        let stream = self
             .readers
             .get(&(offset, length))
             .ok_or_else(|| anyhow!("no stream file for offset={}, length={}", offset, length))?;
-        read_range(path, offset, length)
+        stream.read_range(offset, length)
     }
 }
 
+/// Token that cancels itself after `timeout`.
+pub fn cancel_after(timeout: Duration) -> CancellationToken {
+    let token = CancellationToken::new();
+    let timer = token.clone();
+    tokio::spawn(async move {
+        tokio::select! {
+            _ = tokio::time::sleep(timeout) => timer.cancel(),
+            _ = timer.cancelled() => {}
+        }
+    });
+    token
+}
+
 /// Per-request cwd filter for `build_completion_sections`. When `Some`, the
 /// resulting `CompletionSections.cwd_history` is populated with command lines
 /// matching `(cwd, prefix)`. `None` skips cwd_history entirely (used by the
@@ -115,13 +288,20 @@ struct StreamWriterState {
 
 impl StreamWriterState {
     /// Ensure `writer` is open, lazily creating or appending to stream.bin.
-    /// Returns a mutable reference to the now-open writer.
-    fn ensure_writer(&mut self, stream_path: &std::path::Path) -> Result<&mut StreamWriter> {
+    /// `compress` and `encrypt` only apply when the file is created (encrypt
+    /// wins); appends keep the existing format. Returns a mutable reference
+    /// to the now-open writer.
+    fn ensure_writer(&mut self, stream: &StreamFile, compress: bool, encrypt: bool) -> Result<&mut StreamWriter> {
         if self.writer.is_none() {
-            let w = if stream_path.exists() {
-                StreamWriter::open_append(stream_path)?
+            let w = if stream.path.exists() {
+                StreamWriter::open_append(&stream.path, stream.key.as_ref())?
+            } else if encrypt {
+                let key = stream.key.ok_or_else(|| anyhow!("stream encryption enabled without a master key"))?;
+                StreamWriter::create_encrypted(&stream.path, &key)?
+            } else if compress {
+                StreamWriter::create_compressed(&stream.path)?
             } else {
-                StreamWriter::create(stream_path)?
+                StreamWriter::create(&stream.path)?
             };
             self.writer = Some(w);
         }
@@ -144,6 +324,19 @@ struct Session {
     /// The periodic sweep ends the session once this value's age exceeds
     /// the grace period. Cleared on re-register.
     disconnect_pending_since: Mutex<Option<Instant>>,
+    /// Redacts secrets from output before it reaches stream.bin.
+    secret_filter: SecretFilter,
+    /// Derived from the daemon's master key; `None` without one.
+    stream_key: Option<[u8; 32]>,
+}
+
+impl Session {
+    fn stream_file(&self) -> StreamFile {
+        StreamFile {
+            path: self.dir.join("stream.bin"),
+            key: self.stream_key,
+        }
+    }
 }
 
 pub struct SessionManager {
@@ -153,7 +346,8 @@ pub struct SessionManager {
     clients_history: RwLock<crate::clients_history::ClientsHistory>,
     clients_history_path: PathBuf,
     sessions: RwLock<HashMap<String, Arc<Session>>>,
-    context_config: ContextConfig,
+    /// Swapped by `set_context_config` when daemon.toml changes.
+    context_config: std::sync::RwLock<Arc<ContextConfig>>,
     completion_writer: mpsc::Sender<CompletionRecord>,
     session_writer: mpsc::Sender<SessionUpdateRecord>,
     /// Frozen history cutoff: commands with `started_at <= this` are history.
@@ -171,7 +365,80 @@ pub struct SessionManager {
     /// for KV cache warmup.
     last_completion_context: RwLock<String>,
     sample_writer: mpsc::Sender<CompletionSample>,
+    /// Answered LLM queries, appended under `$omnish_dir/logs/queries`.
+    query_writer: mpsc::Sender<QueryRecord>,
+    queries_dir: PathBuf,
+    /// Token usage of answered queries, appended under `$omnish_dir/logs/usage`.
+    usage_writer: mpsc::Sender<TokenUsageRecord>,
+    usage_dir: PathBuf,
+    /// Per-session token totals, seeded from `usage_dir` on first use.
+    token_usage: std::sync::Mutex<HashMap<String, TokenUsage>>,
     last_sample_time: Mutex<Option<Instant>>,
+    /// Compiled secret patterns shared by every session's `SecretFilter`.
+    redact_patterns: Arc<Vec<regex::bytes::Regex>>,
+    /// `context.completion.exclude_commands_pattern`, compiled on each config change.
+    exclude_commands: std::sync::RwLock<Option<regex::Regex>>,
+    /// Results of `get_session_context_with_limit`.
+    context_cache: std::sync::Mutex<ContextCache>,
+    /// Parsed `.omnish.toml` tables by path, with the mtime they were read at.
+    project_configs: Arc<std::sync::Mutex<HashMap<PathBuf, ProjectConfigEntry>>>,
+    /// Cap on stream.bin bytes read per session by `search_commands` and `grep_with_output`.
+    search_max_bytes: u64,
+    /// Create new stream.bin files zstd-compressed.
+    compress_streams: bool,
+    /// Create new stream.bin files encrypted with a key derived from
+    /// `master_key`.
+    encrypt_streams: bool,
+    /// Key that per-session stream keys are derived from. Needed to read
+    /// encrypted streams even when `encrypt_streams` is off.
+    master_key: Option<[u8; 32]>,
+    /// Trim a session's stream.bin once it grows past this many bytes.
+    max_stream_bytes: u64,
+    /// Save commands.json and meta.json via write-then-rename.
+    atomic_writes: bool,
+    stream_epoch: StreamEpoch,
+    /// Failed stream.bin writes since startup, reported by health checks.
+    stream_write_errors: AtomicU64,
+}
+
+/// Compile `context.completion.exclude_commands_pattern`, dropping it with a
+/// warning when invalid.
+fn compile_exclude_commands(context_config: &ContextConfig) -> Option<regex::Regex> {
+    context_config
+        .completion
+        .exclude_commands_pattern
+        .as_deref()
+        .and_then(|p| match regex::Regex::new(p) {
+            Ok(re) => Some(re),
+            Err(e) => {
+                tracing::warn!("ignoring invalid exclude_commands_pattern: {}", e);
+                None
+            }
+        })
+}
+
+/// Budget from `context.completion`: `max_context_tokens`, else the legacy
+/// `max_context_chars` converted to tokens. Counted with `token_count`.
+fn config_budget(cc: &CompletionContextConfig) -> Option<TokenBudget> {
+    cc.max_context_tokens
+        .or_else(|| cc.max_context_chars.map(omnish_llm::tokens::chars_to_tokens))
+        .map(TokenBudget::new)
+}
+
+/// Shift a command's stream range after `dropped` bytes were cut from the
+/// front of its stream.bin. Fully dropped ranges become empty; a range that
+/// straddles the cut keeps its surviving tail.
+fn shift_stream_range(cmd: &mut CommandRecord, dropped: u64) {
+    let end = cmd.stream_offset + cmd.stream_length;
+    if end <= dropped {
+        cmd.stream_offset = 0;
+        cmd.stream_length = 0;
+    } else if cmd.stream_offset < dropped {
+        cmd.stream_offset = 0;
+        cmd.stream_length = end - dropped;
+    } else {
+        cmd.stream_offset -= dropped;
+    }
 }
 
 /// Infer `last_active` from persisted data so that idle time survives daemon restarts.
@@ -258,6 +525,7 @@ impl SessionManager {
     /// - `omnish_dir`: base directory (e.g., ~/.omnish)
     ///   - sessions are stored in `$omnish_dir/sessions`
     ///   - completion logs are stored in `$omnish_dir/logs/completions`
+    ///   - LLM query logs are stored in `$omnish_dir/logs/queries`
     pub fn new(omnish_dir: PathBuf, context_config: ContextConfig) -> Self {
         let sessions_dir = omnish_dir.join("sessions");
         let completions_dir = omnish_dir.join("logs").join("completions");
@@ -268,20 +536,174 @@ impl SessionManager {
         let session_writer = omnish_store::session_update::spawn_writer_thread(session_updates_dir);
         let samples_dir = omnish_dir.join("logs").join("samples");
         let sample_writer = omnish_store::sample::spawn_sample_writer(samples_dir);
+        let queries_dir = omnish_dir.join("logs").join("queries");
+        let query_writer = omnish_store::query_log::spawn_query_writer_thread(queries_dir.clone());
+        let usage_dir = omnish_dir.join("logs").join("usage");
+        let usage_writer = omnish_store::usage::spawn_usage_writer_thread(usage_dir.clone());
         let clients_history = crate::clients_history::ClientsHistory::load(&clients_history_path);
+        let mut patterns = context_config.redact_patterns.clone();
+        match omnish_store::redact::load_patterns_file(&omnish_dir.join("redact_patterns.toml")) {
+            Ok(extra) => patterns.extend(extra),
+            Err(e) => tracing::warn!("failed to load redact_patterns.toml: {}", e),
+        }
+        let redact_patterns = Arc::new(omnish_store::redact::compile_patterns(&patterns));
+        let exclude_commands = compile_exclude_commands(&context_config);
         Self {
             base_dir: sessions_dir,
             clients_history: RwLock::new(clients_history),
             clients_history_path,
             sessions: RwLock::new(HashMap::new()),
-            context_config,
+            context_config: std::sync::RwLock::new(Arc::new(context_config)),
             completion_writer,
             session_writer,
             history_frozen_until: RwLock::new(None),
             recent_frozen_until: RwLock::new(None),
             last_completion_context: RwLock::new(String::new()),
             sample_writer,
+            query_writer,
+            queries_dir,
+            usage_writer,
+            usage_dir,
+            token_usage: std::sync::Mutex::new(HashMap::new()),
             last_sample_time: Mutex::new(None),
+            redact_patterns,
+            exclude_commands: std::sync::RwLock::new(exclude_commands),
+            context_cache: std::sync::Mutex::new(ContextCache::new(CONTEXT_CACHE_TTL)),
+            project_configs: Arc::new(std::sync::Mutex::new(HashMap::new())),
+            search_max_bytes: omnish_common::config::SearchConfig::default().max_bytes_per_session,
+            compress_streams: false,
+            encrypt_streams: false,
+            master_key: None,
+            max_stream_bytes: omnish_common::config::StorageConfig::default().max_stream_bytes_per_session,
+            atomic_writes: true,
+            stream_epoch: StreamEpoch::default(),
+            stream_write_errors: AtomicU64::new(0),
+        }
+    }
+
+    /// Current `[context]` settings.
+    pub fn context_config(&self) -> Arc<ContextConfig> {
+        self.context_config.read().unwrap().clone()
+    }
+
+    /// Apply reloaded `[context]` settings. Takes effect from the next
+    /// context build; `redact_patterns` only at the next daemon start.
+    pub fn set_context_config(&self, context_config: ContextConfig) {
+        *self.exclude_commands.write().unwrap() = compile_exclude_commands(&context_config);
+        *self.context_config.write().unwrap() = Arc::new(context_config);
+        self.context_cache.lock().unwrap().entries.clear();
+    }
+
+    /// `[context]` settings for `session_id`: the global ones with the
+    /// project's `.omnish.toml` merged on top, if the session's cwd has one.
+    pub async fn session_context_config(&self, session_id: &str) -> Arc<ContextConfig> {
+        let global = self.context_config();
+        let Some(project) = self.project_config(session_id).await else {
+            return global;
+        };
+        let mut base = DaemonConfig::default();
+        base.context = (*global).clone();
+        Arc::new(merge_configs(&base, &project).context)
+    }
+
+    /// Keys set in the nearest `.omnish.toml` at or above the session's
+    /// shell cwd (or the cwd of its last command). The lookup runs on the
+    /// blocking pool and a file is only re-parsed when its mtime changes.
+    pub async fn project_config(&self, session_id: &str) -> Option<Arc<toml::Table>> {
+        let session = {
+            let sessions = self.sessions.read().await;
+            sessions.get(session_id).cloned()?
+        };
+        let cwd = match session.meta.read().await.attrs.get("shell_cwd") {
+            Some(cwd) => cwd.clone(),
+            None => session.commands.read().await.last()?.cwd.clone()?,
+        };
+        let cache = self.project_configs.clone();
+        tokio::task::spawn_blocking(move || {
+            let path = find_project_config(Path::new(&cwd))?;
+            let mtime = std::fs::metadata(&path).and_then(|m| m.modified()).ok()?;
+            if let Some(entry) = cache.lock().unwrap().get(&path) {
+                if entry.mtime == mtime {
+                    return entry.table.clone();
+                }
+            }
+            let table = load_project_config(&path).map(Arc::new);
+            cache.lock().unwrap().insert(path, ProjectConfigEntry { mtime, table: table.clone() });
+            table
+        })
+        .await
+        .ok()
+        .flatten()
+    }
+
+    /// Backend name the session's project config picks for `use_case`
+    /// (`[llm.use_cases]` in `.omnish.toml`). This lets a checked-out
+    /// repository move chat or completion to any configured backend.
+    pub async fn project_backend_name(&self, session_id: &str, use_case: &str) -> Option<String> {
+        let project = self.project_config(session_id).await?;
+        let name = project.get("llm")?.get("use_cases")?.get(use_case)?.as_str()?;
+        Some(name.to_string())
+    }
+
+    fn exclude_commands(&self) -> Option<regex::Regex> {
+        self.exclude_commands.read().unwrap().clone()
+    }
+
+    /// Override how many bytes of command output `search_commands` reads per session.
+    pub fn with_search_max_bytes(mut self, max_bytes: u64) -> Self {
+        self.search_max_bytes = max_bytes;
+        self
+    }
+
+    /// Create new session stream files zstd-compressed.
+    pub fn with_compress_streams(mut self, compress: bool) -> Self {
+        self.compress_streams = compress;
+        self
+    }
+
+    /// Set the key that per-session stream keys are derived from, usually
+    /// loaded from `$omnish_dir/master.key`.
+    pub fn with_master_key(mut self, master_key: [u8; 32]) -> Self {
+        self.master_key = Some(master_key);
+        self
+    }
+
+    /// Create new session stream files encrypted. Requires `with_master_key`.
+    pub fn with_encrypt_streams(mut self, encrypt: bool) -> Self {
+        self.encrypt_streams = encrypt;
+        self
+    }
+
+    /// Override the per-session stream.bin size cap.
+    pub fn with_max_stream_bytes(mut self, max_bytes: u64) -> Self {
+        self.max_stream_bytes = max_bytes;
+        self
+    }
+
+    /// Save commands.json and meta.json via `<file>.tmp` + rename (the
+    /// default), or in place when `enabled` is false.
+    pub fn with_atomic_writes(mut self, enabled: bool) -> Self {
+        self.atomic_writes = enabled;
+        self
+    }
+
+    fn session_key(&self, session_id: &str) -> Option<[u8; 32]> {
+        self.master_key.as_ref().map(|master| derive_session_key(master, session_id))
+    }
+
+    fn file_reader(&self, stream: StreamFile) -> FileStreamReader {
+        FileStreamReader {
+            stream,
+            epoch: self.stream_epoch.clone(),
+            built_at: self.stream_epoch.current(),
+        }
+    }
+
+    fn multi_reader(&self, readers: HashMap<(u64, u64), StreamFile>) -> MultiSessionReader {
+        MultiSessionReader {
+            readers,
+            epoch: self.stream_epoch.clone(),
+            built_at: self.stream_epoch.current(),
         }
     }
 
@@ -353,14 +775,24 @@ impl SessionManager {
 
             let mut load = || -> Result<()> {
                 let meta = SessionMeta::load(&dir)?;
-                let commands = CommandRecord::load_all(&dir)?;
+                let mut commands = CommandRecord::load_all(&dir)?;
+                // A bad checksum means that record alone is damaged - drop it
+                // instead of discarding the whole session.
+                commands.retain(|cmd| {
+                    let ok = cmd.verify_checksum();
+                    if !ok {
+                        tracing::warn!(
+                            "skipping command {} in {:?}: checksum mismatch",
+                            cmd.command_id, dir
+                        );
+                    }
+                    ok
+                });
                 let stream_path = dir.join("stream.bin");
 
-                // Read current file size without opening the writer - keeps fd
-                // usage at zero for loaded-but-inactive sessions.
-                let current_stream_pos = std::fs::metadata(&stream_path)
-                    .map(|m| m.len())
-                    .unwrap_or(0);
+                // Read current stream length without keeping the writer open -
+                // keeps fd usage at zero for loaded-but-inactive sessions.
+                let current_stream_pos = stream_len(&stream_path).unwrap_or(0);
 
                 let last_command_stream_pos = commands
                     .last()
@@ -381,6 +813,7 @@ impl SessionManager {
                 };
 
                 let session_id = meta.session_id.clone();
+                let stream_key = self.session_key(&session_id);
                 sessions.insert(
                     session_id,
                     Arc::new(Session {
@@ -397,6 +830,8 @@ impl SessionManager {
                         pending_sample: Mutex::new(None),
                         current_conn: Mutex::new(None),
                         disconnect_pending_since: Mutex::new(pending_since),
+                        secret_filter: SecretFilter::new(self.redact_patterns.clone()),
+                        stream_key,
                     }),
                 );
                 count += 1;
@@ -437,7 +872,7 @@ impl SessionManager {
         {
             let sessions = self.sessions.read().await;
             if let Some(session) = sessions.get(session_id) {
-                Self::rebind_existing(session, attrs, conn_id).await?;
+                self.rebind_existing(session, attrs, conn_id).await?;
                 drop(sessions);
                 self.touch_clients_history(history_pair).await;
                 return Ok(());
@@ -449,7 +884,7 @@ impl SessionManager {
 
         // Double-check after acquiring write lock
         if let Some(session) = sessions.get(session_id) {
-            Self::rebind_existing(session, attrs, conn_id).await?;
+            self.rebind_existing(session, attrs, conn_id).await?;
             drop(sessions);
             self.touch_clients_history(history_pair).await;
             return Ok(());
@@ -467,8 +902,10 @@ impl SessionManager {
             started_at: now,
             ended_at: None,
             attrs,
+            tags: Vec::new(),
+            description: None,
         };
-        meta.save(&session_dir)?;
+        meta.save_with(&session_dir, self.atomic_writes)?;
 
         // stream.bin is created lazily on first feed_io; no fd opened here.
 
@@ -488,6 +925,8 @@ impl SessionManager {
                 pending_sample: Mutex::new(None),
                 current_conn: Mutex::new(conn_id),
                 disconnect_pending_since: Mutex::new(None),
+                secret_filter: SecretFilter::new(self.redact_patterns.clone()),
+                stream_key: self.session_key(session_id),
             }),
         );
         drop(sessions);
@@ -503,6 +942,7 @@ impl SessionManager {
     /// previously-ended session reactivates cleanly (e.g. when the sweep
     /// raced ahead of the client's reconnect).
     async fn rebind_existing(
+        &self,
         session: &Arc<Session>,
         attrs: std::collections::HashMap<String, String>,
         conn_id: Option<u64>,
@@ -511,7 +951,7 @@ impl SessionManager {
         let was_ended = meta.ended_at.is_some();
         meta.attrs = attrs;
         meta.ended_at = None;
-        meta.save(&session.dir)?;
+        meta.save_with(&session.dir, self.atomic_writes)?;
         drop(meta);
         *session.current_conn.lock().await = conn_id;
         *session.disconnect_pending_since.lock().await = None;
@@ -621,11 +1061,16 @@ impl SessionManager {
         }
 
         let mut meta = session.meta.write().await;
+        let mut changed = false;
         for (k, v) in &attrs {
-            meta.attrs.insert(k.clone(), v.clone());
+            changed |= meta.attrs.insert(k.clone(), v.clone()).as_ref() != Some(v);
         }
-        meta.save(&session.dir)?;
+        meta.save_with(&session.dir, self.atomic_writes)?;
         drop(meta);
+        if changed {
+            // Attrs feed context headers (hostname, environment tags)
+            self.context_cache.lock().unwrap().invalidate_session(session_id);
+        }
 
         // Send to session writer for logging (non-blocking)
         let record = omnish_store::session_update::SessionUpdateRecord::new(
@@ -650,23 +1095,52 @@ impl SessionManager {
             sessions.get(session_id).cloned()
         };
         if let Some(session) = session {
-            let stream_path = session.dir.join("stream.bin");
+            let stream = session.stream_file();
+            // Scrub output only; direction 1 is terminal output.
+            let scrubbed;
+            let data = if direction == 1 && !session.secret_filter.is_empty() {
+                scrubbed = session.secret_filter.scrub(data);
+                &scrubbed[..]
+            } else {
+                data
+            };
             let mut sw = session.stream_writer.lock().await;
-            let writer = sw.ensure_writer(&stream_path)?;
-            writer.write_entry(timestamp_ms, direction, data)?;
-            let pos = writer.position();
+            let written = sw
+                .ensure_writer(&stream, self.compress_streams, self.encrypt_streams)
+                .and_then(|writer| {
+                    writer.write_entry(timestamp_ms, direction, data)?;
+                    Ok(writer.position())
+                });
+            let pos = match written {
+                Ok(pos) => pos,
+                Err(e) => {
+                    self.stream_write_errors.fetch_add(1, Ordering::Relaxed);
+                    return Err(e);
+                }
+            };
             sw.current_stream_pos = pos;
             sw.last_active = Instant::now();
         }
         Ok(())
     }
 
+    /// Number of stream.bin writes that failed since startup.
+    pub fn stream_write_errors(&self) -> u64 {
+        self.stream_write_errors.load(Ordering::Relaxed)
+    }
+
     pub async fn receive_command(&self, session_id: &str, mut record: CommandRecord) -> Result<()> {
         let session = {
             let sessions = self.sessions.read().await;
             sessions.get(session_id).cloned()
         };
         if let Some(session) = session {
+            // The command line and summary are copied from the terminal
+            // unfiltered; scrub them like stream.bin output.
+            if !self.redact_patterns.is_empty() {
+                record.command_line = record.command_line.map(|c| redact_text(&self.redact_patterns, &c));
+                record.output_summary = redact_text(&self.redact_patterns, &record.output_summary);
+            }
             // Extract command line before record is moved
             let next_cmd_line = record.command_line.clone();
 
@@ -681,10 +1155,18 @@ impl SessionManager {
                 sw.last_active = Instant::now();
             }
 
+            record.checksum = Some(record.compute_checksum());
+
             // Lock commands to push and save
-            let mut commands = session.commands.write().await;
-            commands.push(record);
-            CommandRecord::save_all(&commands, &session.dir)?;
+            {
+                let mut commands = session.commands.write().await;
+                commands.push(record);
+                CommandRecord::save_all_with(&commands, &session.dir, self.atomic_writes)?;
+            }
+            self.context_cache.lock().unwrap().invalidate_session(session_id);
+            if let Err(e) = self.enforce_stream_cap(&session).await {
+                tracing::warn!("failed to trim stream for session {}: {}", session_id, e);
+            }
 
             // Check pending sample for completion sampling
             let pending = {
@@ -756,6 +1238,46 @@ impl SessionManager {
         Ok(())
     }
 
+    /// Log an answered LLM query (non-blocking).
+    pub fn record_query(&self, record: QueryRecord) {
+        let _ = self.query_writer.send(record);
+    }
+
+    /// Add the token usage of an answered query to its session's totals
+    /// and log it (non-blocking).
+    pub fn record_token_usage(&self, record: TokenUsageRecord) {
+        // Seed from disk before the record is queued for writing
+        self.token_usage(&record.session_id);
+        self.token_usage
+            .lock()
+            .unwrap()
+            .entry(record.session_id.clone())
+            .or_default()
+            .add(record.usage());
+        let _ = self.usage_writer.send(record);
+    }
+
+    /// Total token usage of the LLM queries of `session_id`. Read from the
+    /// session's usage log once, then kept in memory.
+    pub fn token_usage(&self, session_id: &str) -> TokenUsage {
+        if let Some(usage) = self.token_usage.lock().unwrap().get(session_id) {
+            return *usage;
+        }
+        let loaded = omnish_store::usage::load_session_usage(&self.usage_dir, session_id);
+        *self
+            .token_usage
+            .lock()
+            .unwrap()
+            .entry(session_id.to_string())
+            .or_insert(loaded)
+    }
+
+    /// The last `limit` logged LLM queries of `session_id`, oldest first.
+    /// Read from disk, so history survives daemon restarts.
+    pub fn get_query_history(&self, session_id: &str, limit: usize) -> Vec<QueryRecord> {
+        omnish_store::query_log::load_query_records(&self.queries_dir, session_id, limit)
+    }
+
     pub async fn end_session(&self, session_id: &str) -> Result<()> {
         let session = {
             let sessions = self.sessions.read().await;
@@ -764,7 +1286,7 @@ impl SessionManager {
         if let Some(session) = session {
             let mut meta = session.meta.write().await;
             meta.ended_at = Some(chrono::Utc::now().to_rfc3339());
-            meta.save(&session.dir)?;
+            meta.save_with(&session.dir, self.atomic_writes)?;
 
             // Drop conn binding and grace timer so a subsequent on_disconnect
             // for the same conn doesn't try to end this session again, and
@@ -773,7 +1295,7 @@ impl SessionManager {
             *session.disconnect_pending_since.lock().await = None;
 
             let commands = session.commands.read().await;
-            CommandRecord::save_all(&commands, &session.dir)?;
+            CommandRecord::save_all_with(&commands, &session.dir, self.atomic_writes)?;
 
             // Release the stream.bin fd now that the shell has exited.
             // The session remains in memory for history queries; if more I/O
@@ -796,6 +1318,270 @@ impl SessionManager {
         Ok(())
     }
 
+    /// Trim the session's stream.bin to three quarters of `max_stream_bytes`
+    /// once it is past the cap, then shift every command's range to match.
+    /// Trimming below the cap leaves room so the file is not rewritten on
+    /// every following command.
+    async fn enforce_stream_cap(&self, session: &Session) -> Result<()> {
+        let mut sw = session.stream_writer.lock().await;
+        if sw.current_stream_pos <= self.max_stream_bytes {
+            return Ok(());
+        }
+        let stream = session.stream_file();
+        let target = self.max_stream_bytes / 4 * 3;
+        let trimmed = sw
+            .ensure_writer(&stream, self.compress_streams, self.encrypt_streams)
+            .and_then(|w| w.trim_oldest(target));
+        let dropped = match trimmed {
+            Ok(d) => d,
+            Err(e) => {
+                // Reopen lazily on the next write.
+                sw.writer = None;
+                return Err(e);
+            }
+        };
+        if dropped == 0 {
+            return Ok(());
+        }
+        sw.current_stream_pos -= dropped;
+        sw.last_command_stream_pos = sw.last_command_stream_pos.saturating_sub(dropped);
+        self.stream_epoch.bump();
+
+        let mut commands = session.commands.write().await;
+        for cmd in commands.iter_mut() {
+            shift_stream_range(cmd, dropped);
+        }
+        CommandRecord::save_all_with(&commands, &session.dir, self.atomic_writes)?;
+        tracing::info!(
+            "trimmed {} bytes from {} (now {} bytes)",
+            dropped,
+            stream.path.display(),
+            sw.current_stream_pos
+        );
+        Ok(())
+    }
+
+    /// Add `label` to every command in `session_id` whose command line
+    /// matches the regex `pattern`. Returns how many commands gained the label
+    /// (commands already carrying it are not counted).
+    pub async fn tag_commands(&self, session_id: &str, pattern: &str, label: &str) -> Result<usize> {
+        let re = regex::Regex::new(pattern)?;
+        let session = {
+            let sessions = self.sessions.read().await;
+            sessions
+                .get(session_id)
+                .cloned()
+                .ok_or_else(|| anyhow!("session {} not found", session_id))?
+        };
+        let mut commands = session.commands.write().await;
+        let mut tagged = 0;
+        for cmd in commands.iter_mut() {
+            let matches = cmd.command_line.as_deref().is_some_and(|l| re.is_match(l));
+            if matches && !cmd.tags.iter().any(|t| t == label) {
+                cmd.tags.push(label.to_string());
+                tagged += 1;
+            }
+        }
+        if tagged > 0 {
+            CommandRecord::save_all_with(&commands, &session.dir, self.atomic_writes)?;
+            // Cached multi-session contexts include these commands too
+            self.context_cache.lock().unwrap().entries.clear();
+        }
+        Ok(tagged)
+    }
+
+    /// Replace the user-defined tags and description of `session_id` and
+    /// save its meta.json.
+    pub async fn set_session_metadata(
+        &self,
+        session_id: &str,
+        tags: Vec<String>,
+        description: Option<String>,
+    ) -> Result<()> {
+        let session = {
+            let sessions = self.sessions.read().await;
+            sessions
+                .get(session_id)
+                .cloned()
+                .ok_or_else(|| anyhow!("session {} not found", session_id))?
+        };
+        let mut meta = session.meta.write().await;
+        meta.tags = tags;
+        meta.description = description;
+        meta.save_with(&session.dir, self.atomic_writes)
+    }
+
+    /// Command statistics for `session_id` (see `/stats`).
+    pub async fn get_statistics(&self, session_id: &str) -> Result<SessionStats> {
+        let session = {
+            let sessions = self.sessions.read().await;
+            sessions
+                .get(session_id)
+                .cloned()
+                .ok_or_else(|| anyhow!("session {} not found", session_id))?
+        };
+        let commands = session.commands.read().await;
+        let usage = self.token_usage(session_id);
+        Ok(SessionStats {
+            total_prompt_tokens: usage.prompt_tokens,
+            total_completion_tokens: usage.completion_tokens,
+            ..SessionStats::from_commands(&commands)
+        })
+    }
+
+    /// Terminal output of `session_id`, re-emitted with the recorded pacing.
+    ///
+    /// Yields one chunk per output entry of stream.bin in timestamp order,
+    /// sleeping the original gap to the previous entry divided by `speed`
+    /// before each. Gaps are capped at `MAX_REPLAY_GAP` first so long idle
+    /// periods do not stall the replay. `speed <= 0` replays instantly.
+    pub async fn replay_stream(
+        &self,
+        session_id: &str,
+        speed: f32,
+    ) -> Result<Pin<Box<dyn Stream<Item = Result<Vec<u8>>> + Send>>> {
+        let session = {
+            let sessions = self.sessions.read().await;
+            sessions.get(session_id).cloned()
+        }
+        .ok_or_else(|| anyhow!("session not found: {}", session_id))?;
+        let stream = session.stream_file();
+        let mut entries: Vec<StreamEntry> = if stream.path.exists() {
+            read_entries(&stream.path, stream.key.as_ref())?
+        } else {
+            Vec::new()
+        };
+        // Direction 1 is terminal output; input is echoed back within it.
+        entries.retain(|e| e.direction == 1);
+        entries.sort_by_key(|e| e.timestamp_ms);
+
+        let state = (entries.into_iter(), None::<u64>);
+        Ok(Box::pin(futures_util::stream::unfold(state, move |(mut iter, prev)| async move {
+            let entry = iter.next()?;
+            if let Some(prev) = prev {
+                let gap = Duration::from_millis(entry.timestamp_ms.saturating_sub(prev)).min(MAX_REPLAY_GAP);
+                if speed > 0.0 && !gap.is_zero() {
+                    tokio::time::sleep(gap.div_f32(speed)).await;
+                }
+            }
+            Some((Ok(entry.data), (iter, Some(entry.timestamp_ms))))
+        })))
+    }
+
+    /// Combine ended sessions into a new session `target_id`. Commands are
+    /// replayed in `started_at` order: each command's stream slice is copied
+    /// into the target's stream.bin, so offsets are recomputed by
+    /// `receive_command`. The target takes the first source's attrs and is
+    /// ended once populated. Sources are left untouched.
+    pub async fn merge_sessions(&self, source_ids: &[&str], target_id: &str) -> Result<()> {
+        let mut sources = Vec::new();
+        {
+            let sessions = self.sessions.read().await;
+            if sessions.contains_key(target_id) {
+                return Err(anyhow!("session {} already exists", target_id));
+            }
+            for id in source_ids {
+                let session = sessions
+                    .get(*id)
+                    .cloned()
+                    .ok_or_else(|| anyhow!("session {} not found", id))?;
+                sources.push(session);
+            }
+        }
+        if sources.is_empty() {
+            return Err(anyhow!("no sessions to merge"));
+        }
+
+        let mut merged: Vec<(CommandRecord, StreamFile)> = Vec::new();
+        for session in &sources {
+            let meta = session.meta.read().await;
+            if meta.ended_at.is_none() {
+                return Err(anyhow!("session {} is still active", meta.session_id));
+            }
+            let stream = session.stream_file();
+            for cmd in session.commands.read().await.iter() {
+                merged.push((cmd.clone(), stream.clone()));
+            }
+        }
+        merged.sort_by_key(|(cmd, _)| cmd.started_at);
+
+        let attrs = sources[0].meta.read().await.attrs.clone();
+        self.register(target_id, None, attrs, None).await?;
+        for (mut cmd, stream) in merged {
+            if cmd.stream_length > 0 {
+                for entry in stream.read_range(cmd.stream_offset, cmd.stream_length)? {
+                    self.write_io(target_id, entry.timestamp_ms, entry.direction, &entry.data)
+                        .await?;
+                }
+            }
+            cmd.session_id = target_id.to_string();
+            self.receive_command(target_id, cmd).await?;
+        }
+        self.end_session(target_id).await
+    }
+
+    /// Rewrite the session's stream.bin with only the output that commands
+    /// still reference (plus the running command's output), packed without
+    /// gaps, and save commands.json with the new offsets. Both files are
+    /// replaced via write-then-rename. Output between recorded commands,
+    /// e.g. of commands dropped from commands.json, is discarded.
+    pub async fn vacuum(&self, session_id: &str) -> Result<VacuumStats> {
+        let started = Instant::now();
+        let session = {
+            let sessions = self.sessions.read().await;
+            sessions
+                .get(session_id)
+                .cloned()
+                .ok_or_else(|| anyhow!("session {} not found", session_id))?
+        };
+        let stream = session.stream_file();
+        let stream_path = &stream.path;
+        let mut sw = session.stream_writer.lock().await;
+        let mut commands = session.commands.write().await;
+        if !stream_path.exists() {
+            return Ok(VacuumStats { bytes_before: 0, bytes_after: 0, duration_ms: 0 });
+        }
+        let bytes_before = std::fs::metadata(stream_path)?.len();
+
+        let mut ranges: Vec<(u64, u64)> =
+            commands.iter().map(|c| (c.stream_offset, c.stream_length)).collect();
+        ranges.push((
+            sw.last_command_stream_pos,
+            sw.current_stream_pos.saturating_sub(sw.last_command_stream_pos),
+        ));
+        let compacted = sw
+            .ensure_writer(&stream, self.compress_streams, self.encrypt_streams)
+            .and_then(|w| w.compact(&ranges));
+        let offsets = match compacted {
+            Ok(o) => o,
+            Err(e) => {
+                // Reopen lazily on the next write.
+                sw.writer = None;
+                return Err(e);
+            }
+        };
+        for (cmd, offset) in commands.iter_mut().zip(&offsets) {
+            cmd.stream_offset = *offset;
+        }
+        sw.last_command_stream_pos = offsets[commands.len()];
+        sw.current_stream_pos = sw.writer.as_ref().map_or(0, |w| w.position());
+        self.stream_epoch.bump();
+        CommandRecord::save_all_with(&commands, &session.dir, true)?;
+
+        let stats = VacuumStats {
+            bytes_before,
+            bytes_after: std::fs::metadata(stream_path)?.len(),
+            duration_ms: started.elapsed().as_millis() as u64,
+        };
+        tracing::info!(
+            "vacuumed {}: {} -> {} bytes",
+            stream_path.display(),
+            stats.bytes_before,
+            stats.bytes_after
+        );
+        Ok(stats)
+    }
+
     /// Store a pending completion sample for a session.
     /// Called from handle_completion_request after getting LLM suggestions.
     pub async fn store_pending_sample(&self, sample: PendingSample) {
@@ -836,34 +1622,148 @@ impl SessionManager {
         }
     }
 
-    /// Get a single session attribute value by key.
-    pub async fn get_session_attr(&self, session_id: &str, key: &str) -> Option<String> {
+    /// Commands of one session plus a reader over its stream.bin.
+    pub async fn get_commands_with_reader(
+        &self,
+        session_id: &str,
+    ) -> Result<(Vec<CommandRecord>, Arc<dyn StreamReader>)> {
         let session = {
             let sessions = self.sessions.read().await;
             sessions.get(session_id).cloned()
-        };
-        if let Some(session) = session {
-            let meta = session.meta.read().await;
-            meta.attrs.get(key).cloned()
-        } else {
-            None
         }
+        .ok_or_else(|| anyhow!("session not found: {}", session_id))?;
+        let commands = session.commands.read().await.clone();
+        let reader: Arc<dyn StreamReader> = Arc::new(self.file_reader(session.stream_file()));
+        Ok((commands, reader))
     }
 
-    pub async fn get_session_attrs(&self, session_id: &str) -> std::collections::HashMap<String, String> {
-        let session = {
+    /// Search command lines and stored output across all sessions.
+    ///
+    /// Output is read from each session's stream.bin newest command first,
+    /// stopping once `search_max_bytes` have been read for that session.
+    /// Results are sorted most recent first and capped at `limit`.
+    pub async fn search_commands(&self, pattern: &str, limit: usize) -> Vec<SearchResult> {
+        let Some(re) = crate::search::compile_pattern(pattern) else {
+            return Vec::new();
+        };
+        let session_entries: Vec<_> = {
             let sessions = self.sessions.read().await;
-            sessions.get(session_id).cloned()
+            sessions.values().cloned().collect()
         };
-        if let Some(session) = session {
-            let meta = session.meta.read().await;
-            meta.attrs.clone()
-        } else {
-            std::collections::HashMap::new()
+
+        let mut results = Vec::new();
+        for session in &session_entries {
+            let stream = session.stream_file();
+            let commands = session.commands.read().await.clone();
+            let re = re.clone();
+            let max_bytes = self.search_max_bytes;
+            // Reading stream.bin is blocking file I/O
+            let found = tokio::task::spawn_blocking(move || search_session(&stream, &commands, &re, max_bytes)).await;
+            match found {
+                Ok(found) => results.extend(found),
+                Err(e) => tracing::warn!("search of a session failed: {}", e),
+            }
         }
+        results.sort_by_key(|r| std::cmp::Reverse(r.started_at));
+        results.truncate(limit);
+        results
     }
 
-    /// Get session debug information including metadata, commands count, last active time, and last update timestamp
+    /// Find every output line matching `pattern` across all sessions, with
+    /// two lines of context on each side (like `grep -C 2`).
+    ///
+    /// Reads stream.bin under the same per-session `search_max_bytes` budget
+    /// as `search_commands`. Results are sorted most recent first and capped
+    /// at `limit`.
+    pub async fn grep_with_output(&self, pattern: &str, limit: usize) -> Result<Vec<GrepResult>> {
+        let re = crate::search::compile_pattern(pattern).ok_or_else(|| anyhow!("empty grep pattern"))?;
+        let session_entries: Vec<_> = {
+            let sessions = self.sessions.read().await;
+            sessions.values().cloned().collect()
+        };
+
+        let mut results = Vec::new();
+        for session in &session_entries {
+            let stream = session.stream_file();
+            let commands = session.commands.read().await.clone();
+            let mut bytes_read = 0u64;
+            for cmd in commands.iter().rev() {
+                if cmd.stream_length == 0 || bytes_read >= self.search_max_bytes {
+                    continue;
+                }
+                bytes_read += cmd.stream_length;
+                let entries = stream.read_range(cmd.stream_offset, cmd.stream_length)?;
+                let raw: Vec<u8> = entries
+                    .into_iter()
+                    .filter(|e| e.direction == 1)
+                    .flat_map(|e| e.data)
+                    .collect();
+                let matched_lines = crate::search::grep_lines(&omnish_context::strip_ansi(&raw), &re);
+                if !matched_lines.is_empty() {
+                    results.push(GrepResult {
+                        session_id: cmd.session_id.clone(),
+                        command_line: cmd.command_line.clone().unwrap_or_default(),
+                        matched_lines,
+                        started_at: cmd.started_at,
+                    });
+                }
+            }
+        }
+        results.sort_by_key(|r| std::cmp::Reverse(r.started_at));
+        results.truncate(limit);
+        Ok(results)
+    }
+
+    /// Environment variables reported in the session's `env.<NAME>` attrs,
+    /// sorted by name.
+    pub async fn session_env(&self, session_id: &str) -> Vec<(String, String)> {
+        let mut env: Vec<(String, String)> = self
+            .get_session_attrs(session_id)
+            .await
+            .into_iter()
+            .filter_map(|(k, v)| k.strip_prefix("env.").map(|name| (name.to_string(), v)))
+            .collect();
+        env.sort();
+        env
+    }
+
+    /// Get a single session attribute value by key.
+    pub async fn get_session_attr(&self, session_id: &str, key: &str) -> Option<String> {
+        let session = {
+            let sessions = self.sessions.read().await;
+            sessions.get(session_id).cloned()
+        };
+        if let Some(session) = session {
+            let meta = session.meta.read().await;
+            meta.attrs.get(key).cloned()
+        } else {
+            None
+        }
+    }
+
+    pub async fn get_session_meta(&self, session_id: &str) -> Option<SessionMeta> {
+        let session = {
+            let sessions = self.sessions.read().await;
+            sessions.get(session_id).cloned()
+        }?;
+        let meta = session.meta.read().await;
+        Some(meta.clone())
+    }
+
+    pub async fn get_session_attrs(&self, session_id: &str) -> std::collections::HashMap<String, String> {
+        let session = {
+            let sessions = self.sessions.read().await;
+            sessions.get(session_id).cloned()
+        };
+        if let Some(session) = session {
+            let meta = session.meta.read().await;
+            meta.attrs.clone()
+        } else {
+            std::collections::HashMap::new()
+        }
+    }
+
+    /// Get session debug information including metadata, commands count, last active time, and last update timestamp
     pub async fn get_session_debug_info(&self, session_id: &str) -> Result<(SessionMeta, usize, Duration, Option<u64>)> {
         let session = {
             let sessions = self.sessions.read().await;
@@ -887,6 +1787,57 @@ impl SessionManager {
         Ok((meta, cmd_count, last_active_duration, *last_update))
     }
 
+    /// Metadata of every loaded session, oldest first.
+    pub async fn list_sessions(&self) -> Vec<SessionMeta> {
+        let session_arcs: Vec<_> = {
+            let sessions = self.sessions.read().await;
+            sessions.values().cloned().collect()
+        };
+        let mut metas = Vec::with_capacity(session_arcs.len());
+        for session in &session_arcs {
+            metas.push(session.meta.read().await.clone());
+        }
+        metas.sort_by(|a, b| a.started_at.cmp(&b.started_at).then_with(|| a.session_id.cmp(&b.session_id)));
+        metas
+    }
+
+    /// Sessions whose `hostname` attr is `hostname`, newest first.
+    pub async fn get_sessions_by_host(&self, hostname: &str) -> Vec<SessionMeta> {
+        let mut metas: Vec<SessionMeta> = self
+            .list_sessions()
+            .await
+            .into_iter()
+            .filter(|m| m.attrs.get("hostname").is_some_and(|h| h == hostname))
+            .collect();
+        metas.reverse();
+        metas
+    }
+
+    /// Metadata of loaded sessions, newest first; ended ones only when
+    /// `include_ended`.
+    pub async fn list_all(&self, include_ended: bool) -> Vec<SessionMeta> {
+        let mut metas: Vec<SessionMeta> = self
+            .list_sessions()
+            .await
+            .into_iter()
+            .filter(|m| include_ended || m.ended_at.is_none())
+            .collect();
+        metas.reverse();
+        metas
+    }
+
+    /// Page `page` (0-based) of `list_all(true)` plus the total session count.
+    pub async fn list_all_paged(&self, page: usize, page_size: usize) -> (Vec<SessionMeta>, usize) {
+        let all = self.list_all(true).await;
+        let total = all.len();
+        let page = all
+            .into_iter()
+            .skip(page.saturating_mul(page_size))
+            .take(page_size)
+            .collect();
+        (page, total)
+    }
+
     pub async fn list_active(&self) -> Vec<String> {
         let sessions = self.sessions.read().await;
         let mut result = Vec::new();
@@ -952,6 +1903,7 @@ impl SessionManager {
         struct SessionSnapshot {
             session_id: String,
             hostname: Option<String>,
+            tags: Vec<String>,
             ended: bool,
             last_active: Instant,
             cmd_count: usize,
@@ -974,6 +1926,7 @@ impl SessionManager {
             snapshots.push(SessionSnapshot {
                 session_id: meta.session_id.clone(),
                 hostname: meta.attrs.get("hostname").cloned(),
+                tags: meta.tags.clone(),
                 ended: meta.ended_at.is_some(),
                 last_active: sw.last_active,
                 cmd_count,
@@ -991,17 +1944,19 @@ impl SessionManager {
             // Sort commands by started_at (chronological order)
             all_commands.sort_by_key(|c| c.started_at);
 
-            let total = self.context_config.completion.detailed_commands + self.context_config.completion.history_commands;
+            let cc = self.session_context_config(current_session_id).await;
+            let total = cc.completion.detailed_commands + cc.completion.history_commands;
             let strategy = RecentCommands::new(total)
-                .with_current_session(current_session_id, self.context_config.completion.min_current_session_commands);
+                .with_current_session(current_session_id, cc.completion.min_current_session_commands)
+                .with_exclusion(self.exclude_commands());
 
             // Use the same select+split logic as build_context_with_session
             let (_history_cmds, detailed_cmds) = omnish_context::select_and_split(
                 &strategy,
                 &all_commands,
-                self.context_config.completion.detailed_commands,
+                cc.completion.detailed_commands,
                 Some(current_session_id),
-                self.context_config.completion.min_current_session_commands,
+                cc.completion.min_current_session_commands,
             ).await;
 
             // Count detailed commands per session
@@ -1068,9 +2023,14 @@ impl SessionManager {
                 } else {
                     ("\x1b[2m", "\x1b[0m")
                 };
+                let tags = if s.tags.is_empty() {
+                    String::new()
+                } else {
+                    format!(" [{}]", s.tags.join(", "))
+                };
                 lines.push(format!(
-                    "  {}{} {} [active] cmds={}/{} idle={}{}",
-                    color_start, marker, s.session_id, s.context_cmd_count, s.cmd_count, idle, color_end,
+                    "  {}{} {} [active]{} cmds={}/{} idle={}{}",
+                    color_start, marker, s.session_id, tags, s.context_cmd_count, s.cmd_count, idle, color_end,
                 ));
             }
 
@@ -1252,6 +2212,11 @@ impl SessionManager {
                         match std::fs::remove_dir_all(&dir) {
                             Ok(_) => {
                                 tracing::info!("cleaned up expired session directory: {:?}", dir);
+                                if let Some(sid) = session_id {
+                                    let usage_log = omnish_store::usage::session_usage_path(&self.usage_dir, sid);
+                                    let _ = std::fs::remove_file(usage_log);
+                                    self.token_usage.lock().unwrap().remove(sid);
+                                }
                                 cleaned += 1;
                             }
                             Err(e) => {
@@ -1273,100 +2238,69 @@ impl SessionManager {
     }
 
     pub async fn get_session_context(&self, session_id: &str) -> Result<String> {
-        self.get_session_context_with_limit(session_id, self.context_config.completion.max_context_chars).await
+        let budget = config_budget(&self.session_context_config(session_id).await.completion);
+        self.get_session_context_with_limit(session_id, budget).await
     }
 
     /// Get session context for chat (without history, only recent commands with output).
     /// This is used for LLM chat requests where we only want recent commands.
-    pub async fn get_chat_context(&self, session_id: &str, max_context_chars: Option<usize>) -> Result<String> {
+    /// `format` overrides the configured `context.format`.
+    pub async fn get_chat_context(&self, session_id: &str, budget: Option<TokenBudget>, format: Option<ContextFormat>) -> Result<String> {
         // Clone data under brief locks
-        let (commands, stream_path, hostnames) = {
+        let (commands, stream, hostnames) = {
             let sessions = self.sessions.read().await;
             let session = sessions
                 .get(session_id)
                 .ok_or_else(|| anyhow!("session not found: {}", session_id))?;
 
             let cmds = session.commands.read().await.clone();
-            let path = session.dir.join("stream.bin");
+            let stream = session.stream_file();
             let meta = session.meta.read().await;
             let mut hostnames = HashMap::new();
             if let Some(h) = meta.attrs.get("hostname") {
                 hostnames.insert(session_id.to_string(), h.clone());
             }
-            (cmds, path, hostnames)
+            (cmds, stream, hostnames)
         };
 
         // Build context outside all locks - expensive I/O happens here
-        let reader = FileStreamReader { stream_path };
-        let cc = &self.context_config;
+        let reader = Arc::new(self.file_reader(stream));
+        let cc = self.session_context_config(session_id).await;
 
         // Build context with NO history (only detailed commands with output)
         self.build_context_with_limit(
             &commands,
-            &reader,
+            reader,
             &hostnames,
             session_id,
             cc.completion.detailed_commands,
             0, // No history for chat
             cc.completion.min_current_session_commands,
             cc.completion.max_line_width,
-            max_context_chars,
+            budget,
+            format.unwrap_or(cc.format),
         )
         .await
     }
 
     /// Get all sessions context for chat (without history, only recent commands with output).
     /// This is used for LLM chat requests where we only want recent commands with output.
-    pub async fn get_all_sessions_chat_context(&self, current_session_id: &str, max_context_chars: Option<usize>) -> Result<String> {
-        let cc = &self.context_config;
-
-        // Snapshot session Arcs under brief read lock
-        let session_entries: Vec<_> = {
-            let sessions = self.sessions.read().await;
-            sessions.iter().map(|(sid, s)| (sid.clone(), s.clone())).collect()
-        };
-
-        let mut all_commands = Vec::new();
-        let mut offset_to_path: HashMap<(u64, u64), PathBuf> = HashMap::new();
-        let mut hostnames: HashMap<String, String> = HashMap::new();
-        for (sid, session) in &session_entries {
-            let stream_path = session.dir.join("stream.bin");
-            let meta = session.meta.read().await;
-            if let Some(h) = meta.attrs.get("hostname") {
-                hostnames.insert(sid.clone(), h.clone());
-            }
-            let commands = session.commands.read().await;
-            for cmd in commands.iter() {
-                offset_to_path
-                    .insert((cmd.stream_offset, cmd.stream_length), stream_path.clone());
-            }
-            all_commands.extend(commands.clone());
-        }
-
-        all_commands.sort_by_key(|c| c.started_at);
-
-        if all_commands.is_empty() {
-            return Ok(String::new());
-        }
-
-        // Build context outside all locks
-        let reader = MultiSessionReader {
-            readers: offset_to_path,
-        };
+    pub async fn get_all_sessions_chat_context(&self, current_session_id: &str, budget: Option<TokenBudget>, format: Option<ContextFormat>) -> Result<String> {
+        self.sessions_context(current_session_id, budget, None, false, format).await
+    }
 
-        // Build context with NO history (only detailed commands with output)
-        self.build_context_with_limit(
-            &all_commands,
-            &reader,
-            &hostnames,
-            current_session_id,
-            cc.completion.detailed_commands,
-            0, // No history for chat
-            cc.completion.min_current_session_commands,
-            cc.completion.max_line_width,
-            max_context_chars,
-        )
-        .await
+    /// Like `get_all_sessions_chat_context`, but only with commands from
+    /// sessions whose `hostname` attr is `hostname`. They share one
+    /// token budget.
+    pub async fn get_host_sessions_chat_context(
+        &self,
+        hostname: &str,
+        current_session_id: &str,
+        budget: Option<TokenBudget>,
+        format: Option<ContextFormat>,
+    ) -> Result<String> {
+        self.sessions_context(current_session_id, budget, Some(hostname), false, format)
+            .await
     }
 
     /// Get all commands and a stream reader across all sessions (for tool-use).
@@ -1377,91 +2311,225 @@ impl SessionManager {
         };
 
         let mut all_commands = Vec::new();
-        let mut offset_to_path: HashMap<(u64, u64), PathBuf> = HashMap::new();
+        let mut offset_to_path: HashMap<(u64, u64), StreamFile> = HashMap::new();
         for session in &session_entries {
-            let stream_path = session.dir.join("stream.bin");
+            let stream = session.stream_file();
             let commands = session.commands.read().await;
             for cmd in commands.iter() {
-                offset_to_path.insert((cmd.stream_offset, cmd.stream_length), stream_path.clone());
+                offset_to_path.insert((cmd.stream_offset, cmd.stream_length), stream.clone());
             }
             all_commands.extend(commands.clone());
         }
         all_commands.sort_by_key(|c| c.started_at);
 
-        let reader: Arc<dyn StreamReader> = Arc::new(MultiSessionReader { readers: offset_to_path });
+        let reader: Arc<dyn StreamReader> = Arc::new(self.multi_reader(offset_to_path));
         (all_commands, reader)
     }
 
-    /// Get session context with explicit max_context_chars limit (overrides config)
-    pub async fn get_session_context_with_limit(&self, session_id: &str, max_context_chars: Option<usize>) -> Result<String> {
+    /// Get session context with an explicit token budget (overrides config)
+    pub async fn get_session_context_with_limit(&self, session_id: &str, budget: Option<TokenBudget>) -> Result<String> {
         // Clone data under brief locks
-        let (commands, stream_path, hostnames) = {
+        let (commands, stream, hostnames) = {
             let sessions = self.sessions.read().await;
             let session = sessions
                 .get(session_id)
                 .ok_or_else(|| anyhow!("session not found: {}", session_id))?;
 
             let cmds = session.commands.read().await.clone();
-            let path = session.dir.join("stream.bin");
+            let stream = session.stream_file();
             let meta = session.meta.read().await;
             let mut hostnames = HashMap::new();
             if let Some(h) = meta.attrs.get("hostname") {
                 hostnames.insert(session_id.to_string(), h.clone());
             }
-            (cmds, path, hostnames)
+            (cmds, stream, hostnames)
         };
 
         // Build context outside all locks - expensive I/O happens here
-        let reader = FileStreamReader { stream_path };
-        let cc = &self.context_config;
+        let reader = Arc::new(self.file_reader(stream));
+        let cc = self.session_context_config(session_id).await;
 
-        // Build context with character limit handling
+        // Build context with token limit handling
         self.build_context_with_limit(
             &commands,
-            &reader,
+            reader,
             &hostnames,
             session_id,
             cc.completion.detailed_commands,
             cc.completion.history_commands,
             cc.completion.min_current_session_commands,
             cc.completion.max_line_width,
-            max_context_chars,
+            budget,
+            cc.format,
         )
         .await
     }
 
-    /// Build context with automatic reduction of command count if character limit is exceeded
+    /// Strategy for chat context: the most recent `total` commands, minus
+    /// excluded ones, limited to `max_command_age_hours` when set and to
+    /// commands carrying `required_tags`. With `cwd_bonus`, commands from the
+    /// current session's latest cwd are preferred for full output; it is
+    /// skipped under `required_tags`, as it would bring untagged ones back.
+    fn context_strategy(
+        &self,
+        total: usize,
+        commands: &[CommandRecord],
+        current_session_id: &str,
+        min_current_session_commands: usize,
+        cc: &CompletionContextConfig,
+    ) -> Box<dyn ContextStrategy> {
+        let recent = RecentCommands::new(total)
+            .with_current_session(current_session_id, min_current_session_commands)
+            .with_exclusion(self.exclude_commands())
+            .with_required_tags(cc.required_tags.clone());
+        let cwd = commands
+            .iter()
+            .rev()
+            .filter(|c| c.session_id == current_session_id)
+            .find_map(|c| c.cwd.as_deref());
+        let strategy: Box<dyn ContextStrategy> = match cwd {
+            Some(cwd) if cc.cwd_bonus > 0 && cc.required_tags.is_empty() => Box::new(
+                CwdPreferenceStrategy::new(Box::new(recent), cc.cwd_bonus)
+                    .with_cwd(cwd)
+                    .with_exclusion(self.exclude_commands()),
+            ),
+            _ => Box::new(recent),
+        };
+        match cc.max_command_age_hours {
+            Some(hours) => Box::new(TimeWindowStrategy::new(strategy, Duration::from_secs(hours * 3600))),
+            None => strategy,
+        }
+    }
+
+    /// Formatter for chat context in the given layout.
+    async fn context_formatter(
+        &self,
+        format: ContextFormat,
+        current_session_id: &str,
+        now_ms: u64,
+        cc: &CompletionContextConfig,
+    ) -> Box<dyn ContextFormatter> {
+        let max_output = cc.max_output_bytes_per_command;
+        match format {
+            ContextFormat::Grouped => {
+                let mut formatter = GroupedFormatter::new(current_session_id, now_ms, cc.head_lines, cc.tail_lines)
+                    .with_deferred_times(true)
+                    .with_env(self.session_env(current_session_id).await)
+                    .with_header_tags(session_header_tags(&self.get_session_attrs(current_session_id).await));
+                if let Some(max) = max_output {
+                    formatter = formatter.with_max_output_bytes(max);
+                }
+                Box::new(formatter)
+            }
+            ContextFormat::Interleaved => {
+                let mut formatter = InterleavedFormatter::new(current_session_id, now_ms, cc.head_lines, cc.tail_lines)
+                    .with_deferred_times(true);
+                if let Some(max) = max_output {
+                    formatter = formatter.with_max_output_bytes(max);
+                }
+                Box::new(formatter)
+            }
+            ContextFormat::Xml => {
+                let mut formatter = XmlFormatter::new(current_session_id, cc.head_lines, cc.tail_lines);
+                if let Some(max) = max_output {
+                    formatter = formatter.with_max_output_bytes(max);
+                }
+                Box::new(formatter)
+            }
+        }
+    }
+
+    /// Build context with automatic reduction of command count if its token
+    /// count, as estimated by the budget's backend, exceeds the budget.
+    /// Fails once `context_build_timeout_ms` has passed. Served from the
+    /// context cache while `commands` and the parameters are unchanged.
     #[allow(clippy::too_many_arguments)]
     async fn build_context_with_limit(
         &self,
         commands: &[CommandRecord],
-        reader: &dyn StreamReader,
+        reader: Arc<dyn StreamReader>,
         hostnames: &HashMap<String, String>,
         current_session_id: &str,
         detailed_commands: usize,
         history_commands: usize,
         min_current_session_commands: usize,
         max_line_width: usize,
-        max_context_chars: Option<usize>,
+        budget: Option<TokenBudget>,
+        format: ContextFormat,
     ) -> Result<String> {
         let now_ms = SystemTime::now()
             .duration_since(UNIX_EPOCH)
             .unwrap_or_default()
             .as_millis() as u64;
-        let formatter = GroupedFormatter::new(current_session_id, now_ms, self.context_config.completion.head_lines, self.context_config.completion.tail_lines);
+        let context_config = self.session_context_config(current_session_id).await;
+        let cc = &context_config.completion;
+        let cache_key = ContextCache::key(
+            current_session_id,
+            commands,
+            (
+                (detailed_commands, history_commands, min_current_session_commands, max_line_width),
+                (budget.as_ref().map(|b| (b.max_tokens, b.tokenizer().to_string())), format),
+                (cc.head_lines, cc.tail_lines, cc.max_output_bytes_per_command, cc.max_command_age_hours, cc.cwd_bonus),
+                &cc.required_tags,
+            ),
+        );
+        let cached = self.context_cache.lock().unwrap().get(cache_key).map(str::to_string);
+        let context = match cached {
+            Some(context) => context,
+            None => {
+                let context = self
+                    .build_context_uncached(
+                        commands,
+                        reader,
+                        hostnames,
+                        current_session_id,
+                        (detailed_commands, history_commands, min_current_session_commands),
+                        max_line_width,
+                        budget,
+                        format,
+                        cc,
+                        now_ms,
+                    )
+                    .await?;
+                self.context_cache.lock().unwrap().insert(cache_key, current_session_id, context.clone());
+                context
+            }
+        };
+        Ok(render_relative_times(&context, now_ms))
+    }
+
+    /// `build_context_with_limit` without the cache. Relative times are
+    /// left as placeholders.
+    #[allow(clippy::too_many_arguments)]
+    async fn build_context_uncached(
+        &self,
+        commands: &[CommandRecord],
+        reader: Arc<dyn StreamReader>,
+        hostnames: &HashMap<String, String>,
+        current_session_id: &str,
+        (detailed_commands, history_commands, min_current_session_commands): (usize, usize, usize),
+        max_line_width: usize,
+        budget: Option<TokenBudget>,
+        format: ContextFormat,
+        cc: &CompletionContextConfig,
+        now_ms: u64,
+    ) -> Result<String> {
+        let formatter = self.context_formatter(format, current_session_id, now_ms, cc).await;
+        let token = cancel_after(Duration::from_millis(cc.context_build_timeout_ms));
+        // Also stops the timer once the build is done
+        let _guard = token.clone().drop_guard();
 
         // Start with the original values
         let mut current_detailed = detailed_commands;
         let mut current_history = history_commands;
 
-        // If no character limit, build directly
-        if max_context_chars.is_none() {
+        // If no token limit, build directly
+        let Some(budget) = budget else {
             let total = current_detailed + current_history;
-            let strategy = RecentCommands::new(total)
-                .with_current_session(current_session_id, min_current_session_commands);
-            return omnish_context::build_context_with_session(
-                &strategy,
-                &formatter,
+            let strategy = self.context_strategy(total, commands, current_session_id, min_current_session_commands, cc);
+            return omnish_context::build_context_with_cancel(
+                &*strategy,
+                &*formatter,
                 commands,
                 reader,
                 hostnames,
@@ -1469,11 +2537,11 @@ impl SessionManager {
                 max_line_width,
                 Some(current_session_id),
                 min_current_session_commands,
+                token.clone(),
             )
             .await;
-        }
+        };
 
-        let max_chars = max_context_chars.unwrap();
         let mut context = String::new();
         let mut reduced = false;
 
@@ -1485,23 +2553,23 @@ impl SessionManager {
                 break;
             }
 
-            let strategy = RecentCommands::new(total)
-                .with_current_session(current_session_id, min_current_session_commands);
+            let strategy = self.context_strategy(total, commands, current_session_id, min_current_session_commands, cc);
 
-            context = omnish_context::build_context_with_session(
-                &strategy,
-                &formatter,
+            context = omnish_context::build_context_with_cancel(
+                &*strategy,
+                &*formatter,
                 commands,
-                reader,
+                reader.clone(),
                 hostnames,
                 current_detailed,
                 max_line_width,
                 Some(current_session_id),
                 min_current_session_commands,
+                token.clone(),
             )
             .await?;
 
-            if context.chars().count() <= max_chars {
+            if budget.fits(&render_relative_times(&context, now_ms)) {
                 break;
             }
 
@@ -1536,7 +2604,7 @@ impl SessionManager {
                 "context reduced: detailed={}, history={} (limit={})",
                 current_detailed,
                 current_history,
-                max_chars
+                budget.max_tokens
             );
         }
 
@@ -1568,12 +2636,27 @@ impl SessionManager {
     }
 
     pub async fn get_all_sessions_context(&self, current_session_id: &str) -> Result<String> {
-        self.get_all_sessions_context_with_limit(current_session_id, self.context_config.completion.max_context_chars).await
+        let budget = config_budget(&self.session_context_config(current_session_id).await.completion);
+        self.get_all_sessions_context_with_limit(current_session_id, budget).await
     }
 
-    /// Get all sessions context with explicit max_context_chars limit (overrides config)
-    pub async fn get_all_sessions_context_with_limit(&self, current_session_id: &str, max_context_chars: Option<usize>) -> Result<String> {
-        let cc = &self.context_config;
+    /// Get all sessions context with an explicit token budget (overrides config)
+    pub async fn get_all_sessions_context_with_limit(&self, current_session_id: &str, budget: Option<TokenBudget>) -> Result<String> {
+        self.sessions_context(current_session_id, budget, None, true, None).await
+    }
+
+    /// Context over all sessions, or only those on `host` when given.
+    /// Without `history` only detailed commands (with output) are included,
+    /// as for chat. `format` overrides the configured `context.format`.
+    async fn sessions_context(
+        &self,
+        current_session_id: &str,
+        budget: Option<TokenBudget>,
+        host: Option<&str>,
+        history: bool,
+        format: Option<ContextFormat>,
+    ) -> Result<String> {
+        let cc = self.session_context_config(current_session_id).await;
 
         // Snapshot session Arcs under brief read lock
         let session_entries: Vec<_> = {
@@ -1582,18 +2665,22 @@ impl SessionManager {
         };
 
         let mut all_commands = Vec::new();
-        let mut offset_to_path: HashMap<(u64, u64), PathBuf> = HashMap::new();
+        let mut offset_to_path: HashMap<(u64, u64), StreamFile> = HashMap::new();
         let mut hostnames: HashMap<String, String> = HashMap::new();
         for (sid, session) in &session_entries {
-            let stream_path = session.dir.join("stream.bin");
+            let stream = session.stream_file();
             let meta = session.meta.read().await;
-            if let Some(h) = meta.attrs.get("hostname") {
+            let hostname = meta.attrs.get("hostname");
+            if host.is_some_and(|host| hostname.is_none_or(|h| h != host)) {
+                continue;
+            }
+            if let Some(h) = hostname {
                 hostnames.insert(sid.clone(), h.clone());
             }
             let commands = session.commands.read().await;
             for cmd in commands.iter() {
                 offset_to_path
-                    .insert((cmd.stream_offset, cmd.stream_length), stream_path.clone());
+                    .insert((cmd.stream_offset, cmd.stream_length), stream.clone());
             }
             all_commands.extend(commands.clone());
         }
@@ -1605,21 +2692,20 @@ impl SessionManager {
         }
 
         // Build context outside all locks
-        let reader = MultiSessionReader {
-            readers: offset_to_path,
-        };
+        let reader = Arc::new(self.multi_reader(offset_to_path));
 
-        // Build context with character limit handling
+        // Build context with token limit handling
         self.build_context_with_limit(
             &all_commands,
-            &reader,
+            reader,
             &hostnames,
             current_session_id,
             cc.completion.detailed_commands,
-            cc.completion.history_commands,
+            if history { cc.completion.history_commands } else { 0 },
             cc.completion.min_current_session_commands,
             cc.completion.max_line_width,
-            max_context_chars,
+            budget,
+            format.unwrap_or(cc.format),
         )
         .await
     }
@@ -1673,7 +2759,8 @@ impl SessionManager {
         max_context_chars: Option<usize>,
         cwd_query: Option<CwdQuery<'_>>,
     ) -> Result<CompletionSections> {
-        let cc = &self.context_config.completion;
+        let context_config = self.session_context_config(current_session_id).await;
+        let cc = &context_config.completion;
 
         // Snapshot session Arcs under brief read lock
         let session_entries: Vec<_> = {
@@ -1682,11 +2769,11 @@ impl SessionManager {
         };
 
         let mut all_commands = Vec::new();
-        let mut offset_to_path: HashMap<(u64, u64), PathBuf> = HashMap::new();
+        let mut offset_to_path: HashMap<(u64, u64), StreamFile> = HashMap::new();
         let mut hostnames: HashMap<String, String> = HashMap::new();
         let mut live_cwd: Option<String> = None;
         for (sid, session) in &session_entries {
-            let stream_path = session.dir.join("stream.bin");
+            let stream = session.stream_file();
             let meta = session.meta.read().await;
             if let Some(h) = meta.attrs.get("hostname") {
                 hostnames.insert(sid.clone(), h.clone());
@@ -1699,7 +2786,7 @@ impl SessionManager {
             let commands = session.commands.read().await;
             for cmd in commands.iter() {
                 offset_to_path
-                    .insert((cmd.stream_offset, cmd.stream_length), stream_path.clone());
+                    .insert((cmd.stream_offset, cmd.stream_length), stream.clone());
             }
             all_commands.extend(commands.clone());
         }
@@ -1711,9 +2798,11 @@ impl SessionManager {
         }
 
         // Filter meaningful (non-empty command_line) and sort by started_at
+        let exclude = self.exclude_commands();
         let meaningful: Vec<&CommandRecord> = all_commands
             .iter()
             .filter(|c| c.command_line.is_some())
+            .filter(|c| !is_excluded(exclude.as_ref(), c))
             .collect();
 
         if meaningful.is_empty() {
@@ -1789,9 +2878,7 @@ impl SessionManager {
             (selected, det_count)
         };
 
-        let reader = MultiSessionReader {
-            readers: offset_to_path,
-        };
+        let reader = self.multi_reader(offset_to_path);
         let formatter = CompletionFormatter::new(
             current_session_id,
             cc.head_lines,
@@ -1967,157 +3054,798 @@ impl SessionManager {
             *frozen = Some(ts);
         }
 
-        // Rebuild sections with the advanced cutoff so the returned payload
-        // matches what subsequent requests will send.
-        let rebuilt = self
-            .build_completion_sections(session_id, max_context_chars, None)
-            .await?;
+        // Rebuild sections with the advanced cutoff so the returned payload
+        // matches what subsequent requests will send.
+        let rebuilt = self
+            .build_completion_sections(session_id, max_context_chars, None)
+            .await?;
+
+        // Keep last_completion_context in sync with the rebuilt form so the
+        // next ratio comparison starts from the new baseline.
+        {
+            let mut cached = self.last_completion_context.write().await;
+            *cached = if rebuilt.stable_prefix.is_empty() && rebuilt.remainder.is_empty() {
+                String::new()
+            } else {
+                format!("{}{}", rebuilt.stable_prefix, rebuilt.remainder)
+            };
+        }
+
+        Ok(Some(rebuilt))
+    }
+
+    /// Return the maximum `started_at` across all registered sessions, or
+    /// `None` if no commands exist.
+    async fn latest_command_started_at(&self) -> Option<u64> {
+        let session_entries: Vec<_> = {
+            let sessions = self.sessions.read().await;
+            sessions.values().cloned().collect()
+        };
+        let mut latest: Option<u64> = None;
+        for session in &session_entries {
+            let commands = session.commands.read().await;
+            if let Some(last) = commands.last() {
+                let ts = last.started_at;
+                latest = Some(match latest {
+                    Some(prev) => prev.max(ts),
+                    None => ts,
+                });
+            }
+        }
+        latest
+    }
+
+    /// Get the last completion context (read-only) for logging/analytics purposes.
+    /// Returns the cached context from the previous build, or empty string if no previous context.
+    pub async fn get_last_completion_context(&self) -> String {
+        let cached = self.last_completion_context.read().await;
+        cached.clone()
+    }
+
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[tokio::test]
+    async fn test_close_idle_writers_releases_fd() {
+        let dir = tempfile::tempdir().unwrap();
+        let base = dir.path().to_path_buf();
+        let mgr = SessionManager::new(base, Default::default());
+
+        mgr.register("idle_sess", None, Default::default(), None)
+            .await
+            .unwrap();
+
+        // No writer should be open yet (lazy creation).
+        {
+            let sessions = mgr.sessions.read().await;
+            let session = sessions.get("idle_sess").unwrap();
+            let sw = session.stream_writer.lock().await;
+            assert!(sw.writer.is_none(), "writer should be lazy");
+        }
+
+        // First write opens the writer.
+        mgr.write_io("idle_sess", 100, 0, b"hello").await.unwrap();
+        {
+            let sessions = mgr.sessions.read().await;
+            let session = sessions.get("idle_sess").unwrap();
+            let sw = session.stream_writer.lock().await;
+            assert!(sw.writer.is_some(), "writer should be open after write_io");
+        }
+
+        // With max_idle = 0, the writer is past its idle deadline immediately.
+        let closed = mgr.close_idle_writers(std::time::Duration::ZERO).await;
+        assert_eq!(closed, 1);
+        {
+            let sessions = mgr.sessions.read().await;
+            let session = sessions.get("idle_sess").unwrap();
+            let sw = session.stream_writer.lock().await;
+            assert!(sw.writer.is_none(), "writer should be closed after idle sweep");
+        }
+
+        // Subsequent write reopens the writer; data still flows through.
+        mgr.write_io("idle_sess", 200, 1, b"world").await.unwrap();
+        {
+            let sessions = mgr.sessions.read().await;
+            let session = sessions.get("idle_sess").unwrap();
+            let sw = session.stream_writer.lock().await;
+            assert!(sw.writer.is_some(), "writer should reopen on next write");
+            assert_eq!(sw.current_stream_pos, sw.writer.as_ref().unwrap().position());
+        }
+
+        // With a large max_idle, an active writer is not closed.
+        let closed = mgr.close_idle_writers(std::time::Duration::from_secs(3600)).await;
+        assert_eq!(closed, 0);
+    }
+
+    #[tokio::test]
+    async fn test_load_existing_restores_sessions() {
+        let dir = tempfile::tempdir().unwrap();
+        let base = dir.path().to_path_buf();
+        // Note: completions_dir is no longer needed - SessionManager handles it internally
+
+        // Register a session and add a command via normal flow
+        {
+            let mgr = SessionManager::new(base.clone(), Default::default());
+            mgr.register("sess1", None, Default::default(), None)
+                .await
+                .unwrap();
+            mgr.write_io("sess1", 100, 0, b"$ ls\n").await.unwrap();
+            mgr.write_io("sess1", 200, 1, b"file.txt\n").await.unwrap();
+            mgr.receive_command(
+                "sess1",
+                CommandRecord {
+                    command_id: "cmd1".into(),
+                    session_id: "sess1".into(),
+                    command_line: Some("ls".into()),
+                    cwd: Some("/tmp".into()),
+                    started_at: 100,
+                    ended_at: Some(200),
+                    output_summary: "file.txt".into(),
+                    stream_offset: 0,
+                    stream_length: 0,
+                    exit_code: None,
+                    exit_signal: None,
+                    checksum: None,
+                    tags: Vec::new(),
+                },
+            )
+            .await
+            .unwrap();
+            // Drop the manager (simulates daemon shutdown)
+        }
+
+        // Create a new manager on the same directory and load existing sessions
+        let mgr2 = SessionManager::new(base, Default::default());
+        let count = mgr2.load_existing().await.unwrap();
+        assert_eq!(count, 1);
+
+        let commands = mgr2.get_commands("sess1").await.unwrap();
+        assert_eq!(commands.len(), 1);
+        assert_eq!(commands[0].command_line.as_deref(), Some("ls"));
+
+        let active = mgr2.list_active().await;
+        assert!(active.contains(&"sess1".to_string()));
+    }
+
+    #[tokio::test]
+    async fn test_compressed_stream_survives_reload() {
+        let dir = tempfile::tempdir().unwrap();
+        let base = dir.path().to_path_buf();
+        let mut rec = make_rec(1, "/tmp", "ls");
+        rec.session_id = "sess1".into();
+        {
+            let mgr = SessionManager::new(base.clone(), Default::default()).with_compress_streams(true);
+            mgr.register("sess1", None, Default::default(), None).await.unwrap();
+            mgr.write_io("sess1", 100, 1, b"file.txt\n").await.unwrap();
+            mgr.receive_command("sess1", rec.clone()).await.unwrap();
+        }
+
+        // Reloading without the option keeps appending to the compressed file.
+        let mgr2 = SessionManager::new(base, Default::default());
+        assert_eq!(mgr2.load_existing().await.unwrap(), 1);
+        mgr2.write_io("sess1", 300, 1, b"second\n").await.unwrap();
+        rec.command_id = "cmd2".into();
+        mgr2.receive_command("sess1", rec).await.unwrap();
+
+        let commands = mgr2.get_commands("sess1").await.unwrap();
+        let stream_path = {
+            let sessions = mgr2.sessions.read().await;
+            sessions.get("sess1").unwrap().dir.join("stream.bin")
+        };
+        assert_eq!(&std::fs::read(&stream_path).unwrap()[..3], b"OSZ");
+        assert_eq!(commands[1].stream_offset, 17 + 9);
+        let entries =
+            read_range(&stream_path, commands[1].stream_offset, commands[1].stream_length, None).unwrap();
+        assert_eq!(entries.len(), 1);
+        assert_eq!(entries[0].data, b"second\n");
+    }
+
+    #[tokio::test]
+    async fn test_session_metadata_survives_reload() {
+        let dir = tempfile::tempdir().unwrap();
+        let base = dir.path().to_path_buf();
+        {
+            let mgr = SessionManager::new(base.clone(), Default::default());
+            mgr.register("abc12345", None, Default::default(), None).await.unwrap();
+            mgr.set_session_metadata("abc12345", vec!["prod".into(), "debug".into()], Some("login outage".into()))
+                .await
+                .unwrap();
+            assert!(mgr.set_session_metadata("missing", Vec::new(), None).await.is_err());
+        }
+
+        let mgr = SessionManager::new(base, Default::default());
+        mgr.load_existing().await.unwrap();
+        let meta = mgr.get_session_meta("abc12345").await.unwrap();
+        assert_eq!(meta.tags, vec!["prod", "debug"]);
+        assert_eq!(meta.description.as_deref(), Some("login outage"));
+        let list = mgr.format_sessions_list("abc12345").await;
+        assert!(list.contains("* abc12345 [active] [prod, debug] cmds=0/0"), "{}", list);
+    }
+
+    #[tokio::test]
+    async fn test_encrypted_stream_survives_reload() {
+        let dir = tempfile::tempdir().unwrap();
+        let base = dir.path().to_path_buf();
+        let master = [3u8; 32];
+        let mut rec = make_rec(1, "/tmp", "cat .env");
+        rec.session_id = "sess1".into();
+        {
+            let mgr = SessionManager::new(base.clone(), Default::default())
+                .with_master_key(master)
+                .with_encrypt_streams(true);
+            mgr.register("sess1", None, Default::default(), None).await.unwrap();
+            mgr.write_io("sess1", 100, 1, b"TOKEN=abc123\n").await.unwrap();
+            mgr.receive_command("sess1", rec).await.unwrap();
+        }
+
+        // Encryption off but the key loaded: the session still reads back.
+        let mgr2 = SessionManager::new(base.clone(), Default::default()).with_master_key(master);
+        mgr2.load_existing().await.unwrap();
+        let (commands, reader) = mgr2.get_commands_with_reader("sess1").await.unwrap();
+        let entries = reader.read_command_output(commands[0].stream_offset, commands[0].stream_length).unwrap();
+        assert_eq!(entries[0].data, b"TOKEN=abc123\n");
+        let stream_path = mgr2.sessions.read().await.get("sess1").unwrap().dir.join("stream.bin");
+        assert_eq!(&std::fs::read(&stream_path).unwrap()[..3], b"OSE");
+
+        // Without the master key the output cannot be read.
+        let mgr3 = SessionManager::new(base, Default::default());
+        mgr3.load_existing().await.unwrap();
+        let (commands, reader) = mgr3.get_commands_with_reader("sess1").await.unwrap();
+        assert!(reader.read_command_output(commands[0].stream_offset, commands[0].stream_length).is_err());
+    }
+
+    #[tokio::test]
+    async fn test_stream_trimmed_past_cap() {
+        let dir = tempfile::tempdir().unwrap();
+        let base = dir.path().to_path_buf();
+        let mgr = SessionManager::new(base.clone(), Default::default()).with_max_stream_bytes(1000);
+        mgr.register("sess1", None, Default::default(), None).await.unwrap();
+
+        // Each command owns one 200-byte entry (13 header + 183 data + 4 CRC).
+        let mut stale_reader = None;
+        for i in 0..6u64 {
+            mgr.write_io("sess1", i * 10, 1, &[b'a' + i as u8; 183]).await.unwrap();
+            let mut rec = make_rec(i * 10, "/tmp", &format!("cmd{}", i));
+            rec.command_id = format!("c{}", i);
+            rec.session_id = "sess1".into();
+            if i == 5 {
+                stale_reader = Some(mgr.get_all_commands_with_reader().await.1);
+            }
+            mgr.receive_command("sess1", rec).await.unwrap();
+        }
+
+        // 1200 bytes > 1000: trimmed to <= 750, i.e. the newest three entries.
+        let (commands, reader) = mgr.get_commands_with_reader("sess1").await.unwrap();
+        let ranges: Vec<(u64, u64)> =
+            commands.iter().map(|c| (c.stream_offset, c.stream_length)).collect();
+        assert_eq!(ranges, vec![(0, 0), (0, 0), (0, 0), (0, 200), (200, 200), (400, 200)]);
+        let kept = reader.read_command_output(commands[4].stream_offset, commands[4].stream_length).unwrap();
+        assert_eq!(kept[0].data, vec![b'e'; 183]);
+        assert!(reader.read_command_output(commands[0].stream_offset, commands[0].stream_length).unwrap().is_empty());
+        // A reader built before the trim no longer serves its old offsets.
+        assert!(stale_reader.unwrap().read_command_output(600, 200).unwrap().is_empty());
+
+        // Offsets and the stream survive a reload, and appends continue.
+        drop(mgr);
+        let mgr2 = SessionManager::new(base, Default::default());
+        mgr2.load_existing().await.unwrap();
+        mgr2.write_io("sess1", 100, 1, b"more").await.unwrap();
+        let mut rec = make_rec(100, "/tmp", "cmd6");
+        rec.session_id = "sess1".into();
+        mgr2.receive_command("sess1", rec).await.unwrap();
+        let commands = mgr2.get_commands("sess1").await.unwrap();
+        assert_eq!(commands[3].stream_offset, 0);
+        assert_eq!((commands[6].stream_offset, commands[6].stream_length), (600, 21));
+    }
+
+    #[tokio::test]
+    async fn test_query_history_survives_restart() {
+        let dir = tempfile::tempdir().unwrap();
+        let base = dir.path().to_path_buf();
+        {
+            let mgr = SessionManager::new(base.clone(), Default::default());
+            for (i, q) in ["why does curl fail", "what is errno 111"].iter().enumerate() {
+                mgr.record_query(QueryRecord {
+                    request_id: format!("r{}", i),
+                    session_id: "sess1".into(),
+                    query: q.to_string(),
+                    response: "connection refused".into(),
+                    model: "mock".into(),
+                    latency_ms: 10,
+                    timestamp_ms: 1000 + i as u64,
+                });
+            }
+            tokio::time::sleep(std::time::Duration::from_millis(100)).await;
+        }
+
+        let mgr2 = SessionManager::new(base, Default::default());
+        let history = mgr2.get_query_history("sess1", 10);
+        assert_eq!(history.len(), 2);
+        assert_eq!(history[0].query, "why does curl fail");
+        assert_eq!(history[1].request_id, "r1");
+        assert!(mgr2.get_query_history("other", 10).is_empty());
+    }
+
+    #[tokio::test]
+    async fn test_token_usage_accumulates_into_stats() {
+        let dir = tempfile::tempdir().unwrap();
+        let base = dir.path().to_path_buf();
+        let usage = |i: u64, prompt_tokens: u64, completion_tokens: u64| TokenUsageRecord {
+            request_id: format!("r{}", i),
+            session_id: "sess1".into(),
+            model: "mock".into(),
+            prompt_tokens,
+            completion_tokens,
+            timestamp_ms: 1000 + i,
+            estimated: false,
+        };
+        {
+            let mgr = SessionManager::new(base.clone(), Default::default());
+            mgr.register("sess1", None, HashMap::new(), None).await.unwrap();
+            mgr.record_token_usage(usage(1, 120, 30));
+            mgr.record_token_usage(usage(2, 80, 10));
+            let stats = mgr.get_statistics("sess1").await.unwrap();
+            assert_eq!((stats.total_prompt_tokens, stats.total_completion_tokens), (200, 40));
+            tokio::time::sleep(std::time::Duration::from_millis(100)).await;
+        }
+
+        // Totals are reloaded from the usage log after a restart.
+        let mgr2 = SessionManager::new(base, Default::default());
+        mgr2.record_token_usage(usage(3, 5, 5));
+        assert_eq!(
+            mgr2.token_usage("sess1"),
+            TokenUsage { prompt_tokens: 205, completion_tokens: 45 }
+        );
+        assert_eq!(mgr2.token_usage("other"), TokenUsage::default());
+    }
+
+    #[tokio::test]
+    async fn test_replay_stream_order_and_speed() {
+        use futures_util::StreamExt;
+
+        let dir = tempfile::tempdir().unwrap();
+        let mgr = SessionManager::new(dir.path().to_path_buf(), Default::default());
+        mgr.register("s1", None, HashMap::new(), None).await.unwrap();
+        // Written out of order; input entries are not replayed.
+        mgr.write_io("s1", 1_000, 1, b"one ").await.unwrap();
+        mgr.write_io("s1", 1_100, 0, b"typed").await.unwrap();
+        mgr.write_io("s1", 1_400, 1, b"three").await.unwrap();
+        mgr.write_io("s1", 1_200, 1, b"two ").await.unwrap();
+
+        let started = Instant::now();
+        let chunks: Vec<Vec<u8>> = mgr
+            .replay_stream("s1", 0.0)
+            .await
+            .unwrap()
+            .map(|c| c.unwrap())
+            .collect()
+            .await;
+        assert!(started.elapsed() < Duration::from_millis(100));
+        assert_eq!(chunks, vec![b"one ".to_vec(), b"two ".to_vec(), b"three".to_vec()]);
+
+        // 400ms recorded; at 2x speed all bytes arrive after ~200ms.
+        let started = Instant::now();
+        let bytes: Vec<u8> = mgr
+            .replay_stream("s1", 2.0)
+            .await
+            .unwrap()
+            .map(|c| c.unwrap())
+            .concat()
+            .await;
+        let elapsed = started.elapsed();
+        assert_eq!(bytes, b"one two three");
+        assert!(
+            elapsed >= Duration::from_millis(190) && elapsed < Duration::from_millis(350),
+            "2x replay took {:?}",
+            elapsed
+        );
+
+        assert!(mgr.replay_stream("missing", 1.0).await.is_err());
+    }
+
+    #[tokio::test]
+    async fn test_get_statistics() {
+        let dir = tempfile::tempdir().unwrap();
+        let mgr = SessionManager::new(dir.path().to_path_buf(), Default::default());
+        mgr.register("s1", None, HashMap::new(), None).await.unwrap();
+        let runs = [
+            ("git status", 0),
+            ("cargo build", 101),
+            ("git status", 0),
+            ("ls", 0),
+            ("cargo build", 101),
+            ("git status", 0),
+            ("make", 2),
+        ];
+        for (i, (line, exit)) in runs.iter().enumerate() {
+            let start = 10_000 + i as u64 * 1_000;
+            mgr.receive_command("s1", CommandRecord {
+                command_id: format!("c{}", i),
+                session_id: "s1".into(),
+                command_line: Some(line.to_string()),
+                cwd: None,
+                started_at: start,
+                ended_at: Some(start + 100 * (i as u64 + 1)),
+                output_summary: String::new(),
+                stream_offset: 0,
+                stream_length: 0,
+                exit_code: Some(*exit),
+                exit_signal: None,
+                checksum: None,
+                tags: Vec::new(),
+            }).await.unwrap();
+        }
+
+        let stats = mgr.get_statistics("s1").await.unwrap();
+        assert_eq!(stats.total_commands, 7);
+        assert_eq!(stats.failed_commands, 3);
+        assert_eq!(stats.unique_commands, 4);
+        // First start 10_000, last end 16_000 + 700.
+        assert_eq!(stats.total_session_duration_ms, 6_700);
+        // (100 + 200 + ... + 700) / 7
+        assert_eq!(stats.average_command_duration_ms, 400);
+        assert_eq!(
+            stats.most_used_commands,
+            vec![
+                ("git status".to_string(), 3),
+                ("cargo build".to_string(), 2),
+                ("ls".to_string(), 1),
+            ]
+        );
+        assert!(mgr.get_statistics("missing").await.is_err());
+    }
 
-        // Keep last_completion_context in sync with the rebuilt form so the
-        // next ratio comparison starts from the new baseline.
+    #[tokio::test]
+    async fn test_tag_commands_persists() {
+        let dir = tempfile::tempdir().unwrap();
+        let base = dir.path().to_path_buf();
         {
-            let mut cached = self.last_completion_context.write().await;
-            *cached = if rebuilt.stable_prefix.is_empty() && rebuilt.remainder.is_empty() {
-                String::new()
-            } else {
-                format!("{}{}", rebuilt.stable_prefix, rebuilt.remainder)
-            };
+            let mgr = SessionManager::new(base.clone(), Default::default());
+            mgr.register("s1", None, Default::default(), None).await.unwrap();
+            for (i, cmd) in ["cargo build", "ls", "cargo test"].iter().enumerate() {
+                let mut rec = make_rec(i as u64, "/tmp", cmd);
+                rec.session_id = "s1".into();
+                mgr.receive_command("s1", rec).await.unwrap();
+            }
+            assert_eq!(mgr.tag_commands("s1", "^cargo", "rust").await.unwrap(), 2);
+            assert_eq!(mgr.tag_commands("s1", "cargo", "rust").await.unwrap(), 0);
+            assert_eq!(mgr.tag_commands("s1", "test", "ci").await.unwrap(), 1);
+            assert!(mgr.tag_commands("s1", "(", "bad").await.is_err());
+            assert!(mgr.tag_commands("missing", "ls", "x").await.is_err());
         }
 
-        Ok(Some(rebuilt))
+        let mgr2 = SessionManager::new(base, Default::default());
+        assert_eq!(mgr2.load_existing().await.unwrap(), 1);
+        let tags: Vec<_> = mgr2
+            .get_commands("s1")
+            .await
+            .unwrap()
+            .into_iter()
+            .map(|c| c.tags)
+            .collect();
+        assert_eq!(tags, vec![vec!["rust".to_string()], vec![], vec!["rust".into(), "ci".into()]]);
     }
 
-    /// Return the maximum `started_at` across all registered sessions, or
-    /// `None` if no commands exist.
-    async fn latest_command_started_at(&self) -> Option<u64> {
-        let session_entries: Vec<_> = {
-            let sessions = self.sessions.read().await;
-            sessions.values().cloned().collect()
-        };
-        let mut latest: Option<u64> = None;
-        for session in &session_entries {
-            let commands = session.commands.read().await;
-            if let Some(last) = commands.last() {
-                let ts = last.started_at;
-                latest = Some(match latest {
-                    Some(prev) => prev.max(ts),
-                    None => ts,
-                });
+    #[tokio::test]
+    async fn test_merge_sessions_interleaves_commands() {
+        let dir = tempfile::tempdir().unwrap();
+        let mgr = SessionManager::new(dir.path().to_path_buf(), Default::default());
+        for (sid, steps) in [
+            ("a", [(100, "make build"), (300, "make test")]),
+            ("b", [(200, "tail -f log"), (400, "grep panic log")]),
+        ] {
+            mgr.register(sid, None, Default::default(), None).await.unwrap();
+            for (ts, cmd) in steps {
+                mgr.write_io(sid, ts, 1, format!("out of {}\n", cmd).as_bytes()).await.unwrap();
+                let mut rec = make_rec(ts, "/tmp", cmd);
+                rec.session_id = sid.into();
+                rec.started_at = ts;
+                mgr.receive_command(sid, rec).await.unwrap();
             }
         }
-        latest
-    }
 
-    /// Get the last completion context (read-only) for logging/analytics purposes.
-    /// Returns the cached context from the previous build, or empty string if no previous context.
-    pub async fn get_last_completion_context(&self) -> String {
-        let cached = self.last_completion_context.read().await;
-        cached.clone()
-    }
+        mgr.end_session("b").await.unwrap();
+        assert!(mgr.merge_sessions(&["a", "b"], "m").await.is_err(), "a is still active");
+        mgr.end_session("a").await.unwrap();
+        mgr.merge_sessions(&["a", "b"], "m").await.unwrap();
+        assert!(mgr.merge_sessions(&["a"], "m").await.is_err(), "target exists");
 
-}
+        let commands = mgr.get_commands("m").await.unwrap();
+        let lines: Vec<_> = commands.iter().map(|c| c.command_line.as_deref().unwrap()).collect();
+        assert_eq!(lines, vec!["make build", "tail -f log", "make test", "grep panic log"]);
+        assert!(commands.iter().all(|c| c.session_id == "m"));
 
-#[cfg(test)]
-mod tests {
-    use super::*;
+        let stream_path = {
+            let sessions = mgr.sessions.read().await;
+            sessions.get("m").unwrap().dir.join("stream.bin")
+        };
+        let entries = read_range(&stream_path, commands[1].stream_offset, commands[1].stream_length, None).unwrap();
+        assert_eq!(entries[0].data, b"out of tail -f log\n");
+
+        let ctx = mgr.get_session_context("m").await.unwrap();
+        let pos: Vec<_> = lines.iter().map(|l| ctx.find(l).unwrap()).collect();
+        assert!(pos.windows(2).all(|w| w[0] < w[1]), "{}", ctx);
+    }
 
     #[tokio::test]
-    async fn test_close_idle_writers_releases_fd() {
+    async fn test_vacuum_drops_unreferenced_output() {
         let dir = tempfile::tempdir().unwrap();
         let base = dir.path().to_path_buf();
-        let mgr = SessionManager::new(base, Default::default());
-
-        mgr.register("idle_sess", None, Default::default(), None)
-            .await
-            .unwrap();
+        let mgr = SessionManager::new(base.clone(), Default::default());
+        mgr.register("sess1", None, Default::default(), None).await.unwrap();
+        for i in 0..6u64 {
+            mgr.write_io("sess1", i * 10, 1, &[b'a' + i as u8; 183]).await.unwrap();
+            let mut rec = make_rec(i * 10, "/tmp", &format!("cmd{}", i));
+            rec.command_id = format!("c{}", i);
+            rec.session_id = "sess1".into();
+            mgr.receive_command("sess1", rec).await.unwrap();
+        }
+        mgr.write_io("sess1", 60, 1, b"running").await.unwrap();
 
-        // No writer should be open yet (lazy creation).
+        // Fragment the session: forget commands 1, 2 and 4, leaving their
+        // output as unreferenced gaps in stream.bin.
         {
             let sessions = mgr.sessions.read().await;
-            let session = sessions.get("idle_sess").unwrap();
-            let sw = session.stream_writer.lock().await;
-            assert!(sw.writer.is_none(), "writer should be lazy");
+            let session = sessions.get("sess1").unwrap();
+            let mut commands = session.commands.write().await;
+            commands.retain(|c| !["c1", "c2", "c4"].contains(&c.command_id.as_str()));
+            CommandRecord::save_all(&commands, &session.dir).unwrap();
         }
 
-        // First write opens the writer.
-        mgr.write_io("idle_sess", 100, 0, b"hello").await.unwrap();
-        {
-            let sessions = mgr.sessions.read().await;
-            let session = sessions.get("idle_sess").unwrap();
-            let sw = session.stream_writer.lock().await;
-            assert!(sw.writer.is_some(), "writer should be open after write_io");
+        let stats = mgr.vacuum("sess1").await.unwrap();
+        // 4-byte file header, six 200-byte entries and the 24-byte running one.
+        assert_eq!(stats.bytes_before, 4 + 6 * 200 + 24);
+        assert_eq!(stats.bytes_after, 4 + 3 * 200 + 24);
+
+        let (commands, reader) = mgr.get_commands_with_reader("sess1").await.unwrap();
+        let ranges: Vec<(u64, u64)> =
+            commands.iter().map(|c| (c.stream_offset, c.stream_length)).collect();
+        assert_eq!(ranges, vec![(0, 200), (200, 200), (400, 200)]);
+        for (cmd, byte) in commands.iter().zip([b'a', b'd', b'f']) {
+            let entries = reader.read_command_output(cmd.stream_offset, cmd.stream_length).unwrap();
+            assert_eq!(entries[0].data, vec![byte; 183]);
         }
 
-        // With max_idle = 0, the writer is past its idle deadline immediately.
-        let closed = mgr.close_idle_writers(std::time::Duration::ZERO).await;
-        assert_eq!(closed, 1);
-        {
-            let sessions = mgr.sessions.read().await;
-            let session = sessions.get("idle_sess").unwrap();
-            let sw = session.stream_writer.lock().await;
-            assert!(sw.writer.is_none(), "writer should be closed after idle sweep");
+        // The running command keeps its output, and the rewrite survives a reload.
+        let mut rec = make_rec(70, "/tmp", "cmd6");
+        rec.session_id = "sess1".into();
+        mgr.receive_command("sess1", rec).await.unwrap();
+        drop(mgr);
+        let mgr2 = SessionManager::new(base, Default::default());
+        mgr2.load_existing().await.unwrap();
+        let (commands, reader) = mgr2.get_commands_with_reader("sess1").await.unwrap();
+        assert_eq!((commands[1].stream_offset, commands[1].stream_length), (200, 200));
+        assert_eq!((commands[3].stream_offset, commands[3].stream_length), (600, 24));
+        let entries = reader.read_command_output(commands[3].stream_offset, commands[3].stream_length).unwrap();
+        assert_eq!(entries[0].data, b"running");
+
+        assert!(mgr2.vacuum("missing").await.is_err());
+    }
+
+    #[tokio::test]
+    async fn test_session_context_cache() {
+        let dir = tempfile::tempdir().unwrap();
+        let mgr = SessionManager::new(dir.path().to_path_buf(), Default::default());
+        mgr.register("c", None, HashMap::new(), None).await.unwrap();
+        mgr.write_io("c", 10, 1, b"$ make\r\nbuilt\r\n").await.unwrap();
+        let mut rec = make_rec(10, "/src", "make");
+        rec.session_id = "c".into();
+        mgr.receive_command("c", rec).await.unwrap();
+
+        let first = mgr.get_session_context("c").await.unwrap();
+        let built_at = |mgr: &SessionManager| {
+            let cache = mgr.context_cache.lock().unwrap();
+            assert_eq!(cache.entries.len(), 1);
+            cache.entries.values().next().unwrap().built_at
+        };
+        let cached_at = built_at(&mgr);
+
+        // Unchanged session: served from the cache, not rebuilt
+        assert_eq!(mgr.get_session_context("c").await.unwrap(), first);
+        assert_eq!(built_at(&mgr), cached_at);
+
+        mgr.write_io("c", 20, 1, b"$ make test\r\nok\r\n").await.unwrap();
+        let mut rec = make_rec(20, "/src", "make test");
+        rec.session_id = "c".into();
+        mgr.receive_command("c", rec).await.unwrap();
+        assert!(mgr.context_cache.lock().unwrap().entries.is_empty());
+
+        let fresh = mgr.get_session_context("c").await.unwrap();
+        assert!(fresh.contains("make test"), "{}", fresh);
+        assert!(!first.contains("make test"));
+        assert_ne!(built_at(&mgr), cached_at);
+
+        // Chat contexts are cached too, with ages filled in on every call
+        let chat = mgr.get_chat_context("c", None, None).await.unwrap();
+        assert_eq!(mgr.context_cache.lock().unwrap().entries.len(), 2);
+        assert_eq!(mgr.get_chat_context("c", None, None).await.unwrap(), chat);
+        assert_eq!(mgr.context_cache.lock().unwrap().entries.len(), 2);
+        assert!(chat.contains(" ago]"), "{}", chat);
+        assert!(!chat.contains('\u{1}'), "{:?}", chat);
+        assert!(mgr.context_cache.lock().unwrap().entries.values().all(|e| e.context.contains('\u{1}')));
+
+        // Tagging changes what a context shows
+        assert_eq!(mgr.tag_commands("c", "^make", "build").await.unwrap(), 2);
+        assert!(mgr.context_cache.lock().unwrap().entries.is_empty());
+    }
+
+    #[tokio::test]
+    async fn test_project_config_overrides_context() {
+        let dir = tempfile::tempdir().unwrap();
+        let project = tempfile::tempdir().unwrap();
+        std::fs::write(
+            project.path().join(".omnish.toml"),
+            "[context.completion]\ndetailed_commands = 5\n",
+        )
+        .unwrap();
+        let cwd = project.path().join("src");
+        std::fs::create_dir(&cwd).unwrap();
+
+        let mgr = SessionManager::new(dir.path().to_path_buf(), Default::default());
+        let attrs = HashMap::from([("shell_cwd".to_string(), cwd.display().to_string())]);
+        mgr.register("p", None, attrs, None).await.unwrap();
+        mgr.register("q", None, HashMap::new(), None).await.unwrap();
+        for i in 0..12u64 {
+            let cmd = format!("echo c{:02}", i);
+            mgr.write_io("p", i * 10, 1, format!("$ {}\r\nc{:02}\r\n", cmd, i).as_bytes()).await.unwrap();
+            let mut rec = make_rec(i * 10, &cwd.display().to_string(), &cmd);
+            rec.session_id = "p".into();
+            mgr.receive_command("p", rec).await.unwrap();
         }
 
-        // Subsequent write reopens the writer; data still flows through.
-        mgr.write_io("idle_sess", 200, 1, b"world").await.unwrap();
-        {
-            let sessions = mgr.sessions.read().await;
-            let session = sessions.get("idle_sess").unwrap();
-            let sw = session.stream_writer.lock().await;
-            assert!(sw.writer.is_some(), "writer should reopen on next write");
-            assert_eq!(sw.current_stream_pos, sw.writer.as_ref().unwrap().position());
+        assert_eq!(mgr.session_context_config("p").await.completion.detailed_commands, 5);
+        // Sessions outside the project keep the global settings
+        assert_eq!(
+            mgr.session_context_config("q").await.completion.detailed_commands,
+            mgr.context_config().completion.detailed_commands,
+        );
+
+        let ctx = mgr.get_chat_context("p", None, None).await.unwrap();
+        for i in 7..12 {
+            assert!(ctx.contains(&format!("echo c{:02}", i)), "{}", ctx);
+        }
+        assert!(!ctx.contains("echo c06"), "{}", ctx);
+
+        // An edited file is re-read once its mtime moves
+        let path = project.path().join(".omnish.toml");
+        std::fs::write(&path, "[context.completion]\ndetailed_commands = 3\n").unwrap();
+        std::fs::File::options()
+            .write(true)
+            .open(&path)
+            .unwrap()
+            .set_modified(std::time::SystemTime::now() + Duration::from_secs(5))
+            .unwrap();
+        assert_eq!(mgr.session_context_config("p").await.completion.detailed_commands, 3);
+    }
+
+    #[tokio::test]
+    async fn test_chat_context_cwd_bonus() {
+        let dir = tempfile::tempdir().unwrap();
+        let mut config = ContextConfig::default();
+        config.completion.detailed_commands = 2;
+        config.completion.cwd_bonus = 2;
+        let mgr = SessionManager::new(dir.path().to_path_buf(), config);
+        mgr.register("s", None, HashMap::new(), None).await.unwrap();
+        for (i, cwd) in ["/repo", "/tmp", "/tmp", "/tmp", "/repo"].iter().enumerate() {
+            let i = i as u64;
+            mgr.write_io("s", i * 10, 1, format!("$ cmd{}\r\nout{}\r\n", i, i).as_bytes()).await.unwrap();
+            let mut rec = make_rec(i * 10, cwd, &format!("cmd{}", i));
+            rec.session_id = "s".into();
+            mgr.receive_command("s", rec).await.unwrap();
         }
 
-        // With a large max_idle, an active writer is not closed.
-        let closed = mgr.close_idle_writers(std::time::Duration::from_secs(3600)).await;
-        assert_eq!(closed, 0);
+        let ctx = mgr.get_chat_context("s", None, None).await.unwrap();
+        // The older /repo command displaces the latest /tmp one
+        assert!(ctx.contains("out0") && ctx.contains("out4"), "{}", ctx);
+        assert!(!ctx.contains("out3"), "{}", ctx);
+        assert!(ctx.find("cmd0").unwrap() < ctx.find("cmd4").unwrap(), "{}", ctx);
     }
 
     #[tokio::test]
-    async fn test_load_existing_restores_sessions() {
+    async fn test_chat_context_required_tags() {
         let dir = tempfile::tempdir().unwrap();
-        let base = dir.path().to_path_buf();
-        // Note: completions_dir is no longer needed - SessionManager handles it internally
+        let mut config = ContextConfig::default();
+        config.completion.required_tags = vec!["deploy".to_string()];
+        config.completion.cwd_bonus = 2;
+        let mgr = SessionManager::new(dir.path().to_path_buf(), config);
+        mgr.register("s", None, HashMap::new(), None).await.unwrap();
+        for (i, cmd) in ["kubectl apply -f app.yaml", "ls", "kubectl rollout status app"].iter().enumerate() {
+            let i = i as u64;
+            mgr.write_io("s", i * 10, 1, format!("$ {}\r\nout{}\r\n", cmd, i).as_bytes()).await.unwrap();
+            let mut rec = make_rec(i * 10, "/repo", cmd);
+            rec.session_id = "s".into();
+            mgr.receive_command("s", rec).await.unwrap();
+        }
 
-        // Register a session and add a command via normal flow
-        {
-            let mgr = SessionManager::new(base.clone(), Default::default());
-            mgr.register("sess1", None, Default::default(), None)
-                .await
-                .unwrap();
-            mgr.write_io("sess1", 100, 0, b"$ ls\n").await.unwrap();
-            mgr.write_io("sess1", 200, 1, b"file.txt\n").await.unwrap();
-            mgr.receive_command(
-                "sess1",
-                CommandRecord {
-                    command_id: "cmd1".into(),
-                    session_id: "sess1".into(),
-                    command_line: Some("ls".into()),
-                    cwd: Some("/tmp".into()),
-                    started_at: 100,
-                    ended_at: Some(200),
-                    output_summary: "file.txt".into(),
-                    stream_offset: 0,
-                    stream_length: 0,
-                    exit_code: None,
-                },
-            )
-            .await
-            .unwrap();
-            // Drop the manager (simulates daemon shutdown)
+        let ctx = mgr.get_chat_context("s", None, None).await.unwrap();
+        assert!(!ctx.contains("kubectl"), "{}", ctx);
+
+        assert_eq!(mgr.tag_commands("s", "^kubectl", "deploy").await.unwrap(), 2);
+        let ctx = mgr.get_chat_context("s", None, None).await.unwrap();
+        assert!(ctx.contains("kubectl apply") && ctx.contains("kubectl rollout"), "{}", ctx);
+        assert!(!ctx.contains("out1"), "{}", ctx);
+    }
+
+    #[tokio::test]
+    async fn test_list_all_sessions() {
+        let dir = tempfile::tempdir().unwrap();
+        let mgr = SessionManager::new(dir.path().to_path_buf(), Default::default());
+        for i in 0..8 {
+            let sid = format!("s{}", i);
+            mgr.register(&sid, None, HashMap::new(), None).await.unwrap();
+            mgr.sessions.read().await[&sid].meta.write().await.started_at = format!("2026-01-0{}T00:00:00Z", i + 1);
+            // s1, s4 and s6 end
+            if [1, 4, 6].contains(&i) {
+                mgr.end_session(&sid).await.unwrap();
+            }
         }
 
-        // Create a new manager on the same directory and load existing sessions
-        let mgr2 = SessionManager::new(base, Default::default());
-        let count = mgr2.load_existing().await.unwrap();
-        assert_eq!(count, 1);
+        let ids = |metas: Vec<SessionMeta>| metas.into_iter().map(|m| m.session_id).collect::<Vec<_>>();
+        assert_eq!(ids(mgr.list_all(true).await), vec!["s7", "s6", "s5", "s4", "s3", "s2", "s1", "s0"]);
+        assert_eq!(ids(mgr.list_all(false).await), vec!["s7", "s5", "s3", "s2", "s0"]);
 
-        let commands = mgr2.get_commands("sess1").await.unwrap();
-        assert_eq!(commands.len(), 1);
-        assert_eq!(commands[0].command_line.as_deref(), Some("ls"));
+        let (page, total) = mgr.list_all_paged(1, 3).await;
+        assert_eq!(total, 8);
+        assert_eq!(ids(page), vec!["s4", "s3", "s2"]);
+        let (page, total) = mgr.list_all_paged(2, 3).await;
+        assert_eq!((ids(page), total), (vec!["s1".to_string(), "s0".to_string()], 8));
+        assert!(mgr.list_all_paged(3, 3).await.0.is_empty());
+    }
 
-        let active = mgr2.list_active().await;
-        assert!(active.contains(&"sess1".to_string()));
+    #[tokio::test]
+    async fn test_sessions_by_host() {
+        let dir = tempfile::tempdir().unwrap();
+        let mgr = SessionManager::new(dir.path().to_path_buf(), Default::default());
+        for (i, (sid, host)) in [("a", "server1"), ("b", "laptop"), ("c", "server1")].iter().enumerate() {
+            let attrs = HashMap::from([("hostname".to_string(), host.to_string())]);
+            mgr.register(sid, None, attrs, None).await.unwrap();
+            // Distinct started_at values so the order is deterministic
+            mgr.sessions.read().await[*sid].meta.write().await.started_at = format!("2026-01-0{}T00:00:00Z", i + 1);
+            mgr.write_io(sid, 10, 1, format!("$ make {}\r\nbuilt {}\r\n", sid, sid).as_bytes()).await.unwrap();
+            let mut rec = make_rec(i as u64, "/src", &format!("make {}", sid));
+            rec.session_id = sid.to_string();
+            mgr.receive_command(sid, rec).await.unwrap();
+        }
+
+        let ids: Vec<String> = mgr.get_sessions_by_host("server1").await.into_iter().map(|m| m.session_id).collect();
+        assert_eq!(ids, vec!["c", "a"]);
+        assert!(mgr.get_sessions_by_host("nowhere").await.is_empty());
+
+        let ctx = mgr.get_host_sessions_chat_context("server1", "a", None, None).await.unwrap();
+        assert!(ctx.contains("make a") && ctx.contains("make c"), "{}", ctx);
+        assert!(!ctx.contains("make b"), "{}", ctx);
+        let all = mgr.get_all_sessions_context("a").await.unwrap();
+        assert!(all.contains("make b"), "{}", all);
+
+        // The host's sessions share one budget rather than getting one each
+        let full = mgr.get_chat_context("a", None, None).await.unwrap();
+        let budget = omnish_llm::tokens::token_count(&full) + 10;
+        let ctx = mgr.get_host_sessions_chat_context("server1", "a", Some(TokenBudget::new(budget)), None).await.unwrap();
+        assert!(omnish_llm::tokens::token_count(&ctx) <= budget, "{}", ctx);
+    }
+
+    #[tokio::test]
+    async fn test_chat_context_format_from_config_and_override() {
+        let dir = tempfile::tempdir().unwrap();
+        let config = ContextConfig { format: ContextFormat::Xml, ..Default::default() };
+        let mgr = SessionManager::new(dir.path().to_path_buf(), config);
+        mgr.register("sess1", None, Default::default(), None).await.unwrap();
+        mgr.write_io("sess1", 10, 1, b"$ echo 'a & b'\r\na & b\r\n").await.unwrap();
+        let mut rec = make_rec(1, "/tmp", "echo 'a & b'");
+        rec.session_id = "sess1".into();
+        mgr.receive_command("sess1", rec).await.unwrap();
+
+        let xml = mgr.get_chat_context("sess1", None, None).await.unwrap();
+        assert!(xml.starts_with("<context>"), "{}", xml);
+        assert!(xml.contains("line=\"echo &apos;a &amp; b&apos;\""), "{}", xml);
+        assert!(xml.contains("<output>a &amp; b</output>"), "{}", xml);
+
+        let grouped = mgr.get_chat_context("sess1", None, Some(ContextFormat::Grouped)).await.unwrap();
+        assert!(grouped.contains("$ echo 'a & b'"), "{}", grouped);
+        assert!(!grouped.contains("<context>"), "{}", grouped);
     }
 
     #[tokio::test]
@@ -2151,6 +3879,9 @@ mod tests {
                 stream_offset: 0,
                 stream_length: 0,
                 exit_code: None,
+                exit_signal: None,
+                checksum: None,
+                tags: Vec::new(),
             },
         )
         .await
@@ -2169,6 +3900,9 @@ mod tests {
                 stream_offset: 0,
                 stream_length: 0,
                 exit_code: None,
+                exit_signal: None,
+                checksum: None,
+                tags: Vec::new(),
             },
         )
         .await
@@ -2187,6 +3921,9 @@ mod tests {
                 stream_offset: 0,
                 stream_length: 0,
                 exit_code: None,
+                exit_signal: None,
+                checksum: None,
+                tags: Vec::new(),
             },
         )
         .await
@@ -2244,6 +3981,9 @@ mod tests {
                         stream_offset: 0,
                         stream_length: 0,
                         exit_code: None,
+                        exit_signal: None,
+                        checksum: None,
+                        tags: Vec::new(),
                     },
                 )
                 .await
@@ -2292,6 +4032,9 @@ mod tests {
             stream_offset: 0,
             stream_length: 0,
             exit_code: Some(0),
+            exit_signal: None,
+            checksum: None,
+            tags: Vec::new(),
         }).await.unwrap();
 
         mgr.receive_command("server_active1", CommandRecord {
@@ -2305,6 +4048,9 @@ mod tests {
             stream_offset: 0,
             stream_length: 0,
             exit_code: Some(0),
+            exit_signal: None,
+            checksum: None,
+            tags: Vec::new(),
         }).await.unwrap();
 
         mgr.receive_command("server_active2", CommandRecord {
@@ -2318,6 +4064,9 @@ mod tests {
             stream_offset: 0,
             stream_length: 0,
             exit_code: Some(0),
+            exit_signal: None,
+            checksum: None,
+            tags: Vec::new(),
         }).await.unwrap();
 
         mgr.receive_command("server_dead", CommandRecord {
@@ -2331,6 +4080,9 @@ mod tests {
             stream_offset: 0,
             stream_length: 0,
             exit_code: Some(0),
+            exit_signal: None,
+            checksum: None,
+            tags: Vec::new(),
         }).await.unwrap();
 
         // End the dead session
@@ -2355,7 +4107,39 @@ mod tests {
     }
 
     #[tokio::test]
-    async fn test_max_context_chars_reduces_commands() {
+    async fn test_completion_context_after_first_command() {
+        let dir = tempfile::tempdir().unwrap();
+        let mgr = SessionManager::new(dir.path().to_path_buf(), Default::default());
+        mgr.register("sess1", None, Default::default(), None).await.unwrap();
+        assert!(mgr.build_completion_context("sess1", None).await.unwrap().is_empty());
+
+        mgr.receive_command(
+            "sess1",
+            CommandRecord {
+                command_id: "cmd0".into(),
+                session_id: "sess1".into(),
+                command_line: Some("cargo test".into()),
+                cwd: Some("/tmp".into()),
+                started_at: 1000,
+                ended_at: Some(2000),
+                output_summary: String::new(),
+                stream_offset: 0,
+                stream_length: 0,
+                exit_code: Some(0),
+                exit_signal: None,
+                checksum: None,
+                tags: Vec::new(),
+            },
+        )
+        .await
+        .unwrap();
+
+        let ctx = mgr.build_completion_context("sess1", None).await.unwrap();
+        assert!(ctx.contains("cargo test"), "{ctx}");
+    }
+
+    #[tokio::test]
+    async fn test_max_context_tokens_reduces_commands() {
         use omnish_common::config::{CompletionContextConfig, ContextConfig};
 
         let dir = tempfile::tempdir().unwrap();
@@ -2369,11 +4153,21 @@ mod tests {
                 tail_lines: 20,
                 max_line_width: 512,
                 min_current_session_commands: 5,
+                max_context_tokens: None,
                 max_context_chars: None,
                 detailed_min: 20,
                 detailed_max: 30,
                 cwd_history_limit: 10,
+                exclude_commands_pattern: None,
+                max_command_age_hours: None,
+                cwd_bonus: 0,
+                required_tags: Vec::new(),
+                max_output_bytes_per_command: None,
+                context_build_timeout_ms: 5000,
             },
+            redact_patterns: Vec::new(),
+            tracker: Default::default(),
+            format: Default::default(),
         };
         let mgr_no_limit = SessionManager::new(dir.path().to_path_buf(), cc_no_limit);
         mgr_no_limit.register("sess1", None, Default::default(), None)
@@ -2394,6 +4188,9 @@ mod tests {
                     stream_offset: 0,
                     stream_length: 0,
                     exit_code: Some(0),
+                    exit_signal: None,
+                    checksum: None,
+                    tags: Vec::new(),
                 },
             )
             .await
@@ -2412,11 +4209,21 @@ mod tests {
                 tail_lines: 20,
                 max_line_width: 512,
                 min_current_session_commands: 5,
-                max_context_chars: Some(200), // Small limit
+                max_context_tokens: Some(60), // Small limit
+                max_context_chars: None,
                 detailed_min: 20,
                 detailed_max: 30,
                 cwd_history_limit: 10,
+                exclude_commands_pattern: None,
+                max_command_age_hours: None,
+                cwd_bonus: 0,
+                required_tags: Vec::new(),
+                max_output_bytes_per_command: None,
+                context_build_timeout_ms: 5000,
             },
+            redact_patterns: Vec::new(),
+            tracker: Default::default(),
+            format: Default::default(),
         };
         let mgr_limited = SessionManager::new(dir.path().to_path_buf(), cc_limited);
         mgr_limited.register("sess1", None, Default::default(), None)
@@ -2437,6 +4244,9 @@ mod tests {
                     stream_offset: 0,
                     stream_length: 0,
                     exit_code: Some(0),
+                    exit_signal: None,
+                    checksum: None,
+                    tags: Vec::new(),
                 },
             )
             .await
@@ -2445,15 +4255,16 @@ mod tests {
 
         let ctx_limited = mgr_limited.get_session_context("sess1").await.unwrap();
         let char_count = ctx_limited.chars().count();
+        let token_count = omnish_llm::tokens::token_count(&ctx_limited);
 
-        eprintln!("With limit (200): {} chars", char_count);
+        eprintln!("With limit (60 tokens): {} tokens", token_count);
         eprintln!("Context:\n{}", ctx_limited);
 
         // Context should be under the limit
         assert!(
-            char_count <= 200,
-            "Context {} chars should be under 200 limit",
-            char_count
+            token_count <= 60,
+            "Context {} tokens should be under 60 limit",
+            token_count
         );
 
         // With limit, should have fewer commands than without limit
@@ -2483,11 +4294,21 @@ mod tests {
                 tail_lines: 20,
                 max_line_width: 512,
                 min_current_session_commands: 5,
+                max_context_tokens: None,
                 max_context_chars: None,
                 detailed_min: 20,
                 detailed_max: 30,
                 cwd_history_limit: 10,
+                exclude_commands_pattern: None,
+                max_command_age_hours: None,
+                cwd_bonus: 0,
+                required_tags: Vec::new(),
+                max_output_bytes_per_command: None,
+                context_build_timeout_ms: 5000,
             },
+            redact_patterns: Vec::new(),
+            tracker: Default::default(),
+            format: Default::default(),
         };
         let mgr = SessionManager::new(dir.path().to_path_buf(), cc);
         mgr.register("sess1", None, Default::default(), None)
@@ -2508,6 +4329,9 @@ mod tests {
                     stream_offset: 0,
                     stream_length: 0,
                     exit_code: Some(0),
+                    exit_signal: None,
+                    checksum: None,
+                    tags: Vec::new(),
                 },
             )
             .await
@@ -2530,6 +4354,9 @@ mod tests {
                 stream_offset: 0,
                 stream_length: 0,
                 exit_code: Some(0),
+                exit_signal: None,
+                checksum: None,
+                tags: Vec::new(),
             },
         )
         .await
@@ -2585,9 +4412,15 @@ mod tests {
             stream_offset: 0,
             stream_length: 0,
             exit_code: None,
+            exit_signal: None,
+            checksum: None,
+            tags: Vec::new(),
         }];
 
         CommandRecord::save_all(&commands, &session_dir).unwrap();
+        let usage_log = omnish_store::usage::session_usage_path(&mgr.usage_dir, "test_session");
+        std::fs::create_dir_all(&mgr.usage_dir).unwrap();
+        std::fs::write(&usage_log, "").unwrap();
 
         // Note: CommandRecord::load_all only requires commands.json
         // meta.json and stream.bin are not needed for cleanup logic
@@ -2596,6 +4429,8 @@ mod tests {
         let cleaned = mgr.cleanup_expired_dirs(Duration::from_secs(48 * 3600)).await;
         assert_eq!(cleaned, 1);
         assert!(!session_dir.exists());
+        // The session's usage log goes with it
+        assert!(!usage_log.exists());
     }
 
     #[tokio::test]
@@ -2617,6 +4452,8 @@ mod tests {
             started_at: "2020-01-01T00:00:00Z".into(),
             ended_at: None,
             attrs: Default::default(),
+            tags: Vec::new(),
+            description: None,
         };
         old_meta.save(&old_meta_dir).unwrap();
 
@@ -2649,6 +4486,9 @@ mod tests {
             stream_offset: 0,
             stream_length: 0,
             exit_code: None,
+            exit_signal: None,
+            checksum: None,
+            tags: Vec::new(),
         }];
 
         CommandRecord::save_all(&old_commands, &active_session_dir).unwrap();
@@ -2674,6 +4514,9 @@ mod tests {
             stream_offset: 0,
             stream_length: 0,
             exit_code: None,
+            exit_signal: None,
+            checksum: None,
+            tags: Vec::new(),
         }];
 
         CommandRecord::save_all(&recent_commands, &recent_dir).unwrap();
@@ -2762,6 +4605,9 @@ mod tests {
             stream_offset: 0,
             stream_length: 0,
             exit_code: None,
+            exit_signal: None,
+            checksum: None,
+            tags: Vec::new(),
         }];
 
         CommandRecord::save_all(&fresh_commands, &fresh_dir).unwrap();
@@ -2793,6 +4639,9 @@ mod tests {
             stream_offset: 0,
             stream_length: 0,
             exit_code: None,
+            exit_signal: None,
+            checksum: None,
+            tags: Vec::new(),
         }];
 
         CommandRecord::save_all(&commands, &expired_dir).unwrap();
@@ -3042,6 +4891,147 @@ mod tests {
         assert_eq!(ended, vec!["s1".to_string()]);
     }
 
+    /// A record whose checksum doesn't match is skipped on load; the rest of
+    /// the session (including valid records) survives.
+    #[tokio::test]
+    async fn test_load_existing_skips_bad_checksum() {
+        let dir = tempfile::tempdir().unwrap();
+        let session_dir = {
+            let mgr = SessionManager::new(dir.path().to_path_buf(), Default::default());
+            mgr.register("s1", None, HashMap::new(), None).await.unwrap();
+            let sessions = mgr.sessions.read().await;
+            sessions.get("s1").unwrap().dir.clone()
+        };
+
+        let mut good = make_rec(0, "/tmp", "ls");
+        good.checksum = Some(good.compute_checksum());
+        let mut bad = make_rec(1, "/tmp", "rm -rf build");
+        bad.checksum = Some(bad.compute_checksum() ^ 1);
+        CommandRecord::save_all(&[good, bad], &session_dir).unwrap();
+
+        let mgr2 = SessionManager::new(dir.path().to_path_buf(), Default::default());
+        assert_eq!(mgr2.load_existing().await.unwrap(), 1);
+        let commands = mgr2.get_commands("s1").await.unwrap();
+        assert_eq!(commands.len(), 1);
+        assert_eq!(commands[0].command_line.as_deref(), Some("ls"));
+        assert!(session_dir.exists());
+    }
+
+    /// Output matching a redact pattern never reaches stream.bin, even when
+    /// the secret is split across two IoData chunks. Input is stored as-is.
+    #[tokio::test]
+    async fn test_write_io_redacts_secrets() {
+        let dir = tempfile::tempdir().unwrap();
+        let cc = ContextConfig {
+            redact_patterns: vec![r"password=\w+".to_string()],
+            ..Default::default()
+        };
+        let mgr = SessionManager::new(dir.path().to_path_buf(), cc);
+        mgr.register("s1", None, HashMap::new(), None).await.unwrap();
+        mgr.write_io("s1", 1, 1, b"login password=hun").await.unwrap();
+        mgr.write_io("s1", 2, 1, b"ter2 done").await.unwrap();
+        mgr.write_io("s1", 3, 0, b"password=typed").await.unwrap();
+
+        let stream_path = {
+            let sessions = mgr.sessions.read().await;
+            sessions.get("s1").unwrap().dir.join("stream.bin")
+        };
+        let len = stream_len(&stream_path).unwrap();
+        let entries = read_range(&stream_path, 0, len, None).unwrap();
+        let output: Vec<u8> = entries
+            .iter()
+            .filter(|e| e.direction == 1)
+            .flat_map(|e| e.data.clone())
+            .collect();
+        let output = String::from_utf8(output).unwrap();
+        assert!(!output.contains("hun") && !output.contains("ter2"), "{}", output);
+        assert!(output.contains("***REDACTED***"));
+        assert!(output.ends_with(" done"));
+        assert_eq!(entries[2].data, b"password=typed");
+    }
+
+    #[tokio::test]
+    async fn test_receive_command_redacts_secrets() {
+        let dir = tempfile::tempdir().unwrap();
+        let cc = ContextConfig {
+            redact_patterns: vec![r"password=\w+".to_string()],
+            ..Default::default()
+        };
+        let mgr = SessionManager::new(dir.path().to_path_buf(), cc);
+        mgr.register("s1", None, HashMap::new(), None).await.unwrap();
+        let mut rec = make_rec(1, "/tmp", "mysql -u root password=hunter2");
+        rec.output_summary = "warning: password=hunter2 on the command line".into();
+        mgr.receive_command("s1", rec).await.unwrap();
+
+        let cmds = mgr.get_commands("s1").await.unwrap();
+        assert_eq!(cmds[0].command_line.as_deref(), Some("mysql -u root ***REDACTED***"));
+        assert_eq!(cmds[0].output_summary, "warning: ***REDACTED*** on the command line");
+        // The checksum covers what was stored
+        assert_eq!(cmds[0].checksum, Some(cmds[0].compute_checksum()));
+        let session_dir = mgr.sessions.read().await.get("s1").unwrap().dir.clone();
+        let saved = std::fs::read_to_string(session_dir.join("commands.json")).unwrap();
+        assert!(!saved.contains("hunter2"));
+    }
+
+    #[tokio::test]
+    async fn test_search_commands_matches_output_most_recent_first() {
+        let dir = tempfile::tempdir().unwrap();
+        let mgr = SessionManager::new(dir.path().to_path_buf(), Default::default());
+        mgr.register("s1", None, HashMap::new(), None).await.unwrap();
+        for (i, (cmd, out)) in [
+            ("make", "\x1b[31mlink error: undefined\x1b[0m\n"),
+            ("ls", "a.txt\n"),
+            ("cargo build", "error[E0308]: mismatched types\n"),
+        ]
+        .iter()
+        .enumerate()
+        {
+            let ts = 100 * (i as u64 + 1);
+            mgr.write_io("s1", ts, 1, out.as_bytes()).await.unwrap();
+            let mut rec = make_rec(i as u64, "/tmp", cmd);
+            rec.session_id = "s1".into();
+            rec.started_at = ts;
+            mgr.receive_command("s1", rec).await.unwrap();
+        }
+
+        let results = mgr.search_commands("error", 10).await;
+        let lines: Vec<_> = results.iter().map(|r| r.command_line.as_str()).collect();
+        assert_eq!(lines, vec!["cargo build", "make"]);
+        assert_eq!(results[1].snippet, "link error: undefined");
+
+        // Command lines match too; limit applies.
+        let results = mgr.search_commands("ls|cargo", 1).await;
+        assert_eq!(results.len(), 1);
+        assert_eq!(results[0].command_line, "cargo build");
+
+        // With a zero byte budget only command lines are searched.
+        let mgr = mgr.with_search_max_bytes(0);
+        assert!(mgr.search_commands("undefined", 10).await.is_empty());
+    }
+
+    #[tokio::test]
+    async fn test_grep_with_output_includes_context() {
+        let dir = tempfile::tempdir().unwrap();
+        let mgr = SessionManager::new(dir.path().to_path_buf(), Default::default());
+        mgr.register("s1", None, HashMap::new(), None).await.unwrap();
+        let out = "Connecting to db:5432\nretry 1\nError: connection refused\nretry 2\ngiving up\nbye\n";
+        mgr.write_io("s1", 100, 1, out.as_bytes()).await.unwrap();
+        let mut rec = make_rec(0, "/tmp", "./migrate");
+        rec.session_id = "s1".into();
+        rec.started_at = 100;
+        mgr.receive_command("s1", rec).await.unwrap();
+
+        let results = mgr.grep_with_output("connection", 10).await.unwrap();
+        assert_eq!(results.len(), 1);
+        assert_eq!(results[0].command_line, "./migrate");
+        assert_eq!(
+            results[0].matched_lines,
+            vec!["Connecting to db:5432", "retry 1", "Error: connection refused", "retry 2", "giving up"],
+        );
+        assert!(mgr.grep_with_output("nomatch", 10).await.unwrap().is_empty());
+        assert!(mgr.grep_with_output("", 10).await.is_err());
+    }
+
     fn make_rec(seq: u64, cwd: &str, cmd: &str) -> CommandRecord {
         CommandRecord {
             command_id: format!("c{}", seq),
@@ -3054,6 +5044,9 @@ mod tests {
             stream_offset: 0,
             stream_length: 0,
             exit_code: Some(0),
+            exit_signal: None,
+            checksum: None,
+            tags: Vec::new(),
         }
     }
 
@@ -3167,4 +5160,13 @@ mod tests {
         let out = build_cwd_history(&cmds, Some(q), 10);
         assert!(out.contains("cargo build"), "expected match after shorten_home, got: {:?}", out);
     }
+
+    #[tokio::test]
+    async fn test_cancel_after_fires_once_timeout_passes() {
+        let token = cancel_after(Duration::from_millis(20));
+        assert!(!token.is_cancelled());
+        tokio::time::timeout(Duration::from_secs(2), token.cancelled())
+            .await
+            .expect("token should cancel after its timeout");
+    }
 }
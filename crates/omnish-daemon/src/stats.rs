@@ -0,0 +1,244 @@
+use std::collections::HashMap;
+
+use omnish_store::command::CommandRecord;
+
+/// How many entries `most_used_commands` keeps.
+const TOP_COMMANDS: usize = 3;
+/// How many entries `slowest_commands` keeps.
+const SLOWEST_COMMANDS: usize = 5;
+
+/// Per-session command statistics shown by `/stats`.
+#[derive(Debug, Clone, Default, PartialEq)]
+pub struct SessionStats {
+    pub total_commands: usize,
+    /// Commands that finished with a non-zero exit code.
+    pub failed_commands: usize,
+    /// Distinct command lines.
+    pub unique_commands: usize,
+    /// From the first command's start to the last command's end.
+    pub total_session_duration_ms: u64,
+    /// Mean duration of commands that have finished.
+    pub average_command_duration_ms: u64,
+    /// Most frequent command lines with their counts, most used first
+    /// (ties in alphabetical order).
+    pub most_used_commands: Vec<(String, usize)>,
+    /// Longest finished commands with their durations, slowest first.
+    pub slowest_commands: Vec<(String, u64)>,
+    /// Median and 95th percentile of finished command durations.
+    pub p50_duration_ms: u64,
+    pub p95_duration_ms: u64,
+    /// LLM tokens spent on this session's queries, as reported by the API.
+    pub total_prompt_tokens: u64,
+    pub total_completion_tokens: u64,
+}
+
+impl SessionStats {
+    pub fn from_commands(commands: &[CommandRecord]) -> Self {
+        let mut counts: HashMap<&str, usize> = HashMap::new();
+        for line in commands.iter().filter_map(|c| c.command_line.as_deref()) {
+            *counts.entry(line.trim()).or_default() += 1;
+        }
+        let mut most_used: Vec<(String, usize)> =
+            counts.iter().map(|(l, n)| (l.to_string(), *n)).collect();
+        most_used.sort_by(|a, b| b.1.cmp(&a.1).then_with(|| a.0.cmp(&b.0)));
+        most_used.truncate(TOP_COMMANDS);
+
+        let mut durations: Vec<u64> = commands.iter().filter_map(|c| c.duration_ms()).collect();
+        durations.sort_unstable();
+        let average = if durations.is_empty() {
+            0
+        } else {
+            durations.iter().sum::<u64>() / durations.len() as u64
+        };
+
+        let mut slowest: Vec<(String, u64)> = commands
+            .iter()
+            .filter_map(|c| {
+                let line = c.command_line.as_deref().unwrap_or("(unknown)").trim();
+                c.duration_ms().map(|d| (line.to_string(), d))
+            })
+            .collect();
+        slowest.sort_by_key(|(_, ms)| std::cmp::Reverse(*ms));
+        slowest.truncate(SLOWEST_COMMANDS);
+
+        let first_start = commands.iter().map(|c| c.started_at).min();
+        let last_end = commands.iter().map(|c| c.ended_at.unwrap_or(c.started_at)).max();
+        let span = match (first_start, last_end) {
+            (Some(start), Some(end)) => end.saturating_sub(start),
+            _ => 0,
+        };
+
+        Self {
+            total_commands: commands.len(),
+            failed_commands: commands.iter().filter(|c| matches!(c.exit_code, Some(code) if code != 0)).count(),
+            unique_commands: counts.len(),
+            total_session_duration_ms: span,
+            average_command_duration_ms: average,
+            most_used_commands: most_used,
+            slowest_commands: slowest,
+            p50_duration_ms: percentile(&durations, 50.0),
+            p95_duration_ms: percentile(&durations, 95.0),
+            ..Default::default()
+        }
+    }
+
+    /// Share of failed commands, in percent.
+    pub fn error_rate(&self) -> f64 {
+        if self.total_commands == 0 {
+            0.0
+        } else {
+            self.failed_commands as f64 * 100.0 / self.total_commands as f64
+        }
+    }
+}
+
+/// The `p`th percentile of ascending `sorted`, interpolating linearly
+/// between the two nearest ranks. 0 when empty.
+fn percentile(sorted: &[u64], p: f64) -> u64 {
+    let Some(&last) = sorted.last() else {
+        return 0;
+    };
+    let rank = p.clamp(0.0, 100.0) / 100.0 * (sorted.len() - 1) as f64;
+    let (lo, hi) = (rank.floor() as usize, rank.ceil() as usize);
+    if hi >= sorted.len() {
+        return last;
+    }
+    let fraction = rank - lo as f64;
+    (sorted[lo] as f64 + (sorted[hi] as f64 - sorted[lo] as f64) * fraction).round() as u64
+}
+
+/// Renders `SessionStats` as an aligned two-column table.
+pub struct StatsFormatter;
+
+impl StatsFormatter {
+    pub fn format(stats: &SessionStats) -> String {
+        if stats.total_commands == 0 && stats.total_prompt_tokens + stats.total_completion_tokens == 0 {
+            return "No commands recorded in this session".to_string();
+        }
+        let mut out = String::new();
+        let mut row = |label: &str, value: String| {
+            out.push_str(&format!("{:<18}{}\n", label, value));
+        };
+        row("Commands:", stats.total_commands.to_string());
+        row(
+            "Failed:",
+            format!("{} ({:.1}%)", stats.failed_commands, stats.error_rate()),
+        );
+        row("Unique:", stats.unique_commands.to_string());
+        row("Active duration:", format_duration(stats.total_session_duration_ms));
+        row("Avg per command:", format_duration(stats.average_command_duration_ms));
+        row("Median (p50):", format_duration(stats.p50_duration_ms));
+        row("p95:", format_duration(stats.p95_duration_ms));
+        if stats.total_prompt_tokens + stats.total_completion_tokens > 0 {
+            row(
+                "LLM tokens:",
+                format!("{} prompt, {} completion", stats.total_prompt_tokens, stats.total_completion_tokens),
+            );
+        }
+        out.push_str("Most used:\n");
+        for (line, count) in &stats.most_used_commands {
+            out.push_str(&format!("  {:>4}  {}\n", count, line));
+        }
+        if !stats.slowest_commands.is_empty() {
+            out.push_str("Slowest:\n");
+            for (line, ms) in &stats.slowest_commands {
+                out.push_str(&format!("  {:>8}  {}\n", format_duration(*ms), line));
+            }
+        }
+        out.truncate(out.trim_end().len());
+        out
+    }
+}
+
+/// `1h 2m 3s`, `4m 5s`, `6.7s`, or `89ms`.
+fn format_duration(ms: u64) -> String {
+    let secs = ms / 1000;
+    match secs {
+        0 => format!("{}ms", ms),
+        1..=59 => format!("{:.1}s", ms as f64 / 1000.0),
+        60..=3599 => format!("{}m {}s", secs / 60, secs % 60),
+        _ => format!("{}h {}m {}s", secs / 3600, secs % 3600 / 60, secs % 60),
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn rec(line: &str, started_at: u64, ended_at: u64, exit_code: i32) -> CommandRecord {
+        CommandRecord {
+            command_id: format!("c{}", started_at),
+            session_id: "s1".into(),
+            command_line: Some(line.into()),
+            cwd: None,
+            started_at,
+            ended_at: Some(ended_at),
+            output_summary: String::new(),
+            stream_offset: 0,
+            stream_length: 0,
+            exit_code: Some(exit_code),
+            exit_signal: None,
+            checksum: None,
+            tags: Vec::new(),
+        }
+    }
+
+    #[test]
+    fn test_format_duration() {
+        assert_eq!(format_duration(89), "89ms");
+        assert_eq!(format_duration(6_700), "6.7s");
+        assert_eq!(format_duration(245_000), "4m 5s");
+        assert_eq!(format_duration(3_723_000), "1h 2m 3s");
+    }
+
+    #[test]
+    fn test_percentiles_interpolate() {
+        let commands: Vec<CommandRecord> = (1..=10u64)
+            .map(|i| rec(&format!("cmd{}", i), i * 10_000, i * 10_000 + i * 100, 0))
+            .collect();
+        assert_eq!(commands[2].duration_ms(), Some(300));
+        let stats = SessionStats::from_commands(&commands);
+        assert_eq!(stats.p50_duration_ms, 550);
+        assert_eq!(stats.p95_duration_ms, 955);
+        assert_eq!(
+            stats.slowest_commands,
+            vec![
+                ("cmd10".to_string(), 1000),
+                ("cmd9".to_string(), 900),
+                ("cmd8".to_string(), 800),
+                ("cmd7".to_string(), 700),
+                ("cmd6".to_string(), 600),
+            ]
+        );
+
+        assert_eq!(percentile(&[], 50.0), 0);
+        assert_eq!(percentile(&[42], 95.0), 42);
+        assert_eq!(percentile(&[100, 200], 100.0), 200);
+    }
+
+    #[test]
+    fn test_format_table() {
+        let stats = SessionStats::from_commands(&[
+            rec("make", 0, 2_000, 0),
+            rec("make", 3_000, 5_000, 2),
+        ]);
+        let out = StatsFormatter::format(&stats);
+        assert!(out.contains("Commands:         2"), "{out}");
+        assert!(out.contains("Failed:           1 (50.0%)"), "{out}");
+        assert!(out.contains("Active duration:  5.0s"), "{out}");
+        assert!(out.contains("     2  make"), "{out}");
+        assert!(out.contains("Median (p50):     2.0s"), "{out}");
+        assert!(out.contains("Slowest:\n      2.0s  make"), "{out}");
+        assert!(!out.contains("LLM tokens"), "{out}");
+        let with_tokens = SessionStats { total_prompt_tokens: 1200, total_completion_tokens: 85, ..stats };
+        assert!(
+            StatsFormatter::format(&with_tokens).contains("LLM tokens:       1200 prompt, 85 completion"),
+            "{}",
+            StatsFormatter::format(&with_tokens)
+        );
+        assert_eq!(
+            StatsFormatter::format(&SessionStats::default()),
+            "No commands recorded in this session"
+        );
+    }
+}
@@ -1,6 +1,8 @@
 use anyhow::Result;
 use std::collections::HashMap;
+use std::future::Future;
 use std::path::PathBuf;
+use std::pin::Pin;
 use std::sync::Arc;
 use tokio_cron_scheduler::{Job, JobScheduler};
 use uuid::Uuid;
@@ -35,16 +37,51 @@ pub trait ScheduledTask: Send + Sync {
     fn schedule(&self) -> &str;
     /// Whether the task is enabled (from config).
     fn enabled(&self) -> bool;
-    /// Build the tokio-cron-scheduler Job using the shared context.
-    fn create_job(&self, ctx: &TaskContext) -> Result<Job>;
+    /// Build the task's job using the shared context.
+    fn create_job(&self, ctx: &TaskContext) -> Result<TaskJob>;
     /// Default config values for this task (injected into ConfigMap.defaults).
     fn defaults() -> HashMap<String, serde_json::Value> where Self: Sized;
 }
 
+/// One run of a task's body.
+pub type TaskFn = Arc<dyn Fn() -> Pin<Box<dyn Future<Output = ()> + Send>> + Send + Sync>;
+
+/// A scheduled job plus the closure it runs, kept so the task can also be
+/// fired on demand (`TaskManager::trigger_now`).
+pub struct TaskJob {
+    job: Job,
+    run: TaskFn,
+}
+
+impl TaskJob {
+    /// Run `run` on the cron `schedule` (UTC).
+    pub fn new<F>(schedule: &str, run: F) -> Result<Self>
+    where
+        F: Fn() -> Pin<Box<dyn Future<Output = ()> + Send>> + Send + Sync + 'static,
+    {
+        let run: TaskFn = Arc::new(run);
+        let cron_run = run.clone();
+        let job = Job::new_async(schedule, move |_uuid, _lock| cron_run())?;
+        Ok(Self { job, run })
+    }
+
+    /// Like `new`, with `schedule` in local time.
+    pub fn new_local<F>(schedule: &str, run: F) -> Result<Self>
+    where
+        F: Fn() -> Pin<Box<dyn Future<Output = ()> + Send>> + Send + Sync + 'static,
+    {
+        let run: TaskFn = Arc::new(run);
+        let cron_run = run.clone();
+        let job = Job::new_async_tz(schedule, chrono::Local, move |_uuid, _lock| cron_run())?;
+        Ok(Self { job, run })
+    }
+}
+
 struct TaskEntry {
     uuid: Uuid,
     cron: String,
     enabled: bool,
+    run: TaskFn,
 }
 
 pub struct TaskManager {
@@ -61,12 +98,13 @@ impl TaskManager {
         })
     }
 
-    pub async fn register(&mut self, name: &str, cron: &str, job: Job) -> Result<()> {
-        let uuid = self.scheduler.add(job).await?;
+    pub async fn register(&mut self, name: &str, cron: &str, job: TaskJob) -> Result<()> {
+        let uuid = self.scheduler.add(job.job).await?;
         self.tasks.insert(name.to_string(), TaskEntry {
             uuid,
             cron: cron.to_string(),
             enabled: true,
+            run: job.run,
         });
         tracing::info!("registered task '{}' with schedule '{}'", name, cron);
         Ok(())
@@ -84,6 +122,26 @@ impl TaskManager {
             .collect()
     }
 
+    /// `(name, cron)` for every registered task, sorted by name.
+    pub fn list_jobs(&self) -> Vec<(String, String)> {
+        let mut jobs: Vec<(String, String)> = self.tasks
+            .iter()
+            .map(|(name, entry)| (name.clone(), entry.cron.clone()))
+            .collect();
+        jobs.sort();
+        jobs
+    }
+
+    /// Run the named task once now, in a spawned task, outside its cron
+    /// schedule. Works for disabled tasks too.
+    pub fn trigger_now(&self, name: &str) -> Result<()> {
+        let entry = self.tasks.get(name)
+            .ok_or_else(|| anyhow::anyhow!("task '{}' not found", name))?;
+        tracing::info!("triggering task '{}' manually", name);
+        tokio::spawn((entry.run)());
+        Ok(())
+    }
+
     pub async fn disable(&mut self, name: &str) -> Result<()> {
         let entry = self.tasks.get_mut(name)
             .ok_or_else(|| anyhow::anyhow!("task '{}' not found", name))?;
@@ -199,3 +257,57 @@ pub fn inject_task_defaults(tasks: &mut omnish_common::config::TasksConfig) {
         entry.set_defaults(defaults);
     }
 }
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn flag_job(flag: Arc<std::sync::Mutex<bool>>) -> TaskJob {
+        // Yearly schedule: never fires during the test on its own
+        TaskJob::new("0 0 0 1 1 *", move || {
+            let flag = flag.clone();
+            Box::pin(async move {
+                *flag.lock().unwrap() = true;
+            })
+        })
+        .unwrap()
+    }
+
+    #[tokio::test]
+    async fn test_trigger_now_runs_job() {
+        let flag = Arc::new(std::sync::Mutex::new(false));
+        let mut mgr = TaskManager::new().await.unwrap();
+        mgr.register("test_job", "0 0 0 1 1 *", flag_job(flag.clone())).await.unwrap();
+
+        mgr.trigger_now("test_job").unwrap();
+        for _ in 0..100 {
+            if *flag.lock().unwrap() {
+                break;
+            }
+            tokio::time::sleep(std::time::Duration::from_millis(10)).await;
+        }
+        assert!(*flag.lock().unwrap(), "job should have run");
+    }
+
+    #[tokio::test]
+    async fn test_trigger_now_unknown_task() {
+        let mgr = TaskManager::new().await.unwrap();
+        let err = mgr.trigger_now("nope").unwrap_err();
+        assert!(err.to_string().contains("not found"));
+    }
+
+    #[tokio::test]
+    async fn test_list_jobs_sorted() {
+        let flag = Arc::new(std::sync::Mutex::new(false));
+        let mut mgr = TaskManager::new().await.unwrap();
+        mgr.register("b_job", "0 0 0 1 1 *", flag_job(flag.clone())).await.unwrap();
+        mgr.register("a_job", "0 0 0 1 1 *", flag_job(flag.clone())).await.unwrap();
+        assert_eq!(
+            mgr.list_jobs(),
+            vec![
+                ("a_job".to_string(), "0 0 0 1 1 *".to_string()),
+                ("b_job".to_string(), "0 0 0 1 1 *".to_string()),
+            ]
+        );
+    }
+}
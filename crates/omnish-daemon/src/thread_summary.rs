@@ -1,8 +1,7 @@
 use crate::conversation_mgr::ConversationManager;
-use crate::task_mgr::{ScheduledTask, TaskContext};
+use crate::task_mgr::{ScheduledTask, TaskContext, TaskJob};
 use omnish_common::config::ConfigMap;
 use omnish_llm::{backend::{LlmBackend, LlmRequest, TriggerType, UseCase}, template};
-use tokio_cron_scheduler::Job;
 
 
 pub struct ThreadSummaryTask {
@@ -37,11 +36,11 @@ impl ScheduledTask for ThreadSummaryTask {
         ].into()
     }
 
-    fn create_job(&self, ctx: &TaskContext) -> anyhow::Result<Job> {
+    fn create_job(&self, ctx: &TaskContext) -> anyhow::Result<TaskJob> {
         let conv_mgr = ctx.conv_mgr.clone();
         let llm_holder = ctx.llm_backend.clone();
         let daemon_config = ctx.daemon_config.clone();
-        Ok(Job::new_async(self.schedule(), move |_uuid, _lock| {
+        TaskJob::new(self.schedule(), move || {
             let conv_mgr = conv_mgr.clone();
             let llm = llm_holder.read().unwrap().get_backend(UseCase::Chat);
             let language = daemon_config.read().unwrap().client.language.clone();
@@ -52,7 +51,7 @@ impl ScheduledTask for ThreadSummaryTask {
                 }
                 tracing::debug!("task [thread_summary] finished");
             })
-        })?)
+        })
     }
 }
 
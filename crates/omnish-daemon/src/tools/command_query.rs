@@ -419,6 +419,9 @@ mod tests {
             stream_offset: 0,
             stream_length: 0,
             exit_code,
+            exit_signal: None,
+            checksum: None,
+            tags: Vec::new(),
         }
     }
 
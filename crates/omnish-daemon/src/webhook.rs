@@ -0,0 +1,134 @@
+use serde::Serialize;
+use std::time::Duration;
+
+/// Attempts per delivery before giving up.
+const MAX_ATTEMPTS: u32 = 3;
+/// Pause between attempts.
+const RETRY_BACKOFF: Duration = Duration::from_secs(5);
+
+/// JSON body POSTed to a summary webhook.
+#[derive(Debug, Clone, Serialize, PartialEq)]
+pub struct SummaryPayload {
+    /// Session the summary covers; `None` for cross-session summaries such
+    /// as the hourly one.
+    pub session_id: Option<String>,
+    pub summary: String,
+    /// Generation time (epoch ms).
+    pub timestamp: u64,
+}
+
+/// POSTs summaries to a user-configured URL.
+pub struct WebhookDelivery;
+
+impl WebhookDelivery {
+    /// POST `payload` as JSON to `url`, retrying up to `MAX_ATTEMPTS` times
+    /// on network errors and non-2xx responses. `auth_header`, when set, is
+    /// sent verbatim as the `Authorization` header.
+    pub async fn send(
+        url: &str,
+        auth_header: Option<&str>,
+        payload: &SummaryPayload,
+    ) -> anyhow::Result<()> {
+        Self::send_with_backoff(url, auth_header, payload, RETRY_BACKOFF).await
+    }
+
+    async fn send_with_backoff(
+        url: &str,
+        auth_header: Option<&str>,
+        payload: &SummaryPayload,
+        backoff: Duration,
+    ) -> anyhow::Result<()> {
+        let client = reqwest::Client::builder()
+            .timeout(Duration::from_secs(30))
+            .build()?;
+        let mut last_err = anyhow::anyhow!("no attempt made");
+        for attempt in 1..=MAX_ATTEMPTS {
+            let mut req = client.post(url).json(payload);
+            if let Some(auth) = auth_header {
+                req = req.header(reqwest::header::AUTHORIZATION, auth);
+            }
+            let result = req.send().await;
+            match result {
+                Ok(resp) if resp.status().is_success() => return Ok(()),
+                Ok(resp) => last_err = anyhow::anyhow!("HTTP {}", resp.status()),
+                Err(e) => last_err = e.into(),
+            }
+            tracing::warn!(
+                "webhook {} attempt {}/{} failed: {}",
+                url, attempt, MAX_ATTEMPTS, last_err
+            );
+            if attempt < MAX_ATTEMPTS {
+                tokio::time::sleep(backoff).await;
+            }
+        }
+        Err(last_err)
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use wiremock::matchers::{body_json, header, method, path};
+    use wiremock::{Mock, MockServer, ResponseTemplate};
+
+    fn payload() -> SummaryPayload {
+        SummaryPayload {
+            session_id: None,
+            summary: "Fixed the build".into(),
+            timestamp: 1_700_000_000_000,
+        }
+    }
+
+    #[tokio::test]
+    async fn test_send_posts_json_payload() {
+        let server = MockServer::start().await;
+        Mock::given(method("POST"))
+            .and(path("/hook"))
+            .and(header("content-type", "application/json"))
+            .and(body_json(serde_json::json!({
+                "session_id": null,
+                "summary": "Fixed the build",
+                "timestamp": 1_700_000_000_000u64,
+            })))
+            .respond_with(ResponseTemplate::new(200))
+            .expect(1)
+            .mount(&server)
+            .await;
+
+        let url = format!("{}/hook", server.uri());
+        WebhookDelivery::send(&url, None, &payload()).await.unwrap();
+        server.verify().await;
+    }
+
+    #[tokio::test]
+    async fn test_send_retries_then_gives_up() {
+        let server = MockServer::start().await;
+        Mock::given(method("POST"))
+            .respond_with(ResponseTemplate::new(503))
+            .up_to_n_times(1)
+            .expect(1)
+            .mount(&server)
+            .await;
+        Mock::given(method("POST"))
+            .respond_with(ResponseTemplate::new(200))
+            .expect(1)
+            .mount(&server)
+            .await;
+        WebhookDelivery::send_with_backoff(&server.uri(), None, &payload(), Duration::from_millis(10))
+            .await
+            .unwrap();
+        server.verify().await;
+
+        let server = MockServer::start().await;
+        Mock::given(method("POST"))
+            .respond_with(ResponseTemplate::new(500))
+            .expect(3)
+            .mount(&server)
+            .await;
+        let err = WebhookDelivery::send_with_backoff(&server.uri(), None, &payload(), Duration::from_millis(10))
+            .await
+            .unwrap_err();
+        assert!(err.to_string().contains("500"), "{err}");
+        server.verify().await;
+    }
+}
@@ -0,0 +1,280 @@
+use std::collections::HashMap;
+use std::path::{Path, PathBuf};
+use std::sync::Mutex;
+use std::time::{Duration, Instant};
+
+/// How long a detected workspace is reused before its metadata is re-read.
+const CACHE_TTL: Duration = Duration::from_secs(60);
+
+/// Manifest files recognised while walking up from the cwd, with the
+/// project type they imply.
+const MANIFESTS: &[(&str, &str)] = &[
+    ("Cargo.toml", "rust"),
+    ("pyproject.toml", "python"),
+    ("package.json", "node"),
+];
+
+/// Project-level facts about the directory a session is working in.
+#[derive(Debug, Clone, PartialEq)]
+pub struct WorkspaceContext {
+    /// "rust", "python", "node", or "git" when only a repository was found.
+    pub project_type: String,
+    /// Git root when inside a repository, otherwise the manifest directory.
+    pub root: PathBuf,
+    /// `key: value` lines (package name/version, git remote).
+    pub metadata: String,
+}
+
+impl WorkspaceContext {
+    /// Render as a block for the LLM system prompt.
+    pub fn to_prompt(&self) -> String {
+        let mut out = format!(
+            "<workspace>\nroot: {}\ntype: {}\n",
+            self.root.display(),
+            self.project_type
+        );
+        if !self.metadata.is_empty() {
+            out.push_str(&self.metadata);
+            out.push('\n');
+        }
+        out.push_str("</workspace>");
+        out
+    }
+}
+
+/// Cache key: the root and the nearest manifest dir under it.
+type WorkspaceKey = (PathBuf, Option<PathBuf>);
+
+/// Finds the project a cwd belongs to by walking up to the nearest `.git`
+/// and manifest files. Results are cached per (root, manifest dir) for
+/// `CACHE_TTL`, so packages sharing a git root are told apart.
+pub struct WorkspaceDetector {
+    cache: Mutex<HashMap<WorkspaceKey, (WorkspaceContext, Instant)>>,
+}
+
+impl Default for WorkspaceDetector {
+    fn default() -> Self {
+        Self::new()
+    }
+}
+
+impl WorkspaceDetector {
+    pub fn new() -> Self {
+        Self { cache: Mutex::new(HashMap::new()) }
+    }
+
+    /// Detect the workspace containing `cwd`. Returns None when no marker is
+    /// found up to the filesystem root (or `cwd` does not exist here, e.g. a
+    /// session on another host).
+    pub fn detect(&self, cwd: &Path) -> Option<WorkspaceContext> {
+        let (root, manifest) = find_root(cwd)?;
+        let key = (root, manifest.as_ref().map(|m| m.dir.clone()));
+        if let Some((ctx, at)) = self.cache.lock().unwrap().get(&key) {
+            if at.elapsed() < CACHE_TTL {
+                return Some(ctx.clone());
+            }
+        }
+        let ctx = read_workspace(&key.0, manifest);
+        self.cache
+            .lock()
+            .unwrap()
+            .insert(key, (ctx.clone(), Instant::now()));
+        Some(ctx)
+    }
+}
+
+/// Nearest manifest found while walking up.
+struct Manifest {
+    dir: PathBuf,
+    file: &'static str,
+    kind: &'static str,
+}
+
+/// Walk up from `cwd`. Returns the root (git root, else nearest manifest
+/// dir) and the nearest manifest.
+fn find_root(cwd: &Path) -> Option<(PathBuf, Option<Manifest>)> {
+    let mut manifest = None;
+    for dir in cwd.ancestors() {
+        if manifest.is_none() {
+            manifest = MANIFESTS
+                .iter()
+                .find(|(file, _)| dir.join(file).is_file())
+                .map(|(file, kind)| Manifest { dir: dir.to_path_buf(), file, kind });
+        }
+        if dir.join(".git").exists() {
+            return Some((dir.to_path_buf(), manifest));
+        }
+    }
+    manifest.map(|m| (m.dir.clone(), Some(m)))
+}
+
+fn read_workspace(root: &Path, manifest: Option<Manifest>) -> WorkspaceContext {
+    let mut lines = Vec::new();
+    let project_type = match manifest {
+        Some(Manifest { dir, file, kind }) => {
+            let content = std::fs::read_to_string(dir.join(file)).unwrap_or_default();
+            if let Some((name, version)) = package_info(file, &content) {
+                lines.push(match version {
+                    Some(v) => format!("package: {} {}", name, v),
+                    None => format!("package: {}", name),
+                });
+            }
+            kind
+        }
+        None => "git",
+    };
+    if let Some(url) = git_remote_url(root) {
+        lines.push(format!("git remote: {}", url));
+    }
+    WorkspaceContext {
+        project_type: project_type.to_string(),
+        root: root.to_path_buf(),
+        metadata: lines.join("\n"),
+    }
+}
+
+/// Extract `(name, version)` from a manifest's contents.
+fn package_info(file: &str, content: &str) -> Option<(String, Option<String>)> {
+    let table = |v: &toml::Value, path: &[&str]| -> Option<(String, Option<String>)> {
+        let mut t = v;
+        for key in path {
+            t = t.get(key)?;
+        }
+        let name = t.get("name")?.as_str()?.to_string();
+        let version = t.get("version").and_then(|v| v.as_str()).map(String::from);
+        Some((name, version))
+    };
+    match file {
+        "Cargo.toml" => {
+            let v: toml::Value = toml::from_str(content).ok()?;
+            table(&v, &["package"])
+        }
+        "pyproject.toml" => {
+            let v: toml::Value = toml::from_str(content).ok()?;
+            table(&v, &["project"]).or_else(|| table(&v, &["tool", "poetry"]))
+        }
+        "package.json" => {
+            let v: serde_json::Value = serde_json::from_str(content).ok()?;
+            let name = v.get("name")?.as_str()?.to_string();
+            let version = v.get("version").and_then(|v| v.as_str()).map(String::from);
+            Some((name, version))
+        }
+        _ => None,
+    }
+}
+
+/// URL of the `origin` remote (or the first remote) from `.git/config`.
+fn git_remote_url(root: &Path) -> Option<String> {
+    let config = std::fs::read_to_string(root.join(".git").join("config")).ok()?;
+    let mut current_remote: Option<String> = None;
+    let mut first = None;
+    for line in config.lines().map(str::trim) {
+        if line.starts_with('[') {
+            current_remote = line
+                .strip_prefix("[remote \"")
+                .and_then(|r| r.strip_suffix("\"]"))
+                .map(String::from);
+            continue;
+        }
+        let Some(remote) = current_remote.as_deref() else { continue };
+        if let Some((key, value)) = line.split_once('=') {
+            if key.trim() == "url" {
+                let url = value.trim().to_string();
+                if remote == "origin" {
+                    return Some(url);
+                }
+                first.get_or_insert(url);
+            }
+        }
+    }
+    first
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_detect_from_nested_directory() {
+        let dir = tempfile::tempdir().unwrap();
+        let root = dir.path();
+        std::fs::create_dir_all(root.join(".git")).unwrap();
+        std::fs::write(
+            root.join(".git/config"),
+            "[core]\n\tbare = false\n[remote \"origin\"]\n\turl = git@example.com:me/demo.git\n",
+        )
+        .unwrap();
+        std::fs::write(
+            root.join("Cargo.toml"),
+            "[package]\nname = \"demo\"\nversion = \"0.3.1\"\n",
+        )
+        .unwrap();
+        let nested = root.join("src/bin");
+        std::fs::create_dir_all(&nested).unwrap();
+
+        let ctx = WorkspaceDetector::new().detect(&nested).unwrap();
+        assert_eq!(ctx.project_type, "rust");
+        assert_eq!(ctx.root, root);
+        assert!(ctx.metadata.contains("package: demo 0.3.1"));
+        assert!(ctx.metadata.contains("git remote: git@example.com:me/demo.git"));
+        assert!(ctx.to_prompt().starts_with("<workspace>\n"));
+    }
+
+    #[test]
+    fn test_nearest_manifest_inside_git_root() {
+        let dir = tempfile::tempdir().unwrap();
+        let root = dir.path();
+        std::fs::create_dir_all(root.join(".git")).unwrap();
+        let web = root.join("web");
+        std::fs::create_dir_all(&web).unwrap();
+        std::fs::write(web.join("package.json"), r#"{"name": "web-ui"}"#).unwrap();
+
+        let ctx = WorkspaceDetector::new().detect(&web).unwrap();
+        assert_eq!(ctx.project_type, "node");
+        assert_eq!(ctx.root, root);
+        assert_eq!(ctx.metadata, "package: web-ui");
+    }
+
+    #[test]
+    fn test_monorepo_packages_cached_separately() {
+        let dir = tempfile::tempdir().unwrap();
+        let root = dir.path();
+        std::fs::create_dir_all(root.join(".git")).unwrap();
+        let web = root.join("web");
+        let cli = root.join("cli/src");
+        std::fs::create_dir_all(&web).unwrap();
+        std::fs::create_dir_all(&cli).unwrap();
+        std::fs::write(web.join("package.json"), r#"{"name": "web-ui"}"#).unwrap();
+        std::fs::write(root.join("cli/Cargo.toml"), "[package]\nname = \"cli\"\n").unwrap();
+
+        let detector = WorkspaceDetector::new();
+        let web_ctx = detector.detect(&web).unwrap();
+        let cli_ctx = detector.detect(&cli).unwrap();
+        let root_ctx = detector.detect(root).unwrap();
+        assert_eq!((web_ctx.project_type.as_str(), web_ctx.metadata.as_str()), ("node", "package: web-ui"));
+        assert_eq!((cli_ctx.project_type.as_str(), cli_ctx.metadata.as_str()), ("rust", "package: cli"));
+        assert_eq!(root_ctx.project_type, "git");
+        assert_eq!(detector.detect(&web).unwrap(), web_ctx);
+    }
+
+    #[test]
+    fn test_no_workspace_found() {
+        let dir = tempfile::tempdir().unwrap();
+        assert!(WorkspaceDetector::new().detect(dir.path()).is_none());
+        assert!(WorkspaceDetector::new()
+            .detect(Path::new("/nonexistent/omnish/path"))
+            .is_none());
+    }
+
+    #[test]
+    fn test_detect_uses_cache_within_ttl() {
+        let dir = tempfile::tempdir().unwrap();
+        let root = dir.path();
+        std::fs::write(root.join("pyproject.toml"), "[project]\nname = \"a\"\n").unwrap();
+        let detector = WorkspaceDetector::new();
+        assert_eq!(detector.detect(root).unwrap().metadata, "package: a");
+
+        std::fs::write(root.join("pyproject.toml"), "[project]\nname = \"b\"\n").unwrap();
+        assert_eq!(detector.detect(root).unwrap().metadata, "package: a");
+    }
+}
@@ -1,8 +1,7 @@
-use crate::task_mgr::{ScheduledTask, TaskContext};
+use crate::task_mgr::{ScheduledTask, TaskContext, TaskJob};
 use anyhow::Result;
 use omnish_common::config::ConfigMap;
 use std::time::Duration;
-use tokio_cron_scheduler::Job;
 
 /// Periodic safety net that closes idle stream.bin writers, releasing fds
 /// when neither `SessionEnd` nor a connection-close signal fired (client
@@ -41,11 +40,11 @@ impl ScheduledTask for WriterIdleTask {
         .into()
     }
 
-    fn create_job(&self, ctx: &TaskContext) -> Result<Job> {
+    fn create_job(&self, ctx: &TaskContext) -> Result<TaskJob> {
         let mgr = ctx.session_mgr.clone();
         let minutes = self.config.get_u64("idle_minutes", 10);
         let max_idle = Duration::from_secs(minutes * 60);
-        Ok(Job::new_async(self.schedule(), move |_uuid, _lock| {
+        TaskJob::new(self.schedule(), move || {
             let mgr = mgr.clone();
             Box::pin(async move {
                 tracing::debug!("task [writer_idle] started");
@@ -55,7 +54,7 @@ impl ScheduledTask for WriterIdleTask {
                 }
                 tracing::debug!("task [writer_idle] finished");
             })
-        })?)
+        })
     }
 }
 
@@ -108,6 +108,9 @@ async fn test_command_recording_via_receive_command() {
             stream_offset: 0,
             stream_length: 0,
             exit_code: None,
+            exit_signal: None,
+            checksum: None,
+            tags: Vec::new(),
         },
     )
     .await
@@ -170,6 +173,9 @@ async fn test_commands_persisted_on_session_end() {
             stream_offset: 0,
             stream_length: 0,
             exit_code: None,
+            exit_signal: None,
+            checksum: None,
+            tags: Vec::new(),
         },
     )
     .await
@@ -252,6 +258,9 @@ async fn test_multi_command_session_e2e() {
             stream_offset: 0,
             stream_length: 0,
             exit_code: None,
+            exit_signal: None,
+            checksum: None,
+            tags: Vec::new(),
         },
     )
     .await
@@ -270,6 +279,9 @@ async fn test_multi_command_session_e2e() {
             stream_offset: 0,
             stream_length: 0,
             exit_code: None,
+            exit_signal: None,
+            checksum: None,
+            tags: Vec::new(),
         },
     )
     .await
@@ -387,6 +399,9 @@ async fn test_debug_context_request() {
             stream_offset: 0,
             stream_length: 0,
             exit_code: None,
+            exit_signal: None,
+            checksum: None,
+            tags: Vec::new(),
         },
     )
     .await
@@ -410,6 +425,7 @@ async fn test_interleaved_two_session_context_at_10_and_20_commands() {
             detailed_commands: 10,
             ..Default::default()
         },
+        ..Default::default()
     };
     let mgr = SessionManager::new(dir.path().to_path_buf(), cc);
 
@@ -449,6 +465,9 @@ async fn test_interleaved_two_session_context_at_10_and_20_commands() {
                 stream_offset: 0,
                 stream_length: 0,
                 exit_code: None,
+                exit_signal: None,
+                checksum: None,
+                tags: Vec::new(),
             },
         )
         .await
@@ -556,6 +575,9 @@ async fn test_register_idempotent_reuses_existing_session() {
             stream_offset: 0,
             stream_length: 0,
             exit_code: None,
+            exit_signal: None,
+            checksum: None,
+            tags: Vec::new(),
         },
     )
     .await
@@ -611,6 +633,9 @@ async fn test_ended_session_commands_visible_to_new_session_context() {
             stream_offset: 0,
             stream_length: 0,
             exit_code: None,
+            exit_signal: None,
+            checksum: None,
+            tags: Vec::new(),
         },
     )
     .await
@@ -1,4 +1,4 @@
-use crate::backend::{CacheHint, ContentBlock, LlmBackend, LlmRequest, LlmResponse, StopReason, Usage};
+use crate::backend::{CacheHint, ContentBlock, LlmBackend, LlmRequest, LlmResponse, StopReason, TextStream, Usage};
 use crate::tool::ToolCall;
 use anyhow::Result;
 use async_trait::async_trait;
@@ -15,6 +15,9 @@ const MAX_BACKOFF: Duration = Duration::from_secs(60);
 /// Maximum cache_control breakpoints in a single Anthropic request.
 const MAX_CACHE_BREAKPOINTS: usize = 4;
 
+/// Messages API version sent in the `anthropic-version` header.
+const ANTHROPIC_VERSION: &str = "2023-06-01";
+
 pub struct AnthropicBackend {
     pub config_name: String,
     pub model: String,
@@ -250,7 +253,7 @@ impl LlmBackend for AnthropicBackend {
             let resp = match client
                 .post(format!("{}/v1/messages", self.base_url))
                 .header("x-api-key", &self.api_key)
-                .header("anthropic-version", "2024-04-04")
+                .header("anthropic-version", ANTHROPIC_VERSION)
                 .header("content-type", "application/json")
                 .json(&body)
                 .send()
@@ -429,6 +432,69 @@ impl LlmBackend for AnthropicBackend {
         Err(last_error.unwrap_or_else(|| anyhow::anyhow!("Anthropic API: max retries exhausted")))
     }
 
+    /// Streams plain-text answers only. Tool-use requests need the complete
+    /// content blocks, so they stay on `complete`.
+    async fn stream(&self, req: &LlmRequest) -> Option<Result<TextStream>> {
+        if !req.tools.is_empty() {
+            return None;
+        }
+        let mut body = build_request_body(req, &self.model);
+        body["stream"] = serde_json::Value::Bool(true);
+
+        let resp = match self
+            .client
+            .post(format!("{}/v1/messages", self.base_url))
+            .header("x-api-key", &self.api_key)
+            .header("anthropic-version", ANTHROPIC_VERSION)
+            .header("content-type", "application/json")
+            .json(&body)
+            .send()
+            .await
+        {
+            Ok(r) => r,
+            Err(e) => return Some(Err(anyhow::anyhow!("Anthropic API connection error: {}", e))),
+        };
+        let status = resp.status();
+        if !status.is_success() {
+            let json: serde_json::Value = resp.json().await.unwrap_or_default();
+            let error_msg = json["error"]["message"].as_str().unwrap_or("Unknown API error");
+            return Some(Err(anyhow::anyhow!("Anthropic API error ({}): {}", status, error_msg)));
+        }
+
+        let state = (resp, crate::sse::SseDecoder::new(), std::collections::VecDeque::new(), false);
+        let stream = futures_util::stream::unfold(state, |(mut resp, mut decoder, mut pending, mut done)| async move {
+            loop {
+                if let Some(item) = pending.pop_front() {
+                    return Some((item, (resp, decoder, pending, done)));
+                }
+                if done {
+                    return None;
+                }
+                match resp.chunk().await {
+                    Ok(Some(bytes)) => {
+                        for data in decoder.push(&bytes) {
+                            match parse_stream_event(&data) {
+                                StreamEvent::Text(t) => pending.push_back(Ok(t)),
+                                StreamEvent::Error(e) => {
+                                    pending.push_back(Err(anyhow::anyhow!("Anthropic stream error: {}", e)));
+                                    done = true;
+                                }
+                                StreamEvent::Stop => done = true,
+                                StreamEvent::Other => {}
+                            }
+                        }
+                    }
+                    Ok(None) => done = true,
+                    Err(e) => {
+                        pending.push_back(Err(anyhow::anyhow!("Anthropic stream read error: {}", e)));
+                        done = true;
+                    }
+                }
+            }
+        });
+        Some(Ok(Box::pin(stream)))
+    }
+
     fn name(&self) -> &str {
         &self.config_name
     }
@@ -442,12 +508,56 @@ impl LlmBackend for AnthropicBackend {
     }
 }
 
+/// One decoded event from the Messages API SSE stream.
+#[derive(Debug, PartialEq)]
+enum StreamEvent {
+    Text(String),
+    Error(String),
+    Stop,
+    /// message_start, ping, thinking deltas, etc.
+    Other,
+}
+
+fn parse_stream_event(data: &str) -> StreamEvent {
+    let Ok(json) = serde_json::from_str::<serde_json::Value>(data) else {
+        return StreamEvent::Other;
+    };
+    match json["type"].as_str() {
+        Some("content_block_delta") if json["delta"]["type"] == "text_delta" => {
+            StreamEvent::Text(json["delta"]["text"].as_str().unwrap_or("").to_string())
+        }
+        Some("message_stop") => StreamEvent::Stop,
+        Some("error") => StreamEvent::Error(
+            json["error"]["message"].as_str().unwrap_or("unknown error").to_string(),
+        ),
+        _ => StreamEvent::Other,
+    }
+}
+
 #[cfg(test)]
 mod tests {
-    use super::{build_request_body, is_opus_4_7_or_later};
-    use crate::backend::{CacheHint, CachedText, LlmRequest, TaggedMessage, TriggerType, UseCase};
+    use super::{build_request_body, is_opus_4_7_or_later, parse_stream_event, AnthropicBackend, StreamEvent, ANTHROPIC_VERSION};
+    use crate::backend::{CacheHint, CachedText, LlmBackend, LlmRequest, StopReason, TaggedMessage, TriggerType, UseCase};
     use crate::tool::ToolDef;
 
+    #[test]
+    fn stream_event_parsing() {
+        assert_eq!(
+            parse_stream_event(r#"{"type":"content_block_delta","index":0,"delta":{"type":"text_delta","text":"Hi"}}"#),
+            StreamEvent::Text("Hi".to_string())
+        );
+        assert_eq!(
+            parse_stream_event(r#"{"type":"content_block_delta","index":0,"delta":{"type":"thinking_delta","thinking":"hmm"}}"#),
+            StreamEvent::Other
+        );
+        assert_eq!(parse_stream_event(r#"{"type":"message_stop"}"#), StreamEvent::Stop);
+        assert_eq!(
+            parse_stream_event(r#"{"type":"error","error":{"type":"overloaded_error","message":"Overloaded"}}"#),
+            StreamEvent::Error("Overloaded".to_string())
+        );
+        assert_eq!(parse_stream_event("not json"), StreamEvent::Other);
+    }
+
     #[test]
     fn thinking_enabled_manual_emits_budget_tokens() {
         let mut req = empty_req();
@@ -495,6 +605,51 @@ mod tests {
         assert!(is_opus_4_7_or_later("claude-opus-4-10"));
     }
 
+    #[tokio::test]
+    async fn complete_posts_messages_request() {
+        use wiremock::matchers::{header, method, path};
+        use wiremock::{Mock, MockServer, ResponseTemplate};
+
+        let server = MockServer::start().await;
+        Mock::given(method("POST"))
+            .and(path("/v1/messages"))
+            .and(header("x-api-key", "sk-test"))
+            .and(header("anthropic-version", ANTHROPIC_VERSION))
+            .respond_with(ResponseTemplate::new(200).set_body_json(serde_json::json!({
+                "content": [{"type": "text", "text": "Port 8080 is already in use."}],
+                "stop_reason": "end_turn",
+                "usage": {"input_tokens": 12, "output_tokens": 7},
+            })))
+            .expect(1)
+            .mount(&server)
+            .await;
+
+        let backend = AnthropicBackend {
+            config_name: "claude".into(),
+            model: "claude-sonnet-4-5".into(),
+            api_key: "sk-test".into(),
+            base_url: server.uri(),
+            client: reqwest::Client::new(),
+            max_content_chars: None,
+        };
+        let req = LlmRequest {
+            query: Some("why did the server fail to start?".into()),
+            use_case: UseCase::Analysis,
+            ..empty_req()
+        };
+        let resp = backend.complete(&req).await.unwrap();
+        assert_eq!(resp.text(), "Port 8080 is already in use.");
+        assert_eq!(resp.stop_reason, StopReason::EndTurn);
+        assert_eq!(resp.usage.as_ref().map(|u| u.output_tokens), Some(7));
+
+        let received = server.received_requests().await.unwrap();
+        let body: serde_json::Value = serde_json::from_slice(&received[0].body).unwrap();
+        assert_eq!(body["model"], "claude-sonnet-4-5");
+        assert!(body["max_tokens"].as_u64().unwrap() > 0);
+        assert_eq!(body["messages"][0]["role"], "user");
+        assert!(body["messages"][0]["content"].to_string().contains("why did the server fail to start?"));
+    }
+
     fn empty_req() -> LlmRequest {
         LlmRequest {
             context: String::new(),
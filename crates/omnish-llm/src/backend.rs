@@ -1,6 +1,8 @@
 use anyhow::Result;
 use async_trait::async_trait;
+use futures_util::Stream;
 use serde::{Deserialize, Serialize};
+use std::pin::Pin;
 
 use crate::tool::{ToolCall, ToolDef};
 
@@ -173,14 +175,27 @@ impl LlmResponse {
     }
 }
 
+/// Response text delivered incrementally by `LlmBackend::stream`.
+pub type TextStream = Pin<Box<dyn Stream<Item = Result<String>> + Send>>;
+
 #[async_trait]
 pub trait LlmBackend: Send + Sync {
     async fn complete(&self, req: &LlmRequest) -> Result<LlmResponse>;
+    /// Stream the response text as it is generated. Returns `None` when the
+    /// backend (or this particular request) has no streaming support, in
+    /// which case callers fall back to `complete`.
+    async fn stream(&self, _req: &LlmRequest) -> Option<Result<TextStream>> {
+        None
+    }
     fn name(&self) -> &str;
     /// Returns the maximum content characters limit for this backend's model
     fn max_content_chars(&self) -> Option<usize> {
         None
     }
+    /// Estimate how many tokens `text` costs with this backend's tokenizer.
+    fn estimate_tokens(&self, text: &str) -> usize {
+        crate::tokens::token_count(text)
+    }
     /// Model name of this backend.
     fn model_name(&self) -> &str;
 }
@@ -1,7 +1,10 @@
 use crate::anthropic::AnthropicBackend;
-use crate::backend::{BackendInfo, LlmBackend, UseCase};
+use crate::backend::{BackendInfo, LlmBackend, LlmRequest, LlmResponse, TextStream, UseCase};
 use crate::langfuse::{LangfuseBackend, LangfuseConfig};
+use crate::ollama::OllamaBackend;
 use crate::openai_compat::OpenAiCompatBackend;
+use crate::rate_limit::{RateLimitedBackend, RateLimiter};
+use crate::tokens::TokenBudget;
 use anyhow::{anyhow, Result};
 use async_trait::async_trait;
 use omnish_common::config::{LlmBackendConfig, LlmConfig};
@@ -60,6 +63,17 @@ fn effective_max_content_chars(config: &LlmBackendConfig) -> Option<usize> {
         .or_else(|| config.context_window.map(|cw| cw * 3 / 2))
 }
 
+/// Compute the context token budget from config: `context_window` less the
+/// tokens reserved for the system prompt, query and response. An explicit
+/// `max_content_chars` is converted to tokens and still capped by the window.
+fn context_token_budget(config: &LlmBackendConfig) -> Option<usize> {
+    let from_window = config.context_window.map(crate::tokens::context_budget);
+    match config.max_content_chars.map(crate::tokens::chars_to_tokens) {
+        Some(tokens) => Some(from_window.map_or(tokens, |w| w.min(tokens))),
+        None => from_window,
+    }
+}
+
 /// Create LLM backend from config
 pub fn create_backend(
     name: &str,
@@ -67,8 +81,6 @@ pub fn create_backend(
     proxy: Option<&str>,
     no_proxy: Option<&str>,
 ) -> Result<Arc<dyn LlmBackend>> {
-    let api_key = resolve_api_key(&config.api_key_cmd)?;
-
     // Only apply global proxy if this backend opts in via use_proxy
     let effective_proxy = if config.use_proxy { proxy } else { None };
     let effective_no_proxy = if config.use_proxy { no_proxy } else { None };
@@ -77,6 +89,7 @@ pub fn create_backend(
 
     match config.backend_type.as_str() {
         "anthropic" => {
+            let api_key = resolve_api_key(&config.api_key_cmd)?;
             let client = build_http_client(effective_proxy, effective_no_proxy)?;
             let base_url = config
                 .base_url
@@ -96,6 +109,7 @@ pub fn create_backend(
                 .base_url
                 .clone()
                 .ok_or_else(|| anyhow!("openai-compat requires base_url"))?;
+            let api_key = resolve_api_key(&config.api_key_cmd)?;
             let client = build_http_client(effective_proxy, effective_no_proxy)?;
             Ok(Arc::new(OpenAiCompatBackend {
                 config_name: name.to_string(),
@@ -106,6 +120,13 @@ pub fn create_backend(
                 max_content_chars,
             }))
         }
+        "ollama" => {
+            let client = build_http_client(effective_proxy, effective_no_proxy)?;
+            let base_url = config.base_url.as_deref().unwrap_or(crate::ollama::DEFAULT_BASE_URL);
+            Ok(Arc::new(
+                OllamaBackend::new(name, base_url, &config.model, client).with_max_content_chars(max_content_chars),
+            ))
+        }
         other => Err(anyhow!("unknown backend type: {}", other)),
     }
 }
@@ -131,14 +152,22 @@ pub struct MultiBackend {
     use_case_backends: RwLock<HashMap<String, Arc<dyn LlmBackend>>>,
     /// Default backend for unknown use cases
     default_backend: Arc<dyn LlmBackend>,
+    /// `llm.fallback`: retried once when the selected backend fails.
+    fallback_backend: Option<Arc<dyn LlmBackend>>,
     /// Map from use case name to max_content_chars
     use_case_max_chars: HashMap<String, Option<usize>>,
+    /// Map from use case name to context token budget
+    use_case_context_tokens: HashMap<String, Option<usize>>,
+    /// Context token budget of the default backend
+    default_context_tokens: Option<usize>,
     /// All backends by config name (for per-thread model selection).
     named_backends: HashMap<String, Arc<dyn LlmBackend>>,
     /// Backend info list for listing available models.
     backend_configs: Vec<BackendInfo>,
     /// Default chat backend name.
     chat_backend_name: String,
+    /// Shared by every backend so all use cases draw from one quota.
+    rate_limiter: Arc<RateLimiter>,
 }
 
 impl MultiBackend {
@@ -146,6 +175,11 @@ impl MultiBackend {
     pub fn new(llm_config: &LlmConfig, proxy: Option<&str>, no_proxy: Option<&str>) -> Result<Self> {
         // Resolve Langfuse config if present
         let langfuse_config = resolve_langfuse_config(llm_config, proxy, no_proxy);
+        let rl = &llm_config.rate_limit;
+        let rate_limiter = Arc::new(
+            RateLimiter::new(rl.requests_per_minute, rl.tokens_per_minute)
+                .with_max_wait(std::time::Duration::from_millis(rl.max_wait_ms)),
+        );
 
         // First pass: create all backends by config name
         let mut named_backends = HashMap::new();
@@ -154,6 +188,11 @@ impl MultiBackend {
             match create_backend(name, cfg, proxy, no_proxy) {
                 Ok(backend) => {
                     let backend = maybe_wrap_langfuse(backend, &langfuse_config);
+                    let backend = if rate_limiter.is_enabled() {
+                        RateLimitedBackend::wrap(backend, rate_limiter.clone())
+                    } else {
+                        backend
+                    };
                     named_backends.insert(name.clone(), backend);
                     backend_configs.push(BackendInfo {
                         name: name.clone(),
@@ -170,6 +209,7 @@ impl MultiBackend {
         // Second pass: map use cases to backends
         let use_case_backends = RwLock::new(HashMap::new());
         let mut use_case_max_chars = HashMap::new();
+        let mut use_case_context_tokens = HashMap::new();
         for (use_case_name, backend_name) in &llm_config.use_cases {
             if let Some(backend) = named_backends.get(backend_name) {
                 use_case_backends
@@ -178,6 +218,7 @@ impl MultiBackend {
                     .insert(use_case_name.clone(), backend.clone());
                 if let Some(cfg) = llm_config.backends.get(backend_name) {
                     use_case_max_chars.insert(use_case_name.clone(), effective_max_content_chars(cfg));
+                    use_case_context_tokens.insert(use_case_name.clone(), context_token_budget(cfg));
                 }
             } else {
                 tracing::warn!(
@@ -195,6 +236,20 @@ impl MultiBackend {
                 anyhow!("no LLM backends could be initialized - check backend_type values in daemon.toml")
             })?;
 
+        let default_context_tokens = llm_config
+            .backends
+            .iter()
+            .find(|(name, _)| named_backends.get(*name).is_some_and(|b| Arc::ptr_eq(b, &default_backend)))
+            .and_then(|(_, cfg)| context_token_budget(cfg));
+
+        let fallback_backend = llm_config.fallback.as_ref().and_then(|name| {
+            let backend = named_backends.get(name).cloned();
+            if backend.is_none() {
+                tracing::warn!("fallback backend '{}' not available", name);
+            }
+            backend
+        });
+
         let chat_backend_name = llm_config.use_cases
             .get("chat")
             .cloned()
@@ -203,10 +258,14 @@ impl MultiBackend {
         Ok(Self {
             use_case_backends,
             default_backend,
+            fallback_backend,
             use_case_max_chars,
+            use_case_context_tokens,
+            default_context_tokens,
             named_backends,
             backend_configs,
             chat_backend_name,
+            rate_limiter,
         })
     }
 
@@ -219,11 +278,24 @@ impl MultiBackend {
             UseCase::Summarize => "summarize",
         };
 
-        self.use_case_backends
+        let backend = self
+            .use_case_backends
             .read()
             .ok()
             .and_then(|backends| backends.get(use_case_name).cloned())
-            .unwrap_or_else(|| self.default_backend.clone())
+            .unwrap_or_else(|| self.default_backend.clone());
+        self.with_fallback_for(backend)
+    }
+
+    /// `backend`, retried on `llm.fallback` when it fails (if configured).
+    fn with_fallback_for(&self, backend: Arc<dyn LlmBackend>) -> Arc<dyn LlmBackend> {
+        match &self.fallback_backend {
+            Some(fallback) if !Arc::ptr_eq(fallback, &backend) => Arc::new(FallbackBackend {
+                primary: backend,
+                fallback: fallback.clone(),
+            }),
+            _ => backend,
+        }
     }
 
     /// Get max_content_chars for the given use case
@@ -242,6 +314,24 @@ impl MultiBackend {
             .or_else(|| self.get_backend(use_case).max_content_chars())
     }
 
+    /// Get the context token budget for the given use case, counted with
+    /// that use case's backend. Derived from `context_window` minus what the
+    /// prompt and response need (or from `max_content_chars`).
+    pub fn get_context_budget(&self, use_case: UseCase) -> Option<TokenBudget> {
+        let use_case_name = match use_case {
+            UseCase::Completion => "completion",
+            UseCase::Analysis => "analysis",
+            UseCase::Chat => "chat",
+            UseCase::Summarize => "summarize",
+        };
+
+        let max_tokens = match self.use_case_context_tokens.get(use_case_name) {
+            Some(tokens) => *tokens,
+            None => self.default_context_tokens,
+        }?;
+        Some(TokenBudget::for_backend(max_tokens, self.get_backend(use_case)))
+    }
+
     /// Model name for the given use case.
     pub fn model_name_for_use_case(&self, use_case: UseCase) -> String {
         self.get_backend(use_case).model_name().to_string()
@@ -259,7 +349,23 @@ impl MultiBackend {
 
     /// Get backend by config name (for per-thread model override).
     pub fn get_backend_by_name(&self, name: &str) -> Option<Arc<dyn LlmBackend>> {
-        self.named_backends.get(name).cloned()
+        let backend = self.named_backends.get(name).cloned()?;
+        Some(self.with_fallback_for(backend))
+    }
+
+    /// Complete `req` with the backend configured as `name` instead of the
+    /// use-case backend (per-query model override).
+    pub async fn complete_with_model(&self, name: &str, req: &LlmRequest) -> Result<LlmResponse> {
+        let backend = self
+            .get_backend_by_name(name)
+            .ok_or_else(|| anyhow!("unknown model: {}", name))?;
+        backend.complete(req).await
+    }
+
+    /// Streaming counterpart of `complete_with_model`. Returns None when
+    /// `name` is unknown or the backend cannot stream.
+    pub async fn stream_with_model(&self, name: &str, req: &LlmRequest) -> Option<Result<TextStream>> {
+        self.get_backend_by_name(name)?.stream(req).await
     }
 
     /// Create a MultiBackend wrapping a single backend (for testing).
@@ -269,30 +375,134 @@ impl MultiBackend {
         Self {
             use_case_backends: RwLock::new(HashMap::new()),
             default_backend: backend.clone(),
+            fallback_backend: None,
             use_case_max_chars: HashMap::new(),
+            use_case_context_tokens: HashMap::new(),
+            default_context_tokens: None,
             named_backends: HashMap::from([(name.clone(), backend)]),
             backend_configs: vec![BackendInfo { name: name.clone(), model }],
             chat_backend_name: name,
+            rate_limiter: Arc::new(RateLimiter::new(0, 0)),
         }
     }
+
+    /// Register an additional named backend (for testing, with `from_single`).
+    pub fn with_backend(mut self, backend: Arc<dyn LlmBackend>) -> Self {
+        let name = backend.name().to_string();
+        self.backend_configs.push(BackendInfo { name: name.clone(), model: backend.model_name().to_string() });
+        self.named_backends.insert(name, backend);
+        self
+    }
+
+    /// Budget context for a `context_window`-token model (for testing, with
+    /// `from_single`).
+    pub fn with_context_window(mut self, context_window: usize) -> Self {
+        self.default_context_tokens = Some(crate::tokens::context_budget(context_window));
+        self
+    }
+
+    /// Retry requests that fail on their use-case backend with `backend`.
+    pub fn with_fallback(mut self, backend: Arc<dyn LlmBackend>) -> Self {
+        self.fallback_backend = Some(backend);
+        self
+    }
+
+    /// Limiter applied to every backend of this instance.
+    pub fn rate_limiter(&self) -> &Arc<RateLimiter> {
+        &self.rate_limiter
+    }
 }
 
 #[async_trait]
 impl LlmBackend for MultiBackend {
     async fn complete(&self, req: &crate::backend::LlmRequest) -> Result<crate::backend::LlmResponse> {
-        let backend = self.get_backend(req.use_case);
-        backend.complete(req).await
+        self.get_backend(req.use_case).complete(req).await
+    }
+
+    async fn stream(&self, req: &crate::backend::LlmRequest) -> Option<Result<crate::backend::TextStream>> {
+        self.get_backend(req.use_case).stream(req).await
     }
 
     fn name(&self) -> &str {
         "multi"
     }
 
+    fn estimate_tokens(&self, text: &str) -> usize {
+        self.default_backend.estimate_tokens(text)
+    }
+
     fn model_name(&self) -> &str {
         self.default_backend.model_name()
     }
 }
 
+/// A backend that retries failed requests on `fallback`. Otherwise it
+/// answers as `primary`.
+struct FallbackBackend {
+    primary: Arc<dyn LlmBackend>,
+    fallback: Arc<dyn LlmBackend>,
+}
+
+impl FallbackBackend {
+    /// `fallback_err` carrying both failures, so callers can still inspect it
+    /// (e.g. for a `RateLimitError`) and users see why each backend failed.
+    fn both_failed(&self, primary_err: anyhow::Error, fallback_err: anyhow::Error) -> anyhow::Error {
+        let msg = format!(
+            "backend '{}' failed: {}; fallback '{}' failed: {}",
+            self.primary.name(),
+            primary_err,
+            self.fallback.name(),
+            fallback_err
+        );
+        fallback_err.context(msg)
+    }
+}
+
+#[async_trait]
+impl LlmBackend for FallbackBackend {
+    async fn complete(&self, req: &LlmRequest) -> Result<LlmResponse> {
+        let err = match self.primary.complete(req).await {
+            Ok(resp) => return Ok(resp),
+            Err(e) => e,
+        };
+        tracing::warn!("backend '{}' failed ({}), retrying with '{}'", self.primary.name(), err, self.fallback.name());
+        self.fallback.complete(req).await.map_err(|e| self.both_failed(err, e))
+    }
+
+    /// Falls back when the stream cannot be started. A stream that breaks
+    /// after sending text is not retried, as the text is already out.
+    async fn stream(&self, req: &LlmRequest) -> Option<Result<TextStream>> {
+        let err = match self.primary.stream(req).await? {
+            Ok(stream) => return Some(Ok(stream)),
+            Err(e) => e,
+        };
+        tracing::warn!("backend '{}' failed ({}), retrying with '{}'", self.primary.name(), err, self.fallback.name());
+        let result = match self.fallback.stream(req).await {
+            Some(result) => result,
+            None => self.fallback.complete(req).await.map(|resp| {
+                Box::pin(futures_util::stream::once(async move { Ok(resp.text()) })) as TextStream
+            }),
+        };
+        Some(result.map_err(|e| self.both_failed(err, e)))
+    }
+
+    fn name(&self) -> &str {
+        self.primary.name()
+    }
+
+    fn max_content_chars(&self) -> Option<usize> {
+        self.primary.max_content_chars()
+    }
+
+    fn estimate_tokens(&self, text: &str) -> usize {
+        self.primary.estimate_tokens(text)
+    }
+
+    fn model_name(&self) -> &str {
+        self.primary.model_name()
+    }
+}
+
 /// Resolve Langfuse configuration, returning None if not configured or key missing.
 fn resolve_langfuse_config(llm_config: &LlmConfig, proxy: Option<&str>, no_proxy: Option<&str>) -> Option<LangfuseConfig> {
     let cfg = llm_config.langfuse.as_ref()?;
@@ -342,6 +552,32 @@ mod tests {
         assert!(result.is_err());
     }
 
+    #[test]
+    fn test_context_token_budget_reserves_prompt_and_response() {
+        let mut config = LlmBackendConfig {
+            backend_type: "anthropic".to_string(),
+            model: "claude".to_string(),
+            api_key_cmd: None,
+            base_url: None,
+            use_proxy: false,
+            context_window: Some(200_000),
+            max_content_chars: None,
+        };
+        let budget = context_token_budget(&config).unwrap();
+        assert!(budget < 200_000);
+        assert_eq!(budget, crate::tokens::context_budget(200_000));
+
+        // An explicit character limit is converted, but never exceeds the window
+        config.max_content_chars = Some(30_000);
+        assert_eq!(context_token_budget(&config), Some(20_000));
+        config.max_content_chars = Some(1_000_000);
+        assert_eq!(context_token_budget(&config), Some(budget));
+
+        config.context_window = None;
+        config.max_content_chars = None;
+        assert_eq!(context_token_budget(&config), None);
+    }
+
     #[test]
     fn test_create_anthropic_backend() {
         let config = LlmBackendConfig {
@@ -409,4 +645,168 @@ mod tests {
         let err = result.err().unwrap();
         assert!(err.to_string().contains("unknown backend"));
     }
+
+    #[test]
+    fn test_create_ollama_backend_without_api_key() {
+        let config = LlmBackendConfig {
+            backend_type: "ollama".to_string(),
+            model: "llama3.2".to_string(),
+            api_key_cmd: None,
+            base_url: None,
+            use_proxy: false,
+            context_window: None,
+            max_content_chars: None,
+        };
+
+        let backend = create_backend("local", &config, None, None).unwrap();
+        assert_eq!(backend.name(), "local");
+        assert_eq!(backend.model_name(), "llama3.2");
+    }
+
+    #[tokio::test]
+    async fn test_fallback_answers_when_primary_fails() {
+        use crate::backend::{LlmRequest, TriggerType, UnavailableBackend, UseCase};
+        use wiremock::matchers::{method, path};
+        use wiremock::{Mock, MockServer, ResponseTemplate};
+
+        let server = MockServer::start().await;
+        Mock::given(method("POST"))
+            .and(path("/api/generate"))
+            .respond_with(ResponseTemplate::new(200).set_body_json(serde_json::json!({
+                "response": "from fallback",
+                "done": true,
+            })))
+            .expect(1)
+            .mount(&server)
+            .await;
+        let fallback = Arc::new(OllamaBackend::new("local", &server.uri(), "llama3.2", reqwest::Client::new()));
+        let multi = MultiBackend::from_single(Arc::new(UnavailableBackend)).with_fallback(fallback);
+
+        let req = LlmRequest {
+            context: String::new(),
+            query: Some("hi".into()),
+            trigger: TriggerType::Manual,
+            session_ids: vec![],
+            use_case: UseCase::Analysis,
+            max_content_chars: None,
+            system_prompt: None,
+            enable_thinking: None,
+            tools: vec![],
+            extra_messages: vec![],
+        };
+        let resp = multi.complete(&req).await.unwrap();
+        assert_eq!(resp.text(), "from fallback");
+    }
+
+    fn chat_request() -> crate::backend::LlmRequest {
+        crate::backend::LlmRequest {
+            context: String::new(),
+            query: Some("hi".into()),
+            trigger: crate::backend::TriggerType::Manual,
+            session_ids: vec![],
+            use_case: UseCase::Chat,
+            max_content_chars: None,
+            system_prompt: None,
+            enable_thinking: None,
+            tools: vec![],
+            extra_messages: vec![],
+        }
+    }
+
+    #[tokio::test]
+    async fn test_fallback_covers_selected_backends_and_streams() {
+        use crate::backend::UnavailableBackend;
+        use futures_util::StreamExt;
+        use wiremock::matchers::{method, path};
+        use wiremock::{Mock, MockServer, ResponseTemplate};
+
+        /// Fails to start its stream.
+        struct BrokenStream;
+
+        #[async_trait]
+        impl LlmBackend for BrokenStream {
+            async fn complete(&self, _req: &LlmRequest) -> Result<LlmResponse> {
+                anyhow::bail!("connection refused")
+            }
+            async fn stream(&self, _req: &LlmRequest) -> Option<Result<TextStream>> {
+                Some(Err(anyhow!("connection refused")))
+            }
+            fn name(&self) -> &str {
+                "cloud"
+            }
+            fn model_name(&self) -> &str {
+                "cloud"
+            }
+        }
+
+        let server = MockServer::start().await;
+        Mock::given(method("POST"))
+            .and(path("/api/generate"))
+            .respond_with(ResponseTemplate::new(200).set_body_json(serde_json::json!({
+                "response": "from fallback",
+                "done": true,
+            })))
+            .mount(&server)
+            .await;
+        let fallback = Arc::new(OllamaBackend::new("local", &server.uri(), "llama3.2", reqwest::Client::new()));
+        let multi = MultiBackend::from_single(Arc::new(UnavailableBackend))
+            .with_backend(Arc::new(BrokenStream))
+            .with_fallback(fallback);
+
+        // Backends picked by name (per-thread / per-query model) fall back too
+        let backend = multi.get_backend_by_name("cloud").unwrap();
+        assert_eq!(backend.name(), "cloud");
+        assert_eq!(backend.complete(&chat_request()).await.unwrap().text(), "from fallback");
+
+        // Ollama cannot stream, so the fallback answer arrives as one chunk
+        let mut stream = backend.stream(&chat_request()).await.unwrap().unwrap();
+        assert_eq!(stream.next().await.unwrap().unwrap(), "from fallback");
+        assert!(stream.next().await.is_none());
+    }
+
+    #[tokio::test]
+    async fn test_fallback_failure_reports_both_errors() {
+        use crate::backend::UnavailableBackend;
+        use wiremock::matchers::{method, path};
+        use wiremock::{Mock, MockServer, ResponseTemplate};
+
+        let server = MockServer::start().await;
+        Mock::given(method("POST"))
+            .and(path("/api/generate"))
+            .respond_with(ResponseTemplate::new(404).set_body_json(serde_json::json!({"error": "model not found"})))
+            .mount(&server)
+            .await;
+        let fallback = Arc::new(OllamaBackend::new("local", &server.uri(), "llama3.2", reqwest::Client::new()));
+        let multi = MultiBackend::from_single(Arc::new(UnavailableBackend)).with_fallback(fallback);
+
+        let err = multi.get_backend(UseCase::Chat).complete(&chat_request()).await.unwrap_err().to_string();
+        assert!(err.contains("'unavailable' failed"), "{err}");
+        assert!(err.contains("fallback 'local' failed") && err.contains("model not found"), "{err}");
+    }
+
+    #[test]
+    fn test_missing_fallback_backend_is_ignored() {
+        let mut backends = HashMap::new();
+        backends.insert(
+            "local".to_string(),
+            LlmBackendConfig {
+                backend_type: "ollama".to_string(),
+                model: "llama3.2".to_string(),
+                api_key_cmd: None,
+                base_url: None,
+                use_proxy: false,
+                context_window: None,
+                max_content_chars: None,
+            },
+        );
+        let config = LlmConfig {
+            default: "local".to_string(),
+            backends,
+            fallback: Some("nope".to_string()),
+            ..Default::default()
+        };
+
+        let multi = MultiBackend::new(&config, None, None).unwrap();
+        assert!(multi.fallback_backend.is_none());
+    }
 }
@@ -100,6 +100,12 @@ impl LlmBackend for LangfuseBackend {
         result
     }
 
+    /// Streamed responses are passed through untraced - the full text is
+    /// only known once the caller has drained the stream.
+    async fn stream(&self, req: &LlmRequest) -> Option<Result<crate::backend::TextStream>> {
+        self.inner.stream(req).await
+    }
+
     fn name(&self) -> &str {
         self.inner.name()
     }
@@ -108,6 +114,10 @@ impl LlmBackend for LangfuseBackend {
         self.inner.max_content_chars()
     }
 
+    fn estimate_tokens(&self, text: &str) -> usize {
+        self.inner.estimate_tokens(text)
+    }
+
     fn model_name(&self) -> &str {
         self.inner.model_name()
     }
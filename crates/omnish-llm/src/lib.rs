@@ -3,8 +3,12 @@ pub mod backend;
 pub mod factory;
 pub mod langfuse;
 pub mod message_log;
+pub mod ollama;
 pub mod openai_compat;
 pub mod presets;
 pub mod prompt;
+pub mod rate_limit;
+pub mod sse;
 pub mod template;
+pub mod tokens;
 pub mod tool;
@@ -0,0 +1,181 @@
+use crate::backend::{ContentBlock, LlmBackend, LlmRequest, LlmResponse, StopReason, Usage};
+use anyhow::Result;
+use async_trait::async_trait;
+
+/// Default address of a local Ollama server.
+pub const DEFAULT_BASE_URL: &str = "http://localhost:11434";
+
+/// Single-turn backend for a self-hosted Ollama server (`/api/generate`).
+/// Needs no API key. Multi-turn chat with tools is not supported; point an
+/// `openai-compat` backend at `<base_url>/v1` for that.
+pub struct OllamaBackend {
+    pub config_name: String,
+    pub base_url: String,
+    pub model: String,
+    pub client: reqwest::Client,
+    pub max_content_chars: Option<usize>,
+}
+
+impl OllamaBackend {
+    pub fn new(config_name: &str, base_url: &str, model: &str, client: reqwest::Client) -> Self {
+        Self {
+            config_name: config_name.to_string(),
+            base_url: base_url.trim_end_matches('/').to_string(),
+            model: model.to_string(),
+            client,
+            max_content_chars: None,
+        }
+    }
+
+    pub fn with_max_content_chars(mut self, max: Option<usize>) -> Self {
+        self.max_content_chars = max;
+        self
+    }
+}
+
+fn build_request_body(req: &LlmRequest, model: &str) -> serde_json::Value {
+    let prompt = crate::template::build_user_content(&req.context, req.query.as_deref());
+    let mut body = serde_json::json!({
+        "model": model,
+        "prompt": prompt,
+        "stream": false,
+    });
+    if let Some(ref system) = req.system_prompt {
+        body["system"] = serde_json::Value::String(system.text.clone());
+    }
+    body
+}
+
+#[async_trait]
+impl LlmBackend for OllamaBackend {
+    async fn complete(&self, req: &LlmRequest) -> Result<LlmResponse> {
+        if !req.tools.is_empty() || !req.extra_messages.is_empty() {
+            return Err(anyhow::anyhow!(
+                "ollama backend only answers single-turn requests; use an openai-compat backend with base_url {}/v1 for chat",
+                self.base_url
+            ));
+        }
+        let body = build_request_body(req, &self.model);
+        let resp = self
+            .client
+            .post(format!("{}/api/generate", self.base_url))
+            .json(&body)
+            .send()
+            .await
+            .map_err(|e| anyhow::anyhow!("Ollama connection error: {}", e))?;
+        let status = resp.status();
+        let json: serde_json::Value = resp
+            .json()
+            .await
+            .map_err(|e| anyhow::anyhow!("Ollama response decode error ({}): {}", status, e))?;
+        if !status.is_success() {
+            let error_msg = json["error"].as_str().unwrap_or("unknown error");
+            return Err(anyhow::anyhow!("Ollama API error ({}): {}", status, error_msg));
+        }
+
+        let text = json["response"]
+            .as_str()
+            .ok_or_else(|| anyhow::anyhow!("Invalid response format: missing response field"))?;
+        let stop_reason = match json["done_reason"].as_str() {
+            Some("length") => StopReason::MaxTokens,
+            _ => StopReason::EndTurn,
+        };
+        let usage = match (json["prompt_eval_count"].as_u64(), json["eval_count"].as_u64()) {
+            (None, None) => None,
+            (input, output) => Some(Usage {
+                input_tokens: input.unwrap_or(0),
+                output_tokens: output.unwrap_or(0),
+                ..Default::default()
+            }),
+        };
+        Ok(LlmResponse {
+            content: vec![ContentBlock::Text(text.to_string())],
+            stop_reason,
+            model: self.model.clone(),
+            usage,
+        })
+    }
+
+    fn name(&self) -> &str {
+        &self.config_name
+    }
+
+    fn model_name(&self) -> &str {
+        &self.model
+    }
+
+    fn max_content_chars(&self) -> Option<usize> {
+        self.max_content_chars
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::backend::{TriggerType, UseCase};
+    use wiremock::matchers::{method, path};
+    use wiremock::{Mock, MockServer, ResponseTemplate};
+
+    fn request(query: &str) -> LlmRequest {
+        LlmRequest {
+            context: "$ make\nerror: linker `cc` not found".into(),
+            query: Some(query.into()),
+            trigger: TriggerType::Manual,
+            session_ids: vec![],
+            use_case: UseCase::Analysis,
+            max_content_chars: None,
+            system_prompt: None,
+            enable_thinking: None,
+            tools: vec![],
+            extra_messages: vec![],
+        }
+    }
+
+    #[tokio::test]
+    async fn complete_posts_generate_request() {
+        let server = MockServer::start().await;
+        Mock::given(method("POST"))
+            .and(path("/api/generate"))
+            .respond_with(ResponseTemplate::new(200).set_body_json(serde_json::json!({
+                "model": "llama3.2",
+                "response": "Install build-essential.",
+                "done": true,
+                "done_reason": "stop",
+                "prompt_eval_count": 30,
+                "eval_count": 5,
+            })))
+            .expect(1)
+            .mount(&server)
+            .await;
+
+        let backend = OllamaBackend::new("local", &format!("{}/", server.uri()), "llama3.2", reqwest::Client::new());
+        assert_eq!(backend.name(), "local");
+        let resp = backend.complete(&request("why did make fail?")).await.unwrap();
+        assert_eq!(resp.text(), "Install build-essential.");
+        assert_eq!(resp.usage.as_ref().map(|u| (u.input_tokens, u.output_tokens)), Some((30, 5)));
+
+        let received = server.received_requests().await.unwrap();
+        let body: serde_json::Value = serde_json::from_slice(&received[0].body).unwrap();
+        assert_eq!(body["model"], "llama3.2");
+        assert_eq!(body["stream"], false);
+        let prompt = body["prompt"].as_str().unwrap();
+        assert!(prompt.contains("why did make fail?"), "{prompt}");
+        assert!(prompt.contains("linker `cc` not found"), "{prompt}");
+    }
+
+    #[tokio::test]
+    async fn complete_reports_api_error() {
+        let server = MockServer::start().await;
+        Mock::given(method("POST"))
+            .and(path("/api/generate"))
+            .respond_with(
+                ResponseTemplate::new(404).set_body_json(serde_json::json!({"error": "model 'nope' not found"})),
+            )
+            .mount(&server)
+            .await;
+
+        let backend = OllamaBackend::new("local", &server.uri(), "nope", reqwest::Client::new());
+        let err = backend.complete(&request("hi")).await.unwrap_err();
+        assert!(err.to_string().contains("model 'nope' not found"), "{err}");
+    }
+}
@@ -20,6 +20,19 @@ pub struct OpenAiCompatBackend {
     pub max_content_chars: Option<usize>,
 }
 
+/// BPE tokenizer tiktoken uses for `model`, or None for models it doesn't
+/// know (most non-OpenAI models served over the compatible API).
+fn bpe_for_model(model: &str) -> Option<&'static tiktoken_rs::CoreBPE> {
+    use tiktoken_rs::tokenizer::{get_tokenizer, Tokenizer};
+    Some(match get_tokenizer(model)? {
+        Tokenizer::O200kBase => tiktoken_rs::o200k_base_singleton(),
+        Tokenizer::Cl100kBase => tiktoken_rs::cl100k_base_singleton(),
+        Tokenizer::P50kBase => tiktoken_rs::p50k_base_singleton(),
+        Tokenizer::P50kEdit => tiktoken_rs::p50k_edit_singleton(),
+        Tokenizer::R50kBase | Tokenizer::Gpt2 => tiktoken_rs::r50k_base_singleton(),
+    })
+}
+
 /// Top-level fields we know how to interpret in `choices[0].message`. Any key
 /// outside this set triggers a one-shot telemetry warn so a future vendor
 /// extension (the next `reasoning_content`-style field) gets surfaced rather
@@ -526,6 +539,13 @@ impl LlmBackend for OpenAiCompatBackend {
     fn max_content_chars(&self) -> Option<usize> {
         self.max_content_chars
     }
+
+    fn estimate_tokens(&self, text: &str) -> usize {
+        match bpe_for_model(&self.model) {
+            Some(bpe) => bpe.encode_ordinary(text).len(),
+            None => crate::tokens::token_count(text),
+        }
+    }
 }
 
 #[cfg(test)]
@@ -839,4 +859,74 @@ mod tests {
         assert_eq!(converted[1]["role"], "user");
         assert_eq!(converted[1]["content"], "thanks, now summarize");
     }
+
+    #[test]
+    fn estimate_tokens_uses_tiktoken_for_openai_models() {
+        let backend = |model: &str| OpenAiCompatBackend {
+            config_name: "test".into(),
+            model: model.into(),
+            api_key: String::new(),
+            base_url: String::new(),
+            client: reqwest::Client::new(),
+            max_content_chars: None,
+        };
+        let text = "git log --oneline | head -n 20 # show recent commits";
+        let gpt4o = tiktoken_rs::o200k_base().unwrap().encode_ordinary(text).len();
+        let gpt4 = tiktoken_rs::cl100k_base().unwrap().encode_ordinary(text).len();
+        assert_eq!(backend("gpt-4o").estimate_tokens(text), gpt4o);
+        assert_eq!(backend("gpt-4").estimate_tokens(text), gpt4);
+        // Models tiktoken doesn't know fall back to the heuristic count
+        assert_eq!(
+            backend("deepseek-chat").estimate_tokens(text),
+            crate::tokens::token_count(text)
+        );
+    }
+
+    #[tokio::test]
+    async fn complete_reports_usage_from_response() {
+        use crate::backend::{TriggerType, UseCase};
+        use wiremock::matchers::{method, path};
+        use wiremock::{Mock, MockServer, ResponseTemplate};
+
+        let server = MockServer::start().await;
+        Mock::given(method("POST"))
+            .and(path("/v1/chat/completions"))
+            .respond_with(ResponseTemplate::new(200).set_body_json(serde_json::json!({
+                "choices": [{"message": {"role": "assistant", "content": "Use -v."}, "finish_reason": "stop"}],
+                "usage": {"prompt_tokens": 42, "completion_tokens": 7, "total_tokens": 49},
+            })))
+            .expect(2)
+            .mount(&server)
+            .await;
+
+        let backend = OpenAiCompatBackend {
+            config_name: "test".into(),
+            model: "gpt-4".into(),
+            api_key: "sk-test".into(),
+            base_url: format!("{}/v1", server.uri()),
+            client: reqwest::Client::new(),
+            max_content_chars: None,
+        };
+        let req = LlmRequest {
+            context: String::new(),
+            query: Some("how do I see more output?".into()),
+            trigger: TriggerType::Manual,
+            session_ids: vec![],
+            use_case: UseCase::Analysis,
+            max_content_chars: None,
+            system_prompt: None,
+            enable_thinking: None,
+            tools: vec![],
+            extra_messages: vec![],
+        };
+
+        let mut total = (0, 0);
+        for _ in 0..2 {
+            let resp = backend.complete(&req).await.unwrap();
+            let usage = resp.usage.expect("usage should be parsed");
+            assert_eq!((usage.input_tokens, usage.output_tokens), (42, 7));
+            total = (total.0 + usage.input_tokens, total.1 + usage.output_tokens);
+        }
+        assert_eq!(total, (84, 14));
+    }
 }
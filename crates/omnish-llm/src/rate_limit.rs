@@ -0,0 +1,323 @@
+//! Client-side rate limiting for LLM API calls.
+//!
+//! A `RateLimiter` holds two token buckets (requests and tokens per minute)
+//! shared by every backend of a `MultiBackend`, so concurrent sessions cannot
+//! exhaust the provider's quota between them.
+
+use crate::backend::{LlmBackend, LlmRequest, LlmResponse, TextStream};
+use anyhow::Result;
+use async_trait::async_trait;
+use std::sync::Arc;
+use std::time::Duration;
+use tokio::sync::Mutex;
+use tokio::time::Instant;
+
+/// Returned (inside `anyhow::Error`) when a call would have to wait longer
+/// than the limiter's `max_wait`.
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct RateLimitError {
+    pub retry_after: Duration,
+}
+
+impl std::fmt::Display for RateLimitError {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        let secs = self.retry_after.as_secs_f64().ceil().max(1.0) as u64;
+        write!(f, "Rate limit reached, retry in {}s", secs)
+    }
+}
+
+impl std::error::Error for RateLimitError {}
+
+struct Bucket {
+    capacity: f64,
+    available: f64,
+    per_sec: f64,
+}
+
+impl Bucket {
+    /// `None` when `per_minute` is 0 (unlimited).
+    fn new(per_minute: u32) -> Option<Self> {
+        (per_minute > 0).then(|| Self {
+            capacity: per_minute as f64,
+            available: per_minute as f64,
+            per_sec: per_minute as f64 / 60.0,
+        })
+    }
+
+    fn refill(&mut self, elapsed: Duration) {
+        self.available = (self.available + elapsed.as_secs_f64() * self.per_sec).min(self.capacity);
+    }
+
+    /// Time until `cost` is available (zero if it already is).
+    fn wait_for(&self, cost: f64) -> Duration {
+        let cost = cost.min(self.capacity);
+        if self.available >= cost {
+            Duration::ZERO
+        } else {
+            Duration::from_secs_f64((cost - self.available) / self.per_sec)
+        }
+    }
+}
+
+struct Buckets {
+    requests: Option<Bucket>,
+    tokens: Option<Bucket>,
+    last_refill: Instant,
+}
+
+/// Token-bucket limiter over requests per minute and tokens per minute.
+pub struct RateLimiter {
+    buckets: Arc<Mutex<Buckets>>,
+    max_wait: Duration,
+    enabled: bool,
+}
+
+impl RateLimiter {
+    /// 0 disables the corresponding limit. Buckets start full.
+    pub fn new(requests_per_minute: u32, tokens_per_minute: u32) -> Self {
+        Self {
+            buckets: Arc::new(Mutex::new(Buckets {
+                requests: Bucket::new(requests_per_minute),
+                tokens: Bucket::new(tokens_per_minute),
+                last_refill: Instant::now(),
+            })),
+            max_wait: Duration::from_secs(10),
+            enabled: requests_per_minute > 0 || tokens_per_minute > 0,
+        }
+    }
+
+    /// Longest a caller may be delayed before `acquire` gives up.
+    pub fn with_max_wait(mut self, max_wait: Duration) -> Self {
+        self.max_wait = max_wait;
+        self
+    }
+
+    /// Whether any limit is configured.
+    pub fn is_enabled(&self) -> bool {
+        self.enabled
+    }
+
+    /// Take one request and `estimated_tokens` tokens, waiting for refill
+    /// when necessary. Fails with [`RateLimitError`] if the total wait
+    /// would exceed `max_wait`.
+    pub async fn acquire(&self, estimated_tokens: usize) -> Result<(), RateLimitError> {
+        let deadline = Instant::now() + self.max_wait;
+        loop {
+            let wait = {
+                let mut b = self.buckets.lock().await;
+                let now = Instant::now();
+                let elapsed = now.duration_since(b.last_refill);
+                b.last_refill = now;
+                if let Some(r) = b.requests.as_mut() {
+                    r.refill(elapsed);
+                }
+                if let Some(t) = b.tokens.as_mut() {
+                    t.refill(elapsed);
+                }
+
+                let cost = estimated_tokens as f64;
+                let wait = b
+                    .requests
+                    .as_ref()
+                    .map(|r| r.wait_for(1.0))
+                    .unwrap_or_default()
+                    .max(b.tokens.as_ref().map(|t| t.wait_for(cost)).unwrap_or_default());
+                if wait.is_zero() {
+                    if let Some(r) = b.requests.as_mut() {
+                        r.available -= 1.0;
+                    }
+                    if let Some(t) = b.tokens.as_mut() {
+                        t.available -= cost.min(t.capacity);
+                    }
+                    return Ok(());
+                }
+                wait
+            };
+            if Instant::now() + wait > deadline {
+                return Err(RateLimitError { retry_after: wait });
+            }
+            tokio::time::sleep(wait).await;
+        }
+    }
+
+    /// Give back what `acquire(estimated_tokens)` took, for a call that was
+    /// never made.
+    pub async fn release(&self, estimated_tokens: usize) {
+        let mut b = self.buckets.lock().await;
+        if let Some(r) = b.requests.as_mut() {
+            r.available = (r.available + 1.0).min(r.capacity);
+        }
+        if let Some(t) = b.tokens.as_mut() {
+            t.available = (t.available + estimated_tokens as f64).min(t.capacity);
+        }
+    }
+}
+
+/// Backend wrapper that acquires from a shared [`RateLimiter`] before
+/// every call.
+pub struct RateLimitedBackend {
+    inner: Arc<dyn LlmBackend>,
+    limiter: Arc<RateLimiter>,
+}
+
+impl RateLimitedBackend {
+    pub fn wrap(inner: Arc<dyn LlmBackend>, limiter: Arc<RateLimiter>) -> Arc<dyn LlmBackend> {
+        Arc::new(Self { inner, limiter })
+    }
+
+    fn estimate(&self, req: &LlmRequest) -> usize {
+        let mut tokens = req
+            .system_prompt
+            .as_ref()
+            .map(|s| self.inner.estimate_tokens(&s.text))
+            .unwrap_or(0);
+        if req.extra_messages.is_empty() {
+            tokens += self.inner.estimate_tokens(&req.context);
+            tokens += req.query.as_deref().map(|q| self.inner.estimate_tokens(q)).unwrap_or(0);
+        } else {
+            for m in &req.extra_messages {
+                tokens += self.inner.estimate_tokens(&m.content.to_string());
+            }
+        }
+        tokens
+    }
+}
+
+#[async_trait]
+impl LlmBackend for RateLimitedBackend {
+    async fn complete(&self, req: &LlmRequest) -> Result<LlmResponse> {
+        self.limiter.acquire(self.estimate(req)).await?;
+        self.inner.complete(req).await
+    }
+
+    async fn stream(&self, req: &LlmRequest) -> Option<Result<TextStream>> {
+        let estimate = self.estimate(req);
+        if let Err(e) = self.limiter.acquire(estimate).await {
+            return Some(Err(e.into()));
+        }
+        let stream = self.inner.stream(req).await;
+        if stream.is_none() {
+            // The caller falls back to `complete`, which takes its own permit
+            self.limiter.release(estimate).await;
+        }
+        stream
+    }
+
+    fn name(&self) -> &str {
+        self.inner.name()
+    }
+
+    fn max_content_chars(&self) -> Option<usize> {
+        self.inner.max_content_chars()
+    }
+
+    fn estimate_tokens(&self, text: &str) -> usize {
+        self.inner.estimate_tokens(text)
+    }
+
+    fn model_name(&self) -> &str {
+        self.inner.model_name()
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[tokio::test(start_paused = true)]
+    async fn test_requests_per_minute_waits_for_refill() {
+        let limiter = RateLimiter::new(2, 0).with_max_wait(Duration::from_secs(60));
+        let start = Instant::now();
+        limiter.acquire(0).await.unwrap();
+        limiter.acquire(0).await.unwrap();
+        assert_eq!(start.elapsed(), Duration::ZERO);
+        // Third request needs half a minute of refill at 2/min
+        limiter.acquire(0).await.unwrap();
+        assert!(start.elapsed() >= Duration::from_secs(30));
+    }
+
+    #[tokio::test(start_paused = true)]
+    async fn test_exceeding_max_wait_returns_error() {
+        let limiter = RateLimiter::new(0, 600).with_max_wait(Duration::from_secs(5));
+        limiter.acquire(600).await.unwrap();
+        // 600 tokens/min refills 10 tokens/s; 100 tokens need 10s > 5s
+        let err = limiter.acquire(100).await.unwrap_err();
+        assert_eq!(err.retry_after, Duration::from_secs(10));
+        assert_eq!(err.to_string(), "Rate limit reached, retry in 10s");
+        // Within max_wait the call succeeds after sleeping
+        let start = Instant::now();
+        limiter.acquire(40).await.unwrap();
+        assert!(start.elapsed() >= Duration::from_secs(4));
+    }
+
+    #[tokio::test(start_paused = true)]
+    async fn test_shared_across_tasks() {
+        let limiter = Arc::new(RateLimiter::new(3, 0).with_max_wait(Duration::ZERO));
+        let mut handles = Vec::new();
+        for _ in 0..5 {
+            let l = limiter.clone();
+            handles.push(tokio::spawn(async move { l.acquire(0).await.is_ok() }));
+        }
+        let mut ok = 0;
+        for h in handles {
+            if h.await.unwrap() {
+                ok += 1;
+            }
+        }
+        assert_eq!(ok, 3);
+    }
+
+    struct NoStream;
+
+    #[async_trait]
+    impl LlmBackend for NoStream {
+        async fn complete(&self, _req: &LlmRequest) -> Result<LlmResponse> {
+            Ok(LlmResponse {
+                content: vec![],
+                stop_reason: crate::backend::StopReason::EndTurn,
+                model: "none".into(),
+                usage: None,
+            })
+        }
+
+        fn name(&self) -> &str {
+            "none"
+        }
+
+        fn model_name(&self) -> &str {
+            "none"
+        }
+    }
+
+    #[tokio::test(start_paused = true)]
+    async fn test_stream_fallback_takes_one_permit() {
+        let limiter = Arc::new(RateLimiter::new(1, 0).with_max_wait(Duration::ZERO));
+        assert!(limiter.is_enabled());
+        let backend = RateLimitedBackend::wrap(Arc::new(NoStream), limiter);
+        let req = LlmRequest {
+            context: String::new(),
+            query: Some("hi".into()),
+            trigger: crate::backend::TriggerType::Manual,
+            session_ids: vec![],
+            use_case: crate::backend::UseCase::Chat,
+            max_content_chars: None,
+            system_prompt: None,
+            enable_thinking: None,
+            tools: vec![],
+            extra_messages: vec![],
+        };
+        // No streaming support: the caller falls back to complete
+        assert!(backend.stream(&req).await.is_none());
+        backend.complete(&req).await.unwrap();
+        assert!(backend.complete(&req).await.is_err());
+    }
+
+    #[tokio::test(start_paused = true)]
+    async fn test_unlimited_never_waits() {
+        let limiter = RateLimiter::new(0, 0).with_max_wait(Duration::ZERO);
+        assert!(!limiter.is_enabled());
+        for _ in 0..100 {
+            limiter.acquire(1_000_000).await.unwrap();
+        }
+    }
+}
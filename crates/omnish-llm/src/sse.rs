@@ -0,0 +1,67 @@
+//! Minimal Server-Sent Events decoder for streaming LLM APIs.
+//!
+//! Only `data:` fields are surfaced; `event:`, `id:` and comment lines are
+//! ignored since both Anthropic and OpenAI repeat the event type inside the
+//! JSON payload.
+
+/// Incremental SSE decoder. Feed raw body chunks with [`SseDecoder::push`]
+/// and collect the `data:` payload of each completed event.
+#[derive(Default)]
+pub struct SseDecoder {
+    buf: Vec<u8>,
+    data: Vec<String>,
+}
+
+impl SseDecoder {
+    pub fn new() -> Self {
+        Self::default()
+    }
+
+    /// Append bytes and return the payloads of any events completed by them.
+    /// Multi-line `data:` fields are joined with `\n` as per the spec.
+    pub fn push(&mut self, bytes: &[u8]) -> Vec<String> {
+        self.buf.extend_from_slice(bytes);
+        let mut events = Vec::new();
+        while let Some(pos) = self.buf.iter().position(|&b| b == b'\n') {
+            let line: Vec<u8> = self.buf.drain(..=pos).collect();
+            let line = String::from_utf8_lossy(&line);
+            let line = line.trim_end_matches(['\n', '\r']);
+            if line.is_empty() {
+                if !self.data.is_empty() {
+                    events.push(self.data.join("\n"));
+                    self.data.clear();
+                }
+            } else if let Some(rest) = line.strip_prefix("data:") {
+                self.data.push(rest.strip_prefix(' ').unwrap_or(rest).to_string());
+            }
+        }
+        events
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_single_event() {
+        let mut d = SseDecoder::new();
+        let events = d.push(b"event: ping\ndata: {\"a\":1}\n\n");
+        assert_eq!(events, vec!["{\"a\":1}".to_string()]);
+    }
+
+    #[test]
+    fn test_event_split_across_chunks() {
+        let mut d = SseDecoder::new();
+        assert!(d.push(b"data: hel").is_empty());
+        assert!(d.push(b"lo\r\n").is_empty());
+        assert_eq!(d.push(b"\r\ndata: x\n\n"), vec!["hello".to_string(), "x".to_string()]);
+    }
+
+    #[test]
+    fn test_multiline_data_and_comments() {
+        let mut d = SseDecoder::new();
+        let events = d.push(b": keepalive\ndata: a\ndata: b\n\n");
+        assert_eq!(events, vec!["a\nb".to_string()]);
+    }
+}
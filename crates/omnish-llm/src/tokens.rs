@@ -0,0 +1,137 @@
+//! Lightweight token count estimation.
+//!
+//! Backends with a known tokenizer count exactly through
+//! `LlmBackend::estimate_tokens` (OpenAI models use tiktoken). Everything
+//! else falls back to an approximation that tracks BPE tokenizers closely
+//! enough for deciding how many commands fit: runs of ASCII letters/digits cost roughly
+//! one token per four characters, every punctuation or symbol character costs
+//! one token, whitespace is free, and each non-ASCII character (CJK, emoji)
+//! costs one token.
+
+use std::sync::Arc;
+
+use crate::backend::LlmBackend;
+
+/// Estimate the number of tokens `text` occupies in an LLM prompt.
+pub fn token_count(text: &str) -> usize {
+    let mut tokens = 0;
+    let mut word_len: usize = 0;
+    for c in text.chars() {
+        if c.is_ascii_alphanumeric() || c == '_' {
+            word_len += 1;
+            continue;
+        }
+        tokens += word_len.div_ceil(4);
+        word_len = 0;
+        if !c.is_whitespace() {
+            tokens += 1;
+        }
+    }
+    tokens + word_len.div_ceil(4)
+}
+
+/// Convert a character budget into an approximate token budget.
+///
+/// Mirrors the `context_window * 3 / 2` rule used to derive
+/// `max_content_chars` from `context_window`.
+pub fn chars_to_tokens(chars: usize) -> usize {
+    chars * 2 / 3
+}
+
+/// Tokens of a `context_window`-token model held back for the system
+/// prompt, the query and the response.
+pub fn reserved_tokens(context_window: usize) -> usize {
+    context_window / 4
+}
+
+/// Tokens of a `context_window`-token model left for terminal context.
+pub fn context_budget(context_window: usize) -> usize {
+    context_window - reserved_tokens(context_window)
+}
+
+/// A limit on context size in tokens, counted with the tokenizer of the
+/// backend the context is sent to.
+#[derive(Clone)]
+pub struct TokenBudget {
+    pub max_tokens: usize,
+    backend: Option<Arc<dyn LlmBackend>>,
+}
+
+impl TokenBudget {
+    /// `max_tokens`, counted with `token_count`.
+    pub fn new(max_tokens: usize) -> Self {
+        Self { max_tokens, backend: None }
+    }
+
+    /// `max_tokens`, counted with `backend.estimate_tokens`.
+    pub fn for_backend(max_tokens: usize, backend: Arc<dyn LlmBackend>) -> Self {
+        Self { max_tokens, backend: Some(backend) }
+    }
+
+    /// Tokens `text` costs under this budget's tokenizer.
+    pub fn count(&self, text: &str) -> usize {
+        match &self.backend {
+            Some(backend) => backend.estimate_tokens(text),
+            None => token_count(text),
+        }
+    }
+
+    /// Whether `text` fits in the budget.
+    pub fn fits(&self, text: &str) -> bool {
+        self.count(text) <= self.max_tokens
+    }
+
+    /// Model whose tokenizer counts for this budget; empty for `token_count`.
+    pub fn tokenizer(&self) -> &str {
+        self.backend.as_ref().map(|b| b.model_name()).unwrap_or("")
+    }
+}
+
+impl std::fmt::Debug for TokenBudget {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        f.debug_struct("TokenBudget")
+            .field("max_tokens", &self.max_tokens)
+            .field("tokenizer", &self.tokenizer())
+            .finish()
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_empty_and_whitespace() {
+        assert_eq!(token_count(""), 0);
+        assert_eq!(token_count("   \n\t"), 0);
+    }
+
+    #[test]
+    fn test_words_and_punctuation() {
+        // "git" -> 1, "status" -> 2
+        assert_eq!(token_count("git status"), 3);
+        // "ls" -> 1, "-" -> 1, "la" -> 1
+        assert_eq!(token_count("ls -la"), 3);
+        // "cargo" -> 2, "build" -> 2, "--" -> 2, "release" -> 2
+        assert_eq!(token_count("cargo build --release"), 8);
+    }
+
+    #[test]
+    fn test_non_ascii_counts_per_char() {
+        assert_eq!(token_count("你好"), 2);
+        assert_eq!(token_count("echo 你好"), 3);
+    }
+
+    #[test]
+    fn test_context_budget_leaves_room_for_prompt_and_response() {
+        assert_eq!(context_budget(200_000), 150_000);
+        assert_eq!(context_budget(200_000) + reserved_tokens(200_000), 200_000);
+        assert_eq!(context_budget(0), 0);
+    }
+
+    #[test]
+    fn test_fewer_tokens_than_chars_for_prose() {
+        let text = "the quick brown fox jumps over the lazy dog";
+        assert!(token_count(text) < text.chars().count() / 2);
+    }
+}
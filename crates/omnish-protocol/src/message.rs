@@ -5,7 +5,7 @@ use std::collections::HashMap;
 const MAGIC: [u8; 2] = [0x4F, 0x53]; // "OS" for OmniSh
 
 /// Protocol version - increment on any wire format change.
-pub const PROTOCOL_VERSION: u32 = 25;
+pub const PROTOCOL_VERSION: u32 = 41;
 
 /// Minimum protocol version this build can interoperate with.
 ///
@@ -13,7 +13,7 @@ pub const PROTOCOL_VERSION: u32 = 25;
 /// - Breaking changes (modified existing variant fields): bump both to the same value.
 ///
 /// Server auth accepts peers whose `protocol_version >= MIN_COMPATIBLE_VERSION`.
-pub const MIN_COMPATIBLE_VERSION: u32 = 25;
+pub const MIN_COMPATIBLE_VERSION: u32 = 41;
 
 #[derive(Debug, Clone, Serialize, Deserialize)]
 pub struct ConfigItem {
@@ -158,6 +158,66 @@ pub enum Message {
     /// daemon responds with a stream of `UpdateChunk` messages carrying the
     /// tarball bytes, same shape as the binary update path.
     PluginSyncRequest { hostname: String },
+    /// Daemon -> client (streaming): a piece of an LLM answer to `Request`,
+    /// sent as the backend produces it. The last chunk has `done = true`
+    /// and is followed by the usual final `Response` carrying the full text.
+    StreamingChunk {
+        request_id: String,
+        chunk: String,
+        done: bool,
+    },
+    /// Client -> daemon: render `session_id` in `format`. Answered with
+    /// `ExportResult`; the client writes the file. PROTOCOL_VERSION 28,
+    /// fields changed in 41.
+    ExportRequest {
+        session_id: String,
+        format: ExportFormat,
+    },
+    /// Response to `ExportRequest` with the rendered document. `error` is
+    /// set when the export failed.
+    ExportResult {
+        content: String,
+        command_count: u32,
+        error: Option<String>,
+    },
+    /// Client -> daemon: liveness probe, answered with `HealthStatus`.
+    /// PROTOCOL_VERSION 31.
+    HealthCheck,
+    /// Response to `HealthCheck`. `llm_available` is false when no LLM
+    /// backend is configured; `stream_write_errors` counts failed stream.bin
+    /// writes since the daemon started.
+    HealthStatus {
+        uptime_secs: u64,
+        session_count: usize,
+        llm_available: bool,
+        stream_write_errors: u64,
+    },
+    /// Client -> daemon: several messages packed into one frame (bursts of
+    /// `IoData`). The daemon handles them in order and answers the batch
+    /// with a single `BatchResponse`. PROTOCOL_VERSION 33.
+    Batch { messages: Vec<Message> },
+    /// Client -> daemon: keepalive probe sent by `RpcClient` on idle
+    /// connections. Answered by the transport layer with `Pong`.
+    /// PROTOCOL_VERSION 34.
+    Ping { timestamp_ms: u64 },
+    /// Response to `Ping`, echoing its `timestamp_ms`.
+    Pong { timestamp_ms: u64 },
+    /// Daemon -> client: answer to `Batch`, one result per inner message in
+    /// the same order. A failed message does not stop the ones after it.
+    /// PROTOCOL_VERSION 37.
+    BatchResponse { results: Vec<BatchResult> },
+}
+
+/// Outcome of one message of a `Batch`: its reply, or why it failed.
+#[derive(Debug, Clone, Serialize, Deserialize)]
+pub enum BatchResult {
+    Ok(Box<Message>),
+    Err(String),
+}
+
+#[derive(Debug, Clone, Copy, Serialize, Deserialize, PartialEq, Eq)]
+pub enum ExportFormat {
+    Markdown,
 }
 
 #[derive(Debug, Clone, Serialize, Deserialize, PartialEq, Eq)]
@@ -190,6 +250,29 @@ pub fn versions_compatible(my_min: u32, peer_version: u32) -> bool {
     peer_version >= my_min
 }
 
+/// Error text for a peer at protocol `actual` talking to one at `expected`.
+/// Version 0 is what builds from before `Auth::protocol_version` send.
+pub fn version_mismatch_message(expected: u32, actual: u32) -> String {
+    if actual == 0 {
+        format!(
+            "protocol version mismatch: expected {}, got 0 (peer predates protocol versioning, upgrade it)",
+            expected
+        )
+    } else {
+        format!("protocol version mismatch: expected {}, got {}", expected, actual)
+    }
+}
+
+/// Check a peer's protocol version from the Auth exchange against this
+/// build's `MIN_COMPATIBLE_VERSION`.
+pub fn check_protocol_version(peer_version: u32) -> Result<()> {
+    if versions_compatible(MIN_COMPATIBLE_VERSION, peer_version) {
+        Ok(())
+    } else {
+        bail!("{}", version_mismatch_message(PROTOCOL_VERSION, peer_version))
+    }
+}
+
 #[derive(Debug, Clone, Serialize, Deserialize)]
 pub struct SessionStart {
     pub session_id: String,
@@ -249,6 +332,18 @@ pub struct Request {
     pub session_id: String,
     pub query: String,
     pub scope: RequestScope,
+    /// Backend name (as configured in daemon.toml) to answer this query
+    /// with instead of the chat default. Only affects LLM queries.
+    #[serde(default)]
+    pub model_override: Option<String>,
+    /// The client stops waiting after this many milliseconds; the daemon
+    /// cancels the LLM call at the same deadline. PROTOCOL_VERSION 35.
+    #[serde(default)]
+    pub timeout_ms: Option<u64>,
+    /// Context layout for this query (`grouped`, `interleaved` or `xml`),
+    /// overriding the daemon's `context.format`. PROTOCOL_VERSION 38.
+    #[serde(default)]
+    pub format_hint: Option<String>,
 }
 
 #[derive(Debug, Clone, Serialize, Deserialize)]
@@ -256,6 +351,8 @@ pub enum RequestScope {
     CurrentSession,
     AllSessions,
     Sessions(Vec<String>),
+    /// Every session whose `hostname` attr matches. PROTOCOL_VERSION 39.
+    HostSessions(String),
 }
 
 #[derive(Debug, Clone, Serialize, Deserialize)]
@@ -532,6 +629,73 @@ mod tests {
         assert!(matches!(decoded.payload, Message::Ack));
     }
 
+    #[test]
+    fn test_check_protocol_version() {
+        assert!(check_protocol_version(PROTOCOL_VERSION).is_ok());
+        assert!(check_protocol_version(PROTOCOL_VERSION + 1).is_ok());
+
+        let err = check_protocol_version(MIN_COMPATIBLE_VERSION - 1).unwrap_err().to_string();
+        assert_eq!(
+            err,
+            format!("protocol version mismatch: expected {}, got {}", PROTOCOL_VERSION, MIN_COMPATIBLE_VERSION - 1)
+        );
+        let err = check_protocol_version(0).unwrap_err().to_string();
+        assert!(err.starts_with(&format!("protocol version mismatch: expected {}, got 0", PROTOCOL_VERSION)), "{err}");
+        assert!(err.contains("upgrade"), "{err}");
+    }
+
+    #[test]
+    fn test_frame_starts_with_request_id() {
+        // Older peers must be able to decode the Auth exchange, so the
+        // frame layout stays request_id + payload; versioning is done by
+        // `Auth::protocol_version`.
+        let bytes = Frame { request_id: 7, payload: Message::Ack }.to_bytes().unwrap();
+        assert_eq!(bytes[..8], 7u64.to_be_bytes());
+        assert_eq!(bytes[8..10], MAGIC);
+    }
+
+    #[test]
+    fn test_frame_with_batch() {
+        let io = |data: &[u8]| Message::IoData(IoData {
+            session_id: "s1".into(),
+            direction: IoDirection::Output,
+            timestamp_ms: 1,
+            data: data.to_vec(),
+        });
+        let frame = Frame {
+            request_id: 7,
+            payload: Message::Batch { messages: vec![io(b"a"), io(b"b")] },
+        };
+        let decoded = Frame::from_bytes(&frame.to_bytes().unwrap()).unwrap();
+        match decoded.payload {
+            Message::Batch { messages } => {
+                let data: Vec<Vec<u8>> = messages
+                    .into_iter()
+                    .map(|m| match m {
+                        Message::IoData(io) => io.data,
+                        other => panic!("expected IoData, got {:?}", other),
+                    })
+                    .collect();
+                assert_eq!(data, vec![b"a".to_vec(), b"b".to_vec()]);
+            }
+            other => panic!("expected Batch, got {:?}", other),
+        }
+
+        let frame = Frame {
+            request_id: 7,
+            payload: Message::BatchResponse {
+                results: vec![BatchResult::Ok(Box::new(Message::Ack)), BatchResult::Err("bad".into())],
+            },
+        };
+        match Frame::from_bytes(&frame.to_bytes().unwrap()).unwrap().payload {
+            Message::BatchResponse { results } => {
+                assert!(matches!(&results[0], BatchResult::Ok(m) if matches!(**m, Message::Ack)));
+                assert!(matches!(&results[1], BatchResult::Err(e) if e == "bad"));
+            }
+            other => panic!("expected BatchResponse, got {:?}", other),
+        }
+    }
+
     #[test]
     fn test_frame_with_session_start() {
         let frame = Frame {
@@ -704,7 +868,7 @@ mod tests {
     /// reminding you to bump PROTOCOL_VERSION if the wire format changed.
     #[test]
     fn message_variant_guard() {
-        const EXPECTED_VARIANT_COUNT: usize = 37;
+        const EXPECTED_VARIANT_COUNT: usize = 46;
 
         let variants: Vec<Message> = vec![
             Message::SessionStart(SessionStart {
@@ -739,6 +903,9 @@ mod tests {
                 session_id: String::new(),
                 query: String::new(),
                 scope: RequestScope::CurrentSession,
+                model_override: None,
+                timeout_ms: None,
+                format_hint: None,
             }),
             Message::Response(Response {
                 request_id: String::new(),
@@ -759,6 +926,9 @@ mod tests {
                     stream_offset: 0,
                     stream_length: 0,
                     exit_code: None,
+                    exit_signal: None,
+                    checksum: None,
+                    tags: Vec::new(),
                 },
             }),
             Message::CompletionRequest(CompletionRequest {
@@ -880,6 +1050,15 @@ mod tests {
             Message::PluginSyncCheck { current_checksum: String::new(), hostname: String::new() },
             Message::PluginSyncInfo { checksum: String::new(), available: false, total_size: 0 },
             Message::PluginSyncRequest { hostname: String::new() },
+            Message::StreamingChunk { request_id: String::new(), chunk: String::new(), done: false },
+            Message::ExportRequest { session_id: String::new(), format: ExportFormat::Markdown },
+            Message::ExportResult { content: String::new(), command_count: 0, error: None },
+            Message::HealthCheck,
+            Message::HealthStatus { uptime_secs: 0, session_count: 0, llm_available: false, stream_write_errors: 0 },
+            Message::Batch { messages: vec![] },
+            Message::Ping { timestamp_ms: 0 },
+            Message::Pong { timestamp_ms: 0 },
+            Message::BatchResponse { results: vec![] },
         ];
 
         // Exhaustive match - no wildcard. Compiler will error if a variant is missing.
@@ -921,7 +1100,16 @@ mod tests {
                 | Message::NoticePush { .. }
                 | Message::PluginSyncCheck { .. }
                 | Message::PluginSyncInfo { .. }
-                | Message::PluginSyncRequest { .. } => {}
+                | Message::PluginSyncRequest { .. }
+                | Message::StreamingChunk { .. }
+                | Message::ExportRequest { .. }
+                | Message::ExportResult { .. }
+                | Message::HealthCheck
+                | Message::HealthStatus { .. }
+                | Message::Batch { .. }
+                | Message::Ping { .. }
+                | Message::Pong { .. }
+                | Message::BatchResponse { .. } => {}
             }
         }
 
@@ -963,6 +1151,14 @@ mod tests {
         assert_eq!(variant_index(&Message::PluginSyncCheck { current_checksum: String::new(), hostname: String::new() }), 34, "PluginSyncCheck index shifted");
         assert_eq!(variant_index(&Message::PluginSyncInfo { checksum: String::new(), available: false, total_size: 0 }), 35, "PluginSyncInfo index shifted");
         assert_eq!(variant_index(&Message::PluginSyncRequest { hostname: String::new() }), 36, "PluginSyncRequest index shifted");
+        assert_eq!(variant_index(&Message::StreamingChunk { request_id: String::new(), chunk: String::new(), done: false }), 37, "StreamingChunk index shifted");
+        assert_eq!(variant_index(&Message::ExportRequest { session_id: String::new(), format: ExportFormat::Markdown }), 38, "ExportRequest index shifted");
+        assert_eq!(variant_index(&Message::ExportResult { content: String::new(), command_count: 0, error: None }), 39, "ExportResult index shifted");
+        assert_eq!(variant_index(&Message::HealthCheck), 40, "HealthCheck index shifted");
+        assert_eq!(variant_index(&Message::Batch { messages: vec![] }), 42, "Batch index shifted");
+        assert_eq!(variant_index(&Message::Ping { timestamp_ms: 0 }), 43, "Ping index shifted");
+        assert_eq!(variant_index(&Message::Pong { timestamp_ms: 0 }), 44, "Pong index shifted");
+        assert_eq!(variant_index(&Message::BatchResponse { results: vec![] }), 45, "BatchResponse index shifted");
     }
 
     /// Regression test: ChatReady with populated history must survive a bincode round-trip.
@@ -0,0 +1,29 @@
+use std::io::Write;
+use std::path::{Path, PathBuf};
+
+/// `<path>.tmp`, the staging file `write_atomic` renames over `path`.
+pub fn tmp_path(path: &Path) -> PathBuf {
+    let mut tmp = path.as_os_str().to_owned();
+    tmp.push(".tmp");
+    PathBuf::from(tmp)
+}
+
+/// Write `contents` to `<path>.tmp`, sync it, then rename it over `path`.
+/// On POSIX filesystems readers see either the old file or the new one,
+/// never a partial write.
+pub fn write_atomic(path: &Path, contents: &[u8]) -> std::io::Result<()> {
+    let tmp = tmp_path(path);
+    let mut file = std::fs::File::create(&tmp)?;
+    file.write_all(contents)?;
+    file.sync_data()?;
+    std::fs::rename(&tmp, path)
+}
+
+/// Write `path` with `write_atomic`, or in place when `atomic` is false.
+pub fn write(path: &Path, contents: &[u8], atomic: bool) -> std::io::Result<()> {
+    if atomic {
+        write_atomic(path, contents)
+    } else {
+        std::fs::write(path, contents)
+    }
+}
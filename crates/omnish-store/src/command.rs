@@ -15,13 +15,51 @@ pub struct CommandRecord {
     pub stream_length: u64,
     #[serde(default)]
     pub exit_code: Option<i32>,
+    /// Signal that terminated the command, when the shell reported a
+    /// negative exit code (`-9` -> `Some(9)`).
+    #[serde(default)]
+    pub exit_signal: Option<i32>,
+    /// CRC32 over the identifying fields, set by the daemon when the record
+    /// is stored. `None` for records written before checksums existed.
+    #[serde(default)]
+    pub checksum: Option<u32>,
+    /// User labels added with `/tag`. Not covered by the checksum.
+    #[serde(default)]
+    pub tags: Vec<String>,
 }
 
 impl CommandRecord {
+    /// CRC32 of `command_id`, `command_line` and `exit_code`.
+    pub fn compute_checksum(&self) -> u32 {
+        let input = format!(
+            "{}{}{:?}",
+            self.command_id,
+            self.command_line.as_deref().unwrap_or(""),
+            self.exit_code
+        );
+        crc32fast::hash(input.as_bytes())
+    }
+
+    /// Time from start to end, `None` while the command has not finished.
+    pub fn duration_ms(&self) -> Option<u64> {
+        self.ended_at.map(|end| end.saturating_sub(self.started_at))
+    }
+
+    /// True if the stored checksum matches, or if there is none to check.
+    pub fn verify_checksum(&self) -> bool {
+        self.checksum.is_none_or(|c| c == self.compute_checksum())
+    }
+
+    /// Write `<dir>/commands.json` atomically (see `atomic::write_atomic`).
     pub fn save_all(records: &[CommandRecord], dir: &Path) -> Result<()> {
+        Self::save_all_with(records, dir, true)
+    }
+
+    /// Like `save_all`; `atomic: false` writes the file in place.
+    pub fn save_all_with(records: &[CommandRecord], dir: &Path, atomic: bool) -> Result<()> {
         let path = dir.join("commands.json");
         let json = serde_json::to_string_pretty(records)?;
-        std::fs::write(path, json)?;
+        crate::atomic::write(&path, json.as_bytes(), atomic)?;
         Ok(())
     }
 
@@ -1,6 +1,10 @@
+pub mod atomic;
 pub mod command;
 pub mod completion;
+pub mod query_log;
+pub mod redact;
 pub mod sample;
 pub mod session;
 pub mod session_update;
 pub mod stream;
+pub mod usage;
@@ -0,0 +1,156 @@
+use std::io::{BufRead, BufReader, Write};
+use std::path::{Path, PathBuf};
+use std::sync::mpsc;
+use std::thread;
+
+use serde::{Deserialize, Serialize};
+
+/// One answered LLM query, appended to daily-rotated JSONL files.
+#[derive(Debug, Clone, PartialEq, Serialize, Deserialize)]
+pub struct QueryRecord {
+    pub request_id: String,
+    pub session_id: String,
+    pub query: String,
+    pub response: String,
+    /// Model that produced the response.
+    pub model: String,
+    pub latency_ms: u64,
+    /// When the response completed (epoch ms).
+    pub timestamp_ms: u64,
+}
+
+/// Spawn a writer thread that writes QueryRecords to `<dir>/<date>.jsonl`.
+pub fn spawn_query_writer_thread(dir: PathBuf) -> mpsc::Sender<QueryRecord> {
+    spawn_jsonl_writer_thread(
+        dir,
+        |_: &QueryRecord| chrono::Local::now().format("%Y-%m-%d").to_string(),
+        "query log",
+    )
+}
+
+/// Spawn a writer thread that appends each record as a JSON line to
+/// `<dir>/<file_stem(record)>.jsonl`. `what` names the log in errors.
+pub fn spawn_jsonl_writer_thread<T>(
+    dir: PathBuf,
+    file_stem: fn(&T) -> String,
+    what: &'static str,
+) -> mpsc::Sender<T>
+where
+    T: Serialize + Send + 'static,
+{
+    let (tx, rx): (mpsc::Sender<T>, mpsc::Receiver<T>) = mpsc::channel();
+
+    thread::spawn(move || {
+        std::fs::create_dir_all(&dir).ok();
+        let mut current_stem = String::new();
+        let mut writer: Option<Box<dyn std::io::Write + Send>> = None;
+
+        loop {
+            match rx.try_recv() {
+                Ok(record) => {
+                    let stem = file_stem(&record);
+                    if stem != current_stem {
+                        current_stem = stem;
+                        writer = None;
+                    }
+                    if writer.is_none() {
+                        let path = dir.join(format!("{}.jsonl", current_stem));
+                        match std::fs::OpenOptions::new()
+                            .create(true)
+                            .append(true)
+                            .open(&path)
+                        {
+                            Ok(file) => writer = Some(Box::new(file)),
+                            Err(e) => {
+                                tracing::error!("Failed to open {} file: {}", what, e);
+                                continue;
+                            }
+                        }
+                    }
+                    if let Some(ref mut w) = writer {
+                        if let Ok(json) = serde_json::to_string(&record) {
+                            let _ = writeln!(w, "{}", json);
+                            let _ = w.flush();
+                        }
+                    }
+                }
+                Err(mpsc::TryRecvError::Empty) => {
+                    thread::sleep(std::time::Duration::from_millis(10));
+                }
+                Err(mpsc::TryRecvError::Disconnected) => break,
+            }
+        }
+    });
+
+    tx
+}
+
+/// Load the last `limit` records of `session_id` from the JSONL files in
+/// `dir`, oldest first. Unparseable lines are skipped.
+pub fn load_query_records(dir: &Path, session_id: &str, limit: usize) -> Vec<QueryRecord> {
+    let Ok(entries) = std::fs::read_dir(dir) else {
+        return Vec::new();
+    };
+    let mut files: Vec<PathBuf> = entries
+        .filter_map(|e| e.ok().map(|e| e.path()))
+        .filter(|p| p.extension().is_some_and(|ext| ext == "jsonl"))
+        .collect();
+    files.sort();
+
+    let mut records: Vec<QueryRecord> = Vec::new();
+    for path in files {
+        let Ok(file) = std::fs::File::open(&path) else { continue };
+        for line in BufReader::new(file).lines().map_while(Result::ok) {
+            match serde_json::from_str::<QueryRecord>(&line) {
+                Ok(r) if r.session_id == session_id => records.push(r),
+                _ => {}
+            }
+        }
+    }
+    records.sort_by_key(|r| r.timestamp_ms);
+    let skip = records.len().saturating_sub(limit);
+    records.split_off(skip)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn record(session_id: &str, query: &str, timestamp_ms: u64) -> QueryRecord {
+        QueryRecord {
+            request_id: format!("r{}", timestamp_ms),
+            session_id: session_id.into(),
+            query: query.into(),
+            response: format!("answer to {}", query),
+            model: "mock".into(),
+            latency_ms: 42,
+            timestamp_ms,
+        }
+    }
+
+    #[test]
+    fn test_writer_then_load() {
+        let dir = tempfile::TempDir::new().unwrap();
+        let tx = spawn_query_writer_thread(dir.path().to_path_buf());
+        tx.send(record("s1", "first", 1)).unwrap();
+        tx.send(record("s2", "other", 2)).unwrap();
+        tx.send(record("s1", "second", 3)).unwrap();
+        thread::sleep(std::time::Duration::from_millis(100));
+        drop(tx);
+        thread::sleep(std::time::Duration::from_millis(50));
+
+        let all = load_query_records(dir.path(), "s1", 10);
+        assert_eq!(all, vec![record("s1", "first", 1), record("s1", "second", 3)]);
+        let last = load_query_records(dir.path(), "s1", 1);
+        assert_eq!(last[0].query, "second");
+    }
+
+    #[test]
+    fn test_load_skips_bad_lines_and_missing_dir() {
+        let dir = tempfile::TempDir::new().unwrap();
+        let good = serde_json::to_string(&record("s1", "ok", 5)).unwrap();
+        std::fs::write(dir.path().join("2026-01-01.jsonl"), format!("not json\n{}\n", good)).unwrap();
+        assert_eq!(load_query_records(dir.path(), "s1", 10).len(), 1);
+        assert!(load_query_records(&dir.path().join("missing"), "s1", 10).is_empty());
+    }
+}
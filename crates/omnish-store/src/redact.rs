@@ -0,0 +1,196 @@
+use anyhow::Result;
+use regex::bytes::Regex;
+use std::path::Path;
+use std::sync::{Arc, Mutex};
+
+/// Replacement written in place of every matched secret.
+pub const REDACTED: &[u8] = b"***REDACTED***";
+
+/// Number of raw bytes from previous chunks kept so patterns can match
+/// secrets split across two `read()` calls.
+const WINDOW: usize = 256;
+
+#[derive(serde::Deserialize, Default)]
+struct PatternsFile {
+    #[serde(default)]
+    patterns: Vec<String>,
+}
+
+/// Read `patterns = [...]` from a redact patterns TOML file.
+/// A missing file yields an empty list.
+pub fn load_patterns_file(path: &Path) -> Result<Vec<String>> {
+    if !path.exists() {
+        return Ok(Vec::new());
+    }
+    let content = std::fs::read_to_string(path)?;
+    let file: PatternsFile = toml::from_str(&content)?;
+    Ok(file.patterns)
+}
+
+/// Compile patterns, skipping (and logging) any that fail to parse.
+pub fn compile_patterns(patterns: &[String]) -> Vec<Regex> {
+    patterns
+        .iter()
+        .filter_map(|p| match Regex::new(p) {
+            Ok(re) => Some(re),
+            Err(e) => {
+                tracing::warn!("invalid redact pattern {:?}: {}", p, e);
+                None
+            }
+        })
+        .collect()
+}
+
+/// Return `text` with every match of `patterns` replaced by [`REDACTED`].
+/// For whole strings such as a command line; streamed output goes through
+/// [`SecretFilter`] instead.
+pub fn redact_text(patterns: &[Regex], text: &str) -> String {
+    let mut out = text.as_bytes().to_vec();
+    for re in patterns {
+        if let std::borrow::Cow::Owned(replaced) = re.replace_all(&out, regex::bytes::NoExpand(REDACTED)) {
+            out = replaced;
+        }
+    }
+    String::from_utf8_lossy(&out).into_owned()
+}
+
+/// Scrubs secrets out of a session's output stream before it is stored.
+///
+/// Keeps a window of the raw bytes already seen so a match that starts in
+/// an earlier chunk still redacts the part that lands in the current one.
+/// Bytes already written are never rewritten.
+pub struct SecretFilter {
+    patterns: Arc<Vec<Regex>>,
+    tail: Mutex<Vec<u8>>,
+}
+
+impl SecretFilter {
+    pub fn new(patterns: Arc<Vec<Regex>>) -> Self {
+        Self {
+            patterns,
+            tail: Mutex::new(Vec::new()),
+        }
+    }
+
+    pub fn is_empty(&self) -> bool {
+        self.patterns.is_empty()
+    }
+
+    /// Return `data` with every secret replaced by [`REDACTED`].
+    pub fn scrub(&self, data: &[u8]) -> Vec<u8> {
+        if self.patterns.is_empty() {
+            return data.to_vec();
+        }
+        let mut tail = self.tail.lock().unwrap_or_else(|e| e.into_inner());
+        let offset = tail.len();
+        let mut window = std::mem::take(&mut *tail);
+        window.extend_from_slice(data);
+
+        // Match ranges clipped to the current chunk, relative to `data`.
+        let mut ranges: Vec<(usize, usize)> = self
+            .patterns
+            .iter()
+            .flat_map(|re| re.find_iter(&window))
+            .filter(|m| m.end() > offset && m.end() > m.start())
+            .map(|m| (m.start().max(offset) - offset, m.end() - offset))
+            .collect();
+        ranges.sort_unstable();
+
+        let mut out = Vec::with_capacity(data.len());
+        let mut pos = 0;
+        for (start, end) in ranges {
+            if end <= pos {
+                continue;
+            }
+            if start >= pos {
+                out.extend_from_slice(&data[pos..start]);
+                out.extend_from_slice(REDACTED);
+            }
+            pos = end;
+        }
+        out.extend_from_slice(&data[pos..]);
+
+        let keep = window.len().saturating_sub(WINDOW);
+        *tail = window.split_off(keep);
+        out
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn filter(patterns: &[&str]) -> SecretFilter {
+        let patterns: Vec<String> = patterns.iter().map(|s| s.to_string()).collect();
+        SecretFilter::new(Arc::new(compile_patterns(&patterns)))
+    }
+
+    fn contains(haystack: &[u8], needle: &[u8]) -> bool {
+        haystack.windows(needle.len()).any(|w| w == needle)
+    }
+
+    #[test]
+    fn test_scrub_single_chunk() {
+        let f = filter(&[r"password=\w+"]);
+        assert_eq!(f.scrub(b"login password=hunter2 ok"), b"login ***REDACTED*** ok");
+        assert_eq!(f.scrub(b"nothing here"), b"nothing here");
+    }
+
+    #[test]
+    fn test_scrub_split_across_32_byte_chunks() {
+        // Split inside the key: "passwor" | "d=hunter2"
+        let text = format!("{:<25}password=hunter2 {:<22}", "x", "y");
+        assert_eq!(text.len(), 64);
+        let f = filter(&[r"password=\w+"]);
+        let mut out = f.scrub(&text.as_bytes()[..32]);
+        out.extend(f.scrub(&text.as_bytes()[32..]));
+        assert!(!contains(&out, b"hunter2"));
+        assert!(contains(&out, REDACTED));
+
+        // Split inside the value: "password=hun" | "ter2"
+        let text = format!("{:<20}password=hunter2 {:<27}", "x", "y");
+        assert_eq!(text.len(), 64);
+        let f = filter(&[r"password=\w+"]);
+        let mut out = f.scrub(&text.as_bytes()[..32]);
+        out.extend(f.scrub(&text.as_bytes()[32..]));
+        assert!(!contains(&out, b"hun"));
+        assert!(!contains(&out, b"ter2"));
+        assert!(out.ends_with(format!(" {:<27}", "y").as_bytes()));
+    }
+
+    #[test]
+    fn test_redact_text() {
+        let patterns = compile_patterns(&[r"password=\w+".to_string(), r"sk-[A-Za-z0-9]+".to_string()]);
+        assert_eq!(
+            redact_text(&patterns, "curl -u password=hunter2 -H 'key: sk-abc123'"),
+            "curl -u ***REDACTED*** -H 'key: ***REDACTED***'"
+        );
+        assert_eq!(redact_text(&patterns, "ls -la"), "ls -la");
+        assert_eq!(redact_text(&[], "password=hunter2"), "password=hunter2");
+    }
+
+    #[test]
+    fn test_no_patterns_passthrough() {
+        let f = filter(&[]);
+        assert!(f.is_empty());
+        assert_eq!(f.scrub(b"password=hunter2"), b"password=hunter2");
+    }
+
+    #[test]
+    fn test_invalid_pattern_skipped() {
+        let patterns = vec!["(".to_string(), r"token=\S+".to_string()];
+        assert_eq!(compile_patterns(&patterns).len(), 1);
+    }
+
+    #[test]
+    fn test_load_patterns_file() {
+        let dir = tempfile::tempdir().unwrap();
+        let path = dir.path().join("redact_patterns.toml");
+        assert!(load_patterns_file(&path).unwrap().is_empty());
+        std::fs::write(&path, "patterns = ['password=\\w+', 'sk-[A-Za-z0-9]+']\n").unwrap();
+        assert_eq!(
+            load_patterns_file(&path).unwrap(),
+            vec![r"password=\w+".to_string(), "sk-[A-Za-z0-9]+".to_string()]
+        );
+    }
+}
@@ -12,13 +12,25 @@ pub struct SessionMeta {
     pub ended_at: Option<String>,
     #[serde(default)]
     pub attrs: HashMap<String, String>,
+    /// User-defined labels, set with `/tag-session`.
+    #[serde(default)]
+    pub tags: Vec<String>,
+    /// User-defined note, set with `/describe-session`.
+    #[serde(default)]
+    pub description: Option<String>,
 }
 
 impl SessionMeta {
+    /// Write `<dir>/meta.json` atomically (see `atomic::write_atomic`).
     pub fn save(&self, dir: &Path) -> Result<()> {
+        self.save_with(dir, true)
+    }
+
+    /// Like `save`; `atomic: false` writes the file in place.
+    pub fn save_with(&self, dir: &Path, atomic: bool) -> Result<()> {
         let path = dir.join("meta.json");
         let json = serde_json::to_string_pretty(self)?;
-        std::fs::write(path, json)?;
+        crate::atomic::write(&path, json.as_bytes(), atomic)?;
         Ok(())
     }
 
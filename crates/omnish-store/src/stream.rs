@@ -1,12 +1,120 @@
+use aes_gcm::aead::{Aead, AeadCore, KeyInit, OsRng, Payload};
+use aes_gcm::{Aes256Gcm, Nonce};
 use anyhow::Result;
+use sha2::{Digest, Sha256};
 use std::fs::File;
-use std::io::{BufWriter, Read, Seek, Write};
-use std::path::Path;
+use std::io::{BufReader, BufWriter, Read, Seek, Write};
+use std::path::{Path, PathBuf};
 
-/// Binary format per entry: timestamp_ms(8) + direction(1) + data_len(4) + data(N)
+/// Leading bytes of a zstd-compressed stream file. Plain files without a
+/// header start with a big-endian timestamp, whose first byte is 0x00 for
+/// any realistic date.
+const COMPRESSED_MAGIC: [u8; 3] = [0x4F, 0x53, 0x5A]; // "OSZ"
+/// Leading bytes of a plain stream file with a header.
+const PLAIN_MAGIC: [u8; 3] = [0x4F, 0x53, 0x50]; // "OSP"
+/// Leading bytes of an AES-256-GCM encrypted stream file.
+const ENCRYPTED_MAGIC: [u8; 3] = [0x4F, 0x53, 0x45]; // "OSE"
+/// Version byte after the magic of files whose entries carry a CRC32.
+/// Older compressed files have the zstd frame magic (0x28) in its place.
+const CRC_VERSION: u8 = 2;
+const COMPRESSION_LEVEL: i32 = 3;
+/// timestamp_ms(8) + direction(1) + data_len(4)
+const ENTRY_HEADER_LEN: usize = 13;
+const CRC_LEN: usize = 4;
+const NONCE_LEN: usize = 12;
+const TAG_LEN: usize = 16;
+
+/// On-disk layout of a stream file, detected from its first bytes.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+struct Layout {
+    compressed: bool,
+    /// Entries end with a CRC32. False for files written before
+    /// `CRC_VERSION`, which are read without verification.
+    crc: bool,
+    /// Entry data is a nonce plus AES-256-GCM ciphertext. The GCM tag
+    /// takes the place of the CRC.
+    encrypted: bool,
+}
+
+impl Layout {
+    const PLAIN: Layout = Layout { compressed: false, crc: true, encrypted: false };
+    const COMPRESSED: Layout = Layout { compressed: true, crc: true, encrypted: false };
+    const ENCRYPTED: Layout = Layout { compressed: false, crc: false, encrypted: true };
+
+    fn detect(path: &Path) -> Result<Self> {
+        let mut head = [0u8; 4];
+        let mut file = File::open(path)?;
+        let mut n = 0;
+        while n < head.len() {
+            match file.read(&mut head[n..])? {
+                0 => break,
+                read => n += read,
+            }
+        }
+        let versioned = n == head.len() && head[3] == CRC_VERSION;
+        Ok(if n >= 3 && head[..3] == COMPRESSED_MAGIC {
+            Layout { compressed: true, crc: versioned, encrypted: false }
+        } else if versioned && head[..3] == PLAIN_MAGIC {
+            Self::PLAIN
+        } else if versioned && head[..3] == ENCRYPTED_MAGIC {
+            Self::ENCRYPTED
+        } else {
+            Layout { compressed: false, crc: false, encrypted: false }
+        })
+    }
+
+    /// Bytes before the entry data.
+    fn header(&self) -> &'static [u8] {
+        match (self.compressed, self.crc, self.encrypted) {
+            (_, _, true) => &[ENCRYPTED_MAGIC[0], ENCRYPTED_MAGIC[1], ENCRYPTED_MAGIC[2], CRC_VERSION],
+            (false, false, _) => &[],
+            (true, false, _) => &COMPRESSED_MAGIC,
+            (false, true, _) => &[PLAIN_MAGIC[0], PLAIN_MAGIC[1], PLAIN_MAGIC[2], CRC_VERSION],
+            (true, true, _) => &[COMPRESSED_MAGIC[0], COMPRESSED_MAGIC[1], COMPRESSED_MAGIC[2], CRC_VERSION],
+        }
+    }
+
+    /// Size of an entry holding `data_len` bytes.
+    fn entry_len(&self, data_len: usize) -> usize {
+        ENTRY_HEADER_LEN
+            + data_len
+            + if self.crc { CRC_LEN } else { 0 }
+            + if self.encrypted { NONCE_LEN + TAG_LEN } else { 0 }
+    }
+
+    /// The cipher for reading or appending to a file in this layout.
+    fn cipher(&self, path: &Path, key: Option<&[u8; 32]>) -> Result<Option<Aes256Gcm>> {
+        if !self.encrypted {
+            return Ok(None);
+        }
+        let key = key.ok_or_else(|| anyhow::anyhow!("{} is encrypted and no key was given", path.display()))?;
+        Ok(Some(Aes256Gcm::new(key.into())))
+    }
+}
+
+enum Sink {
+    Plain(BufWriter<File>),
+    /// `None` only after the frame has been finished in `Drop`.
+    Zstd(Option<zstd::Encoder<'static, BufWriter<File>>>),
+}
+
+/// Binary format per entry: timestamp_ms(8) + direction(1) + data_len(4) +
+/// data(N) + crc32(4), the CRC covering everything before it.
+///
+/// Files start with `OSP` (plain) or `OSZ` (zstd frames; each writer
+/// session appends a new frame) and the version byte. In `OSE` files
+/// (encrypted) the data is replaced by nonce(12) + AES-256-GCM
+/// ciphertext and tag (N + 16), with timestamp and direction as associated
+/// data, and there is no CRC; data_len stays the plaintext length. Files from before
+/// the CRC have no version byte, no CRC per entry, and plain ones no
+/// header at all; they are appended to in their own format. `position()`
+/// is always an offset into the uncompressed entry data, after the header.
 pub struct StreamWriter {
-    writer: BufWriter<File>,
+    writer: Sink,
     pos: u64,
+    path: PathBuf,
+    layout: Layout,
+    cipher: Option<Aes256Gcm>,
 }
 
 #[derive(Clone)]
@@ -14,88 +122,455 @@ pub struct StreamEntry {
     pub timestamp_ms: u64,
     pub direction: u8,
     pub data: Vec<u8>,
+    /// CRC32 of timestamp_ms (big-endian), direction and data.
+    pub checksum: u32,
+}
+
+impl StreamEntry {
+    pub fn new(timestamp_ms: u64, direction: u8, data: Vec<u8>) -> Self {
+        let checksum = entry_crc(timestamp_ms, direction, &data);
+        Self { timestamp_ms, direction, data, checksum }
+    }
+
+    /// True if `checksum` matches the entry's contents.
+    pub fn verify(&self) -> bool {
+        self.checksum == entry_crc(self.timestamp_ms, self.direction, &self.data)
+    }
+}
+
+fn entry_crc(timestamp_ms: u64, direction: u8, data: &[u8]) -> u32 {
+    let mut hasher = crc32fast::Hasher::new();
+    hasher.update(&timestamp_ms.to_be_bytes());
+    hasher.update(&[direction]);
+    hasher.update(data);
+    hasher.finalize()
 }
 
 impl StreamWriter {
     pub fn create(path: &Path) -> Result<Self> {
-        let file = File::create(path)?;
+        let mut file = BufWriter::new(File::create(path)?);
+        file.write_all(Layout::PLAIN.header())?;
         Ok(Self {
-            writer: BufWriter::new(file),
+            writer: Sink::Plain(file),
             pos: 0,
+            path: path.to_path_buf(),
+            layout: Layout::PLAIN,
+            cipher: None,
         })
     }
 
-    pub fn open_append(path: &Path) -> Result<Self> {
-        let file = std::fs::OpenOptions::new()
-            .append(true)
-            .open(path)?;
-        let pos = file.metadata()?.len();
+    /// Create a zstd-compressed stream file.
+    pub fn create_compressed(path: &Path) -> Result<Self> {
+        let mut file = BufWriter::new(File::create(path)?);
+        file.write_all(Layout::COMPRESSED.header())?;
         Ok(Self {
-            writer: BufWriter::new(file),
+            writer: Sink::Zstd(Some(zstd::Encoder::new(file, COMPRESSION_LEVEL)?)),
+            pos: 0,
+            path: path.to_path_buf(),
+            layout: Layout::COMPRESSED,
+            cipher: None,
+        })
+    }
+
+    /// Create a stream file whose entry data is encrypted with `session_key`
+    /// (see `derive_session_key`). Each entry gets a random nonce.
+    pub fn create_encrypted(path: &Path, session_key: &[u8; 32]) -> Result<Self> {
+        let mut file = BufWriter::new(File::create(path)?);
+        file.write_all(Layout::ENCRYPTED.header())?;
+        Ok(Self {
+            writer: Sink::Plain(file),
+            pos: 0,
+            path: path.to_path_buf(),
+            layout: Layout::ENCRYPTED,
+            cipher: Some(Aes256Gcm::new(session_key.into())),
+        })
+    }
+
+    /// Reopen an existing file for appending, keeping its format. `key` is
+    /// required for encrypted files and ignored otherwise.
+    pub fn open_append(path: &Path, key: Option<&[u8; 32]>) -> Result<Self> {
+        let layout = Layout::detect(path)?;
+        let cipher = layout.cipher(path, key)?;
+        if layout.compressed {
+            recover_unfinished_frame(path, layout)?;
+        }
+        let pos = stream_len(path)?;
+        Ok(Self {
+            writer: append_sink(path, layout)?,
             pos,
+            path: path.to_path_buf(),
+            layout,
+            cipher,
         })
     }
 
+    /// Drop the oldest entries so that at most `target_bytes` of entry data
+    /// remain. The file is rewritten (in the same format) and swapped in by
+    /// rename, so readers holding the old file are unaffected. Returns the
+    /// number of bytes dropped from the front; existing offsets must be
+    /// shifted down by it.
+    pub fn trim_oldest(&mut self, target_bytes: u64) -> Result<u64> {
+        if self.pos <= target_bytes {
+            return Ok(0);
+        }
+        self.finish_sink()?;
+        let mut data = Vec::new();
+        open_entries(&self.path)?.0.read_to_end(&mut data)?;
+
+        // First entry boundary after which the rest fits.
+        let mut cut = 0usize;
+        while (data.len() - cut) as u64 > target_bytes && cut + ENTRY_HEADER_LEN <= data.len() {
+            let len = u32::from_be_bytes(data[cut + 9..cut + 13].try_into()?) as usize;
+            cut = (cut + self.layout.entry_len(len)).min(data.len());
+        }
+
+        self.replace_contents(&data[cut..])?;
+        Ok(cut as u64)
+    }
+
+    /// Rewrite the file keeping only the bytes covered by `ranges`
+    /// (`(offset, length)` pairs on entry boundaries, e.g. command output
+    /// ranges), packed back to back in file order. Returns the new offset of
+    /// each range; an empty range maps to where its offset ends up. The file
+    /// keeps its format and is swapped in by rename like `trim_oldest`.
+    pub fn compact(&mut self, ranges: &[(u64, u64)]) -> Result<Vec<u64>> {
+        self.finish_sink()?;
+        let mut data = Vec::new();
+        open_entries(&self.path)?.0.read_to_end(&mut data)?;
+        let len = data.len() as u64;
+
+        // Sorted, disjoint spans covering every range.
+        let mut spans: Vec<(u64, u64)> = ranges
+            .iter()
+            .filter(|(_, l)| *l > 0)
+            .map(|&(o, l)| (o.min(len), o.saturating_add(l).min(len)))
+            .collect();
+        spans.sort_unstable();
+        let mut merged: Vec<(u64, u64)> = Vec::new();
+        for (start, end) in spans {
+            match merged.last_mut() {
+                Some(last) if start <= last.1 => last.1 = last.1.max(end),
+                _ => merged.push((start, end)),
+            }
+        }
+
+        let mut kept = Vec::new();
+        let mut new_starts = Vec::with_capacity(merged.len());
+        for &(start, end) in &merged {
+            new_starts.push(kept.len() as u64);
+            kept.extend_from_slice(&data[start as usize..end as usize]);
+        }
+        let offsets = ranges
+            .iter()
+            .map(|&(offset, _)| {
+                let i = merged.partition_point(|&(start, _)| start <= offset);
+                if i == 0 {
+                    return 0;
+                }
+                let (start, end) = merged[i - 1];
+                new_starts[i - 1] + offset.min(end) - start
+            })
+            .collect();
+
+        self.replace_contents(&kept)?;
+        Ok(offsets)
+    }
+
+    /// Replace the file with `data` (entry data in the file's layout) via
+    /// `<file>.tmp` + rename and reopen it for appending.
+    fn replace_contents(&mut self, data: &[u8]) -> Result<()> {
+        let tmp = self.path.with_extension("bin.tmp");
+        {
+            let mut file = BufWriter::new(File::create(&tmp)?);
+            file.write_all(self.layout.header())?;
+            if self.layout.compressed {
+                let mut encoder = zstd::Encoder::new(file, COMPRESSION_LEVEL)?;
+                encoder.write_all(data)?;
+                encoder.finish()?.flush()?;
+            } else {
+                file.write_all(data)?;
+                file.flush()?;
+            }
+        }
+        std::fs::rename(&tmp, &self.path)?;
+        self.writer = append_sink(&self.path, self.layout)?;
+        self.pos = data.len() as u64;
+        Ok(())
+    }
+
+    /// Flush plain output or finish the current zstd frame.
+    fn finish_sink(&mut self) -> Result<()> {
+        match &mut self.writer {
+            Sink::Plain(w) => w.flush()?,
+            Sink::Zstd(encoder) => {
+                if let Some(encoder) = encoder.take() {
+                    encoder.finish()?.flush()?;
+                }
+            }
+        }
+        Ok(())
+    }
+
     pub fn position(&self) -> u64 {
         self.pos
     }
 
     pub fn write_entry(&mut self, timestamp_ms: u64, direction: u8, data: &[u8]) -> Result<()> {
-        self.writer.write_all(&timestamp_ms.to_be_bytes())?;
-        self.writer.write_all(&[direction])?;
-        self.writer.write_all(&(data.len() as u32).to_be_bytes())?;
-        self.writer.write_all(data)?;
-        self.writer.flush()?;
-        self.pos += 8 + 1 + 4 + data.len() as u64;
+        let writer: &mut dyn Write = match &mut self.writer {
+            Sink::Plain(w) => w,
+            Sink::Zstd(w) => w.as_mut().expect("encoder finished before drop"),
+        };
+        writer.write_all(&timestamp_ms.to_be_bytes())?;
+        writer.write_all(&[direction])?;
+        writer.write_all(&(data.len() as u32).to_be_bytes())?;
+        if let Some(cipher) = &self.cipher {
+            let nonce = Aes256Gcm::generate_nonce(&mut OsRng);
+            let aad = entry_aad(timestamp_ms, direction);
+            let sealed = cipher
+                .encrypt(&nonce, Payload { msg: data, aad: &aad })
+                .map_err(|_| anyhow::anyhow!("failed to encrypt stream entry"))?;
+            writer.write_all(&nonce)?;
+            writer.write_all(&sealed)?;
+        } else {
+            writer.write_all(data)?;
+        }
+        if self.layout.crc {
+            writer.write_all(&entry_crc(timestamp_ms, direction, data).to_be_bytes())?;
+        }
+        writer.flush()?;
+        self.pos += self.layout.entry_len(data.len()) as u64;
         Ok(())
     }
 }
 
-pub fn read_range(path: &Path, offset: u64, length: u64) -> Result<Vec<StreamEntry>> {
-    let mut file = File::open(path)?;
-    file.seek(std::io::SeekFrom::Start(offset))?;
-    let mut data = vec![0u8; length as usize];
-    file.read_exact(&mut data)?;
+/// Open `path` for appending in its existing format (a compressed file gets
+/// a new zstd frame).
+fn append_sink(path: &Path, layout: Layout) -> Result<Sink> {
+    let file = std::fs::OpenOptions::new().append(true).open(path)?;
+    Ok(if layout.compressed {
+        Sink::Zstd(Some(zstd::Encoder::new(BufWriter::new(file), COMPRESSION_LEVEL)?))
+    } else {
+        Sink::Plain(BufWriter::new(file))
+    })
+}
 
-    let mut entries = Vec::new();
-    let mut pos = 0;
-    while pos + 13 <= data.len() {
-        let timestamp_ms = u64::from_be_bytes(data[pos..pos + 8].try_into()?);
-        let direction = data[pos + 8];
-        let data_len = u32::from_be_bytes(data[pos + 9..pos + 13].try_into()?) as usize;
-        if pos + 13 + data_len > data.len() {
+/// Repair a compressed file whose last zstd frame was never finished
+/// (the daemon died before the writer was dropped). A new frame appended
+/// after it would not decode, so the entries that can still be decoded
+/// from it are rewritten as a complete frame and a cut-off entry at its
+/// end is dropped.
+fn recover_unfinished_frame(path: &Path, layout: Layout) -> Result<()> {
+    let raw = std::fs::read(path)?;
+    let mut end = layout.header().len().min(raw.len());
+    while end < raw.len() {
+        match zstd::zstd_safe::find_frame_compressed_size(&raw[end..]) {
+            Ok(n) if n > 0 => end += n,
+            _ => break,
+        }
+    }
+    if end == raw.len() {
+        return Ok(());
+    }
+
+    let mut partial = Vec::new();
+    // Keeps whatever decoded before the frame stops
+    let _ = zstd::Decoder::new(&raw[end..])?.read_to_end(&mut partial);
+    let mut keep = 0;
+    while keep + ENTRY_HEADER_LEN <= partial.len() {
+        let len = u32::from_be_bytes(partial[keep + 9..keep + 13].try_into()?) as usize;
+        let next = keep + layout.entry_len(len);
+        if next > partial.len() {
             break;
         }
-        let entry_data = data[pos + 13..pos + 13 + data_len].to_vec();
-        entries.push(StreamEntry {
-            timestamp_ms,
-            direction,
-            data: entry_data,
-        });
-        pos += 13 + data_len;
+        keep = next;
     }
-    Ok(entries)
+    tracing::warn!(
+        "{}: recovered {} bytes from an unfinished compressed frame",
+        path.display(),
+        keep
+    );
+
+    std::fs::OpenOptions::new().write(true).open(path)?.set_len(end as u64)?;
+    if keep > 0 {
+        let file = std::fs::OpenOptions::new().append(true).open(path)?;
+        let mut encoder = zstd::Encoder::new(BufWriter::new(file), COMPRESSION_LEVEL)?;
+        encoder.write_all(&partial[..keep])?;
+        encoder.finish()?.flush()?;
+    }
+    Ok(())
+}
+
+impl Drop for StreamWriter {
+    fn drop(&mut self) {
+        if let Sink::Zstd(encoder) = &mut self.writer {
+            if let Some(encoder) = encoder.take() {
+                if let Err(e) = encoder.finish().and_then(|mut w| w.flush()) {
+                    tracing::warn!("failed to finish compressed stream: {}", e);
+                }
+            }
+        }
+    }
+}
+
+/// Open `path` for reading uncompressed entry data, past the header.
+fn open_entries(path: &Path) -> Result<(Box<dyn Read>, Layout)> {
+    let layout = Layout::detect(path)?;
+    let mut file = File::open(path)?;
+    file.seek(std::io::SeekFrom::Start(layout.header().len() as u64))?;
+    let reader: Box<dyn Read> = if layout.compressed {
+        Box::new(zstd::Decoder::new(file)?)
+    } else {
+        Box::new(BufReader::new(file))
+    };
+    Ok((reader, layout))
+}
+
+/// Length of the uncompressed entry data in `path` (the file size minus
+/// the header for plain files). A trailing frame cut short by a crash
+/// counts up to where it stops decoding.
+pub fn stream_len(path: &Path) -> Result<u64> {
+    let (mut reader, layout) = open_entries(path)?;
+    if !layout.compressed {
+        let header = layout.header().len() as u64;
+        return Ok(std::fs::metadata(path)?.len().saturating_sub(header));
+    }
+    let mut buf = [0u8; 64 * 1024];
+    let mut len = 0u64;
+    loop {
+        match reader.read(&mut buf) {
+            Ok(0) | Err(_) => break,
+            Ok(n) => len += n as u64,
+        }
+    }
+    Ok(len)
+}
+
+/// Entries in `[offset, offset + length)`. Fails on an entry whose CRC
+/// does not match (files without CRCs are not checked). Encrypted files need
+/// `key` and fail on entries that do not decrypt with it; for other files
+/// `key` is ignored.
+pub fn read_range(path: &Path, offset: u64, length: u64, key: Option<&[u8; 32]>) -> Result<Vec<StreamEntry>> {
+    let mut data = vec![0u8; length as usize];
+    let (mut reader, layout) = open_entries(path)?;
+    let cipher = layout.cipher(path, key)?;
+    std::io::copy(&mut (&mut reader).take(offset), &mut std::io::sink())?;
+    reader.read_exact(&mut data)?;
+    parse_entries(&data, layout, offset, cipher.as_ref())
+}
+
+/// Like `read_range`, but maps `[offset, offset + length)` instead of
+/// copying it through a read buffer. Meant for large ranges of plain
+/// stream files; compressed files fall back to `read_range`.
+pub fn read_range_mmap(path: &Path, offset: u64, length: u64, key: Option<&[u8; 32]>) -> Result<Vec<StreamEntry>> {
+    if length == 0 {
+        return Ok(Vec::new());
+    }
+    let layout = Layout::detect(path)?;
+    if layout.compressed {
+        return read_range(path, offset, length, key);
+    }
+    let cipher = layout.cipher(path, key)?;
+    let file = File::open(path)?;
+    let header = layout.header().len() as u64;
+    let file_len = file.metadata()?.len();
+    if header + offset.saturating_add(length) > file_len {
+        anyhow::bail!(
+            "range {}+{} past end of {} ({} bytes)",
+            offset,
+            length,
+            path.display(),
+            file_len.saturating_sub(header)
+        );
+    }
+    // SAFETY: the range lies within the file as checked above. Stream files
+    // are only appended to, or replaced by rename in `trim_oldest`, so the
+    // mapped bytes do not change while the map is alive.
+    let map = unsafe {
+        memmap2::MmapOptions::new()
+            .offset(header + offset)
+            .len(length as usize)
+            .map(&file)?
+    };
+    parse_entries(&map, layout, offset, cipher.as_ref())
 }
 
-pub fn read_entries(path: &Path) -> Result<Vec<StreamEntry>> {
+/// All entries in `path`. A compressed file whose last frame was cut
+/// short by a crash yields the entries that decode before the cut.
+pub fn read_entries(path: &Path, key: Option<&[u8; 32]>) -> Result<Vec<StreamEntry>> {
     let mut data = Vec::new();
-    File::open(path)?.read_to_end(&mut data)?;
+    let (mut reader, layout) = open_entries(path)?;
+    let cipher = layout.cipher(path, key)?;
+    if let Err(e) = reader.read_to_end(&mut data) {
+        if !layout.compressed {
+            return Err(e.into());
+        }
+        tracing::warn!("{}: compressed stream ends early: {}", path.display(), e);
+    }
+    parse_entries(&data, layout, 0, cipher.as_ref())
+}
+
+/// Key for a session's encrypted stream.bin, derived from the daemon's
+/// master key so that only the master key has to be stored.
+pub fn derive_session_key(master_key: &[u8; 32], session_id: &str) -> [u8; 32] {
+    let mut hasher = Sha256::new();
+    hasher.update(b"omnish-stream-key\0");
+    hasher.update(master_key);
+    hasher.update(session_id.as_bytes());
+    hasher.finalize().into()
+}
+
+/// Associated data of an encrypted entry, so its timestamp and direction
+/// cannot be swapped with another entry's.
+fn entry_aad(timestamp_ms: u64, direction: u8) -> [u8; 9] {
+    let mut aad = [0u8; 9];
+    aad[..8].copy_from_slice(&timestamp_ms.to_be_bytes());
+    aad[8] = direction;
+    aad
+}
+
+/// Parse entries from `data`, which starts at entry offset `base`. A
+/// truncated trailing entry is skipped; an entry failing its CRC is an
+/// error. Entries keep the CRC read from disk. `cipher` must be given for
+/// encrypted layouts.
+fn parse_entries(
+    data: &[u8],
+    layout: Layout,
+    base: u64,
+    cipher: Option<&Aes256Gcm>,
+) -> Result<Vec<StreamEntry>> {
     let mut entries = Vec::new();
     let mut pos = 0;
-    while pos + 13 <= data.len() {
+    while pos + ENTRY_HEADER_LEN <= data.len() {
         let timestamp_ms = u64::from_be_bytes(data[pos..pos + 8].try_into()?);
         let direction = data[pos + 8];
         let data_len = u32::from_be_bytes(data[pos + 9..pos + 13].try_into()?) as usize;
-        if pos + 13 + data_len > data.len() {
+        let entry_len = layout.entry_len(data_len);
+        if pos + entry_len > data.len() {
             break;
         }
-        let entry_data = data[pos + 13..pos + 13 + data_len].to_vec();
-        entries.push(StreamEntry {
-            timestamp_ms,
-            direction,
-            data: entry_data,
-        });
-        pos += 13 + data_len;
+        let mut entry = match cipher {
+            Some(cipher) => {
+                let nonce_at = pos + ENTRY_HEADER_LEN;
+                let sealed = &data[nonce_at + NONCE_LEN..pos + entry_len];
+                let aad = entry_aad(timestamp_ms, direction);
+                let plain = cipher
+                    .decrypt(Nonce::from_slice(&data[nonce_at..nonce_at + NONCE_LEN]), Payload { msg: sealed, aad: &aad })
+                    .map_err(|_| anyhow::anyhow!("stream entry at offset {} failed to decrypt", base + pos as u64))?;
+                StreamEntry::new(timestamp_ms, direction, plain)
+            }
+            None => {
+                let body = &data[pos + ENTRY_HEADER_LEN..pos + ENTRY_HEADER_LEN + data_len];
+                StreamEntry::new(timestamp_ms, direction, body.to_vec())
+            }
+        };
+        if layout.crc {
+            entry.checksum = u32::from_be_bytes(data[pos + entry_len - CRC_LEN..pos + entry_len].try_into()?);
+            if !entry.verify() {
+                anyhow::bail!("stream entry at offset {} failed its CRC check", base + pos as u64);
+            }
+        }
+        entries.push(entry);
+        pos += entry_len;
     }
     Ok(entries)
 }
@@ -118,16 +593,333 @@ mod tests {
 
         // Reopen with open_append and write more
         {
-            let mut sw = StreamWriter::open_append(&path).unwrap();
+            let mut sw = StreamWriter::open_append(&path, None).unwrap();
             assert!(sw.position() > 0);
             sw.write_entry(3000, 0, b"appended").unwrap();
         }
 
         // Verify all entries are readable
-        let entries = read_entries(&path).unwrap();
+        let entries = read_entries(&path, None).unwrap();
         assert_eq!(entries.len(), 3);
         assert_eq!(entries[0].data, b"hello");
         assert_eq!(entries[1].data, b"world");
         assert_eq!(entries[2].data, b"appended");
     }
+
+    #[test]
+    fn test_compressed_round_trip() {
+        let dir = tempfile::tempdir().unwrap();
+        let path = dir.path().join("stream.bin");
+
+        // ~1 MB of build-log-like output in 4 KiB chunks
+        let chunks: Vec<Vec<u8>> = (0..256)
+            .map(|i| {
+                (0..4096)
+                    .map(|j| b"Compiling omnish v0.1.0\n"[(i + j) % 24])
+                    .collect()
+            })
+            .collect();
+        let mut offsets = Vec::new();
+        {
+            let mut sw = StreamWriter::create_compressed(&path).unwrap();
+            for (i, chunk) in chunks.iter().enumerate() {
+                offsets.push(sw.position());
+                sw.write_entry(i as u64, 1, chunk).unwrap();
+            }
+            assert_eq!(sw.position(), 256 * (17 + 4096));
+        }
+
+        let raw = std::fs::read(&path).unwrap();
+        assert_eq!(&raw[..3], b"OSZ");
+        assert!(raw.len() < 256 * 4096 / 10);
+
+        let all = read_entries(&path, None).unwrap();
+        assert_eq!(all.len(), chunks.len());
+        for (entry, chunk) in all.iter().zip(&chunks) {
+            assert_eq!(&entry.data, chunk);
+        }
+
+        let mid = read_range(&path, offsets[100], offsets[102] - offsets[100], None).unwrap();
+        assert_eq!(mid.len(), 2);
+        assert_eq!(mid[0].timestamp_ms, 100);
+        assert_eq!(mid[1].data, chunks[101]);
+    }
+
+    #[test]
+    fn test_read_range_mmap_matches_sequential() {
+        let dir = tempfile::tempdir().unwrap();
+        let path = dir.path().join("stream.bin");
+
+        // ~10 MB of entries with varying sizes.
+        let mut offsets = Vec::new();
+        {
+            let mut sw = StreamWriter::create(&path).unwrap();
+            let mut i = 0u64;
+            while sw.position() < 10 * 1024 * 1024 {
+                offsets.push(sw.position());
+                let data = vec![(i % 251) as u8; 1000 + (i as usize * 7919) % 30_000];
+                sw.write_entry(i, (i % 2) as u8, &data).unwrap();
+                i += 1;
+            }
+            offsets.push(sw.position());
+        }
+
+        // Deterministic pseudo-random entry ranges, including multi-MB ones.
+        let mut seed = 12345u64;
+        let n = offsets.len() - 1;
+        for _ in 0..50 {
+            seed = seed.wrapping_mul(6364136223846793005).wrapping_add(1442695040888963407);
+            let a = (seed >> 33) as usize % n;
+            let b = (a + 1 + (seed >> 13) as usize % 200).min(n);
+            let (off, len) = (offsets[a], offsets[b] - offsets[a]);
+            let seq = read_range(&path, off, len, None).unwrap();
+            let mapped = read_range_mmap(&path, off, len, None).unwrap();
+            assert_eq!(mapped.len(), b - a);
+            assert!(seq.iter().zip(&mapped).all(|(x, y)| {
+                x.timestamp_ms == y.timestamp_ms && x.direction == y.direction && x.data == y.data
+            }));
+        }
+
+        assert!(read_range_mmap(&path, offsets[n], 1, None).is_err());
+        assert!(read_range_mmap(&path, 0, 0, None).unwrap().is_empty());
+    }
+
+    #[test]
+    fn test_read_range_mmap_compressed_falls_back() {
+        let dir = tempfile::tempdir().unwrap();
+        let path = dir.path().join("stream.bin");
+        {
+            let mut sw = StreamWriter::create_compressed(&path).unwrap();
+            sw.write_entry(1, 1, b"first").unwrap();
+            sw.write_entry(2, 1, b"second").unwrap();
+        }
+        let entries = read_range_mmap(&path, 0, 17 + 5, None).unwrap();
+        assert_eq!(entries.len(), 1);
+        assert_eq!(entries[0].data, b"first");
+    }
+
+    #[test]
+    fn test_trim_oldest_keeps_newest_entries() {
+        for compressed in [false, true] {
+            let dir = tempfile::tempdir().unwrap();
+            let path = dir.path().join("stream.bin");
+            let mut sw = if compressed {
+                StreamWriter::create_compressed(&path).unwrap()
+            } else {
+                StreamWriter::create(&path).unwrap()
+            };
+            let mut offsets = Vec::new();
+            for i in 0..10u64 {
+                offsets.push(sw.position());
+                sw.write_entry(i, 1, &[i as u8; 83]).unwrap(); // 100 bytes each
+            }
+
+            assert_eq!(sw.trim_oldest(2_000).unwrap(), 0);
+            let dropped = sw.trim_oldest(350).unwrap();
+            assert_eq!(dropped, 700);
+            assert_eq!(sw.position(), 300);
+
+            // Preserved entries are readable at their shifted offsets.
+            let kept = read_range(&path, offsets[8] - dropped, 100, None).unwrap();
+            assert_eq!(kept[0].timestamp_ms, 8);
+            assert_eq!(kept[0].data, vec![8u8; 83]);
+
+            // Writing continues after the trimmed data.
+            sw.write_entry(10, 1, b"after").unwrap();
+            drop(sw);
+            let all = read_entries(&path, None).unwrap();
+            let ts: Vec<u64> = all.iter().map(|e| e.timestamp_ms).collect();
+            assert_eq!(ts, vec![7, 8, 9, 10]);
+            assert!(!dir.path().join("stream.bin.tmp").exists());
+        }
+    }
+
+    #[test]
+    fn test_compact_packs_kept_ranges() {
+        for compressed in [false, true] {
+            let dir = tempfile::tempdir().unwrap();
+            let path = dir.path().join("stream.bin");
+            let mut sw = if compressed {
+                StreamWriter::create_compressed(&path).unwrap()
+            } else {
+                StreamWriter::create(&path).unwrap()
+            };
+            let mut offsets = Vec::new();
+            for i in 0..6u64 {
+                offsets.push(sw.position());
+                sw.write_entry(i, 1, &[i as u8; 83]).unwrap(); // 100 bytes each
+            }
+
+            // Keep entries 1, 3-4 and an empty range inside the dropped entry 5.
+            let ranges = [(offsets[1], 100), (offsets[3], 200), (offsets[4], 100), (offsets[5], 0)];
+            assert_eq!(sw.compact(&ranges).unwrap(), vec![0, 100, 200, 300]);
+            assert_eq!(sw.position(), 300);
+
+            let ts: Vec<u64> = read_range(&path, 100, 200, None).unwrap().iter().map(|e| e.timestamp_ms).collect();
+            assert_eq!(ts, vec![3, 4]);
+            sw.write_entry(6, 1, b"after").unwrap();
+            drop(sw);
+            let ts: Vec<u64> = read_entries(&path, None).unwrap().iter().map(|e| e.timestamp_ms).collect();
+            assert_eq!(ts, vec![1, 3, 4, 6]);
+        }
+    }
+
+    #[test]
+    fn test_open_append_compressed() {
+        let dir = tempfile::tempdir().unwrap();
+        let path = dir.path().join("stream.bin");
+        let end = {
+            let mut sw = StreamWriter::create_compressed(&path).unwrap();
+            sw.write_entry(1000, 0, b"hello").unwrap();
+            sw.position()
+        };
+        assert_eq!(stream_len(&path).unwrap(), end);
+        {
+            let mut sw = StreamWriter::open_append(&path, None).unwrap();
+            assert_eq!(sw.position(), end);
+            sw.write_entry(2000, 1, b"again").unwrap();
+        }
+        let entries = read_range(&path, end, 17 + 5, None).unwrap();
+        assert_eq!(entries.len(), 1);
+        assert_eq!(entries[0].data, b"again");
+        assert_eq!(read_entries(&path, None).unwrap().len(), 2);
+    }
+
+    #[test]
+    fn test_open_append_recovers_unfinished_frame() {
+        let dir = tempfile::tempdir().unwrap();
+        let path = dir.path().join("stream.bin");
+        {
+            let mut sw = StreamWriter::create_compressed(&path).unwrap();
+            sw.write_entry(1000, 0, b"first").unwrap();
+            sw.write_entry(2000, 0, b"second").unwrap();
+            // Simulate a crash: the frame is flushed but never finished
+            std::mem::forget(sw);
+        }
+        assert_eq!(read_entries(&path, None).unwrap().len(), 2);
+        {
+            let mut sw = StreamWriter::open_append(&path, None).unwrap();
+            assert_eq!(sw.position(), 2 * 17 + 11);
+            sw.write_entry(3000, 1, b"after").unwrap();
+        }
+        let data: Vec<Vec<u8>> = read_entries(&path, None).unwrap().into_iter().map(|e| e.data).collect();
+        assert_eq!(data, vec![b"first".to_vec(), b"second".to_vec(), b"after".to_vec()]);
+        assert_eq!(stream_len(&path).unwrap(), 3 * 17 + 16);
+    }
+
+    #[test]
+    fn test_read_range_rejects_corrupted_entry() {
+        let dir = tempfile::tempdir().unwrap();
+        let path = dir.path().join("stream.bin");
+        let (first, second) = {
+            let mut sw = StreamWriter::create(&path).unwrap();
+            sw.write_entry(1000, 1, b"intact").unwrap();
+            let first = sw.position();
+            sw.write_entry(2000, 1, b"damaged").unwrap();
+            (first, sw.position() - first)
+        };
+
+        // Flip one data byte of the second entry
+        let mut raw = std::fs::read(&path).unwrap();
+        let header = raw.len() as u64 - first - second;
+        raw[(header + first + ENTRY_HEADER_LEN as u64) as usize] ^= 0x01;
+        std::fs::write(&path, &raw).unwrap();
+
+        let err = read_range(&path, 0, first + second, None).err().unwrap();
+        assert_eq!(err.to_string(), format!("stream entry at offset {first} failed its CRC check"));
+        assert!(read_range(&path, first, second, None).is_err());
+        assert!(read_range_mmap(&path, first, second, None).is_err());
+        assert!(read_entries(&path, None).is_err());
+
+        // Entries before the damage still read
+        let entries = read_range(&path, 0, first, None).unwrap();
+        assert_eq!(entries.len(), 1);
+        assert_eq!(entries[0].data, b"intact");
+        assert!(entries[0].verify());
+
+        // The checksum comes from disk, not recomputed from the data
+        let raw_first = &raw[header as usize..(header + first) as usize];
+        let stored = u32::from_be_bytes(raw_first[raw_first.len() - CRC_LEN..].try_into().unwrap());
+        assert_eq!(entries[0].checksum, stored);
+        let mut tampered = entries[0].clone();
+        tampered.data[0] ^= 0x01;
+        assert!(!tampered.verify());
+    }
+
+    #[test]
+    fn test_legacy_plain_stream_reads_without_crc() {
+        let dir = tempfile::tempdir().unwrap();
+        let path = dir.path().join("stream.bin");
+        let mut raw = Vec::new();
+        raw.extend_from_slice(&1000u64.to_be_bytes());
+        raw.push(1);
+        raw.extend_from_slice(&3u32.to_be_bytes());
+        raw.extend_from_slice(b"old");
+        std::fs::write(&path, &raw).unwrap();
+
+        let entries = read_range(&path, 0, raw.len() as u64, None).unwrap();
+        assert_eq!(entries.len(), 1);
+        assert_eq!(entries[0].data, b"old");
+        assert!(entries[0].verify());
+
+        // Appending keeps the legacy layout
+        let mut sw = StreamWriter::open_append(&path, None).unwrap();
+        assert_eq!(sw.position(), raw.len() as u64);
+        sw.write_entry(2000, 1, b"new").unwrap();
+        drop(sw);
+        assert_eq!(std::fs::metadata(&path).unwrap().len(), 2 * raw.len() as u64);
+        assert_eq!(read_entries(&path, None).unwrap()[1].data, b"new");
+    }
+
+    #[test]
+    fn test_encrypted_round_trip() {
+        let dir = tempfile::tempdir().unwrap();
+        let path = dir.path().join("stream.bin");
+        let key = derive_session_key(&[7u8; 32], "sess1");
+        let end = {
+            let mut sw = StreamWriter::create_encrypted(&path, &key).unwrap();
+            sw.write_entry(1000, 1, b"DB_PASSWORD=hunter2").unwrap();
+            sw.position()
+        };
+        assert_eq!(end, 13 + 12 + 19 + 16);
+        let raw = std::fs::read(&path).unwrap();
+        assert_eq!(&raw[..4], b"OSE\x02");
+        assert!(!raw.windows(7).any(|w| w == b"hunter2"));
+
+        {
+            let mut sw = StreamWriter::open_append(&path, Some(&key)).unwrap();
+            assert_eq!(sw.position(), end);
+            sw.write_entry(2000, 0, b"ls").unwrap();
+        }
+        let entries = read_range(&path, 0, end, Some(&key)).unwrap();
+        assert_eq!(entries[0].data, b"DB_PASSWORD=hunter2");
+        assert_eq!(entries[0].timestamp_ms, 1000);
+        let all = read_entries(&path, Some(&key)).unwrap();
+        assert_eq!(all.len(), 2);
+        assert_eq!(all[1].data, b"ls");
+        assert_eq!(read_range_mmap(&path, 0, end, Some(&key)).unwrap()[0].data, entries[0].data);
+    }
+
+    #[test]
+    fn test_encrypted_wrong_or_missing_key_fails() {
+        let dir = tempfile::tempdir().unwrap();
+        let path = dir.path().join("stream.bin");
+        let master = [7u8; 32];
+        let key = derive_session_key(&master, "sess1");
+        let end = {
+            let mut sw = StreamWriter::create_encrypted(&path, &key).unwrap();
+            sw.write_entry(1000, 1, b"secret").unwrap();
+            sw.position()
+        };
+
+        let wrong = derive_session_key(&master, "sess2");
+        let err = read_range(&path, 0, end, Some(&wrong)).err().expect("wrong key accepted");
+        assert!(err.to_string().contains("decrypt"), "{err}");
+        assert!(read_range(&path, 0, end, None).is_err());
+        assert!(StreamWriter::open_append(&path, None).is_err());
+        // Plain files ignore a key.
+        let plain = dir.path().join("plain.bin");
+        StreamWriter::create(&plain).unwrap().write_entry(1, 1, b"x").unwrap();
+        assert_eq!(read_entries(&plain, Some(&key)).unwrap()[0].data, b"x");
+    }
 }
@@ -0,0 +1,110 @@
+use std::io::{BufRead, BufReader};
+use std::path::{Path, PathBuf};
+use std::sync::mpsc;
+
+use serde::{Deserialize, Serialize};
+
+/// Token counts reported by the LLM API for one or more queries.
+#[derive(Debug, Clone, Copy, Default, PartialEq, Eq)]
+pub struct TokenUsage {
+    pub prompt_tokens: u64,
+    pub completion_tokens: u64,
+}
+
+impl TokenUsage {
+    pub fn add(&mut self, other: TokenUsage) {
+        self.prompt_tokens += other.prompt_tokens;
+        self.completion_tokens += other.completion_tokens;
+    }
+}
+
+/// Token usage of one LLM query, appended to the session's JSONL file.
+#[derive(Debug, Clone, PartialEq, Serialize, Deserialize)]
+pub struct TokenUsageRecord {
+    pub request_id: String,
+    pub session_id: String,
+    pub model: String,
+    pub prompt_tokens: u64,
+    pub completion_tokens: u64,
+    /// When the response completed (epoch ms).
+    pub timestamp_ms: u64,
+    /// Counts were estimated locally because the API did not report them
+    /// (e.g. streamed replies).
+    #[serde(default, skip_serializing_if = "std::ops::Not::not")]
+    pub estimated: bool,
+}
+
+impl TokenUsageRecord {
+    pub fn usage(&self) -> TokenUsage {
+        TokenUsage {
+            prompt_tokens: self.prompt_tokens,
+            completion_tokens: self.completion_tokens,
+        }
+    }
+}
+
+/// Spawn a writer thread that writes TokenUsageRecords to
+/// `<dir>/<session_id>.jsonl`.
+pub fn spawn_usage_writer_thread(dir: PathBuf) -> mpsc::Sender<TokenUsageRecord> {
+    crate::query_log::spawn_jsonl_writer_thread(
+        dir,
+        |r: &TokenUsageRecord| r.session_id.clone(),
+        "token usage log",
+    )
+}
+
+/// Usage log file of `session_id` in `dir`.
+pub fn session_usage_path(dir: &Path, session_id: &str) -> PathBuf {
+    dir.join(format!("{}.jsonl", session_id))
+}
+
+/// Sum the usage records in `session_id`'s JSONL file in `dir`.
+/// Unparseable lines are skipped.
+pub fn load_session_usage(dir: &Path, session_id: &str) -> TokenUsage {
+    let mut total = TokenUsage::default();
+    let Ok(file) = std::fs::File::open(session_usage_path(dir, session_id)) else {
+        return total;
+    };
+    for line in BufReader::new(file).lines().map_while(Result::ok) {
+        if let Ok(r) = serde_json::from_str::<TokenUsageRecord>(&line) {
+            total.add(r.usage());
+        }
+    }
+    total
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn record(session_id: &str, prompt_tokens: u64, completion_tokens: u64) -> TokenUsageRecord {
+        TokenUsageRecord {
+            request_id: format!("r{}", prompt_tokens),
+            session_id: session_id.into(),
+            model: "mock".into(),
+            prompt_tokens,
+            completion_tokens,
+            timestamp_ms: 1,
+            estimated: false,
+        }
+    }
+
+    #[test]
+    fn test_writer_then_load_sums_per_session() {
+        let dir = tempfile::TempDir::new().unwrap();
+        let tx = spawn_usage_writer_thread(dir.path().to_path_buf());
+        tx.send(record("s1", 100, 20)).unwrap();
+        tx.send(record("s2", 7, 7)).unwrap();
+        tx.send(record("s1", 50, 5)).unwrap();
+        std::thread::sleep(std::time::Duration::from_millis(100));
+        drop(tx);
+
+        assert_eq!(
+            load_session_usage(dir.path(), "s1"),
+            TokenUsage { prompt_tokens: 150, completion_tokens: 25 }
+        );
+        assert!(session_usage_path(dir.path(), "s2").exists());
+        assert_eq!(load_session_usage(dir.path(), "none"), TokenUsage::default());
+        assert_eq!(load_session_usage(&dir.path().join("missing"), "s1"), TokenUsage::default());
+    }
+}
@@ -1,3 +1,4 @@
+use omnish_store::atomic::tmp_path;
 use omnish_store::command::CommandRecord;
 use omnish_store::session::SessionMeta;
 use omnish_store::stream::{read_range, StreamWriter};
@@ -18,6 +19,8 @@ fn test_write_and_read_session_meta() {
             ("tty".to_string(), "/dev/pts/0".to_string()),
             ("cwd".to_string(), "/home/user".to_string()),
         ]),
+        tags: Vec::new(),
+        description: None,
     };
     meta.save(dir.path()).unwrap();
     let loaded = SessionMeta::load(dir.path()).unwrap();
@@ -36,7 +39,7 @@ fn test_stream_writer_and_reader() {
         writer.write_entry(1001, 1, b"total 0\n").unwrap(); // 1 = output
     }
 
-    let entries = omnish_store::stream::read_entries(&path).unwrap();
+    let entries = omnish_store::stream::read_entries(&path, None).unwrap();
     assert_eq!(entries.len(), 2);
     assert_eq!(entries[0].timestamp_ms, 1000);
     assert_eq!(entries[0].direction, 0);
@@ -59,6 +62,9 @@ fn test_command_record_save_and_load() {
             stream_offset: 0,
             stream_length: 512,
             exit_code: None,
+            exit_signal: None,
+            checksum: None,
+            tags: Vec::new(),
         },
         CommandRecord {
             command_id: "sess1:1".into(),
@@ -71,6 +77,9 @@ fn test_command_record_save_and_load() {
             stream_offset: 512,
             stream_length: 1024,
             exit_code: None,
+            exit_signal: None,
+            checksum: None,
+            tags: Vec::new(),
         },
     ];
 
@@ -95,13 +104,13 @@ fn test_stream_writer_position_tracking() {
     let pos0 = writer.position();
     assert_eq!(pos0, 0);
 
-    writer.write_entry(1000, 0, b"ls\n").unwrap(); // 8+1+4+3 = 16 bytes
+    writer.write_entry(1000, 0, b"ls\n").unwrap(); // 8+1+4+3+4 = 20 bytes
     let pos1 = writer.position();
-    assert_eq!(pos1, 16);
+    assert_eq!(pos1, 20);
 
-    writer.write_entry(1001, 1, b"file.txt\n").unwrap(); // 8+1+4+9 = 22 bytes
+    writer.write_entry(1001, 1, b"file.txt\n").unwrap(); // 8+1+4+9+4 = 26 bytes
     let pos2 = writer.position();
-    assert_eq!(pos2, 38); // 16 + 22
+    assert_eq!(pos2, 46); // 20 + 26
 }
 
 #[test]
@@ -117,13 +126,13 @@ fn test_read_range_from_stream() {
     let pos2 = writer.position();
 
     // Read only the second entry's range
-    let entries = read_range(&path, pos1, pos2 - pos1).unwrap();
+    let entries = read_range(&path, pos1, pos2 - pos1, None).unwrap();
     assert_eq!(entries.len(), 1);
     assert_eq!(entries[0].data, b"file.txt\n");
     assert_eq!(entries[0].direction, 1);
 
     // Read both entries
-    let all = read_range(&path, pos0, pos2 - pos0).unwrap();
+    let all = read_range(&path, pos0, pos2 - pos0, None).unwrap();
     assert_eq!(all.len(), 2);
 }
 
@@ -143,8 +152,84 @@ fn test_session_meta_with_parent() {
         started_at: "2026-02-14T10:00:00Z".into(),
         ended_at: None,
         attrs: HashMap::new(),
+        tags: Vec::new(),
+        description: None,
     };
     meta.save(dir.path()).unwrap();
     let loaded = SessionMeta::load(dir.path()).unwrap();
     assert_eq!(loaded.parent_session_id, Some("parent1".into()));
 }
+
+fn command(id: &str, line: &str) -> CommandRecord {
+    CommandRecord {
+        command_id: id.into(),
+        session_id: "sess1".into(),
+        command_line: Some(line.into()),
+        cwd: None,
+        started_at: 1000,
+        ended_at: Some(2000),
+        output_summary: String::new(),
+        stream_offset: 0,
+        stream_length: 0,
+        exit_code: Some(0),
+        exit_signal: None,
+        checksum: None,
+        tags: Vec::new(),
+    }
+}
+
+#[test]
+fn test_interrupted_write_leaves_original_intact() {
+    let dir = tempdir().unwrap();
+    let path = dir.path().join("commands.json");
+    CommandRecord::save_all(&[command("sess1:0", "make")], dir.path()).unwrap();
+    let original = std::fs::read(&path).unwrap();
+
+    // A crash mid-write leaves a truncated staging file behind.
+    let json = serde_json::to_string_pretty(&[command("sess1:0", "make"), command("sess1:1", "make test")]).unwrap();
+    std::fs::write(tmp_path(&path), &json[..json.len() / 2]).unwrap();
+
+    assert_eq!(std::fs::read(&path).unwrap(), original);
+    let loaded = CommandRecord::load_all(dir.path()).unwrap();
+    assert_eq!(loaded.len(), 1);
+    assert_eq!(loaded[0].command_line.as_deref(), Some("make"));
+}
+
+#[test]
+fn test_atomic_save_renames_tmp_over_target() {
+    let dir = tempdir().unwrap();
+    let commands_path = dir.path().join("commands.json");
+    std::fs::write(tmp_path(&commands_path), "{trunc").unwrap();
+    CommandRecord::save_all(&[command("sess1:0", "ls")], dir.path()).unwrap();
+    assert!(!tmp_path(&commands_path).exists());
+    assert_eq!(CommandRecord::load_all(dir.path()).unwrap()[0].command_line.as_deref(), Some("ls"));
+
+    let meta = SessionMeta {
+        session_id: "abc".into(),
+        parent_session_id: None,
+        started_at: "2026-02-14T10:00:00Z".into(),
+        ended_at: None,
+        attrs: HashMap::new(),
+        tags: Vec::new(),
+        description: None,
+    };
+    meta.save(dir.path()).unwrap();
+    assert!(!tmp_path(&dir.path().join("meta.json")).exists());
+    assert_eq!(SessionMeta::load(dir.path()).unwrap().session_id, "abc");
+
+    // In-place mode never touches the staging file.
+    meta.save_with(dir.path(), false).unwrap();
+    assert!(!tmp_path(&dir.path().join("meta.json")).exists());
+    assert_eq!(SessionMeta::load(dir.path()).unwrap().session_id, "abc");
+}
+
+#[test]
+fn test_duration_ms() {
+    let mut cmd = command("sess1:0", "make");
+    assert_eq!(cmd.duration_ms(), Some(1000));
+    cmd.ended_at = None;
+    assert_eq!(cmd.duration_ms(), None);
+    // Clock skew between start and end never underflows.
+    cmd.ended_at = Some(500);
+    assert_eq!(cmd.duration_ms(), Some(0));
+}
@@ -1,24 +1,54 @@
 use crate::osc133_detector::{Osc133Event, Osc133EventKind};
 use crate::prompt_detector::{strip_ansi, PromptDetector};
 use omnish_store::command::CommandRecord;
+use regex::Regex;
 
 const SUMMARY_HEAD_LINES: usize = 5;
 const SUMMARY_TAIL_LINES: usize = 5;
+/// How long after Enter an OSC 133;B may take before the shell is assumed
+/// to emit only 133;A (and OSC 7) and the tracker switches to partial mode.
+const PARTIAL_MODE_TIMEOUT_MS: u64 = 1000;
+/// Continuation prompt (PS2) shown while a multi-line command is typed.
+const DEFAULT_PS2_PATTERN: &str = r"^\s*>\s*$";
+
+/// Where command boundaries come from.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum Osc133Mode {
+    /// No OSC 133 seen: boundaries come from regex prompt detection.
+    Off,
+    /// Only 133;A arrives: a command starts at Enter and ends at the next
+    /// prompt, and its cwd is the OSC 7 directory current at Enter.
+    Partial,
+    /// 133;B and 133;D delimit commands.
+    Full,
+}
 
 struct PendingCommand {
     started_at: u64,
     stream_offset: u64,
     input_buf: Vec<u8>,
+    /// Prompt line plus the shell's echo of the typed command, collected
+    /// until Enter. Only used with a custom prompt regex.
+    echo_buf: Vec<u8>,
     output_lines: Vec<String>,
     /// True once we've seen \r or \n in the input (user pressed Enter).
     /// Output before this point is shell echo and should be excluded from the summary.
     entered: bool,
+    /// When Enter was first seen in the input.
+    entered_at: Option<u64>,
+    /// Tracked cwd at Enter, used when no OSC 133;B reports one.
+    entered_cwd: Option<String>,
+    /// Set by OSC 133;B.
+    saw_command_start: bool,
     /// Command line text from OSC 133;B payload (shell's $BASH_COMMAND).
     osc_command_line: Option<String>,
     /// Original user input from OSC 133;B payload (from `history 1`, preserves aliases).
     osc_original_input: Option<String>,
     /// Current working directory from OSC 133;B payload.
     osc_cwd: Option<String>,
+    /// Set by OSC 133;E: the shell is showing PS2 and waiting for the rest
+    /// of the command. The next 133;B continues this command.
+    awaiting_continuation: bool,
 }
 
 pub struct CommandTracker {
@@ -28,7 +58,14 @@ pub struct CommandTracker {
     pending: Option<PendingCommand>,
     next_seq: u32,
     seen_first_prompt: bool,
-    osc133_mode: bool,
+    osc133_mode: Osc133Mode,
+    /// Custom prompt pattern; `None` uses the built-in default.
+    prompt_regex: Option<Regex>,
+    /// Matches the continuation prompt in regex mode.
+    ps2_regex: Regex,
+    /// Lines of a multi-line command entered so far at PS2 prompts (regex
+    /// mode). Flushed into the command line when the next PS1 arrives.
+    pending_multiline: Option<String>,
 }
 
 impl CommandTracker {
@@ -40,8 +77,49 @@ impl CommandTracker {
             pending: None,
             next_seq: 0,
             seen_first_prompt: false,
-            osc133_mode: false,
+            osc133_mode: Osc133Mode::Off,
+            prompt_regex: None,
+            ps2_regex: Regex::new(DEFAULT_PS2_PATTERN).expect("invalid default PS2 pattern"),
+            pending_multiline: None,
+        }
+    }
+
+    /// Detect prompts with `pattern` instead of the built-in pattern. The
+    /// command line is then read from the echoed prompt line: the `cmd`
+    /// capture group if the pattern has one, else the text after the match.
+    pub fn with_prompt_regex(mut self, pattern: &str) -> Result<Self, regex::Error> {
+        self.set_prompt_regex(Some(pattern))?;
+        Ok(self)
+    }
+
+    /// Replace the prompt pattern; `None` restores the built-in default.
+    pub fn set_prompt_regex(&mut self, pattern: Option<&str>) -> Result<(), regex::Error> {
+        match pattern {
+            Some(p) => {
+                let regex = Regex::new(p)?;
+                self.detector = PromptDetector::with_regex(regex.clone());
+                self.prompt_regex = Some(regex);
+            }
+            None => {
+                self.detector = PromptDetector::new();
+                self.prompt_regex = None;
+            }
         }
+        Ok(())
+    }
+
+    /// Recognize continuation prompts with `pattern`; `None` restores the
+    /// built-in default (`> `). Matched against the last output line once a
+    /// line has been entered.
+    pub fn set_ps2_regex(&mut self, pattern: Option<&str>) -> Result<(), regex::Error> {
+        self.ps2_regex = Regex::new(pattern.unwrap_or(DEFAULT_PS2_PATTERN))?;
+        Ok(())
+    }
+
+    /// Update the working directory used for commands that don't report one
+    /// in their OSC 133;B payload (e.g. from an OSC 7 notification).
+    pub fn set_cwd(&mut self, cwd: String) {
+        self.cwd = Some(cwd);
     }
 
     pub fn tracking(&self) -> bool {
@@ -52,15 +130,46 @@ impl CommandTracker {
         self.pending.is_some()
     }
 
-    pub fn feed_input(&mut self, data: &[u8], _timestamp_ms: u64) {
+    pub fn osc133_mode(&self) -> Osc133Mode {
+        self.osc133_mode
+    }
+
+    pub fn feed_input(&mut self, data: &[u8], timestamp_ms: u64) {
+        self.check_partial_mode(timestamp_ms);
         if let Some(ref mut pending) = self.pending {
             pending.input_buf.extend_from_slice(data);
             if data.contains(&b'\r') || data.contains(&b'\n') {
                 pending.entered = true;
+                if pending.entered_at.is_none() {
+                    pending.entered_at = Some(timestamp_ms);
+                    pending.entered_cwd = self.cwd.clone();
+                }
             }
         }
     }
 
+    /// Switch from full to partial OSC 133 mode once a command has been
+    /// entered and no 133;B arrived within `PARTIAL_MODE_TIMEOUT_MS`.
+    fn check_partial_mode(&mut self, timestamp_ms: u64) {
+        if self.osc133_mode != Osc133Mode::Full {
+            return;
+        }
+        let Some(ref pending) = self.pending else { return };
+        if pending.saw_command_start {
+            return;
+        }
+        if pending
+            .entered_at
+            .is_some_and(|t| timestamp_ms.saturating_sub(t) >= PARTIAL_MODE_TIMEOUT_MS)
+        {
+            tracing::info!(
+                "no OSC 133;B after Enter (session={}), switching to partial OSC 133 mode",
+                self.session_id
+            );
+            self.osc133_mode = Osc133Mode::Partial;
+        }
+    }
+
     fn finalize_command(
         &mut self,
         pending: PendingCommand,
@@ -70,12 +179,26 @@ impl CommandTracker {
     ) -> CommandRecord {
         let seq = self.next_seq;
         self.next_seq += 1;
+        let echoed = self
+            .prompt_regex
+            .as_ref()
+            .and_then(|re| extract_echoed_command(re, &pending.echo_buf));
         let command_line = pending
             .osc_original_input
             .or(pending.osc_command_line)
+            .or(echoed)
             .or_else(|| extract_command_line(&pending.input_buf));
-        // Use runtime cwd if available, otherwise fall back to session cwd
-        let cwd = pending.osc_cwd.or_else(|| self.cwd.clone());
+        let command_line = join_lines(self.pending_multiline.take(), command_line);
+        // Use runtime cwd if available, then the cwd at Enter (partial mode),
+        // otherwise fall back to session cwd
+        let cwd = pending
+            .osc_cwd
+            .or(pending.entered_cwd)
+            .or_else(|| self.cwd.clone());
+        let started_at = match self.osc133_mode {
+            Osc133Mode::Partial => pending.entered_at.unwrap_or(pending.started_at),
+            _ => pending.started_at,
+        };
         let output_summary = make_summary(&pending.output_lines);
         let stream_length = stream_pos - pending.stream_offset;
         CommandRecord {
@@ -83,12 +206,15 @@ impl CommandTracker {
             session_id: self.session_id.clone(),
             command_line,
             cwd,
-            started_at: pending.started_at,
+            started_at,
             ended_at: Some(timestamp_ms),
             output_summary,
             stream_offset: pending.stream_offset,
             stream_length,
             exit_code,
+            exit_signal: exit_code.filter(|&c| c < 0).map(|c| -c),
+            checksum: None,
+            tags: Vec::new(),
         }
     }
 
@@ -111,18 +237,26 @@ impl CommandTracker {
                         pending.output_lines.push(trimmed.to_string());
                     }
                 }
+            } else if self.prompt_regex.is_some() {
+                pending.echo_buf.extend_from_slice(data);
             }
         }
 
         // In osc133 mode, command boundaries come from OSC events, not regex.
-        if self.osc133_mode {
+        if self.osc133_mode != Osc133Mode::Off {
             return Vec::new();
         }
 
+        self.check_continuation(data);
+
         let events = self.detector.feed(data);
         let mut completed = Vec::new();
 
-        for _event in events {
+        for event in events {
+            let prompt_line = match self.prompt_regex {
+                Some(_) => data[event.line_start_offset..].to_vec(),
+                None => Vec::new(),
+            };
             if !self.seen_first_prompt {
                 // First prompt: start tracking, create initial pending command
                 self.seen_first_prompt = true;
@@ -130,11 +264,16 @@ impl CommandTracker {
                     started_at: timestamp_ms,
                     stream_offset: stream_pos,
                     input_buf: Vec::new(),
+                    echo_buf: prompt_line.clone(),
                     output_lines: Vec::new(),
                     entered: false,
+                    entered_at: None,
+                    entered_cwd: None,
+                    saw_command_start: false,
                     osc_command_line: None,
                     osc_original_input: None,
                     osc_cwd: None,
+                    awaiting_continuation: false,
                 });
             } else {
                 // Subsequent prompt: finalize pending command, start new one
@@ -146,11 +285,16 @@ impl CommandTracker {
                     started_at: timestamp_ms,
                     stream_offset: stream_pos,
                     input_buf: Vec::new(),
+                    echo_buf: prompt_line,
                     output_lines: Vec::new(),
                     entered: false,
+                    entered_at: None,
+                    entered_cwd: None,
+                    saw_command_start: false,
                     osc_command_line: None,
                     osc_original_input: None,
                     osc_cwd: None,
+                    awaiting_continuation: false,
                 });
             }
         }
@@ -164,14 +308,24 @@ impl CommandTracker {
         timestamp_ms: u64,
         stream_pos: u64,
     ) -> Vec<CommandRecord> {
-        self.osc133_mode = true;
+        // OSC 7 only reports the cwd; shells that emit it may not emit OSC 133,
+        // so it must not switch off regex prompt detection.
+        match event.kind {
+            Osc133EventKind::WorkingDirChange { .. } => {}
+            // 133;B proves the shell hook is complete.
+            Osc133EventKind::CommandStart { .. } => self.osc133_mode = Osc133Mode::Full,
+            _ if self.osc133_mode == Osc133Mode::Off => self.osc133_mode = Osc133Mode::Full,
+            _ => {}
+        }
+        self.check_partial_mode(timestamp_ms);
         let mut completed = Vec::new();
 
         match event.kind {
             Osc133EventKind::PromptStart => {
-                // Finalize previous command if pending and entered
+                // Finalize previous command if pending and entered. A command
+                // still waiting at PS2 was abandoned (e.g. Ctrl-C) and never ran.
                 if let Some(pending) = self.pending.take() {
-                    if pending.entered {
+                    if pending.entered && !pending.awaiting_continuation {
                         completed.push(self.finalize_command(pending, timestamp_ms, stream_pos, None));
                     }
                 }
@@ -180,11 +334,16 @@ impl CommandTracker {
                     started_at: timestamp_ms,
                     stream_offset: stream_pos,
                     input_buf: Vec::new(),
+                    echo_buf: Vec::new(),
                     output_lines: Vec::new(),
                     entered: false,
+                    entered_at: None,
+                    entered_cwd: None,
+                    saw_command_start: false,
                     osc_command_line: None,
                     osc_original_input: None,
                     osc_cwd: None,
+                    awaiting_continuation: false,
                 });
             }
             Osc133EventKind::CommandStart { command, cwd, original } => {
@@ -199,29 +358,57 @@ impl CommandTracker {
                         started_at: timestamp_ms,
                         stream_offset: stream_pos,
                         input_buf: Vec::new(),
+                        echo_buf: Vec::new(),
                         output_lines: Vec::new(),
                         entered: false,
+                        entered_at: None,
+                        entered_cwd: None,
+                        saw_command_start: false,
                         osc_command_line: None,
                         osc_original_input: None,
                         osc_cwd: None,
+                        awaiting_continuation: false,
                     });
                 }
                 if let Some(ref mut pending) = self.pending {
+                    if pending.awaiting_continuation {
+                        // Next line of a multi-line command: keep the first
+                        // line's start time and join the lines.
+                        pending.awaiting_continuation = false;
+                        pending.osc_command_line = join_lines(pending.osc_command_line.take(), command);
+                        pending.osc_original_input = join_lines(pending.osc_original_input.take(), original);
+                        if pending.osc_cwd.is_none() {
+                            pending.osc_cwd = cwd.or_else(|| self.cwd.clone());
+                        }
+                        return completed;
+                    }
                     pending.entered = true;
+                    pending.saw_command_start = true;
                     pending.started_at = timestamp_ms;
                     pending.osc_command_line = command;
                     pending.osc_original_input = original;
-                    pending.osc_cwd = cwd;
+                    // Snapshot the tracked cwd now: an OSC 7 arriving before
+                    // CommandEnd (e.g. after `cd`) describes the next command.
+                    pending.osc_cwd = cwd.or_else(|| self.cwd.clone());
                 }
             }
             Osc133EventKind::OutputStart => {
                 // No special action - output collection happens via feed_output_raw
             }
+            Osc133EventKind::ContinuationPrompt => {
+                // Incomplete command: keep it pending until the final line runs
+                if let Some(ref mut pending) = self.pending {
+                    pending.awaiting_continuation = true;
+                }
+            }
             Osc133EventKind::CommandEnd { exit_code } => {
                 if let Some(pending) = self.pending.take() {
                     completed.push(self.finalize_command(pending, timestamp_ms, stream_pos, Some(exit_code)));
                 }
             }
+            Osc133EventKind::WorkingDirChange { path } => {
+                self.set_cwd(path);
+            }
             Osc133EventKind::ReadlineLine { .. } | Osc133EventKind::NoReadline => {
                 // Handled by shell_input in omnish-client, not relevant to command tracking
             }
@@ -230,6 +417,34 @@ impl CommandTracker {
         completed
     }
 
+    /// If `data` ends at a PS2 prompt after Enter, the shell wants more of
+    /// the command: move the entered line to `pending_multiline` and collect
+    /// the next line as if at a fresh prompt, keeping the command's start.
+    fn check_continuation(&mut self, data: &[u8]) {
+        let Some(ref mut pending) = self.pending else { return };
+        if !pending.entered {
+            return;
+        }
+        let stripped = strip_ansi(data);
+        let text = String::from_utf8_lossy(&stripped);
+        let last = text.rsplit('\n').next().unwrap_or("").trim_end_matches('\r');
+        if !self.ps2_regex.is_match(last) {
+            return;
+        }
+        let echoed = self
+            .prompt_regex
+            .as_ref()
+            .and_then(|re| extract_echoed_command(re, &pending.echo_buf));
+        let line = echoed.or_else(|| extract_command_line(&pending.input_buf));
+        self.pending_multiline = join_lines(self.pending_multiline.take(), line);
+        if pending.output_lines.last().is_some_and(|l| l == last) {
+            pending.output_lines.pop();
+        }
+        pending.input_buf.clear();
+        pending.echo_buf.clear();
+        pending.entered = false;
+    }
+
     /// Feed raw output bytes for summary collection only (no prompt detection).
     /// Use this in osc133 mode where command boundaries come from OSC events.
     pub fn feed_output_raw(&mut self, data: &[u8], _timestamp_ms: u64, _stream_pos: u64) {
@@ -248,6 +463,30 @@ impl CommandTracker {
     }
 }
 
+/// Join two lines of a multi-line command, tolerating either being absent.
+fn join_lines(first: Option<String>, next: Option<String>) -> Option<String> {
+    match (first, next) {
+        (Some(a), Some(b)) => Some(format!("{}\n{}", a, b)),
+        (a, b) => a.or(b),
+    }
+}
+
+/// Command line from an echoed prompt line matched by a custom prompt regex.
+fn extract_echoed_command(regex: &Regex, echo: &[u8]) -> Option<String> {
+    let line = extract_command_line(&strip_ansi(echo))?;
+    let caps = regex.captures(&line)?;
+    let command = match caps.name("cmd") {
+        Some(m) => m.as_str(),
+        None => &line[caps.get(0)?.end()..],
+    };
+    let command = command.trim();
+    if command.is_empty() {
+        None
+    } else {
+        Some(command.to_string())
+    }
+}
+
 fn extract_command_line(input: &[u8]) -> Option<String> {
     // Replay editing: process backspace (0x7f, 0x08) and Ctrl-U (0x15) on raw
     // bytes before the first \r/\n to reconstruct the actual command line.
@@ -584,8 +823,140 @@ mod tests {
         assert_eq!(cmds[0].command_line.as_deref(), Some("ls"));
     }
 
+    #[test]
+    fn test_custom_prompt_regex_detects_commands() {
+        let mut tracker = make_tracker().with_prompt_regex(r"\$\s+").unwrap();
+        // Default pattern would take this trailing '$' for a prompt
+        assert!(tracker.feed_output(b"costs 5$", 900, 0).is_empty());
+        assert!(!tracker.tracking());
+
+        tracker.feed_output(b"\r\nuser@host:~$ ", 1000, 10);
+        assert!(tracker.tracking());
+        // Tab completion: the echo holds the full command, the input does not
+        tracker.feed_input(b"git st", 1001);
+        tracker.feed_output(b"git st", 1001, 20);
+        tracker.feed_input(b"\t", 1002);
+        tracker.feed_output(b"atus ", 1002, 26);
+        tracker.feed_input(b"\r", 1003);
+        let cmds = tracker.feed_output(b"\r\nOn branch main\r\nuser@host:~$ ", 1004, 31);
+        assert_eq!(cmds.len(), 1);
+        assert_eq!(cmds[0].command_line.as_deref(), Some("git status"));
+
+        tracker.feed_output(b"ls", 1005, 60);
+        tracker.feed_input(b"ls\r", 1005);
+        let cmds = tracker.feed_output(b"\r\na.txt\r\nuser@host:~$ ", 1006, 62);
+        assert_eq!(cmds.len(), 1);
+        assert_eq!(cmds[0].command_line.as_deref(), Some("ls"));
+    }
+
+    #[test]
+    fn test_custom_prompt_regex_cmd_group() {
+        let mut tracker = make_tracker()
+            .with_prompt_regex(r"\]\$\s+(?P<cmd>[^#]*)")
+            .unwrap();
+        tracker.feed_output(b"[~/src]$ ", 1000, 0);
+        tracker.feed_output(b"make test # ci", 1001, 9);
+        tracker.feed_input(b"make test # ci\r", 1001);
+        let cmds = tracker.feed_output(b"\r\nok\r\n[~/src]$ ", 1002, 23);
+        assert_eq!(cmds.len(), 1);
+        assert_eq!(cmds[0].command_line.as_deref(), Some("make test"));
+    }
+
+    #[test]
+    fn test_invalid_prompt_regex_rejected() {
+        assert!(make_tracker().with_prompt_regex(r"\$(").is_err());
+    }
+
+    #[test]
+    fn test_ps2_lines_accumulate_into_one_command() {
+        let mut tracker = make_tracker();
+        tracker.feed_output(b"user@host:~$ ", 1000, 0);
+        let mut pos = 13;
+        for (i, line) in ["for i in 1 2 3", "do", "  echo $i", "done"].iter().enumerate() {
+            let ts = 1001 + i as u64;
+            tracker.feed_output(line.as_bytes(), ts, pos);
+            tracker.feed_input(format!("{}\r", line).as_bytes(), ts);
+            pos += line.len() as u64;
+            if i < 3 {
+                assert!(tracker.feed_output(b"\r\n> ", ts, pos).is_empty());
+                pos += 4;
+            }
+        }
+        let cmds = tracker.feed_output(b"\r\n1\r\n2\r\n3\r\nuser@host:~$ ", 1010, pos);
+
+        assert_eq!(cmds.len(), 1);
+        assert_eq!(cmds[0].command_line.as_deref(), Some("for i in 1 2 3\ndo\necho $i\ndone"));
+        assert_eq!(cmds[0].started_at, 1000);
+        assert_eq!(cmds[0].stream_offset, 0);
+        assert!(cmds[0].output_summary.starts_with("1\n2\n3"), "{}", cmds[0].output_summary);
+
+        // The next command starts from scratch
+        tracker.feed_input(b"ls\r", 1011);
+        let cmds = tracker.feed_output(b"\r\na.txt\r\nuser@host:~$ ", 1012, pos + 40);
+        assert_eq!(cmds[0].command_line.as_deref(), Some("ls"));
+    }
+
+    #[test]
+    fn test_custom_ps2_regex() {
+        let mut tracker = make_tracker();
+        assert!(tracker.set_ps2_regex(Some("(")).is_err());
+        tracker.set_ps2_regex(Some(r"^\.\.\.\s*$")).unwrap();
+        tracker.feed_output(b"$ ", 1000, 0);
+        tracker.feed_input(b"echo 'a\r", 1001);
+        tracker.feed_output(b"\r\n... ", 1001, 2);
+        tracker.feed_input(b"b'\r", 1002);
+        let cmds = tracker.feed_output(b"\r\na\r\nb\r\n$ ", 1003, 8);
+        assert_eq!(cmds.len(), 1);
+        assert_eq!(cmds[0].command_line.as_deref(), Some("echo 'a\nb'"));
+    }
+
     // --- OSC 133 mode tests ---
 
+    #[test]
+    fn test_osc133_continuation_prompt_joins_lines() {
+        use crate::osc133_detector::*;
+        let mut tracker = make_tracker();
+        let ev = |kind| Osc133Event { kind, start: 0, end: 8 };
+        let start = |cmd: &str| Osc133EventKind::CommandStart {
+            command: Some(cmd.into()), cwd: None, original: None,
+        };
+
+        tracker.feed_osc133(ev(Osc133EventKind::PromptStart), 1000, 0);
+        let cmds = tracker.feed_osc133(ev(start("for i in 1 2; do")), 1001, 10);
+        assert!(cmds.is_empty());
+        let cmds = tracker.feed_osc133(ev(Osc133EventKind::ContinuationPrompt), 1002, 20);
+        assert!(cmds.is_empty());
+        let cmds = tracker.feed_osc133(ev(start("echo $i; done")), 1003, 30);
+        assert!(cmds.is_empty(), "continued line must not finalize the command");
+        tracker.feed_osc133(ev(Osc133EventKind::OutputStart), 1004, 40);
+        tracker.feed_output_raw(b"1\r\n2\r\n", 1004, 50);
+        let cmds = tracker.feed_osc133(ev(Osc133EventKind::CommandEnd { exit_code: 0 }), 1005, 60);
+
+        assert_eq!(cmds.len(), 1);
+        assert_eq!(cmds[0].command_line.as_deref(), Some("for i in 1 2; do\necho $i; done"));
+        assert_eq!(cmds[0].started_at, 1001);
+        assert_eq!(cmds[0].stream_offset, 0);
+        assert_eq!(cmds[0].stream_length, 60);
+    }
+
+    #[test]
+    fn test_osc133_abandoned_continuation_not_recorded() {
+        use crate::osc133_detector::*;
+        let mut tracker = make_tracker();
+        let ev = |kind| Osc133Event { kind, start: 0, end: 8 };
+
+        tracker.feed_osc133(ev(Osc133EventKind::PromptStart), 1000, 0);
+        tracker.feed_osc133(
+            ev(Osc133EventKind::CommandStart { command: Some("if true; then".into()), cwd: None, original: None }),
+            1001, 10,
+        );
+        tracker.feed_osc133(ev(Osc133EventKind::ContinuationPrompt), 1002, 20);
+        // Ctrl-C at PS2 brings back the primary prompt
+        let cmds = tracker.feed_osc133(ev(Osc133EventKind::PromptStart), 1003, 30);
+        assert!(cmds.is_empty());
+        assert!(tracker.has_pending());
+    }
+
     #[test]
     fn test_osc133_command_line_from_preexec() {
         use crate::osc133_detector::*;
@@ -665,6 +1036,21 @@ mod tests {
         let cmds = tracker.feed_osc133(Osc133Event { kind: Osc133EventKind::CommandEnd { exit_code: 1 }, start: 0, end: 10 }, 1003, 100);
         assert_eq!(cmds.len(), 1);
         assert_eq!(cmds[0].exit_code, Some(1));
+        assert_eq!(cmds[0].exit_signal, None);
+    }
+
+    #[test]
+    fn test_osc133_negative_exit_is_signal() {
+        use crate::osc133_detector::*;
+        let mut tracker = make_tracker();
+
+        tracker.feed_osc133(Osc133Event { kind: Osc133EventKind::PromptStart, start: 0, end: 8 }, 1000, 0);
+        tracker.feed_osc133(Osc133Event { kind: Osc133EventKind::CommandStart { command: None, cwd: None, original: None }, start: 0, end: 8 }, 1001, 50);
+        tracker.feed_input(b"sleep 100\r", 1001);
+        tracker.feed_osc133(Osc133Event { kind: Osc133EventKind::OutputStart, start: 0, end: 8 }, 1002, 60);
+        let cmds = tracker.feed_osc133(Osc133Event { kind: Osc133EventKind::CommandEnd { exit_code: -9 }, start: 0, end: 10 }, 1003, 100);
+        assert_eq!(cmds[0].exit_code, Some(-9));
+        assert_eq!(cmds[0].exit_signal, Some(9));
     }
 
     #[test]
@@ -709,6 +1095,69 @@ mod tests {
         assert_eq!(cmds2[0].command_id, "sess1:1");
     }
 
+    #[test]
+    fn test_osc7_updates_cwd_without_osc133_mode() {
+        let mut tracker = CommandTracker::new("sess1".into(), Some("/initial".into()));
+        let mut detector = crate::osc133_detector::Osc133Detector::new();
+        for event in detector.feed(b"\x1b]7;file://myhost/tmp/foo\x07") {
+            assert!(tracker.feed_osc133(event, 1000, 0).is_empty());
+        }
+        assert_eq!(tracker.osc133_mode(), Osc133Mode::Off);
+
+        // Regex prompt detection still drives command boundaries.
+        tracker.feed_output(b"user@host:~$ ", 1000, 0);
+        tracker.feed_input(b"ls\r", 1001);
+        tracker.feed_output(b"file.txt\r\n", 1002, 10);
+        let cmds = tracker.feed_output(b"user@host:~$ ", 1003, 20);
+        assert_eq!(cmds.len(), 1);
+        assert_eq!(cmds[0].cwd.as_deref(), Some("/tmp/foo"));
+    }
+
+    #[test]
+    fn test_partial_osc133_uses_osc7_cwd() {
+        use crate::osc133_detector::*;
+        let mut tracker = CommandTracker::new("sess1".into(), Some("/home/user".into()));
+        let prompt = Osc133Event { kind: Osc133EventKind::PromptStart, start: 0, end: 8 };
+        let cd = |path: &str| Osc133Event {
+            kind: Osc133EventKind::WorkingDirChange { path: path.into() },
+            start: 0,
+            end: 8,
+        };
+
+        tracker.feed_osc133(prompt.clone(), 1000, 0);
+        tracker.feed_input(b"cd /tmp\r", 1001);
+        // No 133;B follows; the shell only reports the new directory.
+        assert!(tracker.feed_osc133(cd("/tmp"), 3000, 10).is_empty());
+        assert_eq!(tracker.osc133_mode(), Osc133Mode::Partial);
+        let cmds = tracker.feed_osc133(prompt.clone(), 3001, 20);
+        assert_eq!(cmds.len(), 1);
+        assert_eq!(cmds[0].command_line.as_deref(), Some("cd /tmp"));
+        assert_eq!(cmds[0].cwd.as_deref(), Some("/home/user"));
+        assert_eq!(cmds[0].started_at, 1001);
+        assert_eq!(cmds[0].ended_at, Some(3001));
+        assert_eq!(cmds[0].exit_code, None);
+
+        tracker.feed_input(b"ls\r", 4000);
+        tracker.feed_output_raw(b"file.txt\r\n", 4001, 30);
+        let cmds = tracker.feed_osc133(prompt, 4002, 40);
+        assert_eq!(cmds.len(), 1);
+        assert_eq!(cmds[0].command_line.as_deref(), Some("ls"));
+        assert_eq!(cmds[0].cwd.as_deref(), Some("/tmp"));
+        assert_eq!(cmds[0].started_at, 4000);
+    }
+
+    #[test]
+    fn test_slow_command_start_keeps_full_osc133_mode() {
+        use crate::osc133_detector::*;
+        let mut tracker = make_tracker();
+        tracker.feed_osc133(Osc133Event { kind: Osc133EventKind::PromptStart, start: 0, end: 8 }, 1000, 0);
+        tracker.feed_osc133(Osc133Event { kind: Osc133EventKind::CommandStart { command: None, cwd: None, original: None }, start: 0, end: 8 }, 1001, 50);
+        tracker.feed_input(b"sleep 5\r", 1001);
+        tracker.feed_output_raw(b"", 7000, 60);
+        tracker.feed_input(b"x", 7000);
+        assert_eq!(tracker.osc133_mode(), Osc133Mode::Full);
+    }
+
     #[test]
     fn test_cwd_from_osc133_overrides_session_cwd() {
         use crate::osc133_detector::*;
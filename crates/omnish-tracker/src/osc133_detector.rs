@@ -4,8 +4,12 @@ pub enum Osc133EventKind {
     CommandStart { command: Option<String>, cwd: Option<String>, original: Option<String> },
     OutputStart,
     CommandEnd { exit_code: i32 },
+    /// PS2 continuation prompt: the line just entered is an incomplete command.
+    ContinuationPrompt,
     ReadlineLine { content: String, point: Option<usize> },
     NoReadline,
+    /// OSC 7 `file://host/path` working directory notification.
+    WorkingDirChange { path: String },
 }
 
 #[derive(Debug, Clone)]
@@ -24,6 +28,8 @@ pub struct Osc133Event {
 /// - `\x1b]133;B\x07` -> CommandStart
 /// - `\x1b]133;C\x07` -> OutputStart
 /// - `\x1b]133;D;{exit_code}\x07` -> CommandEnd { exit_code }
+/// - `\x1b]133;E\x07` -> ContinuationPrompt
+/// - `\x1b]7;file://{host}{path}\x07` -> WorkingDirChange { path }
 #[derive(Default)]
 pub struct Osc133Detector {
     buf: Vec<u8>,
@@ -59,7 +65,7 @@ impl Osc133Detector {
                     let seq_start = i + 1 - (self.buf.len() - self.carried_len);
                     let seq_end = i + 1;
 
-                    if let Some(kind) = Self::parse_osc133(&self.buf) {
+                    if let Some(kind) = Self::parse_osc133(&self.buf).or_else(|| Self::parse_osc7(&self.buf)) {
                         events.push(Osc133Event {
                             kind,
                             start: seq_start,
@@ -92,6 +98,15 @@ impl Osc133Detector {
         events
     }
 
+    fn parse_osc7(buf: &[u8]) -> Option<Osc133EventKind> {
+        // ESC ] 7 ; file://<host><path> BEL - host may be empty.
+        let payload = buf.strip_prefix(b"\x1b]7;")?.strip_suffix(b"\x07")?;
+        let rest = payload.strip_prefix(b"file://")?;
+        let path_start = rest.iter().position(|&b| b == b'/')?;
+        let path = percent_decode(&rest[path_start..]);
+        Some(Osc133EventKind::WorkingDirChange { path })
+    }
+
     fn parse_osc133(buf: &[u8]) -> Option<Osc133EventKind> {
         // Expected format: ESC ] 1 3 3 ; <payload> BEL
         // Minimum: \x1b ] 1 3 3 ; X \x07 = 8 bytes
@@ -115,6 +130,7 @@ impl Osc133Detector {
             b"A" => Some(Osc133EventKind::PromptStart),
             b"B" => Some(Osc133EventKind::CommandStart { command: None, cwd: None, original: None }),
             b"C" => Some(Osc133EventKind::OutputStart),
+            b"E" => Some(Osc133EventKind::ContinuationPrompt),
             b"NO_READLINE" => Some(Osc133EventKind::NoReadline),
             _ => {
                 // RL;... - readline line report
@@ -176,6 +192,25 @@ impl Osc133Detector {
     }
 }
 
+/// Decode `%XX` escapes in a file URL path; malformed escapes are kept as-is.
+fn percent_decode(data: &[u8]) -> String {
+    let mut out = Vec::with_capacity(data.len());
+    let mut i = 0;
+    while i < data.len() {
+        if data[i] == b'%' && i + 2 < data.len() {
+            let hex = std::str::from_utf8(&data[i + 1..i + 3]).ok();
+            if let Some(b) = hex.and_then(|h| u8::from_str_radix(h, 16).ok()) {
+                out.push(b);
+                i += 3;
+                continue;
+            }
+        }
+        out.push(data[i]);
+        i += 1;
+    }
+    String::from_utf8_lossy(&out).into_owned()
+}
+
 /// Split a byte slice on unescaped ';' (backslash-semicolon is treated as literal).
 /// Returns unescaped String parts.
 fn split_unescaped_semicolon(data: &[u8]) -> Vec<String> {
@@ -199,10 +234,15 @@ fn split_unescaped_semicolon(data: &[u8]) -> Vec<String> {
     parts
 }
 
-/// Strip all OSC 133 sequences from a byte buffer.
+/// Strip all OSC 133 sequences from a byte buffer. OSC 7 is left in place
+/// so the outer terminal still learns the working directory.
 pub fn strip_osc133(data: &[u8]) -> Vec<u8> {
     let mut detector = Osc133Detector::new();
-    let events = detector.feed(data);
+    let events: Vec<_> = detector
+        .feed(data)
+        .into_iter()
+        .filter(|e| !matches!(e.kind, Osc133EventKind::WorkingDirChange { .. }))
+        .collect();
 
     if events.is_empty() {
         return data.to_vec();
@@ -255,6 +295,16 @@ mod tests {
         assert_eq!(events[0].kind, Osc133EventKind::OutputStart);
     }
 
+    #[test]
+    fn test_continuation_prompt() {
+        let mut detector = Osc133Detector::new();
+        let events = detector.feed(b"> \x1b]133;E\x07");
+        assert_eq!(events.len(), 1);
+        assert_eq!(events[0].kind, Osc133EventKind::ContinuationPrompt);
+        assert_eq!(events[0].start, 2);
+        assert_eq!(events[0].end, 10);
+    }
+
     #[test]
     fn test_command_end_with_exit_code() {
         let mut detector = Osc133Detector::new();
@@ -274,6 +324,13 @@ mod tests {
         );
     }
 
+    #[test]
+    fn test_command_end_negative_signal() {
+        let mut detector = Osc133Detector::new();
+        let events = detector.feed(b"\x1b]133;D;-9\x07");
+        assert_eq!(events[0].kind, Osc133EventKind::CommandEnd { exit_code: -9 });
+    }
+
     #[test]
     fn test_sequence_split_across_chunks() {
         let mut detector = Osc133Detector::new();
@@ -311,6 +368,36 @@ mod tests {
         assert_eq!(events[1].end, 16);
     }
 
+    #[test]
+    fn test_osc7_working_dir_change() {
+        let mut detector = Osc133Detector::new();
+        let events = detector.feed(b"\x1b]7;file://myhost/tmp/foo\x07");
+        assert_eq!(events.len(), 1);
+        assert_eq!(
+            events[0].kind,
+            Osc133EventKind::WorkingDirChange { path: "/tmp/foo".into() }
+        );
+        assert_eq!(events[0].start, 0);
+        assert_eq!(events[0].end, 26);
+    }
+
+    #[test]
+    fn test_osc7_empty_host_and_percent_escapes() {
+        let mut detector = Osc133Detector::new();
+        let events = detector.feed(b"\x1b]7;file:///home/me/My%20Docs\x07");
+        assert_eq!(
+            events[0].kind,
+            Osc133EventKind::WorkingDirChange { path: "/home/me/My Docs".into() }
+        );
+        assert!(detector.feed(b"\x1b]7;http://x/y\x07").is_empty());
+    }
+
+    #[test]
+    fn test_strip_keeps_osc7() {
+        let data = b"\x1b]133;A\x07\x1b]7;file://h/tmp\x07$ ";
+        assert_eq!(strip_osc133(data), b"\x1b]7;file://h/tmp\x07$ ".to_vec());
+    }
+
     #[test]
     fn test_ignores_other_osc_sequences() {
         let mut detector = Osc133Detector::new();
@@ -41,6 +41,14 @@ impl PromptDetector {
         }
     }
 
+    /// Detect prompts with a single, already compiled pattern.
+    pub fn with_regex(pattern: Regex) -> Self {
+        Self {
+            patterns: vec![pattern],
+            line_buf: Vec::new(),
+        }
+    }
+
     pub fn feed(&mut self, data: &[u8]) -> Vec<PromptEvent> {
         let mut events = Vec::new();
         let mut line_start_offset = 0;
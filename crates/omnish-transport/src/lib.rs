@@ -1,4 +1,6 @@
+pub mod reconnect;
 pub mod rpc_client;
+pub mod rpc_pool;
 pub mod rpc_server;
 pub mod tls;
 
@@ -6,10 +8,14 @@ pub mod tls;
 pub enum TransportAddr {
     Unix(String),
     Tcp(String),
+    /// `tls://host:port`: TCP that must be wrapped in TLS.
+    TcpTls(String),
 }
 
 pub fn parse_addr(addr: &str) -> TransportAddr {
-    if let Some(hp) = addr.strip_prefix("tcp://") {
+    if let Some(hp) = addr.strip_prefix("tls://") {
+        TransportAddr::TcpTls(hp.to_string())
+    } else if let Some(hp) = addr.strip_prefix("tcp://") {
         TransportAddr::Tcp(hp.to_string())
     } else if !addr.starts_with('/') && !addr.starts_with('.') && addr.contains(':') {
         TransportAddr::Tcp(addr.to_string())
@@ -69,4 +75,12 @@ mod tests {
             _ => panic!("expected Tcp"),
         }
     }
+
+    #[test]
+    fn test_parse_tls_prefix() {
+        match parse_addr("tls://daemon.example.com:9500") {
+            TransportAddr::TcpTls(hp) => assert_eq!(hp, "daemon.example.com:9500"),
+            _ => panic!("expected TcpTls"),
+        }
+    }
 }
@@ -0,0 +1,89 @@
+use rand::Rng;
+use std::time::Duration;
+
+/// Delay schedule for `RpcClient` reconnect attempts: `initial_ms`, doubling
+/// per failed attempt up to `max_ms`, each spread by +/- `jitter_fraction`
+/// so clients don't all reconnect at once after a daemon restart.
+#[derive(Debug, Clone, Copy, PartialEq)]
+pub struct BackoffConfig {
+    pub initial_ms: u64,
+    pub max_ms: u64,
+    pub jitter_fraction: f64,
+}
+
+impl Default for BackoffConfig {
+    fn default() -> Self {
+        Self {
+            initial_ms: 100,
+            max_ms: 30_000,
+            jitter_fraction: 0.25,
+        }
+    }
+}
+
+/// Walks a `BackoffConfig` schedule, one delay per attempt.
+pub struct Backoff {
+    config: BackoffConfig,
+    base_ms: u64,
+}
+
+impl Backoff {
+    pub fn new(config: BackoffConfig) -> Self {
+        Self {
+            config,
+            base_ms: config.initial_ms.min(config.max_ms),
+        }
+    }
+
+    /// Delay before the next attempt. Advances the schedule.
+    pub fn next_delay(&mut self) -> Duration {
+        let base = self.base_ms;
+        self.base_ms = base.saturating_mul(2).min(self.config.max_ms);
+        let unit = rand::thread_rng().gen_range(-1.0..=1.0);
+        Duration::from_millis(jittered(base, &self.config, unit))
+    }
+}
+
+/// `base_ms` moved by `unit` (in [-1, 1]) times the jitter fraction,
+/// never above `max_ms`.
+fn jittered(base_ms: u64, config: &BackoffConfig, unit: f64) -> u64 {
+    let fraction = config.jitter_fraction.clamp(0.0, 1.0);
+    let ms = base_ms as f64 * (1.0 + fraction * unit.clamp(-1.0, 1.0));
+    (ms.round() as u64).min(config.max_ms)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_delays_double_up_to_max() {
+        let config = BackoffConfig { initial_ms: 100, max_ms: 1000, jitter_fraction: 0.0 };
+        let mut backoff = Backoff::new(config);
+        let delays: Vec<u64> = (0..6).map(|_| backoff.next_delay().as_millis() as u64).collect();
+        assert_eq!(delays, vec![100, 200, 400, 800, 1000, 1000]);
+    }
+
+    #[test]
+    fn test_jitter_stays_in_bounds_and_under_max() {
+        let config = BackoffConfig::default();
+        let mut backoff = Backoff::new(config);
+        let mut base = config.initial_ms;
+        for _ in 0..20 {
+            let delay = backoff.next_delay().as_millis() as u64;
+            let lo = (base as f64 * 0.75).floor() as u64;
+            let hi = ((base as f64 * 1.25).ceil() as u64).min(config.max_ms);
+            assert!((lo..=hi).contains(&delay), "{} not in {}..={}", delay, lo, hi);
+            base = (base * 2).min(config.max_ms);
+        }
+    }
+
+    #[test]
+    fn test_jittered_extremes() {
+        let config = BackoffConfig { initial_ms: 100, max_ms: 1000, jitter_fraction: 0.25 };
+        assert_eq!(jittered(400, &config, -1.0), 300);
+        assert_eq!(jittered(400, &config, 1.0), 500);
+        // Upward jitter never pushes past max_ms
+        assert_eq!(jittered(1000, &config, 1.0), 1000);
+    }
+}
@@ -1,3 +1,4 @@
+use crate::reconnect::{Backoff, BackoffConfig};
 use crate::{parse_addr, TransportAddr};
 use anyhow::Result;
 use omnish_protocol::message::{Frame, Message};
@@ -17,14 +18,15 @@ impl std::fmt::Display for PermanentFailure {
 impl std::error::Error for PermanentFailure {}
 use std::collections::HashMap;
 use std::future::Future;
+use std::path::Path;
 use std::pin::Pin;
 use std::sync::atomic::{AtomicBool, AtomicU64, Ordering};
 use std::sync::Arc;
 use tokio::io::{AsyncRead, AsyncReadExt, AsyncWrite, AsyncWriteExt};
 use tokio::net::{TcpStream, UnixStream};
-use tokio::sync::{mpsc, oneshot, Mutex};
+use tokio::sync::{mpsc, oneshot, Mutex, Notify};
 use tokio::task::JoinHandle;
-use tokio_rustls::TlsConnector;
+use crate::tls::ClientTls;
 
 enum ReplyTx {
     Once(oneshot::Sender<Message>),
@@ -42,9 +44,31 @@ struct Inner {
     connected: Arc<AtomicBool>,
     _write_task: JoinHandle<()>,
     _read_task: JoinHandle<()>,
+    _heartbeat_task: JoinHandle<()>,
     _push_tx: mpsc::Sender<Message>,
 }
 
+/// Default time between keepalive pings on a connection.
+pub const KEEPALIVE_INTERVAL: std::time::Duration = std::time::Duration::from_secs(30);
+/// Default time to wait for the `Pong` before the connection is failed.
+pub const KEEPALIVE_TIMEOUT: std::time::Duration = std::time::Duration::from_secs(5);
+
+/// Keepalive timing, shared by every connection an RpcClient makes
+/// (including reconnects). An interval of 0 disables pings.
+struct Keepalive {
+    interval_ms: AtomicU64,
+    timeout_ms: AtomicU64,
+}
+
+impl Keepalive {
+    fn new() -> Arc<Self> {
+        Arc::new(Self {
+            interval_ms: AtomicU64::new(KEEPALIVE_INTERVAL.as_millis() as u64),
+            timeout_ms: AtomicU64::new(KEEPALIVE_TIMEOUT.as_millis() as u64),
+        })
+    }
+}
+
 /// Epoch millis after which reconnection is allowed again.
 /// 0 means no suppression. Shared between RpcClient and the reconnect loop.
 type SuppressUntil = Arc<AtomicU64>;
@@ -72,7 +96,7 @@ type ReconnectFn = Arc<
 
 type NotifyFn = Arc<dyn Fn() + Send + Sync>;
 
-fn make_connector(addr: &str, tls_connector: Option<TlsConnector>) -> ConnectorFn {
+fn make_connector(addr: &str, tls_connector: Option<ClientTls>) -> ConnectorFn {
     let addr = addr.to_string();
     Arc::new(move || {
         let addr = addr.clone();
@@ -87,14 +111,15 @@ fn make_connector(addr: &str, tls_connector: Option<TlsConnector>) -> ConnectorF
                         Box::new(w) as Box<dyn AsyncWrite + Unpin + Send>,
                     ))
                 }
-                TransportAddr::Tcp(hp) => {
+                TransportAddr::TcpTls(_) if tls.is_none() => {
+                    anyhow::bail!("{} requires TLS but no TLS connector was configured", addr)
+                }
+                TransportAddr::Tcp(hp) | TransportAddr::TcpTls(hp) => {
                     let stream = TcpStream::connect(&hp).await?;
                     stream.set_nodelay(true)?;
                     if let Some(ref tls) = tls {
-                        let domain = rustls::pki_types::ServerName::try_from("localhost")
-                            .unwrap()
-                            .to_owned();
-                        let tls_stream = tls.connect(domain, stream).await?;
+                        let domain = rustls::pki_types::ServerName::try_from(tls.server_name(&hp).to_string())?;
+                        let tls_stream = tls.connector.connect(domain, stream).await?;
                         let (r, w) = tokio::io::split(tls_stream);
                         Ok((
                             Box::new(r) as Box<dyn AsyncRead + Unpin + Send>,
@@ -119,6 +144,7 @@ pub struct RpcClient {
     next_id: Arc<AtomicU64>,
     push_rx: Arc<Mutex<mpsc::Receiver<Message>>>,
     suppress_until: SuppressUntil,
+    keepalive: Arc<Keepalive>,
 }
 
 impl RpcClient {
@@ -126,12 +152,15 @@ impl RpcClient {
         let stream = UnixStream::connect(addr).await?;
         let (reader, writer) = stream.into_split();
         let (push_tx, push_rx) = mpsc::channel::<Message>(64);
-        let inner = Self::create_inner(reader, writer, None, push_tx);
+        let next_id = Arc::new(AtomicU64::new(1));
+        let keepalive = Keepalive::new();
+        let inner = Self::create_inner(reader, writer, None, push_tx, next_id.clone(), keepalive.clone());
         Ok(Self {
             inner: Arc::new(Mutex::new(inner)),
-            next_id: Arc::new(AtomicU64::new(1)),
+            next_id,
             push_rx: Arc::new(Mutex::new(push_rx)),
             suppress_until: Arc::new(AtomicU64::new(0)),
+            keepalive,
         })
     }
 
@@ -140,12 +169,38 @@ impl RpcClient {
         stream.set_nodelay(true)?;
         let (reader, writer) = stream.into_split();
         let (push_tx, push_rx) = mpsc::channel::<Message>(64);
-        let inner = Self::create_inner(reader, writer, None, push_tx);
+        let next_id = Arc::new(AtomicU64::new(1));
+        let keepalive = Keepalive::new();
+        let inner = Self::create_inner(reader, writer, None, push_tx, next_id.clone(), keepalive.clone());
+        Ok(Self {
+            inner: Arc::new(Mutex::new(inner)),
+            next_id,
+            push_rx: Arc::new(Mutex::new(push_rx)),
+            suppress_until: Arc::new(AtomicU64::new(0)),
+            keepalive,
+        })
+    }
+
+    /// Connect over TCP and wrap the stream in TLS, verifying the server as
+    /// `server_name` against `ca_cert` (default: the daemon's cert in
+    /// `~/.omnish/tls/cert.pem`). See `tls::server_name` for the name to use.
+    pub async fn connect_tls(addr: &str, server_name: &str, ca_cert: Option<&Path>) -> Result<Self> {
+        let connector = ClientTls::new(ca_cert)?.connector;
+        let domain = rustls::pki_types::ServerName::try_from(server_name.to_string())?;
+        let stream = TcpStream::connect(addr).await?;
+        stream.set_nodelay(true)?;
+        let tls_stream = connector.connect(domain, stream).await?;
+        let (reader, writer) = tokio::io::split(tls_stream);
+        let (push_tx, push_rx) = mpsc::channel::<Message>(64);
+        let next_id = Arc::new(AtomicU64::new(1));
+        let keepalive = Keepalive::new();
+        let inner = Self::create_inner(reader, writer, None, push_tx, next_id.clone(), keepalive.clone());
         Ok(Self {
             inner: Arc::new(Mutex::new(inner)),
-            next_id: Arc::new(AtomicU64::new(1)),
+            next_id,
             push_rx: Arc::new(Mutex::new(push_rx)),
             suppress_until: Arc::new(AtomicU64::new(0)),
+            keepalive,
         })
     }
 
@@ -153,6 +208,7 @@ impl RpcClient {
         match parse_addr(addr) {
             TransportAddr::Unix(p) => Self::connect_unix(&p).await,
             TransportAddr::Tcp(hp) => Self::connect_tcp(&hp).await,
+            TransportAddr::TcpTls(hp) => Self::connect_tls(&hp, crate::tls::server_name(&hp, None), None).await,
         }
     }
 
@@ -161,6 +217,8 @@ impl RpcClient {
         writer: W,
         disconnect_tx: Option<oneshot::Sender<()>>,
         push_tx: mpsc::Sender<Message>,
+        next_id: Arc<AtomicU64>,
+        keepalive: Arc<Keepalive>,
     ) -> Inner
     where
         R: AsyncRead + Unpin + Send + 'static,
@@ -171,6 +229,7 @@ impl RpcClient {
         let (tx, rx) = mpsc::channel::<WriteRequest>(256);
 
         let connected = Arc::new(AtomicBool::new(true));
+        let dead = Arc::new(Notify::new());
 
         let write_connected = connected.clone();
         let write_pending = pending.clone();
@@ -185,6 +244,15 @@ impl RpcClient {
             read_connected,
             disconnect_tx,
             push_tx.clone(),
+            dead.clone(),
+        ));
+
+        let _heartbeat_task = tokio::spawn(Self::heartbeat_loop(
+            tx.downgrade(),
+            next_id,
+            connected.clone(),
+            keepalive,
+            dead,
         ));
 
         Inner {
@@ -192,6 +260,7 @@ impl RpcClient {
             connected,
             _write_task,
             _read_task,
+            _heartbeat_task,
             _push_tx: push_tx,
         }
     }
@@ -208,7 +277,7 @@ impl RpcClient {
 
     pub async fn connect_with_reconnect(
         addr: &str,
-        tls_connector: Option<TlsConnector>,
+        tls_connector: Option<ClientTls>,
         on_reconnect: impl Fn(&RpcClient) -> Pin<Box<dyn Future<Output = Result<()>> + Send>>
             + Send
             + Sync
@@ -219,7 +288,7 @@ impl RpcClient {
 
     pub async fn connect_with_reconnect_notify(
         addr: &str,
-        tls_connector: Option<TlsConnector>,
+        tls_connector: Option<ClientTls>,
         on_reconnect: impl Fn(&RpcClient) -> Pin<Box<dyn Future<Output = Result<()>> + Send>>
             + Send
             + Sync
@@ -231,13 +300,37 @@ impl RpcClient {
 
     pub async fn connect_with_reconnect_full(
         addr: &str,
-        tls_connector: Option<TlsConnector>,
+        tls_connector: Option<ClientTls>,
+        on_reconnect: impl Fn(&RpcClient) -> Pin<Box<dyn Future<Output = Result<()>> + Send>>
+            + Send
+            + Sync
+            + 'static,
+        on_reconnect_notify: Option<impl Fn() + Send + Sync + 'static>,
+        on_disconnect: Option<impl Fn() + Send + Sync + 'static>,
+    ) -> Result<Self> {
+        Self::connect_with_reconnect_backoff(
+            addr,
+            tls_connector,
+            on_reconnect,
+            on_reconnect_notify,
+            on_disconnect,
+            BackoffConfig::default(),
+        )
+        .await
+    }
+
+    /// Like `connect_with_reconnect_full`, with the delay between reconnect
+    /// attempts following `backoff`.
+    pub async fn connect_with_reconnect_backoff(
+        addr: &str,
+        tls_connector: Option<ClientTls>,
         on_reconnect: impl Fn(&RpcClient) -> Pin<Box<dyn Future<Output = Result<()>> + Send>>
             + Send
             + Sync
             + 'static,
         on_reconnect_notify: Option<impl Fn() + Send + Sync + 'static>,
         on_disconnect: Option<impl Fn() + Send + Sync + 'static>,
+        backoff: BackoffConfig,
     ) -> Result<Self> {
         let notify_fn: Option<NotifyFn> = on_reconnect_notify.map(|f| Arc::new(f) as NotifyFn);
         let disconnect_fn: Option<NotifyFn> = on_disconnect.map(|f| Arc::new(f) as NotifyFn);
@@ -251,8 +344,9 @@ impl RpcClient {
                 // Initial connection succeeded - normal flow
                 let (disc_tx, disc_rx) = oneshot::channel::<()>();
                 let (push_tx, push_rx) = mpsc::channel::<Message>(64);
-                let inner = Self::create_inner(reader, writer, Some(disc_tx), push_tx);
                 let next_id = Arc::new(AtomicU64::new(1));
+                let keepalive = Keepalive::new();
+                let inner = Self::create_inner(reader, writer, Some(disc_tx), push_tx, next_id.clone(), keepalive.clone());
                 let push_rx = Arc::new(Mutex::new(push_rx));
                 let suppress_until = Arc::new(AtomicU64::new(0));
                 let client = Self {
@@ -260,6 +354,7 @@ impl RpcClient {
                     next_id: next_id.clone(),
                     push_rx: push_rx.clone(),
                     suppress_until: suppress_until.clone(),
+                    keepalive: keepalive.clone(),
                 };
 
                 // Call on_reconnect for initial registration
@@ -280,6 +375,8 @@ impl RpcClient {
                     disc_rx,
                     push_rx,
                     suppress_until,
+                    keepalive,
+                    backoff,
                 ));
 
                 Ok(client)
@@ -299,10 +396,12 @@ impl RpcClient {
                     connected,
                     _write_task: tokio::spawn(async {}), // dummy task
                     _read_task: tokio::spawn(async {}),  // dummy task
+                    _heartbeat_task: tokio::spawn(async {}), // dummy task
                     _push_tx: push_tx,
                 };
 
                 let next_id = Arc::new(AtomicU64::new(1));
+                let keepalive = Keepalive::new();
                 let push_rx = Arc::new(Mutex::new(push_rx));
                 let suppress_until = Arc::new(AtomicU64::new(0));
                 let client = Self {
@@ -310,6 +409,7 @@ impl RpcClient {
                     next_id: next_id.clone(),
                     push_rx: push_rx.clone(),
                     suppress_until: suppress_until.clone(),
+                    keepalive: keepalive.clone(),
                 };
 
                 // Create a oneshot channel that's already closed to trigger immediate reconnection
@@ -331,6 +431,8 @@ impl RpcClient {
                     disc_rx,
                     push_rx,
                     suppress_until,
+                    keepalive,
+                    backoff,
                 ));
 
                 Ok(client)
@@ -349,6 +451,8 @@ impl RpcClient {
         mut disc_rx: oneshot::Receiver<()>,
         push_rx: Arc<Mutex<mpsc::Receiver<Message>>>,
         suppress_until: SuppressUntil,
+        keepalive: Arc<Keepalive>,
+        backoff_config: BackoffConfig,
     ) {
         loop {
             // Wait for disconnect notification
@@ -380,14 +484,13 @@ impl RpcClient {
                 suppress_until.store(0, Ordering::Relaxed);
             }
 
-            // Exponential backoff reconnection
-            let mut backoff_ms: u64 = 1000;
-            let max_backoff_ms: u64 = 30_000;
+            // Exponential backoff reconnection, with jitter
+            let mut backoff = Backoff::new(backoff_config);
             let mut consecutive_auth_failures: u32 = 0;
             const MAX_AUTH_FAILURES: u32 = 5;
 
             loop {
-                tokio::time::sleep(std::time::Duration::from_millis(backoff_ms)).await;
+                tokio::time::sleep(backoff.next_delay()).await;
 
                 // Try to connect
                 let (reader, writer) = match connector().await {
@@ -395,14 +498,20 @@ impl RpcClient {
                     Err(_) => {
                         // Connection failure resets auth failure counter (daemon may be down)
                         consecutive_auth_failures = 0;
-                        backoff_ms = (backoff_ms * 2).min(max_backoff_ms);
                         continue;
                     }
                 };
 
                 let (new_disc_tx, new_disc_rx) = oneshot::channel::<()>();
                 let (new_push_tx, new_push_rx) = mpsc::channel::<Message>(64);
-                let new_inner = Self::create_inner(reader, writer, Some(new_disc_tx), new_push_tx);
+                let new_inner = Self::create_inner(
+                    reader,
+                    writer,
+                    Some(new_disc_tx),
+                    new_push_tx,
+                    next_id.clone(),
+                    keepalive.clone(),
+                );
 
                 // Create a temporary client wrapping the new inner for the callback
                 let dummy_push_rx = Arc::new(Mutex::new(mpsc::channel::<Message>(1).1));
@@ -411,6 +520,7 @@ impl RpcClient {
                     next_id: next_id.clone(),
                     push_rx: dummy_push_rx,
                     suppress_until: Arc::new(AtomicU64::new(0)),
+                    keepalive: keepalive.clone(),
                 };
 
                 // Call on_reconnect with the temp client
@@ -425,7 +535,6 @@ impl RpcClient {
                             return;
                         }
                     }
-                    backoff_ms = (backoff_ms * 2).min(max_backoff_ms);
                     continue;
                 }
                 consecutive_auth_failures = 0;
@@ -435,10 +544,7 @@ impl RpcClient {
                 drop(temp_client);
                 let temp_inner_mutex = match Arc::try_unwrap(temp_inner_arc) {
                     Ok(m) => m,
-                    Err(_) => {
-                        backoff_ms = (backoff_ms * 2).min(max_backoff_ms);
-                        continue;
-                    }
+                    Err(_) => continue,
                 };
                 let new_inner = temp_inner_mutex.into_inner();
 
@@ -466,10 +572,20 @@ impl RpcClient {
         }
     }
 
+    /// False once the connection has dropped, including when the last
+    /// keepalive ping went unanswered.
     pub async fn is_connected(&self) -> bool {
         self.inner.lock().await.connected.load(Ordering::SeqCst)
     }
 
+    /// Change the keepalive timing (defaults: `KEEPALIVE_INTERVAL`,
+    /// `KEEPALIVE_TIMEOUT`). A zero `interval` disables pings. Takes effect
+    /// after the current interval elapses.
+    pub fn set_keepalive(&self, interval: std::time::Duration, timeout: std::time::Duration) {
+        self.keepalive.interval_ms.store(interval.as_millis() as u64, Ordering::Relaxed);
+        self.keepalive.timeout_ms.store(timeout.as_millis() as u64, Ordering::Relaxed);
+    }
+
     /// Non-blocking receive of a push message (server-initiated, request_id=0).
     pub async fn try_recv_push(&self) -> Option<Message> {
         self.push_rx.lock().await.try_recv().ok()
@@ -508,6 +624,13 @@ impl RpcClient {
             .map_err(|_| anyhow::anyhow!("read task closed before response"))
     }
 
+    /// `call` that gives up after `timeout`. A reply arriving later is dropped.
+    pub async fn call_with_timeout(&self, msg: Message, timeout: std::time::Duration) -> Result<Message> {
+        tokio::time::timeout(timeout, self.call(msg))
+            .await
+            .map_err(|_| anyhow::anyhow!("rpc timeout after {}ms", timeout.as_millis()))?
+    }
+
     /// Fire-and-forget: send a message without waiting for a response.
     /// Returns Ok(()) once the message is queued for writing.
     pub async fn send(&self, msg: Message) -> Result<()> {
@@ -592,9 +715,17 @@ impl RpcClient {
         connected: Arc<AtomicBool>,
         disconnect_tx: Option<oneshot::Sender<()>>,
         push_tx: mpsc::Sender<Message>,
+        dead: Arc<Notify>,
     ) {
-        while let Ok(l) = reader.read_u32().await {
-            let len = l as usize;
+        loop {
+            // A failed keepalive ends the loop even if the peer went silent
+            let len = tokio::select! {
+                r = reader.read_u32() => match r {
+                    Ok(l) => l as usize,
+                    Err(_) => break,
+                },
+                _ = dead.notified() => break,
+            };
             let mut buf = vec![0u8; len];
             if reader.read_exact(&mut buf).await.is_err() {
                 break;
@@ -667,6 +798,63 @@ impl RpcClient {
             let _ = tx.send(());
         }
     }
+
+    /// Ping the peer every keepalive interval. If no reply arrives within
+    /// the timeout, mark the connection dead and wake the read loop so the
+    /// reconnect loop (if any) takes over. Exits once the connection's
+    /// write channel is gone or the connection has dropped.
+    async fn heartbeat_loop(
+        tx: mpsc::WeakSender<WriteRequest>,
+        next_id: Arc<AtomicU64>,
+        connected: Arc<AtomicBool>,
+        keepalive: Arc<Keepalive>,
+        dead: Arc<Notify>,
+    ) {
+        loop {
+            let interval_ms = keepalive.interval_ms.load(Ordering::Relaxed);
+            let enabled = interval_ms > 0;
+            let wait = if enabled {
+                std::time::Duration::from_millis(interval_ms)
+            } else {
+                KEEPALIVE_INTERVAL
+            };
+            tokio::time::sleep(wait).await;
+            if !connected.load(Ordering::SeqCst) {
+                return;
+            }
+            let Some(tx) = tx.upgrade() else { return };
+            if !enabled {
+                continue;
+            }
+
+            let timestamp_ms = std::time::SystemTime::now()
+                .duration_since(std::time::UNIX_EPOCH)
+                .unwrap_or_default()
+                .as_millis() as u64;
+            let frame = Frame {
+                request_id: next_id.fetch_add(1, Ordering::Relaxed),
+                payload: Message::Ping { timestamp_ms },
+            };
+            let (reply_tx, reply_rx) = oneshot::channel();
+            if tx.send(WriteRequest { frame, reply_tx: ReplyTx::Once(reply_tx) }).await.is_err() {
+                return;
+            }
+            drop(tx);
+
+            let timeout = std::time::Duration::from_millis(keepalive.timeout_ms.load(Ordering::Relaxed));
+            match tokio::time::timeout(timeout, reply_rx).await {
+                Ok(Ok(_)) => {}
+                // Reply sender dropped: the read/write loop already saw the drop
+                Ok(Err(_)) => return,
+                Err(_) => {
+                    tracing::warn!("no pong within {}ms, dropping connection", timeout.as_millis());
+                    connected.store(false, Ordering::SeqCst);
+                    dead.notify_one();
+                    return;
+                }
+            }
+        }
+    }
 }
 
 #[cfg(test)]
@@ -731,6 +919,45 @@ mod tests {
         server.await.unwrap();
     }
 
+    #[tokio::test]
+    async fn test_call_with_timeout_expires() {
+        let dir = tempfile::tempdir().unwrap();
+        let sock_path = dir.path().join("slow.sock");
+        let sock_path_str = sock_path.to_str().unwrap().to_string();
+
+        let listener = UnixListener::bind(&sock_path).unwrap();
+        // Daemon that takes 5s to answer
+        let server = tokio::spawn(async move {
+            let (mut stream, _) = listener.accept().await.unwrap();
+            let frame = read_frame(&mut stream).await.unwrap();
+            tokio::time::sleep(std::time::Duration::from_secs(5)).await;
+            let reply = Frame { request_id: frame.request_id, payload: Message::Ack };
+            let _ = write_frame(&mut stream, &reply).await;
+        });
+
+        let client = RpcClient::connect_unix(&sock_path_str).await.unwrap();
+        let msg = Message::Request(Request {
+            request_id: "r1".to_string(),
+            session_id: "s1".to_string(),
+            query: "slow question".to_string(),
+            scope: RequestScope::CurrentSession,
+            model_override: None,
+            timeout_ms: Some(1000),
+            format_hint: None,
+        });
+        let start = std::time::Instant::now();
+        let err = client
+            .call_with_timeout(msg, std::time::Duration::from_secs(1))
+            .await
+            .unwrap_err();
+        let elapsed = start.elapsed();
+        assert_eq!(err.to_string(), "rpc timeout after 1000ms");
+        assert!(elapsed >= std::time::Duration::from_secs(1), "{:?}", elapsed);
+        assert!(elapsed < std::time::Duration::from_secs(3), "{:?}", elapsed);
+
+        server.abort();
+    }
+
     #[tokio::test]
     async fn test_rpc_client_concurrent_calls() {
         let dir = tempfile::tempdir().unwrap();
@@ -787,6 +1014,9 @@ mod tests {
             session_id: "s1".to_string(),
             query: "what happened?".to_string(),
             scope: RequestScope::CurrentSession,
+            model_override: None,
+            timeout_ms: None,
+            format_hint: None,
         });
 
         let (io_resp, req_resp) = tokio::join!(client.call(io_msg), client.call(req_msg));
@@ -989,6 +1219,128 @@ mod tests {
         assert!(!client.is_connected().await);
     }
 
+    /// Client over an in-memory pipe; returns the server end. Unlike a
+    /// socket, the runtime sees duplex wakeups, so paused time only
+    /// auto-advances once both ends are idle.
+    fn client_over_duplex() -> (RpcClient, tokio::io::DuplexStream) {
+        let (client_end, server_end) = tokio::io::duplex(64 * 1024);
+        let (reader, writer) = tokio::io::split(client_end);
+        let (push_tx, push_rx) = mpsc::channel::<Message>(64);
+        let next_id = Arc::new(AtomicU64::new(1));
+        let keepalive = Keepalive::new();
+        let inner = RpcClient::create_inner(reader, writer, None, push_tx, next_id.clone(), keepalive.clone());
+        let client = RpcClient {
+            inner: Arc::new(Mutex::new(inner)),
+            next_id,
+            push_rx: Arc::new(Mutex::new(push_rx)),
+            suppress_until: Arc::new(AtomicU64::new(0)),
+            keepalive,
+        };
+        (client, server_end)
+    }
+
+    #[tokio::test(start_paused = true)]
+    async fn test_keepalive_detects_silent_connection() {
+        let (client, mut server) = client_over_duplex();
+
+        // Server: reads frames but never answers, like a half-dead NAT mapping
+        let (ping_tx, mut ping_rx) = mpsc::channel::<u64>(4);
+        tokio::spawn(async move {
+            while let Ok(frame) = read_frame(&mut server).await {
+                if let Message::Ping { timestamp_ms } = frame.payload {
+                    let _ = ping_tx.send(timestamp_ms).await;
+                }
+            }
+        });
+        assert!(client.is_connected().await);
+
+        tokio::time::sleep(KEEPALIVE_INTERVAL + std::time::Duration::from_secs(1)).await;
+        assert!(ping_rx.recv().await.is_some(), "ping sent after the interval");
+        assert!(client.is_connected().await, "still within the pong timeout");
+
+        tokio::time::sleep(KEEPALIVE_TIMEOUT).await;
+        assert!(!client.is_connected().await);
+    }
+
+    #[tokio::test(start_paused = true)]
+    async fn test_reconnect_delays_follow_backoff() {
+        let config = BackoffConfig { initial_ms: 100, max_ms: 1000, jitter_fraction: 0.25 };
+
+        // Connector that always fails, recording when each attempt happened
+        let attempts: Arc<std::sync::Mutex<Vec<tokio::time::Instant>>> = Arc::default();
+        let recorded = attempts.clone();
+        let connector: ConnectorFn = Arc::new(move || {
+            recorded.lock().unwrap().push(tokio::time::Instant::now());
+            Box::pin(async { Err(anyhow::anyhow!("daemon down")) })
+        });
+        let on_reconnect: ReconnectFn = Arc::new(|_| Box::pin(async { Ok(()) }));
+
+        let (push_tx, push_rx) = mpsc::channel::<Message>(1);
+        let inner = Inner {
+            tx: mpsc::channel::<WriteRequest>(1).0,
+            connected: Arc::new(AtomicBool::new(false)),
+            _write_task: tokio::spawn(async {}),
+            _read_task: tokio::spawn(async {}),
+            _heartbeat_task: tokio::spawn(async {}),
+            _push_tx: push_tx,
+        };
+        let (disc_tx, disc_rx) = oneshot::channel::<()>();
+        disc_tx.send(()).unwrap();
+        let start = tokio::time::Instant::now();
+        let task = tokio::spawn(RpcClient::reconnect_loop(
+            Arc::new(Mutex::new(inner)),
+            Arc::new(AtomicU64::new(1)),
+            connector,
+            on_reconnect,
+            None,
+            None,
+            disc_rx,
+            Arc::new(Mutex::new(push_rx)),
+            Arc::new(AtomicU64::new(0)),
+            Keepalive::new(),
+            config,
+        ));
+
+        // Step the clock 1ms at a time so each attempt is stamped exactly
+        while attempts.lock().unwrap().len() < 7 {
+            tokio::time::advance(std::time::Duration::from_millis(1)).await;
+            tokio::task::yield_now().await;
+        }
+        task.abort();
+
+        let times = attempts.lock().unwrap().clone();
+        let mut prev = start;
+        let mut base = config.initial_ms;
+        for t in times {
+            let delay = (t - prev).as_millis() as u64;
+            let lo = (base as f64 * 0.75) as u64;
+            let hi = ((base as f64 * 1.25) as u64).min(config.max_ms) + 1;
+            assert!((lo..=hi).contains(&delay), "delay {}ms outside {}..={} (base {})", delay, lo, hi, base);
+            assert!(delay <= config.max_ms + 1, "delay {}ms above max", delay);
+            prev = t;
+            base = (base * 2).min(config.max_ms);
+        }
+    }
+
+    #[tokio::test(start_paused = true)]
+    async fn test_keepalive_answered_stays_connected() {
+        let (client, mut server) = client_over_duplex();
+
+        tokio::spawn(async move {
+            while let Ok(frame) = read_frame(&mut server).await {
+                if let Message::Ping { timestamp_ms } = frame.payload {
+                    let reply = Frame { request_id: frame.request_id, payload: Message::Pong { timestamp_ms } };
+                    write_frame(&mut server, &reply).await.unwrap();
+                }
+            }
+        });
+        client.set_keepalive(std::time::Duration::from_secs(10), std::time::Duration::from_secs(2));
+
+        // First sleep uses the default interval; later ones the new one
+        tokio::time::sleep(KEEPALIVE_INTERVAL + std::time::Duration::from_secs(60)).await;
+        assert!(client.is_connected().await);
+    }
+
     #[tokio::test]
     async fn test_rpc_client_tcp_call_returns_ack() {
         let listener = TcpListener::bind("127.0.0.1:0").await.unwrap();
@@ -1068,6 +1420,9 @@ mod tests {
             session_id: "s1".to_string(),
             query: "tcp test".to_string(),
             scope: RequestScope::CurrentSession,
+            model_override: None,
+            timeout_ms: None,
+            format_hint: None,
         });
 
         let (io_resp, req_resp) = tokio::join!(client.call(io_msg), client.call(req_msg));
@@ -1114,4 +1469,96 @@ mod tests {
 
         server.await.unwrap();
     }
+
+    #[tokio::test]
+    async fn test_rpc_client_tls_round_trip() {
+        let dir = tempfile::tempdir().unwrap();
+        crate::tls::load_or_create_cert(dir.path()).unwrap();
+        let acceptor = crate::tls::acceptor_from_files(
+            &dir.path().join("cert.pem"),
+            &dir.path().join("key.pem"),
+        )
+        .unwrap();
+        let listener = TcpListener::bind("127.0.0.1:0").await.unwrap();
+        let addr = listener.local_addr().unwrap().to_string();
+
+        let server = tokio::spawn(async move {
+            let (stream, _) = listener.accept().await.unwrap();
+            let mut stream = acceptor.accept(stream).await.unwrap();
+            let frame = read_frame(&mut stream).await.unwrap();
+            assert!(matches!(frame.payload, Message::SessionStart(_)));
+            let reply = Frame {
+                request_id: frame.request_id,
+                payload: Message::Ack,
+            };
+            write_frame(&mut stream, &reply).await.unwrap();
+        });
+
+        let client = RpcClient::connect_tls(
+            &addr,
+            crate::tls::server_host(&addr),
+            Some(&dir.path().join("cert.pem")),
+        )
+            .await
+            .unwrap();
+        let msg = Message::SessionStart(SessionStart {
+            session_id: "s1".to_string(),
+            parent_session_id: None,
+            timestamp_ms: 1000,
+            attrs: HashMap::new(),
+        });
+        let resp = client.call(msg).await.unwrap();
+        assert!(matches!(resp, Message::Ack));
+
+        server.await.unwrap();
+    }
+
+    #[tokio::test]
+    async fn test_rpc_client_tls_pinned_cert_connects_by_ip() {
+        // A certificate from before IP names were added: valid for localhost only
+        let dir = tempfile::tempdir().unwrap();
+        let cert = rcgen::generate_simple_self_signed(vec!["localhost".to_string()]).unwrap();
+        std::fs::write(dir.path().join("cert.pem"), cert.cert.pem()).unwrap();
+        std::fs::write(dir.path().join("key.pem"), cert.key_pair.serialize_pem()).unwrap();
+        let acceptor = crate::tls::make_acceptor(dir.path()).unwrap();
+        let listener = TcpListener::bind("127.0.0.1:0").await.unwrap();
+        let addr = format!("tcp://{}", listener.local_addr().unwrap());
+
+        let server = tokio::spawn(async move {
+            let (stream, _) = listener.accept().await.unwrap();
+            let mut stream = acceptor.accept(stream).await.unwrap();
+            let frame = read_frame(&mut stream).await.unwrap();
+            let reply = Frame {
+                request_id: frame.request_id,
+                payload: Message::Ack,
+            };
+            write_frame(&mut stream, &reply).await.unwrap();
+        });
+
+        let tls = ClientTls::pinned(&dir.path().join("cert.pem")).unwrap();
+        let client = RpcClient::connect_with_reconnect(&addr, Some(tls), |_| Box::pin(async { Ok(()) }))
+            .await
+            .unwrap();
+        let resp = client.call(Message::HealthCheck).await.unwrap();
+        assert!(matches!(resp, Message::Ack));
+
+        server.await.unwrap();
+    }
+
+    #[tokio::test]
+    async fn test_rpc_client_tls_rejects_wrong_server_name() {
+        let dir = tempfile::tempdir().unwrap();
+        crate::tls::load_or_create_cert(dir.path()).unwrap();
+        let acceptor = crate::tls::make_acceptor(dir.path()).unwrap();
+        let listener = TcpListener::bind("127.0.0.1:0").await.unwrap();
+        let addr = listener.local_addr().unwrap().to_string();
+        tokio::spawn(async move {
+            let (stream, _) = listener.accept().await.unwrap();
+            let _ = acceptor.accept(stream).await;
+        });
+
+        let result =
+            RpcClient::connect_tls(&addr, "other.example.com", Some(&dir.path().join("cert.pem"))).await;
+        assert!(result.is_err());
+    }
 }
@@ -0,0 +1,210 @@
+use crate::rpc_client::RpcClient;
+use anyhow::Result;
+use omnish_protocol::message::Message;
+use std::sync::atomic::{AtomicUsize, Ordering};
+use std::sync::Arc;
+use tokio::sync::mpsc;
+
+/// Several connections to the same daemon.
+///
+/// Calls on different members are not ordered relative to each other, so
+/// only independent `Request` calls are spread round-robin. Everything else
+/// (SessionStart, IoData, command events) goes through member 0, which also
+/// receives the daemon's push messages. A pool of size 1 behaves exactly
+/// like its single client.
+#[derive(Clone)]
+pub struct RpcClientPool {
+    clients: Arc<Vec<RpcClient>>,
+    next: Arc<AtomicUsize>,
+}
+
+impl RpcClientPool {
+    /// Open `size` connections to `addr` (see `RpcClient::connect`).
+    pub async fn connect_pool(addr: &str, size: usize) -> Result<Self> {
+        if size == 0 {
+            anyhow::bail!("connection pool size must be at least 1");
+        }
+        let mut clients = Vec::with_capacity(size);
+        for _ in 0..size {
+            clients.push(RpcClient::connect(addr).await?);
+        }
+        Ok(Self::from_clients(clients))
+    }
+
+    /// Wrap already-connected clients; the first one is member 0.
+    /// `clients` must not be empty.
+    pub fn from_clients(clients: Vec<RpcClient>) -> Self {
+        assert!(!clients.is_empty(), "RpcClientPool needs at least one client");
+        Self {
+            clients: Arc::new(clients),
+            next: Arc::new(AtomicUsize::new(0)),
+        }
+    }
+
+    pub fn size(&self) -> usize {
+        self.clients.len()
+    }
+
+    /// Member 0, which carries all session-ordered traffic.
+    pub fn primary(&self) -> &RpcClient {
+        &self.clients[0]
+    }
+
+    /// The member the next independent call will use; advances the
+    /// round-robin cursor.
+    pub fn next_client(&self) -> &RpcClient {
+        let i = self.next.fetch_add(1, Ordering::Relaxed) % self.clients.len();
+        &self.clients[i]
+    }
+
+    /// The member `msg` should be sent on.
+    pub fn client_for(&self, msg: &Message) -> &RpcClient {
+        match msg {
+            Message::Request(_) => self.next_client(),
+            _ => self.primary(),
+        }
+    }
+
+    pub async fn call(&self, msg: Message) -> Result<Message> {
+        self.client_for(&msg).call(msg).await
+    }
+
+    pub async fn call_with_timeout(&self, msg: Message, timeout: std::time::Duration) -> Result<Message> {
+        self.client_for(&msg).call_with_timeout(msg, timeout).await
+    }
+
+    pub async fn send(&self, msg: Message) -> Result<()> {
+        self.client_for(&msg).send(msg).await
+    }
+
+    pub async fn call_stream(&self, msg: Message) -> Result<mpsc::Receiver<Message>> {
+        self.client_for(&msg).call_stream(msg).await
+    }
+
+    /// Non-blocking receive of a push message from member 0.
+    pub async fn try_recv_push(&self) -> Option<Message> {
+        self.primary().try_recv_push().await
+    }
+
+    /// Suppress reconnection attempts on every member (see
+    /// `RpcClient::suppress_reconnect`).
+    pub fn suppress_reconnect(&self, duration: std::time::Duration) {
+        for client in self.clients.iter() {
+            client.suppress_reconnect(duration);
+        }
+    }
+
+    /// True if member 0 is connected. Other members reconnect on their own
+    /// and a down member only fails the requests routed to it.
+    pub async fn is_connected(&self) -> bool {
+        self.primary().is_connected().await
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::rpc_server::{RpcServer, CONN_ID};
+    use omnish_protocol::message::{IoData, IoDirection, Request, RequestScope};
+    use std::collections::HashMap;
+    use std::sync::Mutex;
+
+    type Seen = Arc<Mutex<HashMap<u64, usize>>>;
+
+    /// Serve `sock` and count the messages each connection receives.
+    async fn counting_server(sock: &str) -> (Seen, tokio::task::JoinHandle<()>) {
+        let seen: Seen = Arc::new(Mutex::new(HashMap::new()));
+        let handler_seen = seen.clone();
+        let mut server = RpcServer::bind_unix(sock).await.unwrap();
+        let handle = tokio::spawn(async move {
+            server
+                .serve(
+                    move |_msg, tx| {
+                        let seen = handler_seen.clone();
+                        Box::pin(async move {
+                            let conn_id = CONN_ID.try_with(|id| *id).unwrap();
+                            *seen.lock().unwrap().entry(conn_id).or_default() += 1;
+                            let _ = tx.send(Message::Ack).await;
+                        })
+                    },
+                    None,
+                    None,
+                    None,
+                    None,
+                    None,
+                )
+                .await
+                .ok();
+        });
+        (seen, handle)
+    }
+
+    fn request(i: usize) -> Message {
+        Message::Request(Request {
+            request_id: format!("r{i}"),
+            session_id: "s1".into(),
+            query: "__cmd:context".into(),
+            scope: RequestScope::CurrentSession,
+            model_override: None,
+            timeout_ms: None,
+            format_hint: None,
+        })
+    }
+
+    #[tokio::test]
+    async fn test_pool_distributes_concurrent_requests() {
+        let dir = tempfile::tempdir().unwrap();
+        let sock = dir.path().join("pool.sock");
+        let sock_str = sock.to_str().unwrap().to_string();
+        let (seen, server_handle) = counting_server(&sock_str).await;
+
+        let pool = RpcClientPool::connect_pool(&sock_str, 4).await.unwrap();
+        assert_eq!(pool.size(), 4);
+        assert!(pool.is_connected().await);
+
+        let mut tasks = Vec::new();
+        for i in 0..8 {
+            let pool = pool.clone();
+            tasks.push(tokio::spawn(async move { pool.call(request(i)).await }));
+        }
+        for task in tasks {
+            assert!(matches!(task.await.unwrap().unwrap(), Message::Ack));
+        }
+
+        let seen = seen.lock().unwrap();
+        assert_eq!(seen.len(), 4, "calls used {} connections", seen.len());
+        assert!(seen.values().all(|&n| n == 2), "uneven distribution: {:?}", *seen);
+
+        server_handle.abort();
+    }
+
+    #[tokio::test]
+    async fn test_pool_keeps_session_traffic_on_member_zero() {
+        let dir = tempfile::tempdir().unwrap();
+        let sock = dir.path().join("pool.sock");
+        let sock_str = sock.to_str().unwrap().to_string();
+        let (seen, server_handle) = counting_server(&sock_str).await;
+
+        let pool = RpcClientPool::connect_pool(&sock_str, 3).await.unwrap();
+        for i in 0..6 {
+            let io = Message::IoData(IoData {
+                session_id: "s1".into(),
+                direction: IoDirection::Output,
+                timestamp_ms: i,
+                data: vec![b'x'],
+            });
+            assert!(matches!(pool.call(io).await.unwrap(), Message::Ack));
+        }
+
+        let seen = seen.lock().unwrap();
+        assert_eq!(seen.len(), 1, "IoData spread over {} connections", seen.len());
+        assert_eq!(seen.values().sum::<usize>(), 6);
+
+        server_handle.abort();
+    }
+
+    #[tokio::test]
+    async fn test_pool_rejects_zero_size() {
+        assert!(RpcClientPool::connect_pool("/nonexistent/pool.sock", 0).await.is_err());
+    }
+}
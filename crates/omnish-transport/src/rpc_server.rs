@@ -35,6 +35,26 @@ tokio::task_local! {
 
 static NEXT_CONN_ID: AtomicU64 = AtomicU64::new(1);
 
+/// Permissions applied to the Unix socket after binding: owner-only.
+pub const DEFAULT_SOCKET_MODE: u32 = 0o600;
+
+/// True if the directory holding `socket_path` is world-writable, which
+/// would let other users replace the socket.
+pub fn socket_dir_is_world_writable(socket_path: &str) -> bool {
+    #[cfg(unix)]
+    {
+        use std::os::unix::fs::PermissionsExt;
+        let dir = match std::path::Path::new(socket_path).parent() {
+            Some(d) if !d.as_os_str().is_empty() => d,
+            _ => std::path::Path::new("."),
+        };
+        if let Ok(meta) = std::fs::metadata(dir) {
+            return meta.permissions().mode() & 0o002 != 0;
+        }
+    }
+    false
+}
+
 fn is_fd_exhausted(e: &std::io::Error) -> bool {
     // EMFILE (per-process limit) or ENFILE (system-wide limit)
     matches!(e.raw_os_error(), Some(24) | Some(23))
@@ -113,12 +133,17 @@ pub struct RpcServer {
 
 impl RpcServer {
     pub async fn bind_unix(addr: &str) -> Result<Self> {
+        Self::bind_unix_with_mode(addr, DEFAULT_SOCKET_MODE).await
+    }
+
+    /// Bind a Unix socket and chmod it to `socket_mode` right away.
+    pub async fn bind_unix_with_mode(addr: &str, socket_mode: u32) -> Result<Self> {
         let _ = std::fs::remove_file(addr);
         let listener = TokioUnixListener::bind(addr)?;
         #[cfg(unix)]
         {
             use std::os::unix::fs::PermissionsExt;
-            std::fs::set_permissions(addr, std::fs::Permissions::from_mode(0o600))?;
+            std::fs::set_permissions(addr, std::fs::Permissions::from_mode(socket_mode))?;
         }
         Ok(Self {
             listener: Listener::Unix(listener),
@@ -133,9 +158,14 @@ impl RpcServer {
     }
 
     pub async fn bind(addr: &str) -> Result<Self> {
+        Self::bind_with_mode(addr, DEFAULT_SOCKET_MODE).await
+    }
+
+    /// Like `bind`, with `socket_mode` applied when `addr` is a Unix socket.
+    pub async fn bind_with_mode(addr: &str, socket_mode: u32) -> Result<Self> {
         match parse_addr(addr) {
-            TransportAddr::Unix(p) => Self::bind_unix(&p).await,
-            TransportAddr::Tcp(hp) => Self::bind_tcp(&hp).await,
+            TransportAddr::Unix(p) => Self::bind_unix_with_mode(&p, socket_mode).await,
+            TransportAddr::Tcp(hp) | TransportAddr::TcpTls(hp) => Self::bind_tcp(&hp).await,
         }
     }
 
@@ -290,7 +320,14 @@ fn spawn_connection<R, W, F>(
 
             let frame = match auth_result {
                 Ok(Ok(frame)) => frame,
-                Ok(Err(_)) => return,
+                Ok(Err(e)) => {
+                    // IO errors are plain disconnects; anything else is a
+                    // frame we could not decode.
+                    if e.downcast_ref::<std::io::Error>().is_none() {
+                        tracing::warn!("connection rejected before auth: {}", e);
+                    }
+                    return;
+                }
                 Err(_) => {
                     tracing::warn!("auth timeout: client did not send auth within 5 seconds");
                     return;
@@ -299,15 +336,12 @@ fn spawn_connection<R, W, F>(
 
             match frame.payload {
                 Message::Auth(Auth { ref token, protocol_version }) if token == expected_token.as_str() => {
-                    let ok = omnish_protocol::message::versions_compatible(
-                        omnish_protocol::message::MIN_COMPATIBLE_VERSION,
-                        protocol_version,
-                    );
-                    if !ok {
+                    let check = omnish_protocol::message::check_protocol_version(protocol_version);
+                    let ok = check.is_ok();
+                    if let Err(e) = check {
                         tracing::warn!(
-                            "protocol incompatible (client={}, server={}, server_min={})",
-                            protocol_version,
-                            omnish_protocol::message::PROTOCOL_VERSION,
+                            "rejecting client: {} (server_min={})",
+                            e,
                             omnish_protocol::message::MIN_COMPATIBLE_VERSION,
                         );
                     } else if protocol_version != omnish_protocol::message::PROTOCOL_VERSION {
@@ -398,6 +432,15 @@ fn spawn_connection<R, W, F>(
                         continue;
                     }
 
+                    // Keepalive probes are answered here, never reach the handler
+                    if let Message::Ping { timestamp_ms } = frame.payload {
+                        if let Err(e) = write_reply(&writer, frame.request_id, Message::Pong { timestamp_ms }).await {
+                            tracing::warn!("conn#{}: pong write failed: {}", conn_id, e);
+                            break;
+                        }
+                        continue;
+                    }
+
                     let handler = handler.clone();
                     let writer = writer.clone();
                     tokio::spawn(async move {
@@ -530,6 +573,9 @@ mod tests {
             session_id: "s1".to_string(),
             query: "what happened?".to_string(),
             scope: RequestScope::CurrentSession,
+            model_override: None,
+            timeout_ms: None,
+            format_hint: None,
         });
         let resp = client.call(req_msg).await.unwrap();
         match resp {
@@ -545,6 +591,144 @@ mod tests {
         server_handle.abort();
     }
 
+    #[tokio::test]
+    async fn test_ping_answered_without_handler() {
+        let dir = tempfile::tempdir().unwrap();
+        let sock_path = dir.path().join("ping.sock");
+        let sock_path_str = sock_path.to_str().unwrap().to_string();
+
+        let server_addr = sock_path_str.clone();
+        let server_handle = tokio::spawn(async move {
+            let mut server = RpcServer::bind_unix(&server_addr).await.unwrap();
+            server
+                .serve(
+                    |msg, _tx| {
+                        Box::pin(async move {
+                            panic!("handler must not see {:?}", msg);
+                        })
+                    },
+                    None,
+                    None,
+                    None,
+                    None,
+                    None,
+                )
+                .await
+                .ok();
+        });
+        tokio::time::sleep(std::time::Duration::from_millis(50)).await;
+
+        let client = RpcClient::connect_unix(&sock_path_str).await.unwrap();
+        let resp = client.call(Message::Ping { timestamp_ms: 42 }).await.unwrap();
+        assert!(matches!(resp, Message::Pong { timestamp_ms: 42 }));
+
+        server_handle.abort();
+    }
+
+    #[tokio::test]
+    async fn test_old_client_gets_auth_result() {
+        let dir = tempfile::tempdir().unwrap();
+        let sock = dir.path().join("old.sock");
+        let sock_str = sock.to_str().unwrap().to_string();
+
+        let server_addr = sock_str.clone();
+        let server_handle = tokio::spawn(async move {
+            let mut server = RpcServer::bind_unix(&server_addr).await.unwrap();
+            server
+                .serve(
+                    |_msg, tx| Box::pin(async move { let _ = tx.send(Message::Ack).await; }),
+                    Some("token".to_string()),
+                    None,
+                    None,
+                    None,
+                    None,
+                )
+                .await
+                .ok();
+        });
+
+        tokio::time::sleep(std::time::Duration::from_millis(50)).await;
+
+        // An outdated client must still get an answer, so it can show the
+        // protocol mismatch notice and update.
+        let client = RpcClient::connect_unix(&sock_str).await.unwrap();
+        let resp = client
+            .call(Message::Auth(Auth {
+                token: "token".into(),
+                protocol_version: omnish_protocol::message::MIN_COMPATIBLE_VERSION - 1,
+            }))
+            .await;
+        match resp {
+            Ok(Message::AuthResult(ref r)) => assert!(!r.ok),
+            other => panic!("expected AuthResult, got {:?}", other),
+        }
+
+        // A v0 client (from before versioned Auth) is rejected the same way,
+        // and the daemon's answer tells it which version to upgrade to.
+        let client = RpcClient::connect_unix(&sock_str).await.unwrap();
+        let resp = client
+            .call(Message::Auth(Auth { token: "token".into(), protocol_version: 0 }))
+            .await;
+        match resp {
+            Ok(Message::AuthResult(ref r)) => {
+                assert!(!r.ok);
+                let msg = omnish_protocol::message::version_mismatch_message(r.protocol_version, 0);
+                assert!(
+                    msg.starts_with(&format!(
+                        "protocol version mismatch: expected {}, got 0",
+                        omnish_protocol::message::PROTOCOL_VERSION
+                    )),
+                    "{msg}"
+                );
+            }
+            other => panic!("expected AuthResult, got {:?}", other),
+        }
+
+        // A client at the current version is accepted
+        let client = RpcClient::connect_unix(&sock_str).await.unwrap();
+        let resp = client
+            .call(Message::Auth(Auth {
+                token: "token".into(),
+                protocol_version: omnish_protocol::message::PROTOCOL_VERSION,
+            }))
+            .await;
+        match resp {
+            Ok(Message::AuthResult(ref r)) => assert!(r.ok),
+            other => panic!("expected AuthResult, got {:?}", other),
+        }
+
+        server_handle.abort();
+    }
+
+    #[tokio::test]
+    async fn test_bind_unix_sets_socket_mode() {
+        use std::os::unix::fs::PermissionsExt;
+        let dir = tempfile::tempdir().unwrap();
+        let sock = dir.path().join("mode.sock");
+        let sock_str = sock.to_str().unwrap();
+
+        let _server = RpcServer::bind(sock_str).await.unwrap();
+        let mode = std::fs::metadata(&sock).unwrap().permissions().mode();
+        assert_eq!(mode & 0o777, 0o600);
+
+        let _server = RpcServer::bind_with_mode(sock_str, 0o660).await.unwrap();
+        let mode = std::fs::metadata(&sock).unwrap().permissions().mode();
+        assert_eq!(mode & 0o777, 0o660);
+    }
+
+    #[test]
+    fn test_socket_dir_is_world_writable() {
+        use std::os::unix::fs::PermissionsExt;
+        let dir = tempfile::tempdir().unwrap();
+        let sock = dir.path().join("omnish.sock");
+        let sock_str = sock.to_str().unwrap();
+
+        std::fs::set_permissions(dir.path(), std::fs::Permissions::from_mode(0o700)).unwrap();
+        assert!(!socket_dir_is_world_writable(sock_str));
+        std::fs::set_permissions(dir.path(), std::fs::Permissions::from_mode(0o777)).unwrap();
+        assert!(socket_dir_is_world_writable(sock_str));
+    }
+
     #[tokio::test]
     async fn test_multiple_clients_concurrent() {
         let dir = tempfile::tempdir().unwrap();
@@ -597,12 +781,18 @@ mod tests {
                 session_id: "sa".to_string(),
                 query: "from A".to_string(),
                 scope: RequestScope::CurrentSession,
+                model_override: None,
+                timeout_ms: None,
+                format_hint: None,
             })),
             client_b.call(Message::Request(Request {
                 request_id: "b1".to_string(),
                 session_id: "sb".to_string(),
                 query: "from B".to_string(),
                 scope: RequestScope::CurrentSession,
+                model_override: None,
+                timeout_ms: None,
+                format_hint: None,
             })),
         );
 
@@ -673,6 +863,9 @@ mod tests {
                 session_id: "s1".to_string(),
                 query: "hello tcp".to_string(),
                 scope: RequestScope::CurrentSession,
+                model_override: None,
+                timeout_ms: None,
+                format_hint: None,
             }))
             .await
             .unwrap();
@@ -730,12 +923,18 @@ mod tests {
                 session_id: "sa".to_string(),
                 query: "tcp from A".to_string(),
                 scope: RequestScope::CurrentSession,
+                model_override: None,
+                timeout_ms: None,
+                format_hint: None,
             })),
             client_b.call(Message::Request(Request {
                 request_id: "b1".to_string(),
                 session_id: "sb".to_string(),
                 query: "tcp from B".to_string(),
                 scope: RequestScope::CurrentSession,
+                model_override: None,
+                timeout_ms: None,
+                format_hint: None,
             })),
         );
 
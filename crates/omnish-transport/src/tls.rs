@@ -24,7 +24,7 @@ pub fn load_or_create_cert(
     // Generate self-signed cert
     std::fs::create_dir_all(tls_dir)?;
 
-    let cert = rcgen::generate_simple_self_signed(vec!["localhost".to_string()])?;
+    let cert = rcgen::generate_simple_self_signed(self_signed_names())?;
     let cert_pem = cert.cert.pem();
     let key_pem = cert.key_pair.serialize_pem();
 
@@ -41,6 +41,45 @@ pub fn load_or_create_cert(
     load_cert_and_key(&cert_path, &key_path)
 }
 
+/// Names the generated certificate is valid for: loopback plus this
+/// machine's hostname, so remote clients can verify it by that name.
+fn self_signed_names() -> Vec<String> {
+    let mut names = vec!["localhost".to_string(), "127.0.0.1".to_string(), "::1".to_string()];
+    #[cfg(unix)]
+    if let Ok(host) = nix::unistd::gethostname() {
+        if let Some(host) = host.to_str().filter(|h| !h.is_empty() && *h != "localhost") {
+            names.push(host.to_string());
+        }
+    }
+    names
+}
+
+/// Name the daemon's self-signed certificate is always valid for. Clients
+/// that pin that certificate verify it under this name, whatever address
+/// they reach the daemon by.
+pub const PINNED_SERVER_NAME: &str = "localhost";
+
+/// Host part of a `host:port` address (`[::1]:9500` gives `::1`).
+pub fn server_host(addr: &str) -> &str {
+    if let Some(rest) = addr.strip_prefix('[') {
+        if let Some((host, _)) = rest.split_once(']') {
+            return host;
+        }
+    }
+    addr.rsplit_once(':').map_or(addr, |(host, _)| host)
+}
+
+/// TLS server name to verify the daemon at `addr` as. With a CA certificate
+/// it is the address host. Without one the client trusts only the daemon's
+/// own certificate, which is checked as `PINNED_SERVER_NAME` so remote
+/// clients can connect by IP or any hostname.
+pub fn server_name<'a>(addr: &'a str, ca_cert: Option<&Path>) -> &'a str {
+    match ca_cert {
+        Some(_) => server_host(addr),
+        None => PINNED_SERVER_NAME,
+    }
+}
+
 fn load_cert_and_key(
     cert_path: &Path,
     key_path: &Path,
@@ -62,7 +101,13 @@ fn crypto_provider() -> Arc<rustls::crypto::CryptoProvider> {
 
 /// Create a TLS acceptor for the server.
 pub fn make_acceptor(tls_dir: &Path) -> Result<TlsAcceptor> {
-    let (certs, key) = load_or_create_cert(tls_dir)?;
+    load_or_create_cert(tls_dir)?;
+    acceptor_from_files(&tls_dir.join("cert.pem"), &tls_dir.join("key.pem"))
+}
+
+/// Create a TLS acceptor from an existing certificate chain and key.
+pub fn acceptor_from_files(cert_path: &Path, key_path: &Path) -> Result<TlsAcceptor> {
+    let (certs, key) = load_cert_and_key(cert_path, key_path)?;
     let config = rustls::ServerConfig::builder_with_provider(crypto_provider())
         .with_safe_default_protocol_versions()?
         .with_no_client_auth()
@@ -70,6 +115,37 @@ pub fn make_acceptor(tls_dir: &Path) -> Result<TlsAcceptor> {
     Ok(TlsAcceptor::from(Arc::new(config)))
 }
 
+/// Client TLS setup: a connector trusting `ca_cert` (default: the daemon's
+/// self-signed cert) and the name to verify the daemon as.
+#[derive(Clone)]
+pub struct ClientTls {
+    pub connector: TlsConnector,
+    pinned: bool,
+}
+
+impl ClientTls {
+    pub fn new(ca_cert: Option<&Path>) -> Result<Self> {
+        match ca_cert {
+            Some(ca_cert) => Ok(Self { connector: make_connector(ca_cert)?, pinned: false }),
+            None => Self::pinned(&default_tls_dir().join("cert.pem")),
+        }
+    }
+
+    /// Trust only the daemon certificate at `cert_path`.
+    pub fn pinned(cert_path: &Path) -> Result<Self> {
+        Ok(Self { connector: make_connector(cert_path)?, pinned: true })
+    }
+
+    /// See `server_name`.
+    pub fn server_name<'a>(&self, addr: &'a str) -> &'a str {
+        if self.pinned {
+            PINNED_SERVER_NAME
+        } else {
+            server_host(addr)
+        }
+    }
+}
+
 /// Create a TLS connector that trusts the daemon's self-signed cert.
 pub fn make_connector(cert_path: &Path) -> Result<TlsConnector> {
     let cert_pem = std::fs::read(cert_path)?;
@@ -106,6 +182,21 @@ mod tests {
         assert_eq!(certs1[0].as_ref(), certs2[0].as_ref());
     }
 
+    #[test]
+    fn test_server_host() {
+        assert_eq!(server_host("daemon.example.com:9500"), "daemon.example.com");
+        assert_eq!(server_host("10.0.0.5:9500"), "10.0.0.5");
+        assert_eq!(server_host("[::1]:9500"), "::1");
+        assert_eq!(server_host("localhost"), "localhost");
+    }
+
+    #[test]
+    fn test_server_name_pins_without_ca() {
+        assert_eq!(server_name("10.0.0.5:9500", None), PINNED_SERVER_NAME);
+        let ca = Path::new("/etc/omnish/ca.pem");
+        assert_eq!(server_name("daemon.example.com:9500", Some(ca)), "daemon.example.com");
+    }
+
     #[test]
     fn test_make_acceptor() {
         let dir = tempfile::tempdir().unwrap();